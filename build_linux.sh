@@ -85,7 +85,7 @@ echo "  ${HOST_K_CACHE} -> ${CONT_K_CACHE}"
 echo "  ${HOST_WHISPER_MODELS} -> ${CONT_WHISPER_MODELS}"
 
 # Features
-FEATURES_COMMON="whisper-logs"
+FEATURES_COMMON="kokoro,supersonic2,opentts,whisper-logs"
 FEATURES_CPU="${FEATURES_COMMON}"
 FEATURES_OPENBLAS="${FEATURES_COMMON},whisper-openblas"
 FEATURES_VULKAN="${FEATURES_COMMON},whisper-vulkan"
@@ -51,7 +51,7 @@ mkdir -p "${DIST_DIR}" "${PKG_DIR}" "${PROJECT_ROOT}/target-cross" "${ASSETS_DIR
 echo "Version: ${VERSION}"
 echo "macOS: Metal always, MAC_WITH_OPENBLAS=${MAC_WITH_OPENBLAS}"
 
-FEATURES_COMMON="whisper-logs"
+FEATURES_COMMON="kokoro,supersonic2,opentts,whisper-logs"
 FEATURES_MACOS_METAL="${FEATURES_COMMON},whisper-metal"
 FEATURES_MACOS_METAL_OPENBLAS="${FEATURES_COMMON},whisper-metal,whisper-openblas"
 
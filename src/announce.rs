@@ -0,0 +1,138 @@
+// ------------------------------------------------------------------
+//  Announcements (vtmate ctl say, POST /say)
+// ------------------------------------------------------------------
+//
+// Texts spoken as is, from scripts, timers and notifications, queued by
+// priority: an important one goes first and can't be interrupted by voice,
+// one that resumes is said again from the start once the user is done
+// talking over it, a normal one is lost when interrupted (as replies are).
+// Any noise used to cancel a timer for good.
+// The conversation thread speaks them between its turns.
+
+use crate::conversation::Command;
+use crate::state::GLOBAL_STATE;
+use crossbeam_channel::Sender;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// quiet this long (nobody talking, no turn in progress) before an
+// interrupted announcement is said again
+const RESUME_AFTER: Duration = Duration::from_millis(1500);
+const RESUME_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static QUEUE: Mutex<Vec<Announcement>> = Mutex::new(Vec::new());
+static TX_CMD: OnceLock<Sender<Command>> = OnceLock::new();
+// an important announcement is being spoken
+static PROTECTED: AtomicBool = AtomicBool::new(false);
+// a thread waits to resume the interrupted announcements
+static RESUMING: AtomicBool = AtomicBool::new(false);
+
+// API
+// ------------------------------------------------------------------
+
+/// What happens to an announcement talked over, in increasing priority.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+  /// Interrupted for good, like a reply.
+  Normal,
+  /// Said again from the start once the user is done.
+  Resume,
+  /// Not interrupted by voice, only by a key or `vtmate ctl interrupt`.
+  Important,
+}
+
+impl Priority {
+  /// The priority called `name`: normal, resume or important.
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "normal" => Some(Priority::Normal),
+      "resume" => Some(Priority::Resume),
+      "important" => Some(Priority::Important),
+      _ => None,
+    }
+  }
+}
+
+pub struct Announcement {
+  pub text: String,
+  pub priority: Priority,
+}
+
+/// Where the announcements are sent to be spoken.
+pub fn init(tx_cmd: Sender<Command>) {
+  TX_CMD.set(tx_cmd).ok();
+}
+
+/// Queues `text`, to be spoken after the turn in progress (if any). Fails
+/// when the conversation is not running.
+pub fn push(text: String, priority: Priority) -> Result<(), String> {
+  let tx_cmd = TX_CMD.get().ok_or("conversation is not running")?;
+  QUEUE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .push(Announcement { text, priority });
+  tx_cmd
+    .send(Command::Announce)
+    .map_err(|_| "conversation is not running".to_string())
+}
+
+/// The next announcement to speak: the highest priority, the oldest first.
+pub fn pop() -> Option<Announcement> {
+  let mut queue = QUEUE.lock().unwrap_or_else(PoisonError::into_inner);
+  let max = queue.iter().map(|a| a.priority).max()?;
+  let i = queue.iter().position(|a| a.priority == max)?;
+  Some(queue.remove(i))
+}
+
+/// Marks whether an important announcement is being spoken.
+pub fn set_protected(protected: bool) {
+  PROTECTED.store(protected, Ordering::Relaxed);
+}
+
+/// Whether voice should leave the playback alone.
+pub fn is_protected() -> bool {
+  PROTECTED.load(Ordering::Relaxed)
+}
+
+/// Handles `announcement` cut short: one that resumes is queued again, and
+/// the queue is spoken again once the user is done.
+pub fn interrupted(announcement: Announcement) {
+  let mut queue = QUEUE.lock().unwrap_or_else(PoisonError::into_inner);
+  if announcement.priority == Priority::Resume {
+    crate::log::log("info", "Announcement interrupted, said again later");
+    // ahead of the ones of its priority, it came first
+    queue.insert(0, announcement);
+  } else {
+    crate::log::log("debug", "Announcement interrupted");
+  }
+  if queue.is_empty() || RESUMING.swap(true, Ordering::Relaxed) {
+    return;
+  }
+  std::thread::spawn(|| {
+    let mut quiet_since = Instant::now();
+    loop {
+      std::thread::sleep(RESUME_POLL_INTERVAL);
+      if is_busy() {
+        quiet_since = Instant::now();
+      } else if quiet_since.elapsed() >= RESUME_AFTER {
+        break;
+      }
+    }
+    RESUMING.store(false, Ordering::Relaxed);
+    if let Some(tx_cmd) = TX_CMD.get() {
+      let _ = tx_cmd.send(Command::Announce);
+    }
+  });
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// someone talking, or a turn in progress
+fn is_busy() -> bool {
+  GLOBAL_STATE
+    .get()
+    .is_some_and(|state| state.turn.get().is_busy())
+}
@@ -2,34 +2,130 @@
 //  Router
 // ------------------------------------------------------------------
 
-use crate::util::get_user_home_path;
+use crate::config::{AgentSettings, Args, AssetsAction, LangAction};
+use crate::tts::voices::KOKORO_VOICES_PER_LANGUAGE;
+use crate::util::{get_data_dir, get_user_home_path};
 use flate2::read::GzDecoder;
-use std::{fs, io::Cursor};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+#[cfg(feature = "kokoro")]
+use std::io::Cursor;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
 use tar::Archive;
 
+// languages kept in a pruned kokoro voices pack, and the pack's sha256
+const KOKORO_VOICES_LANGS: &str = "kokoro/0.bin.langs";
+// espeak-ng data, relative to the data dir: the shared phoneme tables plus a
+// <lang>_dict dictionary per language
+const ESPEAK_DIR: &str = "espeak-ng-data";
+const ESPEAK_DICT_SUFFIX: &str = "_dict";
+
+// machines with less run the tiny whisper model
+const SMALL_WHISPER_MEMORY: u64 = 4 << 30;
+const SMALL_WHISPER_CORES: usize = 4;
+
+static DOWNLOAD_OPTIONS: OnceLock<DownloadOptions> = OnceLock::new();
+
 // API
 // ------------------------------------------------------------------
 
+/// A model file (or archive) downloaded at runtime.
+pub struct Asset {
+  pub name: &'static str,
+  pub group: &'static str,
+  // relative to the data dir
+  pub path: &'static str,
+  // pre data dir location, relative to the user home
+  pub legacy_path: &'static str,
+  pub url: &'static str,
+  // third-party copies, tried in order when `url` fails and only with
+  // --third-party-mirrors
+  pub mirrors: &'static [&'static str],
+  pub sha256: &'static str,
+  // archives only: extracted files (relative to `path`) and their sha256
+  pub members: &'static [(&'static str, &'static str)],
+}
+
+pub const ASSETS: &[Asset] = &[
+  Asset {
+    name: "whisper-tiny",
+    group: "whisper",
+    path: "whisper/ggml-tiny.bin",
+    legacy_path: ".whisper-models/ggml-tiny.bin",
+    url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny.bin",
+    mirrors: &["https://hf-mirror.com/ggerganov/whisper.cpp/resolve/main/ggml-tiny.bin"],
+    sha256: "be07e048e1e599ad46341c8d2a135645097a538221678b7acdd1b1919c6e1b21",
+    members: &[],
+  },
+  Asset {
+    name: "whisper-small",
+    group: "whisper",
+    path: "whisper/ggml-small.bin",
+    legacy_path: ".whisper-models/ggml-small.bin",
+    url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-small.bin",
+    mirrors: &["https://hf-mirror.com/ggerganov/whisper.cpp/resolve/main/ggml-small.bin"],
+    sha256: "1be3a9b2063867b937e64e2ec7483364a79917e157fa98c5d94b5c1fffea987b",
+    members: &[],
+  },
+  Asset {
+    name: "kokoro-model",
+    group: "kokoro",
+    path: "kokoro/0.onnx",
+    legacy_path: ".cache/k/0.onnx",
+    url: "https://github.com/DavidValin/kokoro-micro/raw/main/models/0.onnx",
+    mirrors: &["https://github.com/8b-is/kokoro-tiny/raw/main/models/0.onnx"],
+    sha256: "7d5df8ecf7d4b1878015a32686053fd0eebe2bc377234608764cc0ef3636a6c5",
+    members: &[],
+  },
+  Asset {
+    name: "kokoro-voices",
+    group: "kokoro",
+    path: "kokoro/0.bin",
+    legacy_path: ".cache/k/0.bin",
+    url: "https://github.com/DavidValin/kokoro-micro/raw/main/models/0.bin",
+    mirrors: &["https://github.com/8b-is/kokoro-tiny/raw/main/models/0.bin"],
+    sha256: "bca610b8308e8d99f32e6fe4197e7ec01679264efed0cac9140fe9c29f1fbf7d",
+    members: &[],
+  },
+  Asset {
+    name: "supersonic2",
+    group: "supersonic2",
+    path: "tts/supersonic2-model",
+    legacy_path: ".vtmate/tts/supersonic2-model",
+    url: "https://github.com/DavidValin/supersonic2-tts/releases/download/1.0.1/supersonic2-model.tgz",
+    mirrors: &[],
+    sha256: "db410b2b6e35057e15ed3cbd1432e9a5159746dfa79c9654ac04be6c9a8c312a",
+    members: SUPERSONIC2_FILES,
+  },
+];
+
+#[cfg(feature = "kokoro")]
 pub fn ensure_piper_espeak_env() {
   // Respect user override
   if std::env::var_os("PIPER_ESPEAKNG_DATA_DIRECTORY").is_some() {
     return;
   }
-  let home = match get_user_home_path() {
-    Some(h) => h,
+  let base = match get_data_dir() {
+    Some(d) => d,
     None => return,
   };
-  let base = home.join(".vtmate");
-  let espeak_dir = base.join("espeak-ng-data");
+  let espeak_dir = base.join(ESPEAK_DIR);
   let marker = base.join(".espeak_extracted");
   if !(marker.exists() && espeak_dir.is_dir()) {
-    let _ = fs::remove_dir_all(&base);
-    if fs::create_dir_all(&base).is_ok() {
-      let gz = GzDecoder::new(Cursor::new(embedded_espeak_archive()));
-      let mut ar = Archive::new(gz);
-      if ar.unpack(&base).is_ok() {
-        let _ = fs::write(&marker, b"ok");
-      }
+    let _ = fs::remove_dir_all(&espeak_dir);
+    // the dictionaries of the kokoro languages only, the others are added
+    // with `vtmate assets lang add`
+    let langs: Vec<String> = KOKORO_VOICES_PER_LANGUAGE
+      .iter()
+      .map(|(lang, _)| espeak_lang(lang))
+      .collect();
+    if fs::create_dir_all(&base).is_ok()
+      && extract_espeak(&base, |lang| lang.is_none_or(|l| langs.contains(&l))).is_ok()
+    {
+      let _ = fs::write(&marker, b"ok");
     }
   }
   unsafe {
@@ -42,267 +138,1037 @@ pub fn ensure_assets_env() {
   if std::env::var_os("KOKORO_TTS_DATA_DIRECTORY").is_some() {
     return;
   }
-  let home = match get_user_home_path() {
-    Some(h) => h,
+  let kokoro_assets_dir = match get_data_dir() {
+    Some(d) => d.join("kokoro"),
     None => return,
   };
-  let kokoro_assets_dir = home.join(".cache/k");
-  let whisper_dir = home.join(".whisper-models");
-
-  // Check if the expected files already exist
-  let bin_path = kokoro_assets_dir.join("0.bin");
-  let onnx_path = kokoro_assets_dir.join("0.onnx");
-  let whisper_small_path = whisper_dir.join("ggml-small.bin");
-  let whisper_tiny_path = whisper_dir.join("ggml-tiny.bin");
-
-  let all_exist = bin_path.exists()
-    && onnx_path.exists()
-    && whisper_small_path.exists()
-    && whisper_tiny_path.exists();
-
-  // When the assets are not present at location, extract them from the binary itself
-  // (they are bundled in the binary file. See: embedded_* functions in this file)
-  if !all_exist {
-    // extract models to disk
-    let _ = fs::remove_dir_all(&kokoro_assets_dir);
-    let _ = fs::remove_dir_all(&whisper_dir);
-    if fs::create_dir_all(&kokoro_assets_dir).is_ok() && fs::create_dir_all(&whisper_dir).is_ok() {
-      let _ = fs::write(bin_path, embedded_kokoro_0_bin());
-      let _ = fs::write(onnx_path, embedded_kokoro_0_onnx());
-      let _ = fs::write(whisper_small_path, embedded_whisper_small());
-      let _ = fs::write(whisper_tiny_path, embedded_whisper_tiny());
-      // extract supersonic2 files
-      let sup_dir = home.join(".vtmate").join("tts");
-      if fs::create_dir_all(&sup_dir).is_ok() {
-        for rel in SUPERSONIC2_FILES {
-          let path = sup_dir.join(rel);
-          let _ = fs::write(path, embedded_supersonic2_file(rel));
+  unsafe {
+    std::env::set_var("KOKORO_TTS_DATA_DIRECTORY", kokoro_assets_dir.as_os_str());
+  }
+}
+
+pub fn ensure_supersonic2_assets() {
+  // Respect user override
+  if std::env::var_os("SUPERSONIC2_DATA_DIRECTORY").is_some() {
+    return;
+  }
+  let sup_dir = match get_data_dir() {
+    Some(d) => d.join("tts").join("supersonic2-model"),
+    None => return,
+  };
+  unsafe {
+    std::env::set_var("SUPERSONIC2_DATA_DIRECTORY", sup_dir.as_os_str());
+  }
+}
+
+/// Sets the extra mirror base URLs (tried first, as `<mirror>/<file name>`),
+/// whether the built-in third-party mirrors are tried after the upstream url
+/// and whether downloads are disabled.
+pub fn set_download_options(mirrors: Vec<String>, third_party: bool, offline: bool) {
+  let _ = DOWNLOAD_OPTIONS.set(DownloadOptions {
+    mirrors,
+    third_party,
+    offline,
+  });
+}
+
+/// Moves models found in their old locations (~/.whisper-models, ~/.cache/k,
+/// ~/.vtmate/tts) into the data dir, so upgrading doesn't download them again.
+pub fn migrate_legacy_assets() {
+  let (data, home) = match (get_data_dir(), get_user_home_path()) {
+    (Some(d), Some(h)) => (d, h),
+    _ => return,
+  };
+  for asset in ASSETS {
+    let target = data.join(asset.path);
+    let legacy = home.join(asset.legacy_path);
+    if target == legacy || target.exists() || !legacy.exists() {
+      continue;
+    }
+    if let Some(parent) = target.parent() {
+      let _ = fs::create_dir_all(parent);
+    }
+    // a rename across filesystems fails, the asset is downloaded again in that case
+    let _ = fs::rename(&legacy, &target);
+  }
+}
+
+/// Downloads the model files the given agents need and are not on disk yet.
+/// The kokoro voices pack only keeps the voices of the languages in use.
+pub fn ensure_agent_assets(agents: &[AgentSettings], with_stt: bool) -> Result<(), String> {
+  let mut needed = assets_for_agents(agents, with_stt)?;
+  let mut keep_langs = kokoro_languages(agents);
+  // a pruned pack missing a language needs the full pack again
+  let mut refetch_voices = false;
+  if needed.iter().any(|a| a.name == "kokoro-voices")
+    && let Some((langs, _)) = pruned_kokoro_voices()
+    && keep_langs.iter().any(|l| !langs.contains(l))
+  {
+    refetch_voices = true;
+    keep_langs.extend(langs);
+    keep_langs.sort();
+    keep_langs.dedup();
+  }
+  needed.retain(|a| !is_installed(a) || (refetch_voices && a.name == "kokoro-voices"));
+  if is_offline() && !needed.is_empty() {
+    return Err(offline_message(&needed));
+  }
+  for asset in needed {
+    pull(asset)?;
+    if asset.name == "kokoro-voices" && !keep_langs.is_empty() {
+      prune_kokoro_voices(&keep_langs)?;
+    }
+  }
+  // espeak languages removed by hand that an agent speaks again
+  #[cfg(feature = "kokoro")]
+  if std::env::var_os("PIPER_ESPEAKNG_DATA_DIRECTORY").as_deref()
+    == get_data_dir().as_deref().map(Path::as_os_str)
+  {
+    let installed = installed_languages()?;
+    for lang in kokoro_languages(agents) {
+      let lang = espeak_lang(&lang);
+      if !installed.iter().any(|(l, _)| *l == lang) {
+        add_language(&lang, None)?;
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Rewrites the kokoro voices pack at `pack` (a npz archive, one entry per
+/// voice) keeping only the voices of `langs`. Returns the number of voices
+/// kept.
+pub fn prune_voices_pack(pack: &Path, langs: &[String]) -> Result<usize, String> {
+  let tmp = pack.with_extension("bin.tmp");
+  let src = File::open(pack).map_err(|e| format!("unable to open {}: {}", pack.display(), e))?;
+  let mut archive = zip::ZipArchive::new(src).map_err(|e| format!("invalid voices pack: {}", e))?;
+  let out = File::create(&tmp).map_err(|e| format!("unable to write {}: {}", tmp.display(), e))?;
+  let mut writer = zip::ZipWriter::new(out);
+  let mut kept = 0;
+  for i in 0..archive.len() {
+    let entry = archive
+      .by_index_raw(i)
+      .map_err(|e| format!("invalid voices pack: {}", e))?;
+    let voice = entry.name().trim_end_matches(".npy").to_string();
+    let keep = KOKORO_VOICES_PER_LANGUAGE
+      .iter()
+      .any(|(lang, voices)| langs.iter().any(|l| l == lang) && voices.contains(&voice.as_str()));
+    if keep {
+      writer
+        .raw_copy_file(entry)
+        .map_err(|e| format!("unable to write {}: {}", tmp.display(), e))?;
+      kept += 1;
+    }
+  }
+  writer
+    .finish()
+    .map_err(|e| format!("unable to write {}: {}", tmp.display(), e))?;
+  fs::rename(&tmp, pack).map_err(|e| format!("unable to write {}: {}", pack.display(), e))?;
+  Ok(kept)
+}
+
+/// Whether the tts models `agent` needs are on disk.
+pub fn is_ready(agent: &AgentSettings) -> bool {
+  assets_for_agents(std::slice::from_ref(agent), false)
+    .is_ok_and(|needed| needed.iter().all(|a| is_installed(a)))
+}
+
+/// The whisper model to use when the configured `model_path` is missing:
+/// the one of the assets table fitting this machine, downloaded after asking
+/// (or right away with `auto_download`). Returns the path of the model to
+/// use, `model_path` itself when it's there.
+pub fn ensure_whisper_model(
+  model_path: &str,
+  auto_download: bool,
+  interactive: bool,
+) -> Result<String, String> {
+  if Path::new(model_path).is_file() {
+    return Ok(model_path.to_string());
+  }
+  let asset = recommended_whisper();
+  let target = data_dir()?.join(asset.path).to_string_lossy().into_owned();
+  if !is_installed(asset) {
+    let question = format!(
+      "Whisper model not found: {}\r\nDownload {} instead, fitting this machine? [Y/n] ",
+      model_path, asset.name
+    );
+    let approved = auto_download || (interactive && confirm(&question));
+    if !approved {
+      return Err(format!(
+        "Whisper model not found: {} (run with --auto-download to use {} instead)",
+        model_path, asset.name
+      ));
+    }
+    pull(asset)?;
+  }
+  crate::log::log(
+    "warning",
+    &format!(
+      "Whisper model {} not found, using {}",
+      model_path, asset.name
+    ),
+  );
+  Ok(target)
+}
+
+/// The whisper model of the assets table fitting the memory and the cores of
+/// this machine: the small one needs about 1 GB and is slow on a few cores.
+pub fn recommended_whisper() -> &'static Asset {
+  let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+  let small = cores >= SMALL_WHISPER_CORES
+    && memory_bytes().is_none_or(|bytes| bytes >= SMALL_WHISPER_MEMORY);
+  let name = if small {
+    "whisper-small"
+  } else {
+    "whisper-tiny"
+  };
+  ASSETS
+    .iter()
+    .find(|a| a.name == name)
+    .expect("whisper assets")
+}
+
+/// Runs `vtmate assets ...` and returns the process exit code.
+pub fn run_assets_command(action: &AssetsAction, args: &Args) -> i32 {
+  let result = match action {
+    AssetsAction::List => list(),
+    AssetsAction::Pull { names } => select(names).and_then(|assets| {
+      for asset in assets {
+        if is_installed(asset) {
+          println!("✅ {} already installed", asset.name);
+          continue;
+        }
+        pull(asset)?;
+        println!("✅ {} installed", asset.name);
+      }
+      Ok(())
+    }),
+    AssetsAction::Verify { names } => select(names).and_then(|assets| {
+      let mut failed = 0;
+      for asset in assets {
+        match verify(asset) {
+          Ok(()) => println!("✅ {} checksum OK", asset.name),
+          Err(e) => {
+            failed += 1;
+            println!("❌ {}: {}", asset.name, e);
+          }
         }
       }
+      if failed > 0 {
+        Err(format!("{} asset(s) failed verification", failed))
+      } else {
+        Ok(())
+      }
+    }),
+    AssetsAction::Rm { names } => select(names).and_then(|assets| {
+      for asset in assets {
+        remove(asset)?;
+        println!("🗑  {} removed", asset.name);
+      }
+      Ok(())
+    }),
+    AssetsAction::Lang { action } => run_lang_command(action),
+    AssetsAction::Prune => crate::config::resolve_settings_path(args)
+      .and_then(|path| {
+        let agents = crate::config::load_settings(&path, args)?;
+        let personas = crate::config::load_personas(&path)?;
+        Ok(crate::config::with_personas(&agents, &personas))
+      })
+      .map_err(|e| format!("Failed to load settings: {}", e))
+      .and_then(|agents| prune(&agents)),
+  };
+  match result {
+    Ok(()) => 0,
+    Err(e) => {
+      println!("❌ {}", e);
+      1
     }
   }
+}
 
-  unsafe {
-    std::env::set_var("KOKORO_TTS_DATA_DIRECTORY", kokoro_assets_dir.as_os_str());
+pub fn is_installed(asset: &Asset) -> bool {
+  let target = match data_dir() {
+    Ok(data) => data.join(asset.path),
+    Err(_) => return false,
+  };
+  if asset.members.is_empty() {
+    target.is_file()
+  } else {
+    asset
+      .members
+      .iter()
+      .all(|(rel, _)| target.join(rel).is_file())
+  }
+}
+
+/// Downloads (resuming a previous partial download if any) and verifies an asset.
+pub fn pull(asset: &Asset) -> Result<(), String> {
+  if is_offline() {
+    return Err(offline_message(&[asset]));
+  }
+  let target = data_dir()?.join(asset.path);
+  if asset.members.is_empty() {
+    fetch(asset, &target)?;
+    if let Err(e) = check_sha256(&target, asset.sha256) {
+      let _ = fs::remove_file(&target);
+      return Err(format!("{}: {}", asset.name, e));
+    }
+    if asset.name == "kokoro-voices" {
+      // the full pack replaced a pruned one
+      let _ = fs::remove_file(data_dir()?.join(KOKORO_VOICES_LANGS));
+    }
+    return Ok(());
+  }
+
+  // archive: download next to the target dir, verify, then extract
+  let tgz = target.with_extension("tgz");
+  fetch(asset, &tgz)?;
+  if let Err(e) = check_sha256(&tgz, asset.sha256) {
+    let _ = fs::remove_file(&tgz);
+    return Err(format!("{}: {}", asset.name, e));
+  }
+  let dest = target
+    .parent()
+    .ok_or_else(|| format!("invalid asset path {}", target.display()))?;
+  let _ = fs::remove_dir_all(&target);
+  let file = File::open(&tgz).map_err(|e| format!("unable to open {}: {}", tgz.display(), e))?;
+  Archive::new(GzDecoder::new(file))
+    .unpack(dest)
+    .map_err(|e| format!("unable to extract {}: {}", tgz.display(), e))?;
+  let _ = fs::remove_file(&tgz);
+  verify(asset)
+}
+
+pub fn verify(asset: &Asset) -> Result<(), String> {
+  let target = data_dir()?.join(asset.path);
+  if !is_installed(asset) {
+    return Err("not installed".to_string());
+  }
+  // a pruned pack has the checksum noted when pruning, a full one the
+  // asset's (the note is stale when pruning was cut short before the rename)
+  if asset.name == "kokoro-voices"
+    && let Some((_, sha256)) = pruned_kokoro_voices()
+    && check_sha256(&target, &sha256).is_ok()
+  {
+    return Ok(());
+  }
+  if asset.members.is_empty() {
+    return check_sha256(&target, asset.sha256);
+  }
+  for (rel, sha256) in asset.members {
+    check_sha256(&target.join(rel), sha256).map_err(|e| format!("{}: {}", rel, e))?;
+  }
+  Ok(())
+}
+
+pub fn remove(asset: &Asset) -> Result<(), String> {
+  let target = data_dir()?.join(asset.path);
+  if asset.name == "kokoro-voices" {
+    let _ = fs::remove_file(data_dir()?.join(KOKORO_VOICES_LANGS));
+  }
+  let res = if asset.members.is_empty() {
+    fs::remove_file(&target)
+  } else {
+    fs::remove_dir_all(&target)
+  };
+  match res {
+    Ok(()) => Ok(()),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+    Err(e) => Err(format!("unable to remove {}: {}", target.display(), e)),
   }
 }
 
 // PRIVATE
 // ------------------------------------------------------------------
 
-// SUPERSONIC2
-// ------------------------------------------------------------------
+fn data_dir() -> Result<PathBuf, String> {
+  get_data_dir().ok_or_else(|| "Unable to determine data directory".to_string())
+}
 
-const SUPERSONIC2_FILES: &[&str] = &[
-  "onnx/vector_estimator.onnx",
-  "onnx/duration_predictor.onnx",
-  "onnx/tts.json",
-  "onnx/text_encoder.onnx",
-  "onnx/vocoder.onnx",
-  "onnx/unicode_indexer.json",
-  "config.json",
-  "voice_styles/F4.json",
-  "voice_styles/F5.json",
-  "voice_styles/M1.json",
-  "voice_styles/F2.json",
-  "voice_styles/F3.json",
-  "voice_styles/M4.json",
-  "voice_styles/M5.json",
-  "voice_styles/F1.json",
-  "voice_styles/M2.json",
-  "voice_styles/M3.json",
-];
+// Assets the agents' tts backends and whisper models rely on, with the tiny
+// whisper model fast mode and low power mode transcribe with.
+fn assets_for_agents(
+  agents: &[AgentSettings],
+  with_stt: bool,
+) -> Result<Vec<&'static Asset>, String> {
+  let data = data_dir()?;
+  let mut needed: Vec<&'static Asset> = Vec::new();
+  for agent in agents {
+    // models in a user provided directory are not managed here
+    let group = match agent.tts.as_str() {
+      // backends left out of this build have nothing to download
+      "kokoro"
+        if cfg!(feature = "kokoro")
+          && !overridden(&data, "KOKORO_TTS_DATA_DIRECTORY", "kokoro") =>
+      {
+        Some("kokoro")
+      }
+      "supersonic2"
+        if cfg!(feature = "supersonic2")
+          && !overridden(&data, "SUPERSONIC2_DATA_DIRECTORY", "tts/supersonic2-model") =>
+      {
+        Some("supersonic2")
+      }
+      _ => None,
+    };
+    if let Some(group) = group {
+      needed.extend(ASSETS.iter().filter(|a| a.group == group));
+    }
+    if with_stt && cfg!(feature = "whisper") {
+      // only the bundled whisper models can be fetched, custom paths are left as is
+      let whisper_path = PathBuf::from(crate::config::resolved_whisper_model_path(
+        &agent.whisper_model_path,
+      ));
+      needed.extend(ASSETS.iter().filter(|a| data.join(a.path) == whisper_path));
+    }
+  }
+  // fast mode (f key, --fast-stt) can be turned on any time, and --low-power
+  // falls back to the tiny model when idle
+  if with_stt && cfg!(feature = "whisper") {
+    let tiny_path = PathBuf::from(crate::config::resolved_whisper_model_path(""));
+    needed.extend(ASSETS.iter().filter(|a| data.join(a.path) == tiny_path));
+  }
+  needed.sort_by_key(|a| a.name);
+  needed.dedup_by_key(|a| a.name);
+  Ok(needed)
+}
 
-pub fn ensure_supersonic2_assets() {
-  // Respect user override
-  if std::env::var_os("SUPERSONIC2_DATA_DIRECTORY").is_some() {
-    return;
+fn kokoro_languages(agents: &[AgentSettings]) -> Vec<String> {
+  let mut langs: Vec<String> = agents
+    .iter()
+    .filter(|a| a.tts == "kokoro")
+    .map(|a| a.language.clone())
+    .collect();
+  langs.sort();
+  langs.dedup();
+  langs
+}
+
+// Removes the assets no agent uses and strips unused kokoro voices.
+fn prune(agents: &[AgentSettings]) -> Result<(), String> {
+  let needed = assets_for_agents(agents, true)?;
+  for asset in ASSETS {
+    if is_installed(asset) && !needed.iter().any(|a| a.name == asset.name) {
+      remove(asset)?;
+      println!("🗑  {} removed (not used by any agent)", asset.name);
+    }
   }
-  let home = match get_user_home_path() {
-    Some(h) => h,
-    None => return,
-  };
-  let base = home.join(".vtmate");
-  let sup_dir = base.join("tts/supersonic2-model");
-
-  let mut all_exist = true;
-  for rel in SUPERSONIC2_FILES {
-    let path = sup_dir.join(rel);
-    if !path.exists() {
-      all_exist = false;
-      break;
+  let langs = kokoro_languages(agents);
+  if needed.iter().any(|a| a.name == "kokoro-voices")
+    && ASSETS
+      .iter()
+      .any(|a| a.name == "kokoro-voices" && is_installed(a))
+  {
+    let kept = prune_kokoro_voices(&langs)?;
+    println!(
+      "✂️  kokoro-voices: kept {} voices for {}",
+      kept,
+      langs.join(", ")
+    );
+  }
+  // espeak only serves kokoro
+  let used: Vec<String> = langs.iter().map(|l| espeak_lang(l)).collect();
+  for (lang, _) in installed_languages()? {
+    if !used.contains(&lang) {
+      remove_language(&lang)?;
+      println!(
+        "🗑  espeak language {} removed (not used by any agent)",
+        lang
+      );
     }
   }
-  if !all_exist {
-    // Extract supersonic2 files from embedded binary
-    let _ = fs::remove_dir_all(&sup_dir);
-    if fs::create_dir_all(&sup_dir).is_ok() {
-      for rel in SUPERSONIC2_FILES {
-        let path = sup_dir.join(rel);
-        if let Some(parent) = path.parent() {
-          let _ = fs::create_dir_all(parent);
-        }
-        let _ = fs::write(path, embedded_supersonic2_file(rel));
+  Ok(())
+}
+
+fn run_lang_command(action: &LangAction) -> Result<(), String> {
+  match action {
+    LangAction::List => {
+      let installed = installed_languages()?;
+      for (lang, size) in &installed {
+        println!(
+          "{:<6} ✅ installed {:>8.2} MB",
+          lang,
+          *size as f64 / 1_048_576.0
+        );
+      }
+      let available: Vec<String> = builtin_languages()
+        .into_iter()
+        .filter(|l| !installed.iter().any(|(i, _)| i == l))
+        .collect();
+      if !available.is_empty() {
+        println!("\nBuilt in, to add: {}", available.join(" "));
       }
+      Ok(())
+    }
+    LangAction::Add { langs, from } => {
+      for lang in langs {
+        let lang = espeak_lang(lang);
+        add_language(&lang, from.as_deref().map(Path::new))?;
+        println!("✅ espeak language {} installed", lang);
+      }
+      Ok(())
+    }
+    LangAction::Rm { langs } => {
+      for lang in langs {
+        let lang = espeak_lang(lang);
+        remove_language(&lang)?;
+        println!("🗑  espeak language {} removed", lang);
+      }
+      Ok(())
     }
   }
+}
 
-  unsafe {
-    std::env::set_var("SUPERSONIC2_DATA_DIRECTORY", sup_dir.as_os_str());
+// The espeak-ng dictionary name of a language code: "en-US" -> "en",
+// "zh" -> "cmn".
+fn espeak_lang(lang: &str) -> String {
+  let lang = lang.split(['-', '_']).next().unwrap_or(lang).to_lowercase();
+  match lang.as_str() {
+    "zh" => "cmn".to_string(),
+    _ => lang,
   }
 }
 
-// Embedded supersonic2 functions
-fn embedded_supersonic2_vector_estimator_onnx() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/onnx/vector_estimator.onnx"
-  ))
+// The language of a dictionary in the espeak-ng data, None for the shared
+// files.
+fn dict_lang(path: &Path) -> Option<String> {
+  let name = path.file_name()?.to_str()?;
+  if path.parent()?.file_name()? != ESPEAK_DIR {
+    return None;
+  }
+  name.strip_suffix(ESPEAK_DICT_SUFFIX).map(|l| l.to_string())
 }
-fn embedded_supersonic2_duration_predictor_onnx() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/onnx/duration_predictor.onnx"
-  ))
+
+// (language, size) of the installed espeak dictionaries.
+fn installed_languages() -> Result<Vec<(String, u64)>, String> {
+  let dir = data_dir()?.join(ESPEAK_DIR);
+  let Ok(entries) = fs::read_dir(&dir) else {
+    return Ok(Vec::new());
+  };
+  let mut langs: Vec<(String, u64)> = entries
+    .flatten()
+    .filter_map(|e| {
+      let lang = dict_lang(&e.path())?;
+      Some((lang, e.metadata().map(|m| m.len()).unwrap_or(0)))
+    })
+    .collect();
+  langs.sort();
+  Ok(langs)
 }
-fn embedded_supersonic2_tts_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/onnx/tts.json"
-  ))
+
+// Installs the dictionary of `lang`: from `from` (a compiled dictionary or a
+// directory holding it), else from the data built in.
+fn add_language(lang: &str, from: Option<&Path>) -> Result<(), String> {
+  let dir = data_dir()?.join(ESPEAK_DIR);
+  if !dir.is_dir() {
+    return Err(format!(
+      "no espeak data in {}, run vtmate with a kokoro agent once",
+      dir.display()
+    ));
+  }
+  let target = dir.join(format!("{}{}", lang, ESPEAK_DICT_SUFFIX));
+  if let Some(from) = from {
+    let src = if from.is_dir() {
+      from.join(format!("{}{}", lang, ESPEAK_DICT_SUFFIX))
+    } else {
+      from.to_path_buf()
+    };
+    fs::copy(&src, &target).map_err(|e| format!("unable to copy {}: {}", src.display(), e))?;
+    return Ok(());
+  }
+  let base = data_dir()?;
+  if extract_espeak(&base, |l| l.as_deref() == Some(lang))? == 0 {
+    return Err(format!(
+      "no espeak data for '{}' built in, add a compiled dictionary with --from",
+      lang
+    ));
+  }
+  Ok(())
 }
-fn embedded_supersonic2_text_encoder_onnx() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/onnx/text_encoder.onnx"
-  ))
+
+fn remove_language(lang: &str) -> Result<(), String> {
+  let target = data_dir()?
+    .join(ESPEAK_DIR)
+    .join(format!("{}{}", lang, ESPEAK_DICT_SUFFIX));
+  fs::remove_file(&target).map_err(|e| match e.kind() {
+    std::io::ErrorKind::NotFound => format!("espeak language {} is not installed", lang),
+    _ => format!("unable to remove {}: {}", target.display(), e),
+  })
 }
-fn embedded_supersonic2_vocoder_onnx() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/onnx/vocoder.onnx"
-  ))
+
+// Extracts the built in espeak data whose language (None for the shared
+// files) `keep` accepts into `base`. Returns the number of dictionaries
+// extracted.
+#[cfg(feature = "kokoro")]
+fn extract_espeak(base: &Path, keep: impl Fn(Option<String>) -> bool) -> Result<usize, String> {
+  let gz = GzDecoder::new(Cursor::new(embedded_espeak_archive()));
+  let mut archive = Archive::new(gz);
+  let mut dicts = 0;
+  let entries = archive
+    .entries()
+    .map_err(|e| format!("invalid espeak data: {}", e))?;
+  for entry in entries {
+    let mut entry = entry.map_err(|e| format!("invalid espeak data: {}", e))?;
+    let path = entry
+      .path()
+      .map_err(|e| format!("invalid espeak data: {}", e))?
+      .to_path_buf();
+    let lang = dict_lang(&path);
+    let is_dict = lang.is_some();
+    if !keep(lang) {
+      continue;
+    }
+    entry
+      .unpack_in(base)
+      .map_err(|e| format!("unable to extract {}: {}", path.display(), e))?;
+    if is_dict {
+      dicts += 1;
+    }
+  }
+  Ok(dicts)
 }
-fn embedded_supersonic2_unicode_indexer_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/onnx/unicode_indexer.json"
-  ))
+
+#[cfg(not(feature = "kokoro"))]
+fn extract_espeak(_base: &Path, _keep: impl Fn(Option<String>) -> bool) -> Result<usize, String> {
+  Err("this build has no kokoro tts, its espeak data isn't built in".to_string())
 }
-fn embedded_supersonic2_config_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/config.json"
-  ))
+
+// The languages of the built in espeak data.
+#[cfg(feature = "kokoro")]
+fn builtin_languages() -> Vec<String> {
+  let gz = GzDecoder::new(Cursor::new(embedded_espeak_archive()));
+  let mut archive = Archive::new(gz);
+  let Ok(entries) = archive.entries() else {
+    return Vec::new();
+  };
+  let mut langs: Vec<String> = entries
+    .flatten()
+    .filter_map(|e| dict_lang(&e.path().ok()?))
+    .collect();
+  langs.sort();
+  langs
 }
-fn embedded_supersonic2_voice_m1_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/voice_styles/M1.json"
-  ))
+
+#[cfg(not(feature = "kokoro"))]
+fn builtin_languages() -> Vec<String> {
+  Vec::new()
 }
-fn embedded_supersonic2_voice_m2_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/voice_styles/M2.json"
-  ))
+
+// (languages, sha256) of the installed kokoro voices pack when it was pruned
+fn pruned_kokoro_voices() -> Option<(Vec<String>, String)> {
+  let content = fs::read_to_string(get_data_dir()?.join(KOKORO_VOICES_LANGS)).ok()?;
+  let mut lines = content.lines();
+  let langs = lines
+    .next()?
+    .split(',')
+    .filter(|l| !l.is_empty())
+    .map(|l| l.to_string())
+    .collect();
+  Some((langs, lines.next()?.trim().to_string()))
 }
-fn embedded_supersonic2_voice_m3_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/voice_styles/M3.json"
-  ))
+
+// Prunes the installed kokoro voices pack to the voices of `langs`, noting
+// the languages and the new checksum. Returns the number of voices kept.
+fn prune_kokoro_voices(langs: &[String]) -> Result<usize, String> {
+  let data = data_dir()?;
+  let path = data.join("kokoro").join("0.bin");
+  let kept = prune_voices_pack(&path, langs)?;
+
+  let mut hasher = Sha256::new();
+  let mut file = File::open(&path).map_err(|e| e.to_string())?;
+  std::io::copy(&mut file, &mut hasher).map_err(|e| e.to_string())?;
+  fs::write(
+    data.join(KOKORO_VOICES_LANGS),
+    format!("{}\n{}\n", langs.join(","), hex::encode(hasher.finalize())),
+  )
+  .map_err(|e| e.to_string())?;
+  Ok(kept)
 }
-fn embedded_supersonic2_voice_m4_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/voice_styles/M4.json"
-  ))
+
+fn overridden(data: &Path, var: &str, default_rel: &str) -> bool {
+  match std::env::var_os(var) {
+    Some(dir) => dir != data.join(default_rel),
+    None => false,
+  }
 }
-fn embedded_supersonic2_voice_m5_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/voice_styles/M5.json"
-  ))
+
+fn list() -> Result<(), String> {
+  let data = data_dir()?;
+  for asset in ASSETS {
+    let target = data.join(asset.path);
+    let size: u64 = if asset.members.is_empty() {
+      fs::metadata(&target).map(|m| m.len()).unwrap_or(0)
+    } else {
+      asset
+        .members
+        .iter()
+        .filter_map(|(rel, _)| fs::metadata(target.join(rel)).ok())
+        .map(|m| m.len())
+        .sum()
+    };
+    let status = if is_installed(asset) {
+      "✅ installed"
+    } else {
+      "⬜ missing  "
+    };
+    println!(
+      "{:<14} {} {:>8.1} MB  {}",
+      asset.name,
+      status,
+      size as f64 / 1_048_576.0,
+      target.display()
+    );
+  }
+  Ok(())
 }
-fn embedded_supersonic2_voice_f1_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/voice_styles/F1.json"
-  ))
+
+// Asks `question` on the terminal (raw mode), true unless answered no.
+fn confirm(question: &str) -> bool {
+  use crossterm::event::{Event, KeyCode, KeyEventKind, read};
+  print!("{}", question);
+  let _ = std::io::stdout().flush();
+  let yes = loop {
+    match read() {
+      Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+        KeyCode::Char('y' | 'Y') | KeyCode::Enter => break true,
+        KeyCode::Char('n' | 'N') | KeyCode::Esc => break false,
+        _ => {}
+      },
+      Ok(_) => {}
+      Err(_) => break false,
+    }
+  };
+  print!("{}\r\n", if yes { "y" } else { "n" });
+  yes
 }
-fn embedded_supersonic2_voice_f2_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/voice_styles/F2.json"
-  ))
+
+// Total memory of the machine, None when unknown.
+#[cfg(target_os = "linux")]
+fn memory_bytes() -> Option<u64> {
+  let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+  let kb = meminfo
+    .lines()
+    .find_map(|line| line.strip_prefix("MemTotal:"))?
+    .trim()
+    .trim_end_matches("kB")
+    .trim()
+    .parse::<u64>()
+    .ok()?;
+  Some(kb * 1024)
 }
-fn embedded_supersonic2_voice_f3_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/voice_styles/F3.json"
-  ))
+
+#[cfg(target_os = "macos")]
+fn memory_bytes() -> Option<u64> {
+  let out = std::process::Command::new("sysctl")
+    .args(["-n", "hw.memsize"])
+    .output()
+    .ok()?;
+  String::from_utf8_lossy(&out.stdout).trim().parse().ok()
 }
-fn embedded_supersonic2_voice_f4_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/voice_styles/F4.json"
-  ))
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn memory_bytes() -> Option<u64> {
+  None
 }
-fn embedded_supersonic2_voice_f5_json() -> &'static [u8] {
-  include_bytes!(concat!(
-    env!("OUT_DIR"),
-    "/embedded/supersonic2-model/voice_styles/F5.json"
+
+// Resolves asset names (or group names); no names means all assets.
+fn select(names: &[String]) -> Result<Vec<&'static Asset>, String> {
+  if names.is_empty() {
+    return Ok(ASSETS.iter().collect());
+  }
+  let mut selected: Vec<&'static Asset> = Vec::new();
+  for name in names {
+    let matches: Vec<&'static Asset> = ASSETS
+      .iter()
+      .filter(|a| a.name == name.as_str() || a.group == name.as_str())
+      .collect();
+    if matches.is_empty() {
+      let valid: Vec<&str> = ASSETS.iter().map(|a| a.name).collect();
+      return Err(format!(
+        "Unknown asset '{}'. Available assets: {}",
+        name,
+        valid.join(", ")
+      ));
+    }
+    for a in matches {
+      if !selected.iter().any(|s| s.name == a.name) {
+        selected.push(a);
+      }
+    }
+  }
+  Ok(selected)
+}
+
+fn check_sha256(path: &Path, expected: &str) -> Result<(), String> {
+  let mut file =
+    File::open(path).map_err(|e| format!("unable to open {}: {}", path.display(), e))?;
+  let mut hasher = Sha256::new();
+  std::io::copy(&mut file, &mut hasher)
+    .map_err(|e| format!("unable to read {}: {}", path.display(), e))?;
+  let hash = hex::encode(hasher.finalize());
+  if hash == expected {
+    Ok(())
+  } else {
+    Err(format!(
+      "checksum mismatch: expected {}, got {}",
+      expected, hash
+    ))
+  }
+}
+
+struct DownloadOptions {
+  mirrors: Vec<String>,
+  third_party: bool,
+  offline: bool,
+}
+
+fn is_offline() -> bool {
+  DOWNLOAD_OPTIONS
+    .get()
+    .is_some_and(|options| options.offline)
+}
+
+// User mirrors first, then the upstream url and, when asked for, the
+// built-in third-party mirrors.
+fn candidate_urls(asset: &Asset) -> Vec<String> {
+  let file_name = asset.url.rsplit('/').next().unwrap_or(asset.name);
+  let options = DOWNLOAD_OPTIONS.get();
+  let mut urls: Vec<String> = options
+    .map(|options| {
+      options
+        .mirrors
+        .iter()
+        .map(|m| format!("{}/{}", m.trim_end_matches('/'), file_name))
+        .collect()
+    })
+    .unwrap_or_default();
+  urls.push(asset.url.to_string());
+  if options.is_some_and(|options| options.third_party) {
+    urls.extend(asset.mirrors.iter().map(|m| m.to_string()));
+  }
+  urls
+}
+
+fn fetch(asset: &Asset, dest: &Path) -> Result<(), String> {
+  let mut errors: Vec<String> = Vec::new();
+  for url in candidate_urls(asset) {
+    match download(&url, dest, asset.name) {
+      Ok(()) => return Ok(()),
+      Err(e) => {
+        print!("\r\x1b[K⚠️  {}\r\n", e);
+        errors.push(e);
+      }
+    }
+  }
+  Err(format!(
+    "{}: no download source worked (use --asset-mirror, --third-party-mirrors or see --offline)\n{}",
+    asset.name,
+    errors.join("\n")
   ))
 }
 
-fn embedded_supersonic2_file(rel: &str) -> &'static [u8] {
-  match rel {
-    "onnx/vector_estimator.onnx" => embedded_supersonic2_vector_estimator_onnx(),
-    "onnx/duration_predictor.onnx" => embedded_supersonic2_duration_predictor_onnx(),
-    "onnx/tts.json" => embedded_supersonic2_tts_json(),
-    "onnx/text_encoder.onnx" => embedded_supersonic2_text_encoder_onnx(),
-    "onnx/vocoder.onnx" => embedded_supersonic2_vocoder_onnx(),
-    "onnx/unicode_indexer.json" => embedded_supersonic2_unicode_indexer_json(),
-    "config.json" => embedded_supersonic2_config_json(),
-    "voice_styles/M1.json" => embedded_supersonic2_voice_m1_json(),
-    "voice_styles/M2.json" => embedded_supersonic2_voice_m2_json(),
-    "voice_styles/M3.json" => embedded_supersonic2_voice_m3_json(),
-    "voice_styles/M4.json" => embedded_supersonic2_voice_m4_json(),
-    "voice_styles/M5.json" => embedded_supersonic2_voice_m5_json(),
-    "voice_styles/F1.json" => embedded_supersonic2_voice_f1_json(),
-    "voice_styles/F2.json" => embedded_supersonic2_voice_f2_json(),
-    "voice_styles/F3.json" => embedded_supersonic2_voice_f3_json(),
-    "voice_styles/F4.json" => embedded_supersonic2_voice_f4_json(),
-    "voice_styles/F5.json" => embedded_supersonic2_voice_f5_json(),
-    _ => panic!("Unknown supersonic2 file {}", rel),
+// Lists where each missing asset must be placed to run without network access.
+fn offline_message(assets: &[&Asset]) -> String {
+  let data = get_data_dir().unwrap_or_default();
+  let mut msg = String::from("Offline mode: missing model files. Place them manually:\n");
+  for asset in assets {
+    let target = data.join(asset.path);
+    if asset.members.is_empty() {
+      msg.push_str(&format!(
+        "  {}\n      file: {}\n      from: {}\n      sha256: {}\n",
+        asset.name,
+        target.display(),
+        asset.url,
+        asset.sha256
+      ));
+    } else {
+      msg.push_str(&format!(
+        "  {}\n      extract into: {} (creates {})\n      from: {}\n      sha256: {}\n",
+        asset.name,
+        target.parent().unwrap_or(&data).display(),
+        target.display(),
+        asset.url,
+        asset.sha256
+      ));
+    }
   }
+  msg
 }
 
+// Downloads `url` into `dest` through a `.part` file, resuming it with an
+// HTTP Range request when a previous download was interrupted.
+fn download(url: &str, dest: &Path, label: &str) -> Result<(), String> {
+  crate::util::runtime().block_on(download_async(url, dest, label))
+}
+
+async fn download_async(url: &str, dest: &Path, label: &str) -> Result<(), String> {
+  if let Some(parent) = dest.parent() {
+    fs::create_dir_all(parent)
+      .map_err(|e| format!("unable to create {}: {}", parent.display(), e))?;
+  }
+  let mut part = dest.as_os_str().to_owned();
+  part.push(".part");
+  let part = PathBuf::from(part);
+  let offset = fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+
+  let client = reqwest::Client::builder()
+    .connect_timeout(Duration::from_secs(15))
+    .build()
+    .map_err(|e| e.to_string())?;
+  let mut req = client.get(url);
+  if offset > 0 {
+    req = req.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+  }
+  let mut resp = req
+    .send()
+    .await
+    .map_err(|e| format!("download of {} failed: {}", url, e))?;
+  let status = resp.status();
+  if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE && offset > 0 {
+    // the partial file is already complete
+    return fs::rename(&part, dest).map_err(|e| e.to_string());
+  }
+  if !status.is_success() {
+    return Err(format!("download of {} failed: HTTP {}", url, status));
+  }
+  let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT;
+  let mut file = if resumed {
+    OpenOptions::new().append(true).open(&part)
+  } else {
+    File::create(&part)
+  }
+  .map_err(|e| format!("unable to write {}: {}", part.display(), e))?;
+
+  let mut done = if resumed { offset } else { 0 };
+  let total = resp.content_length().map(|len| len + done);
+  let mut last_report = 0u64;
+  while let Some(chunk) = resp
+    .chunk()
+    .await
+    .map_err(|e| format!("download of {} interrupted: {}", url, e))?
+  {
+    file
+      .write_all(&chunk)
+      .map_err(|e| format!("unable to write {}: {}", part.display(), e))?;
+    done += chunk.len() as u64;
+    if done - last_report >= 1_048_576 {
+      print_progress(label, done, total);
+      last_report = done;
+    }
+  }
+  print_progress(label, done, total);
+  print!("\r\n");
+  drop(file);
+
+  if let Some(total) = total
+    && done < total
+  {
+    return Err(format!(
+      "download of {} incomplete ({} of {} bytes), run it again to resume",
+      url, done, total
+    ));
+  }
+  fs::rename(&part, dest).map_err(|e| format!("unable to write {}: {}", dest.display(), e))
+}
+
+fn print_progress(label: &str, done: u64, total: Option<u64>) {
+  let mb = |b: u64| b as f64 / 1_048_576.0;
+  match total {
+    Some(total) if total > 0 => print!(
+      "\r\x1b[K⬇️  {} {:.1}/{:.1} MB ({}%)",
+      label,
+      mb(done),
+      mb(total),
+      done * 100 / total
+    ),
+    _ => print!("\r\x1b[K⬇️  {} {:.1} MB", label, mb(done)),
+  }
+  let _ = std::io::stdout().flush();
+}
+
+// SUPERSONIC2
+// ------------------------------------------------------------------
+
+const SUPERSONIC2_FILES: &[(&str, &str)] = &[
+  (
+    "onnx/vector_estimator.onnx",
+    "105e9d66fd8756876b210a6b4aa03fc393b1eaca3a8dadcc8d9a3bc785c86a35",
+  ),
+  (
+    "onnx/duration_predictor.onnx",
+    "6d556b3691165c364be91dc0bd894656b5949f5acd2750d8ec2f954010845011",
+  ),
+  (
+    "onnx/tts.json",
+    "ee531d9af9b80438a2ed703e22155ee6c83b12595ab22fd3bb6de94c7502fe96",
+  ),
+  (
+    "onnx/text_encoder.onnx",
+    "dd5f535ed629f7df86071043e15f541ce1b2ab7f1bdbce4c7892b307bca79fa3",
+  ),
+  (
+    "onnx/vocoder.onnx",
+    "19bd51f47a186069c752403518a40f7ea4c647455056d2511f7249691ecddf7c",
+  ),
+  (
+    "onnx/unicode_indexer.json",
+    "b7662a73a0703f43b97c0f2e089f8e8325e26f5d841aca393b5a54c509c92df1",
+  ),
+  (
+    "config.json",
+    "1caf87d5df2ed84351c04a3b9f1ce2d5656b109cfdfe0c4d1d1ffdccf0ff1a6f",
+  ),
+  (
+    "voice_styles/F1.json",
+    "6106950ebeb8a5da29ea22075f605db659cd07dbc288a68292543d9129aa250f",
+  ),
+  (
+    "voice_styles/F2.json",
+    "8b97feb16d79ac0447136796708feac5f83dbabe92a5be1168212653c38729ae",
+  ),
+  (
+    "voice_styles/F3.json",
+    "7eda5bccb4e6eb7f228fa182462d5fcf982d77628234603599027f0734d70c29",
+  ),
+  (
+    "voice_styles/F4.json",
+    "e056fc2bee393edc8bff761eb28f33fb461e8dad828c3b05348a010ac1b7bb79",
+  ),
+  (
+    "voice_styles/F5.json",
+    "ce7645ad7e3c13cca04e0d62bf890ef9ac401988005ba8f5e9c9b59257bc6931",
+  ),
+  (
+    "voice_styles/M1.json",
+    "a04c823cbda6dd1c7de131ec68fea83bbb70d7f29d61623304eb871e3b83b5a1",
+  ),
+  (
+    "voice_styles/M2.json",
+    "7ddd07bf873a3fd67d09ef4e8293b486beb658158b47e371166198e4c6926072",
+  ),
+  (
+    "voice_styles/M3.json",
+    "e8e77a56459e4dc8cdfeb88e6f778dc9a0adf22e1184414f4b0e82a5d1edbe72",
+  ),
+  (
+    "voice_styles/M4.json",
+    "95322725e4d25d9ed4e7dcccbf0f3726b0e9a2471d876b7942373218dbd30174",
+  ),
+  (
+    "voice_styles/M5.json",
+    "be52f82327da63ff18481ce2dd8060c7df432e0168d748745ef3e21b92d706a5",
+  ),
+];
+
 /// Returns the embedded espeak-ng data archive (tar.gz) as raw bytes.
 ///
 /// The archive file is embedded at compile time.
 /// Make sure this path exists when compiling:
 ///   <crate>/assets/espeak-ng-data.tar.gz
+#[cfg(feature = "kokoro")]
 fn embedded_espeak_archive() -> &'static [u8] {
   include_bytes!(concat!(
     env!("CARGO_MANIFEST_DIR"),
     "/assets/espeak-ng-data.tar.gz"
   ))
 }
-
-fn embedded_kokoro_0_bin() -> &'static [u8] {
-  include_bytes!(concat!(env!("OUT_DIR"), "/embedded/0.bin"))
-}
-
-fn embedded_kokoro_0_onnx() -> &'static [u8] {
-  include_bytes!(concat!(env!("OUT_DIR"), "/embedded/0.onnx"))
-}
-
-fn embedded_whisper_small() -> &'static [u8] {
-  include_bytes!(concat!(env!("OUT_DIR"), "/embedded/ggml-small.bin"))
-}
-
-fn embedded_whisper_tiny() -> &'static [u8] {
-  include_bytes!(concat!(env!("OUT_DIR"), "/embedded/ggml-tiny.bin"))
-}
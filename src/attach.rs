@@ -0,0 +1,266 @@
+// ------------------------------------------------------------------
+//  Attach (terminal client of a --daemon session)
+// ------------------------------------------------------------------
+
+use serde_json::Value;
+
+// API
+// ------------------------------------------------------------------
+
+/// `vtmate attach`: shows the conversation of the session behind the control
+/// socket as it goes and drives it from the keyboard. Detaching leaves the
+/// session (and its models) running. Returns the process exit code.
+#[cfg(unix)]
+pub fn run_attach_command(socket: Option<&str>) -> i32 {
+  use std::io::{BufRead, BufReader, Write};
+
+  let Some(path) = crate::control::socket_path(socket) else {
+    eprintln!("❌ Could not determine the control socket path");
+    return 1;
+  };
+  let stream = match std::os::unix::net::UnixStream::connect(&path) {
+    Ok(s) => s,
+    Err(e) => {
+      eprintln!("❌ No running vtmate on {} ({})", path.display(), e);
+      return 1;
+    }
+  };
+  let Ok(mut writer) = stream.try_clone() else {
+    return 1;
+  };
+  let mut lines = BufReader::new(stream).lines();
+  let welcome = writeln!(writer, r#"{{"cmd":"attach"}}"#)
+    .ok()
+    .and_then(|_| lines.next())
+    .and_then(|l| l.ok())
+    .and_then(|l| serde_json::from_str::<Value>(&l).ok());
+  let Some(welcome) = welcome.filter(|w| w["ok"] == true) else {
+    eprintln!("❌ {} did not accept the attach", path.display());
+    return 1;
+  };
+
+  // everything the session sends, read on its own thread
+  let (tx, rx) = crossbeam_channel::unbounded::<Value>();
+  std::thread::spawn(move || {
+    for line in lines {
+      let Ok(line) = line else { break };
+      if let Ok(msg) = serde_json::from_str::<Value>(&line)
+        && tx.send(msg).is_err()
+      {
+        break;
+      }
+    }
+  });
+
+  let mut view = View::new(&welcome);
+  let _ = crossterm::terminal::enable_raw_mode();
+  view.print_intro(&welcome);
+  let code = loop {
+    match rx.recv_timeout(std::time::Duration::from_millis(50)) {
+      Ok(msg) => view.on_message(&msg),
+      Err(crossbeam_channel::RecvTimeoutError::Disconnected) => {
+        view.print_line("🛑 The session ended");
+        break 1;
+      }
+      Err(crossbeam_channel::RecvTimeoutError::Timeout) => {}
+    }
+    match view.on_keys() {
+      Some(Action::Send(req)) if writeln!(writer, "{}", req).is_err() => {
+        view.print_line("🛑 The session ended");
+        break 1;
+      }
+      Some(Action::Detach) => {
+        view.print_line("👋 Detached, vtmate keeps running");
+        break 0;
+      }
+      _ => {}
+    }
+  };
+  let _ = crossterm::terminal::disable_raw_mode();
+  code
+}
+
+#[cfg(not(unix))]
+pub fn run_attach_command(_socket: Option<&str>) -> i32 {
+  eprintln!("❌ vtmate attach needs the control socket, only available on unix");
+  1
+}
+
+#[cfg(not(unix))]
+pub fn run_attach_command(_socket: Option<&str>) -> i32 {
+  eprintln!("❌ attach needs the control socket, only available on unix");
+  1
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+#[cfg(unix)]
+enum Action {
+  Send(Value),
+  Detach,
+}
+
+// what is on screen and the keyboard state
+#[cfg(unix)]
+struct View {
+  agent: String,
+  paused: bool,
+  // a reply is being streamed on the current line
+  streaming: bool,
+  // text being typed after ENTER
+  input: Option<String>,
+}
+
+#[cfg(unix)]
+impl View {
+  fn new(welcome: &Value) -> Self {
+    Self {
+      agent: welcome["status"]["agent"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| crate::ui::labels().assistant.clone()),
+      paused: welcome["status"]["paused"].as_bool().unwrap_or(false),
+      streaming: false,
+      input: None,
+    }
+  }
+
+  fn print_intro(&mut self, welcome: &Value) {
+    self.print_line(&format!(
+      "🔗 Attached to agent {} · SPACE pause/resume · ESC interrupt · ENTER type a message · q detach",
+      self.agent
+    ));
+    for msg in welcome["history"].as_array().into_iter().flatten() {
+      let text = msg["content"].as_str().unwrap_or("");
+      if msg["role"] == "user" {
+        self.print_line(&format!("{} {}", crate::ui::user_label(), text));
+      } else {
+        let agent = msg["agent"].as_str().unwrap_or(&self.agent).to_string();
+        self.print_line(&format!("{} {}", crate::ui::agent_label(&agent), text));
+      }
+    }
+    if self.paused {
+      self.print_line("⏸  Listening is paused");
+    }
+  }
+
+  fn on_message(&mut self, msg: &Value) {
+    let text = msg["text"].as_str().unwrap_or("");
+    match msg["type"].as_str() {
+      Some("transcript") => self.print_line(&format!("{} {}", crate::ui::user_label(), text)),
+      Some("token") => {
+        if !self.streaming {
+          self.end_line();
+          print!("{} ", crate::ui::agent_label(&self.agent));
+          self.streaming = true;
+        }
+        print!("{}", text.replace('\n', "\r\n"));
+        flush();
+      }
+      Some("done") => self.end_line(),
+      Some("interrupted") => self.print_line("🛑 USER interrupted"),
+      Some(_) => {}
+      // a reply to a request
+      None => {
+        if let Some(paused) = msg["paused"].as_bool() {
+          self.paused = paused;
+          self.print_line(if paused {
+            "⏸  Listening is paused"
+          } else {
+            "▶️  Listening"
+          });
+        }
+        if let Some(e) = msg["error"].as_str() {
+          self.print_line(&format!("❌ {}", e));
+        }
+      }
+    }
+  }
+
+  fn on_keys(&mut self) -> Option<Action> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+    if !event::poll(std::time::Duration::ZERO).unwrap_or(false) {
+      return None;
+    }
+    let Ok(Event::Key(k)) = event::read() else {
+      return None;
+    };
+    if k.kind != KeyEventKind::Press {
+      return None;
+    }
+    let ctrl = k.modifiers.contains(KeyModifiers::CONTROL);
+    if ctrl && matches!(k.code, KeyCode::Char('c') | KeyCode::Char('d')) {
+      return Some(Action::Detach);
+    }
+    if let Some(input) = self.input.as_mut() {
+      match k.code {
+        KeyCode::Enter => {
+          let text = input.trim().to_string();
+          self.input = None;
+          print!("\r\x1b[K");
+          flush();
+          if !text.is_empty() {
+            return Some(Action::Send(
+              serde_json::json!({ "cmd": "ask", "text": text }),
+            ));
+          }
+        }
+        KeyCode::Esc => {
+          self.input = None;
+          print!("\r\x1b[K");
+          flush();
+        }
+        KeyCode::Backspace => {
+          input.pop();
+          self.draw_input();
+        }
+        KeyCode::Char(c) => {
+          input.push(c);
+          self.draw_input();
+        }
+        _ => {}
+      }
+      return None;
+    }
+    match k.code {
+      KeyCode::Char('q') => Some(Action::Detach),
+      KeyCode::Char(' ') => {
+        let cmd = if self.paused { "resume" } else { "pause" };
+        Some(Action::Send(serde_json::json!({ "cmd": cmd })))
+      }
+      KeyCode::Esc => Some(Action::Send(serde_json::json!({ "cmd": "interrupt" }))),
+      KeyCode::Enter => {
+        self.end_line();
+        self.input = Some(String::new());
+        self.draw_input();
+        None
+      }
+      _ => None,
+    }
+  }
+
+  fn draw_input(&self) {
+    print!("\r\x1b[K› {}", self.input.as_deref().unwrap_or(""));
+    flush();
+  }
+
+  fn print_line(&mut self, line: &str) {
+    self.end_line();
+    print!("{}\r\n", line);
+    flush();
+  }
+
+  fn end_line(&mut self) {
+    if self.streaming {
+      print!("\r\n");
+      self.streaming = false;
+    }
+  }
+}
+
+#[cfg(unix)]
+fn flush() {
+  use std::io::Write;
+  let _ = std::io::stdout().flush();
+}
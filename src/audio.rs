@@ -2,15 +2,29 @@
 //  Audio processing
 // ------------------------------------------------------------------
 
+use crate::error::AudioError;
 use cpal::traits::{DeviceTrait, HostTrait};
 use std::path::Path;
+use std::sync::Arc;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Wait between attempts to rebuild a broken audio stream.
+pub const STREAM_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+// audio streams broken right now, and whether listening was paused before
+// the first one broke
+static BROKEN_STREAMS: AtomicUsize = AtomicUsize::new(0);
+static PAUSED_BEFORE: AtomicBool = AtomicBool::new(false);
 
 // API
 // ------------------------------------------------------------------
 
+/// Interleaved samples, shared rather than copied between the threads an
+/// utterance or a phrase goes through.
 #[derive(Clone, Debug)]
 pub struct AudioChunk {
-  pub data: Vec<f32>, // interleaved
+  pub data: Arc<[f32]>, // interleaved
   pub channels: u16,
   pub sample_rate: u32,
 }
@@ -26,35 +40,145 @@ pub fn f32_to_i16(samples: &[f32]) -> Vec<i16> {
     .collect()
 }
 
-pub fn pick_input_stream(host: &cpal::Host) -> Result<(cpal::Device, cpal::Stream), String> {
-  let err = || {
-    "No usable microphone stream could be opened.\n".to_string()
-      + "    • On MacOS: System Settings → Privacy & Security → Microphone → allow your app/Terminal\n"
-      + "    • Also check System Settings → Sound → Input\n"
-  };
-  let dev = host.default_input_device().ok_or_else(err)?;
-  let cfg = dev.default_input_config().map_err(|_| err())?;
+pub fn pick_input_stream(host: &cpal::Host) -> crate::error::Result<(cpal::Device, cpal::Stream)> {
+  let dev = host
+    .default_input_device()
+    .ok_or(AudioError::NoDevice { input: true })?;
+  let cfg = dev
+    .default_input_config()
+    .map_err(|e| AudioError::Stream(e.to_string()))?;
+  // in the device's own sample format (often i16 or u16 with WASAPI)
   let stream = dev
-    .build_input_stream(&cfg.clone().into(), |_data: &[f32], _| {}, |_err| {}, None)
-    .map_err(|_| err())?;
+    .build_input_stream_raw(
+      &cfg.clone().into(),
+      cfg.sample_format(),
+      |_data, _| {},
+      |_err| {},
+      None,
+    )
+    .map_err(|e| AudioError::Stream(e.to_string()))?;
   Ok((dev, stream))
 }
 
-pub fn pick_output_stream(host: &cpal::Host) -> Result<(cpal::Device, cpal::Stream), String> {
-  let err = || {
-    "No usable output stream could be opened.".to_string()
-      + "   • On MacOS: System Settings → Sound → Output (select a device)"
+/// The output device with `name` in its name (case insensitive), for
+/// --secondary-output. The error lists the devices there are.
+pub fn find_output_device(host: &cpal::Host, name: &str) -> Result<cpal::Device, String> {
+  let devices: Vec<cpal::Device> = host
+    .output_devices()
+    .map_err(|e| format!("Unable to list the output devices: {}", e))?
+    .collect();
+  let wanted = name.to_lowercase();
+  let names: Vec<String> = devices.iter().filter_map(|d| d.name().ok()).collect();
+  devices
+    .into_iter()
+    .find(|d| d.name().is_ok_and(|n| n.to_lowercase().contains(&wanted)))
+    .ok_or_else(|| {
+      format!(
+        "No output device named '{}'. Available: {}",
+        name,
+        names.join(", ")
+      )
+    })
+}
+
+/// Output stream config of the device asking for a buffer of `frames`
+/// (within what the device supports), the device default for 0.
+pub fn output_config(supported: &cpal::SupportedStreamConfig, frames: u32) -> cpal::StreamConfig {
+  let mut config: cpal::StreamConfig = supported.clone().into();
+  if frames > 0 {
+    let frames = match supported.buffer_size() {
+      cpal::SupportedBufferSize::Range { min, max } => frames.clamp(*min, *max),
+      cpal::SupportedBufferSize::Unknown => frames,
+    };
+    config.buffer_size = cpal::BufferSize::Fixed(frames);
+  }
+  config
+}
+
+/// Asks realtime scheduling for the audio callback thread calling it (once
+/// per thread), so a loaded system doesn't make the audio stutter. The
+/// callbacks only copy samples, resampling happens on the threads feeding
+/// them. Without permission (rtkit, limits.conf) it stays as it was.
+#[cfg(feature = "rt-priority")]
+pub fn promote_audio_thread(config: &cpal::StreamConfig) {
+  thread_local! {
+    static PROMOTED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+  }
+  if PROMOTED.with(|p| p.replace(true)) {
+    return;
+  }
+  // 0 lets the crate assume a large buffer
+  let frames = match config.buffer_size {
+    cpal::BufferSize::Fixed(n) => n,
+    cpal::BufferSize::Default => 0,
+  };
+  match audio_thread_priority::promote_current_thread_to_real_time(frames, config.sample_rate.0) {
+    Ok(_) => crate::log::log("debug", "Audio thread running with realtime priority"),
+    Err(e) => crate::log::log(
+      "info",
+      &format!("No realtime priority for the audio thread: {}", e),
+    ),
+  }
+}
+
+#[cfg(not(feature = "rt-priority"))]
+pub fn promote_audio_thread(_config: &cpal::StreamConfig) {}
+
+/// Reports an audio stream that stopped working (device unplugged, driver
+/// glitch) and pauses the conversation until `stream_recovered`: listening
+/// stops, and the reply in progress too when it's the output.
+pub fn stream_failed(stream: &str, error: &cpal::StreamError) {
+  crate::log::log(
+    "error",
+    &format!(
+      "The {} stream failed ({}), paused until it's back",
+      stream, error
+    ),
+  );
+  let Some(state) = crate::state::GLOBAL_STATE.get() else {
+    return;
   };
-  let dev = host.default_output_device().ok_or_else(err)?;
-  let cfg = dev.default_output_config().map_err(|_| err())?;
+  if BROKEN_STREAMS.fetch_add(1, Ordering::SeqCst) == 0 {
+    let paused = state.recording_paused.swap(true, Ordering::Relaxed);
+    PAUSED_BEFORE.store(paused, Ordering::Relaxed);
+  }
+  if stream == "output" {
+    state.interrupter.interrupt();
+  }
+}
+
+/// The stream reported by `stream_failed` was rebuilt: listening resumes once
+/// no stream is broken, unless it was paused already.
+pub fn stream_recovered(stream: &str) {
+  crate::log::log("info", &format!("The {} stream is back", stream));
+  let Some(state) = crate::state::GLOBAL_STATE.get() else {
+    return;
+  };
+  if BROKEN_STREAMS.fetch_sub(1, Ordering::SeqCst) == 1 && !PAUSED_BEFORE.load(Ordering::Relaxed) {
+    state.recording_paused.store(false, Ordering::Relaxed);
+  }
+}
+
+pub fn pick_output_stream(host: &cpal::Host) -> crate::error::Result<(cpal::Device, cpal::Stream)> {
+  let dev = host
+    .default_output_device()
+    .ok_or(AudioError::NoDevice { input: false })?;
+  let cfg = dev
+    .default_output_config()
+    .map_err(|e| AudioError::Stream(e.to_string()))?;
   let stream = dev
-    .build_output_stream(
+    .build_output_stream_raw(
       &cfg.clone().into(),
-      |data: &mut [f32], _| data.fill(0.0),
+      cfg.sample_format(),
+      |data: &mut cpal::Data, _| match data.as_slice_mut::<u16>() {
+        // unsigned silence is the middle of the range
+        Some(samples) => samples.fill(u16::MAX / 2 + 1),
+        None => data.bytes_mut().fill(0),
+      },
       |_err| {},
       None,
     )
-    .map_err(|_| err())?;
+    .map_err(|e| AudioError::Stream(e.to_string()))?;
   Ok((dev, stream))
 }
 
@@ -135,7 +259,7 @@ pub fn resample_to(input: &[f32], channels: u16, in_sr: u32, out_sr: u32) -> Vec
   }
 }
 
-pub fn convert_to_mono(utt: &crate::audio::AudioChunk) -> Vec<f32> {
+pub fn convert_to_mono(utt: &crate::audio::AudioChunk) -> Arc<[f32]> {
   let pcm_f32 = &utt.data;
   if utt.channels == 1 {
     pcm_f32.clone()
@@ -148,7 +272,7 @@ pub fn convert_to_mono(utt: &crate::audio::AudioChunk) -> Vec<f32> {
       let sum: f32 = pcm_f32[start..start + ch].iter().sum();
       mono.push(sum / ch as f32);
     }
-    mono
+    mono.into()
   }
 }
 
@@ -202,6 +326,82 @@ pub fn init_wav_writer(path: &Path) -> crossbeam_channel::Sender<AudioChunk> {
   tx
 }
 
+/// Decodes a WAV file (stdin for "-") into the f32 samples utterances are
+/// made of.
+pub fn read_wav(file: &str) -> Result<AudioChunk, String> {
+  let source: Box<dyn std::io::Read> = if file == "-" {
+    Box::new(std::io::stdin())
+  } else {
+    Box::new(std::fs::File::open(file).map_err(|e| format!("Failed to open {}: {}", file, e))?)
+  };
+  let reader = hound::WavReader::new(std::io::BufReader::new(source))
+    .map_err(|e| format!("{} is not a WAV file: {}", file, e))?;
+  let spec = reader.spec();
+  let data: Result<Vec<f32>, hound::Error> = match spec.sample_format {
+    hound::SampleFormat::Float => reader.into_samples::<f32>().collect(),
+    hound::SampleFormat::Int => {
+      let scale = (1_i64 << (spec.bits_per_sample - 1)) as f32;
+      reader
+        .into_samples::<i32>()
+        .map(|s| s.map(|s| s as f32 / scale))
+        .collect()
+    }
+  };
+  Ok(AudioChunk {
+    data: data
+      .map_err(|e| format!("Failed to decode {}: {}", file, e))?
+      .into(),
+    channels: spec.channels,
+    sample_rate: spec.sample_rate,
+  })
+}
+
+/// `seconds` of the default microphone, with a countdown.
+pub fn record_microphone(seconds: u64) -> Result<AudioChunk, String> {
+  use cpal::traits::{DeviceTrait, StreamTrait};
+  use std::io::Write;
+  use std::sync::{Arc, Mutex};
+
+  let host = cpal::default_host();
+  let (device, _) = pick_input_stream(&host).map_err(|e| e.to_string())?;
+  let supported = device.default_input_config().map_err(|e| e.to_string())?;
+  let config: cpal::StreamConfig = supported.clone().into();
+  let samples = Arc::new(Mutex::new(Vec::<f32>::new()));
+  let sink = samples.clone();
+  let stream = device
+    .build_input_stream_raw(
+      &config,
+      supported.sample_format(),
+      move |data: &cpal::Data, _| {
+        let mut sink = sink.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(s) = data.as_slice::<f32>() {
+          sink.extend_from_slice(s);
+        } else if let Some(s) = data.as_slice::<i16>() {
+          sink.extend(s.iter().map(|&v| v as f32 / i16::MAX as f32));
+        } else if let Some(s) = data.as_slice::<u16>() {
+          sink.extend(s.iter().map(|&v| (v as f32 / u16::MAX as f32) * 2.0 - 1.0));
+        }
+      },
+      |e| eprintln!("❌ Microphone error: {}", e),
+      None,
+    )
+    .map_err(|e| format!("Failed to open the microphone: {}", e))?;
+  stream.play().map_err(|e| e.to_string())?;
+  for left in (1..=seconds).rev() {
+    print!("\r🎤 Recording... {:>3}s ", left);
+    let _ = std::io::stdout().flush();
+    std::thread::sleep(std::time::Duration::from_secs(1));
+  }
+  println!("\r🎤 Recording... done");
+  drop(stream);
+  let data = std::mem::take(&mut *samples.lock().unwrap_or_else(PoisonError::into_inner));
+  Ok(AudioChunk {
+    data: data.into(),
+    channels: config.channels,
+    sample_rate: config.sample_rate.0,
+  })
+}
+
 /// Write plain text to a file.
 pub fn write_txt(path: &Path, text: &str) -> Result<(), std::io::Error> {
   std::fs::write(path, text)
@@ -0,0 +1,308 @@
+// ------------------------------------------------------------------
+//  Bench (stt / llm / tts latency per agent)
+// ------------------------------------------------------------------
+
+use crate::audio::AudioChunk;
+use crate::cancel::CancellationToken;
+use crate::config::{AgentSettings, Args};
+use crate::conversation::ChatMessage;
+use crate::engine::{BuiltinTts, LanguageModel, ServerLlm, TextToSpeech};
+use crate::state::{AppState, GLOBAL_STATE};
+use std::sync::Arc;
+use std::time::Instant;
+
+// utterances spoken by each agent's own tts when no WAV file is given
+const CANNED_UTTERANCES: &[&str] = &[
+  "What is the capital of France?",
+  "Give me one tip to sleep better.",
+  "How long should I boil an egg for a soft yolk?",
+];
+// sample rate asked to the tts backends that can resample (opentts)
+const TTS_SAMPLE_RATE: u32 = 22050;
+
+// API
+// ------------------------------------------------------------------
+
+/// Runs every utterance through stt, llm and tts `iterations` times for each
+/// agent (all of them unless some are named) and prints the mean latency of
+/// every stage. Returns the exit code.
+pub fn run_bench_command(
+  iterations: usize,
+  agent_names: &[String],
+  files: &[String],
+  skip_llm: bool,
+  args: &Args,
+) -> i32 {
+  match bench(iterations.max(1), agent_names, files, skip_llm, args) {
+    Ok(()) => 0,
+    Err(e) => {
+      eprintln!("❌ {}", e);
+      1
+    }
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// timings of one utterance going through the pipeline
+#[derive(Default)]
+struct Sample {
+  stt_ms: f64,
+  llm_first_ms: f64,
+  llm_ms: f64,
+  tokens: usize,
+  tts_first_ms: f64,
+  tts_ms: f64,
+  audio_ms: f64,
+}
+
+fn bench(
+  iterations: usize,
+  agent_names: &[String],
+  files: &[String],
+  skip_llm: bool,
+  args: &Args,
+) -> Result<(), String> {
+  let _ = crate::config::ensure_settings_file();
+  let all_agents = crate::config::resolve_settings_path(args)
+    .and_then(|path| crate::config::load_settings(&path, args))
+    .map_err(|e| format!("Failed to load settings: {}", e))?;
+  let agents: Vec<AgentSettings> = if agent_names.is_empty() {
+    all_agents.clone()
+  } else {
+    agent_names
+      .iter()
+      .map(|name| {
+        all_agents
+          .iter()
+          .find(|a| a.name == *name)
+          .cloned()
+          .ok_or_else(|| format!("Agent '{}' not found", name))
+      })
+      .collect::<Result<_, _>>()?
+  };
+  crate::assets::ensure_agent_assets(&agents, false)?;
+  let recordings = files
+    .iter()
+    .map(|f| crate::audio::read_wav(f))
+    .collect::<Result<Vec<_>, _>>()?;
+  GLOBAL_STATE
+    .set(Arc::new(AppState::with_agent(
+      agents[0].clone(),
+      all_agents,
+      true,
+    )))
+    .ok();
+
+  println!(
+    "{:<16} {:<14} {:<12} {:>8} {:>10} {:>9} {:>7} {:>10} {:>8} {:>6}",
+    "agent", "whisper", "tts", "stt ms", "llm 1st", "llm ms", "tok/s", "tts 1st", "tts ms", "rtf"
+  );
+  for agent in &agents {
+    let samples = bench_agent(agent, &recordings, iterations, skip_llm)?;
+    let mean = |f: fn(&Sample) -> f64| samples.iter().map(f).sum::<f64>() / samples.len() as f64;
+    let llm_secs = samples.iter().map(|s| s.llm_ms).sum::<f64>() / 1000.0;
+    let tokens = samples.iter().map(|s| s.tokens).sum::<usize>() as f64;
+    let audio_ms = samples.iter().map(|s| s.audio_ms).sum::<f64>();
+    let tts_ms = samples.iter().map(|s| s.tts_ms).sum::<f64>();
+    println!(
+      "{:<16} {:<14} {:<12} {:>8.0} {:>10} {:>9} {:>7} {:>10.0} {:>8.0} {:>6.2}",
+      agent.name,
+      whisper_name(&agent.whisper_model_path),
+      format!("{}/{}", agent.tts, agent.voice),
+      mean(|s| s.stt_ms),
+      if skip_llm {
+        "-".to_string()
+      } else {
+        format!("{:.0}", mean(|s| s.llm_first_ms))
+      },
+      if skip_llm {
+        "-".to_string()
+      } else {
+        format!("{:.0}", mean(|s| s.llm_ms))
+      },
+      if skip_llm || llm_secs == 0.0 {
+        "-".to_string()
+      } else {
+        format!("{:.1}", tokens / llm_secs)
+      },
+      mean(|s| s.tts_first_ms),
+      mean(|s| s.tts_ms),
+      if audio_ms > 0.0 {
+        tts_ms / audio_ms
+      } else {
+        0.0
+      },
+    );
+  }
+  println!(
+    "\nmeans over {} run(s) of {} utterance(s); rtf is tts time / audio length (below 1 is faster than real time)",
+    iterations,
+    if recordings.is_empty() {
+      CANNED_UTTERANCES.len()
+    } else {
+      recordings.len()
+    }
+  );
+  Ok(())
+}
+
+fn bench_agent(
+  agent: &AgentSettings,
+  recordings: &[AudioChunk],
+  iterations: usize,
+  skip_llm: bool,
+) -> Result<Vec<Sample>, String> {
+  let model_path = crate::config::resolved_whisper_model_path(&agent.whisper_model_path);
+  let ctx = crate::stt::new_whisper_context(&model_path).map_err(|e| e.to_string())?;
+  let tts = BuiltinTts {
+    tts: agent.tts.clone(),
+    out_sample_rate: TTS_SAMPLE_RATE,
+    speed: agent.voice_speed,
+  };
+  let llm = ServerLlm {
+    provider: agent.provider.clone(),
+    baseurl: agent.baseurl.clone(),
+    model: agent.model.clone(),
+  };
+  // the agent's own voice says the canned utterances (this also loads the tts)
+  let utterances: Vec<AudioChunk> = if recordings.is_empty() {
+    CANNED_UTTERANCES
+      .iter()
+      .map(|text| synthesize(&tts, agent, text).map(|(audio, _)| audio))
+      .collect::<Result<_, _>>()?
+  } else {
+    recordings.to_vec()
+  };
+
+  let mut samples = Vec::new();
+  // the first run warms the models up and isn't counted
+  for run in 0..=iterations {
+    for utterance in &utterances {
+      let sample = run_pipeline(&ctx, &llm, &tts, agent, utterance, skip_llm)?;
+      if run > 0 {
+        samples.push(sample);
+      }
+    }
+  }
+  Ok(samples)
+}
+
+fn run_pipeline(
+  ctx: &crate::stt::SttContext,
+  llm: &ServerLlm,
+  tts: &BuiltinTts,
+  agent: &AgentSettings,
+  utterance: &AudioChunk,
+  skip_llm: bool,
+) -> Result<Sample, String> {
+  let mut sample = Sample::default();
+
+  let start = Instant::now();
+  let mono = crate::audio::convert_to_mono(utterance);
+  let transcript =
+    crate::stt::whisper_transcribe_with_ctx(ctx, &mono, utterance.sample_rate, &agent.language)
+      .map_err(|e| format!("{}: transcription failed: {}", agent.name, e))?;
+  sample.stt_ms = ms_since(start);
+
+  let reply = if skip_llm {
+    transcript
+  } else {
+    let messages = [
+      ChatMessage {
+        role: "system".to_string(),
+        content: agent.system_prompt.replace("\\n", "\n"),
+        agent_name: None,
+        meta: None,
+      },
+      ChatMessage {
+        role: "user".to_string(),
+        content: transcript,
+        agent_name: None,
+        meta: None,
+      },
+    ];
+    let mut reply = String::new();
+    let start = Instant::now();
+    llm
+      .stream_reply(&messages, &CancellationToken::new(), &mut |piece| {
+        if sample.tokens == 0 {
+          sample.llm_first_ms = ms_since(start);
+        }
+        sample.tokens += 1;
+        reply.push_str(piece);
+      })
+      .map_err(|e| format!("{}: llm failed: {}", agent.name, e))?;
+    sample.llm_ms = ms_since(start);
+    reply
+  };
+
+  let text = crate::util::strip_special_chars(&reply);
+  if !text.trim().is_empty() {
+    let start = Instant::now();
+    let (audio, first_ms) = synthesize(tts, agent, &text)?;
+    sample.tts_ms = ms_since(start);
+    sample.tts_first_ms = first_ms;
+    sample.audio_ms =
+      audio.data.len() as f64 * 1000.0 / (audio.sample_rate as f64 * audio.channels.max(1) as f64);
+  }
+  Ok(sample)
+}
+
+// Synthesizes `text` as one chunk, with the time the first audio took.
+fn synthesize(
+  tts: &BuiltinTts,
+  agent: &AgentSettings,
+  text: &str,
+) -> Result<(AudioChunk, f64), String> {
+  let (tx, rx) = crossbeam_channel::unbounded::<AudioChunk>();
+  let start = Instant::now();
+  // chunks are timed as they arrive, while the backend is still working
+  let receiver = std::thread::spawn(move || {
+    let mut first_ms = 0.0;
+    let mut format: Option<(u16, u32)> = None;
+    let mut data = Vec::new();
+    for chunk in rx {
+      if format.is_none() {
+        first_ms = ms_since(start);
+        format = Some((chunk.channels, chunk.sample_rate));
+      }
+      data.extend_from_slice(&chunk.data);
+    }
+    let audio = format.map(|(channels, sample_rate)| AudioChunk {
+      data: data.into(),
+      channels,
+      sample_rate,
+    });
+    (audio, first_ms)
+  });
+  tts
+    .speak(
+      text,
+      &agent.language,
+      &agent.voice,
+      tx,
+      &CancellationToken::new(),
+    )
+    .map_err(|e| format!("{}: tts failed: {}", agent.name, e))?;
+  match receiver.join() {
+    Ok((Some(audio), first_ms)) => Ok((audio, first_ms)),
+    _ => Err(format!(
+      "{}: tts '{}' produced no audio",
+      agent.name, agent.tts
+    )),
+  }
+}
+
+fn ms_since(start: Instant) -> f64 {
+  start.elapsed().as_secs_f64() * 1000.0
+}
+
+// "~/.vtmate/models/ggml-small.bin" -> "ggml-small"
+fn whisper_name(path: &str) -> String {
+  std::path::Path::new(path)
+    .file_stem()
+    .map(|s| s.to_string_lossy().to_string())
+    .unwrap_or_else(|| path.to_string())
+}
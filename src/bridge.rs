@@ -0,0 +1,298 @@
+// ------------------------------------------------------------------
+//  Chat bridge (--bridge telegram:CHAT_ID / discord:CHANNEL_ID)
+// ------------------------------------------------------------------
+//
+// Messages of one chat are asked to the running conversation, as if typed
+// in the terminal: they share its history and the reply is spoken at home
+// too. The reply goes back to the chat as text, and as a voice note with
+// --bridge-voice. Only the configured chat is answered, and in it only the
+// members given with --bridge-user. Their messages are asked without the
+// shell commands: a chat can't run one (see crate::shell).
+
+use crate::audio::AudioChunk;
+use crate::cancel::CancellationToken;
+use crate::conversation::Command;
+use crate::engine::{BuiltinTts, TextToSpeech, TurnEvent};
+use crate::state::GLOBAL_STATE;
+use crossbeam_channel::Sender;
+use reqwest::blocking::{Client, multipart};
+use serde_json::{Value, json};
+use std::sync::PoisonError;
+use std::time::{Duration, Instant};
+
+// how long a message waits for its reply
+const REPLY_TIMEOUT: Duration = Duration::from_secs(300);
+// telegram long polling, discord has no such thing and is polled
+const TELEGRAM_POLL_SECS: u64 = 30;
+const DISCORD_POLL_INTERVAL: Duration = Duration::from_secs(3);
+// wait after a failed poll (network down, rate limited)
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+const DISCORD_MAX_CHARS: usize = 2000;
+// sample rate asked to the tts backends that can resample (opentts)
+const VOICE_SAMPLE_RATE: u32 = 22050;
+
+// API
+// ------------------------------------------------------------------
+
+/// Answers the messages of `users` (their ids) in `chat` ("telegram:CHAT_ID"
+/// or "discord:CHANNEL_ID") through the conversation. Blocks.
+pub fn bridge_thread(
+  chat: String,
+  users: Vec<String>,
+  voice: bool,
+  tx_cmd: Sender<Command>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  let (kind, id) = chat
+    .split_once(':')
+    .ok_or("bridge must be telegram:CHAT_ID or discord:CHANNEL_ID")?;
+  let client = Client::builder()
+    .timeout(Duration::from_secs(TELEGRAM_POLL_SECS + 15))
+    .build()?;
+  match kind {
+    "telegram" => {
+      let token =
+        std::env::var("VTMATE_TELEGRAM_TOKEN").map_err(|_| "VTMATE_TELEGRAM_TOKEN is not set")?;
+      telegram_loop(&client, &token, id, &users, voice, &tx_cmd)
+    }
+    "discord" => {
+      let token =
+        std::env::var("VTMATE_DISCORD_TOKEN").map_err(|_| "VTMATE_DISCORD_TOKEN is not set")?;
+      discord_loop(&client, &token, id, &users, voice, &tx_cmd)
+    }
+    other => Err(format!("unknown bridge '{}'", other).into()),
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn telegram_loop(
+  client: &Client,
+  token: &str,
+  chat_id: &str,
+  users: &[String],
+  voice: bool,
+  tx_cmd: &Sender<Command>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  let chat: i64 = chat_id
+    .parse()
+    .map_err(|_| format!("'{}' is not a telegram chat id", chat_id))?;
+  let api = format!("https://api.telegram.org/bot{}", token);
+  crate::log::log("info", &format!("Telegram bridge for chat {}", chat_id));
+  let mut offset = 0_i64;
+  loop {
+    let updates: Value = match client
+      .get(format!("{}/getUpdates", api))
+      .query(&[
+        ("timeout", TELEGRAM_POLL_SECS.to_string()),
+        ("offset", offset.to_string()),
+      ])
+      .send()
+      .and_then(|r| r.json())
+    {
+      Ok(v) => v,
+      Err(e) => {
+        crate::log::log("warning", &format!("Telegram bridge: {}", e));
+        std::thread::sleep(RETRY_DELAY);
+        continue;
+      }
+    };
+    for update in updates["result"].as_array().into_iter().flatten() {
+      offset = offset.max(update["update_id"].as_i64().unwrap_or(0) + 1);
+      let message = &update["message"];
+      if message["chat"]["id"].as_i64() != Some(chat)
+        || !is_user(users, &message["from"]["id"].to_string())
+      {
+        continue;
+      }
+      let Some(text) = message["text"].as_str() else {
+        continue;
+      };
+      let _ = client
+        .post(format!("{}/sendChatAction", api))
+        .json(&json!({ "chat_id": chat_id, "action": "typing" }))
+        .send();
+      let reply = ask(text, tx_cmd);
+      let _ = client
+        .post(format!("{}/sendMessage", api))
+        .json(&json!({ "chat_id": chat_id, "text": reply }))
+        .send();
+      if voice && let Some(wav) = voice_note(&reply) {
+        let form = multipart::Form::new()
+          .text("chat_id", chat_id.to_string())
+          .part(
+            "document",
+            multipart::Part::bytes(wav).file_name("reply.wav"),
+          );
+        let _ = client
+          .post(format!("{}/sendDocument", api))
+          .multipart(form)
+          .send();
+      }
+    }
+  }
+}
+
+fn discord_loop(
+  client: &Client,
+  token: &str,
+  channel_id: &str,
+  users: &[String],
+  voice: bool,
+  tx_cmd: &Sender<Command>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  let url = format!(
+    "https://discord.com/api/v10/channels/{}/messages",
+    channel_id
+  );
+  let auth = format!("Bot {}", token);
+  crate::log::log(
+    "info",
+    &format!("Discord bridge for channel {}", channel_id),
+  );
+  // start after the last message, the channel history isn't answered
+  let latest: Value = client
+    .get(&url)
+    .header("Authorization", &auth)
+    .query(&[("limit", "1")])
+    .send()?
+    .error_for_status()?
+    .json()?;
+  let mut after = latest[0]["id"].as_str().unwrap_or("0").to_string();
+  loop {
+    std::thread::sleep(DISCORD_POLL_INTERVAL);
+    let messages: Value = match client
+      .get(&url)
+      .header("Authorization", &auth)
+      .query(&[("after", after.as_str()), ("limit", "20")])
+      .send()
+      .and_then(|r| r.json())
+    {
+      Ok(v) => v,
+      Err(e) => {
+        crate::log::log("warning", &format!("Discord bridge: {}", e));
+        std::thread::sleep(RETRY_DELAY);
+        continue;
+      }
+    };
+    let Some(messages) = messages.as_array() else {
+      continue;
+    };
+    // newest first
+    for message in messages.iter().rev() {
+      if let Some(id) = message["id"].as_str() {
+        after = id.to_string();
+      }
+      let text = message["content"].as_str().unwrap_or("").trim();
+      if message["author"]["bot"] == true
+        || text.is_empty()
+        || !is_user(users, message["author"]["id"].as_str().unwrap_or(""))
+      {
+        continue;
+      }
+      let reply = ask(text, tx_cmd);
+      for part in split_chars(&reply, DISCORD_MAX_CHARS) {
+        let _ = client
+          .post(&url)
+          .header("Authorization", &auth)
+          .json(&json!({ "content": part }))
+          .send();
+      }
+      if voice && let Some(wav) = voice_note(&reply) {
+        let form = multipart::Form::new().text("payload_json", "{}").part(
+          "files[0]",
+          multipart::Part::bytes(wav).file_name("reply.wav"),
+        );
+        let _ = client
+          .post(&url)
+          .header("Authorization", &auth)
+          .multipart(form)
+          .send();
+      }
+    }
+  }
+}
+
+// Whether the author of a message is one of `users`.
+fn is_user(users: &[String], author: &str) -> bool {
+  users.iter().any(|u| u.trim() == author)
+}
+
+// Asks the conversation and waits for the reply to this message.
+fn ask(text: &str, tx_cmd: &Sender<Command>) -> String {
+  let events = crate::events::subscribe();
+  if tx_cmd.send(Command::BridgeAsk(text.to_string())).is_err() {
+    return "(the conversation is not running)".to_string();
+  }
+  let deadline = Instant::now() + REPLY_TIMEOUT;
+  let mut asked = false;
+  while let Ok(event) = events.recv_deadline(deadline) {
+    match event {
+      TurnEvent::Transcript(t) if t == text => asked = true,
+      TurnEvent::Done(reply) if asked => return reply.trim().to_string(),
+      TurnEvent::Interrupted if asked => return "(interrupted)".to_string(),
+      _ => {}
+    }
+  }
+  "(no reply)".to_string()
+}
+
+// The reply spoken by the current agent, as a WAV file.
+fn voice_note(text: &str) -> Option<Vec<u8>> {
+  let state = GLOBAL_STATE.get()?;
+  let tts = BuiltinTts {
+    tts: state
+      .tts
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone(),
+    out_sample_rate: VOICE_SAMPLE_RATE,
+    speed: crate::state::get_speed(),
+  };
+  let language = state
+    .language
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let voice = state
+    .voice
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let (tx, rx) = crossbeam_channel::unbounded::<AudioChunk>();
+  if let Err(e) = tts.speak(
+    &crate::util::strip_special_chars(text),
+    &language,
+    &voice,
+    tx,
+    &CancellationToken::new(),
+  ) {
+    crate::log::log("warning", &format!("Bridge voice note failed: {}", e));
+    return None;
+  }
+  let chunks: Vec<AudioChunk> = rx.try_iter().collect();
+  let first = chunks.first()?;
+  let spec = hound::WavSpec {
+    channels: first.channels,
+    sample_rate: first.sample_rate,
+    bits_per_sample: 16,
+    sample_format: hound::SampleFormat::Int,
+  };
+  let mut bytes = std::io::Cursor::new(Vec::new());
+  let mut writer = hound::WavWriter::new(&mut bytes, spec).ok()?;
+  for chunk in &chunks {
+    for s in crate::audio::f32_to_i16(&chunk.data) {
+      writer.write_sample(s).ok()?;
+    }
+  }
+  writer.finalize().ok()?;
+  Some(bytes.into_inner())
+}
+
+fn split_chars(text: &str, max: usize) -> Vec<String> {
+  let chars: Vec<char> = text.chars().collect();
+  chars
+    .chunks(max)
+    .map(|c| c.iter().collect::<String>())
+    .collect()
+}
@@ -0,0 +1,110 @@
+// ------------------------------------------------------------------
+//  Cancellation
+// ------------------------------------------------------------------
+//
+// A reply is stopped part way (barge-in, ESC, `vtmate ctl interrupt`) by
+// cancelling its token: the llm stream, the tts and the playback check it
+// between pieces. A turn works with a child of the token current when it
+// started, so an interruption reaches every turn in progress while the
+// ones started after get a fresh token.
+
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+// API
+// ------------------------------------------------------------------
+
+/// Stops work once cancelled. Clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<Node>);
+
+impl CancellationToken {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// A token cancelled along with this one, which can also be cancelled on
+  /// its own.
+  pub fn child(&self) -> Self {
+    Self(Arc::new(Node {
+      cancelled: AtomicBool::new(false),
+      parent: Some(self.0.clone()),
+    }))
+  }
+
+  pub fn cancel(&self) {
+    self.0.cancelled.store(true, Ordering::SeqCst);
+  }
+
+  /// Whether `other` is a clone of this token.
+  pub fn same_as(&self, other: &CancellationToken) -> bool {
+    Arc::ptr_eq(&self.0, &other.0)
+  }
+
+  /// Whether this token or one of its parents was cancelled.
+  pub fn is_cancelled(&self) -> bool {
+    let mut node = Some(&self.0);
+    while let Some(n) = node {
+      if n.cancelled.load(Ordering::SeqCst) {
+        return true;
+      }
+      node = n.parent.as_ref();
+    }
+    false
+  }
+}
+
+/// Interrupts what is in progress, from any thread. Clones share the same
+/// state.
+#[derive(Clone, Debug, Default)]
+pub struct Interrupter(Arc<Shared>);
+
+impl Interrupter {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The token the next interruption cancels. A turn takes a child of it.
+  pub fn token(&self) -> CancellationToken {
+    self
+      .0
+      .current
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone()
+  }
+
+  /// Cancels the current token, what starts from now gets a fresh one.
+  pub fn interrupt(&self) {
+    let old = std::mem::take(
+      &mut *self
+        .0
+        .current
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner),
+    );
+    old.cancel();
+    self.0.count.fetch_add(1, Ordering::SeqCst);
+  }
+
+  /// Interruptions so far.
+  pub fn count(&self) -> u64 {
+    self.0.count.load(Ordering::SeqCst)
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+#[derive(Debug, Default)]
+struct Node {
+  cancelled: AtomicBool,
+  parent: Option<Arc<Node>>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+  current: Mutex<CancellationToken>,
+  count: AtomicU64,
+}
@@ -0,0 +1,385 @@
+// ------------------------------------------------------------------
+//  Live captions (--captions)
+// ------------------------------------------------------------------
+//
+// Subtitles are timed on the session audio: what was said and played, in
+// order, as written to the recording with --save (each chunk followed by the
+// silence the writer adds). An utterance spans its own audio, a phrase the
+// audio synthesized for it, so the .srt / .vtt file lines up with the
+// recorded .wav.
+
+use crate::audio::AudioChunk;
+use crate::engine::TurnEvent;
+use crate::state::GLOBAL_STATE;
+use crossbeam_channel::{Sender, select};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::PoisonError;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+// speech pace used to guess how long a typed message stays on screen
+const MS_PER_WORD: u64 = 400;
+const MIN_CAPTION_MS: u64 = 1200;
+// lines kept in a plain text captions file
+const TEXT_LINES: usize = 2;
+// how often phrases waiting for their audio to be played are checked
+const PENDING_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// None unless captions are written
+static TIMELINE: Mutex<Option<Timeline>> = Mutex::new(None);
+static PHRASE_TX: OnceLock<Sender<Spoken>> = OnceLock::new();
+// the voice sent to playback, other synthesis (voice notes) doesn't count
+static VOICE_TX: OnceLock<Sender<AudioChunk>> = OnceLock::new();
+
+// API
+// ------------------------------------------------------------------
+
+/// Writes what is said (user and assistant) to `path` as it happens:
+/// subtitles for .srt and .vtt files, otherwise a plain text file holding
+/// the last lines, for OBS text sources reading from a file.
+pub fn captions_thread(path: PathBuf) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  let mut writer = CaptionWriter::create(&path)?;
+  let timed = !matches!(writer.format, Format::Text);
+  let (tx_phrases, rx_phrases) = crossbeam_channel::unbounded::<Spoken>();
+  PHRASE_TX
+    .set(tx_phrases)
+    .map_err(|_| "captions are already written")?;
+  *TIMELINE.lock().unwrap_or_else(PoisonError::into_inner) = Some(Timeline::default());
+  let events = crate::events::subscribe();
+  // spoken phrases whose audio is not all played yet
+  let mut pending: VecDeque<Spoken> = VecDeque::new();
+  loop {
+    select! {
+      recv(events) -> event => {
+        let Ok(event) = event else { break };
+        let caption = match event {
+          TurnEvent::Transcript(text) => {
+            let (start_ms, end_ms) = utterance_span(&text);
+            Caption { speaker: "User".to_string(), start_ms, end_ms, text }
+          }
+          // shown as soon as it's sent to the voice, when the timing doesn't matter
+          TurnEvent::Phrase(text) if !timed => Caption {
+            speaker: agent_name(),
+            start_ms: 0,
+            end_ms: 0,
+            text,
+          },
+          _ => continue,
+        };
+        writer.write(caption)?;
+      }
+      recv(rx_phrases) -> spoken => {
+        if let Ok(spoken) = spoken && timed {
+          pending.push_back(spoken);
+        }
+      }
+      default(PENDING_POLL_INTERVAL) => {}
+    }
+    while let Some(caption) = pending.front().and_then(placed) {
+      pending.pop_front();
+      writer.write(caption)?;
+    }
+  }
+  Ok(())
+}
+
+/// An utterance as it was recorded, the transcript that follows spans it.
+pub fn utterance(chunk: &AudioChunk) {
+  if let Some(timeline) = TIMELINE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_mut()
+  {
+    let start = timeline.session_us;
+    timeline.advance(chunk);
+    timeline.utterance = Some((start, start + duration_us(chunk)));
+  }
+}
+
+/// The channel of the assistant voice to playback (set by the tts thread).
+pub fn watch_voice(tx: &Sender<AudioChunk>) {
+  VOICE_TX.set(tx.clone()).ok();
+}
+
+/// A chunk synthesized by a tts backend, about to be sent to `tx`.
+pub fn synthesized(tx: &Sender<AudioChunk>, chunk: &AudioChunk) {
+  if !VOICE_TX.get().is_some_and(|voice| voice.same_channel(tx)) {
+    return;
+  }
+  crate::clips::voice(chunk);
+  if let Some(timeline) = TIMELINE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_mut()
+  {
+    timeline.synthesized_us += duration_us(chunk);
+  }
+}
+
+/// A chunk of the assistant voice, taken by playback (and the recording).
+pub fn played(chunk: &AudioChunk) {
+  if let Some(timeline) = TIMELINE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_mut()
+  {
+    timeline
+      .marks
+      .push_back((timeline.played_us, timeline.session_us));
+    timeline.played_us += duration_us(chunk);
+    timeline.advance(chunk);
+  }
+}
+
+/// A chunk of the assistant voice dropped before playback (interrupted).
+pub fn dropped(chunk: &AudioChunk) {
+  if let Some(timeline) = TIMELINE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_mut()
+  {
+    timeline
+      .marks
+      .push_back((timeline.played_us, timeline.session_us));
+    timeline.played_us += duration_us(chunk);
+  }
+}
+
+/// Assistant voice synthesized so far, a phrase is the audio between two
+/// of these.
+pub fn synthesized_position() -> u64 {
+  TIMELINE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+    .map_or(0, |t| t.synthesized_us)
+}
+
+/// A phrase was spoken, its audio between two `synthesized_position`s.
+pub fn phrase(text: &str, start: u64, end: u64) {
+  if let Some(tx) = PHRASE_TX.get() {
+    let _ = tx.send(Spoken {
+      speaker: agent_name(),
+      text: text.trim().to_string(),
+      start_us: start,
+      end_us: end,
+    });
+  }
+}
+
+/// HH:MM:SS,mmm (srt) or HH:MM:SS.mmm (vtt).
+pub fn timestamp(ms: u64, separator: char) -> String {
+  let d = Duration::from_millis(ms);
+  let secs = d.as_secs();
+  format!(
+    "{:02}:{:02}:{:02}{}{:03}",
+    secs / 3600,
+    secs / 60 % 60,
+    secs % 60,
+    separator,
+    d.subsec_millis()
+  )
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+struct Caption {
+  speaker: String,
+  start_ms: u64,
+  end_ms: u64,
+  text: String,
+}
+
+// a phrase and where its audio is in the synthesized voice
+struct Spoken {
+  speaker: String,
+  text: String,
+  start_us: u64,
+  end_us: u64,
+}
+
+#[derive(Default)]
+struct Timeline {
+  // position in the session audio
+  session_us: u64,
+  // assistant voice synthesized, and taken by playback
+  synthesized_us: u64,
+  played_us: u64,
+  // (played_us, session_us) at the start of the chunks taken by playback
+  // and not captioned yet
+  marks: VecDeque<(u64, u64)>,
+  // span of the last utterance
+  utterance: Option<(u64, u64)>,
+}
+
+impl Timeline {
+  // a chunk is written to the session audio
+  fn advance(&mut self, chunk: &AudioChunk) {
+    self.session_us += duration_us(chunk);
+    if crate::playback::is_recording() {
+      self.session_us += crate::conversation::RECORDING_GAP_MS * 1000;
+    }
+  }
+}
+
+enum Format {
+  Srt,
+  Vtt,
+  Text,
+}
+
+struct CaptionWriter {
+  path: PathBuf,
+  format: Format,
+  file: File,
+  count: usize,
+  last_end_ms: u64,
+  lines: Vec<String>,
+}
+
+impl CaptionWriter {
+  fn create(path: &Path) -> std::io::Result<Self> {
+    let format = match path.extension().and_then(|e| e.to_str()) {
+      Some(e) if e.eq_ignore_ascii_case("srt") => Format::Srt,
+      Some(e) if e.eq_ignore_ascii_case("vtt") => Format::Vtt,
+      _ => Format::Text,
+    };
+    let mut file = File::create(path)?;
+    if let Format::Vtt = format {
+      file.write_all(b"WEBVTT\n\n")?;
+    }
+    Ok(Self {
+      path: path.to_path_buf(),
+      format,
+      file,
+      count: 0,
+      last_end_ms: 0,
+      lines: Vec::new(),
+    })
+  }
+
+  fn write(&mut self, mut caption: Caption) -> std::io::Result<()> {
+    caption.text = crate::redact::redact(&caption.text).into_owned();
+    // cues don't overlap, a late one is pushed after the previous
+    if caption.start_ms < self.last_end_ms {
+      let len = caption.end_ms - caption.start_ms;
+      caption.start_ms = self.last_end_ms;
+      caption.end_ms = caption.start_ms + len;
+    }
+    self.last_end_ms = caption.end_ms;
+    self.count += 1;
+    match self.format {
+      Format::Srt => write!(
+        self.file,
+        "{}\n{} --> {}\n{}: {}\n\n",
+        self.count,
+        timestamp(caption.start_ms, ','),
+        timestamp(caption.end_ms, ','),
+        caption.speaker,
+        caption.text
+      )?,
+      Format::Vtt => write!(
+        self.file,
+        "{} --> {}\n<v {}>{}\n\n",
+        timestamp(caption.start_ms, '.'),
+        timestamp(caption.end_ms, '.'),
+        caption.speaker,
+        caption.text
+      )?,
+      Format::Text => {
+        self
+          .lines
+          .push(format!("{}: {}", caption.speaker, caption.text));
+        if self.lines.len() > TEXT_LINES {
+          self.lines.remove(0);
+        }
+        // rewritten whole, OBS shows the file as it is
+        self.file = OpenOptions::new()
+          .write(true)
+          .truncate(true)
+          .open(&self.path)?;
+        writeln!(self.file, "{}", self.lines.join("\n"))?;
+      }
+    }
+    self.file.flush()
+  }
+}
+
+// The caption of a spoken phrase, once all its audio was taken by playback.
+fn placed(spoken: &Spoken) -> Option<Caption> {
+  let mut timeline = TIMELINE.lock().unwrap_or_else(PoisonError::into_inner);
+  let timeline = timeline.as_mut()?;
+  if timeline.played_us < spoken.end_us {
+    return None;
+  }
+  // the chunks before the phrase belong to earlier ones
+  while timeline
+    .marks
+    .get(1)
+    .is_some_and(|(played, _)| *played <= spoken.start_us)
+  {
+    timeline.marks.pop_front();
+  }
+  let start = timeline
+    .marks
+    .front()
+    .map_or(timeline.session_us, |(played, session)| {
+      session + spoken.start_us.saturating_sub(*played)
+    });
+  // its last chunk, followed by the gap of the recording
+  let end = timeline
+    .marks
+    .iter()
+    .take_while(|(played, _)| *played < spoken.end_us)
+    .last()
+    .map_or(start, |(played, session)| {
+      session + (spoken.end_us - played)
+    });
+  Some(Caption {
+    speaker: spoken.speaker.clone(),
+    start_ms: start / 1000,
+    end_ms: end.max(start) / 1000,
+    text: spoken.text.clone(),
+  })
+}
+
+// The span of the last utterance, or for a typed message a guess from its
+// length, at the end of the session audio.
+fn utterance_span(text: &str) -> (u64, u64) {
+  let mut timeline = TIMELINE.lock().unwrap_or_else(PoisonError::into_inner);
+  let Some(timeline) = timeline.as_mut() else {
+    return (0, 0);
+  };
+  match timeline.utterance.take() {
+    Some((start, end)) => (start / 1000, end / 1000),
+    None => {
+      let start = timeline.session_us / 1000;
+      (start, start + spoken_ms(text))
+    }
+  }
+}
+
+fn duration_us(chunk: &AudioChunk) -> u64 {
+  let frames = chunk.data.len() as u64 / chunk.channels.max(1) as u64;
+  frames * 1_000_000 / chunk.sample_rate.max(1) as u64
+}
+
+fn spoken_ms(text: &str) -> u64 {
+  (text.split_whitespace().count() as u64 * MS_PER_WORD).max(MIN_CAPTION_MS)
+}
+
+fn agent_name() -> String {
+  GLOBAL_STATE
+    .get()
+    .map(|s| {
+      s.agent_name
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+    })
+    .unwrap_or_else(|| "Assistant".to_string())
+}
@@ -0,0 +1,167 @@
+// ------------------------------------------------------------------
+//  Saved replies ("save that")
+// ------------------------------------------------------------------
+//
+// The voice of the last reply is kept as it is synthesized, phrase by
+// phrase. Saying "save that" writes it to ~/.vtmate/replies as a .wav and
+// a .srt of its phrases timed on that audio, named after the time of the
+// save, to keep a spoken explanation as an audio note. Both are encrypted
+// with --encrypt.
+
+use crate::audio::AudioChunk;
+use crate::cancel::CancellationToken;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+const PHRASES: [&str; 6] = [
+  "save that",
+  "save it",
+  "keep that",
+  "save that reply",
+  "save this",
+  "save that answer",
+];
+
+// the last reply, replaced once the next one is synthesized
+static LAST: Mutex<Option<Reply>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+/// Whether `text` asks to save the last reply.
+pub fn request(text: &str) -> bool {
+  let words: String = text
+    .to_lowercase()
+    .chars()
+    .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+    .collect();
+  let words = words.split_whitespace().collect::<Vec<_>>().join(" ");
+  PHRASES.contains(&words.as_str())
+}
+
+/// A phrase of the turn `cancel` belongs to is about to be synthesized, the
+/// first one of a turn starts a new reply.
+pub fn phrase_started(cancel: &CancellationToken) {
+  let mut last = LAST.lock().unwrap_or_else(PoisonError::into_inner);
+  if last.as_ref().is_some_and(|r| r.turn.same_as(cancel)) {
+    return;
+  }
+  *last = Some(Reply {
+    turn: cancel.clone(),
+    audio: Vec::new(),
+    channels: 0,
+    sample_rate: 0,
+    phrase_start: 0,
+    phrases: Vec::new(),
+  });
+}
+
+/// A chunk of the assistant voice, as synthesized.
+pub fn voice(chunk: &AudioChunk) {
+  let mut last = LAST.lock().unwrap_or_else(PoisonError::into_inner);
+  let Some(reply) = last.as_mut() else {
+    return;
+  };
+  if reply.audio.is_empty() {
+    reply.channels = chunk.channels;
+    reply.sample_rate = chunk.sample_rate;
+  }
+  // the backends all resample to the output rate, a change is a bug
+  if (chunk.channels, chunk.sample_rate) == (reply.channels, reply.sample_rate) {
+    reply.audio.extend_from_slice(&chunk.data);
+  }
+}
+
+/// The phrase whose audio came since the previous one.
+pub fn phrase_spoken(text: &str) {
+  if let Some(reply) = LAST.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+    let end = reply.audio.len();
+    let start = std::mem::replace(&mut reply.phrase_start, end);
+    reply.phrases.push((text.trim().to_string(), start, end));
+  }
+}
+
+/// Writes the last reply, returns the path of its .wav.
+pub fn save() -> Result<PathBuf, String> {
+  let last = LAST.lock().unwrap_or_else(PoisonError::into_inner);
+  let reply = last
+    .as_ref()
+    .filter(|r| !r.audio.is_empty())
+    .ok_or("There is no spoken reply to save")?;
+  let home = crate::util::get_user_home_path().ok_or("Unable to determine home directory")?;
+  let dir = home.join(".vtmate").join("replies");
+  std::fs::create_dir_all(&dir)
+    .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+  let name = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+  let wav_path = dir.join(format!("{}.wav", name));
+  let srt_path = dir.join(format!("{}.srt", name));
+
+  let spec = hound::WavSpec {
+    channels: reply.channels,
+    sample_rate: reply.sample_rate,
+    bits_per_sample: 16,
+    sample_format: hound::SampleFormat::Int,
+  };
+  let mut wav = std::io::Cursor::new(Vec::new());
+  let mut write_wav = || -> Result<(), hound::Error> {
+    let mut writer = hound::WavWriter::new(&mut wav, spec)?;
+    for sample in crate::audio::f32_to_i16(&reply.audio) {
+      writer.write_sample(sample)?;
+    }
+    writer.finalize()
+  };
+  write_wav().map_err(|e| format!("Failed to write {}: {}", wav_path.display(), e))?;
+  // encrypted with --encrypt, like the saved conversations
+  crate::encryption::write_file(&wav_path, wav.get_ref())
+    .map_err(|e| format!("Failed to write {}: {}", wav_path.display(), e))?;
+  crate::encryption::write_file(&srt_path, reply.srt().as_bytes())
+    .map_err(|e| format!("Failed to write {}: {}", srt_path.display(), e))?;
+  let wav_path = if crate::encryption::is_enabled() {
+    crate::encryption::encrypted_path(&wav_path)
+  } else {
+    wav_path
+  };
+  crate::log::log(
+    "info",
+    &format!("Saved the last reply to {}", wav_path.display()),
+  );
+  Ok(wav_path)
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+struct Reply {
+  // the turn it was spoken in
+  turn: CancellationToken,
+  audio: Vec<f32>,
+  channels: u16,
+  sample_rate: u32,
+  // where the audio of the next phrase starts
+  phrase_start: usize,
+  // the phrases and their audio (sample offsets)
+  phrases: Vec<(String, usize, usize)>,
+}
+
+impl Reply {
+  fn srt(&self) -> String {
+    let per_second = (self.sample_rate as u64 * self.channels as u64).max(1);
+    let ms = |samples: usize| samples as u64 * 1000 / per_second;
+    self
+      .phrases
+      .iter()
+      .filter(|(text, start, end)| !text.is_empty() && end > start)
+      .enumerate()
+      .map(|(i, (text, start, end))| {
+        format!(
+          "{}\n{} --> {}\n{}\n\n",
+          i + 1,
+          crate::captions::timestamp(ms(*start), ','),
+          crate::captions::timestamp(ms(*end), ','),
+          crate::redact::redact(text)
+        )
+      })
+      .collect()
+  }
+}
@@ -3,10 +3,10 @@
 // ------------------------------------------------------------------
 
 use crate::tts;
-use crate::util::get_user_home_path;
 use crate::util::terminate;
+use crate::util::{get_data_dir, get_user_home_path};
 use anyhow::Error;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use cpal::Device;
 use cpal::traits::DeviceTrait;
 use serde::Deserialize;
@@ -37,6 +37,51 @@ pub struct AgentSettings {
   pub sound_threshold_peak: f32,
   pub end_silence_ms: u64,
   pub voice_speed: f32,
+  #[serde(default)]
+  pub shell_commands: String,
+  #[serde(default)]
+  pub barge_in_ack: String,
+  #[serde(default)]
+  pub wake_phrases: String,
+  #[serde(default)]
+  pub stop_phrases: String,
+  #[serde(default, deserialize_with = "bool_from_str_or_bool")]
+  pub follow_up_questions: bool,
+}
+
+/// A character the agent can take on ("switch to the chef"): its own prompt
+/// and voice over the llm and the rest of the current agent.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Persona {
+  pub name: String,
+  pub system_prompt: String,
+  pub language: String,
+  pub tts: String,
+  pub voice: String,
+  pub voice_speed: f32,
+}
+
+impl Persona {
+  /// `agent` speaking as this persona.
+  pub fn over(&self, agent: &AgentSettings) -> AgentSettings {
+    AgentSettings {
+      system_prompt: self.system_prompt.clone(),
+      language: self.language.clone(),
+      tts: self.tts.clone(),
+      voice: self.voice.clone(),
+      voice_speed: self.voice_speed,
+      ..agent.clone()
+    }
+  }
+}
+
+/// A stored prompt sent instead of what was said when it matches the
+/// trigger ("run my standup prompt").
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Snippet {
+  // phrases separated by ';'
+  pub trigger: String,
+  pub prompt: String,
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -108,10 +153,70 @@ Explanation on the fields:
                           pushed while speaking, then release.
   ------------------------------------------------------------
   * whisper_model_path:   the path to the whisper model.
-                          vtmate unzips 2 models in
-                          ~/.whisper-models, tiny and small.
+                          vtmate downloads 2 models in
+                          <data dir>/whisper, ggml-tiny.bin
+                          and ggml-small.bin (see
+                          `vtmate assets --help`), a bare
+                          file name refers to that folder.
                           You can download bigger models and
                           point to them here
+  ------------------------------------------------------------
+  * shell_commands:       (optional) commands the agent may
+                          run, as "name: command" separated
+                          by ';'. example:
+
+                            "disk space: df -h; backup status: systemctl status backup"
+
+                          The llm can ask for them, or say
+                          "run disk space". They only run
+                          after you answer "yes".
+  ------------------------------------------------------------
+  * barge_in_ack:         (optional) a short phrase like
+                          "okay" said when you talk over a
+                          reply, once your new utterance is
+                          heard, before the answer to it
+  ------------------------------------------------------------
+  * wake_phrases:         (optional) phrases separated by ';'
+                          one of which has to start what you
+                          say for the agent to listen, e.g.
+                          "computer; hey mate". It listens
+                          then until a stop phrase
+  ------------------------------------------------------------
+  * stop_phrases:         (optional) phrases separated by ';'
+                          that end the listening started by a
+                          wake phrase (default: "that's all")
+  ------------------------------------------------------------
+  * follow_up_questions:  (optional) true to have the agent
+                          ask a clarifying question when a
+                          request is ambiguous. The answer to
+                          it needs no wake phrase
+
+Personas:
+
+  [persona] sections define characters the agent can take on,
+  each with name, system_prompt, language, tts, voice and
+  voice_speed as above. example:
+
+    [persona]
+    name = chef
+    system_prompt = "You are a french chef. Answer briefly."
+    language = en
+    tts = kokoro
+    voice = bm_daniel
+    voice_speed = 1.2
+
+  Say "switch to the chef" to talk to it, with the llm of the
+  current agent, and "switch to <agent name>" to go back.
+
+Snippets:
+
+  [snippet] sections store long prompts sent instead of a
+  short trigger phrase (phrases separated by ';'). Words said
+  after the trigger are added to the prompt. example:
+
+    [snippet]
+    trigger = "run my standup prompt; standup"
+    prompt = "Summarize what I did yesterday, what I plan today\nand what blocks me, as three short bullet lists."
 
 "#)]
 pub struct Args {
@@ -138,6 +243,13 @@ pub struct Args {
   #[arg(long, action=clap::ArgAction::SetTrue, help = "list all voices for all languages and tts systems")]
   pub list_voices: bool,
 
+  #[arg(
+    long = "mic-test",
+    action = clap::ArgAction::SetTrue,
+    help = "record the room and your voice, play it back and recommend a sound_threshold_peak"
+  )]
+  pub mic_test: bool,
+
   #[arg(
     short = 'c',
     long = "config",
@@ -172,13 +284,842 @@ pub struct Args {
 
   #[arg(short = 's', long = "save", action = clap::ArgAction::SetTrue, help = "save the conversation to text and audio file in ~/.vtmate/conversations")]
   pub save: bool,
+
+  #[arg(
+    long = "punctuate",
+    action = clap::ArgAction::SetTrue,
+    help = "punctuate what you said in the saved conversations with a short llm call (the llm still gets the raw transcript)"
+  )]
+  pub punctuate: bool,
+
+  #[arg(
+    long = "data-dir",
+    value_name = "DIR",
+    env = "VTMATE_DATA_DIR",
+    global = true,
+    help = "directory for the downloaded models and espeak data (default: $XDG_DATA_HOME/vtmate)"
+  )]
+  pub data_dir: Option<String>,
+
+  #[arg(
+    long = "asset-mirror",
+    value_name = "URL",
+    env = "VTMATE_ASSET_MIRROR",
+    value_delimiter = ',',
+    global = true,
+    help = "base url to download model files from before the default sources (as <URL>/<file name>), can be repeated"
+  )]
+  pub asset_mirror: Vec<String>,
+
+  #[arg(
+    long = "third-party-mirrors",
+    action = clap::ArgAction::SetTrue,
+    env = "VTMATE_THIRD_PARTY_MIRRORS",
+    global = true,
+    help = "when a model file can't be downloaded from its source, try the known third-party copies of it (hf-mirror.com, kokoro-tiny); the checksums are verified all the same"
+  )]
+  pub third_party_mirrors: bool,
+
+  #[arg(long, action = clap::ArgAction::SetTrue, global = true, help = "never download model files, fail listing the missing ones instead")]
+  pub offline: bool,
+
+  #[arg(long = "auto-download", action = clap::ArgAction::SetTrue, global = true, help = "download a whisper model fitting this machine without asking when the configured one is missing")]
+  pub auto_download: bool,
+
+  #[arg(
+    long = "audio-buffer-frames",
+    value_name = "FRAMES",
+    default_value_t = AUDIO_BUFFER_FRAMES_DEFAULT,
+    global = true,
+    help = "output buffer size asked to the audio device, smaller speaks sooner (0 keeps the device default, which can add 100ms+)"
+  )]
+  pub audio_buffer_frames: u32,
+
+  #[arg(
+    long = "secondary-output",
+    value_name = "DEVICE",
+    help = "also play the voice on this output device (a part of its name), e.g. a speaker in another room"
+  )]
+  pub secondary_output: Option<String>,
+
+  #[arg(
+    long = "input-channel",
+    value_name = "N",
+    value_parser = clap::value_parser!(u16).range(1..),
+    help = "listen to channel N (from 1) of a multichannel input alone, e.g. the one XLR input in use on an audio interface, instead of all of them mixed"
+  )]
+  pub input_channel: Option<u16>,
+
+  #[arg(
+    long = "no-tts",
+    action = clap::ArgAction::SetTrue,
+    help = "answer in text only, without speaking (toggle with m during the session)"
+  )]
+  pub no_tts: bool,
+
+  #[arg(
+    long = "no-auto-pace",
+    action = clap::ArgAction::SetTrue,
+    help = "speak numbers, identifiers and code at the voice speed too, instead of slower than the prose"
+  )]
+  pub no_auto_pace: bool,
+
+  #[arg(
+    long = "voice-styles",
+    action = clap::ArgAction::SetTrue,
+    help = "let the llm set how a sentence is said with tags like [whisper] or [excited], spoken and not shown"
+  )]
+  pub voice_styles: bool,
+
+  #[arg(
+    long = "double-check-numbers",
+    value_name = "HOW",
+    value_parser = ["repeat", "spell"],
+    help = "after a reply with dates, amounts or long numbers, say them again: as written (repeat) or digit by digit (spell)"
+  )]
+  pub double_check_numbers: Option<String>,
+
+  #[arg(
+    long = "max-spoken-seconds",
+    value_name = "SECONDS",
+    value_parser = clap::value_parser!(u64).range(5..),
+    help = "stop speaking a reply after about SECONDS and ask \"Shall I go on?\": yes speaks the rest, anything else drops it"
+  )]
+  pub max_spoken_seconds: Option<u64>,
+
+  #[arg(
+    long = "tts-threads",
+    value_name = "N",
+    default_value_t = 0,
+    global = true,
+    help = "threads used by the kokoro voice synthesis (0 lets onnx runtime pick, one per core)"
+  )]
+  pub tts_threads: usize,
+
+  #[arg(
+    long = "tts-ep",
+    value_name = "PROVIDER",
+    default_value = "cpu",
+    value_parser = ["cpu", "coreml", "cuda"],
+    global = true,
+    help = "onnx runtime execution provider of the kokoro voice synthesis (coreml and cuda need a build with that feature)"
+  )]
+  pub tts_ep: String,
+
+  #[arg(
+    long = "utterance-queue",
+    value_name = "N",
+    default_value_t = UTTERANCE_QUEUE_DEFAULT,
+    value_parser = clap::value_parser!(u32).range(1..),
+    help = "utterances waiting to be transcribed before new ones are dropped (the microphone never waits)"
+  )]
+  pub utterance_queue: u32,
+
+  #[arg(
+    long = "playback-queue",
+    value_name = "N",
+    default_value_t = PLAYBACK_QUEUE_DEFAULT,
+    value_parser = clap::value_parser!(u32).range(1..),
+    help = "synthesized audio chunks waiting for playback, the tts waits when it's full"
+  )]
+  pub playback_queue: u32,
+
+  #[arg(
+    long = "interrupt-granularity",
+    value_name = "WHEN",
+    default_value = "instant",
+    value_parser = ["instant", "word", "phrase"],
+    help = "where an interruption cuts the voice: right away, at the end of the current word or of the current phrase"
+  )]
+  pub interrupt_granularity: String,
+
+  #[arg(
+    long = "barge-in-interrupts",
+    action = clap::ArgAction::SetTrue,
+    help = "speech over the reply interrupts it right away, instead of pausing it until a command like louder or go on lets it go on"
+  )]
+  pub barge_in_interrupts: bool,
+
+  #[arg(
+    long = "history-turns",
+    value_name = "N",
+    default_value_t = HISTORY_TURNS_DEFAULT,
+    value_parser = clap::value_parser!(u32).range(1..),
+    help = "turns of the conversation sent to the llm, the oldest are left out (saved conversations keep them)"
+  )]
+  pub history_turns: u32,
+
+  #[arg(
+    long = "history-tokens",
+    value_name = "N",
+    default_value_t = HISTORY_TOKENS_DEFAULT,
+    value_parser = clap::value_parser!(u32).range(1..),
+    help = "approximate size of the conversation sent to the llm, the oldest turns are left out"
+  )]
+  pub history_tokens: u32,
+
+  #[arg(
+    long,
+    value_name = "FILE",
+    help = "write live captions of the conversation to FILE: subtitles for .srt / .vtt, otherwise the last lines as plain text (for OBS text sources)"
+  )]
+  pub captions: Option<String>,
+
+  #[arg(
+    long,
+    value_name = "PATH",
+    help = "write the replies to PATH (a file or a named pipe) as they stream, for other tools to show them live"
+  )]
+  pub mirror: Option<String>,
+
+  #[arg(
+    long,
+    action = clap::ArgAction::SetTrue,
+    help = "show a desktop notification with the first line of the answers completed while the terminal is unfocused or recording is paused"
+  )]
+  pub notify: bool,
+
+  #[arg(
+    long = "media-keys",
+    action = clap::ArgAction::SetTrue,
+    help = "show up as a media player (MPRIS) so the media keys pause, resume and stop the voice (mpris feature, Linux)"
+  )]
+  pub media_keys: bool,
+
+  #[arg(
+    long = "duck",
+    value_name = "PERCENT",
+    num_args = 0..=1,
+    default_missing_value = "20",
+    value_parser = clap::value_parser!(u8).range(0..=100),
+    help = "turn the other audio (music, videos) down to PERCENT of its volume while the agent speaks (default: 20)"
+  )]
+  pub duck: Option<u8>,
+
+  #[arg(
+    long = "mic-gain",
+    value_name = "GAIN",
+    default_value_t = 1.0,
+    value_parser = parse_mic_gain,
+    help = "multiply the microphone input by GAIN (0.1 to 20) before detecting speech, for quiet mics. Adjusted with the + and - keys"
+  )]
+  pub mic_gain: f32,
+
+  #[arg(
+    long = "earcons",
+    action = clap::ArgAction::SetTrue,
+    help = "short cue sounds when listening starts, an utterance is accepted and on errors"
+  )]
+  pub earcons: bool,
+
+  #[arg(
+    long = "user-label",
+    value_name = "TEXT",
+    default_value = "USER",
+    global = true,
+    help = "label of your turns, on screen and in the saved conversations"
+  )]
+  pub user_label: String,
+
+  #[arg(
+    long = "assistant-label",
+    value_name = "TEXT",
+    default_value = "ASSISTANT",
+    global = true,
+    help = "label of the replies, on screen and in the saved conversations (the agent name in debates)"
+  )]
+  pub assistant_label: String,
+
+  #[arg(
+    long = "user-color",
+    value_name = "BG[/FG]",
+    default_value = "white/black",
+    value_parser = parse_label_color,
+    global = true,
+    help = "colors of the user label: a color name (black, red, green, yellow, blue, magenta, cyan, white, grey, none) or a 0-255 terminal color each"
+  )]
+  pub user_color: String,
+
+  #[arg(
+    long = "assistant-color",
+    value_name = "BG[/FG]",
+    default_value = "22/white",
+    value_parser = parse_label_color,
+    global = true,
+    help = "colors of the assistant label, as --user-color"
+  )]
+  pub assistant_color: String,
+
+  #[arg(
+    long = "timestamps",
+    action = clap::ArgAction::SetTrue,
+    global = true,
+    help = "show the time of every turn before its label"
+  )]
+  pub timestamps: bool,
+
+  #[arg(
+    long = "follow-up-window",
+    value_name = "SECONDS",
+    value_parser = clap::value_parser!(u64).range(1..),
+    help = "once a reply is played out, show \"listening\" with a countdown of SECONDS to follow up in (with a chime when --earcons)"
+  )]
+  pub follow_up_window: Option<u64>,
+
+  #[arg(
+    long = "status-led",
+    value_name = "[CHIP:]LINE",
+    value_parser = validate_status_led,
+    help = "drive a led on this gpio line (e.g. 17 or gpiochip4:17) with the state: on while listening, blinking slowly while thinking and fast while speaking, off when paused (gpio feature, Linux)"
+  )]
+  pub status_led: Option<String>,
+
+  #[arg(
+    long = "status-display",
+    value_name = "BUS[:ADDR[:ROWS]]",
+    value_parser = validate_status_display,
+    help = "show the state on a 128x64 (or x32) SSD1306 oled display on this i2c bus, e.g. 1 or 1:0x3d:32 (gpio feature, Linux)"
+  )]
+  pub status_display: Option<String>,
+
+  #[arg(
+    long = "low-power",
+    action = clap::ArgAction::SetTrue,
+    help = "save battery: slower ui refresh and polling, and once idle (--low-power-idle) kokoro is unloaded and the tiny whisper model transcribes until something happens"
+  )]
+  pub low_power: bool,
+
+  #[arg(
+    long = "low-power-idle",
+    value_name = "SECONDS",
+    default_value_t = LOW_POWER_IDLE_SECS_DEFAULT,
+    value_parser = clap::value_parser!(u64).range(1..),
+    requires = "low_power",
+    help = "seconds without activity before the models are unloaded (with --low-power)"
+  )]
+  pub low_power_idle: u64,
+
+  #[arg(
+    long = "rapid-silence",
+    value_name = "MS",
+    default_value_t = RAPID_SILENCE_MS_DEFAULT,
+    value_parser = clap::value_parser!(u64).range(1..=20000),
+    help = "milliseconds of silence ending an utterance in rapid mode (r key), when shorter than end_silence_ms"
+  )]
+  pub rapid_silence: u64,
+
+  #[arg(
+    long = "rapid-idle",
+    value_name = "SECONDS",
+    default_value_t = RAPID_IDLE_SECS_DEFAULT,
+    value_parser = clap::value_parser!(u64).range(1..),
+    help = "seconds without an exchange before rapid mode turns itself off"
+  )]
+  pub rapid_idle: u64,
+
+  #[arg(
+    long = "fast-stt",
+    action = clap::ArgAction::SetTrue,
+    help = "start in fast mode (f key): the utterances are transcribed with the tiny whisper model instead of the model of the agent"
+  )]
+  pub fast_stt: bool,
+
+  #[arg(
+    long = "latency-target-ms",
+    value_name = "MS",
+    value_parser = clap::value_parser!(u64).range(LATENCY_TARGET_MS_MIN..=20000),
+    help = "aim for replies starting this many milliseconds after you stop talking: picks a smaller whisper model, a shorter end_silence_ms and shorter first phrases when needed, the latency reached is shown in the status bar"
+  )]
+  pub latency_target_ms: Option<u64>,
+
+  #[arg(
+    long = "dictate",
+    action = clap::ArgAction::SetTrue,
+    help = "write down what is said instead of answering it, with spoken punctuation (\"comma\", \"period\", \"new line\", ...). Printed to stdout with --headless"
+  )]
+  pub dictate: bool,
+
+  #[arg(
+    long = "dictate-output",
+    value_name = "FILE",
+    requires = "dictate",
+    help = "also append the dictated text to FILE"
+  )]
+  pub dictate_output: Option<String>,
+
+  #[arg(
+    long = "dictate-type",
+    action = clap::ArgAction::SetTrue,
+    requires = "dictate",
+    help = "also type the dictated text into the focused window (xdotool or wtype on Linux, System Events on macOS, SendKeys on Windows)"
+  )]
+  pub dictate_type: bool,
+
+  #[arg(
+    long = "meeting",
+    action = clap::ArgAction::SetTrue,
+    conflicts_with = "dictate",
+    help = "take meeting notes: what is said is noted with its time instead of answered, and summarized with the action items every --meeting-summary minutes. Say \"summary\" to hear it. Written to ~/.vtmate/meetings"
+  )]
+  pub meeting: bool,
+
+  #[arg(
+    long = "meeting-summary",
+    value_name = "MINUTES",
+    default_value_t = MEETING_SUMMARY_MINUTES_DEFAULT,
+    value_parser = clap::value_parser!(u64).range(1..),
+    requires = "meeting",
+    help = "minutes between the updates of the meeting summary (with --meeting)"
+  )]
+  pub meeting_summary: u64,
+
+  #[arg(
+    long = "quiet-hours",
+    value_name = "HH:MM-HH:MM",
+    env = "VTMATE_QUIET_HOURS",
+    value_parser = validate_quiet_hours,
+    help = "do not disturb every day in this time range (e.g. 22:00-07:00): the microphone is off and notifications, cues and announcements wait for the end of it. `z` toggles it any time"
+  )]
+  pub quiet_hours: Option<String>,
+
+  #[arg(
+    long = "speaker-verify",
+    value_name = "MODE",
+    value_parser = ["ignore", "tag"],
+    help = "check utterances against the voiceprint recorded by `vtmate enroll`: ignore the ones of other speakers (TV, other people), or tag them as such for the llm"
+  )]
+  pub speaker_verify: Option<String>,
+
+  #[arg(
+    long = "speaker-threshold",
+    value_name = "DISTANCE",
+    requires = "speaker_verify",
+    help = "furthest an utterance may be from the voiceprint to be yours (default: found at enrollment, about 1.5). Higher lets more through"
+  )]
+  pub speaker_threshold: Option<f32>,
+
+  #[arg(
+    long = "redact",
+    action = clap::ArgAction::SetTrue,
+    help = "mask emails, phone numbers and credit card numbers in the saved conversations, captions and logs"
+  )]
+  pub redact: bool,
+
+  #[arg(
+    long = "redact-pattern",
+    value_name = "REGEX",
+    value_parser = validate_regex,
+    help = "also mask the text matching REGEX (implies --redact), can be repeated"
+  )]
+  pub redact_pattern: Vec<String>,
+
+  #[arg(
+    long = "encrypt",
+    action = clap::ArgAction::SetTrue,
+    requires = "save",
+    help = "encrypt the saved conversations (text and audio), with --encrypt-key-file or the passphrase in VTMATE_PASSPHRASE"
+  )]
+  pub encrypt: bool,
+
+  #[arg(
+    long = "encrypt-key-file",
+    value_name = "PATH",
+    env = "VTMATE_ENCRYPT_KEY_FILE",
+    global = true,
+    help = "key of the encrypted conversations (any file, e.g. 32 random bytes) instead of a passphrase"
+  )]
+  pub encrypt_key_file: Option<String>,
+
+  #[arg(
+    long = "telemetry",
+    action = clap::ArgAction::SetTrue,
+    help = "count which features are used and the kinds of errors (never what is said) in ~/.vtmate/telemetry.json. Nothing is sent: share it with `vtmate telemetry export`"
+  )]
+  pub telemetry: bool,
+
+  #[arg(
+    long,
+    value_name = "URL",
+    env = "OTEL_EXPORTER_OTLP_ENDPOINT",
+    help = "export a trace of every turn (stt, llm, tts and playback spans) to the OpenTelemetry collector at URL, over OTLP/HTTP (e.g. http://localhost:4318)"
+  )]
+  pub otlp_endpoint: Option<String>,
+
+  #[arg(
+    long,
+    value_name = "PORT",
+    help = "serve a REST API to control this session (/status, /say, /ask, /pause, /transcript). A bare port listens on localhost, use HOST:PORT otherwise"
+  )]
+  pub serve: Option<String>,
+
+  #[arg(
+    long = "serve-origin",
+    value_name = "ORIGIN",
+    value_delimiter = ',',
+    help = "web page origin (e.g. https://dash.example.com) allowed to use --serve besides those of this machine, can be repeated"
+  )]
+  pub serve_origin: Vec<String>,
+
+  #[arg(
+    long,
+    value_name = "PORT",
+    help = "run as a Wyoming server (speech to text, text to speech and the agent as intent handler) for Home Assistant, instead of the terminal conversation. A bare port listens on localhost, use HOST:PORT otherwise"
+  )]
+  pub wyoming: Option<String>,
+
+  #[arg(
+    long,
+    value_name = "PORT",
+    conflicts_with = "wyoming",
+    help = "let satellite instances (--satellite) on the network use this one as their central: their utterances are answered here and the replies are spoken back on them. A bare port listens on localhost, use 0.0.0.0:PORT for the LAN"
+  )]
+  pub intercom: Option<String>,
+
+  #[arg(
+    long,
+    value_name = "HOST:PORT",
+    conflicts_with_all = ["intercom", "wyoming", "read_file", "quiet"],
+    help = "run as a satellite of the central instance at HOST:PORT (--intercom): this microphone and speaker are used, the transcription and the llm are the central's"
+  )]
+  pub satellite: Option<String>,
+
+  #[arg(
+    long = "intercom-secret",
+    value_name = "SECRET",
+    help = "shared secret of the intercom: the central (--intercom) only accepts the satellites (--satellite) that give the same one"
+  )]
+  pub intercom_secret: Option<String>,
+
+  #[arg(
+    long = "control-socket",
+    value_name = "PATH",
+    env = "VTMATE_CONTROL_SOCKET",
+    global = true,
+    help = "unix socket to control this session from `vtmate ctl` (default: $XDG_RUNTIME_DIR/vtmate.sock)"
+  )]
+  pub control_socket: Option<String>,
+
+  #[arg(
+    long = "stdio-protocol",
+    action = clap::ArgAction::SetTrue,
+    conflicts_with_all = ["read_file", "quiet", "wyoming"],
+    help = "no terminal ui: read JSON commands from stdin and write the turn events as JSON lines to stdout, to embed vtmate in other programs"
+  )]
+  pub stdio_protocol: bool,
+
+  #[arg(
+    long = "daemon",
+    action = clap::ArgAction::SetTrue,
+    conflicts_with_all = ["read_file", "quiet", "wyoming", "stdio_protocol"],
+    help = "no terminal ui: keep the session and its models running in the background, open a terminal on it with `vtmate attach`"
+  )]
+  pub daemon: bool,
+
+  #[arg(
+    long = "headless",
+    action = clap::ArgAction::SetTrue,
+    conflicts_with_all = ["read_file", "stdio_protocol"],
+    help = "no terminal ui or keyboard: turns and logs are written to stderr as plain lines (for systemd, pair with the control socket)"
+  )]
+  pub headless: bool,
+
+  #[arg(
+    long = "service",
+    action = clap::ArgAction::SetTrue,
+    conflicts_with_all = ["read_file", "stdio_protocol"],
+    help = "run as a systemd service (Type=notify): --headless, readiness once the models are loaded and watchdog pings (WatchdogSec=)"
+  )]
+  pub service: bool,
+
+  #[arg(
+    long = "bridge",
+    value_name = "CHAT",
+    value_parser = validate_bridge,
+    requires = "bridge_user",
+    help = "answer the messages of a chat with this session's conversation: telegram:CHAT_ID or discord:CHANNEL_ID (bot tokens in VTMATE_TELEGRAM_TOKEN / VTMATE_DISCORD_TOKEN). Repeat for several chats"
+  )]
+  pub bridge: Vec<String>,
+
+  #[arg(
+    long = "bridge-user",
+    value_name = "ID",
+    value_delimiter = ',',
+    help = "user id (telegram or discord) whose messages the --bridge chats answer, the other members are ignored. Repeat for several"
+  )]
+  pub bridge_user: Vec<String>,
+
+  #[arg(
+    long = "context",
+    value_name = "NAME[:AGENT]",
+    help = "a separate conversation with its own history (and agent), switched to with x or by saying \"switch to the NAME context\". Repeat for several, the session starts in the first one"
+  )]
+  pub context: Vec<String>,
+
+  #[arg(
+    long = "bridge-voice",
+    action = clap::ArgAction::SetTrue,
+    help = "send the replies to --bridge chats as voice notes too"
+  )]
+  pub bridge_voice: bool,
+
+  #[arg(
+    long = "plugins-dir",
+    value_name = "DIR",
+    env = "VTMATE_PLUGINS_DIR",
+    help = "directory of the plugins to start (default: ~/.vtmate/plugins)"
+  )]
+  pub plugins_dir: Option<String>,
+
+  #[command(subcommand)]
+  pub command: Option<CliCommand>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CliCommand {
+  /// Manage the model files (whisper, kokoro, supersonic2) downloaded on first use
+  Assets {
+    #[command(subcommand)]
+    action: AssetsAction,
+  },
+  /// Control a running vtmate through its control socket
+  Ctl {
+    #[command(subcommand)]
+    action: CtlAction,
+  },
+  /// Open a terminal on a vtmate running with --daemon (q detaches, it keeps running)
+  Attach,
+  /// Speak a text (or stdin) with the agent's voice and exit
+  Say {
+    #[arg(value_name = "TEXT")]
+    text: Vec<String>,
+    #[arg(
+      short = 'o',
+      long = "output",
+      value_name = "FILE",
+      help = "write a WAV file instead of playing it"
+    )]
+    output: Option<String>,
+  },
+  /// Record your voiceprint for --speaker-verify, from the microphone or WAV files
+  Enroll {
+    #[arg(
+      value_name = "FILE",
+      help = "WAV files of your voice (default: record from the microphone)"
+    )]
+    files: Vec<String>,
+    #[arg(
+      long = "seconds",
+      value_name = "N",
+      default_value_t = 20,
+      help = "how long to record from the microphone"
+    )]
+    seconds: u64,
+  },
+  /// Decrypt a conversation saved with --encrypt, to stdout or a file
+  Decrypt {
+    #[arg(value_name = "FILE")]
+    file: String,
+    #[arg(
+      short = 'o',
+      long = "output",
+      value_name = "FILE",
+      help = "write the decrypted conversation to a file instead of printing it"
+    )]
+    output: Option<String>,
+  },
+  /// Transcribe a WAV file (or stdin) with the agent's whisper model and exit
+  Transcribe {
+    #[arg(value_name = "FILE", default_value = "-")]
+    file: String,
+    #[arg(
+      short = 'o',
+      long = "output",
+      value_name = "FILE",
+      help = "write the transcript to a file instead of printing it"
+    )]
+    output: Option<String>,
+  },
+  /// Export or clear the usage counts recorded with --telemetry
+  Telemetry {
+    #[command(subcommand)]
+    action: TelemetryAction,
+  },
+  /// Time whisper, the llm and the tts of each agent on the same utterances
+  Bench {
+    #[arg(
+      value_name = "FILE",
+      help = "WAV files to use as utterances (default: a few phrases spoken by each agent's tts)"
+    )]
+    files: Vec<String>,
+    #[arg(
+      short = 'n',
+      long = "iterations",
+      value_name = "N",
+      default_value_t = 3,
+      help = "runs over the utterances, after a warm-up run"
+    )]
+    iterations: usize,
+    #[arg(
+      long = "agents",
+      value_name = "NAMES",
+      value_delimiter = ',',
+      help = "comma separated agents to compare (default: all of them)"
+    )]
+    agents: Vec<String>,
+    #[arg(long = "skip-llm", action = clap::ArgAction::SetTrue, help = "speak the transcript instead of asking the llm")]
+    skip_llm: bool,
+  },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum CtlAction {
+  /// Print the current agent and activity
+  Status,
+  /// Stop listening
+  Pause,
+  /// Listen again
+  Resume,
+  /// Stop the current reply
+  Interrupt,
+  /// Speak a text as is
+  Say {
+    #[arg(value_name = "TEXT", required = true)]
+    text: Vec<String>,
+    #[arg(
+      long = "priority",
+      value_name = "PRIORITY",
+      default_value = "normal",
+      value_parser = ["normal", "resume", "important"],
+      help = "what talking over it does: normal stops it, resume says it again once you are done, important can't be stopped by voice (only by a key or `vtmate ctl interrupt`) and goes first"
+    )]
+    priority: String,
+  },
+  /// Answer a text as if it had been said
+  Ask {
+    #[arg(value_name = "TEXT", required = true)]
+    text: Vec<String>,
+  },
+  /// Play an earcon (listening, accepted, error, timer or turn), with --earcons
+  Cue {
+    #[arg(value_name = "NAME")]
+    name: String,
+  },
+  /// Turn do not disturb on or off (toggle without argument)
+  Dnd {
+    #[arg(value_name = "on|off", value_parser = ["on", "off"])]
+    state: Option<String>,
+  },
+  /// Speak with another tts backend (the next one without argument)
+  Tts {
+    #[arg(value_name = "BACKEND")]
+    backend: Option<String>,
+  },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum AssetsAction {
+  /// List the known assets and whether they are installed
+  List,
+  /// Download assets (all of them if no name is given), resuming partial downloads
+  Pull {
+    #[arg(value_name = "NAME")]
+    names: Vec<String>,
+  },
+  /// Check the SHA256 of installed assets
+  Verify {
+    #[arg(value_name = "NAME")]
+    names: Vec<String>,
+  },
+  /// Remove installed assets
+  Rm {
+    #[arg(value_name = "NAME", required = true)]
+    names: Vec<String>,
+  },
+  /// Remove the assets, kokoro voices and espeak languages no agent in the settings uses
+  Prune,
+  /// Manage the espeak-ng language data used by kokoro, one language at a time
+  Lang {
+    #[command(subcommand)]
+    action: LangAction,
+  },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum LangAction {
+  /// List the installed languages and the ones that can be added
+  List,
+  /// Install languages from the data built in, or from a compiled espeak-ng dictionary
+  Add {
+    #[arg(value_name = "LANG", required = true)]
+    langs: Vec<String>,
+    #[arg(
+      long = "from",
+      value_name = "PATH",
+      help = "a compiled espeak-ng dictionary (LANG_dict), or a directory holding it, for languages not built in"
+    )]
+    from: Option<String>,
+  },
+  /// Remove installed languages
+  Rm {
+    #[arg(value_name = "LANG", required = true)]
+    langs: Vec<String>,
+  },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum TelemetryAction {
+  /// Print the usage counts (JSON), to share them with the maintainers
+  Export {
+    #[arg(
+      short = 'o',
+      long = "output",
+      value_name = "FILE",
+      help = "write them to a file instead of printing them"
+    )]
+    output: Option<String>,
+  },
+  /// Remove the usage counts
+  Clear,
 }
 
 // internal static values
 pub const HANGOVER_MS_DEFAULT: u64 = 300;
+pub const AUDIO_BUFFER_FRAMES_DEFAULT: u32 = 1024;
+pub const UTTERANCE_QUEUE_DEFAULT: u32 = 4;
+pub const PLAYBACK_QUEUE_DEFAULT: u32 = 2;
+pub const LOW_POWER_IDLE_SECS_DEFAULT: u64 = 120;
+pub const RAPID_SILENCE_MS_DEFAULT: u64 = 700;
+pub const RAPID_IDLE_SECS_DEFAULT: u64 = 60;
+// the lowest --latency-target-ms, the end of speech alone takes most of it
+pub const LATENCY_TARGET_MS_MIN: u64 = 500;
+// under this target whisper models larger than tiny transcribe too slowly
+pub const LATENCY_TINY_WHISPER_MS: u64 = 3000;
+// the end of speech may take this share of the target, down to its floor
+pub const LATENCY_SILENCE_SHARE: f64 = 0.4;
+pub const LATENCY_SILENCE_MS_MIN: u64 = 300;
+pub const MEETING_SUMMARY_MINUTES_DEFAULT: u64 = 10;
+pub const HISTORY_TURNS_DEFAULT: u32 = 200;
+pub const HISTORY_TOKENS_DEFAULT: u32 = 32000;
 pub const MIN_UTTERANCE_MS_DEFAULT: u64 = 300;
+// speech over the playback must last this long to interrupt it
+pub const BARGE_IN_MS_DEFAULT: u64 = 250;
+// silence after which the utterance is transcribed ahead of end_silence_ms
+pub const PROBABLE_SILENCE_MS_DEFAULT: u64 = 350;
 pub const OPENTTS_BASE_URL_DEFAULT: &str = "http://127.0.0.1:5500/api/tts?&vocoder=high&denoiserStrength=0.005&&speakerId=&ssml=false&ssmlNumbers=true&ssmlDates=true&ssmlCurrency=true&cache=false";
 
+fn parse_mic_gain(value: &str) -> Result<f32, String> {
+  let gain: f32 = value.parse().map_err(|e| format!("{}", e))?;
+  if (0.1..=20.0).contains(&gain) {
+    Ok(gain)
+  } else {
+    Err("must be between 0.1 and 20".to_string())
+  }
+}
+
+fn parse_label_color(value: &str) -> Result<String, String> {
+  crate::ui::label_color(value)
+}
+
 fn bool_from_str_or_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
 where
   D: serde::de::Deserializer<'de>,
@@ -210,10 +1151,33 @@ where
 
 pub fn resolved_whisper_model_path(whisper_model_path: &str) -> String {
   let path = if whisper_model_path.is_empty() {
-    "~/.whisper-models/ggml-tiny.bin".to_string()
+    "ggml-tiny.bin".to_string()
   } else {
     whisper_model_path.to_string()
   };
+  // bare file names point to <data dir>/whisper
+  if !path.contains(['/', '\\'])
+    && let Some(data) = get_data_dir()
+  {
+    return data
+      .join("whisper")
+      .join(&path)
+      .to_string_lossy()
+      .into_owned();
+  }
+  // models moved from ~/.whisper-models to the data dir
+  if let (Some(name), Some(data), Some(home)) = (
+    path.strip_prefix("~/.whisper-models/"),
+    get_data_dir(),
+    get_user_home_path(),
+  ) && !home.join(".whisper-models").join(name).exists()
+  {
+    return data
+      .join("whisper")
+      .join(name)
+      .to_string_lossy()
+      .into_owned();
+  }
   if path.starts_with("~") {
     if let Some(home) = get_user_home_path() {
       let rel = path.trim_start_matches("~").trim_start_matches("/");
@@ -228,46 +1192,109 @@ pub fn resolved_whisper_model_path(whisper_model_path: &str) -> String {
   }
 }
 
+/// Gpio chip device and line offset of a "[CHIP:]LINE" led, the chip
+/// being /dev/gpiochip0 when left out.
+pub fn parse_status_led(spec: &str) -> Option<(String, u32)> {
+  let (chip, line) = match spec.rsplit_once(':') {
+    Some((chip, line)) if !chip.is_empty() => (chip, line),
+    Some(_) => return None,
+    None => ("gpiochip0", spec),
+  };
+  let chip = if chip.starts_with('/') {
+    chip.to_string()
+  } else {
+    format!("/dev/{}", chip)
+  };
+  Some((chip, line.trim().parse().ok()?))
+}
+
+/// I2c device, address and rows of a "BUS[:ADDR[:ROWS]]" display, 0x3c
+/// and 64 rows by default.
+pub fn parse_status_display(spec: &str) -> Option<(String, u16, u8)> {
+  let mut parts = spec.split(':');
+  let bus = parts.next()?.trim();
+  let bus = if bus.starts_with('/') {
+    bus.to_string()
+  } else {
+    format!("/dev/i2c-{}", bus.parse::<u32>().ok()?)
+  };
+  let address = match parts.next() {
+    Some(a) => u16::from_str_radix(a.trim().trim_start_matches("0x"), 16).ok()?,
+    None => 0x3c,
+  };
+  let rows = match parts.next() {
+    Some(r) => r.trim().parse().ok().filter(|r| [32, 64].contains(r))?,
+    None => 64,
+  };
+  parts.next().is_none().then_some((bus, address, rows))
+}
+
+/// Start and end (minutes after midnight) of a "HH:MM-HH:MM" range, the end
+/// being the next day when it comes before the start.
+pub fn parse_quiet_hours(range: &str) -> Option<(u32, u32)> {
+  let minutes = |time: &str| -> Option<u32> {
+    let (h, m) = time.trim().split_once(':')?;
+    let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+    (h < 24 && m < 60).then_some(h * 60 + m)
+  };
+  let (start, end) = range.split_once('-')?;
+  let (start, end) = (minutes(start)?, minutes(end)?);
+  (start != end).then_some((start, end))
+}
+
+/// The settings file given with -c (`~` expanded), or ~/.vtmate/settings.
+pub fn resolve_settings_path(args: &Args) -> Result<std::path::PathBuf, Error> {
+  if let Some(ref cfg) = args.config {
+    let mut path = std::path::PathBuf::from(cfg.as_str());
+    if path.starts_with("~")
+      && let Some(home) = get_user_home_path()
+    {
+      let rel = path.strip_prefix("~").unwrap_or(&path);
+      path = home.join(rel.to_str().unwrap_or(""));
+    }
+    Ok(path)
+  } else {
+    Ok(
+      get_user_home_path()
+        .ok_or_else(|| Error::msg("Unable to determine home directory"))?
+        .join(".vtmate")
+        .join("settings"),
+    )
+  }
+}
+
+/// The agent picked with -a (or the first one) from the settings file, for
+/// the subcommands that run a single agent.
+pub fn load_agent(args: &Args) -> Result<AgentSettings, String> {
+  let _ = ensure_settings_file();
+  let agents = resolve_settings_path(args)
+    .and_then(|path| load_settings(&path, args))
+    .map_err(|e| format!("Failed to load settings: {}", e))?;
+  match &args.agent {
+    Some(name) => agents
+      .iter()
+      .find(|a| a.name == *name)
+      .cloned()
+      .ok_or_else(|| format!("Agent '{}' not found", name)),
+    None => agents
+      .first()
+      .cloned()
+      .ok_or_else(|| "No agent configured".to_string()),
+  }
+}
+
 pub fn load_settings(
   settings_path: &std::path::Path,
   args: &Args,
 ) -> Result<Vec<AgentSettings>, Error> {
   // Read the whole INI file
   let ini_contents = read_to_string(settings_path)?;
-  // Split on the section header "[agent]"
-  let blocks: Vec<&str> = ini_contents
-    .split("[agent]")
-    .filter(|b| !b.trim().is_empty())
-    .collect();
+  let blocks = sections(&ini_contents, "agent");
 
   let mut agents = Vec::new();
   let mut errors: Vec<String> = Vec::new();
   for block in blocks {
-    // Preprocess the block to remove surrounding quotes from values
-    let mut clean_section = String::new();
-    // Track ptt value string if present
-    let _ptt_value_str: Option<String> = None;
-    for line in block.lines() {
-      if let Some(idx) = line.find('=') {
-        let (key, val_part) = line.split_at(idx);
-        // trim whitespace around key and value
-        let key = key.trim();
-        // val_part includes the '=' at start
-        let val = &val_part[1..].trim();
-        let val_trimmed = if val.starts_with('"') && val.ends_with('"') {
-          &val[1..val.len() - 1]
-        } else {
-          val
-        };
-        clean_section.push_str(key);
-        clean_section.push('=');
-        clean_section.push_str(val_trimmed);
-        clean_section.push('\n');
-      }
-      // skip lines without '=' (e.g., empty lines)
-    }
-
-    let section = clean_section.trim();
+    let section = block.as_str();
 
     // println!("DEBUG section: {}", section);
     // println!("DEBUG parsing section: {}", section);
@@ -387,11 +1414,130 @@ pub fn load_settings(
     if let Some(ptt_val) = args.ptt {
       agent.ptt = ptt_val;
     }
+    if let Some(target_ms) = args.latency_target_ms {
+      tune_for_latency(agent, target_ms);
+    }
   }
 
   Ok(agents)
 }
 
+/// Trades quality for responsiveness to reply within `target_ms` of the end
+/// of speech (--latency-target-ms): a shorter end_silence_ms and, under
+/// a few seconds, the tiny whisper model in place of a larger ggml one.
+/// Settings already fast enough are kept.
+pub fn tune_for_latency(agent: &mut AgentSettings, target_ms: u64) {
+  let silence_ms = ((target_ms as f64 * LATENCY_SILENCE_SHARE) as u64).max(LATENCY_SILENCE_MS_MIN);
+  agent.end_silence_ms = agent.end_silence_ms.min(silence_ms);
+  // the tiny model is the one always downloaded, other models are left alone
+  let name = std::path::Path::new(&agent.whisper_model_path)
+    .file_name()
+    .and_then(|n| n.to_str())
+    .unwrap_or("");
+  if target_ms < LATENCY_TINY_WHISPER_MS && name.starts_with("ggml-") && !name.contains("tiny") {
+    agent.whisper_model_path = "ggml-tiny.bin".to_string();
+  }
+}
+
+/// The agents, then the first one as each persona: all the voices the
+/// session may speak with, for the assets they need.
+pub fn with_personas(agents: &[AgentSettings], personas: &[Persona]) -> Vec<AgentSettings> {
+  let first = agents.first();
+  agents
+    .iter()
+    .cloned()
+    .chain(personas.iter().filter_map(|p| first.map(|a| p.over(a))))
+    .collect()
+}
+
+/// The [persona] sections of the settings file, none when it has no such
+/// section.
+pub fn load_personas(settings_path: &std::path::Path) -> Result<Vec<Persona>, Error> {
+  let ini_contents = read_to_string(settings_path)?;
+  let mut personas: Vec<Persona> = Vec::new();
+  let mut errors: Vec<String> = Vec::new();
+  for section in sections(&ini_contents, "persona") {
+    let mut persona: Persona = match panic::catch_unwind(|| from_str::<Persona>(&section)) {
+      Ok(Ok(p)) => p,
+      Ok(Err(e)) => {
+        print!("❌ Failed to parse persona's settings section: {}", e);
+        thread::sleep(Duration::from_millis(30));
+        return Err(e.into());
+      }
+      Err(_) => {
+        print!("❌ Panic while parsing persona's section");
+        thread::sleep(Duration::from_millis(30));
+        return Err(Error::msg("panic while parsing persona's section"));
+      }
+    };
+    persona.name = persona.name.trim_matches('"').to_string();
+    persona.system_prompt = persona.system_prompt.trim_matches('"').to_string();
+
+    let checks = [
+      validate_agent_name(&persona.name).map(|_| ()),
+      validate_system_prompt(&persona.system_prompt),
+      validate_tts(&persona.tts),
+      validate_language(&persona.language, &persona.tts),
+      validate_voice(&persona.voice, &persona.language, &persona.tts),
+      validate_voice_speed(persona.voice_speed),
+    ];
+    for e in checks.into_iter().filter_map(Result::err) {
+      errors.push(format!("Persona {}: {}", persona.name, e));
+    }
+    if personas.iter().any(|p| p.name == persona.name) {
+      errors.push(format!("Persona {}: defined twice", persona.name));
+    }
+    personas.push(persona);
+  }
+
+  if !errors.is_empty() {
+    print!("❌ {}", &errors.join("\n").to_string());
+    thread::sleep(Duration::from_millis(30));
+    terminate(1);
+  }
+  Ok(personas)
+}
+
+/// The [snippet] sections of the settings file, none when it has no such
+/// section.
+pub fn load_snippets(settings_path: &std::path::Path) -> Result<Vec<Snippet>, Error> {
+  let ini_contents = read_to_string(settings_path)?;
+  let mut snippets: Vec<Snippet> = Vec::new();
+  let mut errors: Vec<String> = Vec::new();
+  for section in sections(&ini_contents, "snippet") {
+    let mut snippet: Snippet = match panic::catch_unwind(|| from_str::<Snippet>(&section)) {
+      Ok(Ok(s)) => s,
+      Ok(Err(e)) => {
+        print!("❌ Failed to parse snippet's settings section: {}", e);
+        thread::sleep(Duration::from_millis(30));
+        return Err(e.into());
+      }
+      Err(_) => {
+        print!("❌ Panic while parsing snippet's section");
+        thread::sleep(Duration::from_millis(30));
+        return Err(Error::msg("panic while parsing snippet's section"));
+      }
+    };
+    snippet.trigger = snippet.trigger.trim_matches('"').to_string();
+    snippet.prompt = snippet.prompt.trim_matches('"').to_string();
+
+    if snippet.trigger.split(';').all(|t| t.trim().is_empty()) {
+      errors.push("Snippet: the trigger is empty".to_string());
+    }
+    if snippet.prompt.trim().is_empty() {
+      errors.push(format!("Snippet {}: the prompt is empty", snippet.trigger));
+    }
+    snippets.push(snippet);
+  }
+
+  if !errors.is_empty() {
+    print!("❌ {}", &errors.join("\n").to_string());
+    thread::sleep(Duration::from_millis(30));
+    terminate(1);
+  }
+  Ok(snippets)
+}
+
 pub fn ensure_settings_file() -> Result<(), Error> {
   // Determine home directory
   let home =
@@ -421,7 +1567,7 @@ system_prompt = "You are a neutral, helpful AI assistant. Follow the subject of
 sound_threshold_peak = 0.12
 end_silence_ms = 2500
 ptt = true
-whisper_model_path = ~/.whisper-models/ggml-tiny.bin
+whisper_model_path = ggml-tiny.bin
 
 [agent]
 name = explainer
@@ -436,7 +1582,7 @@ system_prompt = "You are a helpful AI assistant. Your only funcion is to explain
 sound_threshold_peak = 0.12
 end_silence_ms = 2500
 ptt = true
-whisper_model_path = ~/.whisper-models/ggml-tiny.bin
+whisper_model_path = ggml-tiny.bin
 
 [agent]
 name = planner
@@ -451,7 +1597,7 @@ system_prompt = You are an ai assistant which assist the user in the creation of
 sound_threshold_peak = 0.12
 end_silence_ms = 2000
 ptt = true
-whisper_model_path = ~/.whisper-models/ggml-tiny.bin
+whisper_model_path = ggml-tiny.bin
 
 [agent]
 name = Ptahhotep
@@ -466,7 +1612,7 @@ system_prompt = You are Ptahhotep, an ancient Egyptian advisor. Follow the subje
 sound_threshold_peak = 0.12
 end_silence_ms = 2500
 ptt = true
-whisper_model_path = ~/.whisper-models/ggml-tiny.bin
+whisper_model_path = ggml-tiny.bin
 
 [agent]
 name = Aristoteles
@@ -481,7 +1627,7 @@ system_prompt = "You are Aristoteles, a creative thinker. Follow the subject of
 sound_threshold_peak = 0.12
 end_silence_ms = 2500
 ptt = true
-whisper_model_path = ~/.whisper-models/ggml-tiny.bin
+whisper_model_path = ggml-tiny.bin
 
 [agent]
 name = Budda
@@ -496,7 +1642,7 @@ system_prompt = You are Budda, a serene guide. Follow the subject of the convers
 sound_threshold_peak = 0.12
 end_silence_ms = 2500
 ptt = true
-whisper_model_path = ~/.whisper-models/ggml-tiny.bin
+whisper_model_path = ggml-tiny.bin
 
 [agent]
 name = Jesus Christ
@@ -511,7 +1657,7 @@ system_prompt = You are Jesus Christ, a compassionate teacher. Follow the subjec
 sound_threshold_peak = 0.12
 end_silence_ms = 2500
 ptt = true
-whisper_model_path = ~/.whisper-models/ggml-tiny.bin
+whisper_model_path = ggml-tiny.bin
 
 
 "#;
@@ -523,11 +1669,18 @@ whisper_model_path = ~/.whisper-models/ggml-tiny.bin
 pub fn pick_input_config(
   device: &Device,
   preferred_sr: u32,
+  input_channel: Option<u16>,
 ) -> Result<cpal::SupportedStreamConfig, Error> {
   use cpal::SampleFormat;
 
   let mut candidates: Vec<cpal::SupportedStreamConfig> = Vec::new();
+  let mut max_channels = 0;
   for range in device.supported_input_configs()? {
+    max_channels = max_channels.max(range.channels());
+    // the channel listened to has to be there
+    if input_channel.is_some_and(|ch| ch > range.channels()) {
+      continue;
+    }
     let min_sr = range.min_sample_rate().0;
     let max_sr = range.max_sample_rate().0;
     let chosen_sr = preferred_sr.clamp(min_sr, max_sr);
@@ -550,10 +1703,14 @@ pub fn pick_input_config(
     (fmt_rank, ch_rank, sr_rank)
   });
 
-  candidates
-    .into_iter()
-    .next()
-    .ok_or_else(|| Error::msg("no supported input configs"))
+  match (candidates.into_iter().next(), input_channel) {
+    (Some(cfg), _) => Ok(cfg),
+    (None, Some(ch)) if max_channels > 0 => Err(Error::msg(format!(
+      "--input-channel {}: the input device has {} channels",
+      ch, max_channels
+    ))),
+    (None, _) => Err(Error::msg("no supported input configs")),
+  }
 }
 
 // PRIVATE
@@ -571,6 +1728,50 @@ fn validate_agent_name(name: &str) -> Result<String, std::io::Error> {
   }
 }
 
+fn validate_bridge(chat: &str) -> Result<String, std::io::Error> {
+  match chat.split_once(':') {
+    Some(("telegram" | "discord", id)) if !id.trim().is_empty() => Ok(chat.to_string()),
+    _ => Err(std::io::Error::new(
+      std::io::ErrorKind::InvalidInput,
+      "bridge must be telegram:CHAT_ID or discord:CHANNEL_ID",
+    )),
+  }
+}
+
+fn validate_quiet_hours(range: &str) -> Result<String, std::io::Error> {
+  match parse_quiet_hours(range) {
+    Some(_) => Ok(range.to_string()),
+    None => Err(std::io::Error::new(
+      std::io::ErrorKind::InvalidInput,
+      "quiet hours must be HH:MM-HH:MM, e.g. 22:00-07:00",
+    )),
+  }
+}
+
+fn validate_status_led(spec: &str) -> Result<String, std::io::Error> {
+  match parse_status_led(spec) {
+    Some(_) => Ok(spec.to_string()),
+    None => Err(std::io::Error::new(
+      std::io::ErrorKind::InvalidInput,
+      "status led must be [CHIP:]LINE, e.g. 17 or gpiochip4:17",
+    )),
+  }
+}
+
+fn validate_status_display(spec: &str) -> Result<String, std::io::Error> {
+  match parse_status_display(spec) {
+    Some(_) => Ok(spec.to_string()),
+    None => Err(std::io::Error::new(
+      std::io::ErrorKind::InvalidInput,
+      "status display must be BUS[:ADDR[:ROWS]], e.g. 1 or 1:0x3d:32 (rows 32 or 64)",
+    )),
+  }
+}
+
+fn validate_regex(pattern: &str) -> Result<String, regex::Error> {
+  regex::Regex::new(pattern).map(|_| pattern.to_string())
+}
+
 fn validate_language(language: &str, tts: &str) -> Result<(), std::io::Error> {
   let lang_clean = language.trim_matches('"');
   let langs = tts::get_all_available_languages();
@@ -791,6 +1992,47 @@ fn validate_voice_speed(value: f32) -> Result<(), std::io::Error> {
 // ------------------------------------------------------------------
 
 // Sanitizes quoted string values in AgentSettings
+// The sections of the settings file under a [`header`] line, as key=value
+// lines with the quotes around the values removed.
+fn sections(ini_contents: &str, header: &str) -> Vec<String> {
+  let mut sections = Vec::new();
+  let mut current: Option<String> = None;
+  for line in ini_contents.lines() {
+    let trimmed = line.trim();
+    if trimmed.starts_with('[') && trimmed.ends_with(']') {
+      sections.extend(current.take());
+      if trimmed[1..trimmed.len() - 1].trim() == header {
+        current = Some(String::new());
+      }
+      continue;
+    }
+    let Some(section) = current.as_mut() else {
+      continue;
+    };
+    // skip lines without '=' (e.g., empty lines)
+    if let Some(idx) = line.find('=') {
+      let (key, val_part) = line.split_at(idx);
+      // val_part includes the '=' at start
+      let val = val_part[1..].trim();
+      let val = if val.len() >= 2 && val.starts_with('"') && val.ends_with('"') {
+        &val[1..val.len() - 1]
+      } else {
+        val
+      };
+      section.push_str(key.trim());
+      section.push('=');
+      section.push_str(val);
+      section.push('\n');
+    }
+  }
+  sections.extend(current);
+  sections
+    .into_iter()
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty())
+    .collect()
+}
+
 fn sanitize_agent_settings(agent: &mut AgentSettings) {
   agent.name = agent.name.trim_matches('"').to_string();
   agent.language = agent.language.trim_matches('"').to_string();
@@ -0,0 +1,226 @@
+// ------------------------------------------------------------------
+//  Contexts (--context, x key, "switch to the <name> context")
+// ------------------------------------------------------------------
+//
+// Separate conversations kept side by side, e.g. "work" and "cooking", so
+// unrelated topics don't end up in the same history sent to the llm. Each
+// has its own history, its own agent and, with -s, its own saved
+// conversation. Switching parks the current one as it is and brings the
+// other one back; a context that doesn't exist yet starts empty, with the
+// current agent.
+
+use crate::history::History;
+use crate::state::GLOBAL_STATE;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::Ordering;
+
+// the context of a session started without --context
+const DEFAULT_CONTEXT: &str = "main";
+
+static CONTEXTS: Mutex<Option<Contexts>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+/// Declares the contexts of the session, "NAME" or "NAME:AGENT" (--context),
+/// the first one being the current one. Call once the state is set.
+pub fn init(specs: &[String]) -> Result<(), String> {
+  let state = GLOBAL_STATE.get().ok_or("state not initialized")?;
+  let mut contexts = Contexts {
+    current: DEFAULT_CONTEXT.to_string(),
+    parked: Vec::new(),
+  };
+  for (i, spec) in specs.iter().enumerate() {
+    let (name, agent) = match spec.split_once(':') {
+      Some((name, agent)) => (name.trim(), Some(agent.trim().to_string())),
+      None => (spec.trim(), None),
+    };
+    if name.is_empty() {
+      return Err(format!("'{}': the context has no name", spec));
+    }
+    if let Some(agent) = &agent
+      && !state.agents.iter().any(|a| &a.name == agent)
+    {
+      return Err(format!("'{}': there is no agent named '{}'", spec, agent));
+    }
+    if i == 0 {
+      contexts.current = name.to_string();
+      if let Some(agent) = agent {
+        use_agent(&agent);
+      }
+      continue;
+    }
+    contexts.parked.push(Parked {
+      name: name.to_string(),
+      history: state
+        .conversation_history
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .empty_like(),
+      agent,
+      save_path: None,
+      start_date: String::new(),
+    });
+  }
+  *CONTEXTS.lock().unwrap_or_else(PoisonError::into_inner) = Some(contexts);
+  Ok(())
+}
+
+/// The name of the current context, None while there is only the default one.
+pub fn label() -> Option<String> {
+  let contexts = CONTEXTS.lock().unwrap_or_else(PoisonError::into_inner);
+  let contexts = contexts.as_ref()?;
+  (contexts.current != DEFAULT_CONTEXT || !contexts.parked.is_empty())
+    .then(|| contexts.current.clone())
+}
+
+/// The context `text` asks to switch to: "switch to the work context",
+/// "switch context to work", "open the cooking conversation", or "switch to
+/// work" for a context that already exists.
+pub fn request(text: &str) -> Option<String> {
+  let words: String = text
+    .to_lowercase()
+    .chars()
+    .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+    .collect();
+  let words: Vec<&str> = words.split_whitespace().collect();
+  let rest = match words.as_slice() {
+    ["switch", "context", "to", rest @ ..] => return name_of(rest),
+    ["switch", "to", rest @ ..] | ["go", "to", rest @ ..] | ["open", rest @ ..] => rest,
+    _ => return None,
+  };
+  let rest = rest.strip_prefix(&["the"]).unwrap_or(rest);
+  match rest {
+    [name @ .., "context" | "conversation"] => name_of(name),
+    name => name_of(name).filter(|name| exists(name)),
+  }
+}
+
+/// Makes `name` the current context, returns the line telling so.
+pub fn switch(name: &str) -> String {
+  let Some(state) = GLOBAL_STATE.get() else {
+    return String::new();
+  };
+  let mut guard = CONTEXTS.lock().unwrap_or_else(PoisonError::into_inner);
+  let contexts = guard.get_or_insert_with(|| Contexts {
+    current: DEFAULT_CONTEXT.to_string(),
+    parked: Vec::new(),
+  });
+  if contexts.current == name {
+    return format!("Already in the {} context.", name);
+  }
+  // the one left is saved as it is
+  crate::conversation::save_session();
+  let target = match contexts.parked.iter().position(|p| p.name == name) {
+    Some(i) => contexts.parked.remove(i),
+    None => Parked {
+      name: name.to_string(),
+      history: state
+        .conversation_history
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .empty_like(),
+      agent: None,
+      save_path: None,
+      start_date: String::new(),
+    },
+  };
+  let current_agent = state
+    .agent_name
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let left = Parked {
+    name: std::mem::replace(&mut contexts.current, target.name),
+    history: std::mem::replace(
+      &mut *state
+        .conversation_history
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner),
+      target.history,
+    ),
+    agent: Some(current_agent),
+    save_path: std::mem::replace(
+      &mut *state
+        .save_path
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner),
+      target.save_path,
+    ),
+    start_date: std::mem::replace(
+      &mut *state
+        .start_date
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner),
+      target.start_date,
+    ),
+  };
+  contexts.parked.push(left);
+  if let Some(agent) = &target.agent {
+    use_agent(agent);
+  }
+  crate::telemetry::feature("context_switch");
+  crate::log::log("info", &format!("Switched to the context '{}'", name));
+  format!(
+    "Switched to the {} context, with {}.",
+    name,
+    state
+      .agent_name
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+  )
+}
+
+/// Switches to the context after the current one, returns the line telling
+/// so. None while there is only one.
+pub fn next() -> Option<String> {
+  let name = {
+    let contexts = CONTEXTS.lock().unwrap_or_else(PoisonError::into_inner);
+    // parked in the order they were left: the first is the oldest
+    contexts.as_ref()?.parked.first()?.name.clone()
+  };
+  Some(switch(&name))
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+struct Contexts {
+  current: String,
+  parked: Vec<Parked>,
+}
+
+// a context that isn't the current one
+struct Parked {
+  name: String,
+  history: History,
+  // the agent it was left with, None to go on with the current one
+  agent: Option<String>,
+  save_path: Option<PathBuf>,
+  start_date: String,
+}
+
+fn name_of(words: &[&str]) -> Option<String> {
+  (!words.is_empty() && words.len() <= 3).then(|| words.join(" "))
+}
+
+fn exists(name: &str) -> bool {
+  CONTEXTS
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+    .is_some_and(|c| c.current == name || c.parked.iter().any(|p| p.name == name))
+}
+
+fn use_agent(name: &str) {
+  let Some(state) = GLOBAL_STATE.get() else {
+    return;
+  };
+  let Some(agent) = state.agents.iter().find(|a| a.name == name) else {
+    return;
+  };
+  state.use_agent(agent);
+  state.recording_paused.store(agent.ptt, Ordering::Relaxed);
+}
@@ -0,0 +1,149 @@
+// ------------------------------------------------------------------
+//  Long replies (--max-spoken-seconds)
+// ------------------------------------------------------------------
+//
+// A reply that would be spoken for longer than --max-spoken-seconds stops
+// at the end of a phrase with "Shall I go on?". Saying yes speaks the rest
+// (stopping again if it's still too long), anything else drops it: a new
+// question is answered as usual. The whole reply is still shown and kept in
+// the history, only the voice is cut.
+
+use crate::state::GLOBAL_STATE;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// words said per second at voice speed 1.0
+const WORDS_PER_SECOND: f32 = 2.5;
+const QUESTION: &str = "Shall I go on?";
+
+// an answer made of these words alone goes on ("yes please", "go ahead")
+const YES: [&str; 13] = [
+  "yes", "yeah", "yep", "sure", "ok", "okay", "continue", "go", "on", "keep", "going", "ahead",
+  "please",
+];
+// and of these, stops ("no thanks", "thats enough")
+const NO: [&str; 10] = [
+  "no", "nope", "thanks", "thank", "you", "stop", "enough", "thats", "never", "mind",
+];
+
+// 0: replies are spoken whole
+static MAX_SECS: AtomicU64 = AtomicU64::new(0);
+// the phrases left when the last reply was paused
+static PENDING: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+/// What was said after "Shall I go on?".
+#[derive(Debug, PartialEq, Eq)]
+pub enum Answer {
+  // the phrases left to speak
+  Go(Vec<String>),
+  // the rest is dropped, nothing to answer
+  Stop,
+  // no reply was paused, or something else was said: answered as usual
+  Other,
+}
+
+/// The longest a reply is spoken before asking to go on, None for no limit.
+pub fn init(max_secs: Option<u64>) {
+  MAX_SECS.store(max_secs.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Seconds it takes to say `text` at the current voice speed, roughly.
+pub fn estimate_secs(text: &str) -> f32 {
+  let speed = GLOBAL_STATE
+    .get()
+    .map(|s| s.speed.load(Ordering::Relaxed) as f32 / 10.0)
+    .filter(|speed| *speed > 0.0)
+    .unwrap_or(1.0);
+  text.split_whitespace().count() as f32 / (WORDS_PER_SECOND * speed)
+}
+
+/// What `text`, said while a reply is paused, asks for. The paused reply is
+/// forgotten either way.
+pub fn answer(text: &str) -> Answer {
+  let Some(rest) = PENDING
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .take()
+  else {
+    return Answer::Other;
+  };
+  let words: String = text
+    .to_lowercase()
+    .chars()
+    .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+    .collect();
+  let only = |set: &[&str]| {
+    words.split_whitespace().next().is_some() && words.split_whitespace().all(|w| set.contains(&w))
+  };
+  if only(&YES) {
+    Answer::Go(rest)
+  } else if only(&NO) {
+    Answer::Stop
+  } else {
+    Answer::Other
+  }
+}
+
+/// The phrases of a reply spoken so far, against the limit.
+pub struct Budget {
+  max_secs: f32,
+  spoken_secs: f32,
+  held: Vec<String>,
+}
+
+impl Budget {
+  /// A new reply. A reply still paused is dropped.
+  pub fn new() -> Self {
+    PENDING
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .take();
+    Self {
+      max_secs: MAX_SECS.load(Ordering::Relaxed) as f32,
+      spoken_secs: 0.0,
+      held: Vec::new(),
+    }
+  }
+
+  /// Whether `phrase` is to be spoken: the phrase going over the limit still
+  /// is, those after it are held.
+  pub fn admit(&mut self, phrase: &str) -> bool {
+    if self.max_secs <= 0.0 {
+      return true;
+    }
+    if self.spoken_secs >= self.max_secs {
+      self.held.push(phrase.to_string());
+      return false;
+    }
+    self.spoken_secs += estimate_secs(phrase);
+    true
+  }
+
+  /// At the end of the reply: when phrases were held, they're kept for
+  /// `answer` and the question to ask is returned.
+  pub fn pause(self) -> Option<&'static str> {
+    if self.held.is_empty() {
+      return None;
+    }
+    crate::log::log(
+      "info",
+      &format!(
+        "Reply paused after ~{:.0}s of speech, {} phrases left",
+        self.spoken_secs,
+        self.held.len()
+      ),
+    );
+    *PENDING.lock().unwrap_or_else(PoisonError::into_inner) = Some(self.held);
+    Some(QUESTION)
+  }
+}
+
+impl Default for Budget {
+  fn default() -> Self {
+    Self::new()
+  }
+}
@@ -0,0 +1,283 @@
+// ------------------------------------------------------------------
+//  Local control socket and `vtmate ctl`
+// ------------------------------------------------------------------
+
+use crate::config::CtlAction;
+use crate::conversation::Command;
+use crate::state::{GLOBAL_STATE, TurnState};
+use crossbeam_channel::Sender;
+use serde_json::{Value, json};
+use std::path::PathBuf;
+use std::sync::PoisonError;
+use std::sync::atomic::Ordering;
+
+// API
+// ------------------------------------------------------------------
+
+/// Socket path: the given one, or vtmate.sock in $XDG_RUNTIME_DIR (the data
+/// dir when unset).
+pub fn socket_path(configured: Option<&str>) -> Option<PathBuf> {
+  if let Some(path) = configured.filter(|p| !p.is_empty()) {
+    return Some(PathBuf::from(path));
+  }
+  std::env::var_os("XDG_RUNTIME_DIR")
+    .map(PathBuf::from)
+    .or_else(crate::util::get_data_dir)
+    .map(|dir| dir.join("vtmate.sock"))
+}
+
+/// Answers one JSON request per line on a unix socket, so scripts and
+/// keybindings can drive this instance:
+///   {"cmd":"status"}  {"cmd":"pause"}  {"cmd":"resume"}  {"cmd":"interrupt"}
+///   {"cmd":"say","text":"...","priority":"normal|resume|important"}
+///   {"cmd":"ask","text":"..."}
+///   {"cmd":"dnd"}  {"cmd":"dnd","on":true}
+///   {"cmd":"tts"}  {"cmd":"tts","backend":"kokoro"}
+/// Every request gets one JSON line back. After {"cmd":"attach"} the
+/// connection also receives the turn events (lines with a "type"), as used
+/// by `vtmate attach`.
+#[cfg(unix)]
+pub fn control_thread(
+  path: PathBuf,
+  tx_cmd: Sender<Command>,
+  stop_play_tx: Sender<()>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  use std::io::{BufRead, BufReader, Write};
+  use std::os::unix::fs::FileTypeExt;
+  use std::os::unix::net::{UnixListener, UnixStream};
+
+  if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+    // whatever else is there isn't ours to remove
+    if !metadata.file_type().is_socket() {
+      return Err(format!("{} exists and is not a socket", path.display()).into());
+    }
+    // a live socket belongs to another instance, a dead one is left over
+    if UnixStream::connect(&path).is_ok() {
+      return Err(format!("{} is in use by another vtmate", path.display()).into());
+    }
+    std::fs::remove_file(&path)?;
+  }
+  if let Some(dir) = path.parent() {
+    std::fs::create_dir_all(dir)?;
+  }
+  let listener = UnixListener::bind(&path)?;
+  crate::log::log(
+    "info",
+    &format!("Control socket listening on {}", path.display()),
+  );
+  for stream in listener.incoming() {
+    let Ok(stream) = stream else { continue };
+    let tx_cmd = tx_cmd.clone();
+    let stop_play_tx = stop_play_tx.clone();
+    std::thread::spawn(move || {
+      let Ok(writer) = stream.try_clone() else {
+        return;
+      };
+      // shared with the events of an attached client
+      let writer = std::sync::Arc::new(std::sync::Mutex::new(writer));
+      for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+          continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+          Ok(req) if req["cmd"] == "interrupt" => {
+            interrupt(&stop_play_tx);
+            json!({ "ok": true })
+          }
+          Ok(req) if req["cmd"] == "attach" => {
+            let events = crate::events::subscribe();
+            let writer = writer.clone();
+            std::thread::spawn(move || {
+              for event in events {
+                let line = crate::server::event_json(&event);
+                if writeln!(
+                  writer.lock().unwrap_or_else(PoisonError::into_inner),
+                  "{}",
+                  line
+                )
+                .is_err()
+                {
+                  break;
+                }
+              }
+            });
+            attach_reply()
+          }
+          Ok(req) => handle_request(&req, &tx_cmd),
+          Err(e) => json!({ "ok": false, "error": e.to_string() }),
+        };
+        if writeln!(
+          writer.lock().unwrap_or_else(PoisonError::into_inner),
+          "{}",
+          reply
+        )
+        .is_err()
+        {
+          break;
+        }
+      }
+    });
+  }
+  Ok(())
+}
+
+/// Stops the current reply: llm, tts and playback.
+pub fn interrupt(stop_play_tx: &Sender<()>) {
+  let state = GLOBAL_STATE.get().expect("AppState not initialized");
+  state.interrupter.interrupt();
+  crate::events::emit(crate::engine::TurnEvent::Interrupted);
+  let _ = stop_play_tx.try_send(());
+  state.turn.advance(
+    &[TurnState::Generating, TurnState::Speaking],
+    TurnState::Interrupted,
+  );
+}
+
+/// `vtmate ctl`: sends one request to the running instance and prints the
+/// reply. Returns the process exit code.
+pub fn run_ctl_command(action: &CtlAction, socket: Option<&str>) -> i32 {
+  let request = match action {
+    CtlAction::Status => json!({ "cmd": "status" }),
+    CtlAction::Pause => json!({ "cmd": "pause" }),
+    CtlAction::Resume => json!({ "cmd": "resume" }),
+    CtlAction::Interrupt => json!({ "cmd": "interrupt" }),
+    CtlAction::Say { text, priority } => {
+      json!({ "cmd": "say", "text": text.join(" "), "priority": priority })
+    }
+    CtlAction::Ask { text } => json!({ "cmd": "ask", "text": text.join(" ") }),
+    CtlAction::Cue { name } => json!({ "cmd": "cue", "name": name }),
+    CtlAction::Dnd { state: None } => json!({ "cmd": "dnd" }),
+    CtlAction::Dnd { state: Some(state) } => json!({ "cmd": "dnd", "on": state == "on" }),
+    CtlAction::Tts { backend } => json!({ "cmd": "tts", "backend": backend }),
+  };
+  let Some(path) = socket_path(socket) else {
+    eprintln!("❌ Could not determine the control socket path");
+    return 1;
+  };
+  match send(&path, &request) {
+    Ok(reply) => {
+      if matches!(action, CtlAction::Status) {
+        println!(
+          "{}",
+          serde_json::to_string_pretty(&reply["status"]).unwrap_or_default()
+        );
+      }
+      if reply["ok"].as_bool() == Some(true) {
+        0
+      } else {
+        eprintln!("❌ {}", reply["error"].as_str().unwrap_or("request failed"));
+        1
+      }
+    }
+    Err(e) => {
+      eprintln!("❌ No running vtmate on {} ({})", path.display(), e);
+      1
+    }
+  }
+}
+
+/// Runs one control request (see [`control_thread`]), returning the reply.
+pub fn handle_request(req: &Value, tx_cmd: &Sender<Command>) -> Value {
+  let state = GLOBAL_STATE.get().expect("AppState not initialized");
+  let text = req["text"].as_str().map(str::trim).unwrap_or("");
+  match req["cmd"].as_str().unwrap_or("") {
+    "status" => json!({ "ok": true, "status": crate::server::status_json(state) }),
+    "pause" | "resume" => {
+      let paused = req["cmd"] == "pause";
+      state.recording_paused.store(paused, Ordering::Relaxed);
+      json!({ "ok": true, "paused": paused })
+    }
+    "say" | "ask" if text.is_empty() => json!({ "ok": false, "error": "missing text" }),
+    "say" => {
+      let name = req["priority"].as_str().unwrap_or("normal");
+      let Some(priority) = crate::announce::Priority::from_name(name) else {
+        return json!({ "ok": false, "error": format!("unknown priority '{}'", name) });
+      };
+      // an announcement waits for do not disturb to end
+      if crate::dnd::is_active() {
+        let text = text.to_string();
+        crate::dnd::run_or_defer(move || {
+          let _ = crate::announce::push(text, priority);
+        });
+        return json!({ "ok": true, "deferred": true });
+      }
+      match crate::announce::push(text.to_string(), priority) {
+        Ok(()) => json!({ "ok": true }),
+        Err(e) => json!({ "ok": false, "error": e }),
+      }
+    }
+    "ask" => match tx_cmd.send(Command::Ask(text.to_string())) {
+      Ok(()) => json!({ "ok": true }),
+      Err(_) => json!({ "ok": false, "error": "conversation is not running" }),
+    },
+    "cue" => {
+      let name = req["name"].as_str().unwrap_or("");
+      match crate::earcons::Earcon::from_name(name) {
+        Some(_) if !crate::earcons::is_enabled() => {
+          json!({ "ok": false, "error": "earcons are off (see --earcons)" })
+        }
+        Some(earcon) => {
+          let deferred = crate::dnd::run_or_defer(move || crate::earcons::play(earcon));
+          json!({ "ok": true, "deferred": deferred })
+        }
+        None => json!({ "ok": false, "error": format!("unknown earcon '{}'", name) }),
+      }
+    }
+    "dnd" => {
+      let active = match req["on"].as_bool() {
+        Some(on) => {
+          crate::dnd::set_active(on);
+          on
+        }
+        None => crate::dnd::toggle(),
+      };
+      json!({ "ok": true, "dnd": active })
+    }
+    "tts" => match crate::tts::switch_backend(req["backend"].as_str()) {
+      Ok((backend, voice)) => json!({ "ok": true, "tts": backend, "voice": voice }),
+      Err(e) => json!({ "ok": false, "error": e }),
+    },
+    other => json!({ "ok": false, "error": format!("unknown command '{}'", other) }),
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// status and conversation so far, for a client attaching
+#[cfg(unix)]
+fn attach_reply() -> Value {
+  let state = GLOBAL_STATE.get().expect("AppState not initialized");
+  let history: Vec<Value> = state
+    .conversation_history
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .iter()
+    .filter(|m| m.role != "system")
+    .map(|m| json!({ "role": m.role, "content": m.content, "agent": m.agent_name }))
+    .collect();
+  json!({
+    "ok": true,
+    "status": crate::server::status_json(state),
+    "history": history,
+  })
+}
+
+#[cfg(unix)]
+fn send(path: &std::path::Path, request: &Value) -> std::io::Result<Value> {
+  use std::io::{BufRead, BufReader, Write};
+  let mut stream = std::os::unix::net::UnixStream::connect(path)?;
+  writeln!(stream, "{}", request)?;
+  let mut line = String::new();
+  BufReader::new(stream).read_line(&mut line)?;
+  serde_json::from_str(&line).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(not(unix))]
+fn send(_path: &std::path::Path, _request: &Value) -> std::io::Result<Value> {
+  Err(std::io::Error::new(
+    std::io::ErrorKind::Unsupported,
+    "the control socket is only available on unix",
+  ))
+}
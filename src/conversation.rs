@@ -3,64 +3,118 @@
 // ------------------------------------------------------------------
 
 use crate::START_INSTANT;
+use crate::cancel::{CancellationToken, Interrupter};
+use crate::engine::TurnEvent;
+use crate::interjection::Interjection;
 use crate::playback::set_wav_tx;
 use crate::state::AppState;
 use crate::state::GLOBAL_STATE;
+use crate::state::TurnState;
 use crate::util::terminate;
 use chrono::Local;
 use crossbeam_channel::{Receiver, Sender, select};
 use hound;
+use serde_json::{Value, json};
 use std::fs;
 use std::path::Path;
-use std::sync::OnceLock;
-use std::sync::{
-  Arc, Mutex,
-  atomic::{AtomicU64, Ordering},
-};
+use std::sync::PoisonError;
+use std::sync::{Arc, Mutex, atomic::Ordering};
 use std::thread;
 use std::time::Duration;
-use tokio::runtime::Builder as TokioBuilder;
 use uuid::Uuid;
 
-static WHISPER_CTX: OnceLock<whisper_rs::WhisperContext> = OnceLock::new();
-
 // API
 // ------------------------------------------------------------------
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ChatMessage {
   pub role: String,
   pub content: String,
   pub agent_name: Option<String>,
+  /// How an assistant reply was produced, kept in the saved conversation
+  pub meta: Option<TurnMeta>,
 }
 
-pub type ConversationHistory = std::sync::Arc<std::sync::Mutex<Vec<ChatMessage>>>;
+/// Model, voice and timings of an assistant reply.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TurnMeta {
+  pub model: String,
+  pub voice: String,
+  /// Transcription of the user utterance
+  pub stt_ms: Option<u64>,
+  /// Mean token probability of that transcription (0 to 1)
+  pub stt_confidence: Option<f32>,
+  /// From the llm request to its first token
+  pub first_token_ms: Option<u64>,
+  /// From the llm request to the end of the reply
+  pub llm_ms: Option<u64>,
+  /// The user talked over the reply or stopped it
+  pub interrupted: bool,
+}
 
-/// Commands sent from keyboard to conversation thread
-pub enum Command {
-  Undo,
+pub type ConversationHistory = std::sync::Arc<std::sync::Mutex<crate::history::History>>;
+
+/// Silence written after every chunk of the saved conversation audio.
+pub const RECORDING_GAP_MS: u64 = 500;
+
+impl TurnMeta {
+  /// One line for the saved transcript:
+  /// `model llama3, voice af_sky, stt 310 ms (91%), first token 640 ms, llm 2100 ms, interrupted`.
+  pub fn summary(&self) -> String {
+    let mut parts = vec![
+      format!("model {}", self.model),
+      format!("voice {}", self.voice),
+    ];
+    if let Some(ms) = self.stt_ms {
+      match self.stt_confidence {
+        Some(c) => parts.push(format!("stt {} ms ({:.0}%)", ms, c * 100.0)),
+        None => parts.push(format!("stt {} ms", ms)),
+      }
+    }
+    if let Some(ms) = self.first_token_ms {
+      parts.push(format!("first token {} ms", ms));
+    }
+    if let Some(ms) = self.llm_ms {
+      parts.push(format!("llm {} ms", ms));
+    }
+    if self.interrupted {
+      parts.push("interrupted".to_string());
+    }
+    parts.join(", ")
+  }
+
+  pub fn to_json(&self) -> Value {
+    json!({
+      "model": self.model,
+      "voice": self.voice,
+      "stt_ms": self.stt_ms,
+      "stt_confidence": self.stt_confidence,
+      "first_token_ms": self.first_token_ms,
+      "llm_ms": self.llm_ms,
+      "interrupted": self.interrupted,
+    })
+  }
 }
 
-/// Initialise the Whisper context once, performing a warm‑up.
-pub fn init_whisper_context(model_path: &str) -> &'static whisper_rs::WhisperContext {
-  WHISPER_CTX.get_or_init(|| {
-    let ctx = whisper_rs::WhisperContext::new_with_params(model_path, Default::default())
-      .expect("Failed to create WhisperContext");
-    // Perform warm‑up to load the model into memory
-    crate::stt::whisper_warmup(model_path).expect("Whisper warm‑up failed");
-    ctx
-  })
+/// Commands sent from keyboard (and the control server) to conversation thread
+pub enum Command {
+  Undo,
+  /// Answer a typed user message, as if it had been spoken
+  Ask(String),
+  /// Answer a message of a chat bridge (see [`crate::bridge`]), like `Ask`
+  /// but without the shell commands
+  BridgeAsk(String),
+  /// Speak the queued announcements (see [`crate::announce`])
+  Announce,
 }
 
 pub fn conversation_thread(
-  rx_utt: Receiver<crate::audio::AudioChunk>,
-  interrupt_counter: Arc<AtomicU64>,
-  model_path: String,
+  rx_text: Receiver<crate::stt::Transcript>,
+  interrupter: Interrupter,
   settings: crate::config::AgentSettings,
-  ui: crate::state::UiState,
   conversation_history: ConversationHistory,
   tx_ui: Sender<String>,
-  tts_tx: Sender<(String, u64, String)>,
+  tts_tx: Sender<(String, CancellationToken, String)>,
   tts_done_rx: Receiver<()>,
   stop_play_tx: Sender<()>,
   rx_cmd: Receiver<Command>,
@@ -68,8 +122,6 @@ pub fn conversation_thread(
   quiet: bool,
   save: bool,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-  let ctx = init_whisper_context(&model_path);
-
   // WAV writer thread: activated when -s option is used
   // WAV writer will be started lazily when the first save path is created.
   let mut wav_tx_opt: Option<crossbeam_channel::Sender<crate::audio::AudioChunk>> = None;
@@ -94,39 +146,44 @@ pub fn conversation_thread(
       )?;
     }
 
-    let rt = TokioBuilder::new_current_thread()
-      .enable_all()
-      .build()
-      .unwrap();
+    let rt = crate::util::runtime();
 
     if let Some(prompt) = &init_prompt {
       // Show user message in UI
       send_user_message_ui(&tx_ui, &prompt, false);
       push_user_message(&conversation_history, &prompt);
       perform_save(&conversation_history, &settings_clone);
-      let system_prompt = settings.system_prompt.replace("\\n", "\n");
-      let messages = create_basic_messages(system_prompt, prompt.clone());
+      let system_prompt = crate::styles::system_prompt(&crate::followup::system_prompt(
+        &settings.system_prompt,
+        &settings,
+      ));
+      let messages = create_basic_messages(system_prompt.replace("\\n", "\n"), prompt.clone());
 
-      let my_interrupt = interrupt_counter.load(Ordering::SeqCst);
+      let cancel = interrupter.token().child();
       let messages_clone = messages.clone();
       let reply = rt
         .block_on(get_response(messages_clone, &settings))
         .unwrap_or_else(|e| {
-          crate::log::log(
-            "error",
-            &format!("Error getting response in quiet mode: {}", e),
-          );
+          crate::error::report(&e);
           String::new()
         });
       if !reply.is_empty() {
-        conversation_history.lock().unwrap().push(ChatMessage {
-          role: "assistant".to_string(),
-          content: reply.clone(),
-          agent_name: Some(settings.name.clone()),
-        });
+        conversation_history
+          .lock()
+          .unwrap_or_else(PoisonError::into_inner)
+          .push(ChatMessage {
+            role: "assistant".to_string(),
+            content: reply.clone(),
+            agent_name: Some(settings.name.clone()),
+            meta: Some(TurnMeta {
+              model: settings.model.clone(),
+              voice: settings.voice.clone(),
+              ..Default::default()
+            }),
+          });
         perform_save(&conversation_history, &settings_clone);
         // Display in UI
-        let label = format!("\x1b[48;5;22;37m{}:\x1b[0m", settings.name);
+        let label = crate::ui::agent_label(&settings.name);
         let _ = tx_ui.send(format!("line|{}", label));
         let _ = tx_ui.send(format!("stream|{}", reply.trim()));
         let _ = tx_ui.send("line|".to_string());
@@ -135,11 +192,10 @@ pub fn conversation_thread(
           &tts_tx,
           &tts_done_rx,
           settings.voice.clone(),
-          &interrupt_counter,
-          my_interrupt,
+          &cancel,
         );
         let state = GLOBAL_STATE.get().expect("AppState not initialized");
-        wait_for_playback(state, &interrupt_counter, my_interrupt);
+        wait_for_playback(state, &cancel);
       }
     }
 
@@ -148,15 +204,14 @@ pub fn conversation_thread(
   }
 
   // Runtime to use for async debate responses
-  let rt = TokioBuilder::new_current_thread()
-    .enable_all()
-    .build()
-    .unwrap();
+  let rt = crate::util::runtime();
 
   // Track interruptions for debate mode
-  let mut last_interrupt = interrupt_counter.load(Ordering::SeqCst);
+  let mut debate_token = interrupter.token();
   let mut debate_interrupted = false;
   let mut pending_user_msg: Option<String> = init_prompt;
+  // token of the last spoken reply, to tell when the user talked over it
+  let mut last_reply_token: Option<CancellationToken> = None;
   let mut prev_debate_enabled = false;
 
   let state = GLOBAL_STATE.get().expect("AppState not initialized");
@@ -170,7 +225,10 @@ pub fn conversation_thread(
       }
     } else {
       // If no initial prompt, use debate subject as first user message
-      let subject = state.debate_subject.lock().unwrap();
+      let subject = state
+        .debate_subject
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
       if !subject.is_empty() {
         let msg = subject.clone();
         send_user_message_ui(&tx_ui, &msg, false);
@@ -190,12 +248,18 @@ pub fn conversation_thread(
       // Reset state for new debate: clear pending message and interrupt flag
       pending_user_msg = None;
       debate_interrupted = false;
-      // Also reset last_interrupt to avoid false interruption detection
-      last_interrupt = interrupt_counter.load(Ordering::SeqCst);
+      // Also take a fresh token to avoid false interruption detection
+      debate_token = interrupter.token();
     }
     prev_debate_enabled = current_debate_enabled;
 
-    if save && state.save_path.lock().unwrap().is_none() {
+    if save
+      && state
+        .save_path
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_none()
+    {
       maybe_setup_and_save(
         &mut wav_tx_opt,
         &conversation_history,
@@ -217,13 +281,16 @@ pub fn conversation_thread(
     //   debate mode
     //  –––––––––––––––––––––––––––––––––––––
     if state.debate_enabled.load(Ordering::SeqCst) {
-      let debate_agents = state.debate_agents.lock().unwrap().clone();
+      let debate_agents = state
+        .debate_agents
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
       if debate_agents.len() >= 2 {
         // Check for interruption
-        let current_interrupt = interrupt_counter.load(Ordering::SeqCst);
-        if current_interrupt != last_interrupt {
+        if debate_token.is_cancelled() {
           debate_interrupted = true;
-          last_interrupt = current_interrupt;
+          debate_token = interrupter.token();
           // Stop any ongoing playback
           state
             .playback
@@ -237,34 +304,29 @@ pub fn conversation_thread(
         // Check for user input or undo command with short timeout
         let mut got_undo = false;
         select! {
-          recv(rx_utt) -> utt_result => {
-            if let Ok(utt) = utt_result {
+          recv(rx_text) -> text_result => {
+            if let Ok(transcript) = text_result {
               // User provided input - process it
               let state = GLOBAL_STATE.get().expect("AppState not initialized");
               state.conversation_paused.store(false, Ordering::Relaxed);
               // Resume debate if it was paused
               state.debate_paused.store(false, Ordering::SeqCst);
-              state.processing_response.store(true, Ordering::Relaxed);
+              state.turn.advance(&[TurnState::Transcribing], TurnState::Idle);
 
               // Apply settings of the agent that will respond next
-              let debate_agents = state.debate_agents.lock().unwrap().clone();
+              let debate_agents = state.debate_agents.lock().unwrap_or_else(PoisonError::into_inner).clone();
               let turn = state.debate_turn.load(Ordering::SeqCst) as usize;
               let agent_count = debate_agents.len();
               let next_agent = &debate_agents[turn % agent_count];
               let _ = apply_agent_settings(state, next_agent);
 
-              let _pcm_f32: Vec<f32> = utt.data.clone();
-              let mono_f32 = crate::audio::convert_to_mono(&utt);
-
-              let user_text = crate::stt::whisper_transcribe_with_ctx(
-                &ctx,
-                &mono_f32,
-                utt.sample_rate,
-                &state.language.lock().unwrap(),
-              )?;
+              crate::traces::begin_turn();
+              transcript.trace();
+              let user_text = crate::plugins::filter_transcript(transcript.text.trim());
               let user_text = user_text.trim().to_string();
 
               if !user_text.is_empty() {
+                crate::events::emit(TurnEvent::Transcript(user_text.clone()));
                 // Clear STOP_STREAM flag to ensure user text displays fully
                 crate::ui::STOP_STREAM.store(false, Ordering::Relaxed);
                 send_user_message_ui(&tx_ui, &user_text, true);
@@ -284,7 +346,7 @@ pub fn conversation_thread(
           }
           recv(rx_cmd) -> cmd_result => {
             if let Ok(Command::Undo) = cmd_result {
-              handle_undo(state, &tx_ui, &conversation_history, &interrupt_counter, &stop_play_tx, &settings);
+              handle_undo(state, &tx_ui, &conversation_history, &interrupter, &stop_play_tx, &settings);
               got_undo = true;
             }
           }
@@ -310,13 +372,21 @@ pub fn conversation_thread(
           // User interrupted - current agent responds to user
           (&debate_agents[turn % agent_count], msg)
         } else {
+          // agents answering each other, a turn of its own
+          crate::traces::begin_turn();
           let current_agent = &debate_agents[turn % agent_count];
-          let subject = state.debate_subject.lock().unwrap().clone();
+          let subject = state
+            .debate_subject
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
           let user_msg = if turn == 0 && !subject.is_empty() {
             format!("{}. Respond as short as possible", subject)
           } else {
             // Get last assistant message as the prompt for next agent
-            let hist = conversation_history.lock().unwrap();
+            let hist = conversation_history
+              .lock()
+              .unwrap_or_else(PoisonError::into_inner);
             hist
               .iter()
               .rev()
@@ -332,11 +402,7 @@ pub fn conversation_thread(
           continue;
         }
         if !user_msg.is_empty() {
-          let my_interrupt = interrupt_counter.load(Ordering::SeqCst);
-          // If interrupted before starting LLM request, skip
-          if interrupt_counter.load(Ordering::SeqCst) != my_interrupt {
-            continue;
-          }
+          let cancel = interrupter.token().child();
           // Set recording pause based on current agent's ptt
           state
             .recording_paused
@@ -354,18 +420,15 @@ pub fn conversation_thread(
             &tx_ui,
             &tts_tx,
             &tts_done_rx,
-            &rt,
-            &interrupt_counter,
+            rt,
+            &cancel,
             user_msg.clone(),
           );
-          state.processing_response.store(false, Ordering::Relaxed);
           // important: next agent will reply to this response using history
 
           // Increment turn only if not interrupted
-          if interrupt_counter.load(Ordering::SeqCst) == my_interrupt {
-            if !state.debate_paused.load(Ordering::SeqCst) {
-              state.debate_turn.fetch_add(1, Ordering::SeqCst);
-            }
+          if !cancel.is_cancelled() && !state.debate_paused.load(Ordering::SeqCst) {
+            state.debate_turn.fetch_add(1, Ordering::SeqCst);
           }
 
           // Reset debate_interrupted flag
@@ -382,6 +445,7 @@ pub fn conversation_thread(
     //  –––––––––––––––––––––––––––––––––––––
     if !state.debate_enabled.load(Ordering::SeqCst) {
       if let Some(user_msg) = pending_user_msg.take() {
+        crate::traces::begin_turn();
         handle_reply(
           state,
           &settings,
@@ -389,8 +453,8 @@ pub fn conversation_thread(
           &tx_ui,
           &tts_tx,
           &tts_done_rx,
-          &rt,
-          &interrupt_counter,
+          rt,
+          &interrupter.token().child(),
           user_msg,
         );
       }
@@ -399,68 +463,196 @@ pub fn conversation_thread(
     select! {
       recv(rx_cmd) -> cmd => {
         if let Ok(command) = cmd {
+          let bridged = matches!(command, Command::BridgeAsk(_));
           match command {
             Command::Undo => {
-              handle_undo(state, &tx_ui, &conversation_history, &interrupt_counter, &stop_play_tx, &settings);
+              handle_undo(state, &tx_ui, &conversation_history, &interrupter, &stop_play_tx, &settings);
+            }
+            Command::Ask(text) | Command::BridgeAsk(text) => {
+              if wait_for_reply_spoken(state, &interrupter) {
+                crate::telemetry::feature("queued_turn");
+              }
+              crate::traces::begin_turn();
+              crate::telemetry::feature("typed_turn");
+              crate::events::emit(TurnEvent::Transcript(text.clone()));
+              crate::ui::STOP_STREAM.store(false, Ordering::Relaxed);
+              send_user_message_ui(&tx_ui, &text, false);
+              push_user_message(&conversation_history, &text);
+              perform_save(&conversation_history, &settings_clone);
+              let agent = current_agent(state, &settings);
+              // typed text never answers a shell confirmation, it's a new request
+              crate::shell::cancel_pending();
+              // and a chat member can't ask for one
+              crate::shell::set_offered(!bridged);
+              handle_reply(
+                state,
+                &agent,
+                &conversation_history,
+                &tx_ui,
+                &tts_tx,
+                &tts_done_rx,
+                rt,
+                &interrupter.token().child(),
+                text,
+              );
+              crate::shell::set_offered(true);
+              if let Some(prompt) = crate::shell::take_confirmation_prompt() {
+                say_as_assistant(state, &prompt, &tx_ui, &tts_tx, &tts_done_rx, &interrupter);
+              }
+            }
+            Command::Announce => {
+              while let Some(announcement) = crate::announce::pop() {
+                let cancel = interrupter.token();
+                crate::announce::set_protected(
+                  announcement.priority == crate::announce::Priority::Important,
+                );
+                say_as_assistant(state, &announcement.text, &tx_ui, &tts_tx, &tts_done_rx, &interrupter);
+                crate::announce::set_protected(false);
+                if cancel.is_cancelled() {
+                  crate::announce::interrupted(announcement);
+                  break;
+                }
+              }
             }
           }
         }
       }
-      recv(rx_utt) -> msg => {
+      recv(rx_text) -> msg => {
         //  –––––––––––––––––––––––––––––––––––––
         //   user audio input handler
         //  –––––––––––––––––––––––––––––––––––––
-        let Ok(utt) = msg else { break };
+        let Ok(mut transcript) = msg else { break };
+        crate::state::advance_turn(&[TurnState::Transcribing], TurnState::Idle);
+        let utt = &transcript.utt;
         if let Some(ref wav_tx) = wav_tx_opt {
           wav_tx.send(utt.clone()).unwrap_or(());
         }
+        crate::captions::utterance(utt);
+
+        // wake phrases: ignored until one is said, then until a stop phrase
+        let agent = current_agent(GLOBAL_STATE.get().expect("AppState not initialized"), &settings);
+        let Some(text) = crate::wake::filter(&transcript.text, &agent, &tx_ui) else {
+          crate::interjection::release();
+          continue;
+        };
+        transcript.text = text;
+
+        // --dictate: written down, not answered
+        if crate::dictate::is_enabled() {
+          crate::log::log("info", &format!("Dictated: '{}'", transcript.text));
+          crate::dictate::write(&crate::plugins::filter_transcript(transcript.text.trim()), &tx_ui);
+          continue;
+        }
 
+        // --meeting: noted, only the summary is ever spoken
+        if crate::meeting::is_enabled() {
+          let text = crate::plugins::filter_transcript(transcript.text.trim());
+          if crate::meeting::is_request(&text) {
+            let state = GLOBAL_STATE.get().expect("AppState not initialized");
+            let summary = crate::meeting::summary();
+            say_as_assistant(state, &summary, &tx_ui, &tts_tx, &tts_done_rx, &interrupter);
+          } else if !text.is_empty() {
+            crate::meeting::record(&text, &tx_ui);
+          }
+          continue;
+        }
+
+        // the last reply still being spoken: this turn waits for it
         let state = GLOBAL_STATE.get().expect("AppState not initialized");
+        if wait_for_reply_spoken(state, &interrupter) {
+          crate::telemetry::feature("queued_turn");
+        }
+
+        crate::traces::begin_turn();
+        crate::telemetry::feature("voice_turn");
         state.conversation_paused.store(false, Ordering::Relaxed);
-        // start rendering for this turn (agent response to user query)
-        state.processing_response.store(true, Ordering::Relaxed);
-        let pcm_f32: Vec<f32> = utt.data.clone();
-        let mono_f32 = crate::audio::convert_to_mono(&utt);
 
         crate::log::log("debug", &format!("Received audio chunk of len {}", utt.data.len()));
-        crate::log::log("debug", &format!("Received mono f32 pcm len {}", pcm_f32.len()));
-        crate::log::log("debug", "Transcribing utterance...");
-        let state = GLOBAL_STATE.get().expect("AppState not initialized");
-        let user_text = crate::stt::whisper_transcribe_with_ctx(&ctx, &mono_f32, utt.sample_rate, &state.language.lock().unwrap())?;
-        crate::log::log("info", &format!("Transcribed: '{}'", user_text));
+        transcript.trace();
+        crate::log::log("info", &format!("Transcribed: '{}'", transcript.text));
+        let user_text = crate::plugins::filter_transcript(transcript.text.trim());
         let system_prompt = {
           let state = GLOBAL_STATE.get().expect("AppState not initialized");
-          state.system_prompt.lock().unwrap().clone()
+          let prompt = state.system_prompt.lock().unwrap_or_else(PoisonError::into_inner).clone();
+          crate::styles::system_prompt(&crate::followup::system_prompt(&prompt, &current_agent(state, &settings)))
         };
-        let hist = conversation_history.lock().unwrap();
+        let hist = conversation_history.lock().unwrap_or_else(PoisonError::into_inner);
         let mut messages = Vec::new();
-        messages.push(ChatMessage{role:"system".to_string(), content:system_prompt.replace("\\n", "\n"), agent_name:None});
+        messages.push(ChatMessage{role:"system".to_string(), content:system_prompt.replace("\\n", "\n"), agent_name:None, meta:None});
 
         for m in hist.iter() {
           messages.push(m.clone());
         }
         // Release the conversation history lock before re-acquiring it to push the user message
         std::mem::drop(hist);
-        messages.push(ChatMessage{role:"user".to_string(), content:user_text.clone(), agent_name:None});
+        messages.push(ChatMessage{role:"user".to_string(), content:user_text.clone(), agent_name:None, meta:None});
 
         let user_text = user_text.trim().to_string();
         let speech_end_ms = crate::util::SPEECH_END_AT.load(std::sync::atomic::Ordering::SeqCst);
         let mut first_phrase_logged = false;
         if user_text.is_empty() {
           crate::log::log("debug", "Transcription returned empty string");
+          crate::ignored::record(crate::ignored::Reason::NoSpeech);
           continue;
         }
 
-        let my_interrupt = interrupt_counter.load(Ordering::SeqCst);
-        if handle_interruption(&interrupt_counter, my_interrupt) {
-          interrupt_counter.store(my_interrupt, Ordering::SeqCst);
+        // said over the reply, paused meanwhile: a command lets it go on,
+        // anything else cuts it off as a barge-in does
+        if crate::interjection::is_held() {
+          let interjection = crate::interjection::classify(&user_text);
+          if let Interjection::Command(control) = interjection {
+            let line = crate::interjection::apply(control);
+            let _ = tx_ui.send(format!("line|\x1b[90m{}\x1b[0m", line));
+            crate::interjection::release();
+            continue;
+          }
+          *state.playback.volume.lock().unwrap_or_else(PoisonError::into_inner) = 0.0;
+          interrupter.interrupt();
+          let _ = stop_play_tx.try_send(());
+          let _ = tx_ui.send("user_interrupt_show|".to_string());
+          crate::events::emit(TurnEvent::Interrupted);
+          state.turn.advance(
+            &[TurnState::Generating, TurnState::Speaking],
+            TurnState::Interrupted,
+          );
+          crate::interjection::release();
+          if interjection == Interjection::Stop {
+            crate::telemetry::feature("interjection_stop");
+            continue;
+          }
+        }
+
+        let cancel = interrupter.token().child();
+        let talked_over = last_reply_token.take().is_some_and(|token| token.is_cancelled());
+        if talked_over {
+          crate::telemetry::feature("barge_in");
+          mark_reply_interrupted(&conversation_history);
+        }
+
+        // "switch to the work context": another conversation from now on
+        if let Some(name) = crate::contexts::request(&user_text)
+          && !state.debate_enabled.load(Ordering::SeqCst)
+        {
+          let line = crate::contexts::switch(&name);
+          say_as_assistant(state, &line, &tx_ui, &tts_tx, &tts_done_rx, &interrupter);
           continue;
         }
 
+        // "switch to the chef": another persona, or back to an agent
+        if let Some(name) = crate::personas::request(&user_text)
+          && !state.debate_enabled.load(Ordering::SeqCst)
+        {
+          let line = crate::personas::switch(&name);
+          say_as_assistant(state, &line, &tx_ui, &tts_tx, &tts_done_rx, &interrupter);
+          continue;
+        }
+
+        crate::events::emit(TurnEvent::Transcript(user_text.clone()));
         // Clear STOP_STREAM flag to ensure user text displays fully
         crate::ui::STOP_STREAM.store(false, Ordering::Relaxed);
         send_user_message_ui(&tx_ui, &user_text, false);
         push_user_message(&conversation_history, &user_text);
+        crate::punctuate::spawn(&user_text, transcript.confidence);
         perform_save(&conversation_history, &settings_clone);
 
         // Check if debate mode is enabled
@@ -470,7 +662,7 @@ pub fn conversation_thread(
           // User has interrupted the debate with new input
           // Update debate subject and continue debate
           {
-            let mut subject = state.debate_subject.lock().unwrap();
+            let mut subject = state.debate_subject.lock().unwrap_or_else(PoisonError::into_inner);
             *subject = user_text.clone();
           }
           // Stop playback immediately
@@ -480,43 +672,162 @@ pub fn conversation_thread(
           continue;
         }
 
-        ui.thinking.store(true, Ordering::Relaxed);
+        // "do not disturb": the microphone is off from now on
+        if crate::dnd::is_request(&user_text) {
+          crate::dnd::set_active(true);
+          say_as_assistant(
+            state,
+            "Do not disturb is on. Press z to turn it off.",
+            &tx_ui,
+            &tts_tx,
+            &tts_done_rx,
+            &interrupter,
+          );
+          continue;
+        }
+
+        // "rapid mode": no wake phrase and a shorter end of speech, for a while
+        crate::rapid::touch();
+        if let Some(on) = crate::rapid::request(&user_text) {
+          crate::telemetry::feature("rapid_mode");
+          crate::rapid::set_active(on);
+          let line = if on {
+            "Rapid mode is on. Press r or say normal mode to turn it off."
+          } else {
+            "Rapid mode is off."
+          };
+          say_as_assistant(state, line, &tx_ui, &tts_tx, &tts_done_rx, &interrupter);
+          continue;
+        }
+
+        // the answer to "Shall I go on?" after a long reply
+        match crate::continuation::answer(&user_text) {
+          crate::continuation::Answer::Go(rest) => {
+            crate::telemetry::feature("reply_continued");
+            speak_rest(state, rest, &tx_ui, &tts_tx, &interrupter);
+            continue;
+          }
+          crate::continuation::Answer::Stop => continue,
+          crate::continuation::Answer::Other => {}
+        }
+
+        // "fast mode": the next utterances transcribed with the tiny model
+        if let Some(on) = crate::fast::request(&user_text) {
+          crate::telemetry::feature("fast_stt");
+          crate::fast::set_active(on);
+          let line = if on {
+            "Fast mode is on. Press f or say accurate mode to turn it off."
+          } else {
+            "Accurate mode is on."
+          };
+          say_as_assistant(state, line, &tx_ui, &tts_tx, &tts_done_rx, &interrupter);
+          continue;
+        }
+
+        // "spell that": the last token of the reply, letter by letter
+        if let Some(spelling) = crate::spell::request(&user_text) {
+          crate::telemetry::feature("spell");
+          let reply = last_reply(&conversation_history).unwrap_or_default();
+          let text = match crate::spell::entity(&reply) {
+            Some(word) => crate::spell::spell(word, spelling),
+            None => "There is nothing to spell yet.".to_string(),
+          };
+          say_as_assistant(state, &text, &tx_ui, &tts_tx, &tts_done_rx, &interrupter);
+          continue;
+        }
+
+        // "save that": the voice of the last reply and its text, to keep
+        if crate::clips::request(&user_text) {
+          crate::telemetry::feature("save_reply");
+          let text = match crate::clips::save() {
+            Ok(_) => "Saved to your replies folder.".to_string(),
+            Err(e) => format!("{}.", e),
+          };
+          say_as_assistant(state, &text, &tx_ui, &tts_tx, &tts_done_rx, &interrupter);
+          continue;
+        }
+
+        // shell commands: the answer to a confirmation, or "run <name>"
+        match crate::shell::intercept(&user_text, &current_agent(state, &settings)) {
+          Some(crate::shell::Intercept::Say(text)) => {
+            say_as_assistant(state, &text, &tx_ui, &tts_tx, &tts_done_rx, &interrupter);
+            continue;
+          }
+          Some(crate::shell::Intercept::Ask(prompt)) => {
+            if let Some(last) = messages.last_mut() {
+              last.content = prompt;
+            }
+          }
+          None => {}
+        }
+
+        // "run my standup prompt": the stored prompt is sent instead, and kept
+        if let Some(prompt) = crate::snippets::expand(&user_text) {
+          crate::telemetry::feature("snippet");
+          if let Some(last) = messages.last_mut() {
+            last.content = prompt.clone();
+          }
+          if let Some(last) = conversation_history.lock().unwrap_or_else(PoisonError::into_inner).last_mut() {
+            last.content = prompt;
+          }
+        }
+
+        // the last reply was talked over: acknowledged before the answer
+        let ack = current_agent(state, &settings).barge_in_ack;
+        if talked_over && !ack.trim().is_empty() {
+          crate::log::log("debug", &format!("Barge-in acknowledged: '{}'", ack));
+          let voice = state.voice.lock().unwrap_or_else(PoisonError::into_inner).clone();
+          let _ = tts_tx.send((crate::util::strip_special_chars(&ack), cancel.clone(), voice));
+        }
+        last_reply_token = Some(cancel.clone());
+
+        state.turn.set(TurnState::Generating);
 
-        // Snapshot interruption counter for this assistant turn.
         let speaker_arc = std::sync::Arc::new(std::sync::Mutex::new(PhraseSpeaker::new()));
+        // --voice-styles: the tags are spoken, not shown
+        let stripper = std::sync::Arc::new(std::sync::Mutex::new(crate::styles::Stripper::default()));
+        let stripper_cloned_for_closure = stripper.clone();
+        // --max-spoken-seconds: the phrases over it are held
+        let budget = std::sync::Arc::new(std::sync::Mutex::new(crate::continuation::Budget::new()));
+        let budget_cloned_for_closure = budget.clone();
         let mut got_any_token = false;
 
         let _ = tx_ui.send("line|".to_string());
-        let _ = tx_ui.send(format!("line|{}", crate::ui::ASSIST_LABEL));
+        let _ = tx_ui.send(format!("line|{}", crate::ui::assist_label()));
 
         // clones for the on_piece closure
         let speaker_arc_cloned_for_closure = speaker_arc.clone();
         let tx_ui_cloned_for_closure = tx_ui.clone();
         let tts_tx_cloned_for_closure = tts_tx.clone();
-        let ui_thinking_cloned_for_closure = ui.thinking.clone();
-        // clones for closure
-        let ui_thinking_for_closure = ui_thinking_cloned_for_closure.clone();
+        let cancel_cloned_for_closure = cancel.clone();
         // Capture conversation history and assistant name for history updates
         let conv_hist_for_closure = conversation_history.clone();
         let assistant_name_for_closure = settings_clone.name.clone();
 
         // called on every chunk received from llm
-        let voice_for_tts = state.voice.lock().unwrap().clone();
+        let voice_for_tts = state.voice.lock().unwrap_or_else(PoisonError::into_inner).clone();
         let voice_for_tts_inner = voice_for_tts.clone();
         // Clone for use inside closure
 
         // reply accumulator for single ChatMessage
         let reply_accum = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
         let reply_accum_cloned = reply_accum.clone();
+        // timings kept with the reply
+        let llm_started = std::time::Instant::now();
+        let first_token_at = std::sync::Arc::new(std::sync::Mutex::new(None));
+        let first_token_at_cloned = first_token_at.clone();
         let on_piece = move |piece: &str| {
           if piece.is_empty() {
             return;
           }
+          crate::events::emit(TurnEvent::Token(piece.to_string()));
           if !got_any_token && !piece.is_empty() {
             got_any_token = true;
-            ui_thinking_for_closure.store(false, Ordering::Relaxed);
+            crate::state::advance_turn(&[TurnState::Generating], TurnState::Speaking);
+            crate::traces::first_token();
+            *first_token_at_cloned.lock().unwrap_or_else(PoisonError::into_inner) = Some(std::time::Instant::now());
           }
-          if let Some(phrase) = speaker_arc_cloned_for_closure.lock().unwrap().push_text(piece) {
+          if let Some(phrase) = speaker_arc_cloned_for_closure.lock().unwrap_or_else(PoisonError::into_inner).push_text(piece) {
             if !first_phrase_logged {
               let elapsed_ms = crate::util::now_ms(&START_INSTANT) - speech_end_ms;
               crate::log::log("info", &format!("Time from speech end to first phrase playback: {:.2?}", elapsed_ms));
@@ -524,18 +835,20 @@ pub fn conversation_thread(
             }
               // accumulate reply for single ChatMessage
             if let Ok(mut acc) = reply_accum_cloned.lock() {
-              acc.push_str(&phrase);
+              acc.push_str(&crate::styles::strip(&phrase));
               acc.push(' ');
             }
             // send the complete phrase to tts
-            let mut cleaned = crate::util::strip_special_chars(&phrase);
-            cleaned.push(' ');
-            crate::log::log("info", &format!("Sending phrase to TTS: '{}' (original: '{}'), interrupt={}", cleaned, phrase, my_interrupt));
-            let _ = tts_tx_cloned_for_closure.send((cleaned, my_interrupt, voice_for_tts_inner.clone()));
+            if budget_cloned_for_closure.lock().unwrap_or_else(PoisonError::into_inner).admit(&phrase) {
+              let mut cleaned = crate::styles::strip_special_chars(&phrase);
+              cleaned.push(' ');
+              crate::log::log("info", &format!("Sending phrase to TTS: '{}' (original: '{}')", cleaned, phrase));
+              let _ = tts_tx_cloned_for_closure.send((cleaned, cancel_cloned_for_closure.clone(), voice_for_tts_inner.clone()));
+            }
           }
 
           // send raw piece immediately
-          let mut ui_piece = piece.to_string();
+          let mut ui_piece = stripper_cloned_for_closure.lock().unwrap_or_else(PoisonError::into_inner).push(piece);
           if ui_piece.ends_with('.') || ui_piece.ends_with('!') || ui_piece.ends_with('?') {
             ui_piece.push(' ');
           }
@@ -545,14 +858,16 @@ pub fn conversation_thread(
           push_or_update_last_assistant(&conv_hist_for_closure, piece, &assistant_name_for_closure);
         };
 
-        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
-        let ollama_url = state.baseurl.lock().unwrap().clone();
-        let interrupt_counter_cloned = interrupt_counter.clone();
-        let llama_url = state.baseurl.lock().unwrap().clone();
-        let model = state.model.lock().unwrap().clone();
-        let engine_type = state.provider.lock().unwrap().clone();
+        let rt = crate::util::runtime();
+        let ollama_url = state.baseurl.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let cancel_cloned = cancel.clone();
+        let llama_url = state.baseurl.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let model = state.model.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let engine_type = state.provider.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let llm_attributes = [("llm.provider", engine_type.clone().into()), ("llm.model", model.clone().into())];
+        let llm_span = crate::traces::start("llm");
 
-        if *state.provider.lock().unwrap() == "llama-server" {
+        if *state.provider.lock().unwrap_or_else(PoisonError::into_inner) == "llama-server" {
           let on_piece_cloned = std::sync::Arc::new(std::sync::Mutex::new(on_piece));
           let handle = std::thread::spawn(move || {
             rt.block_on(async {
@@ -560,14 +875,12 @@ pub fn conversation_thread(
                 &messages,
                 llama_url.as_str(),
                 model.as_str(),
-                engine_type.as_str(),
-                interrupt_counter_cloned.clone(),
-                my_interrupt,
-                &mut *on_piece_cloned.lock().unwrap()
+                engine_type.as_str(),                &cancel_cloned,
+                &mut *on_piece_cloned.lock().unwrap_or_else(PoisonError::into_inner)
               ).await {
                 Ok(_) => Ok(()),
                 Err(e) => {
-                  crate::log::log("error", &format!("llama server error: {e}. Make sure llama-server / llamafile is running"));
+                  crate::error::report(&e);
                   Err(e)
                 }
               }
@@ -576,7 +889,7 @@ pub fn conversation_thread(
           // ignore join result to prevent panic on llama server error
           let _join_result = handle.join();
         } else {
-          let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+          let rt = crate::util::runtime();
           let on_piece_cloned = std::sync::Arc::new(std::sync::Mutex::new(on_piece));
           let handle = std::thread::spawn(move || {
             rt.block_on(async {
@@ -585,14 +898,12 @@ pub fn conversation_thread(
                 ollama_url.as_str(),
                 model.as_str(),
                 engine_type.as_str(),
-
-                interrupt_counter_cloned.clone(),
-                my_interrupt,
-                &mut *on_piece_cloned.lock().unwrap()
+                &cancel_cloned,
+                &mut *on_piece_cloned.lock().unwrap_or_else(PoisonError::into_inner)
               ).await {
                 Ok(_) => Ok(()),
                 Err(e) => {
-                  crate::log::log("error", &format!("ollama error. {}. Make sure ollama is running and model '{}' is available", e, model.as_str()));
+                  crate::error::report(&e);
                   Err(e)
                 }
               }
@@ -601,7 +912,20 @@ pub fn conversation_thread(
           // ignore join result to prevent panic on llama server error
           let _join_result = handle.join();
         }
-        ui_thinking_cloned_for_closure.store(false, Ordering::Relaxed);
+        llm_span.end(&llm_attributes);
+        crate::traces::end_turn();
+        set_reply_meta(&conversation_history, TurnMeta {
+          model: state.model.lock().unwrap_or_else(PoisonError::into_inner).clone(),
+          voice: voice_for_tts.clone(),
+          stt_ms: Some(transcript.duration().as_millis() as u64),
+          stt_confidence: transcript.confidence,
+          first_token_ms: first_token_at
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .map(|at| (at - llm_started).as_millis() as u64),
+          llm_ms: Some(llm_started.elapsed().as_millis() as u64),
+          interrupted: cancel.is_cancelled(),
+        });
         // Prepare clones for post-closure use
         let speaker_arc_for_after = speaker_arc.clone();
         let reply_accum_for_after = reply_accum.clone();
@@ -609,17 +933,45 @@ pub fn conversation_thread(
         let voice_for_tts_for_after = voice_for_tts.clone();
 
         // Flush any remaining phrase from the speaker when stream ends
-        if let Some(last_phrase) = speaker_arc_for_after.lock().unwrap().flush() {
+        if let Some(last_phrase) = speaker_arc_for_after.lock().unwrap_or_else(PoisonError::into_inner).flush() {
           // accumulate reply
           if let Ok(mut acc) = reply_accum_for_after.lock() {
-            acc.push_str(&last_phrase);
+            acc.push_str(&crate::styles::strip(&last_phrase));
             acc.push(' ');
           }
         // send to TTS
-          let mut cleaned = crate::util::strip_special_chars(&last_phrase);
-          cleaned.push(' ');
-          let _ = tts_tx_for_after.send((cleaned, my_interrupt, voice_for_tts_for_after.clone()));
+          if budget.lock().unwrap_or_else(PoisonError::into_inner).admit(&last_phrase) {
+            let mut cleaned = crate::styles::strip_special_chars(&last_phrase);
+            cleaned.push(' ');
+            let _ = tts_tx_for_after.send((cleaned, cancel.clone(), voice_for_tts_for_after.clone()));
+          }
+        }
+        // --max-spoken-seconds: the rest waits for "Shall I go on?"
+        let budget = std::mem::take(&mut *budget.lock().unwrap_or_else(PoisonError::into_inner));
+        let paused = !cancel.is_cancelled() && ask_to_go_on(budget, &tx_ui, &tts_tx, &cancel, &voice_for_tts);
+        // --double-check-numbers: the dates and amounts said again
+        if !cancel.is_cancelled() && !paused {
+          let reply = reply_accum.lock().unwrap_or_else(PoisonError::into_inner).clone();
+          if let Some(check) = crate::numbers::double_check(&reply) {
+            crate::telemetry::feature("double_check_numbers");
+            let _ = tx_ui.send(format!("line|\x1b[90m{}\x1b[0m", check));
+            let _ = tts_tx.send((check, cancel.clone(), voice_for_tts.clone()));
+          }
+        }
+        let interrupted = cancel.is_cancelled();
+        end_reply(state, &tts_tx, interrupted);
+        if !interrupted {
+          let reply = reply_accum.lock().unwrap_or_else(PoisonError::into_inner).trim().to_string();
+          crate::events::emit(TurnEvent::Done(reply));
+          if let Some(prompt) = crate::shell::take_confirmation_prompt() {
+            say_as_assistant(state, &prompt, &tx_ui, &tts_tx, &tts_done_rx, &interrupter);
+          }
         }
+        let held = stripper.lock().unwrap_or_else(PoisonError::into_inner).flush();
+        if !held.is_empty() {
+          let _ = tx_ui.send(format!("stream|{}", held));
+        }
+        strip_style_tags(&conversation_history);
         // Persist conversation after streaming (same as handle_reply does at line 970)
         perform_save(&conversation_history, &settings_clone);
       }
@@ -635,8 +987,8 @@ pub fn conversation_thread(
 async fn get_response(
   messages: Vec<ChatMessage>,
   agent: &crate::config::AgentSettings,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-  let interrupt_counter = Arc::new(AtomicU64::new(0));
+) -> crate::error::Result<String> {
+  let cancel = CancellationToken::new();
   let mut result = String::new();
   let mut on_piece = |piece: &str| {
     result.push_str(piece);
@@ -646,30 +998,61 @@ async fn get_response(
     &agent.baseurl,
     &agent.model,
     &agent.provider,
-    interrupt_counter.clone(),
-    0,
+    &cancel,
     &mut on_piece,
   )
   .await?;
   Ok(result)
 }
 
+/// Settings of the agent currently selected (agents can be switched from the keyboard),
+/// with the persona and the tts backend switched to
+fn current_agent(
+  state: &AppState,
+  fallback: &crate::config::AgentSettings,
+) -> crate::config::AgentSettings {
+  let name = state
+    .agent_name
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let agent = state
+    .agents
+    .iter()
+    .find(|a| a.name == name)
+    .cloned()
+    .unwrap_or_else(|| fallback.clone());
+  crate::tts::apply_backend(crate::personas::apply(agent))
+}
+
 /// Persist conversation history if needed
 fn perform_save(
   conversation_history: &ConversationHistory,
   settings: &crate::config::AgentSettings,
 ) {
   let state = GLOBAL_STATE.get().expect("AppState not initialized");
-  let save_path = state.save_path.lock().unwrap().clone();
+  let save_path = state
+    .save_path
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
   if let Some(path) = save_path {
     let is_debate = state.debate_enabled.load(Ordering::SeqCst);
     let agents = if is_debate {
-      state.debate_agents.lock().unwrap().clone()
+      state
+        .debate_agents
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
     } else {
       vec![settings.clone()]
     };
     let metadata = SaveMetadata {
-      start_date: state.start_date.lock().unwrap().clone(),
+      start_date: state
+        .start_date
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone(),
       agents,
       is_debate,
       system_prompt: settings.system_prompt.clone(),
@@ -689,7 +1072,12 @@ fn maybe_setup_and_save(
     return Ok(());
   }
   let state = GLOBAL_STATE.get().expect("AppState not initialized");
-  if state.save_path.lock().unwrap().is_none() {
+  if state
+    .save_path
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .is_none()
+  {
     let now = Local::now();
     let date_str = now.format("%Y-%m-%d_%H-%M-%S").to_string();
     let uuid_str = &Uuid::new_v4().to_string()[..8];
@@ -699,14 +1087,28 @@ fn maybe_setup_and_save(
       .join("conversations")
       .join(format!("{}_{}.txt", date_str, uuid_str));
 
-    *state.save_path.lock().unwrap() = Some(path.clone());
-    *state.start_date.lock().unwrap() = date_str;
-
-    if let Some(txt_path) = state.save_path.lock().unwrap().clone() {
+    *state
+      .save_path
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = Some(path.clone());
+    *state
+      .start_date
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = date_str;
+
+    if let Some(txt_path) = state
+      .save_path
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone()
+    {
       let wav_path = txt_path.with_extension("wav");
       let (wav_tx, wav_rx) = crossbeam_channel::unbounded::<crate::audio::AudioChunk>();
       set_wav_tx(wav_tx.clone());
       std::thread::spawn(move || {
+        if crate::encryption::is_enabled() {
+          return write_encrypted_wav(&wav_path, wav_rx);
+        }
         let mut writer: Option<hound::WavWriter<std::io::BufWriter<std::fs::File>>> = None;
         while let Ok(chunk) = wav_rx.recv() {
           if writer.is_none() {
@@ -722,7 +1124,8 @@ fn maybe_setup_and_save(
           for s in samples {
             writer.as_mut().unwrap().write_sample(s).unwrap();
           }
-          let silence_samples = (chunk.sample_rate * 500 / 1000) as usize * chunk.channels as usize;
+          let silence_samples =
+            (chunk.sample_rate as u64 * RECORDING_GAP_MS / 1000) as usize * chunk.channels as usize;
           for _ in 0..silence_samples {
             writer.as_mut().unwrap().write_sample(0_i16).unwrap();
           }
@@ -735,16 +1138,28 @@ fn maybe_setup_and_save(
 
   // perform save
   let state = GLOBAL_STATE.get().expect("AppState not initialized");
-  let save_path = state.save_path.lock().unwrap().clone();
+  let save_path = state
+    .save_path
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
   if let Some(path) = save_path {
     let is_debate = state.debate_enabled.load(Ordering::SeqCst);
     let agents = if is_debate {
-      state.debate_agents.lock().unwrap().clone()
+      state
+        .debate_agents
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
     } else {
       vec![settings_clone.clone()]
     };
     let metadata = SaveMetadata {
-      start_date: state.start_date.lock().unwrap().clone(),
+      start_date: state
+        .start_date
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone(),
       agents,
       is_debate,
       system_prompt: settings_clone.system_prompt.clone(),
@@ -755,32 +1170,110 @@ fn maybe_setup_and_save(
   Ok(())
 }
 
+// The audio of a conversation saved with --encrypt, one record per chunk
+// (and the silence after it) so a session cut short keeps its audio.
+fn write_encrypted_wav(path: &std::path::Path, rx: Receiver<crate::audio::AudioChunk>) {
+  let mut writer: Option<crate::encryption::Writer> = None;
+  while let Ok(chunk) = rx.recv() {
+    if writer.is_none() {
+      let created = crate::encryption::Writer::create(path).and_then(|mut w| {
+        w.write_record(&crate::encryption::streaming_wav_header(
+          chunk.channels,
+          chunk.sample_rate,
+        ))?;
+        Ok(w)
+      });
+      match created {
+        Ok(w) => writer = Some(w),
+        Err(e) => {
+          crate::log::log("error", &format!("Failed to save the audio: {}", e));
+          return;
+        }
+      }
+    }
+    let silence_samples =
+      (chunk.sample_rate as u64 * RECORDING_GAP_MS / 1000) as usize * chunk.channels as usize;
+    let mut bytes: Vec<u8> = crate::audio::f32_to_i16(&chunk.data)
+      .iter()
+      .flat_map(|s| s.to_le_bytes())
+      .collect();
+    bytes.resize(bytes.len() + silence_samples * 2, 0);
+    if let Some(Err(e)) = writer.as_mut().map(|w| w.write_record(&bytes)) {
+      crate::log::log("error", &format!("Failed to save the audio: {}", e));
+      return;
+    }
+  }
+}
+
+// the first phrase of a reply ends at its first clause (after a comma with
+// this many words, or at most that many), so speech starts before the whole
+// first sentence is generated
+const FIRST_CLAUSE_MIN_WORDS: usize = 3;
+const FIRST_CLAUSE_MAX_WORDS: usize = 8;
+
 /// Emits phrases when punctuation/newline/length threshold happens.
-struct PhraseSpeaker {
+#[derive(Default)]
+pub struct PhraseSpeaker {
   buf: String,
+  // a phrase was emitted, the next ones are whole sentences
+  started: bool,
 }
 impl PhraseSpeaker {
-  fn new() -> Self {
-    Self { buf: String::new() }
+  pub fn new() -> Self {
+    Self::default()
   }
-  fn push_text(&mut self, s: &str) -> Option<String> {
+  pub fn push_text(&mut self, s: &str) -> Option<String> {
     self.buf.push_str(s);
     // cap phrases by new lines or dots
     let trigger = self.buf.contains('\n') || self.buf.ends_with('.');
-    if trigger { self.flush() } else { None }
+    if trigger {
+      return self.flush();
+    }
+    if !self.started
+      && let Some(end) = self.first_clause_end()
+    {
+      let rest = self.buf.split_off(end);
+      let phrase = self.flush();
+      self.buf = rest;
+      return phrase;
+    }
+    None
   }
-  fn flush(&mut self) -> Option<String> {
+  pub fn flush(&mut self) -> Option<String> {
     let out = self.buf.trim().to_string();
     self.buf.clear();
-    if out.is_empty() { None } else { Some(out) }
+    if out.is_empty() {
+      return None;
+    }
+    // response filters of the plugins see every phrase before it's spoken
+    let out = crate::plugins::filter_response(&out);
+    if out.trim().is_empty() {
+      None
+    } else {
+      self.started = true;
+      Some(out)
+    }
   }
-}
 
-fn handle_interruption(interrupt_counter: &Arc<AtomicU64>, current: u64) -> bool {
-  if interrupt_counter.load(Ordering::SeqCst) != current {
-    true
-  } else {
-    false
+  // End of the first clause in the buffer, once the space after it arrived
+  // (so "1,000" isn't cut)
+  fn first_clause_end(&self) -> Option<usize> {
+    // shorter with a latency target (--latency-target-ms)
+    let (min_words, max_words) = crate::latency::first_clause_words()
+      .unwrap_or((FIRST_CLAUSE_MIN_WORDS, FIRST_CLAUSE_MAX_WORDS));
+    let mut words = 0;
+    let mut prev: Option<char> = None;
+    for (i, c) in self.buf.char_indices() {
+      if c.is_whitespace() && prev.is_some_and(|p| !p.is_whitespace()) {
+        words += 1;
+        let clause = matches!(prev, Some(',' | ';' | ':'));
+        if (clause && words >= min_words) || words >= max_words {
+          return Some(i);
+        }
+      }
+      prev = Some(c);
+    }
+    None
   }
 }
 
@@ -788,34 +1281,39 @@ fn handle_undo(
   state: &AppState,
   tx_ui: &Sender<String>,
   conversation_history: &ConversationHistory,
-  interrupt_counter: &Arc<AtomicU64>,
+  interrupter: &Interrupter,
   stop_play_tx: &Sender<()>,
   settings: &crate::config::AgentSettings,
 ) {
   // Check if this undo was triggered during an ongoing response
-  // (keyboard thread sets this flag and increments the interrupt counter)
+  // (keyboard thread sets this flag and interrupts the reply)
   let was_interrupted = state.undo_pending.swap(false, Ordering::SeqCst);
+  crate::telemetry::feature("undo");
 
   // If a response was in progress, interrupt it (same as Esc)
   if was_interrupted {
     // Remove partial assistant message if present
-    let mut h = conversation_history.lock().unwrap();
-    if let Some(last) = h.last() {
-      if last.role == "assistant" {
-        h.pop();
-      }
+    let mut h = conversation_history
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner);
+    if let Some(last) = h.last()
+      && last.role == "assistant"
+    {
+      h.pop();
     }
     drop(h);
-    // Reset processing flag after interrupt
-    state.processing_response.store(false, Ordering::Relaxed);
-    interrupt_counter.fetch_add(1, Ordering::SeqCst);
+    state.turn.set(TurnState::Interrupted);
+    interrupter.interrupt();
     let _ = stop_play_tx.try_send(());
     let _ = tx_ui.send("user_interrupt_show|".to_string());
+    crate::events::emit(TurnEvent::Interrupted);
     // The interrupted response was NOT saved to history (interrupt check in streaming code),
     // so we do NOT pop — the user message that triggered it stays.
   } else {
     // No ongoing response: remove the last message from history
-    let mut h = conversation_history.lock().unwrap();
+    let mut h = conversation_history
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner);
     h.pop();
     drop(h);
   }
@@ -835,57 +1333,121 @@ fn push_or_update_last_assistant(
   new_piece: &str,
   agent_name: &str,
 ) {
-  let mut hist = conversation_history.lock().unwrap();
-  if let Some(last) = hist.last_mut() {
-    if last.role == "assistant" {
-      last.content.push_str(new_piece);
-      return;
-    }
+  let mut hist = conversation_history
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
+  if let Some(last) = hist.last_mut()
+    && last.role == "assistant"
+  {
+    last.content.push_str(&crate::util::strip_ansi(new_piece));
+    return;
   }
   hist.push(ChatMessage {
     role: "assistant".to_string(),
     content: new_piece.to_string(),
     agent_name: Some(agent_name.to_string()),
+    meta: None,
   });
 }
 
+// The reply just streamed (the last message) without its style tags, see
+// crate::styles
+fn strip_style_tags(conversation_history: &ConversationHistory) {
+  if !crate::styles::is_enabled() {
+    return;
+  }
+  let mut hist = conversation_history
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
+  if let Some(last) = hist.last_mut().filter(|m| m.role == "assistant") {
+    last.content = crate::styles::strip(&last.content);
+  }
+}
+
+// Attaches the metadata to the reply just streamed (the last message)
+// the text of the last reply of an agent
+fn last_reply(conversation_history: &ConversationHistory) -> Option<String> {
+  let hist = conversation_history
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
+  hist
+    .iter()
+    .rev()
+    .find(|m| m.role == "assistant")
+    .map(|m| m.content.clone())
+}
+
+fn set_reply_meta(conversation_history: &ConversationHistory, meta: TurnMeta) {
+  let mut hist = conversation_history
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
+  if let Some(last) = hist.last_mut().filter(|m| m.role == "assistant") {
+    last.meta = Some(meta);
+  }
+}
+
+// The last reply was talked over after it was saved as complete
+fn mark_reply_interrupted(conversation_history: &ConversationHistory) {
+  let mut hist = conversation_history
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
+  if let Some(meta) = hist
+    .last_mut()
+    .filter(|m| m.role == "assistant")
+    .and_then(|m| m.meta.as_mut())
+  {
+    meta.interrupted = true;
+  }
+}
+
 fn handle_reply(
   state: &AppState,
   settings: &crate::config::AgentSettings,
   conversation_history: &ConversationHistory,
   tx_ui: &Sender<String>,
-  tts_tx: &Sender<(String, u64, String)>,
+  tts_tx: &Sender<(String, CancellationToken, String)>,
   tts_done_rx: &Receiver<()>,
   rt: &tokio::runtime::Runtime,
-  interrupt_counter: &Arc<AtomicU64>,
+  cancel: &CancellationToken,
   user_msg: String,
 ) -> Option<String> {
   // Build messages for LLM
-  let system_prompt = settings.system_prompt.replace("\\n", "\n");
-  let messages =
-    create_full_context_messages(system_prompt, user_msg.clone(), conversation_history);
+  let system_prompt = crate::styles::system_prompt(&crate::followup::system_prompt(
+    &settings.system_prompt,
+    settings,
+  ));
+  let messages = create_full_context_messages(
+    system_prompt.replace("\\n", "\n"),
+    user_msg.clone(),
+    conversation_history,
+  );
 
-  let my_interrupt = interrupt_counter.load(Ordering::SeqCst);
+  state.turn.set(TurnState::Generating);
   // Speaker for incremental buffering
   let speaker_arc = Arc::new(Mutex::new(PhraseSpeaker::new()));
   let reply_accum = Arc::new(Mutex::new(String::new()));
   // Pre-add assistant placeholder to history for label display
-  conversation_history.lock().unwrap().push(ChatMessage {
-    role: "assistant".to_string(),
-    content: "".to_string(),
-    agent_name: Some(settings.name.clone()),
-  });
+  conversation_history
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .push(ChatMessage {
+      role: "assistant".to_string(),
+      content: "".to_string(),
+      agent_name: Some(settings.name.clone()),
+      meta: None,
+    });
   let originals = apply_agent_settings(state, settings);
   let assistant_name = settings.name.clone();
   let assistant_name_for_closure = assistant_name.clone();
-  let interrupt_counter_clone = interrupt_counter.clone();
-  let my_interrupt_clone = my_interrupt;
 
   // render assistant label
-  let label = format!("\x1b[48;5;22;37m{}:\x1b[0m", assistant_name);
+  let label = crate::ui::agent_label(&assistant_name);
   let _ = tx_ui.send("line|".to_string());
   let _ = tx_ui.send(format!("line|{}", label));
 
+  // timings kept with the reply
+  let llm_started = std::time::Instant::now();
+  let first_token_at = Arc::new(Mutex::new(None));
   let mut on_piece = {
     let speaker_arc = speaker_arc.clone();
     let reply_accum = reply_accum.clone();
@@ -893,10 +1455,22 @@ fn handle_reply(
     let tx_ui = tx_ui.clone();
     let voice = settings.voice.clone();
     let conversation_history = conversation_history.clone();
+    let first_token_at = first_token_at.clone();
+    let cancel = cancel.clone();
+    let mut got_any_token = false;
     move |piece: &str| {
       if piece.is_empty() {
         return;
       }
+      if !got_any_token {
+        got_any_token = true;
+        crate::state::advance_turn(&[TurnState::Generating], TurnState::Speaking);
+        crate::traces::first_token();
+        *first_token_at
+          .lock()
+          .unwrap_or_else(PoisonError::into_inner) = Some(std::time::Instant::now());
+      }
+      crate::events::emit(TurnEvent::Token(piece.to_string()));
       // Keep the partial reply in history while streaming, so the history can be
       // rendered in real‑time
       push_or_update_last_assistant(&conversation_history, piece, &assistant_name);
@@ -906,36 +1480,59 @@ fn handle_reply(
       }
       // Buffer via speaker and get phrase (if delimiter reached)
       let phrase = {
-        let mut speaker = speaker_arc.lock().unwrap();
+        let mut speaker = speaker_arc.lock().unwrap_or_else(PoisonError::into_inner);
         speaker.push_text(piece)
       };
       if let Some(ref phrase) = phrase {
-        let _ = tx_ui.send(format!("stream|{}", phrase));
+        let _ = tx_ui.send(format!("stream|{}", crate::styles::strip(phrase)));
         let _ = tx_ui.send("line|".to_string());
         // TTS
-        let _ = tts_tx.send((phrase.clone(), my_interrupt, voice.clone()));
+        let _ = tts_tx.send((phrase.clone(), cancel.clone(), voice.clone()));
         let _ = tts_done_rx.recv();
       }
-      if interrupt_counter_clone.load(Ordering::SeqCst) != my_interrupt_clone {
-        if let Some(rem) = speaker_arc.lock().unwrap().flush() {
-          // Prevents the partially‑generated text from being lost when the user interrupts
-          push_or_update_last_assistant(&conversation_history, &rem, &assistant_name);
-        }
+      if cancel.is_cancelled()
+        && let Some(rem) = speaker_arc
+          .lock()
+          .unwrap_or_else(PoisonError::into_inner)
+          .flush()
+      {
+        // Prevents the partially‑generated text from being lost when the user interrupts
+        push_or_update_last_assistant(&conversation_history, &rem, &assistant_name);
       }
     }
   };
 
+  let llm_span = crate::traces::start("llm");
   let stream_result = rt.block_on(crate::llm::llama_server_stream_response_into(
     &messages,
     &settings.baseurl,
     &settings.model,
     &settings.provider,
-    interrupt_counter.clone(),
-    my_interrupt,
+    cancel,
     &mut on_piece,
   ));
+  llm_span.end(&[
+    ("llm.provider", settings.provider.clone().into()),
+    ("llm.model", settings.model.clone().into()),
+  ]);
+  crate::traces::end_turn();
+  set_reply_meta(
+    conversation_history,
+    TurnMeta {
+      model: settings.model.clone(),
+      voice: settings.voice.clone(),
+      first_token_ms: first_token_at
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .map(|at| (at - llm_started).as_millis() as u64),
+      llm_ms: Some(llm_started.elapsed().as_millis() as u64),
+      interrupted: cancel.is_cancelled(),
+      ..Default::default()
+    },
+  );
   if let Err(e) = stream_result {
-    crate::log::log("error", &format!("Streaming error: {}", e));
+    crate::error::report(&e);
+    end_reply(state, tts_tx, cancel.is_cancelled());
     restore_agent_settings(state, originals);
     // Persist conversation on interruption
     perform_save(&conversation_history, settings);
@@ -943,9 +1540,13 @@ fn handle_reply(
   }
 
   // Flush remaining phrase
-  if let Some(last_phrase) = speaker_arc.lock().unwrap().flush() {
-    let _ = tts_tx.send((last_phrase.clone(), my_interrupt, settings.voice.clone()));
-    let _ = tx_ui.send(format!("stream|{}", last_phrase));
+  if let Some(last_phrase) = speaker_arc
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .flush()
+  {
+    let _ = tts_tx.send((last_phrase.clone(), cancel.clone(), settings.voice.clone()));
+    let _ = tx_ui.send(format!("stream|{}", crate::styles::strip(&last_phrase)));
     let _ = tx_ui.send("line|".to_string());
     // Add the final, un‑puncuated fragment to the history
     // (handles replies that end without a punctuation mark or newline)
@@ -958,28 +1559,37 @@ fn handle_reply(
 
   // Final reply string
   let reply = {
-    let mut acc = reply_accum.lock().unwrap();
-    let cloned = acc.clone();
+    let mut acc = reply_accum.lock().unwrap_or_else(PoisonError::into_inner);
+    let cloned = crate::styles::strip(&acc);
     acc.clear();
     cloned
   };
   // If interrupted, flush any remaining buffered text to history
-  if interrupt_counter.load(Ordering::SeqCst) != my_interrupt {
-    if let Some(rem) = speaker_arc.lock().unwrap().flush() {
-      // Flushes any remaining buffered text if the user interrupted
-      // after streaming but before the conversation was saved
-      // (covers the edge‑case where the user hits Esc right after the stream ends
-      // but before the conversation file is written )
-      push_or_update_last_assistant(&conversation_history, &rem, &assistant_name_for_closure);
-    }
+  if cancel.is_cancelled()
+    && let Some(rem) = speaker_arc
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .flush()
+  {
+    // Flushes any remaining buffered text if the user interrupted
+    // after streaming but before the conversation was saved
+    // (covers the edge‑case where the user hits Esc right after the stream ends
+    // but before the conversation file is written )
+    push_or_update_last_assistant(&conversation_history, &rem, &assistant_name_for_closure);
+  }
+
+  if !cancel.is_cancelled() {
+    crate::events::emit(TurnEvent::Done(reply.clone()));
   }
 
   // Persist conversation after streaming
+  strip_style_tags(conversation_history);
   perform_save(&conversation_history, settings);
 
   // Restore settings and wait playback
   restore_agent_settings(state, originals);
-  wait_for_playback(state, &interrupt_counter, my_interrupt);
+  end_reply(state, tts_tx, cancel.is_cancelled());
+  wait_for_playback(state, cancel);
   Some(reply)
 }
 
@@ -1005,7 +1615,7 @@ fn split_into_phrases(text: &str) -> Vec<String> {
 
 fn send_user_message_ui(tx_ui: &Sender<String>, text: &str, use_stream: bool) {
   let _ = tx_ui.send("line|\n".to_string());
-  let _ = tx_ui.send(format!("line|{}", crate::ui::USER_LABEL));
+  let _ = tx_ui.send(format!("line|{}", crate::ui::user_label()));
   let msg = if use_stream {
     format!("stream|{}", text)
   } else {
@@ -1016,50 +1626,184 @@ fn send_user_message_ui(tx_ui: &Sender<String>, text: &str, use_stream: bool) {
 }
 
 fn push_user_message(history: &ConversationHistory, text: &str) {
-  history.lock().unwrap().push(ChatMessage {
-    role: "user".to_string(),
-    content: text.to_string(),
-    agent_name: None,
-  });
+  history
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .push(ChatMessage {
+      role: "user".to_string(),
+      content: text.to_string(),
+      agent_name: None,
+      meta: None,
+    });
 }
 
-fn wait_for_playback(
-  state: &crate::state::AppState,
-  interrupt_counter: &Arc<AtomicU64>,
-  my_interrupt: u64,
-) {
+fn wait_for_playback(state: &crate::state::AppState, cancel: &CancellationToken) {
   let playback_active = state.playback.playback_active.clone();
   // Wait until playback starts if it hasn't already
   while !playback_active.load(Ordering::SeqCst) {
-    if interrupt_counter.load(Ordering::SeqCst) != my_interrupt {
+    if cancel.is_cancelled() {
       return;
     }
     thread::sleep(Duration::from_millis(10));
   }
   // Playback is active, wait until it stops
   while playback_active.load(Ordering::SeqCst) {
-    if interrupt_counter.load(Ordering::SeqCst) != my_interrupt {
+    if cancel.is_cancelled() {
       return;
     }
     thread::sleep(Duration::from_millis(10));
   }
 }
 
+/// Holds a new turn back while the last reply is still being spoken, so
+/// their phrases don't mix. Talking over the reply (an interruption) or
+/// pausing it lets the turn go. Returns whether it waited.
+fn wait_for_reply_spoken(state: &AppState, interrupter: &Interrupter) -> bool {
+  let cancel = interrupter.token();
+  let speaking =
+    || state.turn.get() == TurnState::Speaking && !state.playback.paused.load(Ordering::Relaxed);
+  if !speaking() {
+    return false;
+  }
+  crate::log::log("info", "Utterance queued until the reply is spoken");
+  while speaking() && !cancel.is_cancelled() {
+    thread::sleep(Duration::from_millis(20));
+  }
+  true
+}
+
+// quiet needed before the voice is taken to be done with a reply
+const SPEECH_SETTLE: Duration = Duration::from_millis(200);
+
+/// Moves the turn on once the llm is done with a reply: Interrupted when it
+/// was cut short, else Idle once the voice has said all of it.
+fn end_reply(
+  state: &AppState,
+  tts_tx: &Sender<(String, CancellationToken, String)>,
+  interrupted: bool,
+) {
+  let replying = [TurnState::Generating, TurnState::Speaking];
+  if interrupted {
+    state.turn.advance(&replying, TurnState::Interrupted);
+    return;
+  }
+  if !speech_pending(state, tts_tx) {
+    state.turn.advance(&replying, TurnState::Idle);
+    return;
+  }
+  state
+    .turn
+    .advance(&[TurnState::Generating], TurnState::Speaking);
+  let tts_tx = tts_tx.clone();
+  thread::spawn(move || {
+    let state = GLOBAL_STATE.get().expect("AppState not initialized");
+    // the gap between a phrase synthesized and its playback isn't the end
+    let mut quiet_since: Option<std::time::Instant> = None;
+    while state.turn.get() == TurnState::Speaking {
+      if speech_pending(state, &tts_tx) {
+        quiet_since = None;
+      } else if quiet_since
+        .get_or_insert_with(std::time::Instant::now)
+        .elapsed()
+        >= SPEECH_SETTLE
+      {
+        state.turn.advance(&[TurnState::Speaking], TurnState::Idle);
+        break;
+      }
+      thread::sleep(Duration::from_millis(20));
+    }
+  });
+}
+
+// phrases queued, being synthesized or played
+fn speech_pending(state: &AppState, tts_tx: &Sender<(String, CancellationToken, String)>) -> bool {
+  !tts_tx.is_empty()
+    || crate::tts::is_synthesizing()
+    || state.playback.playback_active.load(Ordering::Relaxed)
+}
+
+// Asks "Shall I go on?" when phrases of the reply were held, true if so
+fn ask_to_go_on(
+  budget: crate::continuation::Budget,
+  tx_ui: &Sender<String>,
+  tts_tx: &Sender<(String, CancellationToken, String)>,
+  cancel: &CancellationToken,
+  voice: &str,
+) -> bool {
+  let Some(question) = budget.pause() else {
+    return false;
+  };
+  let _ = tx_ui.send(format!("line|\x1b[90m{}\x1b[0m", question));
+  let _ = tts_tx.send((question.to_string(), cancel.clone(), voice.to_string()));
+  true
+}
+
+/// Speaks the rest of a reply paused by --max-spoken-seconds, already shown,
+/// asking again once over the limit.
+fn speak_rest(
+  state: &AppState,
+  rest: Vec<String>,
+  tx_ui: &Sender<String>,
+  tts_tx: &Sender<(String, CancellationToken, String)>,
+  interrupter: &Interrupter,
+) {
+  let cancel = interrupter.token().child();
+  state.turn.set(TurnState::Speaking);
+  let voice = state
+    .voice
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let mut budget = crate::continuation::Budget::new();
+  for phrase in rest {
+    if budget.admit(&phrase) {
+      let mut cleaned = crate::util::strip_special_chars(&phrase);
+      cleaned.push(' ');
+      let _ = tts_tx.send((cleaned, cancel.clone(), voice.clone()));
+    }
+  }
+  if !cancel.is_cancelled() {
+    ask_to_go_on(budget, tx_ui, tts_tx, &cancel, &voice);
+  }
+  end_reply(state, tts_tx, cancel.is_cancelled());
+}
+
+/// Shows and speaks a text as the assistant, without the llm.
+fn say_as_assistant(
+  state: &AppState,
+  text: &str,
+  tx_ui: &Sender<String>,
+  tts_tx: &Sender<(String, CancellationToken, String)>,
+  tts_done_rx: &Receiver<()>,
+  interrupter: &Interrupter,
+) {
+  let cancel = interrupter.token().child();
+  state.turn.set(TurnState::Speaking);
+  let _ = tx_ui.send(format!("line|{}", text));
+  let voice = state
+    .voice
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  process_tts_phrases(text, tts_tx, tts_done_rx, voice, &cancel);
+  end_reply(state, tts_tx, cancel.is_cancelled());
+  crate::events::emit(TurnEvent::Done(text.to_string()));
+}
+
 fn process_tts_phrases(
   reply: &str,
-  tts_tx: &Sender<(String, u64, String)>,
+  tts_tx: &Sender<(String, CancellationToken, String)>,
   tts_done_rx: &Receiver<()>,
   voice: String,
-  interrupt_counter: &Arc<AtomicU64>,
-  my_interrupt: u64,
+  cancel: &CancellationToken,
 ) {
   let phrases = split_into_phrases(reply);
   for phrase in phrases {
-    if interrupt_counter.load(Ordering::SeqCst) != my_interrupt {
+    if cancel.is_cancelled() {
       break;
     }
     let cleaned = crate::util::strip_special_chars(&phrase);
-    let _ = tts_tx.send((cleaned, my_interrupt, voice.clone()));
+    let _ = tts_tx.send((cleaned, cancel.clone(), voice.clone()));
     let _ = tts_done_rx.recv();
   }
 }
@@ -1070,11 +1814,13 @@ fn create_basic_messages(system_prompt: String, user_msg: String) -> Vec<ChatMes
       role: "system".to_string(),
       content: system_prompt,
       agent_name: None,
+      meta: None,
     },
     ChatMessage {
       role: "user".to_string(),
       content: user_msg,
       agent_name: None,
+      meta: None,
     },
   ]
 }
@@ -1091,9 +1837,12 @@ fn create_full_context_messages(
     role: "system".to_string(),
     content: system_prompt,
     agent_name: None,
+    meta: None,
   });
   // history messages
-  let hist = conversation_history.lock().unwrap();
+  let hist = conversation_history
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
   for m in hist.iter() {
     messages.push(m.clone());
   }
@@ -1102,6 +1851,7 @@ fn create_full_context_messages(
     role: "user".to_string(),
     content: user_msg,
     agent_name: None,
+    meta: None,
   });
   messages
 }
@@ -1121,24 +1871,61 @@ fn apply_agent_settings(
   u32,
 ) {
   // Store original settings
-  let original_voice = state.voice.lock().unwrap().clone();
-  let original_tts = state.tts.lock().unwrap().clone();
-  let original_language = state.language.lock().unwrap().clone();
-  let original_baseurl = state.baseurl.lock().unwrap().clone();
-  let original_provider = state.provider.lock().unwrap().clone();
-  let original_model = state.model.lock().unwrap().clone();
-  let original_system_prompt = state.system_prompt.lock().unwrap().clone();
+  let original_voice = state
+    .voice
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let original_tts = state
+    .tts
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let original_language = state
+    .language
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let original_baseurl = state
+    .baseurl
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let original_provider = state
+    .provider
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let original_model = state
+    .model
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let original_system_prompt = state
+    .system_prompt
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
   let original_ptt = state.ptt.load(std::sync::atomic::Ordering::Relaxed);
   let original_speed = state.speed.load(std::sync::atomic::Ordering::Relaxed);
 
   // Apply new agent settings
-  *state.voice.lock().unwrap() = agent.voice.clone();
-  *state.tts.lock().unwrap() = agent.tts.clone();
-  *state.language.lock().unwrap() = agent.language.clone();
-  *state.baseurl.lock().unwrap() = agent.baseurl.clone();
-  *state.provider.lock().unwrap() = agent.provider.clone();
-  *state.model.lock().unwrap() = agent.model.clone();
-  *state.system_prompt.lock().unwrap() = agent.system_prompt.clone();
+  *state.voice.lock().unwrap_or_else(PoisonError::into_inner) = agent.voice.clone();
+  *state.tts.lock().unwrap_or_else(PoisonError::into_inner) = agent.tts.clone();
+  *state
+    .language
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner) = agent.language.clone();
+  *state.baseurl.lock().unwrap_or_else(PoisonError::into_inner) = agent.baseurl.clone();
+  *state
+    .provider
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner) = agent.provider.clone();
+  *state.model.lock().unwrap_or_else(PoisonError::into_inner) = agent.model.clone();
+  *state
+    .system_prompt
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner) = agent.system_prompt.clone();
   state
     .ptt
     .store(agent.ptt, std::sync::atomic::Ordering::Relaxed);
@@ -1175,19 +1962,41 @@ fn restore_agent_settings(
   ),
 ) {
   let (voice, tts, language, baseurl, provider, model, system_prompt, ptt, speed) = originals;
-  *state.voice.lock().unwrap() = voice;
-  *state.tts.lock().unwrap() = tts;
-  *state.language.lock().unwrap() = language;
-  *state.baseurl.lock().unwrap() = baseurl;
-  *state.provider.lock().unwrap() = provider;
-  *state.model.lock().unwrap() = model;
-  *state.system_prompt.lock().unwrap() = system_prompt;
+  *state.voice.lock().unwrap_or_else(PoisonError::into_inner) = voice;
+  *state.tts.lock().unwrap_or_else(PoisonError::into_inner) = tts;
+  *state
+    .language
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner) = language;
+  *state.baseurl.lock().unwrap_or_else(PoisonError::into_inner) = baseurl;
+  *state
+    .provider
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner) = provider;
+  *state.model.lock().unwrap_or_else(PoisonError::into_inner) = model;
+  *state
+    .system_prompt
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner) = system_prompt;
   state.ptt.store(ptt, std::sync::atomic::Ordering::Relaxed);
   state
     .speed
     .store(speed, std::sync::atomic::Ordering::Relaxed);
 }
 
+/// Saves the conversation as it is, the reply in progress included, when
+/// the session is being saved (-s). Run on shutdown.
+pub fn save_session() {
+  let Some(state) = GLOBAL_STATE.get() else {
+    return;
+  };
+  let Some(fallback) = state.agents.first() else {
+    return;
+  };
+  let settings = current_agent(state, fallback);
+  perform_save(&state.conversation_history, &settings);
+}
+
 pub struct SaveMetadata {
   pub start_date: String,
   pub agents: Vec<crate::config::AgentSettings>,
@@ -1217,25 +2026,40 @@ pub fn save_conversation(
     conv_dir.join(format!("{}_{}.txt", date_str, uuid_str))
   };
 
-  let hist = history.lock().unwrap();
+  let hist = history.lock().unwrap_or_else(PoisonError::into_inner);
   let mut content = String::new();
 
   content.push_str(crate::ui::get_banner());
   content.push_str("\n\n");
 
-  for msg in hist.iter() {
+  let labels = crate::ui::labels();
+  for (msg, bookmarked) in hist.transcript() {
     let label = if msg.role == "user" {
-      "USER"
+      labels.user.as_str()
     } else if msg.role == "assistant" {
       if metadata.map_or(false, |m| m.is_debate) {
-        msg.agent_name.as_deref().unwrap_or("ASSISTANT")
+        msg.agent_name.as_deref().unwrap_or(&labels.assistant)
       } else {
-        "ASSISTANT"
+        labels.assistant.as_str()
       }
     } else {
       &msg.role
     };
-    content.push_str(&format!("{}:\n{}\n\n", label, msg.content));
+    let text = match msg.role.as_str() {
+      "user" => crate::punctuate::readable(&msg.content),
+      _ => std::borrow::Cow::Borrowed(msg.content.as_str()),
+    };
+    let bookmark = if bookmarked { " 🔖" } else { "" };
+    content.push_str(&format!(
+      "{}:{}\n{}\n",
+      label,
+      bookmark,
+      crate::redact::redact(&text)
+    ));
+    if let Some(meta) = &msg.meta {
+      content.push_str(&format!("[{}]\n", meta.summary()));
+    }
+    content.push('\n');
   }
 
   if let Some(meta) = metadata {
@@ -1278,6 +2102,49 @@ pub fn save_conversation(
     content.push_str("##########################################\n");
   }
 
-  fs::write(filepath, content)?;
+  crate::encryption::write_file(&filepath, content.as_bytes())?;
+
+  // the same turns with their metadata, for analysis
+  let session = session_json(&hist, metadata);
+  crate::encryption::write_file(
+    &filepath.with_extension("json"),
+    serde_json::to_string_pretty(&session)?.as_bytes(),
+  )?;
   Ok(())
 }
+
+// {"start_date": .., "debate": .., "agents": [..], "turns": [{"role": ..,
+// "agent": .., "content": .., "meta": {..}}]}
+fn session_json(history: &crate::history::History, metadata: Option<&SaveMetadata>) -> Value {
+  let turns: Vec<Value> = history
+    .transcript()
+    .map(|(msg, _)| {
+      let mut turn = json!({
+        "role": msg.role,
+        "agent": msg.agent_name,
+        "content": crate::redact::redact(&msg.content),
+        "meta": msg.meta.as_ref().map(TurnMeta::to_json),
+      });
+      // the raw transcript stays the content, as sent to the llm
+      let readable = crate::punctuate::readable(&msg.content);
+      if msg.role == "user" && readable != msg.content {
+        turn["punctuated"] = crate::redact::redact(&readable).into();
+      }
+      turn
+    })
+    .collect();
+  let mut session = json!({
+    "dropped_messages": history.dropped(),
+    "turns": turns,
+  });
+  if let Some(meta) = metadata {
+    session["start_date"] = meta.start_date.clone().into();
+    session["debate"] = meta.is_debate.into();
+    session["agents"] = meta
+      .agents
+      .iter()
+      .map(|a| json!({ "name": a.name, "tts": a.tts, "model": a.model, "voice": a.voice }))
+      .collect();
+  }
+  session
+}
@@ -0,0 +1,226 @@
+// ------------------------------------------------------------------
+//  Dictation (--dictate)
+// ------------------------------------------------------------------
+//
+// What is said is written down instead of answered: shown in the terminal
+// (printed to stdout with --headless), appended to a file and/or typed into
+// the focused window. Spoken punctuation ("comma", "new line", ...) becomes
+// the sign. Typed prompts still go to the agent.
+
+use crossbeam_channel::Sender;
+use std::io::Write;
+use std::sync::PoisonError;
+use std::sync::{Mutex, OnceLock};
+
+// longest first: "new line" before "new"
+const SPOKEN_SIGNS: [(&str, &str); 10] = [
+  ("new paragraph", "\n\n"),
+  ("new line", "\n"),
+  ("full stop", "."),
+  ("question mark", "?"),
+  ("exclamation mark", "!"),
+  ("exclamation point", "!"),
+  ("period", "."),
+  ("comma", ","),
+  ("colon", ":"),
+  ("semicolon", ";"),
+];
+// the punctuation whisper adds by itself, replaced by a spoken sign
+const WHISPER_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?'];
+
+static SINKS: OnceLock<Sinks> = OnceLock::new();
+// last character written, for the spacing and capitals of the next words
+static LAST_CHAR: Mutex<Option<char>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+/// Turns dictation on: the text goes to `output` (appended) when given, is
+/// typed when `type_keys`, and is printed to stdout instead of shown in the
+/// terminal ui when `print`.
+pub fn init(output: Option<String>, type_keys: bool, print: bool) {
+  SINKS
+    .set(Sinks {
+      output,
+      type_keys,
+      print,
+    })
+    .ok();
+}
+
+pub fn is_enabled() -> bool {
+  SINKS.get().is_some()
+}
+
+/// Writes down what was said in an utterance.
+pub fn write(text: &str, tx_ui: &Sender<String>) {
+  let Some(sinks) = SINKS.get() else {
+    return;
+  };
+  let piece = {
+    let mut last = LAST_CHAR.lock().unwrap_or_else(PoisonError::into_inner);
+    let piece = punctuate(text, *last);
+    if let Some(c) = piece.chars().last() {
+      *last = Some(c);
+    }
+    piece
+  };
+  if piece.is_empty() {
+    return;
+  }
+  if sinks.print {
+    let mut out = std::io::stdout();
+    let _ = out.write_all(piece.as_bytes());
+    let _ = out.flush();
+  } else if !piece.trim().is_empty() {
+    let _ = tx_ui.send(format!("line|📝 {}", piece.trim()));
+  }
+  if let Some(path) = &sinks.output {
+    let written = std::fs::OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(path)
+      .and_then(|mut f| f.write_all(crate::redact::redact(&piece).as_bytes()));
+    if let Err(e) = written {
+      crate::log::log("error", &format!("Failed to write {}: {}", path, e));
+    }
+  }
+  if sinks.type_keys
+    && let Err(e) = type_text(&piece)
+  {
+    crate::log::log("error", &format!("Typing the dictation failed: {}", e));
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+struct Sinks {
+  output: Option<String>,
+  type_keys: bool,
+  print: bool,
+}
+
+// `text` with its spoken signs replaced, spaced and capitalized to follow
+// `last` (the last character written before).
+fn punctuate(text: &str, last: Option<char>) -> String {
+  let words: Vec<&str> = text.split_whitespace().collect();
+  let normalized: Vec<String> = words
+    .iter()
+    .map(|w| {
+      w.chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase()
+    })
+    .collect();
+  let mut out = String::new();
+  let mut last = last;
+  // end of the last spoken sign, kept
+  let mut kept = 0;
+  let mut i = 0;
+  while i < words.len() {
+    let sign = SPOKEN_SIGNS.iter().find_map(|(phrase, sign)| {
+      let phrase: Vec<&str> = phrase.split(' ').collect();
+      let end = i + phrase.len();
+      (end <= words.len() && normalized[i..end] == phrase[..]).then_some((*sign, end))
+    });
+    if let Some((sign, end)) = sign {
+      // what whisper put before the spoken sign goes
+      while out.len() > kept && (out.ends_with(' ') || out.ends_with(WHISPER_PUNCTUATION)) {
+        out.pop();
+      }
+      out.push_str(sign);
+      kept = out.len();
+      last = sign.chars().last();
+      i = end;
+      continue;
+    }
+    let word = words[i];
+    if last.is_some_and(|c| !c.is_whitespace()) {
+      out.push(' ');
+    }
+    let sentence_start = last.is_none_or(|c| ".?!\n".contains(c));
+    let mut chars = word.chars();
+    if sentence_start && let Some(first) = chars.next() {
+      out.extend(first.to_uppercase());
+      out.push_str(chars.as_str());
+    } else {
+      out.push_str(word);
+    }
+    last = word.chars().last();
+    i += 1;
+  }
+  out
+}
+
+// Types `text` into the focused window with the keyboard emulation of the
+// system: wtype (Wayland) or xdotool (X11), System Events on macOS and
+// SendKeys on Windows.
+fn type_text(text: &str) -> Result<(), String> {
+  #[cfg(target_os = "macos")]
+  let mut command = {
+    let script = text
+      .split('\n')
+      .map(|line| {
+        format!(
+          "keystroke \"{}\"",
+          line.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+      })
+      .collect::<Vec<_>>()
+      .join(" & return & ");
+    let mut command = std::process::Command::new("osascript");
+    command.args([
+      "-e",
+      &format!("tell application \"System Events\" to {}", script),
+    ]);
+    command
+  };
+  #[cfg(target_os = "windows")]
+  let mut command = {
+    let keys: String = text
+      .chars()
+      .map(|c| match c {
+        '\n' => "{ENTER}".to_string(),
+        '+' | '^' | '%' | '~' | '(' | ')' | '{' | '}' | '[' | ']' => format!("{{{}}}", c),
+        '\'' => "''".to_string(),
+        c => c.to_string(),
+      })
+      .collect();
+    let mut command = std::process::Command::new("powershell");
+    command.args([
+      "-NoProfile",
+      "-Command",
+      &format!(
+        "Add-Type -AssemblyName System.Windows.Forms; [System.Windows.Forms.SendKeys]::SendWait('{}')",
+        keys
+      ),
+    ]);
+    command
+  };
+  #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+  let mut command = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+    let mut command = std::process::Command::new("wtype");
+    command.args(["--", text]);
+    command
+  } else {
+    let mut command = std::process::Command::new("xdotool");
+    command.args(["type", "--clearmodifiers", "--", text]);
+    command
+  };
+  let status = command
+    .stdout(std::process::Stdio::null())
+    .stderr(std::process::Stdio::null())
+    .status()
+    .map_err(|e| format!("{:?}: {}", command.get_program(), e))?;
+  if status.success() {
+    Ok(())
+  } else {
+    Err(format!(
+      "{:?} exited with {}",
+      command.get_program(),
+      status
+    ))
+  }
+}
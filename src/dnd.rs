@@ -0,0 +1,142 @@
+// ------------------------------------------------------------------
+//  Do not disturb (--quiet-hours, z key, "do not disturb")
+// ------------------------------------------------------------------
+//
+// While on, the microphone is off and what vtmate would bring up by itself
+// (desktop notifications, cues and announcements from the control socket or
+// the REST API) waits for it to be turned off. Replies to what was typed are
+// still spoken.
+
+use chrono::Timelike;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+const SCHEDULE_POLL_INTERVAL: Duration = Duration::from_secs(20);
+// said to turn it on, it can only be turned off by other means: the microphone
+// is off by then
+const PHRASES: [&str; 4] = [
+  "do not disturb",
+  "dont disturb me",
+  "do not disturb me",
+  "quiet time",
+];
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static DEFERRED: Mutex<Vec<Box<dyn FnOnce() + Send>>> = Mutex::new(Vec::new());
+
+// API
+// ------------------------------------------------------------------
+
+/// A daily time range, possibly over midnight.
+#[derive(Clone, Copy, Debug)]
+pub struct QuietHours {
+  // minutes after midnight
+  start: u32,
+  end: u32,
+}
+
+impl QuietHours {
+  /// Parses "HH:MM-HH:MM" (--quiet-hours).
+  pub fn parse(range: &str) -> Option<Self> {
+    crate::config::parse_quiet_hours(range).map(|(start, end)| Self { start, end })
+  }
+
+  pub fn contains(&self, minute: u32) -> bool {
+    if self.start < self.end {
+      (self.start..self.end).contains(&minute)
+    } else {
+      minute >= self.start || minute < self.end
+    }
+  }
+
+  /// The end, as "HH:MM".
+  pub fn end(&self) -> String {
+    format!("{:02}:{:02}", self.end / 60, self.end % 60)
+  }
+}
+
+pub fn is_active() -> bool {
+  ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Turns do not disturb on or off, running what waited for it when off.
+pub fn set_active(active: bool) {
+  if ACTIVE.swap(active, Ordering::Relaxed) == active {
+    return;
+  }
+  crate::log::log(
+    "info",
+    &format!("Do not disturb {}", if active { "on" } else { "off" }),
+  );
+  if !active {
+    let deferred = std::mem::take(&mut *DEFERRED.lock().unwrap_or_else(PoisonError::into_inner));
+    for run in deferred {
+      run();
+    }
+  }
+}
+
+/// Flips do not disturb, returns whether it's now on.
+pub fn toggle() -> bool {
+  let active = !is_active();
+  set_active(active);
+  active
+}
+
+/// Runs `f` now, or once do not disturb is off. Returns whether it waits.
+pub fn run_or_defer(f: impl FnOnce() + Send + 'static) -> bool {
+  {
+    let mut deferred = DEFERRED.lock().unwrap_or_else(PoisonError::into_inner);
+    if is_active() {
+      deferred.push(Box::new(f));
+      return true;
+    }
+  }
+  f();
+  false
+}
+
+/// Whether `text` asks to turn do not disturb on.
+pub fn is_request(text: &str) -> bool {
+  let words: String = text
+    .to_lowercase()
+    .chars()
+    .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+    .collect();
+  let words = words.split_whitespace().collect::<Vec<_>>().join(" ");
+  PHRASES.contains(&words.as_str())
+}
+
+/// Turns do not disturb on when the quiet hours start and off when they end,
+/// a manual change holds until the next of them. Blocks.
+pub fn schedule_thread(hours: QuietHours) {
+  crate::log::log(
+    "info",
+    &format!(
+      "Quiet hours: do not disturb until {} every day",
+      hours.end()
+    ),
+  );
+  let mut inside = hours.contains(minute_of_day());
+  if inside {
+    set_active(true);
+  }
+  loop {
+    std::thread::sleep(SCHEDULE_POLL_INTERVAL);
+    let now = hours.contains(minute_of_day());
+    if now != inside {
+      inside = now;
+      set_active(now);
+    }
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn minute_of_day() -> u32 {
+  let now = chrono::Local::now();
+  now.hour() * 60 + now.minute()
+}
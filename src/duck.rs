@@ -0,0 +1,208 @@
+// ------------------------------------------------------------------
+//  Ducking (--duck)
+// ------------------------------------------------------------------
+//
+// Music playing on the same machine covers the voice. With --duck the other
+// audio is turned down while the agent speaks and back up once it's done:
+// the other streams of the PulseAudio / PipeWire server (through pactl) on
+// Linux, Music and Spotify on macOS. Their volume is put back as it was,
+// also when vtmate exits while ducking.
+
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// the gaps between phrases don't bring the music back up
+const RESTORE_AFTER: Duration = Duration::from_millis(800);
+#[cfg(target_os = "macos")]
+const PLAYERS: [&str; 2] = ["Music", "Spotify"];
+
+// the volumes to put back, None while not ducking
+static SAVED: Mutex<Option<Vec<Saved>>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+/// Turns the other audio down to `level` percent of its volume while the
+/// agent speaks. Blocks.
+pub fn duck_thread(level: u8) {
+  if cfg!(target_os = "windows") {
+    crate::log::log("warning", "--duck is not supported on Windows");
+    return;
+  }
+  let Some(state) = crate::state::GLOBAL_STATE.get() else {
+    return;
+  };
+  crate::util::on_shutdown(restore);
+  let mut quiet_since: Option<Instant> = None;
+  loop {
+    std::thread::sleep(crate::power::poll_interval(POLL_INTERVAL));
+    let speaking = state.playback.playback_active.load(Ordering::Relaxed);
+    let ducking = SAVED
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .is_some();
+    if speaking {
+      quiet_since = None;
+      if !ducking {
+        duck(level);
+      }
+    } else if ducking {
+      let quiet = quiet_since.get_or_insert_with(Instant::now);
+      if quiet.elapsed() >= RESTORE_AFTER {
+        restore();
+        quiet_since = None;
+      }
+    }
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// an audio stream (Linux) or player (macOS) and its volume before ducking
+struct Saved {
+  id: String,
+  volumes: Vec<u32>,
+}
+
+fn duck(level: u8) {
+  let saved = match volumes() {
+    Ok(saved) => saved,
+    Err(e) => {
+      crate::log::log("warning", &format!("Failed to duck the other audio: {}", e));
+      Vec::new()
+    }
+  };
+  for s in &saved {
+    let ducked: Vec<u32> = s.volumes.iter().map(|v| v * level as u32 / 100).collect();
+    if let Err(e) = set_volumes(&s.id, &ducked) {
+      crate::log::log("warning", &format!("Failed to duck {}: {}", s.id, e));
+    }
+  }
+  crate::log::log("debug", &format!("Ducked {} audio streams", saved.len()));
+  *SAVED.lock().unwrap_or_else(PoisonError::into_inner) = Some(saved);
+}
+
+fn restore() {
+  let Some(saved) = SAVED.lock().unwrap_or_else(PoisonError::into_inner).take() else {
+    return;
+  };
+  for s in &saved {
+    // a stream gone since is no error
+    let _ = set_volumes(&s.id, &s.volumes);
+  }
+}
+
+fn run(command: &mut Command) -> Result<String, String> {
+  let output = command
+    .stdin(Stdio::null())
+    .stderr(Stdio::null())
+    .output()
+    .map_err(|e| format!("{:?}: {}", command.get_program(), e))?;
+  if !output.status.success() {
+    return Err(format!(
+      "{:?} exited with {}",
+      command.get_program(),
+      output.status
+    ));
+  }
+  Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// The other streams of the sound server, with their volume per channel.
+#[cfg(not(target_os = "macos"))]
+fn volumes() -> Result<Vec<Saved>, String> {
+  let listing = run(
+    Command::new("pactl")
+      .args(["list", "sink-inputs"])
+      .env("LC_ALL", "C"),
+  )?;
+  let own_pid = format!("\"{}\"", std::process::id());
+  let mut saved = Vec::new();
+  let mut current: Option<(Saved, bool)> = None;
+  for line in listing.lines() {
+    let line = line.trim();
+    if let Some(id) = line.strip_prefix("Sink Input #") {
+      saved.extend(current.take().filter(|(_, own)| !own).map(|(s, _)| s));
+      let id = id.trim().to_string();
+      current = Some((
+        Saved {
+          id,
+          volumes: Vec::new(),
+        },
+        false,
+      ));
+    } else if let Some((s, own)) = current.as_mut() {
+      if let Some(volume) = line.strip_prefix("Volume:") {
+        // front-left: 65536 / 100% / 0.00 dB,   front-right: 65536 / 100% / 0.00 dB
+        s.volumes = volume
+          .split(',')
+          .filter_map(|channel| {
+            channel
+              .split(':')
+              .nth(1)?
+              .split('/')
+              .next()?
+              .trim()
+              .parse()
+              .ok()
+          })
+          .collect();
+      } else if let Some(pid) = line.strip_prefix("application.process.id =") {
+        *own = pid.trim() == own_pid;
+      }
+    }
+  }
+  saved.extend(current.filter(|(_, own)| !own).map(|(s, _)| s));
+  Ok(
+    saved
+      .into_iter()
+      .filter(|s| !s.volumes.is_empty())
+      .collect(),
+  )
+}
+
+#[cfg(not(target_os = "macos"))]
+fn set_volumes(id: &str, volumes: &[u32]) -> Result<(), String> {
+  let volumes: Vec<String> = volumes.iter().map(u32::to_string).collect();
+  run(
+    Command::new("pactl")
+      .args(["set-sink-input-volume", id])
+      .args(&volumes),
+  )
+  .map(|_| ())
+}
+
+// The players running, with their volume.
+#[cfg(target_os = "macos")]
+fn volumes() -> Result<Vec<Saved>, String> {
+  let mut saved = Vec::new();
+  for player in PLAYERS {
+    let script = format!(
+      "if application \"{0}\" is running then tell application \"{0}\" to get sound volume",
+      player
+    );
+    let volume = run(Command::new("osascript").args(["-e", &script]))?;
+    if let Ok(volume) = volume.trim().parse() {
+      saved.push(Saved {
+        id: player.to_string(),
+        volumes: vec![volume],
+      });
+    }
+  }
+  Ok(saved)
+}
+
+#[cfg(target_os = "macos")]
+fn set_volumes(id: &str, volumes: &[u32]) -> Result<(), String> {
+  let script = format!(
+    "tell application \"{}\" to set sound volume to {}",
+    id,
+    volumes.first().copied().unwrap_or(0)
+  );
+  run(Command::new("osascript").args(["-e", &script])).map(|_| ())
+}
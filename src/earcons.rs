@@ -0,0 +1,137 @@
+// ------------------------------------------------------------------
+//  Earcons (--earcons)
+// ------------------------------------------------------------------
+//
+// Short cues telling what is going on between the spoken turns: listening,
+// utterance accepted, error, timer done and the end of a reply. They are synthesized (nothing to
+// ship or download) and mixed over the playback, so they never wait for a
+// reply to end nor count as one.
+
+use crate::audio::AudioChunk;
+use std::f32::consts::TAU;
+use std::sync::{
+  Arc, OnceLock,
+  atomic::{AtomicBool, Ordering},
+};
+use std::time::Duration;
+
+const SAMPLE_RATE: u32 = 24_000;
+// quiet enough not to be heard back as speech by the microphone
+const GAIN: f32 = 0.15;
+const FADE_MS: u32 = 5;
+const LISTENING_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// rendered once, in the order of Earcon::ALL
+static RENDERED: OnceLock<Vec<AudioChunk>> = OnceLock::new();
+
+// API
+// ------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Earcon {
+  /// Listening started (or resumed).
+  Listening,
+  /// An utterance was captured and goes to the transcription.
+  Accepted,
+  /// Something failed.
+  Error,
+  /// A timer went off (played on request, e.g. `vtmate ctl cue timer`).
+  TimerDone,
+  /// A reply was played out, your turn (with --follow-up-window).
+  TurnEnd,
+}
+
+impl Earcon {
+  pub const ALL: [Earcon; 5] = [
+    Earcon::Listening,
+    Earcon::Accepted,
+    Earcon::Error,
+    Earcon::TimerDone,
+    Earcon::TurnEnd,
+  ];
+
+  /// The earcon called `name`: listening, accepted, error, timer or turn.
+  pub fn from_name(name: &str) -> Option<Self> {
+    match name {
+      "listening" => Some(Earcon::Listening),
+      "accepted" => Some(Earcon::Accepted),
+      "error" => Some(Earcon::Error),
+      "timer" => Some(Earcon::TimerDone),
+      "turn" => Some(Earcon::TurnEnd),
+      _ => None,
+    }
+  }
+
+  // (frequency in Hz, duration in ms) notes, 0 Hz being a rest
+  fn notes(self) -> &'static [(f32, u32)] {
+    match self {
+      Earcon::Listening => &[(660.0, 60), (880.0, 80)],
+      Earcon::Accepted => &[(880.0, 50)],
+      Earcon::Error => &[(440.0, 120), (330.0, 160)],
+      Earcon::TimerDone => &[
+        (1047.0, 90),
+        (0.0, 60),
+        (1047.0, 90),
+        (0.0, 60),
+        (1047.0, 90),
+      ],
+      Earcon::TurnEnd => &[(784.0, 40), (0.0, 30), (784.0, 40)],
+    }
+  }
+}
+
+pub fn set_enabled(enabled: bool) {
+  ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+  ENABLED.load(Ordering::Relaxed)
+}
+
+/// Plays `earcon` over the playback, when the earcons are on.
+pub fn play(earcon: Earcon) {
+  if !is_enabled() {
+    return;
+  }
+  let rendered = RENDERED.get_or_init(|| Earcon::ALL.iter().map(|e| render(*e)).collect());
+  if let Some(i) = Earcon::ALL.iter().position(|e| *e == earcon) {
+    crate::playback::mix(&rendered[i]);
+  }
+}
+
+/// Plays the listening cue every time listening resumes, whatever resumed
+/// it (space, push to talk, the control socket). Blocks.
+pub fn listening_thread(recording_paused: Arc<AtomicBool>) {
+  let mut was_paused = recording_paused.load(Ordering::Relaxed);
+  loop {
+    std::thread::sleep(crate::power::poll_interval(LISTENING_POLL_INTERVAL));
+    let paused = recording_paused.load(Ordering::Relaxed);
+    if was_paused && !paused {
+      play(Earcon::Listening);
+    }
+    was_paused = paused;
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// Sine notes, faded in and out so they don't click.
+fn render(earcon: Earcon) -> AudioChunk {
+  let mut data = Vec::new();
+  let fade = (SAMPLE_RATE * FADE_MS / 1000) as usize;
+  for &(freq, ms) in earcon.notes() {
+    let len = (SAMPLE_RATE * ms / 1000) as usize;
+    for i in 0..len {
+      let envelope = (i.min(len - 1 - i) as f32 / fade as f32).min(1.0);
+      let phase = TAU * freq * i as f32 / SAMPLE_RATE as f32;
+      data.push(phase.sin() * envelope * GAIN);
+    }
+  }
+  AudioChunk {
+    data: data.into(),
+    channels: 1,
+    sample_rate: SAMPLE_RATE,
+  }
+}
@@ -0,0 +1,367 @@
+// ------------------------------------------------------------------
+//  Encryption at rest (--encrypt)
+// ------------------------------------------------------------------
+//
+// Saved conversations (the text and the audio) are written encrypted with
+// XChaCha20-Poly1305, under a key read from a key file (its SHA-256) or
+// derived from a passphrase (Argon2id, salted once per session). Read them
+// back with `vtmate decrypt`.
+//
+// An encrypted file is MAGIC, the key source (1 byte), the salt and then
+// records: a nonce, the ciphertext length (u32 LE) and the ciphertext. The
+// index of a record is authenticated along with it, records can't be
+// reordered nor dropped but from the end (a session cut short).
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const MAGIC: &[u8; 8] = b"VTMENC01";
+const KEY_FILE: u8 = 0;
+const PASSPHRASE: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN;
+const PASSPHRASE_ENV: &str = "VTMATE_PASSPHRASE";
+// wav sizes of an audio recording written as it goes
+const WAV_SIZE_UNKNOWN: u32 = u32::MAX;
+const WAV_HEADER_LEN: usize = 44;
+const NO_ENCRYPTION: &str = "vtmate was built without the encryption feature";
+
+// set once at startup, None (the default) writes plain files
+static SESSION: OnceLock<SessionKey> = OnceLock::new();
+
+// API
+// ------------------------------------------------------------------
+
+/// Turns encryption on for the files saved by this session, with the key in
+/// `key_file` or the passphrase in $VTMATE_PASSPHRASE.
+pub fn init(enabled: bool, key_file: Option<&str>) -> Result<(), String> {
+  if !enabled {
+    return Ok(());
+  }
+  if !cfg!(feature = "encryption") {
+    return Err(NO_ENCRYPTION.to_string());
+  }
+  let secret = Secret::load(key_file)?;
+  let salt = match secret {
+    Secret::KeyFile(_) => [0; SALT_LEN],
+    Secret::Passphrase(_) => random_salt()?,
+  };
+  let key = secret.derive(&salt)?;
+  SESSION
+    .set(SessionKey {
+      source: secret.source(),
+      salt,
+      key,
+    })
+    .ok();
+  Ok(())
+}
+
+pub fn is_enabled() -> bool {
+  SESSION.get().is_some()
+}
+
+/// Where `path` is written to when encrypted ("chat.txt" -> "chat.txt.enc").
+pub fn encrypted_path(path: &Path) -> PathBuf {
+  let mut name = path.as_os_str().to_os_string();
+  name.push(".enc");
+  PathBuf::from(name)
+}
+
+/// Writes `data` to `path`, encrypted (to `encrypted_path`) when encryption
+/// is on.
+pub fn write_file(path: &Path, data: &[u8]) -> std::io::Result<()> {
+  if !is_enabled() {
+    return fs::write(path, data);
+  }
+  let mut writer = Writer::create(path)?;
+  writer.write_record(data)
+}
+
+/// An encrypted file written as it goes, one record at a time.
+pub struct Writer {
+  file: BufWriter<File>,
+  key: [u8; 32],
+  index: u64,
+}
+
+impl Writer {
+  /// Creates `encrypted_path(path)`. Fails when encryption is off.
+  pub fn create(path: &Path) -> std::io::Result<Self> {
+    let session = SESSION
+      .get()
+      .ok_or_else(|| std::io::Error::other("encryption is off"))?;
+    let mut file = BufWriter::new(File::create(encrypted_path(path))?);
+    file.write_all(MAGIC)?;
+    file.write_all(&[session.source])?;
+    file.write_all(&session.salt)?;
+    Ok(Self {
+      file,
+      key: session.key,
+      index: 0,
+    })
+  }
+
+  /// Encrypts `data` as the next record, on disk once this returns.
+  pub fn write_record(&mut self, data: &[u8]) -> std::io::Result<()> {
+    let (nonce, ciphertext) = seal(&self.key, self.index, data)?;
+    self.file.write_all(&nonce)?;
+    self
+      .file
+      .write_all(&(ciphertext.len() as u32).to_le_bytes())?;
+    self.file.write_all(&ciphertext)?;
+    self.index += 1;
+    self.file.flush()
+  }
+}
+
+/// Header of a 16 bit WAV recording of unknown length, its sizes are set by
+/// `vtmate decrypt`.
+pub fn streaming_wav_header(channels: u16, sample_rate: u32) -> Vec<u8> {
+  let block_align = channels * 2;
+  let mut header = Vec::with_capacity(WAV_HEADER_LEN);
+  header.extend_from_slice(b"RIFF");
+  header.extend_from_slice(&WAV_SIZE_UNKNOWN.to_le_bytes());
+  header.extend_from_slice(b"WAVEfmt ");
+  header.extend_from_slice(&16u32.to_le_bytes());
+  header.extend_from_slice(&1u16.to_le_bytes());
+  header.extend_from_slice(&channels.to_le_bytes());
+  header.extend_from_slice(&sample_rate.to_le_bytes());
+  header.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+  header.extend_from_slice(&block_align.to_le_bytes());
+  header.extend_from_slice(&16u16.to_le_bytes());
+  header.extend_from_slice(b"data");
+  header.extend_from_slice(&WAV_SIZE_UNKNOWN.to_le_bytes());
+  header
+}
+
+/// `vtmate decrypt`: prints an encrypted conversation (or writes it to
+/// `output`). Returns the exit code.
+pub fn run_decrypt_command(file: &str, output: Option<&str>, args: &crate::config::Args) -> i32 {
+  match decrypt_file(Path::new(file), args.encrypt_key_file.as_deref()) {
+    Ok(data) => {
+      let written = match output {
+        Some(path) => fs::write(path, &data),
+        None => std::io::stdout().write_all(&data),
+      };
+      match written {
+        Ok(()) => 0,
+        Err(e) => {
+          eprintln!("❌ Failed to write the decrypted conversation: {}", e);
+          1
+        }
+      }
+    }
+    Err(e) => {
+      eprintln!("❌ {}", e);
+      1
+    }
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+struct SessionKey {
+  source: u8,
+  salt: [u8; SALT_LEN],
+  key: [u8; 32],
+}
+
+enum Secret {
+  KeyFile([u8; 32]),
+  Passphrase(String),
+}
+
+impl Secret {
+  fn load(key_file: Option<&str>) -> Result<Self, String> {
+    use sha2::{Digest, Sha256};
+
+    if let Some(path) = key_file {
+      let contents =
+        fs::read(path).map_err(|e| format!("Failed to read the key file {}: {}", path, e))?;
+      if contents.is_empty() {
+        return Err(format!("The key file {} is empty", path));
+      }
+      return Ok(Secret::KeyFile(Sha256::digest(&contents).into()));
+    }
+    match std::env::var(PASSPHRASE_ENV) {
+      Ok(passphrase) if !passphrase.is_empty() => Ok(Secret::Passphrase(passphrase)),
+      _ => Err(format!(
+        "Encryption needs a key: pass --encrypt-key-file or set {}",
+        PASSPHRASE_ENV
+      )),
+    }
+  }
+
+  fn source(&self) -> u8 {
+    match self {
+      Secret::KeyFile(_) => KEY_FILE,
+      Secret::Passphrase(_) => PASSPHRASE,
+    }
+  }
+
+  fn derive(&self, salt: &[u8; SALT_LEN]) -> Result<[u8; 32], String> {
+    match self {
+      Secret::KeyFile(key) => Ok(*key),
+      Secret::Passphrase(passphrase) => derive_from_passphrase(passphrase, salt),
+    }
+  }
+}
+
+// Decrypts every complete record of `path`, a record cut short at the end
+// (vtmate stopped while writing) is reported and left out.
+fn decrypt_file(path: &Path, key_file: Option<&str>) -> Result<Vec<u8>, String> {
+  if !cfg!(feature = "encryption") {
+    return Err(NO_ENCRYPTION.to_string());
+  }
+  let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+  if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+    return Err(format!(
+      "{} is not a file encrypted by vtmate",
+      path.display()
+    ));
+  }
+  let source = bytes[MAGIC.len()];
+  let salt: [u8; SALT_LEN] = bytes[MAGIC.len() + 1..HEADER_LEN].try_into().unwrap();
+  let secret = Secret::load(key_file)?;
+  if secret.source() != source {
+    return Err(match source {
+      KEY_FILE => "This file was encrypted with a key file, pass --encrypt-key-file".to_string(),
+      _ => format!(
+        "This file was encrypted with a passphrase, set {}",
+        PASSPHRASE_ENV
+      ),
+    });
+  }
+  let key = secret.derive(&salt)?;
+
+  let mut data = Vec::new();
+  let mut rest = &bytes[HEADER_LEN..];
+  let mut index = 0;
+  while !rest.is_empty() {
+    let Some((nonce, len, body)) = split_record(rest) else {
+      eprintln!(
+        "⚠️  {} ends with an incomplete record, skipped",
+        path.display()
+      );
+      break;
+    };
+    let ciphertext = &body[..len];
+    let plaintext = open(&key, index, nonce, ciphertext)
+      .map_err(|_| "Wrong key or passphrase, or a damaged file".to_string())?;
+    data.extend_from_slice(&plaintext);
+    rest = &body[len..];
+    index += 1;
+  }
+  fix_wav_sizes(&mut data);
+  Ok(data)
+}
+
+// (nonce, ciphertext length, what follows the length) of the record at the
+// start of `bytes`, None when it's incomplete
+fn split_record(bytes: &[u8]) -> Option<(&[u8], usize, &[u8])> {
+  if bytes.len() < NONCE_LEN + 4 {
+    return None;
+  }
+  let (nonce, rest) = bytes.split_at(NONCE_LEN);
+  let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+  let body = &rest[4..];
+  (body.len() >= len).then_some((nonce, len, body))
+}
+
+// a recording written as it goes gets the sizes of what was decrypted
+fn fix_wav_sizes(data: &mut [u8]) {
+  if data.len() < WAV_HEADER_LEN
+    || &data[..4] != b"RIFF"
+    || &data[36..40] != b"data"
+    || data[4..8] != WAV_SIZE_UNKNOWN.to_le_bytes()
+  {
+    return;
+  }
+  let riff_len = (data.len() - 8) as u32;
+  let data_len = (data.len() - WAV_HEADER_LEN) as u32;
+  data[4..8].copy_from_slice(&riff_len.to_le_bytes());
+  data[40..44].copy_from_slice(&data_len.to_le_bytes());
+}
+
+#[cfg(feature = "encryption")]
+fn seal(key: &[u8; 32], index: u64, data: &[u8]) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+  use chacha20poly1305::XChaCha20Poly1305;
+  use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+
+  let cipher = XChaCha20Poly1305::new(key.into());
+  let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+  let aad = index.to_le_bytes();
+  let ciphertext = cipher
+    .encrypt(
+      &nonce,
+      Payload {
+        msg: data,
+        aad: &aad,
+      },
+    )
+    .map_err(|_| std::io::Error::other("encryption failed"))?;
+  Ok((nonce.to_vec(), ciphertext))
+}
+
+#[cfg(feature = "encryption")]
+fn open(key: &[u8; 32], index: u64, nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, ()> {
+  use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+  use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+  let cipher = XChaCha20Poly1305::new(key.into());
+  let aad = index.to_le_bytes();
+  cipher
+    .decrypt(
+      XNonce::from_slice(nonce),
+      Payload {
+        msg: ciphertext,
+        aad: &aad,
+      },
+    )
+    .map_err(|_| ())
+}
+
+#[cfg(feature = "encryption")]
+fn derive_from_passphrase(passphrase: &str, salt: &[u8; SALT_LEN]) -> Result<[u8; 32], String> {
+  let mut key = [0u8; 32];
+  argon2::Argon2::default()
+    .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+    .map_err(|e| format!("Failed to derive the key from the passphrase: {}", e))?;
+  Ok(key)
+}
+
+#[cfg(feature = "encryption")]
+fn random_salt() -> Result<[u8; SALT_LEN], String> {
+  use chacha20poly1305::aead::rand_core::RngCore;
+
+  let mut salt = [0u8; SALT_LEN];
+  chacha20poly1305::aead::OsRng
+    .try_fill_bytes(&mut salt)
+    .map_err(|e| format!("No randomness for the salt: {}", e))?;
+  Ok(salt)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn seal(_key: &[u8; 32], _index: u64, _data: &[u8]) -> std::io::Result<(Vec<u8>, Vec<u8>)> {
+  Err(std::io::Error::other(NO_ENCRYPTION))
+}
+
+#[cfg(not(feature = "encryption"))]
+fn open(_key: &[u8; 32], _index: u64, _nonce: &[u8], _ciphertext: &[u8]) -> Result<Vec<u8>, ()> {
+  Err(())
+}
+
+#[cfg(not(feature = "encryption"))]
+fn derive_from_passphrase(_passphrase: &str, _salt: &[u8; SALT_LEN]) -> Result<[u8; 32], String> {
+  Err(NO_ENCRYPTION.to_string())
+}
+
+#[cfg(not(feature = "encryption"))]
+fn random_salt() -> Result<[u8; SALT_LEN], String> {
+  Err(NO_ENCRYPTION.to_string())
+}
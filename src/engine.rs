@@ -0,0 +1,330 @@
+// ------------------------------------------------------------------
+//  Engine - embeddable voice loop
+// ------------------------------------------------------------------
+
+use crate::audio::AudioChunk;
+use crate::cancel::{CancellationToken, Interrupter};
+use crate::config::AgentSettings;
+use crate::conversation::{ChatMessage, PhraseSpeaker};
+use crate::stt::SttContext;
+use crate::tts::SpeakOutcome;
+use crossbeam_channel::Sender;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+// the plumbing around the stages, which return crate::error::Error
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+// the whisper contexts of the WhisperStt backends, by model path, loaded once
+static WHISPER_CONTEXTS: Mutex<Option<HashMap<String, Arc<SttContext>>>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+/// What happens during a turn, in order. Reported through the callback
+/// given to [`Engine::listen`] and [`Engine::ask`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum TurnEvent {
+  /// The user utterance, as transcribed.
+  Transcript(String),
+  /// A piece of the reply as streamed by the llm.
+  Token(String),
+  /// A phrase of the reply handed to the tts backend.
+  Phrase(String),
+  /// The turn was interrupted (see [`Engine::interrupter`]).
+  Interrupted,
+  /// The turn completed, with the full reply.
+  Done(String),
+}
+
+/// Turns an utterance into text.
+pub trait SpeechToText: Send + Sync {
+  fn transcribe(&self, utterance: &AudioChunk, language: &str) -> crate::error::Result<String>;
+}
+
+/// Streams a reply to `messages`, calling `on_piece` for every token.
+/// Should stop early once `cancel` is cancelled.
+pub trait LanguageModel: Send + Sync {
+  fn stream_reply(
+    &self,
+    messages: &[ChatMessage],
+    cancel: &CancellationToken,
+    on_piece: &mut dyn FnMut(&str),
+  ) -> crate::error::Result<()>;
+}
+
+/// Synthesizes `text`, sending the audio to `tx` as it is produced, until
+/// `cancel` is cancelled.
+pub trait TextToSpeech: Send + Sync {
+  fn speak(
+    &self,
+    text: &str,
+    language: &str,
+    voice: &str,
+    tx: Sender<AudioChunk>,
+    cancel: &CancellationToken,
+  ) -> crate::error::Result<SpeakOutcome>;
+}
+
+/// Whisper speech to text (the `whisper` feature), using the agent's model.
+/// The model is loaded on the first utterance, a missing or invalid one is
+/// an error of `transcribe`.
+pub struct WhisperStt {
+  pub model_path: String,
+}
+
+/// Ollama / llama-server client, as configured for the agent.
+pub struct ServerLlm {
+  pub provider: String,
+  pub baseurl: String,
+  pub model: String,
+}
+
+/// The built-in tts backends (kokoro, supersonic2, opentts).
+pub struct BuiltinTts {
+  pub tts: String,
+  /// Sample rate of the audio output, used by opentts.
+  pub out_sample_rate: u32,
+  /// Voice speed, 1.0 being the voice's own (kokoro, supersonic2).
+  pub speed: f32,
+}
+
+/// One agent's turns: utterance -> transcript -> llm reply -> speech, with
+/// the history of the conversation.
+///
+/// This is the part of the voice loop the binary shares: the backends, the
+/// transcript plugins and the phrase splitting. The binary's own
+/// conversation adds its commands on top (shell commands, snippets,
+/// continuations, meetings, contexts, ...), which the engine leaves out.
+/// Audio capture and playback stay with the caller: feed utterances to
+/// [`Engine::listen`] and play what arrives on the audio sender. No global
+/// state is set up, several engines can run side by side.
+pub struct Engine {
+  settings: AgentSettings,
+  stt: Box<dyn SpeechToText>,
+  llm: Box<dyn LanguageModel>,
+  tts: Box<dyn TextToSpeech>,
+  history: Vec<ChatMessage>,
+  interrupter: Interrupter,
+}
+
+impl Engine {
+  /// Engine with the backends configured in `settings`. Model files are
+  /// expected in place (see [`crate::assets::ensure_agent_assets`]).
+  pub fn new(settings: AgentSettings, out_sample_rate: u32) -> Self {
+    let stt = WhisperStt {
+      model_path: crate::config::resolved_whisper_model_path(&settings.whisper_model_path),
+    };
+    let llm = ServerLlm {
+      provider: settings.provider.clone(),
+      baseurl: settings.baseurl.clone(),
+      model: settings.model.clone(),
+    };
+    let tts = BuiltinTts {
+      tts: settings.tts.clone(),
+      out_sample_rate,
+      speed: settings.voice_speed,
+    };
+    Self::with_backends(settings, Box::new(stt), Box::new(llm), Box::new(tts))
+  }
+
+  /// Engine with custom backends.
+  pub fn with_backends(
+    settings: AgentSettings,
+    stt: Box<dyn SpeechToText>,
+    llm: Box<dyn LanguageModel>,
+    tts: Box<dyn TextToSpeech>,
+  ) -> Self {
+    Self {
+      settings,
+      stt,
+      llm,
+      tts,
+      history: Vec::new(),
+      interrupter: Interrupter::new(),
+    }
+  }
+
+  /// Interrupts the current turn, from any thread.
+  pub fn interrupter(&self) -> Interrupter {
+    self.interrupter.clone()
+  }
+
+  pub fn settings(&self) -> &AgentSettings {
+    &self.settings
+  }
+
+  /// Conversation so far (user and assistant messages).
+  pub fn history(&self) -> &[ChatMessage] {
+    &self.history
+  }
+
+  pub fn clear_history(&mut self) {
+    self.history.clear();
+  }
+
+  /// Transcribes `utterance` and answers it. Returns the reply, empty when
+  /// nothing was said.
+  pub fn listen(
+    &mut self,
+    utterance: &AudioChunk,
+    audio_tx: Sender<AudioChunk>,
+    mut on_event: impl FnMut(TurnEvent),
+  ) -> crate::error::Result<String> {
+    let text = self.stt.transcribe(utterance, &self.settings.language)?;
+    let text = crate::plugins::filter_transcript(text.trim());
+    let text = text.trim().to_string();
+    if text.is_empty() {
+      return Ok(String::new());
+    }
+    on_event(TurnEvent::Transcript(text.clone()));
+    self.ask(&text, audio_tx, on_event)
+  }
+
+  /// Sends `text` to the llm and speaks the reply phrase by phrase while it
+  /// streams. Returns the (possibly partial) reply.
+  pub fn ask(
+    &mut self,
+    text: &str,
+    audio_tx: Sender<AudioChunk>,
+    mut on_event: impl FnMut(TurnEvent),
+  ) -> crate::error::Result<String> {
+    let cancel = self.interrupter.token().child();
+    let mut messages = vec![ChatMessage {
+      role: "system".to_string(),
+      content: self.settings.system_prompt.replace("\\n", "\n"),
+      agent_name: None,
+      meta: None,
+    }];
+    messages.extend(self.history.iter().cloned());
+    messages.push(ChatMessage {
+      role: "user".to_string(),
+      content: text.to_string(),
+      agent_name: None,
+      meta: None,
+    });
+
+    let mut reply = String::new();
+    let mut speaker = PhraseSpeaker::new();
+    let mut interrupted = false;
+    let mut speak_error: Option<crate::error::Error> = None;
+    let settings = &self.settings;
+    let tts = &self.tts;
+    let mut speak = |phrase: String, on_event: &mut dyn FnMut(TurnEvent)| {
+      if interrupted || speak_error.is_some() {
+        return;
+      }
+      on_event(TurnEvent::Phrase(phrase.clone()));
+      let cleaned = crate::util::strip_special_chars(&phrase);
+      match tts.speak(
+        &cleaned,
+        &settings.language,
+        &settings.voice,
+        audio_tx.clone(),
+        &cancel,
+      ) {
+        Ok(SpeakOutcome::Interrupted) => interrupted = true,
+        Ok(SpeakOutcome::Completed) => {}
+        Err(e) => speak_error = Some(e),
+      }
+    };
+
+    self
+      .llm
+      .stream_reply(&messages, &cancel, &mut |piece: &str| {
+        reply.push_str(piece);
+        on_event(TurnEvent::Token(piece.to_string()));
+        if let Some(phrase) = speaker.push_text(piece) {
+          speak(phrase, &mut on_event);
+        }
+      })?;
+    if let Some(phrase) = speaker.flush() {
+      speak(phrase, &mut on_event);
+    }
+    if let Some(e) = speak_error {
+      return Err(e);
+    }
+
+    self.history.push(messages.pop().expect("user message"));
+    if !reply.is_empty() {
+      self.history.push(ChatMessage {
+        role: "assistant".to_string(),
+        content: reply.clone(),
+        agent_name: Some(self.settings.name.clone()),
+        meta: None,
+      });
+    }
+    if interrupted || cancel.is_cancelled() {
+      on_event(TurnEvent::Interrupted);
+    } else {
+      on_event(TurnEvent::Done(reply.clone()));
+    }
+    Ok(reply)
+  }
+}
+
+impl SpeechToText for WhisperStt {
+  fn transcribe(&self, utterance: &AudioChunk, language: &str) -> crate::error::Result<String> {
+    let ctx = whisper_context(&self.model_path)?;
+    let mono = crate::audio::convert_to_mono(utterance);
+    crate::stt::whisper_transcribe_with_ctx(&ctx, &mono, utterance.sample_rate, language)
+  }
+}
+
+impl LanguageModel for ServerLlm {
+  fn stream_reply(
+    &self,
+    messages: &[ChatMessage],
+    cancel: &CancellationToken,
+    on_piece: &mut dyn FnMut(&str),
+  ) -> crate::error::Result<()> {
+    crate::util::runtime().block_on(crate::llm::llama_server_stream_response_into(
+      &messages.to_vec(),
+      &self.baseurl,
+      &self.model,
+      &self.provider,
+      cancel,
+      on_piece,
+    ))
+  }
+}
+
+impl TextToSpeech for BuiltinTts {
+  fn speak(
+    &self,
+    text: &str,
+    language: &str,
+    voice: &str,
+    tx: Sender<AudioChunk>,
+    cancel: &CancellationToken,
+  ) -> crate::error::Result<SpeakOutcome> {
+    crate::tts::speak(
+      text,
+      &self.tts,
+      crate::config::OPENTTS_BASE_URL_DEFAULT,
+      language,
+      voice,
+      self.speed,
+      self.out_sample_rate,
+      tx,
+      cancel,
+    )
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// The context of the model at `model_path`, loaded the first time.
+fn whisper_context(model_path: &str) -> crate::error::Result<Arc<SttContext>> {
+  let mut contexts = WHISPER_CONTEXTS
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
+  let contexts = contexts.get_or_insert_with(HashMap::new);
+  if let Some(ctx) = contexts.get(model_path) {
+    return Ok(ctx.clone());
+  }
+  let ctx = Arc::new(crate::stt::new_whisper_context(model_path)?);
+  contexts.insert(model_path.to_string(), ctx.clone());
+  Ok(ctx)
+}
@@ -0,0 +1,173 @@
+// ------------------------------------------------------------------
+//  Errors of the voice loop
+// ------------------------------------------------------------------
+//
+// What went wrong in a stage of the voice loop (audio, stt, llm, tts) and
+// what can be done about it. The stages return these instead of bare
+// strings, so every failure is reported the same way by `report`: logged,
+// counted by crate::telemetry and shown in an error panel with the hint.
+
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// API
+// ------------------------------------------------------------------
+
+#[derive(Debug)]
+pub enum Error {
+  Audio(AudioError),
+  Stt(SttError),
+  Llm(LlmError),
+  Tts(TtsError),
+}
+
+#[derive(Debug)]
+pub enum AudioError {
+  // no such device, or it went away
+  NoDevice { input: bool },
+  // the device refused the stream or it failed
+  Stream(String),
+}
+
+#[derive(Debug)]
+pub enum SttError {
+  // the whisper model file isn't there
+  ModelMissing(String),
+  Whisper(String),
+}
+
+#[derive(Debug)]
+pub enum LlmError {
+  // nothing answers at the url (connection refused, timeout)
+  Unreachable {
+    provider: String,
+    url: String,
+    reason: String,
+  },
+  // the server answered with an error status
+  Status {
+    model: String,
+    url: String,
+    status: u16,
+  },
+}
+
+#[derive(Debug)]
+pub enum TtsError {
+  // nothing answers at the OpenTTS url
+  OpenTtsUnreachable { url: String, reason: String },
+  // a voice model file isn't there
+  ModelMissing(String),
+  Synthesis(String),
+}
+
+impl Error {
+  /// The stage that failed, as counted by crate::telemetry.
+  pub fn stage(&self) -> &'static str {
+    match self {
+      Error::Audio(_) => "audio",
+      Error::Stt(_) => "stt",
+      Error::Llm(_) => "llm",
+      Error::Tts(_) => "tts",
+    }
+  }
+
+  /// What the user can do about it, when there's something to do.
+  pub fn hint(&self) -> Option<String> {
+    match self {
+      Error::Audio(AudioError::NoDevice { input: true }) => Some(
+        "Plug in a microphone and allow the terminal to use it (MacOS: System Settings → Privacy & Security → Microphone, Windows: Settings → Privacy & security → Microphone)".to_string(),
+      ),
+      Error::Audio(AudioError::NoDevice { input: false }) => Some(
+        "Plug in speakers or headphones and select them (MacOS: System Settings → Sound → Output, Windows: Settings → System → Sound → Output)".to_string(),
+      ),
+      Error::Audio(AudioError::Stream(_)) => {
+        Some("Another program may hold the device, or try --audio-buffer-frames".to_string())
+      }
+      Error::Stt(SttError::ModelMissing(_)) => Some(
+        "Run with --auto-download to use a model fitting this machine instead".to_string(),
+      ),
+      Error::Stt(SttError::Whisper(_)) => None,
+      Error::Llm(LlmError::Unreachable { provider, url, .. }) => Some(match provider.as_str() {
+        "ollama" => format!("Start ollama with: ollama serve (expected at {})", url),
+        _ => format!("Start llama-server or llamafile listening at {}", url),
+      }),
+      Error::Llm(LlmError::Status {
+        model,
+        status: 404,
+        ..
+      }) => Some(format!(
+        "The model may not be installed, with ollama: ollama pull {}",
+        model
+      )),
+      Error::Llm(LlmError::Status { .. }) => None,
+      Error::Tts(TtsError::OpenTtsUnreachable { .. }) => Some(
+        "Start OpenTTS with: docker run --rm -p 5500:5500 synesthesiam/opentts:all".to_string(),
+      ),
+      Error::Tts(TtsError::ModelMissing(_)) => {
+        Some("Download it with: vtmate assets pull".to_string())
+      }
+      Error::Tts(TtsError::Synthesis(_)) => None,
+    }
+  }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Error::Audio(AudioError::NoDevice { input }) => write!(
+        f,
+        "No audio {} device",
+        if *input { "input" } else { "output" }
+      ),
+      Error::Audio(AudioError::Stream(e)) => write!(f, "Audio stream failed: {}", e),
+      Error::Stt(SttError::ModelMissing(path)) => write!(f, "Whisper model not found: {}", path),
+      Error::Stt(SttError::Whisper(e)) => write!(f, "Transcription failed: {}", e),
+      Error::Llm(LlmError::Unreachable { url, reason, .. }) => {
+        write!(f, "Can't reach the llm at {}: {}", url, reason)
+      }
+      Error::Llm(LlmError::Status { url, status, .. }) => {
+        write!(f, "The llm at {} returned HTTP {}", url, status)
+      }
+      Error::Tts(TtsError::OpenTtsUnreachable { url, reason }) => {
+        write!(f, "Can't reach OpenTTS at {}: {}", url, reason)
+      }
+      Error::Tts(TtsError::ModelMissing(path)) => write!(f, "Voice model not found: {}", path),
+      Error::Tts(TtsError::Synthesis(e)) => write!(f, "Speech synthesis failed: {}", e),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl From<AudioError> for Error {
+  fn from(e: AudioError) -> Self {
+    Error::Audio(e)
+  }
+}
+
+impl From<SttError> for Error {
+  fn from(e: SttError) -> Self {
+    Error::Stt(e)
+  }
+}
+
+impl From<LlmError> for Error {
+  fn from(e: LlmError) -> Self {
+    Error::Llm(e)
+  }
+}
+
+impl From<TtsError> for Error {
+  fn from(e: TtsError) -> Self {
+    Error::Tts(e)
+  }
+}
+
+/// Reports a failure of the voice loop: logged, counted and shown in the
+/// error panel with its hint.
+pub fn report(error: &Error) {
+  crate::telemetry::error(error.stage());
+  crate::log::error_panel(&error.to_string(), error.hint().as_deref());
+}
@@ -0,0 +1,40 @@
+// ------------------------------------------------------------------
+//  Turn events
+// ------------------------------------------------------------------
+
+use crate::engine::TurnEvent;
+use crossbeam_channel::{Receiver, Sender, TrySendError, bounded};
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+// events buffered per subscriber before a slow one starts missing them
+const SUBSCRIBER_CAP: usize = 1024;
+
+static SUBSCRIBERS: Mutex<Vec<Sender<TurnEvent>>> = Mutex::new(Vec::new());
+
+// API
+// ------------------------------------------------------------------
+
+/// Receives every turn event from now on (for external frontends).
+pub fn subscribe() -> Receiver<TurnEvent> {
+  let (tx, rx) = bounded(SUBSCRIBER_CAP);
+  SUBSCRIBERS
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .push(tx);
+  rx
+}
+
+/// Publishes a turn event to the subscribers, never blocking the caller.
+pub fn emit(event: TurnEvent) {
+  let mut subscribers = SUBSCRIBERS.lock().unwrap_or_else(PoisonError::into_inner);
+  if subscribers.is_empty() {
+    return;
+  }
+  subscribers.retain(|tx| {
+    !matches!(
+      tx.try_send(event.clone()),
+      Err(TrySendError::Disconnected(_))
+    )
+  });
+}
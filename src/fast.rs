@@ -0,0 +1,72 @@
+// ------------------------------------------------------------------
+//  Fast transcription (f key, "fast mode", --fast-stt)
+// ------------------------------------------------------------------
+//
+// The whisper model of the agent is accurate but can be slow on long
+// utterances. In fast mode the utterances are transcribed with the tiny model
+// instead, good enough for short commands; switching back to accurate mode
+// (for dictation, say) takes effect from the next utterance. Both models stay
+// loaded once used, so switching is instant. When the tiny model can't be
+// loaded fast mode turns itself off with an error, it never quietly stays on
+// with the model of the agent.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const ON_PHRASES: [&str; 4] = [
+  "fast mode",
+  "fast mode on",
+  "fast transcription",
+  "quick transcription",
+];
+const OFF_PHRASES: [&str; 4] = [
+  "fast mode off",
+  "accurate mode",
+  "accurate transcription",
+  "slow mode",
+];
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+// API
+// ------------------------------------------------------------------
+
+/// Whether the utterances are transcribed with the tiny model.
+pub fn is_active() -> bool {
+  ACTIVE.load(Ordering::Relaxed)
+}
+
+pub fn set_active(active: bool) {
+  if ACTIVE.swap(active, Ordering::Relaxed) != active {
+    crate::log::log(
+      "info",
+      &format!(
+        "Transcription mode: {}",
+        if active { "fast" } else { "accurate" }
+      ),
+    );
+  }
+}
+
+/// Flips fast mode, returns whether it's now on.
+pub fn toggle() -> bool {
+  let active = !is_active();
+  set_active(active);
+  active
+}
+
+/// Whether `text` asks for fast mode (Some(true)) or accurate mode.
+pub fn request(text: &str) -> Option<bool> {
+  let words: String = text
+    .to_lowercase()
+    .chars()
+    .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+    .collect();
+  let words = words.split_whitespace().collect::<Vec<_>>().join(" ");
+  if ON_PHRASES.contains(&words.as_str()) {
+    Some(true)
+  } else if OFF_PHRASES.contains(&words.as_str()) {
+    Some(false)
+  } else {
+    None
+  }
+}
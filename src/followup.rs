@@ -0,0 +1,133 @@
+// ------------------------------------------------------------------
+//  Follow-up window (--follow-up-window, follow_up_questions)
+// ------------------------------------------------------------------
+//
+// On long answers it is hard to tell when it is your turn again. Once a
+// spoken reply has been played out, the status line shows "listening" with a
+// countdown of the window to follow up in, and the turn end earcon plays
+// (with --earcons). Speaking, or a new reply, closes the window.
+//
+// An agent with follow_up_questions is told to ask back when a request is
+// ambiguous. A reply ending with such a question opens the window too, and
+// while it is open the answer needs no wake phrase.
+
+use crate::config::AgentSettings;
+use crate::engine::TurnEvent;
+use crate::state::GLOBAL_STATE;
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+// the playback stays this long without anything left to speak before the
+// reply counts as played out (phrases come with gaps)
+const PLAYED_OUT_AFTER: Duration = Duration::from_millis(600);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// the window after a question, without --follow-up-window
+const QUESTION_WINDOW: Duration = Duration::from_secs(8);
+const CLARIFY_INSTRUCTION: &str = "This is a spoken conversation. When a request is ambiguous \
+  or misses something you need, do not guess: ask one short clarifying question and end your \
+  reply with it.";
+
+static OPEN_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+/// The time left to follow up in, None when the window is closed.
+pub fn remaining() -> Option<Duration> {
+  let open_until = (*OPEN_UNTIL.lock().unwrap_or_else(PoisonError::into_inner))?;
+  let left = open_until.saturating_duration_since(Instant::now());
+  (!left.is_zero()).then_some(left)
+}
+
+/// The system prompt of `agent` to send the llm: with follow_up_questions,
+/// told to ask back rather than guess.
+pub fn system_prompt(prompt: &str, agent: &AgentSettings) -> String {
+  if agent.follow_up_questions {
+    format!("{}\n\n{}", prompt, CLARIFY_INSTRUCTION)
+  } else {
+    prompt.to_string()
+  }
+}
+
+/// Whether some agent asks back (follow_up_questions), for the window to
+/// run without --follow-up-window.
+pub fn asks_questions(agents: &[AgentSettings]) -> bool {
+  agents.iter().any(|a| a.follow_up_questions)
+}
+
+/// Opens the window for `window` after every reply played out (None: only
+/// after a question of an agent with follow_up_questions), `phrases` being
+/// the queue of the phrases still to speak. Blocks.
+pub fn window_thread<T>(window: Option<Duration>, phrases: Receiver<T>) {
+  let events = crate::events::subscribe();
+  while let Ok(event) = events.recv() {
+    if closes(&event) {
+      *OPEN_UNTIL.lock().unwrap_or_else(PoisonError::into_inner) = None;
+      continue;
+    }
+    let TurnEvent::Done(reply) = event else {
+      continue;
+    };
+    let open_for = match window {
+      Some(window) => window,
+      None if asks_back(&reply) => QUESTION_WINDOW,
+      None => continue,
+    };
+    if played_out(&events, &phrases) {
+      crate::earcons::play(crate::earcons::Earcon::TurnEnd);
+      *OPEN_UNTIL.lock().unwrap_or_else(PoisonError::into_inner) = Some(Instant::now() + open_for);
+    }
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// `reply` ends with a question of an agent with follow_up_questions
+fn asks_back(reply: &str) -> bool {
+  let Some(state) = GLOBAL_STATE.get() else {
+    return false;
+  };
+  let name = state
+    .agent_name
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  state
+    .agents
+    .iter()
+    .any(|a| a.name == name && a.follow_up_questions)
+    && reply.trim_end().ends_with('?')
+}
+
+// the user spoke, or a new reply started
+fn closes(event: &TurnEvent) -> bool {
+  matches!(
+    event,
+    TurnEvent::Transcript(_) | TurnEvent::Token(_) | TurnEvent::Interrupted
+  )
+}
+
+// Waits for the reply just completed to be played out, false when the user
+// spoke or a new reply started first.
+fn played_out<T>(events: &Receiver<TurnEvent>, phrases: &Receiver<T>) -> bool {
+  let Some(state) = GLOBAL_STATE.get() else {
+    return false;
+  };
+  let mut quiet_since = Instant::now();
+  loop {
+    match events.recv_timeout(POLL_INTERVAL) {
+      Ok(event) if closes(&event) => return false,
+      Ok(_) | Err(RecvTimeoutError::Timeout) => {}
+      Err(RecvTimeoutError::Disconnected) => return false,
+    }
+    if state.playback.playback_active.load(Ordering::Relaxed) || !phrases.is_empty() {
+      quiet_since = Instant::now();
+    } else if quiet_since.elapsed() >= PLAYED_OUT_AFTER {
+      return true;
+    }
+  }
+}
@@ -0,0 +1,75 @@
+// ------------------------------------------------------------------
+//  Microphone gain (--mic-gain, + / - keys)
+// ------------------------------------------------------------------
+//
+// A software gain on the microphone input, applied before the voice
+// detection and the transcription: a quiet laptop mic reaches the
+// sound_threshold_peak of the agent without speaking up. Samples pushed past
+// full scale are clipped, which the status bar shows for a moment so the
+// gain can be turned down.
+
+use crate::START_INSTANT;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+pub const MIN: f32 = 0.1;
+pub const MAX: f32 = 20.0;
+// each key press multiplies or divides the gain by this
+const STEP: f32 = 1.25;
+// a sample this close to full scale is clipped, or about to be
+const CLIP_LEVEL: f32 = 0.99;
+// how long the clipping stays shown after the last clipped callback
+const CLIP_SHOWN_MS: u64 = 1500;
+
+// f32 bits, 1.0
+static GAIN: AtomicU32 = AtomicU32::new(0x3f80_0000);
+// when the input was last clipped, 0 for never
+static CLIPPED_AT_MS: AtomicU64 = AtomicU64::new(0);
+
+// API
+// ------------------------------------------------------------------
+
+pub fn init(gain: f32) {
+  set(gain);
+}
+
+pub fn get() -> f32 {
+  f32::from_bits(GAIN.load(Ordering::Relaxed))
+}
+
+/// Turns the gain up a step (or down), returns the new gain.
+pub fn step(up: bool) -> f32 {
+  let gain = if up { get() * STEP } else { get() / STEP };
+  set(gain);
+  get()
+}
+
+/// Applies the gain to the samples of an input callback, clipping them to
+/// full scale.
+pub fn apply(samples: &mut [f32]) {
+  let gain = get();
+  let mut clipped = false;
+  for s in samples.iter_mut() {
+    let v = *s * gain;
+    clipped |= v.abs() >= CLIP_LEVEL;
+    *s = v.clamp(-1.0, 1.0);
+  }
+  if clipped {
+    CLIPPED_AT_MS.store(
+      crate::util::now_ms(&START_INSTANT).max(1),
+      Ordering::Relaxed,
+    );
+  }
+}
+
+/// Whether the input was clipped lately.
+pub fn is_clipping() -> bool {
+  let at = CLIPPED_AT_MS.load(Ordering::Relaxed);
+  at > 0 && crate::util::now_ms(&START_INSTANT).saturating_sub(at) < CLIP_SHOWN_MS
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn set(gain: f32) {
+  GAIN.store(gain.clamp(MIN, MAX).to_bits(), Ordering::Relaxed);
+}
@@ -0,0 +1,286 @@
+// ------------------------------------------------------------------
+//  Status led and display (--status-led, --status-display)
+// ------------------------------------------------------------------
+//
+// For headless builds (a Raspberry Pi "smart speaker"): what the terminal
+// shows, listening, thinking or speaking, goes to a led on a gpio line and/or
+// a small SSD1306 oled display on an i2c bus. Linux only, through the gpio
+// character device and i2c-dev, behind the gpio feature.
+
+use crate::state::{AppState, TurnState};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// led blink half periods, in poll intervals
+const THINKING_BLINK: u32 = 10;
+const SPEAKING_BLINK: u32 = 3;
+
+// API
+// ------------------------------------------------------------------
+
+/// The led and display the state goes to.
+pub struct StatusSinks {
+  led: Option<Led>,
+  display: Option<Display>,
+}
+
+impl StatusSinks {
+  /// Opens the led (`led`, "[CHIP:]LINE") and the display (`display`,
+  /// "BUS[:ADDR[:ROWS]]").
+  pub fn open(led: Option<&str>, display: Option<&str>) -> Result<Self, String> {
+    Ok(Self {
+      led: led.map(Led::open).transpose()?,
+      display: display.map(Display::open).transpose()?,
+    })
+  }
+
+  /// Keeps the led and the display in step with the state. Blocks.
+  pub fn run(mut self, state: Arc<AppState>) {
+    let mut shown = None;
+    let mut tick: u32 = 0;
+    loop {
+      let status = Status::of(&state);
+      if let Some(led) = self.led.as_mut() {
+        let lit = match status {
+          Status::Off => false,
+          Status::Listening => true,
+          Status::Thinking => (tick / THINKING_BLINK).is_multiple_of(2),
+          Status::Speaking => (tick / SPEAKING_BLINK).is_multiple_of(2),
+        };
+        led.set(lit);
+      }
+      if let Some(display) = self.display.as_mut()
+        && shown != Some(status.label())
+      {
+        shown = Some(status.label());
+        display.show(status.label());
+      }
+      tick = tick.wrapping_add(1);
+      std::thread::sleep(crate::power::poll_interval(POLL_INTERVAL));
+    }
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+  // recording paused or do not disturb
+  Off,
+  Listening,
+  Thinking,
+  Speaking,
+}
+
+impl Status {
+  fn of(state: &AppState) -> Self {
+    match state.turn.get() {
+      TurnState::Speaking => Status::Speaking,
+      TurnState::Transcribing | TurnState::Generating => Status::Thinking,
+      _ if state.recording_paused.load(Ordering::Relaxed) || crate::dnd::is_active() => Status::Off,
+      _ => Status::Listening,
+    }
+  }
+
+  fn label(self) -> &'static str {
+    match self {
+      Status::Off if crate::dnd::is_active() => "DND",
+      Status::Off => "PAUSED",
+      Status::Listening => "LISTENING",
+      Status::Thinking => "THINKING",
+      Status::Speaking => "SPEAKING",
+    }
+  }
+}
+
+#[cfg(all(feature = "gpio", target_os = "linux"))]
+struct Led {
+  handle: gpio_cdev::LineHandle,
+  lit: Option<bool>,
+}
+
+#[cfg(all(feature = "gpio", target_os = "linux"))]
+impl Led {
+  fn open(spec: &str) -> Result<Self, String> {
+    let (chip, line) =
+      crate::config::parse_status_led(spec).ok_or(format!("Invalid status led {}", spec))?;
+    let handle = gpio_cdev::Chip::new(&chip)
+      .and_then(|mut chip| chip.get_line(line))
+      .and_then(|line| line.request(gpio_cdev::LineRequestFlags::OUTPUT, 0, "vtmate"))
+      .map_err(|e| format!("Unable to open the status led {}: {}", spec, e))?;
+    Ok(Self { handle, lit: None })
+  }
+
+  fn set(&mut self, lit: bool) {
+    if self.lit == Some(lit) {
+      return;
+    }
+    self.lit = Some(lit);
+    if let Err(e) = self.handle.set_value(lit as u8) {
+      crate::log::log("debug", &format!("Status led failed: {}", e));
+    }
+  }
+}
+
+#[cfg(all(feature = "gpio", target_os = "linux"))]
+struct Display {
+  device: i2cdev::linux::LinuxI2CDevice,
+  // 8 rows each
+  pages: u8,
+}
+
+#[cfg(all(feature = "gpio", target_os = "linux"))]
+impl Display {
+  const WIDTH: usize = 128;
+  // the largest write the i2c adapters take in one go, with its control byte
+  const CHUNK: usize = 16;
+
+  fn open(spec: &str) -> Result<Self, String> {
+    let (bus, address, rows) = crate::config::parse_status_display(spec)
+      .ok_or(format!("Invalid status display {}", spec))?;
+    let device = i2cdev::linux::LinuxI2CDevice::new(&bus, address)
+      .map_err(|e| format!("Unable to open the status display {}: {}", spec, e))?;
+    let mut display = Self {
+      device,
+      pages: rows / 8,
+    };
+    // SSD1306 power up: charge pump on, horizontal addressing, flipped to
+    // read with the pins on top
+    let com_pins = if rows == 32 { 0x02 } else { 0x12 };
+    display
+      .command(&[
+        0xae,
+        0xd5,
+        0x80,
+        0xa8,
+        rows - 1,
+        0xd3,
+        0x00,
+        0x40,
+        0x8d,
+        0x14,
+        0x20,
+        0x00,
+        0xa1,
+        0xc8,
+        0xda,
+        com_pins,
+        0x81,
+        0xcf,
+        0xd9,
+        0xf1,
+        0xdb,
+        0x40,
+        0xa4,
+        0xa6,
+        0xaf,
+      ])
+      .map_err(|e| format!("Unable to set up the status display {}: {}", spec, e))?;
+    Ok(display)
+  }
+
+  // `text` (capitals and spaces) centered, at twice the font size
+  fn show(&mut self, text: &str) {
+    let pages = self.pages as usize;
+    let mut frame = vec![0u8; Self::WIDTH * pages];
+    let columns: Vec<u8> = text
+      .chars()
+      .flat_map(|c| glyph(c).into_iter().chain([0]))
+      .flat_map(|column| [column, column])
+      .collect();
+    let left = Self::WIDTH.saturating_sub(columns.len()) / 2;
+    let top = pages / 2 - 1;
+    for (x, column) in columns.iter().take(Self::WIDTH - left).enumerate() {
+      // every bit of the column doubled, over two pages
+      let tall = (0..8).fold(0u16, |tall, bit| {
+        tall | ((*column as u16 >> bit & 1) * 0b11) << (bit * 2)
+      });
+      frame[top * Self::WIDTH + left + x] = tall as u8;
+      frame[(top + 1) * Self::WIDTH + left + x] = (tall >> 8) as u8;
+    }
+    let result = self
+      .command(&[0x21, 0, Self::WIDTH as u8 - 1, 0x22, 0, self.pages - 1])
+      .and_then(|_| {
+        frame.chunks(Self::CHUNK).try_for_each(|chunk| {
+          let mut data = vec![0x40];
+          data.extend_from_slice(chunk);
+          i2cdev::core::I2CDevice::write(&mut self.device, &data)
+        })
+      });
+    if let Err(e) = result {
+      crate::log::log("debug", &format!("Status display failed: {}", e));
+    }
+  }
+
+  fn command(&mut self, bytes: &[u8]) -> Result<(), i2cdev::linux::LinuxI2CError> {
+    for byte in bytes {
+      i2cdev::core::I2CDevice::write(&mut self.device, &[0x00, *byte])?;
+    }
+    Ok(())
+  }
+}
+
+// 5x7 font columns, lowest bit on top
+#[cfg(all(feature = "gpio", target_os = "linux"))]
+fn glyph(c: char) -> [u8; 5] {
+  match c.to_ascii_uppercase() {
+    'A' => [0x7c, 0x12, 0x11, 0x12, 0x7c],
+    'B' => [0x7f, 0x49, 0x49, 0x49, 0x36],
+    'C' => [0x3e, 0x41, 0x41, 0x41, 0x22],
+    'D' => [0x7f, 0x41, 0x41, 0x22, 0x1c],
+    'E' => [0x7f, 0x49, 0x49, 0x49, 0x41],
+    'F' => [0x7f, 0x09, 0x09, 0x09, 0x01],
+    'G' => [0x3e, 0x41, 0x49, 0x49, 0x7a],
+    'H' => [0x7f, 0x08, 0x08, 0x08, 0x7f],
+    'I' => [0x00, 0x41, 0x7f, 0x41, 0x00],
+    'J' => [0x20, 0x40, 0x41, 0x3f, 0x01],
+    'K' => [0x7f, 0x08, 0x14, 0x22, 0x41],
+    'L' => [0x7f, 0x40, 0x40, 0x40, 0x40],
+    'M' => [0x7f, 0x02, 0x0c, 0x02, 0x7f],
+    'N' => [0x7f, 0x04, 0x08, 0x10, 0x7f],
+    'O' => [0x3e, 0x41, 0x41, 0x41, 0x3e],
+    'P' => [0x7f, 0x09, 0x09, 0x09, 0x06],
+    'Q' => [0x3e, 0x41, 0x51, 0x21, 0x5e],
+    'R' => [0x7f, 0x09, 0x19, 0x29, 0x46],
+    'S' => [0x26, 0x49, 0x49, 0x49, 0x32],
+    'T' => [0x01, 0x01, 0x7f, 0x01, 0x01],
+    'U' => [0x3f, 0x40, 0x40, 0x40, 0x3f],
+    'V' => [0x1f, 0x20, 0x40, 0x20, 0x1f],
+    'W' => [0x3f, 0x40, 0x38, 0x40, 0x3f],
+    'X' => [0x63, 0x14, 0x08, 0x14, 0x63],
+    'Y' => [0x07, 0x08, 0x70, 0x08, 0x07],
+    'Z' => [0x61, 0x51, 0x49, 0x45, 0x43],
+    _ => [0; 5],
+  }
+}
+
+// gpio left out of this build, or not on Linux
+#[cfg(not(all(feature = "gpio", target_os = "linux")))]
+struct Led;
+
+#[cfg(not(all(feature = "gpio", target_os = "linux")))]
+impl Led {
+  fn open(_spec: &str) -> Result<Self, String> {
+    Err(UNSUPPORTED.to_string())
+  }
+
+  fn set(&mut self, _lit: bool) {}
+}
+
+#[cfg(not(all(feature = "gpio", target_os = "linux")))]
+struct Display;
+
+#[cfg(not(all(feature = "gpio", target_os = "linux")))]
+impl Display {
+  fn open(_spec: &str) -> Result<Self, String> {
+    Err(UNSUPPORTED.to_string())
+  }
+
+  fn show(&mut self, _text: &str) {}
+}
+
+#[cfg(not(all(feature = "gpio", target_os = "linux")))]
+const UNSUPPORTED: &str = "The status led and display need a Linux build with the gpio feature";
@@ -0,0 +1,179 @@
+// ------------------------------------------------------------------
+//  Conversation history (bounded)
+// ------------------------------------------------------------------
+//
+// The messages of the conversation, sent along every llm request. The oldest
+// turns are left out of the requests once there are more than `max_turns` of
+// them or their text is over `max_tokens`, so a day-long session keeps a
+// steady prompt size. They stay in the transcript, the saved conversation is
+// always whole.
+
+use crate::conversation::ChatMessage;
+use std::collections::{BTreeSet, VecDeque};
+
+// a rough token count, close enough for english and most llm tokenizers
+const BYTES_PER_TOKEN: usize = 4;
+
+// API
+// ------------------------------------------------------------------
+
+#[derive(Debug)]
+pub struct History {
+  messages: VecDeque<ChatMessage>,
+  max_turns: usize,
+  max_tokens: usize,
+  // messages dropped from the requests, oldest first
+  earlier: Vec<ChatMessage>,
+  // bookmarked messages (b key), numbered from the start of the conversation
+  bookmarks: BTreeSet<usize>,
+}
+
+impl History {
+  pub fn new(max_turns: usize, max_tokens: usize) -> Self {
+    Self {
+      messages: VecDeque::new(),
+      max_turns: max_turns.max(1),
+      max_tokens: max_tokens.max(1),
+      earlier: Vec::new(),
+      bookmarks: BTreeSet::new(),
+    }
+  }
+
+  /// A new empty history with the same limits.
+  pub fn empty_like(&self) -> Self {
+    Self::new(self.max_turns, self.max_tokens)
+  }
+
+  /// Changes the limits, dropping the oldest turns over them.
+  pub fn set_limits(&mut self, max_turns: usize, max_tokens: usize) {
+    self.max_turns = max_turns.max(1);
+    self.max_tokens = max_tokens.max(1);
+    self.trim();
+  }
+
+  /// Adds a message, dropping the oldest turns over the limits. Terminal
+  /// escapes are left out, they'd end up in the prompts.
+  pub fn push(&mut self, mut message: ChatMessage) {
+    if message.content.contains('\x1b') {
+      message.content = crate::util::strip_ansi(&message.content);
+    }
+    self.messages.push_back(message);
+    self.trim();
+  }
+
+  pub fn pop(&mut self) -> Option<ChatMessage> {
+    let message = self.messages.pop_back();
+    self
+      .bookmarks
+      .remove(&(self.earlier.len() + self.messages.len()));
+    message
+  }
+
+  pub fn last(&self) -> Option<&ChatMessage> {
+    self.messages.back()
+  }
+
+  /// The last message, to stream the reply into. The limits are checked on
+  /// the next push.
+  pub fn last_mut(&mut self) -> Option<&mut ChatMessage> {
+    self.messages.back_mut()
+  }
+
+  pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, ChatMessage> {
+    self.messages.iter()
+  }
+
+  pub fn len(&self) -> usize {
+    self.messages.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.messages.is_empty()
+  }
+
+  /// Number of old messages dropped to stay within the limits.
+  pub fn dropped(&self) -> usize {
+    self.earlier.len()
+  }
+
+  /// Every message of the conversation, the dropped ones too, with whether
+  /// it's bookmarked. What gets saved.
+  pub fn transcript(&self) -> impl Iterator<Item = (&ChatMessage, bool)> {
+    self
+      .earlier
+      .iter()
+      .chain(self.messages.iter())
+      .enumerate()
+      .map(|(n, message)| (message, self.bookmarks.contains(&n)))
+  }
+
+  pub fn clear(&mut self) {
+    self.messages.clear();
+    self.earlier.clear();
+    self.bookmarks.clear();
+  }
+
+  /// Bookmarks the last message, the current turn (b key), or removes its
+  /// bookmark. Whether it's now bookmarked, None without messages.
+  pub fn toggle_bookmark(&mut self) -> Option<bool> {
+    if self.messages.is_empty() {
+      return None;
+    }
+    let last = self.earlier.len() + self.messages.len() - 1;
+    if self.bookmarks.remove(&last) {
+      Some(false)
+    } else {
+      self.bookmarks.insert(last);
+      Some(true)
+    }
+  }
+
+  /// Whether the message at `index` (in the order of `iter`) is bookmarked.
+  pub fn is_bookmarked(&self, index: usize) -> bool {
+    self.bookmarks.contains(&(self.earlier.len() + index))
+  }
+
+  /// The bookmarked messages still kept, oldest first.
+  pub fn bookmarked(&self) -> Vec<&ChatMessage> {
+    self
+      .bookmarks
+      .iter()
+      .filter_map(|n| n.checked_sub(self.earlier.len()))
+      .filter_map(|i| self.messages.get(i))
+      .collect()
+  }
+
+  // Drops whole turns (a user message and the replies to it) from the front
+  // into `earlier`, never the last one.
+  fn trim(&mut self) {
+    let mut bytes: usize = self.messages.iter().map(|m| m.content.len()).sum();
+    let mut turns = self.messages.iter().filter(|m| m.role == "user").count();
+    while (turns > self.max_turns || bytes / BYTES_PER_TOKEN > self.max_tokens)
+      && self.messages.len() > 1
+    {
+      let Some(first) = self.messages.pop_front() else {
+        break;
+      };
+      bytes -= first.content.len();
+      if first.role == "user" {
+        turns -= 1;
+      }
+      self.earlier.push(first);
+      // the replies of the dropped turn go with it
+      while self.messages.len() > 1 && self.messages.front().is_some_and(|m| m.role != "user") {
+        let reply = self.messages.pop_front().unwrap();
+        bytes -= reply.content.len();
+        self.earlier.push(reply);
+      }
+    }
+  }
+}
+
+impl Default for History {
+  fn default() -> Self {
+    Self::new(
+      crate::config::HISTORY_TURNS_DEFAULT as usize,
+      crate::config::HISTORY_TOKENS_DEFAULT as usize,
+    )
+  }
+}
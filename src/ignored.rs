@@ -0,0 +1,70 @@
+// ------------------------------------------------------------------
+//  Ignored utterances
+// ------------------------------------------------------------------
+//
+// Utterances that never reach the agent: too short to be speech, nothing
+// recognizable in them, another voice (--speaker-verify ignore), the
+// transcription too far behind or a satellite's utterance too long. Each one is counted and its reason shown in
+// the status bar for a moment, so being ignored doesn't look arbitrary.
+
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// how long the reason stays in the status bar
+const SHOWN_FOR: Duration = Duration::from_millis(2500);
+
+static COUNT: AtomicU64 = AtomicU64::new(0);
+static LAST: Mutex<Option<(Reason, Instant)>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reason {
+  // under MIN_UTTERANCE_MS
+  TooShort,
+  // transcribed as nothing
+  NoSpeech,
+  // not the enrolled voice
+  OtherSpeaker,
+  // the utterance queue was full
+  Behind,
+  // a satellite's utterance over the intercom's limit
+  TooLong,
+}
+
+impl Reason {
+  pub fn label(self) -> &'static str {
+    match self {
+      Reason::TooShort => "too short",
+      Reason::NoSpeech => "no speech heard",
+      Reason::OtherSpeaker => "another voice",
+      Reason::Behind => "transcription behind",
+      Reason::TooLong => "too long",
+    }
+  }
+}
+
+/// Counts an utterance ignored for `reason`.
+pub fn record(reason: Reason) {
+  COUNT.fetch_add(1, Ordering::Relaxed);
+  *LAST.lock().unwrap_or_else(PoisonError::into_inner) = Some((reason, Instant::now()));
+  // a reply paused for it goes on
+  crate::interjection::release();
+}
+
+/// Utterances ignored so far.
+pub fn count() -> u64 {
+  COUNT.load(Ordering::Relaxed)
+}
+
+/// The reason of the last one, while it's still shown.
+pub fn recent() -> Option<Reason> {
+  LAST
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .filter(|(_, at)| at.elapsed() < SHOWN_FOR)
+    .map(|(reason, _)| reason)
+}
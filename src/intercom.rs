@@ -0,0 +1,495 @@
+// ------------------------------------------------------------------
+//  Intercom (--intercom PORT / --satellite HOST:PORT)
+// ------------------------------------------------------------------
+//
+// A satellite is an instance with a microphone and a speaker only: it
+// detects the utterances and plays the replies, while the central instance
+// it's connected to transcribes, answers and synthesizes them. They talk over
+// TCP with the framing of the Wyoming protocol (a JSON line per event, then
+// its payload):
+//
+//   satellite -> central   hello {name, secret}, audio-start {rate, width, channels},
+//                          audio-chunk (i16 LE), audio-stop, interrupt
+//   central -> satellite   transcript {text}, phrase {text}, done {text},
+//                          audio-chunk {rate, width, channels} (i16 LE),
+//                          phrase-end, clear
+//
+// The replies go to the satellite heard last, and are played on the central
+// as well. An utterance heard by the central's own microphone takes them
+// back. With --intercom-secret the central hangs up on a satellite whose
+// hello doesn't carry the same secret.
+
+use crate::audio::AudioChunk;
+use crate::cancel::Interrupter;
+use crate::engine::TurnEvent;
+use crate::state::{TurnState, advance_turn};
+use crate::wyoming::{
+  MAX_UTTERANCE_SECS, audio_format, max_utterance_bytes, read_event, write_event,
+};
+use crossbeam_channel::{Receiver, Sender, select, unbounded};
+use serde_json::json;
+use std::io::{BufReader, Error, ErrorKind};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+// samples per audio-chunk event
+const CHUNK_SAMPLES: usize = 2048;
+// wait before connecting again to a central that went away
+const RETRY_DELAY: Duration = Duration::from_secs(3);
+// how often the satellite looks for an interruption to pass on
+const INTERRUPT_POLL: Duration = Duration::from_millis(50);
+
+// what the central sends to a satellite
+enum Outgoing {
+  Voice(AudioChunk),
+  PhraseEnd,
+  Clear,
+  Turn(TurnEvent),
+}
+
+struct Satellite {
+  id: u64,
+  tx: Sender<Outgoing>,
+}
+
+static SATELLITES: Mutex<Vec<Satellite>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+// the satellite heard last, 0 when it's the central itself
+static ACTIVE: AtomicU64 = AtomicU64::new(0);
+
+// API
+// ------------------------------------------------------------------
+
+/// Central side: accepts the satellites on `addr` and hands their utterances
+/// to the transcription. Blocks, one thread per satellite.
+pub fn intercom_thread(
+  addr: &str,
+  secret: Option<String>,
+  tx_utt: Sender<AudioChunk>,
+  stop_play_tx: Sender<()>,
+) -> std::io::Result<()> {
+  let listener = TcpListener::bind(addr)?;
+  let events = crate::events::subscribe();
+  thread::spawn(move || {
+    for event in events {
+      if matches!(
+        event,
+        TurnEvent::Transcript(_) | TurnEvent::Phrase(_) | TurnEvent::Done(_)
+      ) {
+        send_active(Outgoing::Turn(event));
+      }
+    }
+  });
+  for stream in listener.incoming() {
+    let Ok(stream) = stream else { continue };
+    let tx_utt = tx_utt.clone();
+    let stop_play_tx = stop_play_tx.clone();
+    let secret = secret.clone();
+    thread::spawn(move || {
+      if let Err(e) = serve_satellite(stream, secret.as_deref(), tx_utt, stop_play_tx) {
+        crate::log::log("debug", &format!("Intercom connection error: {}", e));
+      }
+    });
+  }
+  Ok(())
+}
+
+/// Audio of the reply being played, for the satellite heard last.
+pub fn played(chunk: &AudioChunk) {
+  send_active(Outgoing::Voice(chunk.clone()));
+}
+
+/// The audio of a phrase ended.
+pub fn phrase_end() {
+  send_active(Outgoing::PhraseEnd);
+}
+
+/// The playback was stopped.
+pub fn stopped() {
+  send_active(Outgoing::Clear);
+}
+
+/// The central's own microphone was heard: the replies stay here.
+pub fn heard_here() {
+  ACTIVE.store(0, Ordering::Relaxed);
+}
+
+/// Satellite side: sends the utterances to the central at `central` and
+/// plays its replies, reconnecting whenever the connection is lost.
+pub fn satellite_thread(
+  central: String,
+  secret: Option<String>,
+  rx_utt: Receiver<AudioChunk>,
+  tx_play: Sender<AudioChunk>,
+  stop_play_tx: Sender<()>,
+  tx_ui: Sender<String>,
+  interrupter: Interrupter,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  loop {
+    let stream = match TcpStream::connect(&central) {
+      Ok(stream) => stream,
+      Err(e) => {
+        crate::log::log(
+          "warning",
+          &format!("Intercom: central {} unreachable: {}", central, e),
+        );
+        thread::sleep(RETRY_DELAY);
+        continue;
+      }
+    };
+    let _ = stream.set_nodelay(true);
+    let closer = stream.try_clone()?;
+    let _ = tx_ui.send(format!(
+      "line|\x1b[32m📡 Connected to the central at {}\x1b[0m",
+      central
+    ));
+    if let Err(e) = run_satellite(
+      stream,
+      secret.as_deref(),
+      &rx_utt,
+      &tx_play,
+      &stop_play_tx,
+      &tx_ui,
+      &interrupter,
+    ) {
+      crate::log::log("debug", &format!("Intercom connection error: {}", e));
+    }
+    // ends the reader of the replies as well
+    let _ = closer.shutdown(std::net::Shutdown::Both);
+    let _ = tx_ui.send(format!(
+      "line|\x1b[33m📡 Lost the central at {}, reconnecting\x1b[0m",
+      central
+    ));
+    thread::sleep(RETRY_DELAY);
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn send_active(message: Outgoing) {
+  let active = ACTIVE.load(Ordering::Relaxed);
+  if active == 0 {
+    return;
+  }
+  let satellites = SATELLITES.lock().unwrap_or_else(PoisonError::into_inner);
+  if let Some(satellite) = satellites.iter().find(|s| s.id == active) {
+    let _ = satellite.tx.send(message);
+  }
+}
+
+// one satellite connected to the central
+fn serve_satellite(
+  stream: TcpStream,
+  secret: Option<&str>,
+  tx_utt: Sender<AudioChunk>,
+  stop_play_tx: Sender<()>,
+) -> std::io::Result<()> {
+  let _ = stream.set_nodelay(true);
+  let peer = stream
+    .peer_addr()
+    .map(|a| a.to_string())
+    .unwrap_or_default();
+  let mut writer = stream.try_clone()?;
+  let mut reader = BufReader::new(stream);
+  let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+  let (tx, rx) = unbounded::<Outgoing>();
+  SATELLITES
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .push(Satellite { id, tx });
+  thread::spawn(move || {
+    for message in rx {
+      if send(&mut writer, message).is_err() {
+        break;
+      }
+    }
+  });
+
+  let result = read_satellite(&mut reader, id, &peer, secret, &tx_utt, &stop_play_tx);
+  SATELLITES
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .retain(|s| s.id != id);
+  let _ = ACTIVE.compare_exchange(id, 0, Ordering::Relaxed, Ordering::Relaxed);
+  crate::log::log(
+    "info",
+    &format!("Intercom: satellite {} disconnected", peer),
+  );
+  result
+}
+
+// the events of one satellite, until it disconnects
+fn read_satellite(
+  reader: &mut BufReader<TcpStream>,
+  id: u64,
+  peer: &str,
+  secret: Option<&str>,
+  tx_utt: &Sender<AudioChunk>,
+  stop_play_tx: &Sender<()>,
+) -> std::io::Result<()> {
+  let mut format = (16000u32, 2u16, 1u16);
+  let mut pcm: Vec<u8> = Vec::new();
+  // the audio of the utterance went over MAX_UTTERANCE_SECS
+  let mut too_long = false;
+  let mut greeted = secret.is_none();
+  while let Some(event) = read_event(reader)? {
+    if !greeted && event.kind != "hello" {
+      return Err(Error::new(ErrorKind::PermissionDenied, "no hello"));
+    }
+    match event.kind.as_str() {
+      "hello" => {
+        let name = event
+          .data
+          .get("name")
+          .and_then(|n| n.as_str())
+          .unwrap_or("satellite");
+        let given = event.data.get("secret").and_then(|s| s.as_str());
+        if let Some(secret) = secret
+          && !given.is_some_and(|given| same_secret(given, secret))
+        {
+          crate::log::log(
+            "warning",
+            &format!(
+              "Intercom: satellite '{}' at {} gave the wrong secret",
+              name, peer
+            ),
+          );
+          return Err(Error::new(ErrorKind::PermissionDenied, "wrong secret"));
+        }
+        greeted = true;
+        crate::log::log(
+          "info",
+          &format!("Intercom: satellite '{}' connected from {}", name, peer),
+        );
+      }
+      "audio-start" => {
+        format = audio_format(&event)?;
+        pcm.clear();
+        too_long = false;
+      }
+      "audio-chunk" => {
+        if too_long || pcm.len() + event.payload.len() > max_utterance_bytes(format) {
+          too_long = true;
+          pcm = Vec::new();
+        } else {
+          pcm.extend_from_slice(&event.payload);
+        }
+      }
+      "audio-stop" if too_long => {
+        too_long = false;
+        crate::log::log(
+          "warning",
+          &format!(
+            "Intercom: utterance of {} over {} s dropped",
+            peer, MAX_UTTERANCE_SECS
+          ),
+        );
+        crate::ignored::record(crate::ignored::Reason::TooLong);
+      }
+      "audio-stop" => {
+        let (sample_rate, _, channels) = format;
+        let data: Arc<[f32]> = std::mem::take(&mut pcm)
+          .chunks_exact(2)
+          .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / i16::MAX as f32)
+          .collect();
+        ACTIVE.store(id, Ordering::Relaxed);
+        crate::util::SPEECH_END_AT
+          .store(crate::util::now_ms(&crate::START_INSTANT), Ordering::SeqCst);
+        advance_turn(
+          &[TurnState::Idle, TurnState::Listening],
+          TurnState::Transcribing,
+        );
+        if tx_utt
+          .try_send(AudioChunk {
+            data,
+            channels,
+            sample_rate,
+          })
+          .is_err()
+        {
+          crate::ignored::record(crate::ignored::Reason::Behind);
+        }
+      }
+      "interrupt" if ACTIVE.load(Ordering::Relaxed) == id => {
+        if let Some(state) = crate::state::GLOBAL_STATE.get() {
+          state.interrupter.interrupt();
+        }
+        let _ = stop_play_tx.try_send(());
+        crate::events::emit(TurnEvent::Interrupted);
+        advance_turn(
+          &[TurnState::Generating, TurnState::Speaking],
+          TurnState::Interrupted,
+        );
+      }
+      _ => {}
+    }
+  }
+  Ok(())
+}
+
+fn send(writer: &mut TcpStream, message: Outgoing) -> std::io::Result<()> {
+  match message {
+    Outgoing::Voice(chunk) => {
+      let format = json!({ "rate": chunk.sample_rate, "width": 2, "channels": chunk.channels });
+      let bytes: Vec<u8> = crate::audio::f32_to_i16(&chunk.data)
+        .iter()
+        .flat_map(|s| s.to_le_bytes())
+        .collect();
+      write_event(writer, "audio-chunk", format, &bytes)
+    }
+    Outgoing::PhraseEnd => write_event(writer, "phrase-end", json!({}), &[]),
+    Outgoing::Clear => write_event(writer, "clear", json!({}), &[]),
+    Outgoing::Turn(TurnEvent::Transcript(text)) => {
+      write_event(writer, "transcript", json!({ "text": text }), &[])
+    }
+    Outgoing::Turn(TurnEvent::Phrase(text)) => {
+      write_event(writer, "phrase", json!({ "text": text }), &[])
+    }
+    Outgoing::Turn(TurnEvent::Done(text)) => {
+      write_event(writer, "done", json!({ "text": text }), &[])
+    }
+    Outgoing::Turn(_) => Ok(()),
+  }
+}
+
+// compares all of the bytes, the time taken tells nothing of the secret
+fn same_secret(given: &str, secret: &str) -> bool {
+  given.len() == secret.len()
+    && given
+      .bytes()
+      .zip(secret.bytes())
+      .fold(0, |diff, (a, b)| diff | (a ^ b))
+      == 0
+}
+
+// one connection of the satellite to the central, until it's lost
+fn run_satellite(
+  stream: TcpStream,
+  secret: Option<&str>,
+  rx_utt: &Receiver<AudioChunk>,
+  tx_play: &Sender<AudioChunk>,
+  stop_play_tx: &Sender<()>,
+  tx_ui: &Sender<String>,
+  interrupter: &Interrupter,
+) -> std::io::Result<()> {
+  let mut writer = stream.try_clone()?;
+  let (tx_closed, rx_closed) = unbounded::<()>();
+  {
+    let mut reader = BufReader::new(stream);
+    let tx_play = tx_play.clone();
+    let stop_play_tx = stop_play_tx.clone();
+    let tx_ui = tx_ui.clone();
+    thread::spawn(move || {
+      while let Ok(Some(event)) = read_event(&mut reader) {
+        let text = event
+          .data
+          .get("text")
+          .and_then(|t| t.as_str())
+          .unwrap_or("")
+          .to_string();
+        match event.kind.as_str() {
+          "audio-chunk" => {
+            let field = |key: &str, default: u64| {
+              event
+                .data
+                .get(key)
+                .and_then(|v| v.as_u64())
+                .unwrap_or(default)
+            };
+            let data: Arc<[f32]> = event
+              .payload
+              .chunks_exact(2)
+              .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / i16::MAX as f32)
+              .collect();
+            let _ = tx_play.send(AudioChunk {
+              data,
+              channels: field("channels", 1) as u16,
+              sample_rate: field("rate", 22050) as u32,
+            });
+          }
+          "phrase-end" => {
+            let _ = tx_play.send(crate::playback::phrase_end());
+          }
+          "clear" => {
+            let _ = stop_play_tx.try_send(());
+          }
+          "transcript" => {
+            advance_turn(&[TurnState::Idle], TurnState::Generating);
+            let _ = tx_ui.send("line|\n".to_string());
+            let _ = tx_ui.send(format!("line|{}", crate::ui::user_label()));
+            let _ = tx_ui.send(format!("line|{}", text));
+            let _ = tx_ui.send("line|".to_string());
+            let _ = tx_ui.send(format!("line|{}", crate::ui::assist_label()));
+          }
+          "phrase" => {
+            let _ = tx_ui.send(format!("stream|{} ", text));
+          }
+          "done" => {
+            advance_turn(
+              &[TurnState::Generating, TurnState::Speaking],
+              TurnState::Idle,
+            );
+            let _ = tx_ui.send("line|".to_string());
+          }
+          _ => {}
+        }
+      }
+      let _ = tx_closed.send(());
+    });
+  }
+
+  let mut hello = json!({ "name": satellite_name() });
+  if let Some(secret) = secret {
+    hello["secret"] = secret.into();
+  }
+  write_event(&mut writer, "hello", hello, &[])?;
+  let mut interruptions = interrupter.count();
+  loop {
+    select! {
+      recv(rx_utt) -> msg => {
+        let Ok(utterance) = msg else { return Ok(()) };
+        send_utterance(&mut writer, &utterance)?;
+        advance_turn(&[TurnState::Transcribing], TurnState::Idle);
+      }
+      recv(rx_closed) -> _ => return Ok(()),
+      default(INTERRUPT_POLL) => {}
+    }
+    // a barge-in or Esc here stops the reply on the central too
+    let count = interrupter.count();
+    if count != interruptions {
+      interruptions = count;
+      let _ = stop_play_tx.try_send(());
+      write_event(&mut writer, "interrupt", json!({}), &[])?;
+    }
+  }
+}
+
+fn send_utterance(writer: &mut TcpStream, utterance: &AudioChunk) -> std::io::Result<()> {
+  let format = json!({
+    "rate": utterance.sample_rate,
+    "width": 2,
+    "channels": utterance.channels,
+  });
+  write_event(writer, "audio-start", format, &[])?;
+  let samples = crate::audio::f32_to_i16(&utterance.data);
+  for part in samples.chunks(CHUNK_SAMPLES * utterance.channels.max(1) as usize) {
+    let bytes: Vec<u8> = part.iter().flat_map(|s| s.to_le_bytes()).collect();
+    write_event(writer, "audio-chunk", json!({}), &bytes)?;
+  }
+  write_event(writer, "audio-stop", json!({}), &[])
+}
+
+fn satellite_name() -> String {
+  std::env::var("HOSTNAME")
+    .ok()
+    .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+    .map(|n| n.trim().to_string())
+    .filter(|n| !n.is_empty())
+    .unwrap_or_else(|| "satellite".to_string())
+}
@@ -0,0 +1,136 @@
+// ------------------------------------------------------------------
+//  Interjections (speech over the reply)
+// ------------------------------------------------------------------
+//
+// Speech over the reply pauses it instead of cutting it off, until what was
+// said is transcribed: a control command ("louder", "slower", "go on") is
+// applied and the reply goes on where it was, "stop" ends it, and anything
+// else is a new question that interrupts it, as before.
+
+use crate::state::GLOBAL_STATE;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// off unless set_enabled: speech over the reply interrupts it at once
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// the reply paused for an interjection not transcribed yet
+static HELD: AtomicBool = AtomicBool::new(false);
+
+// API
+// ------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interjection {
+  // applied, the reply goes on
+  Command(Control),
+  // the reply ends, nothing to answer
+  Stop,
+  // the reply ends and it's answered
+  Question,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+  Louder,
+  Quieter,
+  Faster,
+  Slower,
+  // "go on", "ok": nothing but to let the reply go on
+  Continue,
+}
+
+/// Pauses the reply on speech over it rather than interrupting it
+/// (off with --barge-in-interrupts).
+pub fn set_enabled(enabled: bool) {
+  ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Speech over the reply: pauses it until the utterance is classified.
+/// False when it should be interrupted right away, as when off or already
+/// paused by the user.
+pub fn hold() -> bool {
+  if !ENABLED.load(Ordering::Relaxed) {
+    return false;
+  }
+  let Some(state) = GLOBAL_STATE.get() else {
+    return false;
+  };
+  if HELD.load(Ordering::Relaxed) {
+    return true;
+  }
+  if state.playback.paused.load(Ordering::Relaxed) {
+    return false;
+  }
+  state.playback.paused.store(true, Ordering::Relaxed);
+  HELD.store(true, Ordering::Relaxed);
+  crate::log::log("info", "Reply paused for an interjection");
+  true
+}
+
+/// Whether the reply waits for an interjection to be classified.
+pub fn is_held() -> bool {
+  HELD.load(Ordering::Relaxed)
+}
+
+/// Unpauses the reply held for an interjection: it goes on, or what is left
+/// of it after an interruption is dropped by then.
+pub fn release() {
+  if HELD.swap(false, Ordering::Relaxed)
+    && let Some(state) = GLOBAL_STATE.get()
+  {
+    state.playback.paused.store(false, Ordering::Relaxed);
+  }
+}
+
+/// What an utterance said over the reply is.
+pub fn classify(text: &str) -> Interjection {
+  let words = normalize(text);
+  let words = words.strip_suffix(" please").unwrap_or(&words);
+  let words = words.strip_prefix("please ").unwrap_or(words);
+  let control = match words {
+    "louder" | "speak up" | "volume up" | "turn it up" | "a bit louder" => Control::Louder,
+    "quieter" | "softer" | "volume down" | "turn it down" | "not so loud" | "a bit quieter" => {
+      Control::Quieter
+    }
+    "faster" | "speed up" | "talk faster" | "speak faster" | "a bit faster" => Control::Faster,
+    "slower" | "slow down" | "talk slower" | "speak slower" | "a bit slower" => Control::Slower,
+    "go on" | "continue" | "keep going" | "go ahead" | "ok" | "okay" | "yes" | "yeah" | "right"
+    | "uh huh" | "mhm" | "sorry" => Control::Continue,
+    "stop" | "stop it" | "stop talking" | "be quiet" | "quiet" | "shut up" | "enough"
+    | "thats enough" | "never mind" | "nevermind" | "cancel" => return Interjection::Stop,
+    _ => return Interjection::Question,
+  };
+  Interjection::Command(control)
+}
+
+/// Applies a control command, returns the line telling what it did.
+pub fn apply(control: Control) -> String {
+  crate::telemetry::feature("interjection_command");
+  match control {
+    Control::Louder => format!("🔊 Louder ({:.2}x)", crate::playback::step_level(true)),
+    Control::Quieter => format!("🔉 Quieter ({:.2}x)", crate::playback::step_level(false)),
+    Control::Faster | Control::Slower => {
+      // a step of the up / down keys is too small to be heard
+      for _ in 0..2 {
+        if control == Control::Faster {
+          crate::state::increase_voice_speed();
+        } else {
+          crate::state::decrease_voice_speed();
+        }
+      }
+      format!("⏩ Voice speed {:.1}", crate::state::get_speed())
+    }
+    Control::Continue => "▶️ Going on".to_string(),
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn normalize(text: &str) -> String {
+  let words: String = text
+    .to_lowercase()
+    .chars()
+    .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+    .collect();
+  words.split_whitespace().collect::<Vec<_>>().join(" ")
+}
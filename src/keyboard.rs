@@ -2,18 +2,20 @@
 //  Keyboard handling
 // ------------------------------------------------------------------
 
+use crate::cancel::Interrupter;
 use crate::conversation::Command;
-use crate::state::{GLOBAL_STATE, decrease_voice_speed, increase_voice_speed};
+use crate::state::{GLOBAL_STATE, TurnState, decrease_voice_speed, increase_voice_speed};
 use crossbeam_channel::Sender;
 use crossterm::{
   event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
   terminal,
 };
 
-use crate::util::terminate;
+use crate::util::shutdown;
+use std::sync::PoisonError;
 use std::sync::{
   Arc,
-  atomic::{AtomicBool, AtomicU64, Ordering},
+  atomic::{AtomicBool, Ordering},
 };
 use std::thread;
 use std::time::{Duration, Instant};
@@ -33,7 +35,7 @@ pub fn keyboard_thread(
   tx_ui: Sender<String>,
   recording_paused: Arc<AtomicBool>,
   stop_play_tx: Sender<()>,
-  interrupt_counter: Arc<AtomicU64>,
+  interrupter: Interrupter,
   // Optional parameters for read-file mode
   read_file_mode: Option<ReadFileMode>,
   tx_cmd: Sender<Command>,
@@ -53,7 +55,14 @@ pub fn keyboard_thread(
     }
 
     if event::poll(Duration::from_millis(50)).unwrap_or(false) {
-      if let Ok(Event::Key(k)) = event::read() {
+      let ev = event::read();
+      crate::power::touch();
+      match ev {
+        Ok(Event::FocusGained) => crate::notify::set_focused(true),
+        Ok(Event::FocusLost) => crate::notify::set_focused(false),
+        _ => {}
+      }
+      if let Ok(Event::Key(k)) = ev {
         // Handle read-file mode separately
         if let Some(ref rfm) = read_file_mode {
           if k.kind != KeyEventKind::Press {
@@ -75,7 +84,7 @@ pub fn keyboard_thread(
               if curr > 0 {
                 // Stop current playback
                 let _ = stop_play_tx.try_send(());
-                interrupt_counter.fetch_add(1, Ordering::SeqCst);
+                interrupter.interrupt();
                 // Move to previous phrase
                 rfm.current_phrase.store(curr - 1, Ordering::SeqCst);
                 rfm.tts_paused.store(false, Ordering::SeqCst);
@@ -89,7 +98,7 @@ pub fn keyboard_thread(
               if curr < rfm.phrases_len - 1 {
                 // Stop current playback
                 let _ = stop_play_tx.try_send(());
-                interrupt_counter.fetch_add(1, Ordering::SeqCst);
+                interrupter.interrupt();
                 // Move to next phrase
                 rfm.current_phrase.store(curr + 1, Ordering::SeqCst);
                 rfm.tts_paused.store(false, Ordering::SeqCst);
@@ -103,8 +112,8 @@ pub fn keyboard_thread(
                 // Resume TTS playback - move index back one element if possible
                 let curr = rfm.current_phrase.load(Ordering::SeqCst);
                 if curr > 0 {
-                  // Immediately abort any ongoing TTS/LLM by incrementing interrupt counter
-                  interrupt_counter.fetch_add(1, Ordering::SeqCst);
+                  // Immediately abort any ongoing TTS/LLM by interrupting it
+                  interrupter.interrupt();
                   thread::sleep(Duration::from_millis(10));
                   // Stop playback first
                   let _ = stop_play_tx.try_send(());
@@ -116,7 +125,7 @@ pub fn keyboard_thread(
                 // Stop TTS playback
                 rfm.tts_paused.store(true, Ordering::SeqCst);
                 let _ = stop_play_tx.try_send(());
-                interrupt_counter.fetch_add(1, Ordering::SeqCst);
+                interrupter.interrupt();
               }
             }
             _ => {}
@@ -131,7 +140,7 @@ pub fn keyboard_thread(
         if k.modifiers.contains(KeyModifiers::CONTROL) {
           if let KeyCode::Char('c') | KeyCode::Char('C') = k.code {
             thread::sleep(Duration::from_millis(20));
-            terminate(0);
+            shutdown(0);
           }
           // Ctrl+D toggles debate mode or shows modal
           if let KeyCode::Char('d') | KeyCode::Char('D') = k.code {
@@ -145,10 +154,18 @@ pub fn keyboard_thread(
                 if agents.len() >= 2 {
                   // Show modal for agent selection
                   state.debate_modal_visible.store(true, Ordering::SeqCst);
-                  *state.debate_modal_selected_agent1.lock().unwrap() = 0;
-                  *state.debate_modal_selected_agent2.lock().unwrap() =
-                    if agents.len() > 1 { 1 } else { 0 };
-                  *state.debate_modal_focus.lock().unwrap() = 0;
+                  *state
+                    .debate_modal_selected_agent1
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = 0;
+                  *state
+                    .debate_modal_selected_agent2
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = if agents.len() > 1 { 1 } else { 0 };
+                  *state
+                    .debate_modal_focus
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner) = 0;
                   let _ = tx_ui.send("modal_show|".to_string());
                 } else {
                   // Not enough agents
@@ -160,11 +177,18 @@ pub fn keyboard_thread(
                 // Exiting debate mode
                 state.debate_enabled.store(false, Ordering::SeqCst);
                 state.reset_conversation();
-                state.debate_agents.lock().unwrap().clear();
+                state
+                  .debate_agents
+                  .lock()
+                  .unwrap_or_else(PoisonError::into_inner)
+                  .clear();
                 state.debate_turn.store(0, Ordering::SeqCst);
-                *state.debate_subject.lock().unwrap() = String::new();
+                *state
+                  .debate_subject
+                  .lock()
+                  .unwrap_or_else(PoisonError::into_inner) = String::new();
                 // Interrupt any ongoing TTS playback
-                interrupt_counter.fetch_add(1, Ordering::SeqCst);
+                interrupter.interrupt();
                 state
                   .playback
                   .playback_active
@@ -181,29 +205,27 @@ pub fn keyboard_thread(
           && k.kind == KeyEventKind::Press
         {
           // If a response is currently being processed, cancel undo
-          if state.processing_response.load(Ordering::Relaxed) {
+          if state.turn.get() == TurnState::Generating {
             continue;
           }
           // Interrupt TTS
-          interrupt_counter.fetch_add(1, Ordering::SeqCst);
+          interrupter.interrupt();
           thread::sleep(Duration::from_millis(10));
           // Ensure we also stop any ongoing playback first
           let _ = stop_play_tx.try_send(());
           // If a response is in progress, interrupt it first
-          if state.processing_response.load(Ordering::Relaxed) {
-            if !state.undo_pending.load(Ordering::SeqCst) {
-              state.undo_pending.store(true, Ordering::SeqCst);
-              // If debate is active, pause it
-              if state.debate_enabled.load(Ordering::SeqCst) {
-                if !state.debate_paused.load(Ordering::SeqCst) {
-                  state.debate_paused.store(true, Ordering::SeqCst);
-                  // send UI
-                  let _ = tx_ui.send(
-                    "line|\n\x1b[32m🚩 Debate paused, speak again to continue \x1b[0m\n"
-                      .to_string(),
-                  );
-                }
-              }
+          if state.turn.get() == TurnState::Generating && !state.undo_pending.load(Ordering::SeqCst)
+          {
+            state.undo_pending.store(true, Ordering::SeqCst);
+            // If debate is active, pause it
+            if state.debate_enabled.load(Ordering::SeqCst)
+              && !state.debate_paused.load(Ordering::SeqCst)
+            {
+              state.debate_paused.store(true, Ordering::SeqCst);
+              // send UI
+              let _ = tx_ui.send(
+                "line|\n\x1b[32m🚩 Debate paused, speak again to continue \x1b[0m\n".to_string(),
+              );
             }
           }
           // Send undo command
@@ -223,8 +245,14 @@ pub fn keyboard_thread(
             KeyCode::Enter => {
               // Confirm selection and start debate
               let agents = state.agents.as_ref();
-              let agent1_idx = *state.debate_modal_selected_agent1.lock().unwrap();
-              let agent2_idx = *state.debate_modal_selected_agent2.lock().unwrap();
+              let agent1_idx = *state
+                .debate_modal_selected_agent1
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+              let agent2_idx = *state
+                .debate_modal_selected_agent2
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
 
               if agent1_idx == agent2_idx {
                 let _ = tx_ui.send(
@@ -232,9 +260,15 @@ pub fn keyboard_thread(
                 );
               } else {
                 let debate_agents = vec![agents[agent1_idx].clone(), agents[agent2_idx].clone()];
-                *state.debate_agents.lock().unwrap() = debate_agents;
+                *state
+                  .debate_agents
+                  .lock()
+                  .unwrap_or_else(PoisonError::into_inner) = debate_agents;
                 state.debate_turn.store(0, Ordering::SeqCst);
-                *state.debate_subject.lock().unwrap() =
+                *state
+                  .debate_subject
+                  .lock()
+                  .unwrap_or_else(PoisonError::into_inner) =
                   "Let's debate. What should we discuss?".to_string();
                 state.debate_enabled.store(true, Ordering::SeqCst);
                 state.reset_conversation();
@@ -249,12 +283,18 @@ pub fn keyboard_thread(
               }
             }
             KeyCode::Up => {
-              let focus = *state.debate_modal_focus.lock().unwrap();
+              let focus = *state
+                .debate_modal_focus
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
               let agents = state.agents.as_ref();
 
               if focus == 0 {
                 // Agent 1 selection - move up
-                let mut agent1_idx = state.debate_modal_selected_agent1.lock().unwrap();
+                let mut agent1_idx = state
+                  .debate_modal_selected_agent1
+                  .lock()
+                  .unwrap_or_else(PoisonError::into_inner);
                 *agent1_idx = if *agent1_idx == 0 {
                   agents.len() - 1
                 } else {
@@ -263,7 +303,10 @@ pub fn keyboard_thread(
                 let _ = tx_ui.send("modal_update|".to_string());
               } else if focus == 1 {
                 // Agent 2 selection - move up
-                let mut agent2_idx = state.debate_modal_selected_agent2.lock().unwrap();
+                let mut agent2_idx = state
+                  .debate_modal_selected_agent2
+                  .lock()
+                  .unwrap_or_else(PoisonError::into_inner);
                 *agent2_idx = if *agent2_idx == 0 {
                   agents.len() - 1
                 } else {
@@ -273,24 +316,36 @@ pub fn keyboard_thread(
               }
             }
             KeyCode::Down => {
-              let focus = *state.debate_modal_focus.lock().unwrap();
+              let focus = *state
+                .debate_modal_focus
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
               let agents = state.agents.as_ref();
 
               if focus == 0 {
                 // Agent 1 selection - move down
-                let mut agent1_idx = state.debate_modal_selected_agent1.lock().unwrap();
+                let mut agent1_idx = state
+                  .debate_modal_selected_agent1
+                  .lock()
+                  .unwrap_or_else(PoisonError::into_inner);
                 *agent1_idx = (*agent1_idx + 1) % agents.len();
                 let _ = tx_ui.send("modal_update|".to_string());
               } else if focus == 1 {
                 // Agent 2 selection - move down
-                let mut agent2_idx = state.debate_modal_selected_agent2.lock().unwrap();
+                let mut agent2_idx = state
+                  .debate_modal_selected_agent2
+                  .lock()
+                  .unwrap_or_else(PoisonError::into_inner);
                 *agent2_idx = (*agent2_idx + 1) % agents.len();
                 let _ = tx_ui.send("modal_update|".to_string());
               }
             }
             KeyCode::Left | KeyCode::Right | KeyCode::Tab => {
               // Switch focus between agent1, agent2, and confirm button
-              let mut focus = state.debate_modal_focus.lock().unwrap();
+              let mut focus = state
+                .debate_modal_focus
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
               if k.code == KeyCode::Left {
                 *focus = if *focus == 0 { 2 } else { *focus - 1 };
               } else {
@@ -336,12 +391,16 @@ pub fn keyboard_thread(
           KeyCode::Esc => {
             let state = GLOBAL_STATE.get().expect("AppState not initialized");
             // Interrupt LLM/TTS
-            interrupt_counter.fetch_add(1, Ordering::SeqCst);
+            interrupter.interrupt();
+            crate::events::emit(crate::engine::TurnEvent::Interrupted);
             thread::sleep(Duration::from_millis(10));
             // Ensure we also stop any ongoing playback first
             let _ = stop_play_tx.try_send(());
             thread::sleep(Duration::from_millis(10));
-            state.processing_response.store(false, Ordering::Relaxed);
+            state.turn.advance(
+              &[TurnState::Generating, TurnState::Speaking],
+              TurnState::Interrupted,
+            );
             if state.debate_enabled.load(Ordering::SeqCst) {
               // only send the message once when we transition from running to paused
               if !state.debate_paused.load(Ordering::SeqCst) {
@@ -369,6 +428,137 @@ pub fn keyboard_thread(
             }
           }
 
+          // bookmark the current turn
+          KeyCode::Char('b') => {
+            if k.kind == KeyEventKind::Press {
+              crate::telemetry::feature("bookmark");
+              let mut hist = state
+                .conversation_history
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+              let line = match hist.toggle_bookmark() {
+                Some(true) => format!(
+                  "🔖 Bookmarked: {} (B lists the bookmarks)",
+                  bookmark_preview(hist.last().map_or("", |m| m.content.as_str()))
+                ),
+                Some(false) => "🔖 Bookmark removed".to_string(),
+                None => "🔖 Nothing to bookmark yet".to_string(),
+              };
+              let _ = tx_ui.send(format!("line|\n\x1b[35m{}\x1b[0m\n", line));
+            }
+          }
+
+          // list the bookmarks
+          KeyCode::Char('B') => {
+            if k.kind == KeyEventKind::Press {
+              let hist = state
+                .conversation_history
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+              let bookmarked = hist.bookmarked();
+              let mut lines = vec![format!("🔖 {} bookmarks", bookmarked.len())];
+              for (n, msg) in bookmarked.iter().enumerate() {
+                lines.push(format!("  {}. {}", n + 1, bookmark_preview(&msg.content)));
+              }
+              let _ = tx_ui.send(format!("line|\n\x1b[35m{}\x1b[0m\n", lines.join("\n")));
+            }
+          }
+
+          // toggle do not disturb
+          KeyCode::Char('z') | KeyCode::Char('Z') => {
+            if k.kind == KeyEventKind::Press {
+              crate::telemetry::feature("dnd_key");
+              let line = if crate::dnd::toggle() {
+                "🌙 Do not disturb ON, microphone off (press z again to turn it off)"
+              } else {
+                "🔔 Do not disturb OFF"
+              };
+              let _ = tx_ui.send(format!("line|\n\x1b[35m{}\x1b[0m\n", line));
+            }
+          }
+
+          // toggle rapid mode
+          KeyCode::Char('r') | KeyCode::Char('R') => {
+            if k.kind == KeyEventKind::Press {
+              crate::telemetry::feature("rapid_mode");
+              let line = if crate::rapid::toggle() {
+                "⚡ Rapid mode ON, no wake phrase and a shorter end of speech (press r again to turn it off)"
+              } else {
+                "⚡ Rapid mode OFF"
+              };
+              let _ = tx_ui.send(format!("line|\n\x1b[35m{}\x1b[0m\n", line));
+            }
+          }
+
+          // toggle fast transcription
+          KeyCode::Char('f') | KeyCode::Char('F') => {
+            if k.kind == KeyEventKind::Press {
+              crate::telemetry::feature("fast_stt");
+              let line = if crate::fast::toggle() {
+                "🐇 Fast mode ON, transcribed with the tiny model (press f again for the accurate one)"
+              } else {
+                "🐢 Accurate mode, transcribed with the model of the agent"
+              };
+              let _ = tx_ui.send(format!("line|\n\x1b[35m{}\x1b[0m\n", line));
+            }
+          }
+
+          // next tts backend
+          KeyCode::Char('t') | KeyCode::Char('T') => {
+            if k.kind == KeyEventKind::Press {
+              let line = match crate::tts::switch_backend(None) {
+                Ok((backend, voice)) => format!(
+                  "🗣️ Speaking with {} ({}) from the next phrase (press t for the next tts)",
+                  backend, voice
+                ),
+                Err(e) => format!("🗣️ {}", e),
+              };
+              let _ = tx_ui.send(format!("line|\n\x1b[35m{}\x1b[0m\n", line));
+            }
+          }
+
+          // next context
+          KeyCode::Char('x') | KeyCode::Char('X') => {
+            if k.kind == KeyEventKind::Press && !state.debate_enabled.load(Ordering::SeqCst) {
+              let line = match crate::contexts::next() {
+                Some(line) => {
+                  // a reply in progress belongs to the context left
+                  state.interrupter.interrupt();
+                  let _ = stop_play_tx.try_send(());
+                  line
+                }
+                None => "Only one context, start with --context NAME or say \"switch to the NAME context\"".to_string(),
+              };
+              let _ = tx_ui.send(format!("line|\n\x1b[35m🗂️  {}\x1b[0m\n", line));
+            }
+          }
+
+          // toggle text only answers
+          KeyCode::Char('m') | KeyCode::Char('M') => {
+            if k.kind == KeyEventKind::Press {
+              crate::telemetry::feature("text_only_key");
+              let line = if crate::tts::toggle_muted() {
+                let _ = stop_play_tx.try_send(());
+                "🔇 Voice OFF, answers in text only (press m again to hear them)"
+              } else {
+                "🔊 Voice ON"
+              };
+              let _ = tx_ui.send(format!("line|\n\x1b[35m{}\x1b[0m\n", line));
+            }
+          }
+
+          // microphone gain up / down
+          KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Char('-') => {
+            if k.kind == KeyEventKind::Press {
+              crate::telemetry::feature("mic_gain_key");
+              let gain = crate::gain::step(k.code != KeyCode::Char('-'));
+              let _ = tx_ui.send(format!(
+                "line|\n\x1b[35m🎙️ Microphone gain {:.2}x\x1b[0m\n",
+                gain
+              ));
+            }
+          }
+
           // increase voice speed
           KeyCode::Up => {
             increase_voice_speed();
@@ -383,25 +573,18 @@ pub fn keyboard_thread(
           KeyCode::Left => {
             if !state.debate_enabled.load(Ordering::SeqCst) {
               let agents = state.agents.as_ref();
-              let current_name = state.agent_name.lock().unwrap().clone();
+              let current_name = state
+                .agent_name
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone();
               let pos = agents
                 .iter()
                 .position(|a| a.name == current_name)
                 .unwrap_or(0);
               let new_idx = if pos == 0 { agents.len() - 1 } else { pos - 1 };
               let new_agent = &agents[new_idx];
-              *state.voice.lock().unwrap() = new_agent.voice.clone();
-              *state.agent_name.lock().unwrap() = new_agent.name.clone();
-              *state.tts.lock().unwrap() = new_agent.tts.clone();
-              *state.language.lock().unwrap() = new_agent.language.clone();
-              *state.provider.lock().unwrap() = new_agent.provider.clone();
-              *state.baseurl.lock().unwrap() = new_agent.baseurl.clone();
-              *state.model.lock().unwrap() = new_agent.model.clone();
-              *state.system_prompt.lock().unwrap() = new_agent.system_prompt.clone();
-              state.ptt.store(new_agent.ptt, Ordering::Relaxed);
-              state
-                .speed
-                .store((new_agent.voice_speed * 10.0) as u32, Ordering::Relaxed);
+              state.use_agent(new_agent);
               if state.ptt.load(Ordering::Relaxed) {
                 recording_paused.store(true, Ordering::Relaxed);
               } else {
@@ -409,6 +592,7 @@ pub fn keyboard_thread(
               }
               // Reset conversation history when changing agents
               state.reset_conversation();
+              crate::telemetry::feature("agent_switch");
               let _ = tx_ui.send(format!(
                 "line|\n\x1b[32m🤖 Agent switched to '\x1b[37m{}\x1b[0m\x1b[32m' language: \x1b[37m{}\x1b[0m",
                 new_agent.name,
@@ -422,25 +606,18 @@ pub fn keyboard_thread(
             if !state.debate_enabled.load(Ordering::SeqCst) {
               let state = GLOBAL_STATE.get().unwrap();
               let agents = state.agents.as_ref();
-              let current_name = state.agent_name.lock().unwrap().clone();
+              let current_name = state
+                .agent_name
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone();
               let pos = agents
                 .iter()
                 .position(|a| a.name == current_name)
                 .unwrap_or(0);
               let new_idx = (pos + 1) % agents.len();
               let new_agent = &agents[new_idx];
-              *state.voice.lock().unwrap() = new_agent.voice.clone();
-              *state.agent_name.lock().unwrap() = new_agent.name.clone();
-              *state.tts.lock().unwrap() = new_agent.tts.clone();
-              *state.language.lock().unwrap() = new_agent.language.clone();
-              *state.provider.lock().unwrap() = new_agent.provider.clone();
-              *state.baseurl.lock().unwrap() = new_agent.baseurl.clone();
-              *state.model.lock().unwrap() = new_agent.model.clone();
-              *state.system_prompt.lock().unwrap() = new_agent.system_prompt.clone();
-              state.ptt.store(new_agent.ptt, Ordering::Relaxed);
-              state
-                .speed
-                .store((new_agent.voice_speed * 10.0) as u32, Ordering::Relaxed);
+              state.use_agent(new_agent);
               if state.ptt.load(Ordering::Relaxed) {
                 recording_paused.store(true, Ordering::Relaxed);
               } else {
@@ -448,6 +625,7 @@ pub fn keyboard_thread(
               }
               // Reset conversation history when changing agents
               state.reset_conversation();
+              crate::telemetry::feature("agent_switch");
               let _ = tx_ui.send(format!(
                 "line|\n\x1b[32m🤖 Agent switched to '\x1b[37m{}\x1b[0m\x1b[32m' language: \x1b[37m{}\x1b[0m",
                 new_agent.name,
@@ -485,3 +663,15 @@ pub fn keyboard_thread(
   // Always restore terminal state.
   let _ = terminal::disable_raw_mode();
 }
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// the start of a bookmarked message, on one line
+fn bookmark_preview(text: &str) -> String {
+  let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+  match line.char_indices().nth(60) {
+    Some((end, _)) => format!("{}…", &line[..end]),
+    None => line,
+  }
+}
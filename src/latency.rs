@@ -0,0 +1,59 @@
+// ------------------------------------------------------------------
+//  Latency target (--latency-target-ms)
+// ------------------------------------------------------------------
+//
+// The time from the end of speech to the first sound of the reply. With a
+// target, the agents are tuned for it when the settings are loaded (see
+// config::tune_for_latency) and the first phrase of a reply is cut shorter
+// so its synthesis starts sooner. The latency reached by the last turn is
+// shown in the status bar next to the target.
+
+use crate::START_INSTANT;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// the first phrase ends at its first clause of at least this many words, or
+// at most that many, while a target is set
+const TARGET_CLAUSE_MIN_WORDS: usize = 2;
+const TARGET_CLAUSE_MAX_WORDS: usize = 5;
+
+// 0 while no target is set
+static TARGET_MS: AtomicU64 = AtomicU64::new(0);
+// the end of speech already measured, and the latency measured for it
+static MEASURED_SPEECH_END: AtomicU64 = AtomicU64::new(0);
+static REACHED_MS: AtomicU64 = AtomicU64::new(0);
+
+// API
+// ------------------------------------------------------------------
+
+pub fn init(target_ms: Option<u64>) {
+  TARGET_MS.store(target_ms.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// The latency aimed for, if any.
+pub fn target_ms() -> Option<u64> {
+  Some(TARGET_MS.load(Ordering::Relaxed)).filter(|ms| *ms > 0)
+}
+
+/// The words of the first clause of a reply, at least and at most, or None
+/// to keep the usual ones.
+pub fn first_clause_words() -> Option<(usize, usize)> {
+  target_ms().map(|_| (TARGET_CLAUSE_MIN_WORDS, TARGET_CLAUSE_MAX_WORDS))
+}
+
+/// Called as audio is queued for playback: the first audio after the end
+/// of an utterance gives the latency of that turn.
+pub fn audio_queued() {
+  let speech_end = crate::util::SPEECH_END_AT.load(Ordering::SeqCst);
+  if speech_end == 0 || MEASURED_SPEECH_END.swap(speech_end, Ordering::Relaxed) == speech_end {
+    return;
+  }
+  let reached = crate::util::now_ms(&START_INSTANT).saturating_sub(speech_end);
+  REACHED_MS.store(reached, Ordering::Relaxed);
+  crate::log::log("info", &format!("Voice to voice latency: {} ms", reached));
+}
+
+/// The latency of the last turn, None before the first one.
+pub fn reached_ms() -> Option<u64> {
+  Some(REACHED_MS.load(Ordering::Relaxed))
+    .filter(|_| MEASURED_SPEECH_END.load(Ordering::Relaxed) > 0)
+}
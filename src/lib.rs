@@ -0,0 +1,168 @@
+// ------------------------------------------------------------------
+//  vtmate
+// ------------------------------------------------------------------
+
+//! The voice assistant behind the `vtmate` binary.
+//!
+//! To embed a voice turn in another application use [`Engine`]: an
+//! utterance is transcribed, answered by the llm and spoken phrase by phrase,
+//! with the built-in backends or your own [`SpeechToText`], [`LanguageModel`]
+//! or [`TextToSpeech`]. Audio capture and playback stay with the caller.
+//!
+//! The other modules are the terminal front-end of the binary. They're public
+//! for it and the tests only, not an API.
+
+use std::sync::OnceLock;
+use std::time::Instant;
+
+pub mod engine;
+pub mod error;
+#[cfg(feature = "testing")]
+pub mod testing;
+
+#[doc(hidden)]
+pub mod announce;
+#[doc(hidden)]
+pub mod assets;
+#[doc(hidden)]
+pub mod attach;
+#[doc(hidden)]
+pub mod audio;
+#[doc(hidden)]
+pub mod bench;
+#[doc(hidden)]
+pub mod bridge;
+#[doc(hidden)]
+pub mod cancel;
+#[doc(hidden)]
+pub mod captions;
+#[doc(hidden)]
+pub mod clips;
+#[doc(hidden)]
+pub mod config;
+#[doc(hidden)]
+pub mod contexts;
+#[doc(hidden)]
+pub mod continuation;
+#[doc(hidden)]
+pub mod control;
+#[doc(hidden)]
+pub mod conversation;
+#[doc(hidden)]
+pub mod dictate;
+#[doc(hidden)]
+pub mod dnd;
+#[doc(hidden)]
+pub mod duck;
+#[doc(hidden)]
+pub mod earcons;
+#[doc(hidden)]
+pub mod encryption;
+#[doc(hidden)]
+pub mod events;
+#[doc(hidden)]
+pub mod fast;
+#[doc(hidden)]
+pub mod followup;
+#[doc(hidden)]
+pub mod gain;
+#[doc(hidden)]
+pub mod gpio;
+#[doc(hidden)]
+pub mod history;
+#[doc(hidden)]
+pub mod ignored;
+#[doc(hidden)]
+pub mod intercom;
+#[doc(hidden)]
+pub mod interjection;
+#[doc(hidden)]
+pub mod latency;
+#[doc(hidden)]
+pub mod llm;
+#[doc(hidden)]
+pub mod log;
+#[doc(hidden)]
+pub mod meeting;
+#[doc(hidden)]
+pub mod metrics;
+#[doc(hidden)]
+pub mod mictest;
+#[doc(hidden)]
+pub mod mirror;
+#[doc(hidden)]
+pub mod mpris;
+#[doc(hidden)]
+pub mod notify;
+#[doc(hidden)]
+pub mod numbers;
+#[doc(hidden)]
+pub mod pace;
+#[doc(hidden)]
+pub mod personas;
+#[doc(hidden)]
+pub mod playback;
+#[doc(hidden)]
+pub mod plugins;
+#[doc(hidden)]
+pub mod power;
+#[doc(hidden)]
+pub mod punctuate;
+#[doc(hidden)]
+pub mod rapid;
+#[doc(hidden)]
+pub mod record;
+#[doc(hidden)]
+pub mod redact;
+#[doc(hidden)]
+pub mod say;
+#[doc(hidden)]
+pub mod server;
+#[doc(hidden)]
+pub mod service;
+#[doc(hidden)]
+pub mod shell;
+#[doc(hidden)]
+pub mod snippets;
+#[doc(hidden)]
+pub mod speaker;
+#[doc(hidden)]
+pub mod spell;
+#[doc(hidden)]
+pub mod state;
+#[doc(hidden)]
+pub mod stdio;
+#[doc(hidden)]
+pub mod stt;
+#[doc(hidden)]
+pub mod styles;
+#[doc(hidden)]
+pub mod supervisor;
+#[doc(hidden)]
+pub mod telemetry;
+#[doc(hidden)]
+pub mod traces;
+#[doc(hidden)]
+pub mod transcribe;
+#[doc(hidden)]
+pub mod tts;
+#[doc(hidden)]
+pub mod ui;
+#[doc(hidden)]
+pub mod util;
+#[doc(hidden)]
+pub mod wake;
+#[doc(hidden)]
+pub mod wyoming;
+
+pub use audio::AudioChunk;
+pub use cancel::{CancellationToken, Interrupter};
+pub use config::AgentSettings;
+pub use conversation::ChatMessage;
+pub use engine::{
+  BuiltinTts, Engine, LanguageModel, ServerLlm, SpeechToText, TextToSpeech, TurnEvent, WhisperStt,
+};
+pub use tts::SpeakOutcome;
+
+/// Process start, timestamps of the recording pipeline are relative to it.
+pub static START_INSTANT: OnceLock<Instant> = OnceLock::new();
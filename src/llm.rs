@@ -2,23 +2,120 @@
 //  LLM handling
 // ------------------------------------------------------------------
 
+use crate::cancel::CancellationToken;
+use crate::error::LlmError;
 use bytes::Bytes;
 use futures_util::StreamExt;
 use reqwest::StatusCode;
-use serde_json::json;
-use std::sync::{Arc, atomic::AtomicU64};
+use serde_json::{Value, json};
 
-/// Stream response from Llama/Ollama endpoints, fallback if one fails, and mid-stream cancellation support
+// tool call rounds per reply, so a model calling tools in a loop still ends
+const MAX_TOOL_ROUNDS: usize = 4;
+
+/// Stream response from Llama/Ollama endpoints, fallback if one fails, and mid-stream cancellation support.
+/// Tool calls (see [`crate::plugins`] and [`crate::shell`]) are run and their results sent back until the model answers.
 pub async fn llama_server_stream_response_into(
   messages: &Vec<crate::conversation::ChatMessage>,
   llama_host: &str,
   llama_model: &str,
   server_type: &str,
 
-  interrupt_counter: Arc<AtomicU64>,
-  expected_interrupt: u64,
+  cancel: &CancellationToken,
   on_piece: &mut dyn FnMut(&str),
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+) -> crate::error::Result<()> {
+  let mut tools = crate::plugins::tool_specs();
+  tools.extend(crate::shell::tool_specs());
+  let mut chat: Vec<Value> = messages
+    .iter()
+    .map(|m| json!({ "role": m.role, "content": m.content }))
+    .collect();
+  for _ in 0..=MAX_TOOL_ROUNDS {
+    let mut calls: Vec<ToolCall> = Vec::new();
+    stream_once(
+      &chat,
+      &tools,
+      llama_host,
+      llama_model,
+      server_type,
+      cancel,
+      on_piece,
+      &mut calls,
+    )
+    .await?;
+    calls.retain(|c| !c.name.is_empty());
+    for (i, call) in calls.iter_mut().enumerate() {
+      if call.id.is_empty() {
+        call.id = format!("call_{}", i);
+      }
+    }
+    if calls.is_empty() || cancel.is_cancelled() {
+      return Ok(());
+    }
+    chat.push(json!({
+      "role": "assistant",
+      "content": "",
+      "tool_calls": calls.iter().map(|c| json!({
+        "id": c.id,
+        "type": "function",
+        "function": { "name": c.name, "arguments": c.arguments() },
+      })).collect::<Vec<_>>(),
+    }));
+    for call in &calls {
+      let result = if call.name == crate::shell::TOOL_NAME {
+        crate::shell::call_tool(&call.arguments())
+      } else {
+        crate::plugins::call_tool(&call.name, &call.arguments())
+      };
+      chat.push(
+        json!({ "role": "tool", "tool_call_id": call.id, "name": call.name, "content": result }),
+      );
+    }
+  }
+  Ok(())
+}
+
+// a tool call as streamed, arguments arrive in pieces with the OpenAI api
+#[derive(Default)]
+struct ToolCall {
+  id: String,
+  name: String,
+  arguments: String,
+}
+
+impl ToolCall {
+  fn arguments(&self) -> Value {
+    serde_json::from_str(&self.arguments).unwrap_or(json!({}))
+  }
+}
+
+// the OpenAI api wants tool call arguments as a string, ollama as an object
+fn oai_messages(chat: &[Value]) -> Vec<Value> {
+  chat
+    .iter()
+    .map(|m| {
+      let mut m = m.clone();
+      if let Some(calls) = m.get_mut("tool_calls").and_then(|c| c.as_array_mut()) {
+        for call in calls {
+          let args = call["function"]["arguments"].to_string();
+          call["function"]["arguments"] = json!(args);
+        }
+      }
+      m
+    })
+    .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn stream_once(
+  chat: &[Value],
+  tools: &[Value],
+  llama_host: &str,
+  llama_model: &str,
+  server_type: &str,
+  cancel: &CancellationToken,
+  on_piece: &mut dyn FnMut(&str),
+  tool_calls: &mut Vec<ToolCall>,
+) -> crate::error::Result<()> {
   #[derive(Clone, Copy, Debug)]
   enum ApiKind {
     OaiChat,
@@ -71,10 +168,10 @@ pub async fn llama_server_stream_response_into(
 
   let client = reqwest::Client::new();
   let tries = candidates(llama_host, server_type);
-  let mut last_err: Option<String> = None;
+  let mut last_err: Option<LlmError> = None;
 
   for (url, kind) in tries {
-    if interrupt_counter.load(std::sync::atomic::Ordering::SeqCst) != expected_interrupt {
+    if cancel.is_cancelled() {
       return Ok(());
     }
 
@@ -82,18 +179,21 @@ pub async fn llama_server_stream_response_into(
 
     let req = match kind {
       ApiKind::OaiChat => {
-        let payload = json!({
+        let mut payload = json!({
           "model": llama_model,
-          "messages": messages.iter().map(|m| json!({ "role": m.role, "content": m.content })).collect::<Vec<_>>(),
+          "messages": oai_messages(chat),
           "think": false,
           "stream": true
         });
+        if !tools.is_empty() {
+          payload["tools"] = json!(tools);
+        }
         client.post(&url).json(&payload)
       }
       ApiKind::OllamaGenerate => {
-        let prompt_str = messages
+        let prompt_str = chat
           .iter()
-          .map(|m| m.content.as_str())
+          .filter_map(|m| m["content"].as_str())
           .collect::<Vec<&str>>()
           .join("\n");
         let payload = json!({
@@ -106,38 +206,59 @@ pub async fn llama_server_stream_response_into(
         client.post(&url).json(&payload)
       }
       ApiKind::OllamaChat => {
-        let payload = json!({
+        let mut payload = json!({
           "model": llama_model,
-          "messages": messages.iter().map(|m| json!({ "role": m.role, "content": m.content })).collect::<Vec<_>>(),
+          "messages": chat,
           "think": false,
           "stream": true
         });
+        if !tools.is_empty() {
+          payload["tools"] = json!(tools);
+        }
         client.post(&url).json(&payload)
       }
     };
+    // joins the llm server to the turn trace when --otlp-endpoint is on
+    let req = match crate::traces::traceparent() {
+      Some(traceparent) => req.header("traceparent", traceparent),
+      None => req,
+    };
 
     let resp = match tokio::time::timeout(std::time::Duration::from_secs(120), req.send()).await {
       Ok(Ok(r)) => r,
       Ok(Err(e)) => {
-        last_err = Some(format!("Request to {} failed: {}", url, e));
-        log::warn!("{}", last_err.as_ref().unwrap());
+        log::warn!("Request to {} failed: {}", url, e);
+        last_err = Some(LlmError::Unreachable {
+          provider: server_type.to_string(),
+          url: llama_host.to_string(),
+          reason: e.to_string(),
+        });
         continue;
       }
       Err(_) => {
-        last_err = Some(format!("Request to {} timed out", url));
-        log::warn!("{}", last_err.as_ref().unwrap());
+        log::warn!("Request to {} timed out", url);
+        last_err = Some(LlmError::Unreachable {
+          provider: server_type.to_string(),
+          url: llama_host.to_string(),
+          reason: "timed out".to_string(),
+        });
         continue;
       }
     };
 
     if !resp.status().is_success() {
       let status = resp.status();
-      last_err = Some(format!("Endpoint {} returned HTTP {}", url, status));
-      log::warn!("{}", last_err.as_ref().unwrap());
+      log::warn!("Endpoint {} returned HTTP {}", url, status);
+      let error = LlmError::Status {
+        model: llama_model.to_string(),
+        url: url.clone(),
+        status: status.as_u16(),
+      };
       if should_fallback_status(status) {
+        last_err = Some(error);
         continue;
       } else {
-        return Err(last_err.clone().unwrap().into());
+        return Err(error.into());
       }
     }
 
@@ -147,7 +268,7 @@ pub async fn llama_server_stream_response_into(
 
     while let Some(chunk_result) = stream.next().await {
       // check stop signal mid-stream
-      if interrupt_counter.load(std::sync::atomic::Ordering::SeqCst) != expected_interrupt {
+      if cancel.is_cancelled() {
         return Ok(());
       }
 
@@ -176,6 +297,14 @@ pub async fn llama_server_stream_response_into(
                   on_piece(content);
                 }
               }
+              // ollama sends each tool call whole
+              for call in message["tool_calls"].as_array().into_iter().flatten() {
+                tool_calls.push(ToolCall {
+                  id: format!("call_{}", tool_calls.len()),
+                  name: call["function"]["name"].as_str().unwrap_or("").to_string(),
+                  arguments: call["function"]["arguments"].to_string(),
+                });
+              }
             } else {
               match kind {
                 ApiKind::OaiChat | ApiKind::OllamaChat | ApiKind::OllamaGenerate => {
@@ -187,6 +316,22 @@ pub async fn llama_server_stream_response_into(
                             on_piece(content);
                           }
                         }
+                        for call in delta["tool_calls"].as_array().into_iter().flatten() {
+                          let index = call["index"].as_u64().unwrap_or(0) as usize;
+                          if tool_calls.len() <= index {
+                            tool_calls.resize_with(index + 1, ToolCall::default);
+                          }
+                          let tc = &mut tool_calls[index];
+                          if let Some(id) = call["id"].as_str() {
+                            tc.id = id.to_string();
+                          }
+                          if let Some(name) = call["function"]["name"].as_str() {
+                            tc.name.push_str(name);
+                          }
+                          if let Some(args) = call["function"]["arguments"].as_str() {
+                            tc.arguments.push_str(args);
+                          }
+                        }
                       }
                       if choice.get("finish_reason").and_then(|r| r.as_str()) == Some("stop") {
                         return Ok(());
@@ -213,7 +358,11 @@ pub async fn llama_server_stream_response_into(
   // all endpoints failed
   Err(
     last_err
-      .unwrap_or_else(|| "No endpoint candidates succeeded".to_string())
+      .unwrap_or_else(|| LlmError::Unreachable {
+        provider: server_type.to_string(),
+        url: llama_host.to_string(),
+        reason: "no endpoint answered".to_string(),
+      })
       .into(),
   )
 }
@@ -7,6 +7,8 @@ use std::sync::OnceLock;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 static VERBOSE: AtomicBool = AtomicBool::new(false);
+// no terminal ui: logs go straight to stderr
+static PLAIN: AtomicBool = AtomicBool::new(false);
 
 static TX_UI: OnceLock<Sender<String>> = OnceLock::new();
 
@@ -17,6 +19,11 @@ pub fn set_verbose(v: bool) {
   VERBOSE.store(v, Ordering::Relaxed);
 }
 
+/// Writes the logs to stderr as plain lines instead of the ui (--headless).
+pub fn set_plain(plain: bool) {
+  PLAIN.store(plain, Ordering::Relaxed);
+}
+
 pub fn set_tx_ui_sender(sender: Sender<String>) {
   TX_UI.set(sender).ok();
 }
@@ -26,9 +33,18 @@ pub fn is_verbose() -> bool {
 }
 
 pub fn log(msg_type: &str, msg: &str) {
+  if msg_type == "error" {
+    crate::earcons::play(crate::earcons::Earcon::Error);
+  }
   if !is_verbose() && msg_type != "error" {
     return;
   }
+  // transcripts and replies end up in the logs
+  let msg = crate::redact::redact(msg);
+  if PLAIN.load(Ordering::Relaxed) {
+    eprintln!("{}: {}", msg_type, msg);
+    return;
+  }
   let emoji = match msg_type {
     "debug" => "🐛",
     "info" => "ℹ️",
@@ -41,3 +57,38 @@ pub fn log(msg_type: &str, msg: &str) {
     let _ = sender.send(format!("line|{}", formatted));
   }
 }
+
+/// Shows the text of a segment of a long utterance still being transcribed.
+/// Always shown.
+pub fn partial_transcript(text: &str) {
+  let text = crate::redact::redact(text);
+  if PLAIN.load(Ordering::Relaxed) {
+    eprintln!("partial: {}", text);
+    return;
+  }
+  let formatted = format!("\r\x1b[K✍️  \x1b[90m{}…\x1b[0m\n", text);
+  if let Some(sender) = TX_UI.get() {
+    let _ = sender.send(format!("line|{}", formatted));
+  }
+}
+
+/// Shows an error the user should act on: the message on a red badge, and
+/// below it what to do about it, when known. Unlike the logs, always shown.
+pub fn error_panel(message: &str, hint: Option<&str>) {
+  crate::earcons::play(crate::earcons::Earcon::Error);
+  let message = crate::redact::redact(message);
+  if PLAIN.load(Ordering::Relaxed) {
+    eprintln!("error: {}", message);
+    if let Some(hint) = hint {
+      eprintln!("hint: {}", hint);
+    }
+    return;
+  }
+  let mut formatted = format!("\r\x1b[K\x1b[41;37m ❌ {} \x1b[0m\n", message);
+  if let Some(hint) = hint {
+    formatted.push_str(&format!("\r\x1b[K💡  {}\n", hint));
+  }
+  if let Some(sender) = TX_UI.get() {
+    let _ = sender.send(format!("line|{}", formatted));
+  }
+}
@@ -3,32 +3,26 @@ use clap::Parser;
 use cpal::traits::DeviceTrait;
 use crossbeam_channel::{bounded, unbounded};
 use crossterm::terminal::{self};
-use std::path::{Path, PathBuf};
+use std::path::Path;
+use std::sync::PoisonError;
 
 use ctrlc;
 use std::io::IsTerminal;
-use std::sync::{Arc, OnceLock, atomic::Ordering};
+use std::sync::{Arc, atomic::Ordering};
 use std::thread::{self, Builder as ThreadBuilder};
 use std::time::Duration;
 use std::time::Instant;
 
-mod assets;
-mod audio;
-mod config;
-mod conversation;
 mod keyboard;
-mod llm;
-mod log;
-mod playback;
-mod record;
-mod state;
-mod stt;
-mod tts;
-mod ui;
-mod util;
 use crate::conversation::Command;
-
-static START_INSTANT: OnceLock<Instant> = OnceLock::new();
+use vtmate::{
+  START_INSTANT, announce, assets, attach, audio, bench, bridge, cancel, captions, config,
+  contexts, continuation, control, conversation, dictate, dnd, duck, earcons, encryption, engine,
+  error, events, fast, followup, gain, gpio, intercom, interjection, latency, log, meeting,
+  metrics, mictest, mirror, mpris, notify, numbers, pace, personas, playback, plugins, power,
+  punctuate, rapid, record, redact, say, server, service, snippets, speaker, state, stdio, stt,
+  styles, supervisor, telemetry, traces, transcribe, tts, ui, util, wyoming,
+};
 
 fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   let mut args = crate::config::Args::parse();
@@ -42,37 +36,207 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     }
   }
   crate::log::set_verbose(args.verbose || false);
+  // set before anything is logged
+  if let Err(e) = redact::init(args.redact, &args.redact_pattern) {
+    println!("❌ Invalid --redact-pattern: {}", e);
+    util::terminate(1);
+  }
+  if let Err(e) = encryption::init(args.encrypt, args.encrypt_key_file.as_deref()) {
+    println!("❌ {}", e);
+    util::terminate(1);
+  }
+  ui::init_labels(ui::Labels {
+    user: args.user_label.clone(),
+    assistant: args.assistant_label.clone(),
+    user_color: args.user_color.clone(),
+    assistant_color: args.assistant_color.clone(),
+    timestamps: args.timestamps,
+  });
   let _ = START_INSTANT.get_or_init(Instant::now);
 
-  // Ctrl-C handler to set should_exit flag
+  // Ctrl-C, SIGTERM and the terminal closing (SIGHUP) end the session cleanly
   let should_exit = Arc::new(std::sync::atomic::AtomicBool::new(false));
   ctrlc::set_handler(move || {
-    crate::util::terminate(0);
+    crate::util::shutdown(0);
   })
   .expect("Error setting Ctrl-C handler");
 
+  util::set_data_dir(args.data_dir.as_deref());
+  assets::set_download_options(
+    args.asset_mirror.clone(),
+    args.third_party_mirrors,
+    args.offline,
+  );
+
+  // ---------------------------------------------------
+  // handle subcommands
+  // ---------------------------------------------------
+  if let Some(config::CliCommand::Assets { ref action }) = args.command {
+    std::process::exit(assets::run_assets_command(action, &args));
+  }
+  if let Some(config::CliCommand::Ctl { ref action }) = args.command {
+    std::process::exit(control::run_ctl_command(
+      action,
+      args.control_socket.as_deref(),
+    ));
+  }
+  if let Some(config::CliCommand::Attach) = args.command {
+    std::process::exit(attach::run_attach_command(args.control_socket.as_deref()));
+  }
+  if let Some(config::CliCommand::Telemetry { ref action }) = args.command {
+    std::process::exit(telemetry::run_telemetry_command(action));
+  }
+  if let Some(config::CliCommand::Enroll { ref files, seconds }) = args.command {
+    std::process::exit(speaker::run_enroll_command(files, seconds));
+  }
+  if let Some(config::CliCommand::Decrypt {
+    ref file,
+    ref output,
+  }) = args.command
+  {
+    std::process::exit(encryption::run_decrypt_command(
+      file,
+      output.as_deref(),
+      &args,
+    ));
+  }
+
+  // the daemon is reached through the control socket only
+  if args.daemon {
+    #[cfg(not(unix))]
+    {
+      println!("❌ --daemon needs the control socket, only available on unix");
+      util::terminate(1);
+    }
+    // closing the terminal it was started from doesn't stop it
+    // SAFETY: ignoring a signal, no handler involved
+    #[cfg(unix)]
+    unsafe {
+      libc::signal(libc::SIGHUP, libc::SIG_IGN);
+    }
+  }
+
+  // stdout carries the protocol only, everything else is printed to stderr
+  if args.stdio_protocol {
+    stdio::take_stdout();
+  }
+
+  // no terminal needed: turns and logs are written to stderr as plain lines
+  let headless = args.headless || args.daemon || args.service;
+  log::set_plain(headless);
+
+  // move models from the old per-tool folders into the data dir
+  assets::migrate_legacy_assets();
   // make sure piper phonemes are unpacked
+  #[cfg(feature = "kokoro")]
   assets::ensure_piper_espeak_env();
-  // make sure the user has the whisper + tts models unpacked
+  // point the tts engines to their model directories
   assets::ensure_assets_env();
   assets::ensure_supersonic2_assets();
+  // threads and execution provider of the kokoro onnx session
+  #[cfg(feature = "kokoro")]
+  tts::kokoro_tts::configure_onnx(args.tts_threads, &args.tts_ep);
+
+  if let Some(config::CliCommand::Say {
+    ref text,
+    ref output,
+  }) = args.command
+  {
+    std::process::exit(say::run_say_command(text, output.as_deref(), &args));
+  }
+  if let Some(config::CliCommand::Bench {
+    ref files,
+    iterations,
+    ref agents,
+    skip_llm,
+  }) = args.command
+  {
+    std::process::exit(bench::run_bench_command(
+      iterations, agents, files, skip_llm, &args,
+    ));
+  }
+  if let Some(config::CliCommand::Transcribe {
+    ref file,
+    ref output,
+  }) = args.command
+  {
+    std::process::exit(transcribe::run_transcribe_command(
+      file,
+      output.as_deref(),
+      &args,
+    ));
+  }
+
+  // utterances of other speakers are left out or tagged
+  if let Some(mode) = args.speaker_verify.as_deref()
+    && let Err(e) = speaker::init(mode, args.speaker_threshold)
+  {
+    println!("❌ {}", e);
+    util::terminate(1);
+  }
+
+  // answers in text only until m is pressed
+  tts::set_muted(args.no_tts);
+  pace::set_enabled(!args.no_auto_pace);
+  styles::set_enabled(args.voice_styles);
+  numbers::init(args.double_check_numbers.as_deref());
+  continuation::init(args.max_spoken_seconds);
+  punctuate::set_enabled(args.punctuate);
+
+  // utterances written down instead of answered
+  if args.dictate {
+    dictate::init(args.dictate_output.clone(), args.dictate_type, headless);
+  }
+
+  // utterances noted as meeting notes instead of answered
+  if args.meeting {
+    match meeting::init() {
+      Ok(path) => log::log("info", &format!("Meeting notes: {}", path.display())),
+      Err(e) => {
+        println!("❌ {}", e);
+        util::terminate(1);
+      }
+    }
+  }
+
+  // led and oled display of a headless build, opened now to fail early
+  let status_sinks = if args.status_led.is_some() || args.status_display.is_some() {
+    match gpio::StatusSinks::open(args.status_led.as_deref(), args.status_display.as_deref()) {
+      Ok(sinks) => Some(sinks),
+      Err(e) => {
+        println!("❌ {}", e);
+        util::terminate(1);
+      }
+    }
+  } else {
+    None
+  };
 
   // ---------------------------------------------------
   // setup thread communication channels
   // ---------------------------------------------------
   // channel for utterance audio chunks
-  let (tx_utt, rx_utt) = bounded::<audio::AudioChunk>(1);
+  let (tx_utt, rx_utt) = bounded::<audio::AudioChunk>(args.utterance_queue as usize);
   // channel for tts phrases
-  let (tx_tts, rx_tts) = unbounded::<(String, u64, String)>();
+  let (tx_tts, rx_tts) = unbounded::<(String, cancel::CancellationToken, String)>();
   let (tts_done_tx, tts_done_rx) = crossbeam_channel::bounded(0);
 
   // channel for playback audio chunks
-  let (tx_play, rx_play) = bounded::<audio::AudioChunk>(1);
+  let (tx_play, rx_play) = bounded::<audio::AudioChunk>(args.playback_queue as usize);
+  // queue depths reported by the status
+  metrics::watch_queue("utterances", &rx_utt);
+  metrics::watch_queue("phrases", &rx_tts);
+  metrics::watch_queue("playback", &rx_play);
   // channel for ui messages
   let (tx_ui, rx_ui) = bounded::<String>(1);
   log::set_tx_ui_sender(tx_ui.clone());
+  // feature and error counts, only with --telemetry
+  telemetry::init(&args);
+  playback::set_interrupt_granularity(&args.interrupt_granularity);
+  // a satellite has no conversation to tell a command from a question
+  interjection::set_enabled(!args.barge_in_interrupts && args.satellite.is_none());
 
-  if !util::terminal_supported() {
+  if !headless && !util::terminal_supported() {
     log::log(
       "error",
       "Terminal does not support colors or emojis. Please use a different terminal. continuing...",
@@ -88,6 +252,13 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     util::terminate(0);
   }
 
+  // ---------------------------------------------------
+  // handle --mic-test
+  // ---------------------------------------------------
+  if args.mic_test {
+    std::process::exit(mictest::run_mic_test(&args));
+  }
+
   // ---------------------------------------------------
   // quiet mode validation
   // ---------------------------------------------------
@@ -109,22 +280,7 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     // Load settings first to get agent configuration
     let _ = config::ensure_settings_file();
-    let settings_path = if let Some(ref cfg) = args.config {
-      // Resolve potential ~ path
-      let mut path = PathBuf::from(cfg.as_str());
-      if path.starts_with("~") {
-        if let Some(home) = get_user_home_path() {
-          let rel = path.strip_prefix("~").unwrap_or(&path);
-          path = home.join(rel.to_str().unwrap_or(""));
-        }
-      }
-      path
-    } else {
-      get_user_home_path()
-        .ok_or("Unable to determine home directory")?
-        .join(".vtmate")
-        .join("settings")
-    };
+    let settings_path = config::resolve_settings_path(&args)?;
 
     let agents = match config::load_settings(&settings_path, &args) {
       Ok(v) => v,
@@ -160,16 +316,25 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
       }
     };
 
+    // download missing models before starting the engines
+    if let Err(e) = assets::ensure_agent_assets(&agents, false) {
+      print!(
+        "❌ Failed to download assets: {}\r\n",
+        e.replace('\n', "\r\n")
+      );
+      util::terminate(1);
+    }
+
     // Read the filename or stdin
     let content = util::read_file(filename);
 
     // Initialize TTS engines only if needed
-    let use_supersonic = agents.iter().any(|a| a.tts == "supersonic2");
-    let use_kokoro = agents.iter().any(|a| a.tts == "kokoro");
-    if use_supersonic {
+    #[cfg(feature = "supersonic2")]
+    if agents.iter().any(|a| a.tts == "supersonic2") {
       tts::supersonic2_tts::start_supersonic_engine()?;
     }
-    if use_kokoro {
+    #[cfg(feature = "kokoro")]
+    if agents.iter().any(|a| a.tts == "kokoro") {
       tts::kokoro_tts::start_kokoro_engine()?;
     }
 
@@ -183,43 +348,34 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     // Setup audio output for TTS
     let host = cpal::default_host();
-    let (out_dev, _out_stream) = audio::pick_output_stream(&host).unwrap_or_else(|msg| {
-      crate::log::log("error", &format!("{}", msg));
+    let (out_dev, _out_stream) = audio::pick_output_stream(&host).unwrap_or_else(|e| {
+      crate::error::report(&e);
       util::terminate(1)
     });
 
     let out_cfg_supported = out_dev.default_output_config()?;
-    let out_cfg: cpal::StreamConfig = out_cfg_supported.clone().into();
+    let out_cfg = audio::output_config(&out_cfg_supported, args.audio_buffer_frames);
     let out_sample_rate = out_cfg.sample_rate.0;
     let out_channels = out_cfg.channels;
 
     // Setup channels for TTS and playback
-    let (tx_play, rx_play) = bounded::<audio::AudioChunk>(1);
-    let (tx_tts, rx_tts) = unbounded::<(String, u64, String)>();
+    let (tx_play, rx_play) = bounded::<audio::AudioChunk>(args.playback_queue as usize);
+    let (tx_tts, rx_tts) = unbounded::<(String, cancel::CancellationToken, String)>();
     let (tts_done_tx, tts_done_rx) = crossbeam_channel::unbounded();
     let (stop_play_tx, stop_play_rx) = unbounded::<()>();
     // Command channel for undo
     let (tx_cmd_conv, _rx_cmd_conv) = unbounded::<Command>();
 
-    let interrupt_counter = app_state.interrupt_counter.clone();
+    let interrupter = app_state.interrupter.clone();
 
     // Start TTS thread
     let _tts_handle = thread::spawn({
       let out_sample_rate = out_sample_rate.clone();
       let tx_play = tx_play.clone();
-      let interrupt_counter = interrupt_counter.clone();
       let stop_play_tx = stop_play_tx.clone();
 
       move || {
-        tts::tts_thread(
-          out_sample_rate,
-          tx_play,
-          interrupt_counter,
-          rx_tts,
-          stop_play_tx,
-          tts_done_tx,
-        )
-        .unwrap();
+        tts::tts_thread(out_sample_rate, tx_play, rx_tts, stop_play_tx, tts_done_tx).unwrap();
       }
     });
 
@@ -229,15 +385,6 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let paused = Arc::new(std::sync::atomic::AtomicBool::new(false));
     let volume = Arc::new(std::sync::Mutex::new(1.0_f32));
 
-    let ui_state = state::UiState {
-      thinking: Arc::new(std::sync::atomic::AtomicBool::new(false)),
-      playing: Arc::new(std::sync::atomic::AtomicBool::new(false)),
-      agent_speaking: Arc::new(std::sync::atomic::AtomicBool::new(false)),
-      peak: Arc::new(std::sync::Mutex::new(0.0)),
-      spinner_index: 0,
-      quiet: args.quiet,
-    };
-
     // Setup WAV writer and txt export for read mode
     let home_dir = get_user_home_path().unwrap();
     let read_dir = home_dir.join(".vtmate").join("read-files");
@@ -269,7 +416,6 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
           gate_until_ms,
           paused,
           out_channels,
-          ui_state,
           volume,
         )
       }
@@ -327,7 +473,7 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
       let current_phrase = current_phrase.clone();
       let tts_paused = tts_paused.clone();
       let should_exit = should_exit.clone();
-      let interrupt_counter = interrupt_counter.clone();
+      let interrupter = interrupter.clone();
       let stop_play_tx = stop_play_tx.clone();
       let display_update_tx = display_update_tx.clone();
       let phrases_len = phrases.len();
@@ -346,7 +492,7 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
           tx_ui_dummy,
           Arc::new(std::sync::atomic::AtomicBool::new(false)), // dummy recording_paused
           stop_play_tx,
-          interrupt_counter,
+          interrupter,
           Some(read_file_mode),
           tx_cmd_conv,
         )
@@ -405,7 +551,9 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
       // Handle keyboard navigation - user jumped to a different phrase
       if idx != last_idx {
         // Clear the display and rebuild from scratch
-        let mut displayed = displayed_phrases.lock().unwrap();
+        let mut displayed = displayed_phrases
+          .lock()
+          .unwrap_or_else(PoisonError::into_inner);
         displayed.clear();
         // Add all phrases before the current index
         for i in 0..idx {
@@ -434,13 +582,14 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let cleaned = crate::util::strip_special_chars(phrase);
         if !cleaned.is_empty() {
           // Show this phrase as current (highlighted) - THIS IS WHEN IT STARTS PLAYING
-          let displayed = displayed_phrases.lock().unwrap();
+          let displayed = displayed_phrases
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
           update_display(&mut out, &displayed, Some(phrase));
           drop(displayed);
 
-          let expected_interrupt = interrupt_counter.load(Ordering::SeqCst);
           tx_tts
-            .send((cleaned, expected_interrupt, settings.voice.clone()))
+            .send((cleaned, interrupter.token(), settings.voice.clone()))
             .unwrap();
 
           // Wait for TTS synthesis to complete or navigation
@@ -493,7 +642,9 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
           thread::sleep(Duration::from_millis(100));
 
           // NOW that playback is done, move phrase from current to completed (unhighlighted)
-          let mut displayed = displayed_phrases.lock().unwrap();
+          let mut displayed = displayed_phrases
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
           if !displayed.contains(phrase) {
             displayed.push(phrase.clone());
           }
@@ -524,8 +675,11 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     util::terminate(0);
   }
 
-  let _ = terminal::enable_raw_mode();
+  if !headless {
+    let _ = terminal::enable_raw_mode();
+  }
   env_logger::init();
+  #[cfg(feature = "whisper")]
   whisper_rs::install_logging_hooks();
 
   // ---------------------------------------------------
@@ -533,22 +687,7 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   // ---------------------------------------------------
   // force creation of default config file if unexisting
   let _ = config::ensure_settings_file();
-  let settings_path = if let Some(ref cfg) = args.config {
-    // Resolve potential ~ path
-    let mut path = PathBuf::from(cfg.as_str());
-    if path.starts_with("~") {
-      if let Some(home) = get_user_home_path() {
-        let rel = path.strip_prefix("~").unwrap_or(&path);
-        path = home.join(rel.to_str().unwrap_or(""));
-      }
-    }
-    path
-  } else {
-    get_user_home_path()
-      .ok_or("Unable to determine home directory")?
-      .join(".vtmate")
-      .join("settings")
-  };
+  let settings_path = config::resolve_settings_path(&args)?;
 
   // load and file settings, merge cli args and validate
   let agents = match config::load_settings(&settings_path, &args) {
@@ -582,6 +721,50 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     }
   };
 
+  let personas = match config::load_personas(&settings_path) {
+    Ok(v) => v,
+    Err(e) => {
+      print!("❌ Failed to load personas: {}", e);
+      thread::sleep(Duration::from_millis(300));
+      util::terminate(1);
+    }
+  };
+  let snippets = match config::load_snippets(&settings_path) {
+    Ok(v) => v,
+    Err(e) => {
+      print!("❌ Failed to load snippets: {}", e);
+      thread::sleep(Duration::from_millis(300));
+      util::terminate(1);
+    }
+  };
+
+  // download missing models (first run) before any engine needs them, a
+  // satellite uses those of its central
+  let voiced = config::with_personas(&agents, &personas);
+  if args.satellite.is_none()
+    && let Err(e) = assets::ensure_agent_assets(&voiced, true)
+  {
+    print!(
+      "❌ Failed to download assets: {}\r\n",
+      e.replace('\n', "\r\n")
+    );
+    thread::sleep(Duration::from_millis(300));
+    util::terminate(1);
+  }
+  // a whisper model of its own that isn't there: one fitting this machine
+  let mut whisper_path = config::resolved_whisper_model_path(&settings.whisper_model_path);
+  if args.satellite.is_none() && cfg!(feature = "whisper") {
+    let interactive = !headless && stdin_is_tty;
+    match assets::ensure_whisper_model(&whisper_path, args.auto_download, interactive) {
+      Ok(path) => whisper_path = path,
+      Err(e) => {
+        print!("❌ {}\r\n", e.replace('\n', "\r\n"));
+        thread::sleep(Duration::from_millis(300));
+        util::terminate(1);
+      }
+    }
+  }
+
   // Initialize AppState with the selected voice
   let state: Arc<state::AppState> = Arc::new(state::AppState::with_agent(
     settings.clone(),
@@ -589,7 +772,41 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     args.quiet,
   ));
 
+  state
+    .conversation_history
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .set_limits(args.history_turns as usize, args.history_tokens as usize);
   state::GLOBAL_STATE.set(state.clone()).unwrap();
+  if let Err(e) = contexts::init(&args.context) {
+    println!("❌ Invalid --context {}", e);
+    util::terminate(1);
+  }
+  personas::init(personas);
+  snippets::init(snippets);
+  // the reply in progress is saved too when the session ends
+  util::on_shutdown(conversation::save_session);
+
+  // start the plugins (tools and text filters)
+  let plugin_names = plugins::plugins_dir(args.plugins_dir.as_deref())
+    .map(|dir| plugins::load(&dir))
+    .unwrap_or_default();
+
+  // ---------------------------------------------------
+  // Wyoming server (no terminal ui, runs until killed)
+  // ---------------------------------------------------
+  if let Some(ref addr) = args.wyoming {
+    let addr = server::serve_addr(addr);
+    print!(
+      "🏠 Wyoming server for agent '{}' listening on tcp://{}\r\n",
+      settings.name, addr
+    );
+    if let Err(e) = wyoming::wyoming_server(&addr, &settings) {
+      print!("❌ Wyoming server error: {}\r\n", e);
+      util::terminate(1);
+    }
+    util::terminate(0);
+  }
 
   // If initial prompt provided, process it before starting conversation thread
   // (initial prompt handling moved after TTS thread starts to avoid deadlock)
@@ -598,16 +815,23 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   let status_line = state.status_line.clone();
   let conversation_history = state.conversation_history.clone();
 
-  // Start UI thread
-  let ui_handle = ui::spawn_ui_thread(
-    ui.clone(),
-    status_line.clone(),
-    rx_ui,
-    conversation_history.clone(),
-  );
+  // Start UI thread (the protocol replaces it in --stdio-protocol)
+  let ui_handle = if args.stdio_protocol {
+    thread::spawn(move || for _ in rx_ui {})
+  } else if headless {
+    // no terminal: turns go to stderr (the service log)
+    ui::spawn_plain_thread(rx_ui)
+  } else {
+    ui::spawn_ui_thread(
+      ui.clone(),
+      status_line.clone(),
+      rx_ui,
+      conversation_history.clone(),
+    )
+  };
 
-  // interrupt counter
-  let _interrupt_counter = state.interrupt_counter.clone();
+  // interrupter
+  let _interrupter = state.interrupter.clone();
 
   // (Debate logic removed – will be placed after prompt handling)
 
@@ -615,18 +839,17 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   let tx_ui_for_keyboard = tx_ui.clone();
   let (stop_play_tx, stop_play_rx) = unbounded::<()>(); // stop playback signal
   let (tx_cmd_conv, rx_cmd_conv) = unbounded::<Command>(); // command channel for undo
+  announce::init(tx_cmd_conv.clone());
 
-  // Resolve Whisper model path and log it
-  let whisper_path = config::resolved_whisper_model_path(&settings.whisper_model_path);
   crate::log::log("info", &format!("Whisper model path: {}", whisper_path));
 
   let host = cpal::default_host();
-  let (in_dev, _in_stream) = audio::pick_input_stream(&host).unwrap_or_else(|msg| {
-    log::log("error", &format!("{}", msg));
+  let (in_dev, _in_stream) = audio::pick_input_stream(&host).unwrap_or_else(|e| {
+    error::report(&e);
     util::terminate(1)
   });
-  let (out_dev, _out_stream) = audio::pick_output_stream(&host).unwrap_or_else(|msg| {
-    log::log("error", &format!("{}", msg));
+  let (out_dev, _out_stream) = audio::pick_output_stream(&host).unwrap_or_else(|e| {
+    error::report(&e);
     util::terminate(1)
   });
   log::log(
@@ -645,20 +868,24 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   );
 
   let out_cfg_supported = out_dev.default_output_config()?;
-  let out_cfg: cpal::StreamConfig = out_cfg_supported.clone().into();
+  let out_cfg = audio::output_config(&out_cfg_supported, args.audio_buffer_frames);
   let out_sample_rate = out_cfg.sample_rate.0;
   let out_channels = out_cfg.channels;
 
-  let in_cfg_supported = config::pick_input_config(&in_dev, out_sample_rate)?;
+  let in_cfg_supported = config::pick_input_config(&in_dev, out_sample_rate, args.input_channel)?;
   let in_cfg: cpal::StreamConfig = in_cfg_supported.clone().into();
 
   log::log(
     "info",
     &format!(
-      "Picked Input:  {} ch @ {} Hz ({:?})",
+      "Picked Input:  {} ch @ {} Hz ({:?}){}",
       in_cfg.channels,
       in_cfg.sample_rate.0,
-      in_cfg_supported.sample_format()
+      in_cfg_supported.sample_format(),
+      args
+        .input_channel
+        .map(|ch| format!(", listening to channel {}", ch))
+        .unwrap_or_default()
     ),
   );
   log::log(
@@ -680,6 +907,9 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   log::log("info", &format!("Language: {}", settings.language));
   log::log("info", &format!("TTS voice: {}", settings.voice));
   log::log("info", &format!("LLM provider: {}", settings.provider));
+  if !plugin_names.is_empty() {
+    log::log("info", &format!("Plugins: {}", plugin_names.join(", ")));
+  }
 
   if settings.provider == "ollama" {
     log::log("info", &format!("ollama base url: {}", settings.baseurl));
@@ -716,7 +946,7 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   if state.ptt.load(Ordering::Relaxed) {
     recording_paused.store(true, Ordering::Relaxed);
   }
-  let interrupt_counter = state.interrupt_counter.clone();
+  let interrupter = state.interrupter.clone();
   let paused = state.playback.paused.clone();
   let playback_active = state.playback.playback_active.clone();
   let gate_until_ms = state.playback.gate_until_ms.clone();
@@ -725,27 +955,35 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   let volume_play = volume.clone();
   let volume_rec = volume.clone();
 
+  // ---------------------------------------------------
+  // Thread: follow-up window (--follow-up-window, follow_up_questions)
+  // ---------------------------------------------------
+  if args.follow_up_window.is_some() || followup::asks_questions(&state.agents) {
+    let window = args.follow_up_window.map(Duration::from_secs);
+    let phrases = rx_tts.clone();
+    thread::spawn(move || followup::window_thread(window, phrases));
+  }
+
   // ---------------------------------------------------
   // Thread: TTS
   // ---------------------------------------------------
 
   let stop_play_tx_for_tts = stop_play_tx.clone();
-  let tts_handle = thread::spawn({
+  let tts_handle = supervisor::spawn("tts", {
     // voice_state not needed; voice passed per message
     let out_sample_rate = out_sample_rate.clone();
     let tx_play = tx_play.clone();
-    let interrupt_counter = interrupt_counter.clone();
 
     move || {
+      // after a crash: release the conversation waiting for the lost phrase
+      let _ = tts_done_tx.try_send(());
       tts::tts_thread(
         out_sample_rate,
-        tx_play,
-        interrupt_counter,
-        rx_tts,
-        stop_play_tx_for_tts,
-        tts_done_tx,
+        tx_play.clone(),
+        rx_tts.clone(),
+        stop_play_tx_for_tts.clone(),
+        tts_done_tx.clone(),
       )
-      .unwrap();
     }
   });
 
@@ -757,36 +995,96 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   let playback_active_for_play = playback_active.clone();
   let gate_until_ms_for_play = gate_until_ms.clone();
   let paused_for_play = paused.clone();
-  let ui_for_play = ui.clone();
   let volume_play_for_play = volume_play.clone();
-  let play_handle = thread::spawn({
+  let play_handle = supervisor::spawn("playback", {
     move || {
       playback::playback_thread(
         &START_INSTANT,
         out_dev.clone(),
         out_cfg_supported.clone(),
         out_cfg.clone(),
-        rx_play_for_playback,
-        stop_play_rx,
+        rx_play_for_playback.clone(),
+        stop_play_rx.clone(),
         playback_active_for_play.clone(),
         gate_until_ms_for_play.clone(),
         paused_for_play.clone(),
         out_channels,
-        ui_for_play.clone(),
         volume_play_for_play.clone(),
       )
     }
   });
 
+  // ---------------------------------------------------
+  // Thread: earcons (--earcons)
+  // ---------------------------------------------------
+  earcons::set_enabled(args.earcons);
+  if args.earcons {
+    let recording_paused = recording_paused.clone();
+    thread::spawn(move || earcons::listening_thread(recording_paused));
+  }
+
+  // ---------------------------------------------------
+  // Thread: low power (--low-power)
+  // ---------------------------------------------------
+  power::set_enabled(args.low_power);
+  rapid::init(args.rapid_silence, Duration::from_secs(args.rapid_idle));
+  fast::set_active(args.fast_stt);
+  latency::init(args.latency_target_ms);
+  gain::init(args.mic_gain);
+  if args.low_power {
+    let idle = Duration::from_secs(args.low_power_idle);
+    thread::spawn(move || power::idle_thread(idle));
+  }
+
+  // ---------------------------------------------------
+  // Thread: meeting summary (--meeting)
+  // ---------------------------------------------------
+  if args.meeting {
+    let interval = Duration::from_secs(args.meeting_summary * 60);
+    thread::spawn(move || meeting::summary_thread(interval));
+  }
+
+  // ---------------------------------------------------
+  // Thread: status led and display (--status-led, --status-display)
+  // ---------------------------------------------------
+  if let Some(sinks) = status_sinks {
+    let state = state.clone();
+    thread::spawn(move || sinks.run(state));
+  }
+
+  // ---------------------------------------------------
+  // Thread: quiet hours (--quiet-hours)
+  // ---------------------------------------------------
+  if let Some(hours) = args.quiet_hours.as_deref().and_then(dnd::QuietHours::parse) {
+    thread::spawn(move || dnd::schedule_thread(hours));
+  }
+
+  // ---------------------------------------------------
+  // Thread: secondary output (--secondary-output)
+  // ---------------------------------------------------
+  if let Some(ref name) = args.secondary_output {
+    match audio::find_output_device(&host, name) {
+      Ok(device) => {
+        let volume = volume_play.clone();
+        let paused = paused.clone();
+        thread::spawn(move || {
+          if let Err(e) = playback::secondary_output_thread(device, volume, paused) {
+            crate::log::log("error", &format!("Secondary output error: {}", e));
+          }
+        });
+      }
+      Err(e) => crate::log::log("error", &e),
+    }
+  }
+
   // ---------------------------------------------------
   // Thread: record
   // ---------------------------------------------------
   let tx_utt_for_rec = tx_utt.clone();
   let playback_active_for_rec = playback_active.clone();
   let gate_until_ms_for_rec = gate_until_ms.clone();
-  let interrupt_counter_for_rec = interrupt_counter.clone();
+  let interrupter_for_rec = interrupter.clone();
   let ui_peak_for_rec = ui.peak.clone();
-  let ui_for_rec = ui.clone();
   let volume_rec_for_rec = volume_rec.clone();
   let recording_paused_for_record_for_rec = recording_paused_for_record.clone();
   let tx_ui_for_record = tx_ui.clone();
@@ -807,11 +1105,11 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             settings.end_silence_ms,
             playback_active_for_rec.clone(),
             gate_until_ms_for_rec.clone(),
-            interrupt_counter_for_rec.clone(),
+            interrupter_for_rec.clone(),
             ui_peak_for_rec.clone(),
-            ui_for_rec.clone(),
             volume_rec_for_rec.clone(),
             recording_paused_for_record_for_rec.clone(),
+            args.input_channel,
           )
         }
       })?
@@ -820,55 +1118,243 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     thread::spawn(|| Ok::<(), Box<dyn std::error::Error + Send + Sync>>(()))
   };
 
+  // ---------------------------------------------------
+  // Thread: transcription
+  // ---------------------------------------------------
+  let (tx_text, rx_text) = bounded::<stt::Transcript>(args.utterance_queue as usize);
+  metrics::watch_queue("transcripts", &rx_text);
+  let rx_utt_for_stt = rx_utt.clone();
+  let whisper_path_for_stt = whisper_path.clone();
+  if args.satellite.is_none() {
+    supervisor::spawn("transcription", move || {
+      stt::transcribe_thread(
+        whisper_path_for_stt.clone(),
+        rx_utt_for_stt.clone(),
+        tx_text.clone(),
+      );
+      Ok(())
+    });
+  }
+
   // ---------------------------------------------------
   // Thread: conversation
   // ---------------------------------------------------
-  let rx_utt_for_conv = rx_utt.clone();
-  let interrupt_counter_for_conv = interrupt_counter.clone();
-  let whisper_path_for_conv = whisper_path.clone();
+  let interrupter_for_conv = interrupter.clone();
   let settings_for_conv = settings.clone();
-  let ui_for_conv = ui.clone();
   let conversation_history_for_conv = conversation_history.clone();
   let tx_tts_for_conv = tx_tts.clone();
   let tx_ui_for_conv = tx_ui.clone();
   let tts_done_rx_for_conv = tts_done_rx.clone();
 
-  let init_prompt_for_conv = initial_prompt.clone();
+  let mut init_prompt_for_conv = initial_prompt.clone();
   let stop_play_tx_conv = stop_play_tx.clone();
-  let conv_handle = thread::spawn(move || {
-    conversation::conversation_thread(
-      rx_utt_for_conv,
-      interrupt_counter_for_conv.clone(),
-      whisper_path_for_conv.clone(),
-      settings_for_conv.clone(),
-      ui_for_conv.clone(),
-      conversation_history_for_conv.clone(),
-      tx_ui_for_conv.clone(),
-      tx_tts_for_conv.clone(),
-      tts_done_rx_for_conv.clone(),
-      stop_play_tx_conv,
-      rx_cmd_conv,
-      init_prompt_for_conv,
-      args.quiet,
-      args.save,
-    )
-  });
+  let conv_handle = if let Some(central) = args.satellite.clone() {
+    // ---------------------------------------------------
+    // Thread: satellite (--satellite), the central answers
+    // ---------------------------------------------------
+    let rx_utt = rx_utt.clone();
+    let tx_play = tx_play.clone();
+    let stop_play_tx = stop_play_tx.clone();
+    let tx_ui = tx_ui.clone();
+    let interrupter = interrupter.clone();
+    let secret = args.intercom_secret.clone();
+    supervisor::spawn("satellite", move || {
+      intercom::satellite_thread(
+        central.clone(),
+        secret.clone(),
+        rx_utt.clone(),
+        tx_play.clone(),
+        stop_play_tx.clone(),
+        tx_ui.clone(),
+        interrupter.clone(),
+      )
+    })
+  } else {
+    supervisor::spawn("conversation", move || {
+      // after a crash: the turn that was in progress is over
+      if let Some(state) = state::GLOBAL_STATE.get() {
+        state.turn.advance(
+          &[state::TurnState::Generating, state::TurnState::Speaking],
+          state::TurnState::Idle,
+        );
+      }
+      conversation::conversation_thread(
+        rx_text.clone(),
+        interrupter_for_conv.clone(),
+        settings_for_conv.clone(),
+        conversation_history_for_conv.clone(),
+        tx_ui_for_conv.clone(),
+        tx_tts_for_conv.clone(),
+        tts_done_rx_for_conv.clone(),
+        stop_play_tx_conv.clone(),
+        rx_cmd_conv.clone(),
+        // the initial prompt is asked once, not again after a crash
+        init_prompt_for_conv.take(),
+        args.quiet,
+        args.save,
+      )
+    })
+  };
+
+  // ---------------------------------------------------
+  // Thread: captions (--captions)
+  // ---------------------------------------------------
+  if let Some(ref path) = args.captions {
+    let path = std::path::PathBuf::from(path);
+    thread::spawn(move || {
+      if let Err(e) = captions::captions_thread(path) {
+        crate::log::log("error", &format!("Captions error: {}", e));
+      }
+    });
+  }
+
+  // ---------------------------------------------------
+  // Thread: ducking of the other audio (--duck)
+  // ---------------------------------------------------
+  if let Some(level) = args.duck {
+    thread::spawn(move || duck::duck_thread(level));
+  }
+
+  // ---------------------------------------------------
+  // Thread: media keys (--media-keys)
+  // ---------------------------------------------------
+  if args.media_keys {
+    let state = state.clone();
+    let stop_play_tx = stop_play_tx.clone();
+    thread::spawn(move || {
+      if let Err(e) = mpris::media_keys_thread(state, stop_play_tx) {
+        crate::log::log("error", &e);
+      }
+    });
+  }
+
+  // ---------------------------------------------------
+  // Thread: reply mirror (--mirror)
+  // ---------------------------------------------------
+  if let Some(ref path) = args.mirror {
+    let path = std::path::PathBuf::from(path);
+    thread::spawn(move || {
+      if let Err(e) = mirror::mirror_thread(&path) {
+        crate::log::log("error", &format!("Mirror error: {}", e));
+      }
+    });
+  }
+
+  // ---------------------------------------------------
+  // Thread: desktop notifications (--notify)
+  // ---------------------------------------------------
+  if args.notify {
+    // the terminal reports when it loses and gets the focus back
+    let _ = crossterm::execute!(std::io::stdout(), crossterm::event::EnableFocusChange);
+    thread::spawn(notify::notify_thread);
+  }
+
+  // ---------------------------------------------------
+  // Thread: trace export (--otlp-endpoint)
+  // ---------------------------------------------------
+  if let Some(endpoint) = args.otlp_endpoint.clone() {
+    thread::spawn(move || {
+      if let Err(e) = traces::trace_thread(&endpoint) {
+        crate::log::log("error", &format!("Trace export error: {}", e));
+      }
+    });
+  }
+
+  // ---------------------------------------------------
+  // Thread: intercom central (--intercom)
+  // ---------------------------------------------------
+  if let Some(ref intercom) = args.intercom {
+    let addr = server::serve_addr(intercom);
+    let tx_utt = tx_utt.clone();
+    let stop_play_tx = stop_play_tx.clone();
+    let secret = args.intercom_secret.clone();
+    thread::spawn(move || {
+      if let Err(e) = intercom::intercom_thread(&addr, secret, tx_utt, stop_play_tx) {
+        crate::log::log("error", &format!("Intercom error: {}", e));
+      }
+    });
+  }
+
+  // ---------------------------------------------------
+  // Thread: control server (--serve)
+  // ---------------------------------------------------
+  if let Some(ref serve) = args.serve {
+    let addr = server::serve_addr(serve);
+    let origins = args.serve_origin.clone();
+    let tx_cmd_for_server = tx_cmd_conv.clone();
+    thread::spawn(move || {
+      if let Err(e) = server::server_thread(addr, origins, tx_cmd_for_server) {
+        crate::log::log("error", &format!("Control server error: {}", e));
+      }
+    });
+  }
+
+  // ---------------------------------------------------
+  // Thread: control socket (vtmate ctl)
+  // ---------------------------------------------------
+  #[cfg(unix)]
+  if let Some(path) = control::socket_path(args.control_socket.as_deref()) {
+    let tx_cmd_for_ctl = tx_cmd_conv.clone();
+    let stop_play_tx_for_ctl = stop_play_tx.clone();
+    let daemon = args.daemon;
+    thread::spawn(move || {
+      if let Err(e) = control::control_thread(path, tx_cmd_for_ctl, stop_play_tx_for_ctl) {
+        if daemon {
+          // a daemon nobody can reach is of no use
+          eprintln!("❌ Control socket error: {}", e);
+          util::terminate(1);
+        }
+        crate::log::log("warning", &format!("Control socket disabled: {}", e));
+      }
+    });
+  }
+
+  // ---------------------------------------------------
+  // Threads: chat bridges (--bridge)
+  // ---------------------------------------------------
+  for chat in args.bridge.clone() {
+    let tx_cmd_for_bridge = tx_cmd_conv.clone();
+    let voice = args.bridge_voice;
+    let users = args.bridge_user.clone();
+    thread::spawn(move || {
+      if let Err(e) = bridge::bridge_thread(chat.clone(), users, voice, tx_cmd_for_bridge) {
+        crate::log::log("error", &format!("Bridge {} stopped: {}", chat, e));
+      }
+    });
+  }
 
   // ---------------------------------------------------
   // Thread: keyboard
   // ---------------------------------------------------
   let recording_paused_for_key = recording_paused.clone();
   let stop_play_tx_for_key = stop_play_tx.clone();
-  let key_handle = thread::spawn(move || {
-    keyboard::keyboard_thread(
-      tx_ui_for_keyboard.clone(),
-      recording_paused_for_key.clone(),
-      stop_play_tx_for_key.clone(),
-      interrupt_counter.clone(),
-      None, // No read-file mode
-      tx_cmd_conv,
-    );
-  });
+  let key_handle = if args.stdio_protocol {
+    // commands come from stdin instead, closing it ends the session
+    thread::spawn(stdio::events_thread);
+    thread::spawn(move || {
+      stdio::commands_thread(tx_cmd_conv, stop_play_tx_for_key);
+      util::shutdown(0);
+    })
+  } else if headless {
+    // runs until killed, driven through the control socket if any
+    thread::spawn(move || {
+      let _keep_conversation_open = tx_cmd_conv;
+      loop {
+        thread::park();
+      }
+    })
+  } else {
+    thread::spawn(move || {
+      keyboard::keyboard_thread(
+        tx_ui_for_keyboard.clone(),
+        recording_paused_for_key.clone(),
+        stop_play_tx_for_key.clone(),
+        interrupter.clone(),
+        None, // No read-file mode
+        tx_cmd_conv,
+      );
+    })
+  };
 
   // Enable debate mode if requested
   if let Some(ref debate_args) = args.debate {
@@ -911,11 +1397,27 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
       }
     };
     state.debate_enabled.store(true, Ordering::SeqCst);
-    *state.debate_subject.lock().unwrap() = subject;
-    *state.debate_agents.lock().unwrap() = vec![agent1, agent2];
+    *state
+      .debate_subject
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = subject;
+    *state
+      .debate_agents
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = vec![agent1, agent2];
     state.debate_turn.store(0, Ordering::SeqCst);
   }
 
+  // ---------------------------------------------------
+  // systemd service (--service)
+  // ---------------------------------------------------
+  if args.service {
+    // ready means the whisper model is loaded, not loading on the first utterance
+    stt::init_whisper_context(&whisper_path);
+    service::ready();
+    service::watchdog_loop();
+  }
+
   // If running in interactive terminal, block until keyboard thread exits.
   let _ = key_handle.join();
 
@@ -0,0 +1,230 @@
+// ------------------------------------------------------------------
+//  Meeting notes (--meeting)
+// ------------------------------------------------------------------
+//
+// Everything said is noted, timestamped, instead of answered, and every few
+// minutes (--meeting-summary) the agent's llm rewrites a rolling summary of
+// the meeting with its action items. Nothing is spoken unless asked: saying
+// "summary" or "action items" speaks the latest one. The notes and the
+// summary are written to ~/.vtmate/meetings, encrypted with --encrypt.
+
+use crate::cancel::CancellationToken;
+use crate::conversation::ChatMessage;
+use crate::engine::{LanguageModel, ServerLlm};
+use crate::state::GLOBAL_STATE;
+use crossbeam_channel::Sender;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::PoisonError;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+const REQUEST_PHRASES: [&str; 7] = [
+  "summary",
+  "meeting summary",
+  "give me the summary",
+  "summarize the meeting",
+  "summarise the meeting",
+  "action items",
+  "what are the action items",
+];
+const SUMMARY_PROMPT: &str = "You take the notes of a meeting. Given the summary so far and \
+  what was said since, write the summary of the whole meeting in a few short paragraphs, then \
+  the action items, one per line with who does what. Plain text, no markdown: it is read aloud.";
+
+static MEETING: OnceLock<Mutex<Meeting>> = OnceLock::new();
+// one summary at a time, the periodic one or the one asked for
+static SUMMARIZING: Mutex<()> = Mutex::new(());
+
+// API
+// ------------------------------------------------------------------
+
+/// Starts the notes of a new meeting, returns where they are written.
+pub fn init() -> Result<PathBuf, String> {
+  let home = crate::util::get_user_home_path().ok_or("Unable to determine home directory")?;
+  let dir = home.join(".vtmate").join("meetings");
+  std::fs::create_dir_all(&dir)
+    .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+  let started = chrono::Local::now();
+  let notes_path = dir.join(format!("{}.txt", started.format("%Y-%m-%d_%H-%M-%S")));
+  let header = format!("Meeting of {}\n\n", started.format("%Y-%m-%d %H:%M"));
+  let failed = |e: std::io::Error| format!("Failed to write {}: {}", notes_path.display(), e);
+  // encrypted notes are written a record per line, as they are said
+  let encrypted = if crate::encryption::is_enabled() {
+    let mut writer = crate::encryption::Writer::create(&notes_path).map_err(failed)?;
+    writer.write_record(header.as_bytes()).map_err(failed)?;
+    Some(writer)
+  } else {
+    std::fs::write(&notes_path, header).map_err(failed)?;
+    None
+  };
+  MEETING
+    .set(Mutex::new(Meeting {
+      summary_path: notes_path.with_extension("summary.txt"),
+      notes_path: notes_path.clone(),
+      encrypted,
+      notes: Vec::new(),
+      summarized: 0,
+      summary: None,
+    }))
+    .ok();
+  Ok(if crate::encryption::is_enabled() {
+    crate::encryption::encrypted_path(&notes_path)
+  } else {
+    notes_path
+  })
+}
+
+pub fn is_enabled() -> bool {
+  MEETING.get().is_some()
+}
+
+/// Whether `text` asks for the summary.
+pub fn is_request(text: &str) -> bool {
+  let words: String = text
+    .to_lowercase()
+    .chars()
+    .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+    .collect();
+  let words = words.split_whitespace().collect::<Vec<_>>().join(" ");
+  REQUEST_PHRASES.contains(&words.as_str())
+}
+
+/// Notes what was said in an utterance.
+pub fn record(text: &str, tx_ui: &Sender<String>) {
+  let Some(meeting) = MEETING.get() else {
+    return;
+  };
+  let line = format!("[{}] {}", chrono::Local::now().format("%H:%M:%S"), text);
+  let _ = tx_ui.send(format!("line|🗒️  {}", line));
+  let mut meeting = meeting.lock().unwrap_or_else(PoisonError::into_inner);
+  let redacted = format!("{}\n", crate::redact::redact(&line));
+  let written = match meeting.encrypted.as_mut() {
+    Some(writer) => writer.write_record(redacted.as_bytes()),
+    None => std::fs::OpenOptions::new()
+      .append(true)
+      .open(&meeting.notes_path)
+      .and_then(|mut f| f.write_all(redacted.as_bytes())),
+  };
+  if let Err(e) = written {
+    crate::log::log(
+      "error",
+      &format!("Failed to write {}: {}", meeting.notes_path.display(), e),
+    );
+  }
+  meeting.notes.push(line);
+}
+
+/// The summary of the meeting up to now, brought up to date first.
+pub fn summary() -> String {
+  summarize();
+  MEETING
+    .get()
+    .and_then(|m| {
+      m.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .summary
+        .clone()
+    })
+    .unwrap_or_else(|| "Nothing was said yet.".to_string())
+}
+
+/// Updates the summary every `interval`, when something was said. Blocks.
+pub fn summary_thread(interval: Duration) {
+  loop {
+    std::thread::sleep(interval);
+    summarize();
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+struct Meeting {
+  notes_path: PathBuf,
+  summary_path: PathBuf,
+  // the notes file with --encrypt
+  encrypted: Option<crate::encryption::Writer>,
+  // timestamped lines
+  notes: Vec<String>,
+  // lines already in the summary
+  summarized: usize,
+  summary: Option<String>,
+}
+
+// Folds the lines said since the last summary into it.
+fn summarize() {
+  let (Some(meeting), Some(state)) = (MEETING.get(), GLOBAL_STATE.get()) else {
+    return;
+  };
+  let _summarizing = SUMMARIZING.lock().unwrap_or_else(PoisonError::into_inner);
+  let (new_lines, previous, count) = {
+    let meeting = meeting.lock().unwrap_or_else(PoisonError::into_inner);
+    if meeting.summarized == meeting.notes.len() {
+      return;
+    }
+    (
+      meeting.notes[meeting.summarized..].join("\n"),
+      meeting.summary.clone(),
+      meeting.notes.len(),
+    )
+  };
+  let llm = ServerLlm {
+    provider: state
+      .provider
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone(),
+    baseurl: state
+      .baseurl
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone(),
+    model: state
+      .model
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone(),
+  };
+  let messages = [
+    ChatMessage {
+      role: "system".to_string(),
+      content: SUMMARY_PROMPT.to_string(),
+      agent_name: None,
+      meta: None,
+    },
+    ChatMessage {
+      role: "user".to_string(),
+      content: format!(
+        "Summary so far:\n{}\n\nSaid since:\n{}",
+        previous.as_deref().unwrap_or("(the meeting just started)"),
+        new_lines
+      ),
+      agent_name: None,
+      meta: None,
+    },
+  ];
+  let mut reply = String::new();
+  // nothing interrupts a summary
+  let result = llm.stream_reply(&messages, &CancellationToken::new(), &mut |piece| {
+    reply.push_str(piece)
+  });
+  if let Err(e) = result {
+    crate::log::log("error", &format!("Meeting summary failed: {}", e));
+    return;
+  }
+  let reply = reply.trim().to_string();
+  let mut meeting = meeting.lock().unwrap_or_else(PoisonError::into_inner);
+  if let Err(e) = crate::encryption::write_file(
+    &meeting.summary_path,
+    crate::redact::redact(&reply).as_bytes(),
+  ) {
+    crate::log::log(
+      "error",
+      &format!("Failed to write {}: {}", meeting.summary_path.display(), e),
+    );
+  }
+  crate::log::log("info", "Meeting summary updated");
+  meeting.summarized = count;
+  meeting.summary = Some(reply);
+}
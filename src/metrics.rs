@@ -0,0 +1,65 @@
+// ------------------------------------------------------------------
+//  Metrics (queue depths)
+// ------------------------------------------------------------------
+
+use crossbeam_channel::Receiver;
+use serde_json::{Map, Value, json};
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+static QUEUES: Mutex<Vec<Queue>> = Mutex::new(Vec::new());
+
+// API
+// ------------------------------------------------------------------
+
+/// Reports the depth of the channel behind `rx` as `name` (a clone of the
+/// receiver is kept, it never receives).
+pub fn watch_queue<T: Send + 'static>(name: &'static str, rx: &Receiver<T>) {
+  let rx = rx.clone();
+  QUEUES
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .push(Queue {
+      name,
+      capacity: rx.capacity(),
+      len: Box::new(move || rx.len()),
+      dropped: 0,
+    });
+}
+
+/// Counts a message dropped because the queue `name` was full.
+pub fn dropped(name: &str) {
+  let mut queues = QUEUES.lock().unwrap_or_else(PoisonError::into_inner);
+  if let Some(queue) = queues.iter_mut().find(|q| q.name == name) {
+    queue.dropped += 1;
+  }
+}
+
+/// Depth, capacity and drops of every watched queue:
+/// `{"utterances": {"len": 0, "capacity": 4, "dropped": 0}, ...}`.
+pub fn queues_json() -> Value {
+  let queues = QUEUES.lock().unwrap_or_else(PoisonError::into_inner);
+  let mut out = Map::new();
+  for queue in queues.iter() {
+    out.insert(
+      queue.name.to_string(),
+      json!({
+        "len": (queue.len)(),
+        "capacity": queue.capacity,
+        "dropped": queue.dropped,
+      }),
+    );
+  }
+  Value::Object(out)
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+struct Queue {
+  name: &'static str,
+  // None for unbounded channels
+  capacity: Option<usize>,
+  len: Box<dyn Fn() -> usize + Send>,
+  dropped: u64,
+}
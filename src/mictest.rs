@@ -0,0 +1,214 @@
+// ------------------------------------------------------------------
+//  Microphone test (--mic-test)
+// ------------------------------------------------------------------
+//
+// Most setup problems are the levels of the microphone: the VU bar stays
+// flat, or the noise of the room keeps an utterance from ending. The test
+// records a moment of the quiet room then a few seconds of voice, plays the
+// voice back, shows the levels of both and recommends a sound_threshold_peak
+// between them. The recommendation is also said with the voice of the agent
+// when its models are there.
+
+use crate::config::{AgentSettings, Args};
+use crate::state::{AppState, GLOBAL_STATE};
+use std::sync::Arc;
+
+const QUIET_SECS: u64 = 2;
+const SPEECH_SECS: u64 = 4;
+// windows the levels are measured on
+const WINDOW_MS: u64 = 10;
+// share of the windows under the level taken for the room, and for the voice
+// (which doesn't fill the whole recording)
+const NOISE_PERCENTILE: f32 = 0.95;
+const VOICE_PERCENTILE: f32 = 0.9;
+// the voice gate (see crate::record) closes at half the threshold: the noise
+// must stay well under that to let an utterance end
+const NOISE_MARGIN: f32 = 2.5;
+// a threshold over this share of the voice misses its quieter syllables
+const VOICE_MARGIN: f32 = 0.5;
+// under this the microphone is muted, or not the one spoken to
+const MIN_VOICE: f32 = 0.02;
+// a sample this close to full scale is clipped
+const CLIP_LEVEL: f32 = 0.99;
+
+// API
+// ------------------------------------------------------------------
+
+/// Peak and rms of some mono samples.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Levels {
+  pub peak: f32,
+  pub rms: f32,
+}
+
+impl Levels {
+  pub fn of(samples: &[f32]) -> Self {
+    if samples.is_empty() {
+      return Self::default();
+    }
+    Self {
+      peak: samples.iter().fold(0.0, |m, s| m.max(s.abs())),
+      rms: (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt(),
+    }
+  }
+}
+
+/// The sound_threshold_peak for a room sounding like `quiet` and a voice
+/// like `speech` (mono samples at `sample_rate`), or why there's none.
+pub fn recommend_threshold(quiet: &[f32], speech: &[f32], sample_rate: u32) -> Result<f32, String> {
+  let noise = percentile(&window_peaks(quiet, sample_rate), NOISE_PERCENTILE);
+  let voice = percentile(&window_peaks(speech, sample_rate), VOICE_PERCENTILE);
+  if voice < MIN_VOICE {
+    return Err(
+      "Hardly anything was heard: check the microphone is the input device and isn't muted, or turn it up with --mic-gain".to_string(),
+    );
+  }
+  let threshold = (noise * voice).sqrt().max(noise * NOISE_MARGIN);
+  if threshold > voice * VOICE_MARGIN {
+    return Err(format!(
+      "The voice ({:.3}) is hardly louder than the room ({:.3}): get closer to the microphone or away from the noise",
+      voice, noise
+    ));
+  }
+  Ok(((threshold * 100.0).round() / 100.0).max(0.01))
+}
+
+/// Runs the microphone test. Returns the exit code.
+pub fn run_mic_test(args: &Args) -> i32 {
+  crate::gain::init(args.mic_gain);
+  let agent = crate::config::load_agent(args).ok();
+  let result = (|| -> Result<f32, String> {
+    println!("🤫 Stay quiet for {} seconds...", QUIET_SECS);
+    let quiet = record(QUIET_SECS)?;
+    println!(
+      "\n🗣  Now talk for {} seconds, as you would to the agent:",
+      SPEECH_SECS
+    );
+    let speech = record(SPEECH_SECS)?;
+    let sample_rate = speech.sample_rate;
+    let speech = crate::audio::convert_to_mono(&speech);
+    let quiet = crate::audio::convert_to_mono(&quiet);
+
+    let (room, voice) = (Levels::of(&quiet), Levels::of(&speech));
+    println!();
+    println!("  room:   peak {:.3}  rms {:.3}", room.peak, room.rms);
+    println!("  voice:  peak {:.3}  rms {:.3}", voice.peak, voice.rms);
+    if voice.peak >= CLIP_LEVEL {
+      println!("⚠️  The voice clips: turn the microphone (or --mic-gain) down");
+    }
+
+    println!("\n🔊 This is how you sound:");
+    let playback = crate::audio::AudioChunk {
+      data: speech.clone(),
+      channels: 1,
+      sample_rate,
+    };
+    if let Err(e) = crate::say::play_with(args.audio_buffer_frames, |_, tx| {
+      tx.send(playback)?;
+      Ok(())
+    }) {
+      println!("⚠️  Can't play it back: {}", e);
+    }
+
+    recommend_threshold(&quiet, &speech, sample_rate)
+  })();
+
+  match result {
+    Ok(threshold) => {
+      let current = agent
+        .as_ref()
+        .map(|a| format!(" (the agent has {})", a.sound_threshold_peak))
+        .unwrap_or_default();
+      let gain = if args.mic_gain != 1.0 {
+        format!(", with --mic-gain {}", args.mic_gain)
+      } else {
+        String::new()
+      };
+      println!(
+        "\n✅ Recommended: sound_threshold_peak = {}{}{}",
+        threshold, gain, current
+      );
+      if let Some(agent) = agent.as_ref().filter(|_| !args.no_tts) {
+        say(
+          agent,
+          &format!("Set the sound threshold peak to {}.", threshold),
+          args,
+        );
+      }
+      0
+    }
+    Err(e) => {
+      eprintln!("\n❌ {}", e);
+      if let Some(agent) = agent.as_ref().filter(|_| !args.no_tts) {
+        say(agent, &e, args);
+      }
+      1
+    }
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// `seconds` of the microphone, with the gain of --mic-gain
+fn record(seconds: u64) -> Result<crate::audio::AudioChunk, String> {
+  let chunk = crate::audio::record_microphone(seconds)?;
+  let mut data = chunk.data.to_vec();
+  crate::gain::apply(&mut data);
+  Ok(crate::audio::AudioChunk {
+    data: data.into(),
+    ..chunk
+  })
+}
+
+// The peak of every WINDOW_MS of `samples`
+fn window_peaks(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+  let len = (sample_rate as u64 * WINDOW_MS / 1000).max(1) as usize;
+  samples
+    .chunks(len)
+    .map(|w| w.iter().fold(0.0, |m: f32, s| m.max(s.abs())))
+    .collect()
+}
+
+// The level `share` of the values are under
+fn percentile(values: &[f32], share: f32) -> f32 {
+  if values.is_empty() {
+    return 0.0;
+  }
+  let mut sorted = values.to_vec();
+  sorted.sort_by(|a, b| a.total_cmp(b));
+  let at = ((sorted.len() - 1) as f32 * share).round() as usize;
+  sorted[at]
+}
+
+// Says `text` with the agent's voice when its models are there, the test
+// doesn't download anything
+fn say(agent: &AgentSettings, text: &str, args: &Args) {
+  if !crate::assets::is_ready(agent) {
+    return;
+  }
+  GLOBAL_STATE
+    .set(Arc::new(AppState::with_agent(
+      agent.clone(),
+      vec![agent.clone()],
+      true,
+    )))
+    .ok();
+  let text = crate::util::strip_special_chars(text);
+  let _ = crate::say::play_with(args.audio_buffer_frames, |out_sample_rate, tx| {
+    let tts = crate::engine::BuiltinTts {
+      tts: agent.tts.clone(),
+      out_sample_rate,
+      speed: agent.voice_speed,
+    };
+    crate::engine::TextToSpeech::speak(
+      &tts,
+      &text,
+      &agent.language,
+      &agent.voice,
+      tx,
+      &crate::cancel::CancellationToken::new(),
+    )?;
+    Ok(())
+  });
+}
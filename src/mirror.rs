@@ -0,0 +1,59 @@
+// ------------------------------------------------------------------
+//  Reply mirror (--mirror)
+// ------------------------------------------------------------------
+//
+// The replies of the agent written to a file or a named pipe as the llm
+// streams them, for other tools to show live (a browser overlay, a second
+// screen). Each reply is followed by an empty line; one cut short ends with
+// "(interrupted)". A pipe can be reopened by another reader at any time: the
+// replies streamed while nobody reads it are skipped.
+
+use crate::engine::TurnEvent;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+
+// API
+// ------------------------------------------------------------------
+
+/// Writes the replies to `path` as they stream. Blocks.
+pub fn mirror_thread(path: &Path) -> std::io::Result<()> {
+  loop {
+    // a pipe blocks here until there is a reader
+    let file = OpenOptions::new()
+      .write(true)
+      .create(true)
+      .truncate(true)
+      .open(path)?;
+    match mirror(file) {
+      Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+        crate::log::log("debug", "Mirror reader gone, waiting for another");
+      }
+      result => return result,
+    }
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn mirror(mut file: File) -> std::io::Result<()> {
+  // subscribed once there is a reader, so it doesn't get a backlog
+  let events = crate::events::subscribe();
+  // a reply is being streamed
+  let mut streaming = false;
+  while let Ok(event) = events.recv() {
+    let text = match &event {
+      TurnEvent::Token(piece) => piece.clone(),
+      // spoken lines that didn't come from the llm (confirmations, spelling)
+      TurnEvent::Done(reply) if !streaming => format!("{}\n\n", reply),
+      TurnEvent::Done(_) => "\n\n".to_string(),
+      TurnEvent::Interrupted if streaming => "\n(interrupted)\n\n".to_string(),
+      _ => continue,
+    };
+    streaming = matches!(event, TurnEvent::Token(_));
+    file.write_all(text.as_bytes())?;
+    file.flush()?;
+  }
+  Ok(())
+}
@@ -0,0 +1,130 @@
+// ------------------------------------------------------------------
+//  Media keys (--media-keys)
+// ------------------------------------------------------------------
+//
+// vtmate shows up as a media player on the desktop (MPRIS), so the media
+// keys and the player widgets control the voice: play/pause pauses and
+// resumes it where it was, stop cuts the reply like ESC. Linux only, behind
+// the mpris feature.
+
+use crate::state::{AppState, TurnState};
+use crossbeam_channel::Sender;
+use std::sync::Arc;
+use std::sync::PoisonError;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// API
+// ------------------------------------------------------------------
+
+/// Registers the media player and keeps its status in step with the voice,
+/// stopping a reply through `stop_play_tx`. Blocks.
+pub fn media_keys_thread(state: Arc<AppState>, stop_play_tx: Sender<()>) -> Result<(), String> {
+  let mut player = Player::register(state.clone(), stop_play_tx)?;
+  let mut shown = None;
+  loop {
+    std::thread::sleep(crate::power::poll_interval(POLL_INTERVAL));
+    let status = Status::of(&state);
+    let agent = state
+      .agent_name
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone();
+    if shown.as_ref() != Some(&(status, agent.clone())) {
+      player.show(status, &agent);
+      shown = Some((status, agent));
+    }
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+  Speaking,
+  Paused,
+  Stopped,
+}
+
+impl Status {
+  fn of(state: &AppState) -> Self {
+    if state.turn.get() != TurnState::Speaking {
+      Status::Stopped
+    } else if state.playback.paused.load(Ordering::Relaxed) {
+      Status::Paused
+    } else {
+      Status::Speaking
+    }
+  }
+}
+
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+struct Player {
+  controls: souvlaki::MediaControls,
+}
+
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+impl Player {
+  fn register(state: Arc<AppState>, stop_play_tx: Sender<()>) -> Result<Self, String> {
+    use souvlaki::MediaControlEvent;
+    let mut controls = souvlaki::MediaControls::new(souvlaki::PlatformConfig {
+      dbus_name: "vtmate",
+      display_name: "vtmate",
+      hwnd: None,
+    })
+    .map_err(|e| format!("Unable to register the media player: {:?}", e))?;
+    controls
+      .attach(move |event| {
+        let paused = &state.playback.paused;
+        match event {
+          MediaControlEvent::Play => paused.store(false, Ordering::Relaxed),
+          MediaControlEvent::Pause => paused.store(true, Ordering::Relaxed),
+          MediaControlEvent::Toggle => {
+            paused.fetch_xor(true, Ordering::Relaxed);
+          }
+          MediaControlEvent::Stop => {
+            paused.store(false, Ordering::Relaxed);
+            crate::control::interrupt(&stop_play_tx);
+          }
+          _ => return,
+        }
+        crate::telemetry::feature("media_key");
+      })
+      .map_err(|e| format!("Unable to listen to the media keys: {:?}", e))?;
+    Ok(Self { controls })
+  }
+
+  fn show(&mut self, status: Status, agent: &str) {
+    let playback = match status {
+      Status::Speaking => souvlaki::MediaPlayback::Playing { progress: None },
+      Status::Paused => souvlaki::MediaPlayback::Paused { progress: None },
+      Status::Stopped => souvlaki::MediaPlayback::Stopped,
+    };
+    let result = self.controls.set_playback(playback).and_then(|_| {
+      self.controls.set_metadata(souvlaki::MediaMetadata {
+        title: Some(agent),
+        artist: Some("vtmate"),
+        ..Default::default()
+      })
+    });
+    if let Err(e) = result {
+      crate::log::log("debug", &format!("Media player update failed: {:?}", e));
+    }
+  }
+}
+
+// media keys left out of this build
+#[cfg(not(all(feature = "mpris", target_os = "linux")))]
+struct Player;
+
+#[cfg(not(all(feature = "mpris", target_os = "linux")))]
+impl Player {
+  fn register(_state: Arc<AppState>, _stop_play_tx: Sender<()>) -> Result<Self, String> {
+    Err("The media keys need a Linux build with the mpris feature".to_string())
+  }
+
+  fn show(&mut self, _status: Status, _agent: &str) {}
+}
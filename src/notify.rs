@@ -0,0 +1,72 @@
+// ------------------------------------------------------------------
+//  Desktop notifications (--notify)
+// ------------------------------------------------------------------
+
+use crate::engine::TurnEvent;
+use crate::state::GLOBAL_STATE;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// longest notification body, the rest of the line is cut
+const MAX_BODY_CHARS: usize = 200;
+
+// whether the terminal has the focus, as reported by the terminal itself
+static FOCUSED: AtomicBool = AtomicBool::new(true);
+
+// API
+// ------------------------------------------------------------------
+
+/// Records a focus change of the terminal (crossterm focus events).
+pub fn set_focused(focused: bool) {
+  FOCUSED.store(focused, Ordering::Relaxed);
+}
+
+/// Shows a desktop notification with the first line of every answer that
+/// completes while the terminal is unfocused or recording is paused.
+pub fn notify_thread() {
+  let events = crate::events::subscribe();
+  for event in events {
+    let TurnEvent::Done(reply) = event else {
+      continue;
+    };
+    let Some(state) = GLOBAL_STATE.get() else {
+      continue;
+    };
+    let paused = state.recording_paused.load(Ordering::Relaxed);
+    if FOCUSED.load(Ordering::Relaxed) && !paused {
+      continue;
+    }
+    let Some(line) = reply.lines().map(str::trim).find(|l| !l.is_empty()) else {
+      continue;
+    };
+    let mut body: String = line.chars().take(MAX_BODY_CHARS).collect();
+    if line.chars().count() > MAX_BODY_CHARS {
+      body.push('…');
+    }
+    let agent = state
+      .agent_name
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone();
+    crate::dnd::run_or_defer(move || show(&agent, &body));
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+#[cfg(feature = "notifications")]
+fn show(summary: &str, body: &str) {
+  if let Err(e) = notify_rust::Notification::new()
+    .appname("vtmate")
+    .summary(summary)
+    .body(body)
+    .show()
+  {
+    crate::log::log("warning", &format!("Notification failed: {}", e));
+  }
+}
+
+// notifications left out of this build
+#[cfg(not(feature = "notifications"))]
+fn show(_summary: &str, _body: &str) {}
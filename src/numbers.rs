@@ -0,0 +1,213 @@
+// ------------------------------------------------------------------
+//  Number double-check (--double-check-numbers)
+// ------------------------------------------------------------------
+//
+// A misheard amount or date does more harm than any other word. With
+// --double-check-numbers a reply holding some is followed by a phrase saying
+// them again: as they were written (`repeat`, spoken slower by crate::pace)
+// or digit by digit (`spell`). Small counts ("2 of them") are left out.
+
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+// bare numbers with fewer digits are counts, not worth a second time
+const MIN_DIGITS: usize = 3;
+// numbers said again at most, the first ones of the reply
+const MAX_NUMBERS: usize = 4;
+
+const MONTHS: [&str; 12] = [
+  "january",
+  "february",
+  "march",
+  "april",
+  "may",
+  "june",
+  "july",
+  "august",
+  "september",
+  "october",
+  "november",
+  "december",
+];
+// words after a number making it an amount
+const UNITS: [&str; 10] = [
+  "dollars", "euros", "pounds", "yen", "percent", "cents", "usd", "eur", "gbp", "%",
+];
+
+static MODE: Mutex<Option<Mode>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+  // the numbers as written
+  Repeat,
+  // digit by digit
+  Spell,
+}
+
+/// Sets the double-check from --double-check-numbers (`repeat` or `spell`).
+pub fn init(mode: Option<&str>) {
+  *MODE.lock().unwrap_or_else(PoisonError::into_inner) = match mode {
+    Some("repeat") => Some(Mode::Repeat),
+    Some("spell") => Some(Mode::Spell),
+    _ => None,
+  };
+}
+
+/// The phrase saying the critical numbers of `reply` again, when the
+/// double-check is on and there are some. Ready to be spoken as it is: the
+/// symbols the tts would drop are words.
+pub fn double_check(reply: &str) -> Option<String> {
+  let mode = (*MODE.lock().unwrap_or_else(PoisonError::into_inner))?;
+  check_phrase(reply, mode)
+}
+
+/// The phrase saying the critical numbers of `reply` again in `mode`.
+pub fn check_phrase(reply: &str, mode: Mode) -> Option<String> {
+  let numbers = critical_numbers(reply);
+  if numbers.is_empty() {
+    return None;
+  }
+  let said: Vec<String> = numbers
+    .iter()
+    .map(|n| match mode {
+      Mode::Repeat => repeat(n),
+      Mode::Spell => spell(n),
+    })
+    .collect();
+  let list = match said.split_last() {
+    Some((last, [])) => last.clone(),
+    Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+    None => return None,
+  };
+  Some(format!("To be sure, {}", list))
+}
+
+/// Dates, amounts and long numbers of `text`, in order, as written.
+pub fn critical_numbers(text: &str) -> Vec<String> {
+  let words: Vec<&str> = text.split_whitespace().map(trim_word).collect();
+  let mut found: Vec<String> = Vec::new();
+  let mut i = 0;
+  while i < words.len() && found.len() < MAX_NUMBERS {
+    let word = words[i];
+    if !word.chars().any(|c| c.is_ascii_digit()) {
+      i += 1;
+      continue;
+    }
+    let prev = i.checked_sub(1).map(|p| words[p]);
+    let next = words.get(i + 1).copied();
+    let after = words.get(i + 2).copied();
+    let number = if prev.is_some_and(is_month) {
+      // March 5 (2024)
+      let year = next.filter(|y| is_year(y));
+      i += 1 + year.is_some() as usize;
+      join(&[prev.unwrap(), word], year)
+    } else if next.is_some_and(is_month) {
+      // 5 March (2024)
+      let year = after.filter(|y| is_year(y));
+      i += 2 + year.is_some() as usize;
+      join(&[word, next.unwrap()], year)
+    } else if next.is_some_and(is_unit) {
+      // 1,250 euros
+      i += 2;
+      join(&[word, next.unwrap()], None)
+    } else if word.starts_with(['$', '€', '£', '¥'])
+      || word.ends_with('%')
+      || word.chars().filter(|c| c.is_ascii_digit()).count() >= MIN_DIGITS
+    {
+      i += 1;
+      word.to_string()
+    } else {
+      i += 1;
+      continue;
+    };
+    if !found.contains(&number) {
+      found.push(number);
+    }
+  }
+  found
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// the punctuation ending a sentence or clause isn't part of the number
+fn trim_word(word: &str) -> &str {
+  word
+    .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '"'])
+    .trim_start_matches(['(', '"'])
+}
+
+fn join(words: &[&str], year: Option<&str>) -> String {
+  let mut text = words.join(" ");
+  if let Some(year) = year {
+    text.push_str(", ");
+    text.push_str(year);
+  }
+  text
+}
+
+fn is_month(word: &str) -> bool {
+  let word = word.to_lowercase();
+  word.len() >= 3 && MONTHS.iter().any(|m| m.starts_with(word.as_str()))
+}
+
+fn is_year(word: &str) -> bool {
+  word.len() == 4 && word.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_unit(word: &str) -> bool {
+  UNITS.contains(&word.to_lowercase().as_str())
+}
+
+// "$1,250.50" -> "1,250.50 dollars"
+fn repeat(number: &str) -> String {
+  number
+    .split_whitespace()
+    .map(|word| match unit(word) {
+      "" => word.to_string(),
+      unit => format!("{}{}", word.trim_matches(['$', '€', '£', '¥', '%']), unit),
+    })
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+// the word of the currency or percent sign of a number
+fn unit(word: &str) -> &'static str {
+  match word.chars().next() {
+    Some('$') => " dollars",
+    Some('€') => " euros",
+    Some('£') => " pounds",
+    Some('¥') => " yen",
+    _ if word.ends_with('%') => " percent",
+    _ => "",
+  }
+}
+
+// "$1,250.50" -> "1, 2, 5, 0, point, 5, 0 dollars"
+fn spell(number: &str) -> String {
+  number
+    .split_whitespace()
+    .map(|word| {
+      if !word.chars().any(|c| c.is_ascii_digit()) {
+        return word.to_string();
+      }
+      let digits: Vec<String> = word
+        .chars()
+        .filter_map(|c| match c {
+          '0'..='9' => Some(c.to_string()),
+          '.' => Some("point".to_string()),
+          '-' => Some("dash".to_string()),
+          '/' => Some("slash".to_string()),
+          ':' => Some("colon".to_string()),
+          c if c.is_alphabetic() => Some(c.to_string()),
+          _ => None,
+        })
+        .collect();
+      format!("{}{}", digits.join(", "), unit(word))
+    })
+    .collect::<Vec<_>>()
+    .join(" ")
+}
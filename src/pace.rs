@@ -0,0 +1,89 @@
+// ------------------------------------------------------------------
+//  Pace (--no-auto-pace)
+// ------------------------------------------------------------------
+//
+// Numbers, identifiers spelled out letter by letter and code are hard to
+// follow at the speed of prose. Before synthesis a phrase is split in runs
+// of either, the dense ones being spoken slower than the voice speed and the
+// prose around them at the voice speed.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// speed of the dense runs, relative to the voice speed
+const DENSE_PACE: f32 = 0.75;
+// shorter digit runs are read as words ("at 5", "2 of them")
+const DENSE_DIGITS: usize = 3;
+// in a word, these are code or an address rather than prose
+const CODE_CHARS: &[char] = &[
+  '_', '(', ')', '{', '}', '[', ']', '<', '>', '=', '/', '\\', ':', '.', '@', '#', '$', '%', '&',
+  '*', '+', '|', '~', '^',
+];
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+// API
+// ------------------------------------------------------------------
+
+pub fn set_enabled(enabled: bool) {
+  ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// The runs of `phrase` to speak one after the other, with their speed
+/// relative to the voice speed: 1.0 for prose, slower for numbers, spelled
+/// out identifiers and code. The phrase as a single run when it has none.
+pub fn runs(phrase: &str) -> Vec<(String, f32)> {
+  if !ENABLED.load(Ordering::Relaxed) {
+    return vec![(phrase.to_string(), 1.0)];
+  }
+  let words: Vec<&str> = phrase.split_whitespace().collect();
+  let mut dense: Vec<bool> = words.iter().map(|w| is_dense(w)).collect();
+  if !dense.contains(&true) {
+    return vec![(phrase.to_string(), 1.0)];
+  }
+  // a word between two dense ones goes with them ("ID 4 and 5")
+  for i in 1..words.len().saturating_sub(1) {
+    if !dense[i] && dense[i - 1] && dense[i + 1] {
+      dense[i] = true;
+    }
+  }
+  let mut runs: Vec<(String, f32)> = Vec::new();
+  for (word, dense) in words.iter().zip(dense) {
+    let pace = if dense { DENSE_PACE } else { 1.0 };
+    match runs.last_mut() {
+      Some((text, last)) if *last == pace => {
+        text.push(' ');
+        text.push_str(word);
+      }
+      _ => runs.push((word.to_string(), pace)),
+    }
+  }
+  runs
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn is_dense(word: &str) -> bool {
+  let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+  if word.is_empty() {
+    return false;
+  }
+  let digits = word.chars().filter(|c| c.is_ascii_digit()).count();
+  let letters = word.chars().filter(|c| c.is_alphabetic()).count();
+  // phone numbers, ids, versions, serials
+  if digits >= DENSE_DIGITS || (digits > 0 && letters > 0) {
+    return true;
+  }
+  // acronyms and codes read letter by letter (API, XJ)
+  if letters >= 2 && word.chars().all(|c| !c.is_alphabetic() || c.is_uppercase()) {
+    return true;
+  }
+  // camelCase
+  let mut chars = word.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c.is_lowercase() && chars.peek().is_some_and(|n| n.is_uppercase()) {
+      return true;
+    }
+  }
+  word.contains(CODE_CHARS)
+}
@@ -0,0 +1,132 @@
+// ------------------------------------------------------------------
+//  Personas ([persona] sections, "switch to the chef")
+// ------------------------------------------------------------------
+//
+// A persona is a character the agent takes on: its own system prompt, voice,
+// speed and language, while the llm, the microphone settings and the rest
+// stay those of the current agent. It's switched to by voice, as are the
+// agents, whose own prompt and voice come back with them. The conversation
+// goes on as it is.
+
+use crate::config::{AgentSettings, Persona};
+use crate::state::GLOBAL_STATE;
+use std::sync::PoisonError;
+use std::sync::atomic::Ordering;
+use std::sync::{Mutex, OnceLock};
+
+static PERSONAS: OnceLock<Vec<Persona>> = OnceLock::new();
+// the persona taken on, None while the agent speaks as itself
+static ACTIVE: Mutex<Option<Persona>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+/// Sets the personas of the settings file. Call once.
+pub fn init(personas: Vec<Persona>) {
+  let _ = PERSONAS.set(personas);
+}
+
+/// The persona or agent `text` asks to switch to: "switch to the chef",
+/// "talk to the chef", "let me talk to the planner".
+pub fn request(text: &str) -> Option<String> {
+  let words = normalize(text);
+  let words: Vec<&str> = words.split_whitespace().collect();
+  let rest = match words.as_slice() {
+    ["switch", "to", rest @ ..]
+    | ["talk", "to", rest @ ..]
+    | ["let", "me", "talk", "to", rest @ ..]
+    | ["put", rest @ .., "on"] => rest,
+    _ => return None,
+  };
+  let rest = rest.strip_prefix(&["the"]).unwrap_or(rest);
+  if rest.is_empty() {
+    return None;
+  }
+  let wanted = rest.join(" ");
+  let state = GLOBAL_STATE.get()?;
+  personas()
+    .iter()
+    .map(|p| &p.name)
+    .chain(state.agents.iter().map(|a| &a.name))
+    .find(|name| normalize(name) == wanted)
+    .cloned()
+}
+
+/// Takes on the persona `name`, or goes back to the agent `name`. Returns
+/// the line telling so.
+pub fn switch(name: &str) -> String {
+  let Some(state) = GLOBAL_STATE.get() else {
+    return String::new();
+  };
+  if let Some(persona) = personas().iter().find(|p| p.name == name) {
+    *state.voice.lock().unwrap_or_else(PoisonError::into_inner) = persona.voice.clone();
+    *state.tts.lock().unwrap_or_else(PoisonError::into_inner) = persona.tts.clone();
+    *state
+      .language
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = persona.language.clone();
+    *state
+      .system_prompt
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = persona.system_prompt.clone();
+    state
+      .speed
+      .store((persona.voice_speed * 10.0) as u32, Ordering::Relaxed);
+    *ACTIVE.lock().unwrap_or_else(PoisonError::into_inner) = Some(persona.clone());
+    crate::tts::clear_backend();
+    crate::telemetry::feature("persona_switch");
+    crate::log::log("info", &format!("Switched to the persona '{}'", name));
+    return format!("Switched to {}.", name);
+  }
+  let Some(agent) = state.agents.iter().find(|a| a.name == name) else {
+    return String::new();
+  };
+  // the agent as it is configured, the persona left behind
+  state.use_agent(agent);
+  state.recording_paused.store(agent.ptt, Ordering::Relaxed);
+  crate::telemetry::feature("agent_switch");
+  crate::log::log("info", &format!("Switched to the agent '{}'", name));
+  format!("Switched to {}.", name)
+}
+
+/// The persona taken on, if any.
+pub fn active() -> Option<String> {
+  ACTIVE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+    .map(|p| p.name.clone())
+}
+
+/// `agent` with the prompt and voice of the persona taken on.
+pub fn apply(agent: AgentSettings) -> AgentSettings {
+  match ACTIVE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+  {
+    Some(persona) => persona.over(&agent),
+    None => agent,
+  }
+}
+
+/// Drops the persona taken on, as when another agent is chosen.
+pub fn clear() {
+  *ACTIVE.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn personas() -> &'static [Persona] {
+  PERSONAS.get().map(Vec::as_slice).unwrap_or(&[])
+}
+
+fn normalize(text: &str) -> String {
+  let words: String = text
+    .to_lowercase()
+    .chars()
+    .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+    .collect();
+  words.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -2,15 +2,17 @@
 //  Playback
 // ------------------------------------------------------------------
 
-use crate::state::GLOBAL_STATE;
+use crate::state::TurnState;
 use cpal::traits::{DeviceTrait, StreamTrait};
 use crossbeam_channel::Sender;
 use crossbeam_channel::{Receiver, select};
+use std::borrow::Cow;
 use std::collections::VecDeque;
 use std::sync::OnceLock;
+use std::sync::PoisonError;
 use std::sync::{
   Arc, Mutex,
-  atomic::{AtomicBool, AtomicU64, Ordering},
+  atomic::{AtomicBool, AtomicU8, AtomicU32, AtomicU64, Ordering},
 };
 use std::thread;
 use std::time::Duration;
@@ -19,11 +21,119 @@ use std::time::Instant;
 // API
 
 static WAV_TX: OnceLock<Sender<crate::audio::AudioChunk>> = OnceLock::new();
+// set while a --secondary-output device plays along
+static SECONDARY_TX: OnceLock<Sender<Secondary>> = OnceLock::new();
+// sounds mixed over the playback (earcons), set once the output is known
+static CUES: OnceLock<Cues> = OnceLock::new();
+// where an interruption cuts the playback, a Granularity
+static GRANULARITY: AtomicU8 = AtomicU8::new(Granularity::Instant as u8);
+// loudness asked by voice ("louder"), f32 bits, over the volume
+static LEVEL: AtomicU32 = AtomicU32::new(0x3f80_0000);
+// samples of the current reply played and still queued, and the samples the
+// output plays per second (see `progress`)
+static PLAYED: AtomicU64 = AtomicU64::new(0);
+static QUEUED: AtomicU64 = AtomicU64::new(0);
+static SAMPLES_PER_SEC: AtomicU64 = AtomicU64::new(0);
+
+// samples quieter than this for WORD_GAP_MS are a pause between two words
+const WORD_GAP_LEVEL: f32 = 0.02;
+const WORD_GAP_MS: u64 = 20;
+// pausing and resuming fade the voice out and in over this long, a jump to
+// silence pops
+const PAUSE_FADE_MS: f32 = 15.0;
+// bounds and step of the loudness asked by voice
+const LEVEL_MIN: f32 = 0.25;
+const LEVEL_MAX: f32 = 2.0;
+const LEVEL_STEP: f32 = 1.25;
 
 /// Set the global channel used by the WAV writer thread.
 pub fn set_wav_tx(tx: Sender<crate::audio::AudioChunk>) {
   WAV_TX.set(tx).ok();
 }
+
+/// Where an interruption cuts the playback (--interrupt-granularity):
+/// `instant`, at the end of the current `word` or of the current `phrase`.
+pub fn set_interrupt_granularity(name: &str) {
+  let granularity = match name {
+    "word" => Granularity::Word,
+    "phrase" => Granularity::Phrase,
+    _ => Granularity::Instant,
+  };
+  GRANULARITY.store(granularity as u8, Ordering::Relaxed);
+}
+
+/// Makes the voice louder or quieter by a step, returns the new level.
+pub fn step_level(up: bool) -> f32 {
+  let level = if up {
+    level() * LEVEL_STEP
+  } else {
+    level() / LEVEL_STEP
+  };
+  let level = level.clamp(LEVEL_MIN, LEVEL_MAX);
+  LEVEL.store(level.to_bits(), Ordering::Relaxed);
+  level
+}
+
+/// Sent on the playback channel after the audio of a phrase, so an
+/// interruption can end the playback there.
+pub fn phrase_end() -> crate::audio::AudioChunk {
+  crate::audio::AudioChunk {
+    data: Arc::from([]),
+    channels: 1,
+    sample_rate: 1,
+  }
+}
+
+/// How far the voice of the current reply got, while some is left to play.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+  pub played_secs: f32,
+  // of the audio synthesized so far, the reply may go on
+  pub remaining_secs: f32,
+}
+
+impl Progress {
+  /// The share played, from 0 to 1.
+  pub fn fraction(&self) -> f32 {
+    let total = self.played_secs + self.remaining_secs;
+    if total > 0.0 {
+      self.played_secs / total
+    } else {
+      0.0
+    }
+  }
+}
+
+/// The progress of the reply being spoken, None when nothing is queued.
+pub fn progress() -> Option<Progress> {
+  let per_sec = SAMPLES_PER_SEC.load(Ordering::Relaxed) as f32;
+  let queued = QUEUED.load(Ordering::Relaxed);
+  if per_sec == 0.0 || queued == 0 {
+    return None;
+  }
+  Some(Progress {
+    played_secs: PLAYED.load(Ordering::Relaxed) as f32 / per_sec,
+    remaining_secs: queued as f32 / per_sec,
+  })
+}
+
+/// Whether the played audio is saved (-s).
+pub fn is_recording() -> bool {
+  WAV_TX.get().is_some()
+}
+
+/// Plays `chunk` mixed over whatever is playing, without queueing behind it
+/// or counting as playback (the earcons). Dropped before playback started.
+pub fn mix(chunk: &crate::audio::AudioChunk) {
+  if let Some(cues) = CUES.get() {
+    let data = to_output(chunk, cues.channels, cues.sample_rate);
+    cues
+      .queue
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .push(data);
+  }
+}
 // ------------------------------------------------------------------
 
 pub fn playback_thread(
@@ -37,302 +147,495 @@ pub fn playback_thread(
   gate_until_ms: Arc<AtomicU64>,
   paused: Arc<AtomicBool>,
   out_channels: u16,
-  ui: crate::state::UiState,
   volume: Arc<Mutex<f32>>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   // inst removed
   // let inst_ptr = &start_instant;
   use cpal::SampleFormat;
 
-  let queue: Arc<Mutex<VecDeque<f32>>> = Arc::new(Mutex::new(VecDeque::new()));
+  let queue: Arc<Mutex<PlaybackQueue>> = Arc::new(Mutex::new(PlaybackQueue::default()));
+  let _ = CUES.set(Cues {
+    queue: Mutex::new(PlaybackQueue::default()),
+    channels: out_channels,
+    sample_rate: config.sample_rate.0,
+  });
   let volume_for_stream = volume.clone();
   let sample_format = supported.sample_format();
   let hangover_ms = crate::util::env_u64("HANGOVER_MS", crate::config::HANGOVER_MS_DEFAULT);
 
+  let word_gap = word_gap_samples(config.sample_rate.0, out_channels);
+  SAMPLES_PER_SEC.store(
+    config.sample_rate.0 as u64 * out_channels as u64,
+    Ordering::Relaxed,
+  );
+
   // When this reaches a few callbacks in a row of "no real audio", we mark not-playing.
   let empty_callbacks = Arc::new(AtomicU64::new(0));
 
-  let err_fn = |e| crate::log::log("error", &format!("output stream error: {}", e));
-
-  let stream = match sample_format {
-    SampleFormat::F32 => device.build_output_stream(
-      &config,
-      {
-        let queue = queue.clone();
-        let playback_active = playback_active.clone();
-        let gate_until_ms = gate_until_ms.clone();
-        let paused = paused.clone();
-        let ui = ui.clone();
-        let empty_callbacks = empty_callbacks.clone();
-        move |out: &mut [f32], _| {
-          let vol = *volume_for_stream.lock().unwrap();
-          if vol == 0.0 {
-            // Restore volume to default before returning
-            *volume_for_stream.lock().unwrap() = 1.0;
-            queue.lock().unwrap().clear();
-            playback_active.store(false, Ordering::Relaxed);
-            ui.playing.store(false, Ordering::Relaxed);
-            gate_until_ms.store(
-              crate::util::now_ms(start_instant).saturating_add(hangover_ms),
-              Ordering::Relaxed,
-            );
-            return;
-          }
-          let mut q = queue.lock().unwrap();
+  // stream errors end up in the playback loop, which rebuilds the stream
+  let (tx_stream_err, rx_stream_err) = crossbeam_channel::bounded::<cpal::StreamError>(1);
+  let err_fn = move |e| {
+    let _ = tx_stream_err.try_send(e);
+  };
 
-          // Spacebar pause: output silence but do NOT consume queued samples.
-          if paused.load(Ordering::Relaxed) {
-            for s in out.iter_mut() {
-              *s = 0.0;
-            }
-            // Keep "playing" state if we still have audio queued.
-            if !q.is_empty() {
-              playback_active.store(true, Ordering::Relaxed);
-              ui.playing.store(true, Ordering::Relaxed);
-              empty_callbacks.store(0, Ordering::Relaxed);
-            }
-            return;
-          }
+  // the stream for a config, with the device default buffer as a fallback
+  // when the asked one is refused
+  let build_stream =
+    |config: &cpal::StreamConfig| -> Result<cpal::Stream, crate::engine::BoxError> {
+      Ok(match sample_format {
+        SampleFormat::F32 => device.build_output_stream(
+          config,
+          {
+            let queue = queue.clone();
+            let playback_active = playback_active.clone();
+            let gate_until_ms = gate_until_ms.clone();
+            let paused = paused.clone();
+            let empty_callbacks = empty_callbacks.clone();
+            let volume_for_stream = volume_for_stream.clone();
+            let stream_config = config.clone();
+            let mut fade = PauseFade::new(config.sample_rate.0, config.channels);
+            move |out: &mut [f32], _| {
+              crate::audio::promote_audio_thread(&stream_config);
+              let mut vol = *volume_for_stream
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+              if vol == 0.0 && play_until_cut(&queue, word_gap) {
+                vol = 1.0;
+                *volume_for_stream
+                  .lock()
+                  .unwrap_or_else(PoisonError::into_inner) = vol;
+              }
+              if vol == 0.0 {
+                // Restore volume to default before returning
+                *volume_for_stream
+                  .lock()
+                  .unwrap_or_else(PoisonError::into_inner) = 1.0;
+                queue.lock().unwrap_or_else(PoisonError::into_inner).clear();
+                track_progress(0, 0);
+                playback_active.store(false, Ordering::Relaxed);
+                gate_until_ms.store(
+                  crate::util::now_ms(start_instant).saturating_add(hangover_ms),
+                  Ordering::Relaxed,
+                );
+                return;
+              }
+              let mut q = queue.lock().unwrap_or_else(PoisonError::into_inner);
+              let vol = vol * level();
 
-          let mut any_real = false;
-          for s in out.iter_mut() {
-            if let Some(v) = q.pop_front() {
-              *s = v.clamp(-1.0, 1.0) * vol;
-              any_real = true;
-            } else {
-              *s = 0.0;
-            }
-          }
-          if any_real {
-            empty_callbacks.store(0, Ordering::Relaxed);
-          } else {
-            let n = empty_callbacks.fetch_add(1, Ordering::Relaxed) + 1;
-            if n >= 1 {
-              playback_active.store(false, Ordering::Relaxed);
-              ui.playing.store(false, Ordering::Relaxed);
-              gate_until_ms.store(
-                crate::util::now_ms(start_instant).saturating_add(hangover_ms),
-                Ordering::Relaxed,
-              );
-            }
-          }
-        }
-      },
-      err_fn,
-      None,
-    )?,
-    SampleFormat::I16 => device.build_output_stream(
-      &config,
-      {
-        let queue = queue.clone();
-        let playback_active = playback_active.clone();
-        let gate_until_ms = gate_until_ms.clone();
-        let paused = paused.clone();
-        let ui = ui.clone();
-        let empty_callbacks = empty_callbacks.clone();
-        move |out: &mut [i16], _| {
-          let vol = *volume_for_stream.lock().unwrap();
-          if vol == 0.0 {
-            queue.lock().unwrap().clear();
-            playback_active.store(false, Ordering::Relaxed);
-            ui.playing.store(false, Ordering::Relaxed);
-            gate_until_ms.store(
-              crate::util::now_ms(start_instant).saturating_add(hangover_ms),
-              Ordering::Relaxed,
-            );
-
-            // ✅ FIX: silence
-            for s in out.iter_mut() {
-              *s = 0;
-            }
-            return;
-          }
-          let mut q = queue.lock().unwrap();
+              // Spacebar pause: once faded out, output silence but do NOT
+              // consume queued samples.
+              let pausing = paused.load(Ordering::Relaxed);
+              if pausing && fade.is_silent() {
+                for s in out.iter_mut() {
+                  *s = 0.0;
+                }
+                mix_cues(out);
+                // Keep "playing" state if we still have audio queued.
+                if !q.is_empty() {
+                  playback_active.store(true, Ordering::Relaxed);
+                  empty_callbacks.store(0, Ordering::Relaxed);
+                }
+                return;
+              }
 
-          if paused.load(Ordering::Relaxed) {
-            for s in out.iter_mut() {
-              *s = 0;
-            }
-            if !q.is_empty() {
-              playback_active.store(true, Ordering::Relaxed);
-              ui.playing.store(true, Ordering::Relaxed);
-              empty_callbacks.store(0, Ordering::Relaxed);
+              let take = fade.samples(pausing, out.len());
+              let n = q.pop_into(&mut out[..take], |v| {
+                fade
+                  .apply(v.clamp(-1.0, 1.0) * vol, pausing)
+                  .clamp(-1.0, 1.0)
+              });
+              fade.played(pausing, n < take);
+              track_progress(n, q.len());
+              out[n..].fill(0.0);
+              mix_cues(out);
+              let any_real = n > 0;
+              if any_real {
+                empty_callbacks.store(0, Ordering::Relaxed);
+              } else {
+                let n = empty_callbacks.fetch_add(1, Ordering::Relaxed) + 1;
+                if n >= 1 {
+                  playback_active.store(false, Ordering::Relaxed);
+                  gate_until_ms.store(
+                    crate::util::now_ms(start_instant).saturating_add(hangover_ms),
+                    Ordering::Relaxed,
+                  );
+                }
+              }
             }
-            return;
-          }
+          },
+          err_fn.clone(),
+          None,
+        )?,
+        SampleFormat::I16 => device.build_output_stream(
+          config,
+          {
+            let queue = queue.clone();
+            let playback_active = playback_active.clone();
+            let gate_until_ms = gate_until_ms.clone();
+            let paused = paused.clone();
+            let empty_callbacks = empty_callbacks.clone();
+            let volume_for_stream = volume_for_stream.clone();
+            let stream_config = config.clone();
+            let mut fade = PauseFade::new(config.sample_rate.0, config.channels);
+            move |out: &mut [i16], _| {
+              crate::audio::promote_audio_thread(&stream_config);
+              let mut vol = *volume_for_stream
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+              if vol == 0.0 && play_until_cut(&queue, word_gap) {
+                vol = 1.0;
+                *volume_for_stream
+                  .lock()
+                  .unwrap_or_else(PoisonError::into_inner) = vol;
+              }
+              if vol == 0.0 {
+                queue.lock().unwrap_or_else(PoisonError::into_inner).clear();
+                track_progress(0, 0);
+                playback_active.store(false, Ordering::Relaxed);
+                gate_until_ms.store(
+                  crate::util::now_ms(start_instant).saturating_add(hangover_ms),
+                  Ordering::Relaxed,
+                );
 
-          let mut any_real = false;
-          for s in out.iter_mut() {
-            if let Some(v) = q.pop_front() {
-              any_real = true;
-              let v = v.clamp(-1.0, 1.0);
-              *s = ((v * vol).clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
-            } else {
-              *s = 0;
-            }
-          }
+                // ✅ FIX: silence
+                for s in out.iter_mut() {
+                  *s = 0;
+                }
+                return;
+              }
+              let mut q = queue.lock().unwrap_or_else(PoisonError::into_inner);
+              let vol = vol * level();
 
-          if any_real {
-            empty_callbacks.store(0, Ordering::Relaxed);
-          } else {
-            let n = empty_callbacks.fetch_add(1, Ordering::Relaxed) + 1;
-            if n >= 1 {
-              playback_active.store(false, Ordering::Relaxed);
-              ui.playing.store(false, Ordering::Relaxed);
-              gate_until_ms.store(
-                crate::util::now_ms(start_instant).saturating_add(hangover_ms),
-                Ordering::Relaxed,
-              );
-            }
-          }
-        }
-      },
-      err_fn,
-      None,
-    )?,
-    SampleFormat::U16 => device.build_output_stream(
-      &config,
-      {
-        let queue = queue.clone();
-        let playback_active = playback_active.clone();
-        let gate_until_ms = gate_until_ms.clone();
-        let paused = paused.clone();
-        let ui = ui.clone();
-        let empty_callbacks = empty_callbacks.clone();
-        move |out: &mut [u16], _| {
-          let vol = *volume_for_stream.lock().unwrap();
-          if vol == 0.0 {
-            queue.lock().unwrap().clear();
-            playback_active.store(false, Ordering::Relaxed);
-            ui.playing.store(false, Ordering::Relaxed);
-            gate_until_ms.store(
-              crate::util::now_ms(start_instant).saturating_add(hangover_ms),
-              Ordering::Relaxed,
-            );
-
-            // ✅ FIX: silence for unsigned (midpoint)
-            for s in out.iter_mut() {
-              *s = u16::MAX / 2;
-            }
-            return;
-          }
-          let mut q = queue.lock().unwrap();
+              let pausing = paused.load(Ordering::Relaxed);
+              if pausing && fade.is_silent() {
+                for s in out.iter_mut() {
+                  *s = 0;
+                }
+                mix_cues(out);
+                if !q.is_empty() {
+                  playback_active.store(true, Ordering::Relaxed);
+                  empty_callbacks.store(0, Ordering::Relaxed);
+                }
+                return;
+              }
 
-          if paused.load(Ordering::Relaxed) {
-            for s in out.iter_mut() {
-              *s = u16::MAX / 2;
-            }
-            if !q.is_empty() {
-              playback_active.store(true, Ordering::Relaxed);
-              ui.playing.store(true, Ordering::Relaxed);
-              empty_callbacks.store(0, Ordering::Relaxed);
-            }
-            return;
-          }
+              let take = fade.samples(pausing, out.len());
+              let n = q.pop_into(&mut out[..take], |v| {
+                let v = fade.apply(v.clamp(-1.0, 1.0) * vol, pausing);
+                (v.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+              });
+              fade.played(pausing, n < take);
+              track_progress(n, q.len());
+              out[n..].fill(0);
+              mix_cues(out);
+              let any_real = n > 0;
 
-          let mut any_real = false;
-          for s in out.iter_mut() {
-            if let Some(v) = q.pop_front() {
-              any_real = true;
-              let v = v.clamp(-1.0, 1.0);
-              let norm = (v + 1.0) * 0.5;
-              *s = ((norm * vol).clamp(-1.0, 1.0) * u16::MAX as f32) as u16;
-            } else {
-              *s = u16::MAX / 2;
+              if any_real {
+                empty_callbacks.store(0, Ordering::Relaxed);
+              } else {
+                let n = empty_callbacks.fetch_add(1, Ordering::Relaxed) + 1;
+                if n >= 1 {
+                  playback_active.store(false, Ordering::Relaxed);
+                  gate_until_ms.store(
+                    crate::util::now_ms(start_instant).saturating_add(hangover_ms),
+                    Ordering::Relaxed,
+                  );
+                }
+              }
             }
-          }
+          },
+          err_fn.clone(),
+          None,
+        )?,
+        SampleFormat::U16 => device.build_output_stream(
+          config,
+          {
+            let queue = queue.clone();
+            let playback_active = playback_active.clone();
+            let gate_until_ms = gate_until_ms.clone();
+            let paused = paused.clone();
+            let empty_callbacks = empty_callbacks.clone();
+            let volume_for_stream = volume_for_stream.clone();
+            let stream_config = config.clone();
+            let mut fade = PauseFade::new(config.sample_rate.0, config.channels);
+            move |out: &mut [u16], _| {
+              crate::audio::promote_audio_thread(&stream_config);
+              let mut vol = *volume_for_stream
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+              if vol == 0.0 && play_until_cut(&queue, word_gap) {
+                vol = 1.0;
+                *volume_for_stream
+                  .lock()
+                  .unwrap_or_else(PoisonError::into_inner) = vol;
+              }
+              if vol == 0.0 {
+                queue.lock().unwrap_or_else(PoisonError::into_inner).clear();
+                track_progress(0, 0);
+                playback_active.store(false, Ordering::Relaxed);
+                gate_until_ms.store(
+                  crate::util::now_ms(start_instant).saturating_add(hangover_ms),
+                  Ordering::Relaxed,
+                );
+
+                // ✅ FIX: silence for unsigned (midpoint)
+                for s in out.iter_mut() {
+                  *s = u16::MAX / 2;
+                }
+                return;
+              }
+              let mut q = queue.lock().unwrap_or_else(PoisonError::into_inner);
+              let vol = vol * level();
+
+              let pausing = paused.load(Ordering::Relaxed);
+              if pausing && fade.is_silent() {
+                for s in out.iter_mut() {
+                  *s = u16::MAX / 2;
+                }
+                mix_cues(out);
+                if !q.is_empty() {
+                  playback_active.store(true, Ordering::Relaxed);
+                  empty_callbacks.store(0, Ordering::Relaxed);
+                }
+                return;
+              }
 
-          if any_real {
-            empty_callbacks.store(0, Ordering::Relaxed);
-          } else {
-            let n = empty_callbacks.fetch_add(1, Ordering::Relaxed) + 1;
-            if n >= 1 {
-              playback_active.store(false, Ordering::Relaxed);
-              ui.playing.store(false, Ordering::Relaxed);
-              gate_until_ms.store(
-                crate::util::now_ms(start_instant).saturating_add(hangover_ms),
-                Ordering::Relaxed,
-              );
+              let take = fade.samples(pausing, out.len());
+              // the volume scales the signal around the midpoint, not the offset
+              let n = q.pop_into(&mut out[..take], |v| {
+                let v = fade.apply(v.clamp(-1.0, 1.0) * vol, pausing);
+                ((v.clamp(-1.0, 1.0) + 1.0) * 0.5 * u16::MAX as f32) as u16
+              });
+              fade.played(pausing, n < take);
+              track_progress(n, q.len());
+              out[n..].fill(u16::MAX / 2);
+              mix_cues(out);
+              let any_real = n > 0;
+
+              if any_real {
+                empty_callbacks.store(0, Ordering::Relaxed);
+              } else {
+                let n = empty_callbacks.fetch_add(1, Ordering::Relaxed) + 1;
+                if n >= 1 {
+                  playback_active.store(false, Ordering::Relaxed);
+                  gate_until_ms.store(
+                    crate::util::now_ms(start_instant).saturating_add(hangover_ms),
+                    Ordering::Relaxed,
+                  );
+                }
+              }
             }
-          }
-        }
-      },
-      err_fn,
-      None,
-    )?,
-    other => return Err(format!("unsupported output format: {other:?}").into()),
+          },
+          err_fn.clone(),
+          None,
+        )?,
+        other => return Err(format!("unsupported output format: {other:?}").into()),
+      })
+    };
+  let open_stream = || match build_stream(&config) {
+    Err(e) if matches!(config.buffer_size, cpal::BufferSize::Fixed(_)) => {
+      crate::log::log(
+        "warning",
+        &format!(
+          "Output buffer of {:?} refused ({}), using the device default",
+          config.buffer_size, e
+        ),
+      );
+      build_stream(&cpal::StreamConfig {
+        buffer_size: cpal::BufferSize::Default,
+        ..config.clone()
+      })
+    }
+    result => result,
   };
+  let mut stream = open_stream()?;
 
   loop {
     stream.play()?;
     // Reset state before each stream
-    *volume.lock().unwrap() = 1.0;
-    queue.lock().unwrap().clear();
+    *volume.lock().unwrap_or_else(PoisonError::into_inner) = 1.0;
+    queue.lock().unwrap_or_else(PoisonError::into_inner).clear();
     empty_callbacks.store(0, Ordering::Relaxed);
     playback_active.store(false, Ordering::Relaxed);
-    ui.playing.store(false, Ordering::Relaxed);
     loop {
       select! {
+        recv(rx_stream_err) -> err => {
+          let Ok(err) = err else { continue };
+          crate::audio::stream_failed("output", &err);
+          drop(stream);
+          stream = loop {
+            thread::sleep(crate::audio::STREAM_RETRY_INTERVAL);
+            match open_stream() {
+              Ok(stream) => break stream,
+              Err(e) => crate::log::log("debug", &format!("Output stream not back yet: {}", e)),
+            }
+          };
+          // stale errors of the broken stream
+          while rx_stream_err.try_recv().is_ok() {}
+          crate::audio::stream_recovered("output");
+          break;
+        }
         recv(stop_play_rx) -> _ => {
           // Drain any pending audio chunks from rx_audio
-          while let Ok(_) = rx_audio.try_recv() {}
+          while let Ok(chunk) = rx_audio.try_recv() {
+            crate::captions::dropped(&chunk);
+          }
+          if let Some(tx) = SECONDARY_TX.get() {
+            let _ = tx.send(Secondary::Clear);
+          }
+          crate::intercom::stopped();
+          // the end of the current word or phrase still plays
+          if play_until_cut(&queue, word_gap) {
+            continue;
+          }
           // Clear queue immediately before stopping
-          queue.lock().unwrap().clear();
+          queue.lock().unwrap_or_else(PoisonError::into_inner).clear();
           // Stop current stream immediately by dropping it; let outer loop recreate
           break;
         }
         recv(rx_audio) -> msg => {
           let Ok(chunk) = msg else { break };
+          if chunk.data.is_empty() {
+            queue.lock().unwrap_or_else(PoisonError::into_inner).end_phrase();
+            if let Some(tx) = SECONDARY_TX.get() {
+              let _ = tx.send(Secondary::PhraseEnd);
+            }
+            crate::intercom::phrase_end();
+            continue;
+          }
+          // the data that will actually be played
+          let data = to_output(&chunk, out_channels, config.sample_rate.0);
           // Forward to wav writer if set
           if let Some(tx) = WAV_TX.get() {
-            // Determine data that will actually be played
-            let mut out_data = if chunk.channels != out_channels {
-              convert_channels(&chunk.data, chunk.channels, out_channels)
-            } else {
-              chunk.data.clone()
-            };
-            if chunk.sample_rate != config.sample_rate.0 {
-              let resampled = crate::audio::resample_to(&out_data, out_channels, chunk.sample_rate, config.sample_rate.0);
-              out_data = resampled;
-            }
             let writer_chunk = crate::audio::AudioChunk {
-              data: out_data,
+              data: data.clone(),
               channels: out_channels,
               sample_rate: config.sample_rate.0,
             };
             tx.send(writer_chunk).unwrap_or(());
           }
+          crate::captions::played(&chunk);
+          crate::intercom::played(&chunk);
+          if let Some(tx) = SECONDARY_TX.get() {
+            let _ = tx.send(Secondary::Audio(chunk.clone()));
+          }
           let channels = out_channels as usize;
           let max_samples = crate::tts::QUEUE_CAP_FRAMES * channels;
           loop {
-            let q = queue.lock().unwrap();
-            if q.len() + chunk.data.len() <= max_samples {
+            let q = queue.lock().unwrap_or_else(PoisonError::into_inner);
+            if q.len() + data.len() <= max_samples {
               break;
             }
             drop(q);
             thread::sleep(Duration::from_millis(5));
           }
 
-          if GLOBAL_STATE.get().unwrap().processing_response.load(Ordering::Relaxed) || *volume.lock().unwrap() == 0.0 {
-            let mut vol = volume.lock().unwrap();
+          // the reply cut short before this one was muted
+          if *volume.lock().unwrap_or_else(PoisonError::into_inner) == 0.0 {
+            let mut vol = volume.lock().unwrap_or_else(PoisonError::into_inner);
             *vol = 1.0;
-            GLOBAL_STATE.get().unwrap().processing_response.store(false, Ordering::Relaxed);
           }
-          let mut q = queue.lock().unwrap();
-          let data = if chunk.channels != out_channels {
-            convert_channels(&chunk.data, chunk.channels, out_channels)
-          } else {
-            chunk.data.clone()
-          };
-          if chunk.sample_rate != config.sample_rate.0 {
-            let resampled = crate::audio::resample_to(&data, out_channels, chunk.sample_rate, config.sample_rate.0);
-            for s in resampled { q.push_back(s); }
-          } else {
-            for s in data { q.push_back(s); }
+          // the voice of the reply starts
+          if crate::state::advance_turn(&[TurnState::Generating], TurnState::Speaking) {
+            PLAYED.store(0, Ordering::Relaxed);
           }
+          crate::latency::audio_queued();
+          let mut q = queue.lock().unwrap_or_else(PoisonError::into_inner);
+          q.push(data);
+          track_progress(0, q.len());
           empty_callbacks.store(0, Ordering::Relaxed);
           playback_active.store(true, Ordering::Relaxed);
-          ui.playing.store(true, Ordering::Relaxed);
+        }
+      }
+    }
+  }
+}
+
+/// Plays whatever the main output plays on a second device as well
+/// (--secondary-output), converted to that device's format. It follows the
+/// interruptions, pauses and volume of the main output. Blocks.
+pub fn secondary_output_thread(
+  device: cpal::Device,
+  volume: Arc<Mutex<f32>>,
+  paused: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  use cpal::SampleFormat;
+
+  let supported = device.default_output_config()?;
+  let config: cpal::StreamConfig = supported.clone().into();
+  let (tx, rx) = crossbeam_channel::unbounded::<Secondary>();
+  SECONDARY_TX
+    .set(tx)
+    .map_err(|_| "the secondary output is already running")?;
+  let queue: Arc<Mutex<PlaybackQueue>> = Arc::new(Mutex::new(PlaybackQueue::default()));
+  let word_gap = word_gap_samples(config.sample_rate.0, config.channels);
+
+  let (tx_stream_err, rx_stream_err) = crossbeam_channel::bounded::<cpal::StreamError>(1);
+  let open_stream = || -> Result<cpal::Stream, crate::engine::BoxError> {
+    let tx_stream_err = tx_stream_err.clone();
+    let err_fn = move |e| {
+      let _ = tx_stream_err.try_send(e);
+    };
+    let (queue, volume, paused) = (queue.clone(), volume.clone(), paused.clone());
+    let stream = match supported.sample_format() {
+      SampleFormat::F32 => {
+        build_secondary_stream::<f32>(&device, &config, queue, volume, paused, word_gap, err_fn)?
+      }
+      SampleFormat::I16 => {
+        build_secondary_stream::<i16>(&device, &config, queue, volume, paused, word_gap, err_fn)?
+      }
+      SampleFormat::U16 => {
+        build_secondary_stream::<u16>(&device, &config, queue, volume, paused, word_gap, err_fn)?
+      }
+      other => return Err(format!("unsupported output format: {other:?}").into()),
+    };
+    stream.play()?;
+    Ok(stream)
+  };
+  let mut _stream = open_stream()?;
+  crate::log::log(
+    "info",
+    &format!(
+      "Secondary output: {} ({} ch @ {} Hz)",
+      device.name().unwrap_or("<unknown>".into()),
+      config.channels,
+      config.sample_rate.0
+    ),
+  );
+
+  // more than this queued means the device runs behind the main one
+  let max_samples = 2 * crate::tts::QUEUE_CAP_FRAMES * config.channels as usize;
+  loop {
+    select! {
+      recv(rx_stream_err) -> err => {
+        let Ok(err) = err else { continue };
+        // unlike the main output, the conversation goes on meanwhile
+        crate::log::log("warning", &format!("Secondary output stream failed: {}", err));
+        drop(_stream);
+        _stream = loop {
+          thread::sleep(crate::audio::STREAM_RETRY_INTERVAL);
+          match open_stream() {
+            Ok(stream) => break stream,
+            Err(e) => crate::log::log("debug", &format!("Secondary output not back yet: {}", e)),
+          }
+        };
+        while rx_stream_err.try_recv().is_ok() {}
+        queue.lock().unwrap_or_else(PoisonError::into_inner).clear();
+      }
+      recv(rx) -> msg => {
+        let Ok(msg) = msg else { return Ok(()) };
+        let mut q = queue.lock().unwrap_or_else(PoisonError::into_inner);
+        match msg {
+          Secondary::Audio(chunk) => {
+            let data = to_output(&chunk, config.channels, config.sample_rate.0);
+            // skip ahead rather than drift further behind
+            if q.len() + data.len() > max_samples {
+              q.clear();
+            }
+            q.push(data);
+          }
+          Secondary::PhraseEnd => q.end_phrase(),
+          Secondary::Clear => q.cut(granularity(), word_gap),
         }
       }
     }
@@ -342,6 +645,352 @@ pub fn playback_thread(
 // PRIVATE
 // ------------------------------------------------------------------
 
+fn level() -> f32 {
+  f32::from_bits(LEVEL.load(Ordering::Relaxed))
+}
+
+// The progress of the reply after an output callback played `played` more
+// samples, `queued` left
+fn track_progress(played: usize, queued: usize) {
+  PLAYED.fetch_add(played as u64, Ordering::Relaxed);
+  QUEUED.store(queued as u64, Ordering::Relaxed);
+}
+
+struct Cues {
+  queue: Mutex<PlaybackQueue>,
+  channels: u16,
+  sample_rate: u32,
+}
+
+// Adds the sounds waiting in CUES to the samples an output callback wrote.
+fn mix_cues<T>(out: &mut [T])
+where
+  T: cpal::Sample + cpal::FromSample<f32>,
+  f32: cpal::FromSample<T>,
+{
+  let Some(cues) = CUES.get() else {
+    return;
+  };
+  let mut q = cues.queue.lock().unwrap_or_else(PoisonError::into_inner);
+  if !q.is_empty() {
+    q.pop_with(out, |o, v| {
+      *o = T::from_sample((o.to_sample::<f32>() + v).clamp(-1.0, 1.0));
+    });
+  }
+}
+
+// what the playback sends the secondary output
+enum Secondary {
+  Audio(crate::audio::AudioChunk),
+  PhraseEnd,
+  // the playback was stopped
+  Clear,
+}
+
+fn build_secondary_stream<T>(
+  device: &cpal::Device,
+  config: &cpal::StreamConfig,
+  queue: Arc<Mutex<PlaybackQueue>>,
+  volume: Arc<Mutex<f32>>,
+  paused: Arc<AtomicBool>,
+  word_gap: usize,
+  err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+  T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+  let stream_config = config.clone();
+  let mut fade = PauseFade::new(config.sample_rate.0, config.channels);
+  device.build_output_stream(
+    config,
+    move |out: &mut [T], _| {
+      crate::audio::promote_audio_thread(&stream_config);
+      let vol = *volume.lock().unwrap_or_else(PoisonError::into_inner);
+      let mut q = queue.lock().unwrap_or_else(PoisonError::into_inner);
+      // muted by an interruption: what is queued past the cut is never played
+      let vol = if vol == 0.0 {
+        q.cut(granularity(), word_gap);
+        1.0
+      } else {
+        vol
+      } * level();
+      let pausing = paused.load(Ordering::Relaxed);
+      let take = fade.samples(pausing, out.len());
+      let written = q.pop_into(&mut out[..take], |v| {
+        T::from_sample(fade.apply(v * vol, pausing).clamp(-1.0, 1.0))
+      });
+      fade.played(pausing, written < take);
+      out[written..].fill(T::EQUILIBRIUM);
+    },
+    err_fn,
+    None,
+  )
+}
+
+// The gain of an output around a pause (space): it goes down to silence a
+// frame at a time once paused, then back up on resume.
+struct PauseFade {
+  gain: f32,
+  // per frame
+  step: f32,
+  channels: usize,
+  // samples of the current frame scaled so far
+  sample: usize,
+}
+
+impl PauseFade {
+  fn new(sample_rate: u32, channels: u16) -> Self {
+    Self {
+      gain: 1.0,
+      step: 1000.0 / (PAUSE_FADE_MS * sample_rate.max(1) as f32),
+      channels: channels.max(1) as usize,
+      sample: 0,
+    }
+  }
+
+  // faded out, the queue waits for the resume
+  fn is_silent(&self) -> bool {
+    self.gain <= 0.0
+  }
+
+  // Samples of a callback of `len` to take from the queue: all of them, or
+  // while pausing those the fade out still lasts.
+  fn samples(&self, pausing: bool, len: usize) -> usize {
+    if !pausing {
+      return len;
+    }
+    let frames = (self.gain / self.step).ceil() as usize;
+    (frames * self.channels).min(len)
+  }
+
+  // The sample `v` at the gain of its frame.
+  fn apply(&mut self, v: f32, pausing: bool) -> f32 {
+    let out = v * self.gain;
+    self.sample += 1;
+    if self.sample == self.channels {
+      self.sample = 0;
+      self.gain = if pausing {
+        (self.gain - self.step).max(0.0)
+      } else {
+        (self.gain + self.step).min(1.0)
+      };
+    }
+    out
+  }
+
+  // After a callback: the queue ran dry while pausing, nothing is left to
+  // fade out.
+  fn played(&mut self, pausing: bool, ran_dry: bool) {
+    if pausing && ran_dry {
+      self.gain = 0.0;
+      self.sample = 0;
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Granularity {
+  Instant,
+  Word,
+  Phrase,
+}
+
+fn granularity() -> Granularity {
+  match GRANULARITY.load(Ordering::Relaxed) {
+    g if g == Granularity::Word as u8 => Granularity::Word,
+    g if g == Granularity::Phrase as u8 => Granularity::Phrase,
+    _ => Granularity::Instant,
+  }
+}
+
+fn word_gap_samples(sample_rate: u32, channels: u16) -> usize {
+  (sample_rate as u64 * WORD_GAP_MS / 1000) as usize * channels as usize
+}
+
+// On an interruption, cuts the queue at the --interrupt-granularity edge:
+// true when there is still audio to play up to it, false when the playback
+// stops right away.
+fn play_until_cut(queue: &Mutex<PlaybackQueue>, word_gap: usize) -> bool {
+  let granularity = granularity();
+  if granularity == Granularity::Instant {
+    return false;
+  }
+  let mut q = queue.lock().unwrap_or_else(PoisonError::into_inner);
+  q.cut(granularity, word_gap);
+  !q.is_empty()
+}
+
+// Audio waiting for the output device, kept as the chunks it arrived in and
+// read with slice copies. The chunks ending a phrase are marked, so an
+// interruption can let the playback go on up to there.
+#[derive(Default)]
+struct PlaybackQueue {
+  chunks: VecDeque<Queued>,
+  // samples of the front chunk already played
+  pos: usize,
+  // samples queued
+  len: usize,
+}
+
+struct Queued {
+  data: Arc<[f32]>,
+  // samples of `data` to play, less than all of it once cut
+  end: usize,
+  phrase_end: bool,
+}
+
+impl PlaybackQueue {
+  fn push(&mut self, data: Arc<[f32]>) {
+    if !data.is_empty() {
+      self.len += data.len();
+      self.chunks.push_back(Queued {
+        end: data.len(),
+        data,
+        phrase_end: false,
+      });
+    }
+  }
+
+  // the last chunk pushed ends a phrase
+  fn end_phrase(&mut self) {
+    if let Some(last) = self.chunks.back_mut() {
+      last.phrase_end = true;
+    }
+  }
+
+  fn len(&self) -> usize {
+    self.len
+  }
+
+  fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  fn clear(&mut self) {
+    self.chunks.clear();
+    self.pos = 0;
+    self.len = 0;
+  }
+
+  // Drops what is queued past the end of the current phrase, or of the
+  // current word: the first `word_gap` quiet samples in a row before it.
+  // Cutting again at the same granularity keeps the same end.
+  fn cut(&mut self, granularity: Granularity, word_gap: usize) {
+    let keep = match granularity {
+      Granularity::Instant => 0,
+      Granularity::Phrase => self.phrase_len(),
+      Granularity::Word => self.word_len(word_gap),
+    };
+    self.truncate(keep);
+  }
+
+  // samples queued up to the end of the current phrase, all when its end
+  // isn't queued yet
+  fn phrase_len(&self) -> usize {
+    let mut len = 0;
+    for (i, chunk) in self.chunks.iter().enumerate() {
+      len += chunk.end - if i == 0 { self.pos } else { 0 };
+      if chunk.phrase_end {
+        break;
+      }
+    }
+    len
+  }
+
+  fn word_len(&self, word_gap: usize) -> usize {
+    let mut len = 0;
+    let mut quiet = 0;
+    for (i, chunk) in self.chunks.iter().enumerate() {
+      let from = if i == 0 { self.pos } else { 0 };
+      for &v in &chunk.data[from..chunk.end] {
+        len += 1;
+        if v.abs() < WORD_GAP_LEVEL {
+          quiet += 1;
+          if quiet >= word_gap {
+            return len - quiet;
+          }
+        } else {
+          quiet = 0;
+        }
+      }
+      if chunk.phrase_end {
+        break;
+      }
+    }
+    len
+  }
+
+  // Keeps the next `keep` samples, the last of them now ending a phrase.
+  fn truncate(&mut self, keep: usize) {
+    if keep == 0 {
+      self.clear();
+      return;
+    }
+    let mut len = 0;
+    let mut kept = 0;
+    for (i, chunk) in self.chunks.iter_mut().enumerate() {
+      let from = if i == 0 { self.pos } else { 0 };
+      kept += 1;
+      if len + chunk.end - from >= keep {
+        chunk.end = from + keep - len;
+        chunk.phrase_end = true;
+        break;
+      }
+      len += chunk.end - from;
+    }
+    self.chunks.truncate(kept);
+    self.len = self.len.min(keep);
+  }
+
+  // Fills `out` from the front of the queue converting every sample with
+  // `f`, returns how many were written (less than `out` when it runs dry).
+  fn pop_into<T>(&mut self, out: &mut [T], mut f: impl FnMut(f32) -> T) -> usize {
+    self.pop_with(out, |o, v| *o = f(v))
+  }
+
+  // Like `pop_into`, `f` combining each sample with what `out` holds.
+  fn pop_with<T>(&mut self, out: &mut [T], mut f: impl FnMut(&mut T, f32)) -> usize {
+    let mut written = 0;
+    while written < out.len() {
+      let Some(front) = self.chunks.front() else {
+        break;
+      };
+      let src = &front.data[self.pos..front.end];
+      let n = src.len().min(out.len() - written);
+      for (o, &v) in out[written..written + n].iter_mut().zip(&src[..n]) {
+        f(o, v);
+      }
+      written += n;
+      self.pos += n;
+      if self.pos == front.end {
+        self.chunks.pop_front();
+        self.pos = 0;
+      }
+    }
+    self.len -= written;
+    written
+  }
+}
+
+// The chunk as the output device plays it: shared as is when its format
+// already matches, converted otherwise.
+fn to_output(
+  chunk: &crate::audio::AudioChunk,
+  out_channels: u16,
+  out_sample_rate: u32,
+) -> Arc<[f32]> {
+  let data = if chunk.channels == out_channels {
+    Cow::Borrowed(&chunk.data[..])
+  } else {
+    Cow::Owned(convert_channels(&chunk.data, chunk.channels, out_channels))
+  };
+  if chunk.sample_rate == out_sample_rate {
+    Arc::from(data)
+  } else {
+    crate::audio::resample_to(&data, out_channels, chunk.sample_rate, out_sample_rate).into()
+  }
+}
+
 fn convert_channels(input: &[f32], in_channels: u16, out_channels: u16) -> Vec<f32> {
   if in_channels == out_channels {
     return input.to_vec();
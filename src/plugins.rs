@@ -0,0 +1,249 @@
+// ------------------------------------------------------------------
+//  Plugins (external programs speaking JSON lines)
+// ------------------------------------------------------------------
+//
+// Every executable in the plugins directory is started once and kept
+// running. vtmate writes one JSON request per line to its stdin and reads
+// one JSON reply per line from its stdout:
+//
+//   {"type":"describe"}
+//     -> {"name":"weather","tools":[{"name","description","parameters"}],
+//         "filters":["transcript","response"]}
+//   {"type":"tool","name":"get_weather","arguments":{"city":"Oslo"}}
+//     -> {"result":"12 degrees and rain"}   (or {"error":"..."})
+//   {"type":"transcript","text":"..."}      what the user said
+//     -> {"text":"..."}                     (empty to drop the utterance)
+//   {"type":"response","text":"..."}        a phrase about to be spoken
+//     -> {"text":"..."}
+//
+// Tools are offered to the llm (chat endpoints with tool calling) and
+// `parameters` is a JSON schema, as in the OpenAI tools format.
+
+use crossbeam_channel::{Receiver, unbounded};
+use serde_json::{Value, json};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::PoisonError;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+// how long a plugin gets to answer before it's skipped
+const PLUGIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+static PLUGINS: OnceLock<Vec<Plugin>> = OnceLock::new();
+
+// API
+// ------------------------------------------------------------------
+
+/// The plugins directory: the given one, or ~/.vtmate/plugins.
+pub fn plugins_dir(configured: Option<&str>) -> Option<PathBuf> {
+  match configured.filter(|d| !d.is_empty()) {
+    Some(dir) => Some(PathBuf::from(dir)),
+    None => crate::util::get_user_home_path().map(|h| h.join(".vtmate").join("plugins")),
+  }
+}
+
+/// Starts the plugins found in `dir` (once). Returns their names.
+pub fn load(dir: &Path) -> Vec<String> {
+  let plugins = PLUGINS.get_or_init(|| {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+      return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+      .filter_map(|e| e.ok().map(|e| e.path()))
+      .filter(|p| is_executable(p))
+      .collect();
+    paths.sort();
+    paths
+      .iter()
+      .filter_map(|path| match Plugin::start(path) {
+        Ok(plugin) => Some(plugin),
+        Err(e) => {
+          crate::log::log(
+            "warning",
+            &format!("Plugin {} not loaded: {}", path.display(), e),
+          );
+          None
+        }
+      })
+      .collect()
+  });
+  plugins.iter().map(|p| p.name.clone()).collect()
+}
+
+/// Tools of all plugins, in the chat completions `tools` format.
+pub fn tool_specs() -> Vec<Value> {
+  loaded()
+    .iter()
+    .flat_map(|p| p.tools.iter())
+    .map(|tool| {
+      json!({
+        "type": "function",
+        "function": {
+          "name": tool["name"],
+          "description": tool["description"],
+          "parameters": tool.get("parameters").cloned().unwrap_or(json!({ "type": "object", "properties": {} })),
+        },
+      })
+    })
+    .collect()
+}
+
+/// Runs a tool, returning what to hand back to the llm.
+pub fn call_tool(name: &str, arguments: &Value) -> String {
+  let Some(plugin) = loaded()
+    .iter()
+    .find(|p| p.tools.iter().any(|t| t["name"] == name))
+  else {
+    return format!("error: unknown tool '{}'", name);
+  };
+  crate::log::log("info", &format!("Tool call {} {}", name, arguments));
+  match plugin.request(&json!({ "type": "tool", "name": name, "arguments": arguments })) {
+    Ok(reply) => match reply.get("error").and_then(|e| e.as_str()) {
+      Some(e) => format!("error: {}", e),
+      None => match &reply["result"] {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+      },
+    },
+    Err(e) => format!("error: {}", e),
+  }
+}
+
+/// Passes a transcript through the plugins' transcript filters.
+pub fn filter_transcript(text: &str) -> String {
+  filter("transcript", text)
+}
+
+/// Passes a phrase of the reply through the plugins' response filters.
+pub fn filter_response(text: &str) -> String {
+  filter("response", text)
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+struct Plugin {
+  name: String,
+  tools: Vec<Value>,
+  filters: Vec<String>,
+  io: Mutex<PluginIo>,
+}
+
+struct PluginIo {
+  stdin: ChildStdin,
+  lines: Receiver<String>,
+  _child: Child,
+}
+
+impl Plugin {
+  fn start(path: &Path) -> Result<Self, String> {
+    let mut child = Command::new(path)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::null())
+      .spawn()
+      .map_err(|e| e.to_string())?;
+    let stdin = child.stdin.take().ok_or("no stdin")?;
+    let stdout = child.stdout.take().ok_or("no stdout")?;
+    // replies are read on their own thread so a stuck plugin can time out
+    let (tx, lines) = unbounded();
+    std::thread::spawn(move || {
+      for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else { break };
+        if tx.send(line).is_err() {
+          break;
+        }
+      }
+    });
+    let mut plugin = Plugin {
+      name: path
+        .file_stem()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default(),
+      tools: Vec::new(),
+      filters: Vec::new(),
+      io: Mutex::new(PluginIo {
+        stdin,
+        lines,
+        _child: child,
+      }),
+    };
+    let info = plugin.request(&json!({ "type": "describe" }))?;
+    if let Some(name) = info["name"].as_str() {
+      plugin.name = name.to_string();
+    }
+    plugin.tools = info["tools"]
+      .as_array()
+      .map(|tools| {
+        tools
+          .iter()
+          .filter(|t| t["name"].is_string())
+          .cloned()
+          .collect()
+      })
+      .unwrap_or_default();
+    plugin.filters = info["filters"]
+      .as_array()
+      .map(|f| {
+        f.iter()
+          .filter_map(|v| v.as_str().map(String::from))
+          .collect()
+      })
+      .unwrap_or_default();
+    Ok(plugin)
+  }
+
+  fn request(&self, req: &Value) -> Result<Value, String> {
+    let mut io = self.io.lock().unwrap_or_else(PoisonError::into_inner);
+    // drop anything left over from a request that timed out
+    while io.lines.try_recv().is_ok() {}
+    writeln!(io.stdin, "{}", req)
+      .and_then(|_| io.stdin.flush())
+      .map_err(|e| format!("plugin {} is gone ({})", self.name, e))?;
+    let line = io
+      .lines
+      .recv_timeout(PLUGIN_TIMEOUT)
+      .map_err(|_| format!("plugin {} did not answer", self.name))?;
+    serde_json::from_str(&line).map_err(|e| format!("plugin {}: {}", self.name, e))
+  }
+}
+
+fn loaded() -> &'static [Plugin] {
+  PLUGINS.get().map(|p| p.as_slice()).unwrap_or(&[])
+}
+
+fn filter(kind: &str, text: &str) -> String {
+  let mut text = text.to_string();
+  for plugin in loaded()
+    .iter()
+    .filter(|p| p.filters.iter().any(|f| f == kind))
+  {
+    match plugin.request(&json!({ "type": kind, "text": text })) {
+      Ok(reply) => {
+        if let Some(t) = reply["text"].as_str() {
+          text = t.to_string();
+        }
+      }
+      Err(e) => crate::log::log("warning", &e),
+    }
+  }
+  text
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+  use std::os::unix::fs::PermissionsExt;
+  std::fs::metadata(path)
+    .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+    .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+  path.is_file()
+    && path
+      .extension()
+      .is_some_and(|e| e.eq_ignore_ascii_case("exe") || e.eq_ignore_ascii_case("bat"))
+}
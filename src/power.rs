@@ -0,0 +1,90 @@
+// ------------------------------------------------------------------
+//  Low power (--low-power)
+// ------------------------------------------------------------------
+//
+// For laptops on battery: the ui redraws and the polling loops run less
+// often, and once nothing happened for a while (--low-power-idle) the kokoro
+// model is unloaded and the utterances are transcribed with the tiny whisper
+// model. The next activity (an utterance, a key, a reply) loads the models
+// back.
+
+use crate::state::GLOBAL_STATE;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// how much slower the polling loops run, awake and idle
+const SLOW_FACTOR: u32 = 5;
+const IDLE_SLOW_FACTOR: u32 = 20;
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static IDLE: AtomicBool = AtomicBool::new(false);
+static LAST_ACTIVITY: Mutex<Option<Instant>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+pub fn set_enabled(enabled: bool) {
+  ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+  ENABLED.load(Ordering::Relaxed)
+}
+
+/// Whether the models are unloaded for lack of activity.
+pub fn is_idle() -> bool {
+  IDLE.load(Ordering::Relaxed)
+}
+
+/// The wait of a polling loop that waits `normal` otherwise.
+pub fn poll_interval(normal: Duration) -> Duration {
+  if !is_enabled() {
+    normal
+  } else if is_idle() {
+    normal * IDLE_SLOW_FACTOR
+  } else {
+    normal * SLOW_FACTOR
+  }
+}
+
+/// Records some activity, loading the models back when idle.
+pub fn touch() {
+  if !is_enabled() {
+    return;
+  }
+  *LAST_ACTIVITY.lock().unwrap_or_else(PoisonError::into_inner) = Some(Instant::now());
+  if IDLE.swap(false, Ordering::Relaxed) {
+    crate::log::log("info", "Low power: awake, loading the models back");
+    crate::stt::resume();
+    crate::tts::resume_engines();
+  }
+}
+
+/// Unloads the models after `timeout` without activity, a turn in progress
+/// counting as one. Blocks.
+pub fn idle_thread(timeout: Duration) {
+  touch();
+  loop {
+    std::thread::sleep(IDLE_POLL_INTERVAL);
+    let busy = GLOBAL_STATE
+      .get()
+      .is_some_and(|state| state.turn.get().is_busy());
+    if busy {
+      touch();
+      continue;
+    }
+    let since = LAST_ACTIVITY
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .map(|t| t.elapsed());
+    if !is_idle() && since.is_some_and(|since| since >= timeout) {
+      IDLE.store(true, Ordering::Relaxed);
+      crate::log::log("info", "Low power: idle, unloading the models");
+      crate::stt::suspend();
+      crate::tts::suspend_engines();
+    }
+  }
+}
@@ -0,0 +1,133 @@
+// ------------------------------------------------------------------
+//  Punctuation of the transcripts (--punctuate)
+// ------------------------------------------------------------------
+//
+// Whisper often leaves what was said without punctuation or capitals, which
+// reads badly in a saved conversation. With --punctuate every utterance is
+// punctuated in the background by a short call to the agent's llm, used in
+// the saved conversations only: the llm still gets the raw transcript.
+//
+// Only punctuation and casing may change. A transcription whisper was unsure
+// of is kept raw, so it doesn't read more certain than it is, and so is an
+// answer of the llm that changed the words.
+
+use crate::cancel::CancellationToken;
+use crate::conversation::ChatMessage;
+use crate::engine::{LanguageModel, ServerLlm};
+use crate::state::GLOBAL_STATE;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// transcriptions less confident than this are not punctuated
+const MIN_CONFIDENCE: f32 = 0.6;
+const PROMPT: &str = "Add punctuation and capitalization to the transcript of speech the user \
+  sends. Do not add, remove, reorder or change any word, do not answer it. Reply with the \
+  punctuated transcript only.";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// raw transcript -> punctuated
+static PUNCTUATED: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+pub fn set_enabled(enabled: bool) {
+  ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+  ENABLED.load(Ordering::Relaxed)
+}
+
+/// Punctuates the transcript `raw` in the background, when on and whisper's
+/// `confidence` in it is high enough. See `readable`.
+pub fn spawn(raw: &str, confidence: Option<f32>) {
+  if !is_enabled() || confidence.is_some_and(|c| c < MIN_CONFIDENCE) {
+    return;
+  }
+  let raw = raw.to_string();
+  std::thread::spawn(move || match punctuate(&raw) {
+    Ok(Some(text)) => {
+      PUNCTUATED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get_or_insert_with(HashMap::new)
+        .insert(raw, text);
+    }
+    Ok(None) => crate::log::log(
+      "debug",
+      "Punctuation changed the words, kept the transcript",
+    ),
+    Err(e) => crate::log::log("warning", &format!("Failed to punctuate: {}", e)),
+  });
+}
+
+/// The transcript `raw` punctuated, once done, `raw` otherwise.
+pub fn readable(raw: &str) -> Cow<'_, str> {
+  match PUNCTUATED
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+    .and_then(|p| p.get(raw))
+  {
+    Some(text) => Cow::Owned(text.clone()),
+    None => Cow::Borrowed(raw),
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// the punctuated `raw`, None when the llm changed its words
+fn punctuate(raw: &str) -> Result<Option<String>, crate::engine::BoxError> {
+  let state = GLOBAL_STATE.get().ok_or("not started")?;
+  let llm = ServerLlm {
+    provider: state
+      .provider
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone(),
+    baseurl: state
+      .baseurl
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone(),
+    model: state
+      .model
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone(),
+  };
+  let messages = [
+    ChatMessage {
+      role: "system".to_string(),
+      content: PROMPT.to_string(),
+      agent_name: None,
+      meta: None,
+    },
+    ChatMessage {
+      role: "user".to_string(),
+      content: raw.to_string(),
+      agent_name: None,
+      meta: None,
+    },
+  ];
+  let mut reply = String::new();
+  llm.stream_reply(&messages, &CancellationToken::new(), &mut |piece| {
+    reply.push_str(piece)
+  })?;
+  let reply = reply.trim();
+  Ok((words(reply) == words(raw)).then(|| reply.to_string()))
+}
+
+// the words of `text`, without punctuation or casing
+fn words(text: &str) -> Vec<String> {
+  text
+    .split(|c: char| !c.is_alphanumeric() && c != '\'')
+    .map(|w| w.replace('\'', "").to_lowercase())
+    .filter(|w| !w.is_empty())
+    .collect()
+}
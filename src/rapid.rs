@@ -0,0 +1,127 @@
+// ------------------------------------------------------------------
+//  Rapid mode (r key, "rapid mode", --rapid-silence, --rapid-idle)
+// ------------------------------------------------------------------
+//
+// For quick back-and-forth exchanges: while on, the wake phrases are not
+// needed and an utterance ends after a shorter silence than the
+// end_silence_ms of the agent (--rapid-silence). It turns itself off once
+// nothing was said or replied for a while (--rapid-idle), back to the
+// settings of the agent.
+
+use crate::state::GLOBAL_STATE;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+const ON_PHRASES: [&str; 5] = [
+  "rapid mode",
+  "rapid mode on",
+  "start rapid mode",
+  "conversation mode",
+  "start conversation mode",
+];
+const OFF_PHRASES: [&str; 5] = [
+  "rapid mode off",
+  "stop rapid mode",
+  "end rapid mode",
+  "stop conversation mode",
+  "normal mode",
+];
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static SILENCE_MS: AtomicU64 = AtomicU64::new(crate::config::RAPID_SILENCE_MS_DEFAULT);
+static IDLE_SECS: AtomicU64 = AtomicU64::new(crate::config::RAPID_IDLE_SECS_DEFAULT);
+static LAST_ACTIVITY: Mutex<Option<Instant>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+/// The silence ending an utterance (--rapid-silence) and the inactivity
+/// turning it off (--rapid-idle) while on.
+pub fn init(silence_ms: u64, idle: Duration) {
+  SILENCE_MS.store(silence_ms, Ordering::Relaxed);
+  IDLE_SECS.store(idle.as_secs(), Ordering::Relaxed);
+}
+
+/// Whether it's on, turning it off when idle for too long.
+pub fn is_active() -> bool {
+  if !ACTIVE.load(Ordering::Relaxed) {
+    return false;
+  }
+  if busy() {
+    touch();
+    return true;
+  }
+  let idle = Duration::from_secs(IDLE_SECS.load(Ordering::Relaxed));
+  let since = LAST_ACTIVITY
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .map(|t| t.elapsed());
+  if since.is_some_and(|since| since >= idle) {
+    crate::log::log("info", "Rapid mode off: idle");
+    set_active(false);
+    return false;
+  }
+  true
+}
+
+pub fn set_active(active: bool) {
+  if active {
+    touch();
+  }
+  if ACTIVE.swap(active, Ordering::Relaxed) != active {
+    crate::log::log(
+      "info",
+      &format!("Rapid mode {}", if active { "on" } else { "off" }),
+    );
+  }
+}
+
+/// Flips rapid mode, returns whether it's now on.
+pub fn toggle() -> bool {
+  let active = !is_active();
+  set_active(active);
+  active
+}
+
+/// Records an exchange, which keeps it on.
+pub fn touch() {
+  *LAST_ACTIVITY.lock().unwrap_or_else(PoisonError::into_inner) = Some(Instant::now());
+}
+
+/// The silence ending an utterance, for an agent waiting `end_silence_ms`.
+pub fn end_silence_ms(end_silence_ms: u64) -> u64 {
+  if is_active() {
+    end_silence_ms.min(SILENCE_MS.load(Ordering::Relaxed))
+  } else {
+    end_silence_ms
+  }
+}
+
+/// Whether `text` asks to turn rapid mode on (Some(true)) or off.
+pub fn request(text: &str) -> Option<bool> {
+  let words: String = text
+    .to_lowercase()
+    .chars()
+    .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+    .collect();
+  let words = words.split_whitespace().collect::<Vec<_>>().join(" ");
+  if ON_PHRASES.contains(&words.as_str()) {
+    Some(true)
+  } else if OFF_PHRASES.contains(&words.as_str()) {
+    Some(false)
+  } else {
+    None
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// speech or a turn in progress counts as activity
+fn busy() -> bool {
+  GLOBAL_STATE
+    .get()
+    .is_some_and(|state| state.turn.get().is_busy())
+}
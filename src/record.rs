@@ -3,15 +3,35 @@
 // ------------------------------------------------------------------
 
 use crate::START_INSTANT;
+use crate::cancel::Interrupter;
+use crate::state::TurnState;
 use cpal::traits::{DeviceTrait, StreamTrait};
-use crossbeam_channel::Sender;
+use crossbeam_channel::{Receiver, Sender, TrySendError};
 use std::sync::OnceLock;
+use std::sync::PoisonError;
 use std::sync::{
   Arc, Mutex,
   atomic::{AtomicBool, AtomicU64, Ordering},
 };
+use std::thread;
 use std::time::Instant;
 
+// pause between words allowed while a barge-in is pending
+const BARGE_IN_MAX_GAP_MS: u64 = 150;
+// the voice gate closes under this share of the voice threshold, once the
+// level stayed there for GATE_HOLD_MS
+const GATE_RELEASE_RATIO: f32 = 0.5;
+const GATE_HOLD_MS: u64 = 150;
+// silence kept before and after the speech of an utterance: whisper clips
+// the words without any, and slows down and makes some up in a long one
+const LEAD_SILENCE_MS: u64 = 100;
+const TRAIL_SILENCE_MS: u64 = 250;
+// windows the speech energy is measured on
+const ENERGY_WINDOW_MS: u64 = 10;
+// rms of a speech window against the peak threshold of the voice detection
+// (speech peaks around 4 times its rms)
+const ENERGY_RATIO: f32 = 0.25;
+
 // API
 // ------------------------------------------------------------------
 
@@ -26,16 +46,17 @@ pub fn record_thread(
   end_silence_ms: u64,
   playback_active: Arc<AtomicBool>,
   gate_until_ms: Arc<AtomicU64>,
-  interrupt_counter: Arc<AtomicU64>,
+  interrupter: Interrupter,
   peak: Arc<Mutex<f32>>,
-
-  ui: crate::state::UiState,
   volume: Arc<Mutex<f32>>,
   recording_paused: Arc<AtomicBool>,
+  input_channel: Option<u16>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
   use cpal::SampleFormat;
 
-  let channels = config.channels;
+  // one channel of the input alone (--input-channel) is mono audio
+  let pick = input_channel.map(|ch| (ch - 1) as usize);
+  let channels = if pick.is_some() { 1 } else { config.channels };
   let sample_rate = config.sample_rate.0;
   let sample_format = supported.sample_format();
 
@@ -48,582 +69,593 @@ pub fn record_thread(
   let user_speaking = Arc::new(AtomicBool::new(false));
   let last_voice_ms = Arc::new(AtomicU64::new(0));
 
-  // debounced stop signal
-  let stop_sent = Arc::new(AtomicBool::new(false));
+  // barge-in debounce, see BargeIn
+  let barge_in_ms = crate::util::env_u64("BARGE_IN_MS", crate::config::BARGE_IN_MS_DEFAULT);
 
-  let err_fn = |e| crate::log::log("error", &format!("input stream error: {}", e));
+  // speculative transcription on a probable end of speech, off the audio thread
+  let probable_silence_ms = match crate::util::env_u64(
+    "PROBABLE_SILENCE_MS",
+    crate::config::PROBABLE_SILENCE_MS_DEFAULT,
+  ) {
+    ms if ms < end_silence_ms => ms,
+    _ => u64::MAX,
+  };
+  let (tx_spec, rx_spec) = crossbeam_channel::bounded::<()>(1);
+  thread::spawn({
+    let utt_buf = utt_buf.clone();
+    move || speculation_thread(rx_spec, utt_buf, channels, sample_rate, vad_thresh)
+  });
+
+  // stream errors end up in the loop below, which rebuilds the stream
+  let (tx_stream_err, rx_stream_err) = crossbeam_channel::bounded::<cpal::StreamError>(1);
+  let err_fn = move |e| {
+    let _ = tx_stream_err.try_send(e);
+  };
 
-  let stream = match sample_format {
-    SampleFormat::F32 => build_input_f32(
-      start_instant,
-      &device,
-      &config,
-      channels,
-      sample_rate,
-      tx_utt.clone(),
-      vad_thresh,
-      end_silence_ms,
-      min_utt_ms,
-      hangover_ms,
-      playback_active.clone(),
-      gate_until_ms.clone(),
-      interrupt_counter.clone(),
-      utt_buf.clone(),
-      user_speaking.clone(),
-      last_voice_ms.clone(),
-      stop_sent.clone(),
-      peak.clone(),
-      ui,
-      volume.clone(),
-      recording_paused.clone(),
-      tx_ui.clone(),
-      err_fn,
-    )?,
-
-    SampleFormat::I16 => build_input_i16(
+  let open_stream = || -> Result<cpal::Stream, crate::engine::BoxError> {
+    let capture = Capture {
       start_instant,
-      &device,
-      &config,
       channels,
       sample_rate,
-      tx_utt.clone(),
+      tx_utt: tx_utt.clone(),
       vad_thresh,
       end_silence_ms,
+      probable_silence_ms,
+      tx_spec: tx_spec.clone(),
       min_utt_ms,
       hangover_ms,
-      playback_active.clone(),
-      gate_until_ms.clone(),
-      interrupt_counter.clone(),
-      utt_buf.clone(),
-      user_speaking.clone(),
-      last_voice_ms.clone(),
-      stop_sent.clone(),
-      peak.clone(),
-      ui,
-      volume.clone(),
-      recording_paused.clone(),
-      tx_ui.clone(),
-      err_fn,
-    )?,
-
-    SampleFormat::U16 => build_input_u16(
-      start_instant,
-      &device,
-      &config,
-      channels,
+      playback_active: playback_active.clone(),
+      gate_until_ms: gate_until_ms.clone(),
+      interrupter: interrupter.clone(),
+      utt_buf: utt_buf.clone(),
+      user_speaking: user_speaking.clone(),
+      last_voice_ms: last_voice_ms.clone(),
+      peak: peak.clone(),
+      volume: volume.clone(),
+      recording_paused: recording_paused.clone(),
+      tx_ui: tx_ui.clone(),
+      speculated: false,
+      barge_in: BargeIn::new(barge_in_ms),
+      gate: Gate::default(),
+      clock: Clock::Wall,
+    };
+    Ok(match sample_format {
+      SampleFormat::F32 => {
+        build_input(&device, &config, pick, capture, |s: f32| s, err_fn.clone())?
+      }
+      SampleFormat::I16 => build_input(
+        &device,
+        &config,
+        pick,
+        capture,
+        |s: i16| s as f32 / 32768.0,
+        err_fn.clone(),
+      )?,
+      SampleFormat::U16 => build_input(
+        &device,
+        &config,
+        pick,
+        capture,
+        |s: u16| (s as f32 / u16::MAX as f32) * 2.0 - 1.0,
+        err_fn.clone(),
+      )?,
+      other => return Err(format!("unsupported input format: {other:?}").into()),
+    })
+  };
+
+  let mut stream = open_stream()?;
+  stream.play()?;
+
+  // Keep the stream alive until the program exits, rebuilding it when broken
+  for err in rx_stream_err.iter() {
+    crate::audio::stream_failed("input", &err);
+    drop(stream);
+    stream = loop {
+      thread::sleep(crate::audio::STREAM_RETRY_INTERVAL);
+      let started = open_stream().and_then(|stream| {
+        stream.play()?;
+        Ok(stream)
+      });
+      match started {
+        Ok(stream) => break stream,
+        Err(e) => crate::log::log("debug", &format!("Input stream not back yet: {}", e)),
+      }
+    };
+    // stale errors of the broken stream
+    while rx_stream_err.try_recv().is_ok() {}
+    user_speaking.store(false, Ordering::Relaxed);
+    crate::state::advance_turn(&[TurnState::Listening], TurnState::Idle);
+    utt_buf
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clear();
+    crate::audio::stream_recovered("input");
+  }
+  Ok(())
+}
+
+/// The voice detection of the microphone input run on recorded samples, for
+/// tests (testing feature). Time is the audio fed so far, the wall clock has
+/// no part in it.
+#[cfg(feature = "testing")]
+pub struct Segmenter {
+  capture: Capture,
+  rx_utt: Receiver<crate::audio::AudioChunk>,
+  callback_len: usize,
+}
+
+#[cfg(feature = "testing")]
+impl Segmenter {
+  /// Utterances of mono audio at `sample_rate`, with the voice threshold and
+  /// end silence of an agent.
+  pub fn new(sample_rate: u32, vad_thresh: f32, end_silence_ms: u64) -> Self {
+    let (tx_utt, rx_utt) = crossbeam_channel::unbounded();
+    let (tx_ui, _) = crossbeam_channel::unbounded();
+    // no speculative transcription
+    let (tx_spec, _) = crossbeam_channel::bounded(1);
+    let capture = Capture {
+      start_instant: &START_INSTANT,
+      channels: 1,
       sample_rate,
-      tx_utt.clone(),
+      tx_utt,
       vad_thresh,
       end_silence_ms,
-      min_utt_ms,
-      hangover_ms,
-      playback_active.clone(),
-      gate_until_ms.clone(),
-      interrupt_counter.clone(),
-      utt_buf.clone(),
-      user_speaking.clone(),
-      last_voice_ms.clone(),
-      stop_sent.clone(),
-      peak.clone(),
-      ui,
-      volume.clone(),
-      recording_paused.clone(),
-      tx_ui.clone(),
-      err_fn,
-    )?,
-
-    other => return Err(format!("unsupported input format: {other:?}").into()),
-  };
+      probable_silence_ms: u64::MAX,
+      tx_spec,
+      min_utt_ms: crate::config::MIN_UTTERANCE_MS_DEFAULT,
+      hangover_ms: crate::config::HANGOVER_MS_DEFAULT,
+      playback_active: Arc::new(AtomicBool::new(false)),
+      gate_until_ms: Arc::new(AtomicU64::new(0)),
+      interrupter: Interrupter::new(),
+      utt_buf: Arc::new(Mutex::new(Vec::new())),
+      user_speaking: Arc::new(AtomicBool::new(false)),
+      last_voice_ms: Arc::new(AtomicU64::new(0)),
+      peak: Arc::new(Mutex::new(0.0)),
+      volume: Arc::new(Mutex::new(1.0)),
+      recording_paused: Arc::new(AtomicBool::new(false)),
+      tx_ui,
+      speculated: false,
+      barge_in: BargeIn::new(crate::config::BARGE_IN_MS_DEFAULT),
+      gate: Gate::default(),
+      clock: Clock::Frames(0),
+    };
+    Self {
+      capture,
+      rx_utt,
+      // 10ms callbacks
+      callback_len: (sample_rate as usize / 100).max(1),
+    }
+  }
 
-  stream.play()?;
+  /// Feeds `samples` as successive input callbacks, returns the utterances
+  /// they completed.
+  pub fn feed(&mut self, samples: &[f32]) -> Vec<crate::audio::AudioChunk> {
+    for callback in samples.chunks(self.callback_len) {
+      self.capture.process(callback);
+    }
+    self.rx_utt.try_iter().collect()
+  }
 
-  // Keep the stream alive until the program exits
-  loop {
-    std::thread::sleep(std::time::Duration::from_millis(10));
+  /// Set while the agent speaks: voice fed then interrupts it (barge-in).
+  pub fn playback_active(&self) -> Arc<AtomicBool> {
+    self.capture.playback_active.clone()
+  }
+
+  /// Interrupted by every barge-in.
+  pub fn interrupter(&self) -> Interrupter {
+    self.capture.interrupter.clone()
   }
 }
 
 // PRIVATE
 // ------------------------------------------------------------------
 
-fn build_input_f32(
-  start_instant: &'static OnceLock<Instant>,
+// Opens the input stream in the device's sample format: every callback is
+// converted to f32 (the channel `pick` alone, if any) and goes through the
+// same Capture
+fn build_input<T: cpal::SizedSample + Send + 'static>(
   device: &cpal::Device,
   config: &cpal::StreamConfig,
-  channels: u16,
-  sample_rate: u32,
-  tx_utt: Sender<crate::audio::AudioChunk>,
-  vad_thresh: f32,
-  end_silence_ms: u64,
-  min_utt_ms: u64,
-  hangover_ms: u64,
-  playback_active: Arc<AtomicBool>,
-  gate_until_ms: Arc<AtomicU64>,
-  interrupt_counter: Arc<AtomicU64>,
-  utt_buf: Arc<Mutex<Vec<f32>>>,
-  user_speaking: Arc<AtomicBool>,
-  last_voice_ms: Arc<AtomicU64>,
-  stop_sent: Arc<AtomicBool>,
-  peak: Arc<Mutex<f32>>,
-  ui: crate::state::UiState,
-  volume: Arc<Mutex<f32>>,
-  recording_paused: Arc<AtomicBool>,
-  tx_ui: Sender<String>,
+  pick: Option<usize>,
+  mut capture: Capture,
+  to_f32: fn(T) -> f32,
   mut err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
 ) -> Result<cpal::Stream, cpal::BuildStreamError> {
+  // reused by every callback: no allocation in the audio thread once warm
+  let mut frame: Vec<f32> = Vec::with_capacity(scratch_capacity(config));
+  let stream_config = config.clone();
   device.build_input_stream(
     config,
-    move |data: &[f32], _| {
-      let local_peak = peak_abs(data);
-
-      if let Ok(mut p) = peak.lock() {
-        *p = local_peak;
-      }
-      if recording_paused.load(Ordering::Relaxed) {
-        // flush buffer if not empty
-        let mut b = utt_buf.lock().unwrap();
-        if !b.is_empty() {
-          let audio = std::mem::take(&mut *b);
-          let denom = (sample_rate as u64).saturating_mul(channels as u64).max(1);
-          let dur_ms = (audio.len() as u64).saturating_mul(1000) / denom;
-          if dur_ms >= min_utt_ms {
-            crate::util::SPEECH_END_AT.store(
-              crate::util::now_ms(&START_INSTANT),
-              std::sync::atomic::Ordering::SeqCst,
-            );
-            let _ = tx_utt.send(crate::audio::AudioChunk {
-              data: audio,
-              channels,
-              sample_rate,
-            });
-          } else {
-            crate::log::log(
-              "info",
-              &format!(
-                "[{}ms] utterance too short ({}ms < {}ms), dropped",
-                crate::util::now_ms(start_instant),
-                dur_ms,
-                min_utt_ms
-              ),
-            );
-          }
-        }
-        return;
-      }
-      let local_peak = peak_abs(data);
-
-      // use previously computed peak for threshold check
-      if local_peak >= vad_thresh {
-        last_voice_ms.store(crate::util::now_ms(start_instant), Ordering::Relaxed);
-        ui.agent_speaking.store(true, Ordering::Relaxed);
-
-        if !user_speaking.swap(true, Ordering::Relaxed) {
-          let mut b = utt_buf.lock().unwrap();
-          b.clear();
-          crate::log::log("info", &format!("Audio detected (peak: {:.3})", local_peak));
-        }
-        {
-          let mut b = utt_buf.lock().unwrap();
-          b.extend_from_slice(data);
-        }
-
-        if playback_active.load(Ordering::Relaxed) && !stop_sent.load(Ordering::Relaxed) {
-          // silence audio
-          let mut vol = volume.lock().unwrap();
-          *vol = 0.0;
-          interrupt_counter.fetch_add(1, Ordering::SeqCst);
-          let _ = tx_ui.send("user_interrupt_show|".to_string());
-          stop_sent.store(true, Ordering::Relaxed);
-          gate_until_ms.store(
-            crate::util::now_ms(start_instant).saturating_add(hangover_ms),
-            Ordering::Relaxed,
-          );
-          playback_active.store(false, Ordering::Relaxed);
-          stop_sent.store(false, Ordering::Relaxed);
-        }
-      } else if user_speaking.load(Ordering::Relaxed) {
-        {
-          let mut b = utt_buf.lock().unwrap();
-          b.extend_from_slice(data);
-        }
-        let last = last_voice_ms.load(Ordering::Relaxed);
-
-        // silence detected
-        if last > 0
-          && !crate::state::GLOBAL_STATE
-            .get()
-            .unwrap()
-            .ptt
-            .load(Ordering::Relaxed)
-          && crate::util::now_ms(start_instant).saturating_sub(last) >= end_silence_ms
-        {
-          crate::log::log("info", "Silence detected");
-          ui.agent_speaking.store(false, Ordering::Relaxed);
-          user_speaking.store(false, Ordering::Relaxed);
-          stop_sent.store(false, Ordering::Relaxed);
-          let mut b = utt_buf.lock().unwrap();
-          if !b.is_empty() {
-            let audio = std::mem::take(&mut *b);
-            let denom = (sample_rate as u64).saturating_mul(channels as u64).max(1);
-            let dur_ms = (audio.len() as u64).saturating_mul(1000) / denom;
-            crate::log::log(
-              "info",
-              &format!(
-                "Speech ended after (~{}ms) of silence; samples={})",
-                dur_ms,
-                audio.len()
-              ),
-            );
-            // new utterance
-            if dur_ms >= min_utt_ms {
-              crate::util::SPEECH_END_AT.store(
-                crate::util::now_ms(&START_INSTANT),
-                std::sync::atomic::Ordering::SeqCst,
-              );
-              // commit utterance audio
-              let _ = tx_utt.send(crate::audio::AudioChunk {
-                data: audio,
-                channels,
-                sample_rate,
-              });
-            } else {
-              crate::log::log(
-                "info",
-                &format!(
-                  "[{}ms] utterance too short ({}ms < {}ms), dropped",
-                  crate::util::now_ms(start_instant),
-                  dur_ms,
-                  min_utt_ms
-                ),
-              );
-            }
-          }
-        }
-      } else {
-        stop_sent.store(false, Ordering::Relaxed);
+    move |data: &[T], _| {
+      crate::audio::promote_audio_thread(&stream_config);
+      frame.clear();
+      match pick {
+        Some(ch) => frame.extend(
+          data
+            .iter()
+            .skip(ch)
+            .step_by(stream_config.channels.max(1) as usize)
+            .map(|&s| to_f32(s)),
+        ),
+        None => frame.extend(data.iter().map(|&s| to_f32(s))),
       }
+      crate::gain::apply(&mut frame);
+      capture.process(&frame);
     },
     move |e| err_fn(e),
     None,
   )
 }
 
-fn build_input_i16(
+// Voice detection and utterance capture, on the f32 samples of a callback
+struct Capture {
   start_instant: &'static OnceLock<Instant>,
-  device: &cpal::Device,
-  config: &cpal::StreamConfig,
   channels: u16,
   sample_rate: u32,
   tx_utt: Sender<crate::audio::AudioChunk>,
   vad_thresh: f32,
   end_silence_ms: u64,
+  probable_silence_ms: u64,
+  tx_spec: Sender<()>,
   min_utt_ms: u64,
   hangover_ms: u64,
   playback_active: Arc<AtomicBool>,
   gate_until_ms: Arc<AtomicU64>,
-  interrupt_counter: Arc<AtomicU64>,
+  interrupter: Interrupter,
   utt_buf: Arc<Mutex<Vec<f32>>>,
   user_speaking: Arc<AtomicBool>,
   last_voice_ms: Arc<AtomicU64>,
-  stop_sent: Arc<AtomicBool>,
   peak: Arc<Mutex<f32>>,
-  ui: crate::state::UiState,
   volume: Arc<Mutex<f32>>,
   recording_paused: Arc<AtomicBool>,
   tx_ui: Sender<String>,
-  mut err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
-) -> Result<cpal::Stream, cpal::BuildStreamError> {
-  device.build_input_stream(
-    config,
-    move |data: &[f32], _| {
-      if recording_paused.load(Ordering::Relaxed) {
-        // Flush buffer if not empty
-        let mut b = utt_buf.lock().unwrap();
-        if !b.is_empty() {
-          let audio = std::mem::take(&mut *b);
-          let denom = (sample_rate as u64).saturating_mul(channels as u64).max(1);
-          let dur_ms = (audio.len() as u64).saturating_mul(1000) / denom;
-          if dur_ms >= min_utt_ms {
-            crate::util::SPEECH_END_AT.store(
-              crate::util::now_ms(&START_INSTANT),
-              std::sync::atomic::Ordering::SeqCst,
-            );
-            let _ = tx_utt.send(crate::audio::AudioChunk {
-              data: audio,
-              channels,
-              sample_rate,
-            });
-          } else {
-            crate::log::log(
-              "info",
-              &format!(
-                "[{}ms] utterance too short ({}ms < {}ms), dropped",
-                crate::util::now_ms(start_instant),
-                dur_ms,
-                min_utt_ms
-              ),
-            );
-          }
-        }
-        return;
-      }
+  // speculative transcription asked for since the last voice
+  speculated: bool,
+  barge_in: BargeIn,
+  gate: Gate,
+  clock: Clock,
+}
 
-      // Convert to f32 interleaved (preserve existing behavior)
-      let mut tmp = Vec::with_capacity(data.len());
-      for &s in data {
-        tmp.push((s as f32) / 32768.0);
-      }
+// time of the callbacks: the wall clock, or the audio processed so far for
+// recorded samples (testing), so they segment the same on any machine
+enum Clock {
+  Wall,
+  #[cfg(feature = "testing")]
+  Frames(u64),
+}
 
-      let local_peak = peak_abs(&tmp);
-      if let Ok(mut p) = peak.lock() {
-        *p = local_peak;
-      }
+impl Capture {
+  fn process(&mut self, data: &[f32]) {
+    #[cfg(feature = "testing")]
+    if let Clock::Frames(frames) = &mut self.clock {
+      *frames += (data.len() / self.channels.max(1) as usize) as u64;
+    }
 
-      if local_peak >= vad_thresh {
-        last_voice_ms.store(crate::util::now_ms(start_instant), Ordering::Relaxed);
-        ui.agent_speaking.store(true, Ordering::Relaxed);
+    let local_peak = peak_abs(data);
+    if let Ok(mut p) = self.peak.lock() {
+      *p = local_peak;
+    }
 
-        if !user_speaking.swap(true, Ordering::Relaxed) {
-          let mut b = utt_buf.lock().unwrap();
+    if self.recording_paused.load(Ordering::Relaxed) || crate::dnd::is_active() {
+      // flush buffer if not empty
+      let audio = std::mem::take(&mut *self.utt_buf.lock().unwrap_or_else(PoisonError::into_inner));
+      if !audio.is_empty() {
+        self.commit(audio);
+      }
+      return;
+    }
+
+    let now = self.now_ms();
+    if self.gate.voice(local_peak, self.vad_thresh, now) {
+      self
+        .last_voice_ms
+        .store(self.gate.heard_ms, Ordering::Relaxed);
+      self.speculated = false;
+      // over the reply, the user is heard once it's interrupted
+      crate::state::advance_turn(
+        &[TurnState::Idle, TurnState::Interrupted],
+        TurnState::Listening,
+      );
+
+      {
+        let mut b = self.utt_buf.lock().unwrap_or_else(PoisonError::into_inner);
+        if !self.user_speaking.swap(true, Ordering::Relaxed) {
           b.clear();
           crate::log::log("info", &format!("Audio detected (peak: {:.3})", local_peak));
         }
-        {
-          let mut b = utt_buf.lock().unwrap();
-          b.extend_from_slice(&tmp);
-        }
+        b.extend_from_slice(data);
+      }
 
-        if playback_active.load(Ordering::Relaxed) && !stop_sent.load(Ordering::Relaxed) {
-          // silence audio
-          let mut vol = volume.lock().unwrap();
-          *vol = 0.0;
-          interrupt_counter.fetch_add(1, Ordering::SeqCst);
-          let _ = tx_ui.send("user_interrupt_show|".to_string());
-          stop_sent.store(true, Ordering::Relaxed);
-          gate_until_ms.store(
-            crate::util::now_ms(start_instant).saturating_add(hangover_ms),
-            Ordering::Relaxed,
+      // an important announcement is not interrupted by voice
+      let playing =
+        self.playback_active.load(Ordering::Relaxed) && !crate::announce::is_protected();
+      // speech over the reply cuts it off, unless it's paused until what is
+      // said is known (see crate::interjection). Only the level over the
+      // threshold counts: the echo of the reply would hold the gate open
+      if local_peak < self.vad_thresh {
+        self.barge_in.silence(now, self.gate.loud_ms);
+      } else if self.barge_in.voice(now, playing) && !crate::interjection::hold() {
+        // silence audio
+        *self.volume.lock().unwrap_or_else(PoisonError::into_inner) = 0.0;
+        self.interrupter.interrupt();
+        let _ = self.tx_ui.send("user_interrupt_show|".to_string());
+        crate::events::emit(crate::engine::TurnEvent::Interrupted);
+        crate::state::advance_turn(
+          &[TurnState::Generating, TurnState::Speaking],
+          TurnState::Interrupted,
+        );
+        self
+          .gate_until_ms
+          .store(now.saturating_add(self.hangover_ms), Ordering::Relaxed);
+        self.playback_active.store(false, Ordering::Relaxed);
+      }
+    } else if self.user_speaking.load(Ordering::Relaxed) {
+      self
+        .utt_buf
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .extend_from_slice(data);
+      let last = self.last_voice_ms.load(Ordering::Relaxed);
+      self.barge_in.silence(now, self.gate.loud_ms);
+      let silent_ms = now.saturating_sub(last);
+
+      // probable end of speech: transcribe already, used if nothing else is said
+      if last > 0 && !self.speculated && silent_ms >= self.probable_silence_ms {
+        self.speculated = true;
+        let _ = self.tx_spec.try_send(());
+      }
+
+      // silence detected
+      let ptt = crate::state::GLOBAL_STATE
+        .get()
+        .is_some_and(|state| state.ptt.load(Ordering::Relaxed));
+      let end_silence_ms = crate::rapid::end_silence_ms(self.end_silence_ms);
+      if last > 0 && !ptt && silent_ms >= end_silence_ms {
+        crate::log::log("info", "Silence detected");
+        self.user_speaking.store(false, Ordering::Relaxed);
+        self.barge_in.reset();
+        let audio =
+          std::mem::take(&mut *self.utt_buf.lock().unwrap_or_else(PoisonError::into_inner));
+        if audio.is_empty() {
+          crate::state::advance_turn(&[TurnState::Listening], TurnState::Idle);
+        } else {
+          crate::log::log(
+            "info",
+            &format!(
+              "Speech ended after (~{}ms) of silence; samples={})",
+              self.duration_ms(&audio),
+              audio.len()
+            ),
           );
-          playback_active.store(false, Ordering::Relaxed);
-          stop_sent.store(false, Ordering::Relaxed);
-        }
-      } else if user_speaking.load(Ordering::Relaxed) {
-        {
-          let mut b = utt_buf.lock().unwrap();
-          b.extend_from_slice(&tmp);
-        }
-        let last = last_voice_ms.load(Ordering::Relaxed);
-        if last > 0
-          && !crate::state::GLOBAL_STATE
-            .get()
-            .unwrap()
-            .ptt
-            .load(Ordering::Relaxed)
-          && crate::util::now_ms(start_instant).saturating_sub(last) >= end_silence_ms
-        {
-          crate::log::log("info", "Silence detected");
-          ui.agent_speaking.store(false, Ordering::Relaxed);
-          user_speaking.store(false, Ordering::Relaxed);
-          stop_sent.store(false, Ordering::Relaxed);
-          let mut b = utt_buf.lock().unwrap();
-          if !b.is_empty() {
-            let audio = std::mem::take(&mut *b);
-            let denom = (sample_rate as u64).saturating_mul(channels as u64).max(1);
-            let dur_ms = (audio.len() as u64).saturating_mul(1000) / denom;
-            crate::log::log(
-              "info",
-              &format!(
-                "Speech ended after (~{}ms) of silence; samples={})",
-                dur_ms,
-                audio.len()
-              ),
-            );
-            if dur_ms >= min_utt_ms {
-              crate::util::SPEECH_END_AT.store(
-                crate::util::now_ms(&START_INSTANT),
-                std::sync::atomic::Ordering::SeqCst,
-              );
-              let _ = tx_utt.send(crate::audio::AudioChunk {
-                data: audio,
-                channels,
-                sample_rate,
-              });
-            } else {
-              // FIX: match f32 behavior (warn + drop)
-              crate::log::log(
-                "info",
-                &format!(
-                  "[{}ms] utterance too short ({}ms < {}ms), dropped",
-                  crate::util::now_ms(start_instant),
-                  dur_ms,
-                  min_utt_ms
-                ),
-              );
-            }
-          }
+          self.commit(audio);
         }
-      } else {
-        stop_sent.store(false, Ordering::Relaxed);
       }
-    },
-    move |e| err_fn(e),
-    None,
-  )
+    } else {
+      self.barge_in.reset();
+    }
+  }
+
+  // New utterance, unless it's too short to be speech. The silence around the
+  // speech (the end silence, mostly) is left out
+  fn commit(&self, mut audio: Vec<f32>) {
+    let speech = speech_span(&audio, self.channels, self.sample_rate, self.vad_thresh);
+    audio.truncate(speech.end);
+    audio.drain(..speech.start);
+    let dur_ms = self.duration_ms(&audio);
+    if dur_ms < self.min_utt_ms {
+      crate::log::log(
+        "info",
+        &format!(
+          "[{}ms] utterance too short ({}ms < {}ms), dropped",
+          self.now_ms(),
+          dur_ms,
+          self.min_utt_ms
+        ),
+      );
+      crate::ignored::record(crate::ignored::Reason::TooShort);
+      crate::state::advance_turn(&[TurnState::Listening], TurnState::Idle);
+      return;
+    }
+    crate::state::advance_turn(&[TurnState::Listening], TurnState::Transcribing);
+    crate::intercom::heard_here();
+    crate::util::SPEECH_END_AT.store(crate::util::now_ms(&START_INSTANT), Ordering::SeqCst);
+    send_utterance(
+      &self.tx_utt,
+      crate::audio::AudioChunk {
+        data: audio.into(),
+        channels: self.channels,
+        sample_rate: self.sample_rate,
+      },
+    );
+  }
+
+  fn now_ms(&self) -> u64 {
+    match self.clock {
+      Clock::Wall => crate::util::now_ms(self.start_instant),
+      #[cfg(feature = "testing")]
+      Clock::Frames(frames) => frames * 1000 / self.sample_rate.max(1) as u64,
+    }
+  }
+
+  fn duration_ms(&self, audio: &[f32]) -> u64 {
+    let denom = (self.sample_rate as u64)
+      .saturating_mul(self.channels as u64)
+      .max(1);
+    (audio.len() as u64).saturating_mul(1000) / denom
+  }
 }
 
-fn build_input_u16(
-  start_instant: &'static OnceLock<Instant>,
-  device: &cpal::Device,
-  config: &cpal::StreamConfig,
+// Barge-in detection: speech over the playback interrupts it once it lasted
+// `persist_ms` (a cough or a door doesn't), and only once per utterance
+struct BargeIn {
+  persist_ms: u64,
+  state: BargeInState,
+}
+
+#[derive(Clone, Copy)]
+enum BargeInState {
+  Idle,
+  // speech over the playback, not long enough yet
+  Pending { since_ms: u64 },
+  // playback interrupted, until the utterance ends
+  Fired,
+}
+
+impl BargeIn {
+  fn new(persist_ms: u64) -> Self {
+    Self {
+      persist_ms,
+      state: BargeInState::Idle,
+    }
+  }
+
+  // Voice heard at `now_ms`, true when it should interrupt the playback now.
+  fn voice(&mut self, now_ms: u64, playing: bool) -> bool {
+    let since_ms = match self.state {
+      BargeInState::Fired => return false,
+      _ if !playing => {
+        self.state = BargeInState::Idle;
+        return false;
+      }
+      BargeInState::Idle => now_ms,
+      BargeInState::Pending { since_ms } => since_ms,
+    };
+    if now_ms.saturating_sub(since_ms) >= self.persist_ms {
+      self.state = BargeInState::Fired;
+      true
+    } else {
+      self.state = BargeInState::Pending { since_ms };
+      false
+    }
+  }
+
+  // No voice since `last_voice_ms`: a pending barge-in with a gap longer
+  // than BARGE_IN_MAX_GAP_MS was only a noise.
+  fn silence(&mut self, now_ms: u64, last_voice_ms: u64) {
+    if matches!(self.state, BargeInState::Pending { .. })
+      && now_ms.saturating_sub(last_voice_ms) > BARGE_IN_MAX_GAP_MS
+    {
+      self.state = BargeInState::Idle;
+    }
+  }
+
+  fn reset(&mut self) {
+    self.state = BargeInState::Idle;
+  }
+}
+
+// Noise gate of the voice detection, with hysteresis: it opens on a peak
+// over the voice threshold and stays open while the level is over
+// GATE_RELEASE_RATIO of it, and GATE_HOLD_MS more, so the quieter syllables
+// and the short dips of a sentence don't split it
+#[derive(Default)]
+struct Gate {
+  open: bool,
+  // last callback over the release level, and over the voice threshold
+  heard_ms: u64,
+  loud_ms: u64,
+}
+
+impl Gate {
+  // Whether the callback at `now_ms`, peaking at `peak`, is voice.
+  fn voice(&mut self, peak: f32, vad_thresh: f32, now_ms: u64) -> bool {
+    if peak >= vad_thresh {
+      self.open = true;
+      self.loud_ms = now_ms;
+    }
+    if self.open && peak >= vad_thresh * GATE_RELEASE_RATIO {
+      self.heard_ms = now_ms;
+    } else if self.open && now_ms.saturating_sub(self.heard_ms) > GATE_HOLD_MS {
+      self.open = false;
+    }
+    self.open
+  }
+}
+
+// Hands an utterance to the conversation without ever blocking the audio
+// thread: when the queue is full it's dropped, and counted
+fn send_utterance(tx_utt: &Sender<crate::audio::AudioChunk>, utt: crate::audio::AudioChunk) {
+  if let Err(TrySendError::Full(_)) = tx_utt.try_send(utt) {
+    crate::metrics::dropped("utterances");
+    crate::ignored::record(crate::ignored::Reason::Behind);
+    crate::log::log(
+      "warning",
+      "Utterance dropped, the transcription is behind (see --utterance-queue)",
+    );
+  }
+}
+
+// Transcribes the utterance so far every time the callbacks see a probable
+// end of speech
+fn speculation_thread(
+  rx_spec: Receiver<()>,
+  utt_buf: Arc<Mutex<Vec<f32>>>,
   channels: u16,
   sample_rate: u32,
-  tx_utt: Sender<crate::audio::AudioChunk>,
   vad_thresh: f32,
-  end_silence_ms: u64,
-  min_utt_ms: u64,
-  hangover_ms: u64,
-  playback_active: Arc<AtomicBool>,
-  gate_until_ms: Arc<AtomicU64>,
-  interrupt_counter: Arc<AtomicU64>,
-  utt_buf: Arc<Mutex<Vec<f32>>>,
-  user_speaking: Arc<AtomicBool>,
-  last_voice_ms: Arc<AtomicU64>,
-  stop_sent: Arc<AtomicBool>,
-  peak: Arc<Mutex<f32>>,
-  ui: crate::state::UiState,
-  volume: Arc<Mutex<f32>>,
-  recording_paused: Arc<AtomicBool>,
-  tx_ui: Sender<String>,
-  mut err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
-) -> Result<cpal::Stream, cpal::BuildStreamError> {
-  device.build_input_stream(
-    config,
-    move |data: &[u16], _| {
-      // Convert once (preserve existing behavior), and reuse for peak + utt_buf + resample
-      let mut tmp = Vec::with_capacity(data.len());
-      for &s in data {
-        tmp.push((s as f32 / u16::MAX as f32) * 2.0 - 1.0);
-      }
-
-      let local_peak = peak_abs(&tmp);
-      if let Ok(mut p) = peak.lock() {
-        *p = local_peak;
-      }
+) {
+  for () in rx_spec {
+    // trimmed as it will be when committed, to match it
+    let data: Arc<[f32]> = {
+      let buf = utt_buf.lock().unwrap_or_else(PoisonError::into_inner);
+      buf[speech_span(&buf, channels, sample_rate, vad_thresh)].into()
+    };
+    let Some(state) = crate::state::GLOBAL_STATE.get() else {
+      continue;
+    };
+    if data.is_empty() || state.ptt.load(Ordering::Relaxed) {
+      continue;
+    }
+    let language = state
+      .language
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone();
+    let utt = crate::audio::AudioChunk {
+      data,
+      channels,
+      sample_rate,
+    };
+    crate::stt::speculate(utt, vad_thresh, &language);
+  }
+}
 
-      if recording_paused.load(Ordering::Relaxed) {
-        // flush buffer if not empty
-        let mut b = utt_buf.lock().unwrap();
-        if !b.is_empty() {
-          let audio = std::mem::take(&mut *b);
-          let denom = (sample_rate as u64).saturating_mul(channels as u64).max(1);
-          let dur_ms = (audio.len() as u64).saturating_mul(1000) / denom;
-          if dur_ms >= min_utt_ms {
-            crate::util::SPEECH_END_AT.store(
-              crate::util::now_ms(&START_INSTANT),
-              std::sync::atomic::Ordering::SeqCst,
-            );
-            let _ = tx_utt.send(crate::audio::AudioChunk {
-              data: audio,
-              channels,
-              sample_rate,
-            });
-          } else {
-            crate::log::log(
-              "info",
-              &format!(
-                "[{}ms] utterance too short ({}ms < {}ms), dropped",
-                crate::util::now_ms(start_instant),
-                dur_ms,
-                min_utt_ms
-              ),
-            );
-          }
-        }
-        return;
-      }
-      if local_peak >= vad_thresh {
-        // FIX: remove duplicate stores
-        last_voice_ms.store(crate::util::now_ms(start_instant), Ordering::Relaxed);
-        ui.agent_speaking.store(true, Ordering::Relaxed);
+// Samples of the f32 conversion buffer: the device buffer when it's fixed,
+// 100ms otherwise (it grows once if a callback brings more)
+fn scratch_capacity(config: &cpal::StreamConfig) -> usize {
+  let frames = match config.buffer_size {
+    cpal::BufferSize::Fixed(n) => n as usize,
+    cpal::BufferSize::Default => config.sample_rate.0 as usize / 10,
+  };
+  frames * config.channels as usize
+}
 
-        if !user_speaking.swap(true, Ordering::Relaxed) {
-          let mut b = utt_buf.lock().unwrap();
-          b.clear();
-          crate::log::log("info", &format!("Audio detected (peak: {:.3})", local_peak));
-        }
-        {
-          let mut b = utt_buf.lock().unwrap();
-          b.extend_from_slice(&tmp);
-        }
+// The samples from a little before the first window of speech to a little
+// after the last, all of them when none is loud enough
+fn speech_span(
+  audio: &[f32],
+  channels: u16,
+  sample_rate: u32,
+  vad_thresh: f32,
+) -> std::ops::Range<usize> {
+  let frame = channels.max(1) as usize;
+  let samples = |ms: u64| (sample_rate as u64 * ms / 1000) as usize * frame;
+  let window = samples(ENERGY_WINDOW_MS).max(frame);
+  let voiced = |w: &[f32]| rms(w) >= vad_thresh * ENERGY_RATIO;
+  let Some(first) = audio.chunks(window).position(voiced) else {
+    return 0..audio.len();
+  };
+  let last = audio.chunks(window).rposition(voiced).unwrap_or(first);
+  let start = (first * window).saturating_sub(samples(LEAD_SILENCE_MS));
+  let end = ((last + 1) * window + samples(TRAIL_SILENCE_MS)).min(audio.len());
+  start..end
+}
 
-        if playback_active.load(Ordering::Relaxed) && !stop_sent.load(Ordering::Relaxed) {
-          // silence audio
-          let mut vol = volume.lock().unwrap();
-          *vol = 0.0;
-          interrupt_counter.fetch_add(1, Ordering::SeqCst);
-          let _ = tx_ui.send("user_interrupt_show|".to_string());
-          stop_sent.store(true, Ordering::Relaxed);
-          gate_until_ms.store(
-            crate::util::now_ms(start_instant).saturating_add(hangover_ms),
-            Ordering::Relaxed,
-          );
-          playback_active.store(false, Ordering::Relaxed);
-          stop_sent.store(false, Ordering::Relaxed);
-        }
-      } else if user_speaking.load(Ordering::Relaxed) {
-        {
-          let mut b = utt_buf.lock().unwrap();
-          b.extend_from_slice(&tmp);
-        }
-        let last = last_voice_ms.load(Ordering::Relaxed);
-        if last > 0
-          && !crate::state::GLOBAL_STATE
-            .get()
-            .unwrap()
-            .ptt
-            .load(Ordering::Relaxed)
-          && crate::util::now_ms(start_instant).saturating_sub(last) >= end_silence_ms
-        {
-          crate::log::log("info", "Silence detected");
-          // FIX: ensure UI clears speaking state on silence
-          ui.agent_speaking.store(false, Ordering::Relaxed);
-
-          user_speaking.store(false, Ordering::Relaxed);
-          stop_sent.store(false, Ordering::Relaxed);
-
-          let mut b = utt_buf.lock().unwrap();
-          if !b.is_empty() {
-            let audio = std::mem::take(&mut *b);
-            let denom = (sample_rate as u64).saturating_mul(channels as u64).max(1);
-            let dur_ms = (audio.len() as u64).saturating_mul(1000) / denom;
-            crate::log::log(
-              "info",
-              &format!(
-                "Speech ended after (~{}ms) of silence; samples={})",
-                dur_ms,
-                audio.len()
-              ),
-            );
-            if dur_ms >= min_utt_ms {
-              crate::util::SPEECH_END_AT.store(
-                crate::util::now_ms(&START_INSTANT),
-                std::sync::atomic::Ordering::SeqCst,
-              );
-              let _ = tx_utt.send(crate::audio::AudioChunk {
-                data: audio,
-                channels,
-                sample_rate,
-              });
-            }
-          }
-        }
-      } else {
-        stop_sent.store(false, Ordering::Relaxed);
-      }
-    },
-    move |e| err_fn(e),
-    None,
-  )
+fn rms(x: &[f32]) -> f32 {
+  if x.is_empty() {
+    return 0.0;
+  }
+  (x.iter().map(|v| v * v).sum::<f32>() / x.len() as f32).sqrt()
 }
 
 fn peak_abs(x: &[f32]) -> f32 {
@@ -0,0 +1,155 @@
+// ------------------------------------------------------------------
+//  Redaction (--redact, --redact-pattern)
+// ------------------------------------------------------------------
+//
+// Masks personal data in what vtmate writes down: saved conversations,
+// captions and logs. The conversation itself (the llm requests, the spoken
+// reply) is left as it is, the agent still hears what was said.
+
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+// lengths of the card numbers in use (visa, mastercard, amex, ...)
+const CARD_DIGITS: std::ops::RangeInclusive<usize> = 13..=19;
+// shorter digit runs are years, amounts or times, longer ones are no phones;
+// dates, year ranges and amounts within the range are told apart by their
+// grouping
+const PHONE_DIGITS: std::ops::RangeInclusive<usize> = 7..=15;
+
+// set once at startup, None (the default) leaves the text untouched
+static RULES: OnceLock<Vec<Rule>> = OnceLock::new();
+
+// API
+// ------------------------------------------------------------------
+
+/// Turns redaction on with the built-in patterns (emails, phone and card
+/// numbers) and the extra `patterns`. Does nothing when not `enabled` and
+/// `patterns` is empty.
+pub fn init(enabled: bool, patterns: &[String]) -> Result<(), regex::Error> {
+  if !enabled && patterns.is_empty() {
+    return Ok(());
+  }
+  let mut rules = Vec::new();
+  for pattern in patterns {
+    rules.push(Rule {
+      regex: Regex::new(pattern)?,
+      mask: "[redacted]",
+      check: None,
+    });
+  }
+  // cards go before phones, both being runs of digits
+  rules.push(Rule {
+    regex: Regex::new(r"\b\d(?:[ -]?\d){12,18}\b")?,
+    mask: "[card]",
+    check: Some(is_card_number),
+  });
+  rules.push(Rule {
+    regex: Regex::new(r"\b[\w.%+-]+@[\w-]+(?:\.[\w-]+)*\.[A-Za-z]{2,}\b")?,
+    mask: "[email]",
+    check: None,
+  });
+  rules.push(Rule {
+    regex: Regex::new(
+      r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{1,4}\)[ .-]?|\b)\d{1,4}(?:[ .-]?\d{1,4}){1,5}\b",
+    )?,
+    mask: "[phone]",
+    check: Some(is_phone_number),
+  });
+  RULES.set(rules).ok();
+  Ok(())
+}
+
+/// `text` with its personal data masked, as it is when redaction is off.
+pub fn redact(text: &str) -> Cow<'_, str> {
+  let Some(rules) = RULES.get() else {
+    return Cow::Borrowed(text);
+  };
+  let mut text = Cow::Borrowed(text);
+  for rule in rules {
+    let replaced = rule.regex.replace_all(&text, |caps: &Captures| {
+      let found = &caps[0];
+      match rule.check {
+        Some(check) if !check(found) => found.to_string(),
+        _ => rule.mask.to_string(),
+      }
+    });
+    if let Cow::Owned(replaced) = replaced {
+      text = Cow::Owned(replaced);
+    }
+  }
+  text
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+struct Rule {
+  regex: Regex,
+  mask: &'static str,
+  // tells a real match from a look-alike, when the regex can't
+  check: Option<fn(&str) -> bool>,
+}
+
+fn digits(text: &str) -> Vec<u32> {
+  text.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+// Luhn checksum, so order numbers and the like are left alone.
+fn is_card_number(text: &str) -> bool {
+  let digits = digits(text);
+  if !CARD_DIGITS.contains(&digits.len()) {
+    return false;
+  }
+  let sum: u32 = digits
+    .iter()
+    .rev()
+    .enumerate()
+    .map(|(i, &d)| match (i % 2 == 1, d * 2) {
+      (true, doubled) if doubled > 9 => doubled - 9,
+      (true, doubled) => doubled,
+      (false, _) => d,
+    })
+    .sum();
+  sum.is_multiple_of(10)
+}
+
+fn is_phone_number(text: &str) -> bool {
+  if !PHONE_DIGITS.contains(&digits(text).len()) {
+    return false;
+  }
+  // an international prefix or an area code, only phones have them
+  if text.starts_with('+') || text.contains('(') {
+    return true;
+  }
+  let groups: Vec<&str> = text.split([' ', '.', '-']).collect();
+  !is_date(&groups) && !is_year_range(&groups) && !is_amount(text, &groups)
+}
+
+// 2024-05-17, 17.05.2024, 05-17-2024
+fn is_date(groups: &[&str]) -> bool {
+  let number =
+    |group: &str, max: u32| group.len() <= 2 && group.parse().is_ok_and(|n| (1..=max).contains(&n));
+  let day = |group: &str| number(group, 31);
+  let month = |group: &str| number(group, 12);
+  match groups {
+    [year, m, d] if year.len() == 4 => month(m) && day(d),
+    [a, b, year] if year.len() == 4 => (day(a) && month(b)) || (month(a) && day(b)),
+    _ => false,
+  }
+}
+
+// 1999-2001
+fn is_year_range(groups: &[&str]) -> bool {
+  let year =
+    |group: &str| group.len() == 4 && group.parse().is_ok_and(|n: u32| (1000..=2999).contains(&n));
+  matches!(groups, [from, to] if year(from) && year(to))
+}
+
+// 1.250.000
+fn is_amount(text: &str, groups: &[&str]) -> bool {
+  !text.contains([' ', '-'])
+    && groups.len() > 1
+    && (1..=3).contains(&groups[0].len())
+    && groups[1..].iter().all(|group| group.len() == 3)
+}
@@ -0,0 +1,166 @@
+// ------------------------------------------------------------------
+//  Say (one-shot text to speech)
+// ------------------------------------------------------------------
+
+use crate::audio::AudioChunk;
+use crate::cancel::CancellationToken;
+use crate::config::{AgentSettings, Args};
+use crate::engine::{BuiltinTts, TextToSpeech};
+use crate::state::{AppState, GLOBAL_STATE};
+use cpal::traits::DeviceTrait;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// sample rate asked to the tts backends that can resample (opentts) when
+// writing a file
+const FILE_SAMPLE_RATE: u32 = 22050;
+
+// API
+// ------------------------------------------------------------------
+
+/// Speaks `text` (stdin when empty or "-") with the agent's tts and voice,
+/// or writes it to the `output` WAV file. Returns the exit code.
+pub fn run_say_command(text: &[String], output: Option<&str>, args: &Args) -> i32 {
+  let result = crate::config::load_agent(args).and_then(|agent| {
+    let text = match text.join(" ") {
+      t if t.trim().is_empty() || t == "-" => crate::util::read_file("-"),
+      t => t,
+    };
+    let text = crate::util::strip_special_chars(&text);
+    if text.trim().is_empty() {
+      return Err("Nothing to say".to_string());
+    }
+    crate::assets::ensure_agent_assets(std::slice::from_ref(&agent), false)?;
+    GLOBAL_STATE
+      .set(Arc::new(AppState::with_agent(
+        agent.clone(),
+        vec![agent.clone()],
+        true,
+      )))
+      .ok();
+    match output {
+      Some(path) => write_wav(&agent, &text, Path::new(path)),
+      None => play(&agent, &text, args.audio_buffer_frames),
+    }
+    .map_err(|e| e.to_string())
+  });
+  match result {
+    Ok(()) => 0,
+    Err(e) => {
+      eprintln!("❌ {}", e);
+      1
+    }
+  }
+}
+
+/// Plays the audio `produce` sends, given the sample rate of the output, on
+/// the default output device. Returns once it was all heard.
+pub fn play_with(
+  buffer_frames: u32,
+  produce: impl FnOnce(
+    u32,
+    crossbeam_channel::Sender<AudioChunk>,
+  ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  let host = cpal::default_host();
+  let (device, _stream) = crate::audio::pick_output_stream(&host)?;
+  let supported = device.default_output_config()?;
+  let config = crate::audio::output_config(&supported, buffer_frames);
+  let out_sample_rate = config.sample_rate.0;
+  let out_channels = config.channels;
+
+  let (tx_play, rx_play) = crossbeam_channel::bounded::<AudioChunk>(1);
+  let (_stop_play_tx, stop_play_rx) = crossbeam_channel::unbounded::<()>();
+  let playback_active = Arc::new(AtomicBool::new(false));
+  thread::spawn({
+    let playback_active = playback_active.clone();
+    move || {
+      crate::playback::playback_thread(
+        &crate::START_INSTANT,
+        device,
+        supported,
+        config,
+        rx_play,
+        stop_play_rx,
+        playback_active,
+        Arc::new(AtomicU64::new(0)),
+        Arc::new(AtomicBool::new(false)),
+        out_channels,
+        Arc::new(Mutex::new(1.0_f32)),
+      )
+    }
+  });
+
+  produce(out_sample_rate, tx_play)?;
+  // wait for the queued audio to be played
+  thread::sleep(Duration::from_millis(100));
+  while playback_active.load(Ordering::Relaxed) {
+    thread::sleep(Duration::from_millis(50));
+  }
+  thread::sleep(Duration::from_millis(100));
+  Ok(())
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn synthesize(
+  agent: &AgentSettings,
+  text: &str,
+  out_sample_rate: u32,
+  tx: crossbeam_channel::Sender<AudioChunk>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  let tts = BuiltinTts {
+    tts: agent.tts.clone(),
+    out_sample_rate,
+    speed: agent.voice_speed,
+  };
+  tts.speak(
+    text,
+    &agent.language,
+    &agent.voice,
+    tx,
+    &CancellationToken::new(),
+  )?;
+  Ok(())
+}
+
+fn write_wav(
+  agent: &AgentSettings,
+  text: &str,
+  path: &Path,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  let (tx, rx) = crossbeam_channel::unbounded::<AudioChunk>();
+  synthesize(agent, text, FILE_SAMPLE_RATE, tx)?;
+  let chunks: Vec<AudioChunk> = rx.try_iter().collect();
+  let Some(first) = chunks.first() else {
+    return Err(format!("tts '{}' produced no audio", agent.tts).into());
+  };
+  let spec = hound::WavSpec {
+    channels: first.channels,
+    sample_rate: first.sample_rate,
+    bits_per_sample: 16,
+    sample_format: hound::SampleFormat::Int,
+  };
+  let mut writer = hound::WavWriter::create(path, spec)?;
+  for chunk in &chunks {
+    for s in crate::audio::f32_to_i16(&chunk.data) {
+      writer.write_sample(s)?;
+    }
+  }
+  writer.finalize()?;
+  Ok(())
+}
+
+fn play(
+  agent: &AgentSettings,
+  text: &str,
+  buffer_frames: u32,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  play_with(buffer_frames, |out_sample_rate, tx_play| {
+    synthesize(agent, text, out_sample_rate, tx_play)
+  })
+}
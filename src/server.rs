@@ -0,0 +1,508 @@
+// ------------------------------------------------------------------
+//  Control server (--serve)
+// ------------------------------------------------------------------
+
+use crate::conversation::Command;
+use crate::engine::TurnEvent;
+use crate::state::{AppState, GLOBAL_STATE, TurnState};
+use base64::Engine as _;
+use crossbeam_channel::Sender;
+use serde_json::{Value, json};
+use sha1::{Digest, Sha1};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::PoisonError;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+const MAX_BODY_BYTES: usize = 64 * 1024;
+// longest line and most lines of a request head
+const MAX_HEAD_LINE_BYTES: u64 = 8 * 1024;
+const MAX_HEADERS: usize = 64;
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// websocket opcodes (RFC 6455 5.2)
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+// largest frame taken from a client, which has nothing to send but control
+// frames
+const MAX_FRAME_BYTES: u64 = 64 * 1024;
+
+// API
+// ------------------------------------------------------------------
+
+/// Address to listen on for `--serve`: a bare port listens on localhost only.
+pub fn serve_addr(value: &str) -> String {
+  if value.parse::<u16>().is_ok() {
+    format!("127.0.0.1:{}", value)
+  } else {
+    value.to_string()
+  }
+}
+
+/// Small REST API to drive a running instance:
+///   GET  /status       current agent and activity
+///   GET  /transcript   conversation history
+///   POST /say  {text}  speak a text as is, with an optional "priority":
+///                      normal, resume or important (see crate::announce)
+///   POST /ask  {text}  answer a text as if the user had said it
+///   POST /pause        toggle listening
+///   GET  /events       WebSocket stream of the turn events, as JSON
+/// Requests are turned into conversation commands, like keyboard input.
+/// Browsers can reach it from any page they show, only the pages of this
+/// machine and those of `origins` are let in (see `origin_allowed`), the
+/// Host must be the address listened on (against DNS rebinding) and the
+/// POST bodies JSON (which a page can't send without a preflight).
+pub fn server_thread(
+  addr: String,
+  origins: Vec<String>,
+  tx_cmd: Sender<Command>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  let listener = TcpListener::bind(&addr)?;
+  let bound = listener.local_addr()?;
+  crate::log::log(
+    "info",
+    &format!("Control server listening on http://{}", addr),
+  );
+  for stream in listener.incoming() {
+    let Ok(stream) = stream else { continue };
+    let tx_cmd = tx_cmd.clone();
+    let origins = origins.clone();
+    thread::spawn(move || {
+      if let Err(e) = handle_connection(stream, bound, &origins, &tx_cmd) {
+        crate::log::log("debug", &format!("Control server connection error: {}", e));
+      }
+    });
+  }
+  Ok(())
+}
+
+/// Current agent and activity, as reported by /status and `vtmate ctl status`.
+pub fn status_json(state: &AppState) -> Value {
+  let turn = state.turn.get();
+  json!({
+    "agent": *state.agent_name.lock().unwrap_or_else(PoisonError::into_inner),
+    "language": *state.language.lock().unwrap_or_else(PoisonError::into_inner),
+    "tts": *state.tts.lock().unwrap_or_else(PoisonError::into_inner),
+    "voice": *state.voice.lock().unwrap_or_else(PoisonError::into_inner),
+    "provider": *state.provider.lock().unwrap_or_else(PoisonError::into_inner),
+    "model": *state.model.lock().unwrap_or_else(PoisonError::into_inner),
+    "paused": state.recording_paused.load(Ordering::Relaxed),
+    "dnd": crate::dnd::is_active(),
+    "turn": turn.name(),
+    "thinking": turn == TurnState::Generating,
+    "speaking": turn == TurnState::Speaking,
+    "processing": matches!(turn, TurnState::Transcribing | TurnState::Generating),
+    "debate": state.debate_enabled.load(Ordering::Relaxed),
+    "queues": crate::metrics::queues_json(),
+  })
+}
+
+/// A turn event as sent to the clients: `{"type": ..., "text": ...}`.
+pub fn event_json(event: &TurnEvent) -> Value {
+  match event {
+    TurnEvent::Transcript(text) => json!({ "type": "transcript", "text": text }),
+    TurnEvent::Token(text) => json!({ "type": "token", "text": text }),
+    TurnEvent::Phrase(text) => json!({ "type": "phrase", "text": text }),
+    TurnEvent::Interrupted => json!({ "type": "interrupted" }),
+    TurnEvent::Done(text) => json!({ "type": "done", "text": text }),
+  }
+}
+
+/// The Sec-WebSocket-Accept answering a Sec-WebSocket-Key (RFC 6455).
+pub fn websocket_accept(key: &str) -> String {
+  let digest = Sha1::digest(format!("{}{}", key, WEBSOCKET_GUID).as_bytes());
+  base64::engine::general_purpose::STANDARD.encode(digest)
+}
+
+/// Whether a request with this Origin header is let in: none (not sent by a
+/// browser), a page of this machine (localhost or a loopback address) or one
+/// of `allowed`.
+pub fn origin_allowed(origin: Option<&str>, allowed: &[String]) -> bool {
+  let Some(origin) = origin else {
+    return true;
+  };
+  if allowed
+    .iter()
+    .any(|a| a.trim_end_matches('/').eq_ignore_ascii_case(origin))
+  {
+    return true;
+  }
+  match origin.split_once("://") {
+    Some(("http" | "https", host)) => is_local_host(host),
+    _ => false,
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+struct Request {
+  method: String,
+  path: String,
+  // names lowercased
+  headers: Vec<(String, String)>,
+  body: Vec<u8>,
+}
+
+impl Request {
+  fn header(&self, name: &str) -> Option<&str> {
+    self
+      .headers
+      .iter()
+      .find(|(n, _)| n == name)
+      .map(|(_, v)| v.as_str())
+  }
+}
+
+// "localhost" or a loopback address, with or without a port
+fn is_local_host(host: &str) -> bool {
+  let name = match host.strip_prefix('[') {
+    Some(v6) => v6.split(']').next().unwrap_or(""),
+    None => host.split(':').next().unwrap_or(""),
+  };
+  name.eq_ignore_ascii_case("localhost") || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+// A Host header naming the server listening on `bound`: its address, a
+// loopback one when it listens on loopback or on every interface (any address
+// then), on its port. Names other than localhost are refused, as a page whose
+// domain was pointed at this machine sends its own.
+fn host_allowed(host: &str, bound: SocketAddr) -> bool {
+  let (name, port) = match host.strip_prefix('[') {
+    Some(v6) => match v6.split_once(']') {
+      Some((name, rest)) => (name, rest.strip_prefix(':')),
+      None => return false,
+    },
+    None => match host.split_once(':') {
+      Some((name, port)) => (name, Some(port)),
+      None => (host, None),
+    },
+  };
+  if port.map_or(Ok(80), str::parse::<u16>) != Ok(bound.port()) {
+    return false;
+  }
+  let loopback = bound.ip().is_loopback() || bound.ip().is_unspecified();
+  match name.parse::<IpAddr>() {
+    Ok(ip) => ip == bound.ip() || bound.ip().is_unspecified() || (loopback && ip.is_loopback()),
+    Err(_) => loopback && name.eq_ignore_ascii_case("localhost"),
+  }
+}
+
+fn handle_connection(
+  stream: TcpStream,
+  bound: SocketAddr,
+  origins: &[String],
+  tx_cmd: &Sender<Command>,
+) -> std::io::Result<()> {
+  stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+  let req = match read_request(&stream) {
+    Ok(req) => req,
+    Err(e) => return write_response(stream, 400, &json!({ "error": e })),
+  };
+  // a page of another site, websockets aren't subject to CORS
+  if !origin_allowed(req.header("origin"), origins) {
+    return write_response(stream, 403, &json!({ "error": "origin not allowed" }));
+  }
+  if req
+    .header("host")
+    .is_some_and(|host| !host_allowed(host, bound))
+  {
+    return write_response(stream, 403, &json!({ "error": "host not allowed" }));
+  }
+  if req.path == "/events" {
+    return match req.header("sec-websocket-key") {
+      Some(key) if req.method == "GET" => stream_events(stream, key),
+      _ => write_response(
+        stream,
+        400,
+        &json!({ "error": "websocket upgrade required" }),
+      ),
+    };
+  }
+  let (status, body) = route(&req, tx_cmd);
+  write_response(stream, status, &body)
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, String> {
+  let mut reader = BufReader::new(stream);
+  let mut line = String::new();
+  read_head_line(&mut reader, &mut line)?;
+  let mut parts = line.split_whitespace();
+  let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+    return Err("malformed request line".to_string());
+  };
+  let method = method.to_string();
+  let path = target.split('?').next().unwrap_or("").to_string();
+
+  let mut headers = Vec::new();
+  for count in 0.. {
+    read_head_line(&mut reader, &mut line)?;
+    let header = line.trim_end();
+    if header.is_empty() {
+      break;
+    }
+    if count == MAX_HEADERS {
+      return Err("too many headers".to_string());
+    }
+    if let Some((name, value)) = header.split_once(':') {
+      headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+    }
+  }
+  let content_length = match headers.iter().find(|(n, _)| n == "content-length") {
+    Some((_, v)) => v
+      .parse::<usize>()
+      .map_err(|_| "invalid content-length".to_string())?,
+    None => 0,
+  };
+  if content_length > MAX_BODY_BYTES {
+    return Err("request body too large".to_string());
+  }
+  let mut body = vec![0u8; content_length];
+  reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+  Ok(Request {
+    method,
+    path,
+    headers,
+    body,
+  })
+}
+
+// A line of the request head into `line`, the size checked as it's read.
+fn read_head_line(reader: &mut impl BufRead, line: &mut String) -> Result<(), String> {
+  line.clear();
+  reader
+    .take(MAX_HEAD_LINE_BYTES)
+    .read_line(line)
+    .map_err(|e| e.to_string())?;
+  if !line.ends_with('\n') && line.len() as u64 == MAX_HEAD_LINE_BYTES {
+    return Err("request line or header too long".to_string());
+  }
+  Ok(())
+}
+
+fn route(req: &Request, tx_cmd: &Sender<Command>) -> (u16, Value) {
+  // a form or text/plain body is sent by any page without asking first
+  let json = req
+    .header("content-type")
+    .and_then(|v| v.split(';').next())
+    .is_some_and(|v| v.trim().eq_ignore_ascii_case("application/json"));
+  if req.method == "POST" && matches!(req.path.as_str(), "/say" | "/ask") && !json {
+    return (
+      415,
+      json!({ "error": "the body must be JSON (Content-Type: application/json)" }),
+    );
+  }
+  let state = GLOBAL_STATE.get().expect("AppState not initialized");
+  match (req.method.as_str(), req.path.as_str()) {
+    ("GET", "/status") => (200, status_json(state)),
+    ("GET", "/transcript") => (200, transcript_json(state)),
+    ("POST", "/say") => {
+      let Some(text) = text_of(&req.body) else {
+        return (400, json!({ "error": "missing text" }));
+      };
+      let name = priority_of(&req.body);
+      let Some(priority) = crate::announce::Priority::from_name(&name) else {
+        return (
+          400,
+          json!({ "error": format!("unknown priority '{}'", name) }),
+        );
+      };
+      // an announcement waits for do not disturb to end
+      if crate::dnd::is_active() {
+        crate::dnd::run_or_defer(move || {
+          let _ = crate::announce::push(text, priority);
+        });
+        return (202, json!({ "queued": true, "deferred": true }));
+      }
+      match crate::announce::push(text, priority) {
+        Ok(()) => (202, json!({ "queued": true })),
+        Err(e) => (503, json!({ "error": e })),
+      }
+    }
+    ("POST", "/ask") => {
+      let Some(text) = text_of(&req.body) else {
+        return (400, json!({ "error": "missing text" }));
+      };
+      match tx_cmd.send(Command::Ask(text)) {
+        Ok(()) => (202, json!({ "queued": true })),
+        Err(_) => (503, json!({ "error": "conversation is not running" })),
+      }
+    }
+    ("POST", "/pause") => {
+      let paused = !state.recording_paused.load(Ordering::Relaxed);
+      state.recording_paused.store(paused, Ordering::Relaxed);
+      (200, json!({ "paused": paused }))
+    }
+    (_, "/status" | "/transcript" | "/say" | "/ask" | "/pause") => {
+      (405, json!({ "error": "method not allowed" }))
+    }
+    _ => (404, json!({ "error": "not found" })),
+  }
+}
+
+// `{"text": "..."}`
+fn text_of(body: &[u8]) -> Option<String> {
+  let body = serde_json::from_slice::<Value>(body).ok()?;
+  let text = body.get("text")?.as_str()?.trim();
+  if text.is_empty() {
+    None
+  } else {
+    Some(text.to_string())
+  }
+}
+
+// `{"priority": "..."}`, normal when not given
+fn priority_of(body: &[u8]) -> String {
+  serde_json::from_slice::<Value>(body)
+    .ok()
+    .and_then(|v| v.get("priority")?.as_str().map(str::to_string))
+    .unwrap_or_else(|| "normal".to_string())
+}
+
+fn transcript_json(state: &AppState) -> Value {
+  let history = state
+    .conversation_history
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
+  Value::Array(
+    history
+      .iter()
+      .map(|m| json!({ "role": m.role, "content": m.content, "agent": m.agent_name }))
+      .collect(),
+  )
+}
+
+fn write_response(mut stream: TcpStream, status: u16, body: &Value) -> std::io::Result<()> {
+  let reason = match status {
+    200 => "OK",
+    202 => "Accepted",
+    400 => "Bad Request",
+    403 => "Forbidden",
+    404 => "Not Found",
+    405 => "Method Not Allowed",
+    415 => "Unsupported Media Type",
+    _ => "Service Unavailable",
+  };
+  let body = body.to_string();
+  write!(
+    stream,
+    "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+    status,
+    reason,
+    body.len(),
+    body
+  )?;
+  stream.flush()
+}
+
+// Pushes every turn event as a WebSocket (RFC 6455) text frame until the
+// client goes away. The client frames are read on a thread of their own:
+// pings are answered, a close is echoed and ends the stream.
+fn stream_events(mut stream: TcpStream, key: &str) -> std::io::Result<()> {
+  let accept = websocket_accept(key);
+  write!(
+    stream,
+    "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+    accept
+  )?;
+  stream.set_read_timeout(None)?;
+  let mut reader = stream.try_clone()?;
+  let (tx_frames, frames) = crossbeam_channel::bounded(8);
+  thread::spawn(move || {
+    // a broken frame or a closed socket drops the sender, which ends the stream
+    while let Ok(frame) = read_frame(&mut reader) {
+      let close = frame.0 == OP_CLOSE;
+      if tx_frames.send(frame).is_err() || close {
+        break;
+      }
+    }
+  });
+  let result = pump_events(&mut stream, &frames);
+  // unblocks the reader thread
+  let _ = stream.shutdown(Shutdown::Both);
+  result
+}
+
+// Writes the turn events and answers the client frames, until either side
+// closes.
+fn pump_events(
+  stream: &mut TcpStream,
+  frames: &crossbeam_channel::Receiver<(u8, Vec<u8>)>,
+) -> std::io::Result<()> {
+  let events = crate::events::subscribe();
+  loop {
+    crossbeam_channel::select! {
+      recv(events) -> event => match event {
+        Ok(event) => write_frame(stream, OP_TEXT, event_json(&event).to_string().as_bytes())?,
+        Err(_) => return Ok(()),
+      },
+      recv(frames) -> frame => match frame {
+        Ok((OP_PING, payload)) => write_frame(stream, OP_PONG, &payload)?,
+        // the status code of the client, if any, is echoed back
+        Ok((OP_CLOSE, payload)) => {
+          return write_frame(stream, OP_CLOSE, &payload[..payload.len().min(2)]);
+        }
+        Ok(_) => {}
+        Err(_) => return Ok(()),
+      },
+      // ping idle connections, a failed write means the client is gone
+      default(Duration::from_secs(30)) => write_frame(stream, OP_PING, &[])?,
+    }
+  }
+}
+
+// A client frame (opcode, payload), unmasked. InvalidData when its payload is
+// over MAX_FRAME_BYTES, before reading it.
+fn read_frame(stream: &mut impl Read) -> std::io::Result<(u8, Vec<u8>)> {
+  let mut head = [0u8; 2];
+  stream.read_exact(&mut head)?;
+  let len = match head[1] & 0x7f {
+    126 => {
+      let mut len = [0u8; 2];
+      stream.read_exact(&mut len)?;
+      u16::from_be_bytes(len) as u64
+    }
+    127 => {
+      let mut len = [0u8; 8];
+      stream.read_exact(&mut len)?;
+      u64::from_be_bytes(len)
+    }
+    len => len as u64,
+  };
+  if len > MAX_FRAME_BYTES {
+    return Err(std::io::Error::new(
+      std::io::ErrorKind::InvalidData,
+      "websocket frame too large",
+    ));
+  }
+  let mut mask = [0u8; 4];
+  if head[1] & 0x80 != 0 {
+    stream.read_exact(&mut mask)?;
+  }
+  let mut payload = vec![0u8; len as usize];
+  stream.read_exact(&mut payload)?;
+  for (i, byte) in payload.iter_mut().enumerate() {
+    *byte ^= mask[i % 4];
+  }
+  Ok((head[0] & 0x0f, payload))
+}
+
+// unmasked server frame, final fragment
+fn write_frame(stream: &mut TcpStream, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+  let mut frame = vec![0x80 | opcode];
+  let len = payload.len();
+  if len < 126 {
+    frame.push(len as u8);
+  } else if len <= u16::MAX as usize {
+    frame.push(126);
+    frame.extend_from_slice(&(len as u16).to_be_bytes());
+  } else {
+    frame.push(127);
+    frame.extend_from_slice(&(len as u64).to_be_bytes());
+  }
+  frame.extend_from_slice(payload);
+  stream.write_all(&frame)
+}
@@ -0,0 +1,81 @@
+// ------------------------------------------------------------------
+//  Service (--service, systemd notifications)
+// ------------------------------------------------------------------
+//
+// Speaks the sd_notify protocol of a `Type=notify` unit: readiness once the
+// models are loaded, watchdog pings while the worker threads are alive and
+// a stopping notice on shutdown. Without $NOTIFY_SOCKET every call is a no-op.
+
+use std::thread;
+use std::time::Duration;
+
+// API
+// ------------------------------------------------------------------
+
+/// Tells systemd the service is up (the unit leaves "activating").
+pub fn ready() {
+  notify("READY=1\nSTATUS=Listening");
+  // a unit stop shuts down like Ctrl+C, saying so first
+  crate::util::on_shutdown(|| notify("STOPPING=1\nSTATUS=Stopping"));
+}
+
+/// Pings the watchdog of the unit (WatchdogSec=) at half its timeout, as long
+/// as the supervised threads run: once one of them gave up systemd restarts
+/// the service. Blocks, returns right away when the unit has no watchdog.
+pub fn watchdog_loop() {
+  let Some(interval) = watchdog_interval() else {
+    return;
+  };
+  crate::log::log(
+    "info",
+    &format!("Pinging the systemd watchdog every {:?}", interval),
+  );
+  loop {
+    if crate::supervisor::healthy() {
+      notify("WATCHDOG=1");
+    }
+    thread::sleep(interval);
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// half of $WATCHDOG_USEC, when the watchdog is meant for this process
+fn watchdog_interval() -> Option<Duration> {
+  let usec: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+  if let Ok(pid) = std::env::var("WATCHDOG_PID")
+    && pid.parse::<u32>().ok() != Some(std::process::id())
+  {
+    return None;
+  }
+  (usec > 0).then(|| Duration::from_micros(usec / 2))
+}
+
+#[cfg(unix)]
+fn notify(message: &str) {
+  use std::os::unix::net::UnixDatagram;
+
+  let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+    return;
+  };
+  let Ok(socket) = UnixDatagram::unbound() else {
+    return;
+  };
+  // "@name" is a socket of the abstract namespace
+  let sent = match path.to_str().and_then(|p| p.strip_prefix('@')) {
+    #[cfg(target_os = "linux")]
+    Some(name) => {
+      use std::os::linux::net::SocketAddrExt;
+      std::os::unix::net::SocketAddr::from_abstract_name(name)
+        .and_then(|addr| socket.send_to_addr(message.as_bytes(), &addr))
+    }
+    _ => socket.send_to(message.as_bytes(), &path),
+  };
+  if let Err(e) = sent {
+    crate::log::log("warning", &format!("systemd notification failed: {}", e));
+  }
+}
+
+#[cfg(not(unix))]
+fn notify(_message: &str) {}
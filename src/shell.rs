@@ -0,0 +1,319 @@
+// ------------------------------------------------------------------
+//  Shell commands (whitelisted, run after a spoken confirmation)
+// ------------------------------------------------------------------
+//
+// An agent lists the commands it may run in its `shell_commands`
+// setting: "backup status: systemctl status backup; disk space: df -h".
+// The llm can ask for one of them (a tool), or the user can say
+// "run backup status". Either way nothing runs until the user answers
+// "yes" to the spoken confirmation, then the output is handed to the llm
+// to be summarized. Only utterances go through `intercept`: typed text
+// (/ask, the control socket, stdio, a chat bridge) can't confirm a command
+// nor ask for one by name.
+
+use crate::config::AgentSettings;
+use crate::state::GLOBAL_STATE;
+use serde_json::{Value, json};
+use std::io::Read;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+pub const TOOL_NAME: &str = "run_shell_command";
+
+// output handed to the llm, the rest is cut
+const MAX_OUTPUT_CHARS: usize = 4000;
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+// how often a running command is checked on
+const WAIT_POLL: Duration = Duration::from_millis(50);
+// wait for the output once the command ended, a process it started in the
+// background can keep its pipes open
+const OUTPUT_GRACE: Duration = Duration::from_secs(1);
+const YES_WORDS: &[&str] = &[
+  "yes",
+  "yeah",
+  "yep",
+  "sure",
+  "ok",
+  "okay",
+  "confirm",
+  "do it",
+  "go ahead",
+  "yes please",
+  "si",
+  "sí",
+  "oui",
+  "ja",
+  "sim",
+];
+const NO_WORDS: &[&str] = &[
+  "no", "nope", "cancel", "stop", "don't", "do not", "non", "nein", "não",
+];
+
+// command waiting for the user's answer, and whether it was asked already
+static PENDING: Mutex<Option<(ShellCommand, bool)>> = Mutex::new(None);
+// whether the llm may ask for a command in the turn being answered
+static OFFERED: AtomicBool = AtomicBool::new(true);
+
+// API
+// ------------------------------------------------------------------
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShellCommand {
+  pub name: String,
+  pub command: String,
+}
+
+/// What the conversation does with an utterance while shell commands are
+/// involved.
+pub enum Intercept {
+  /// Speak this instead of asking the llm.
+  Say(String),
+  /// The command ran: send this to the llm instead of the user's words.
+  Ask(String),
+}
+
+/// Parses a `shell_commands` setting: `name: command` entries separated by
+/// `;` (a bare command is its own name).
+pub fn parse_commands(setting: &str) -> Vec<ShellCommand> {
+  setting
+    .split(';')
+    .map(str::trim)
+    .filter(|e| !e.is_empty())
+    .map(|entry| match entry.split_once(':') {
+      Some((name, command)) if !command.trim().is_empty() => ShellCommand {
+        name: name.trim().to_string(),
+        command: command.trim().to_string(),
+      },
+      _ => ShellCommand {
+        name: entry.to_string(),
+        command: entry.to_string(),
+      },
+    })
+    .collect()
+}
+
+/// The shell tool for the current agent (none when it has no commands, or
+/// while not offered).
+pub fn tool_specs() -> Vec<Value> {
+  let commands = current_commands();
+  if commands.is_empty() || !OFFERED.load(Ordering::Relaxed) {
+    return Vec::new();
+  }
+  let names: Vec<&str> = commands.iter().map(|c| c.name.as_str()).collect();
+  vec![json!({
+    "type": "function",
+    "function": {
+      "name": TOOL_NAME,
+      "description": format!(
+        "Run one of the user's shell commands to check something on their computer: {}. The user confirms by voice first, then you get the output.",
+        names.join(", ")
+      ),
+      "parameters": {
+        "type": "object",
+        "properties": { "name": { "type": "string", "enum": names } },
+        "required": ["name"],
+      },
+    },
+  })]
+}
+
+/// The llm asked for a command: it waits for the user's confirmation.
+pub fn call_tool(arguments: &Value) -> String {
+  let name = arguments["name"].as_str().unwrap_or("");
+  if !OFFERED.load(Ordering::Relaxed) {
+    return "error: commands can't be run from this conversation".to_string();
+  }
+  let Some(command) = find(&current_commands(), name) else {
+    return format!("error: '{}' is not one of the allowed commands", name);
+  };
+  *PENDING.lock().unwrap_or_else(PoisonError::into_inner) = Some((command, false));
+  "The user is being asked to confirm, the command runs only if they say yes. Don't ask for confirmation yourself.".to_string()
+}
+
+/// The confirmation to speak after a reply that asked for a command.
+pub fn take_confirmation_prompt() -> Option<String> {
+  let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+  let (command, asked) = pending.as_mut()?;
+  if *asked {
+    return None;
+  }
+  *asked = true;
+  Some(confirmation_prompt(command))
+}
+
+/// Handles the answer to a confirmation and "run <name>" requests, for
+/// utterances only. `None` lets the utterance go to the llm as usual.
+pub fn intercept(text: &str, agent: &AgentSettings) -> Option<Intercept> {
+  let words = normalize(text);
+  let pending = PENDING
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .take();
+  if let Some((command, true)) = pending {
+    if YES_WORDS.contains(&words.as_str()) {
+      return Some(Intercept::Ask(run(&command)));
+    }
+    if NO_WORDS.contains(&words.as_str()) {
+      return Some(Intercept::Say("Cancelled.".to_string()));
+    }
+    // anything else drops the command and is a new request
+    return None;
+  }
+  let name = words.strip_prefix("run ")?;
+  let command = find(&parse_commands(&agent.shell_commands), name)?;
+  let prompt = confirmation_prompt(&command);
+  *PENDING.lock().unwrap_or_else(PoisonError::into_inner) = Some((command, true));
+  Some(Intercept::Say(prompt))
+}
+
+/// Whether the llm is offered the shell tool in the turns that follow: not
+/// while answering a chat bridge, whose members aren't the user at home.
+pub fn set_offered(offered: bool) {
+  OFFERED.store(offered, Ordering::Relaxed);
+}
+
+/// Drops the command waiting for a confirmation, when typed text comes
+/// before the answer.
+pub fn cancel_pending() {
+  PENDING
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .take();
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn current_commands() -> Vec<ShellCommand> {
+  let Some(state) = GLOBAL_STATE.get() else {
+    return Vec::new();
+  };
+  let name = state
+    .agent_name
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  state
+    .agents
+    .iter()
+    .find(|a| a.name == name)
+    .map(|a| parse_commands(&a.shell_commands))
+    .unwrap_or_default()
+}
+
+fn find(commands: &[ShellCommand], name: &str) -> Option<ShellCommand> {
+  let name = normalize(name);
+  commands
+    .iter()
+    .find(|c| normalize(&c.name) == name)
+    .cloned()
+}
+
+// lowercase words without punctuation, as transcripts come ("Yes.", "Run disk space!")
+fn normalize(text: &str) -> String {
+  text
+    .to_lowercase()
+    .split(|c: char| c.is_whitespace() || (c.is_ascii_punctuation() && c != '\''))
+    .filter(|w| !w.is_empty())
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+fn confirmation_prompt(command: &ShellCommand) -> String {
+  format!("Should I run {}? Say yes to confirm.", command.name)
+}
+
+// Runs the command and words its output for the llm to summarize.
+fn run(command: &ShellCommand) -> String {
+  crate::log::log(
+    "info",
+    &format!("Running shell command: {}", command.command),
+  );
+  let result = match execute(&command.command) {
+    Ok(Some((status, mut text))) => {
+      if text.chars().count() > MAX_OUTPUT_CHARS {
+        text = text.chars().take(MAX_OUTPUT_CHARS).collect::<String>() + "\n[output cut]";
+      }
+      let status = match status.code() {
+        Some(code) => format!("exit code {}", code),
+        None => "killed by a signal".to_string(),
+      };
+      format!("It ended with {}. Output:\n{}", status, text.trim())
+    }
+    Ok(None) => format!(
+      "It was still running after {} seconds and was stopped.",
+      COMMAND_TIMEOUT.as_secs()
+    ),
+    Err(e) => format!("It could not be started: {}", e),
+  };
+  format!(
+    "I ran the command \"{}\" (`{}`). {}\n\nTell me the result in one or two short spoken sentences.",
+    command.name, command.command, result
+  )
+}
+
+// Runs `line` in the shell, without our stdin (the terminal), and returns its
+// status with its stdout then its stderr. None when it was still running at
+// COMMAND_TIMEOUT: it's killed then.
+fn execute(line: &str) -> std::io::Result<Option<(ExitStatus, String)>> {
+  #[cfg(unix)]
+  let mut command = Command::new("sh");
+  #[cfg(unix)]
+  command.args(["-c", line]);
+  #[cfg(not(unix))]
+  let mut command = Command::new("cmd");
+  #[cfg(not(unix))]
+  command.args(["/C", line]);
+  let mut child = command
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+
+  // both pipes are read as it runs, a full one would block it
+  let (tx, rx) = crossbeam_channel::unbounded();
+  if let Some(stdout) = child.stdout.take() {
+    drain(0, stdout, tx.clone());
+  }
+  if let Some(stderr) = child.stderr.take() {
+    drain(1, stderr, tx);
+  }
+
+  let deadline = Instant::now() + COMMAND_TIMEOUT;
+  let status = loop {
+    if let Some(status) = child.try_wait()? {
+      break status;
+    }
+    if Instant::now() >= deadline {
+      let _ = child.kill();
+      let _ = child.wait();
+      return Ok(None);
+    }
+    std::thread::sleep(WAIT_POLL);
+  };
+  let mut output = [Vec::new(), Vec::new()];
+  while let Ok((index, bytes)) = rx.recv_timeout(OUTPUT_GRACE) {
+    output[index] = bytes;
+  }
+  let text = output
+    .iter()
+    .map(|bytes| String::from_utf8_lossy(bytes))
+    .collect();
+  Ok(Some((status, text)))
+}
+
+// reads `pipe` to its end on a thread, then sends it tagged with `index`
+fn drain(
+  index: usize,
+  mut pipe: impl Read + Send + 'static,
+  tx: crossbeam_channel::Sender<(usize, Vec<u8>)>,
+) {
+  std::thread::spawn(move || {
+    let mut bytes = Vec::new();
+    let _ = pipe.read_to_end(&mut bytes);
+    let _ = tx.send((index, bytes));
+  });
+}
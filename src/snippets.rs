@@ -0,0 +1,58 @@
+// ------------------------------------------------------------------
+//  Snippets ([snippet] sections, "run my standup prompt")
+// ------------------------------------------------------------------
+//
+// A long prompt is impractical to dictate every time. A snippet stores it in
+// the settings file behind a short trigger phrase: saying the trigger sends
+// the stored prompt to the llm instead, with whatever was said after the
+// trigger added to it ("standup, mention the release"). The prompt is what
+// the history keeps, so the next turns know what was asked.
+
+use crate::config::Snippet;
+use std::sync::OnceLock;
+
+static SNIPPETS: OnceLock<Vec<Snippet>> = OnceLock::new();
+
+// API
+// ------------------------------------------------------------------
+
+/// Sets the snippets of the settings file. Call once.
+pub fn init(snippets: Vec<Snippet>) {
+  let _ = SNIPPETS.set(snippets);
+}
+
+/// The prompt `text` stands for when it starts with the trigger of a
+/// snippet, the longest trigger matching.
+pub fn expand(text: &str) -> Option<String> {
+  let words = normalize(text);
+  let (snippet, trigger) = snippets()
+    .iter()
+    .flat_map(|s| s.trigger.split(';').map(move |t| (s, normalize(t))))
+    .filter(|(_, trigger)| !trigger.is_empty())
+    .filter(|(_, trigger)| words == *trigger || words.starts_with(&format!("{} ", trigger)))
+    .max_by_key(|(_, trigger)| trigger.len())?;
+  let prompt = snippet.prompt.replace("\\n", "\n");
+  let rest = words[trigger.len()..].trim();
+  crate::log::log("info", &format!("Snippet '{}' expanded", trigger));
+  Some(if rest.is_empty() {
+    prompt
+  } else {
+    format!("{}\n\n{}", prompt, rest)
+  })
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn snippets() -> &'static [Snippet] {
+  SNIPPETS.get().map(Vec::as_slice).unwrap_or(&[])
+}
+
+fn normalize(text: &str) -> String {
+  let words: String = text
+    .to_lowercase()
+    .chars()
+    .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+    .collect();
+  words.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,135 @@
+// ------------------------------------------------------------------
+//  Spelling ("spell that")
+// ------------------------------------------------------------------
+//
+// Tokens, emails and names are easy to mishear. Saying "spell that" makes
+// the agent say the last of them in its reply again letter by letter,
+// "spell that phonetically" with the NATO alphabet. The llm is not asked.
+
+const LETTER_PHRASES: [&str; 6] = [
+  "spell that",
+  "spell it",
+  "spell that out",
+  "spell it out",
+  "can you spell that",
+  "how do you spell that",
+];
+const NATO_PHRASES: [&str; 6] = [
+  "spell that phonetically",
+  "spell it phonetically",
+  "spell that in nato",
+  "spell it in nato",
+  "spell that with the nato alphabet",
+  "spell it with the nato alphabet",
+];
+
+const NATO: [&str; 26] = [
+  "Alfa", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel", "India", "Juliett",
+  "Kilo", "Lima", "Mike", "November", "Oscar", "Papa", "Quebec", "Romeo", "Sierra", "Tango",
+  "Uniform", "Victor", "Whiskey", "X-ray", "Yankee", "Zulu",
+];
+
+// API
+// ------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Spelling {
+  Letters,
+  Nato,
+}
+
+/// The spelling `text` asks for, if it's a spelling request.
+pub fn request(text: &str) -> Option<Spelling> {
+  let words: String = text
+    .to_lowercase()
+    .chars()
+    .filter(|c| c.is_alphanumeric() || c.is_whitespace())
+    .collect();
+  let words = words.split_whitespace().collect::<Vec<_>>().join(" ");
+  if LETTER_PHRASES.contains(&words.as_str()) {
+    Some(Spelling::Letters)
+  } else if NATO_PHRASES.contains(&words.as_str()) {
+    Some(Spelling::Nato)
+  } else {
+    None
+  }
+}
+
+/// The word of `reply` most likely meant by "that": the last email, address,
+/// token or identifier, else the last name, else the last word.
+pub fn entity(reply: &str) -> Option<&str> {
+  let words: Vec<&str> = reply
+    .split_whitespace()
+    .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()))
+    .filter(|w| !w.is_empty())
+    .collect();
+  words
+    .iter()
+    .rev()
+    .find(|w| is_token(w))
+    .or_else(|| {
+      words
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(i, w)| *i > 0 && w.starts_with(char::is_uppercase))
+        .map(|(_, w)| w)
+    })
+    .or(words.last())
+    .copied()
+}
+
+/// `word` said one character at a time, e.g. "j, dot, d, o, e" or
+/// "Juliett, dot, Delta, Oscar, Echo".
+pub fn spell(word: &str, spelling: Spelling) -> String {
+  let mixed_case = word.chars().any(char::is_uppercase) && word.chars().any(char::is_lowercase);
+  word
+    .chars()
+    .map(|c| match c {
+      'a'..='z' | 'A'..='Z' => {
+        let name = match spelling {
+          Spelling::Letters => c.to_ascii_uppercase().to_string(),
+          Spelling::Nato => NATO[(c.to_ascii_lowercase() as u8 - b'a') as usize].to_string(),
+        };
+        if mixed_case && c.is_ascii_uppercase() {
+          format!("capital {}", name)
+        } else {
+          name
+        }
+      }
+      c => symbol_name(c).map_or_else(|| c.to_string(), str::to_string),
+    })
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// emails, addresses, ids and identifiers: digits, symbols or odd casing
+fn is_token(word: &str) -> bool {
+  let upper = word.chars().filter(|c| c.is_uppercase()).count();
+  word
+    .chars()
+    .any(|c| c.is_ascii_digit() || symbol_name(c).is_some())
+    || upper > 1
+    || (upper == 1 && !word.starts_with(char::is_uppercase))
+}
+
+fn symbol_name(c: char) -> Option<&'static str> {
+  Some(match c {
+    '@' => "at",
+    '.' => "dot",
+    '-' => "dash",
+    '_' => "underscore",
+    '/' => "slash",
+    '\\' => "backslash",
+    ':' => "colon",
+    '+' => "plus",
+    '#' => "hash",
+    '&' => "and",
+    '=' => "equals",
+    '~' => "tilde",
+    _ => return None,
+  })
+}
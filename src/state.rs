@@ -2,18 +2,105 @@
 //  Application state
 // ------------------------------------------------------------------
 
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU32, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, OnceLock};
 
 // API
 // ------------------------------------------------------------------
 
+/// Where the conversation is. One state for every thread to go by, each
+/// moving it along as a turn goes through it (see [`Turn`]).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TurnState {
+  /// Waiting for the user
+  Idle,
+  /// The user is talking
+  Listening,
+  /// What was said is being transcribed
+  Transcribing,
+  /// The llm is answering, nothing out yet
+  Generating,
+  /// The reply is coming out: streamed, then spoken until the voice runs out
+  Speaking,
+  /// The reply was cut short (voice, key, `vtmate ctl interrupt`)
+  Interrupted,
+}
+
+impl TurnState {
+  const ALL: [TurnState; 6] = [
+    TurnState::Idle,
+    TurnState::Listening,
+    TurnState::Transcribing,
+    TurnState::Generating,
+    TurnState::Speaking,
+    TurnState::Interrupted,
+  ];
+
+  /// A turn in progress, the user talking included.
+  pub fn is_busy(self) -> bool {
+    !matches!(self, TurnState::Idle | TurnState::Interrupted)
+  }
+
+  pub fn name(self) -> &'static str {
+    match self {
+      TurnState::Idle => "idle",
+      TurnState::Listening => "listening",
+      TurnState::Transcribing => "transcribing",
+      TurnState::Generating => "generating",
+      TurnState::Speaking => "speaking",
+      TurnState::Interrupted => "interrupted",
+    }
+  }
+}
+
+/// The current [`TurnState`], shared by the threads.
+#[derive(Debug)]
+pub struct Turn(AtomicU8);
+
+impl Turn {
+  pub fn new() -> Self {
+    Self(AtomicU8::new(TurnState::Idle as u8))
+  }
+
+  pub fn get(&self) -> TurnState {
+    TurnState::ALL[self.0.load(Ordering::SeqCst) as usize]
+  }
+
+  pub fn set(&self, to: TurnState) {
+    let from = TurnState::ALL[self.0.swap(to as u8, Ordering::SeqCst) as usize];
+    log_move(from, to);
+  }
+
+  /// Moves to `to` only from one of `from`, as another thread may have moved
+  /// it on already. Returns whether it did.
+  pub fn advance(&self, from: &[TurnState], to: TurnState) -> bool {
+    let moved = self
+      .0
+      .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+        from
+          .contains(&TurnState::ALL[current as usize])
+          .then_some(to as u8)
+      });
+    match moved {
+      Ok(from) => {
+        log_move(TurnState::ALL[from as usize], to);
+        true
+      }
+      Err(_) => false,
+    }
+  }
+}
+
+impl Default for Turn {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 #[derive(Clone, Debug)]
 pub struct UiState {
-  pub thinking: Arc<AtomicBool>,
-  pub playing: Arc<AtomicBool>,
-  pub agent_speaking: Arc<AtomicBool>, // voice activity flag
-  pub peak: Arc<Mutex<f32>>,           // current audio peak
+  pub peak: Arc<Mutex<f32>>, // current audio peak
   pub spinner_index: usize,
   pub quiet: bool,
 }
@@ -45,9 +132,9 @@ pub struct AppState {
   pub system_prompt: Arc<Mutex<String>>,
   pub playback: PlaybackState,
   pub status_line: Arc<Mutex<String>>,
-  pub interrupt_counter: Arc<AtomicU64>,
+  pub interrupter: crate::cancel::Interrupter,
   pub recording_paused: Arc<AtomicBool>,
-  pub processing_response: Arc<AtomicBool>,
+  pub turn: Turn,
   pub ptt: Arc<AtomicBool>,
   pub sound_threshold_peak: Arc<Mutex<f32>>,
   pub end_silence_ms: Arc<Mutex<u64>>,
@@ -78,15 +165,12 @@ impl AppState {
       model: Arc::new(Mutex::new(String::new())),
       system_prompt: Arc::new(Mutex::new(String::new())),
       ui: UiState {
-        thinking: Arc::new(AtomicBool::new(false)),
-        playing: Arc::new(AtomicBool::new(false)),
-        agent_speaking: Arc::new(AtomicBool::new(false)), // tts synthesizing
         peak: Arc::new(Mutex::new(0.0)),
         spinner_index: 0,
         quiet: false,
       },
       speed: AtomicU32::new(12),
-      conversation_history: std::sync::Arc::new(std::sync::Mutex::new(Vec::new())),
+      conversation_history: Arc::new(Mutex::new(crate::history::History::default())),
       agent_name: Arc::new(Mutex::new(String::new())),
       agents: Arc::new(Vec::new()),
       playback: PlaybackState {
@@ -96,9 +180,9 @@ impl AppState {
         volume: Arc::new(Mutex::new(1.0_f32)),
       },
       status_line: Arc::new(Mutex::new(String::new())),
-      interrupt_counter: Arc::new(AtomicU64::new(0)),
+      interrupter: crate::cancel::Interrupter::new(),
       recording_paused: Arc::new(AtomicBool::new(false)),
-      processing_response: Arc::new(AtomicBool::new(false)),
+      turn: Turn::new(),
       ptt: Arc::new(AtomicBool::new(false)),
       sound_threshold_peak: Arc::new(Mutex::new(0.0)),
       end_silence_ms: Arc::new(Mutex::new(0)),
@@ -125,18 +209,39 @@ impl AppState {
   ) -> Self {
     let mut state = Self::new();
     state.ui.quiet = quiet;
-    *state.voice.lock().unwrap() = settings.voice.clone();
-    *state.agent_name.lock().unwrap() = settings.name.clone();
-    *state.tts.lock().unwrap() = settings.tts.clone();
-    *state.language.lock().unwrap() = settings.language.clone();
-    *state.provider.lock().unwrap() = settings.provider.clone();
-    *state.baseurl.lock().unwrap() = settings.baseurl.clone();
-    *state.model.lock().unwrap() = settings.model.clone();
-    *state.system_prompt.lock().unwrap() = settings.system_prompt.clone();
+    *state.voice.lock().unwrap_or_else(PoisonError::into_inner) = settings.voice.clone();
+    *state
+      .agent_name
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = settings.name.clone();
+    *state.tts.lock().unwrap_or_else(PoisonError::into_inner) = settings.tts.clone();
+    *state
+      .language
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = settings.language.clone();
+    *state
+      .provider
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = settings.provider.clone();
+    *state.baseurl.lock().unwrap_or_else(PoisonError::into_inner) = settings.baseurl.clone();
+    *state.model.lock().unwrap_or_else(PoisonError::into_inner) = settings.model.clone();
+    *state
+      .system_prompt
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = settings.system_prompt.clone();
     state.ptt.store(settings.ptt, Ordering::Relaxed);
-    *state.sound_threshold_peak.lock().unwrap() = settings.sound_threshold_peak;
-    *state.end_silence_ms.lock().unwrap() = settings.end_silence_ms;
-    *state.whisper_model_path.lock().unwrap() = settings.whisper_model_path.clone();
+    *state
+      .sound_threshold_peak
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = settings.sound_threshold_peak;
+    *state
+      .end_silence_ms
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = settings.end_silence_ms;
+    *state
+      .whisper_model_path
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = settings.whisper_model_path.clone();
     state
       .speed
       .store((settings.voice_speed * 10.0) as u32, Ordering::Relaxed);
@@ -144,13 +249,55 @@ impl AppState {
     state
   }
 
+  /// Makes `agent` the current one: its voice, llm, prompt and modes.
+  pub fn use_agent(&self, agent: &crate::config::AgentSettings) {
+    *self.voice.lock().unwrap_or_else(PoisonError::into_inner) = agent.voice.clone();
+    *self
+      .agent_name
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = agent.name.clone();
+    *self.tts.lock().unwrap_or_else(PoisonError::into_inner) = agent.tts.clone();
+    *self.language.lock().unwrap_or_else(PoisonError::into_inner) = agent.language.clone();
+    *self.provider.lock().unwrap_or_else(PoisonError::into_inner) = agent.provider.clone();
+    *self.baseurl.lock().unwrap_or_else(PoisonError::into_inner) = agent.baseurl.clone();
+    *self.model.lock().unwrap_or_else(PoisonError::into_inner) = agent.model.clone();
+    *self
+      .system_prompt
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = agent.system_prompt.clone();
+    self.ptt.store(agent.ptt, Ordering::Relaxed);
+    self
+      .speed
+      .store((agent.voice_speed * 10.0) as u32, Ordering::Relaxed);
+    crate::personas::clear();
+    crate::tts::clear_backend();
+  }
+
   pub fn reset_conversation(&self) {
-    self.conversation_history.lock().unwrap().clear();
-    *self.save_path.lock().unwrap() = None;
-    *self.start_date.lock().unwrap() = String::new();
+    self
+      .conversation_history
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clear();
+    *self
+      .save_path
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = None;
+    *self
+      .start_date
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner) = String::new();
   }
 }
 
+/// Moves the turn along (see [`Turn::advance`]) from a thread without the
+/// app state at hand. Nothing happens before it exists, as in tests.
+pub fn advance_turn(from: &[TurnState], to: TurnState) -> bool {
+  GLOBAL_STATE
+    .get()
+    .is_some_and(|state| state.turn.advance(from, to))
+}
+
 pub fn get_speed() -> f32 {
   let state = GLOBAL_STATE.get().expect("AppState not initialized");
   state.speed.load(Ordering::Relaxed) as f32 / 10.0
@@ -173,3 +320,12 @@ pub fn decrease_voice_speed() {
     state.speed.store(cur, Ordering::Relaxed);
   }
 }
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn log_move(from: TurnState, to: TurnState) {
+  if from != to {
+    crate::log::log("debug", &format!("Turn: {} -> {}", from.name(), to.name()));
+  }
+}
@@ -0,0 +1,96 @@
+// ------------------------------------------------------------------
+//  JSONL stdio protocol (--stdio-protocol)
+// ------------------------------------------------------------------
+
+use crate::conversation::Command;
+use crossbeam_channel::Sender;
+use serde_json::{Value, json};
+use std::io::{BufRead, Write};
+use std::sync::PoisonError;
+use std::sync::{Mutex, OnceLock};
+
+static OUT: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+// API
+// ------------------------------------------------------------------
+
+/// Keeps stdout for the protocol: from here on anything else printed
+/// (download progress, library output) goes to stderr instead.
+pub fn take_stdout() {
+  let _ = OUT.set(Mutex::new(protocol_output()));
+}
+
+/// Writes one protocol line to stdout.
+pub fn write_line(value: &Value) {
+  let Some(out) = OUT.get() else { return };
+  let mut out = out.lock().unwrap_or_else(PoisonError::into_inner);
+  let _ = writeln!(out, "{}", value);
+  let _ = out.flush();
+}
+
+/// Forwards every turn event to stdout, one JSON object per line.
+pub fn events_thread() {
+  let events = crate::events::subscribe();
+  write_line(&json!({ "type": "ready" }));
+  for event in events {
+    write_line(&crate::server::event_json(&event));
+  }
+}
+
+/// Reads one JSON command per line from stdin: the control socket commands
+/// (status, pause, resume, say, ask) plus interrupt and quit. Every command
+/// gets a `result` line back. Returns when stdin is closed or on quit.
+pub fn commands_thread(tx_cmd: Sender<Command>, stop_play_tx: Sender<()>) {
+  let stdin = std::io::stdin();
+  for line in stdin.lock().lines() {
+    let Ok(line) = line else { break };
+    if line.trim().is_empty() {
+      continue;
+    }
+    let req = match serde_json::from_str::<Value>(&line) {
+      Ok(req) => req,
+      Err(e) => {
+        write_line(&json!({ "type": "result", "ok": false, "error": e.to_string() }));
+        continue;
+      }
+    };
+    let mut reply = match req["cmd"].as_str() {
+      Some("quit") => {
+        write_line(&json!({ "type": "result", "ok": true }));
+        return;
+      }
+      Some("interrupt") => {
+        crate::control::interrupt(&stop_play_tx);
+        json!({ "ok": true })
+      }
+      _ => crate::control::handle_request(&req, &tx_cmd),
+    };
+    reply["type"] = json!("result");
+    if let Some(id) = req.get("id") {
+      reply["id"] = id.clone();
+    }
+    write_line(&reply);
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// a copy of the real stdout, with fd 1 pointed at stderr
+#[cfg(unix)]
+fn protocol_output() -> Box<dyn Write + Send> {
+  use std::os::fd::FromRawFd;
+  // SAFETY: plain fd duplication, the new fd is owned by the File
+  unsafe {
+    let fd = libc::dup(libc::STDOUT_FILENO);
+    if fd < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+      return Box::new(std::io::stdout());
+    }
+    Box::new(std::fs::File::from_raw_fd(fd))
+  }
+}
+
+#[cfg(not(unix))]
+fn protocol_output() -> Box<dyn Write + Send> {
+  Box::new(std::io::stdout())
+}
@@ -2,37 +2,291 @@
 //  STT - Speech to Text
 // ------------------------------------------------------------------
 
+#[cfg(feature = "whisper")]
 use crate::audio;
+use crate::audio::AudioChunk;
+#[cfg(feature = "whisper")]
+use crate::error::SttError;
+use crate::state::{GLOBAL_STATE, TurnState};
+use crossbeam_channel::{Receiver, Sender};
+use std::sync::PoisonError;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::time::SystemTime;
+#[cfg(feature = "whisper")]
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContext};
 
+/// Speech recognition context, a placeholder when built without whisper.
+#[cfg(feature = "whisper")]
+pub type SttContext = WhisperContext;
+#[cfg(not(feature = "whisper"))]
+pub struct SttContext;
+
+// the shared context, unloaded while idle in low power mode
+static WHISPER_CTX: Mutex<Option<Arc<SttContext>>> = Mutex::new(None);
+// its model, to load it back
+static WHISPER_MODEL: OnceLock<String> = OnceLock::new();
+
+// transcription started on a probable end of speech (one at a time)
+static SPECULATION: Mutex<Option<Speculation>> = Mutex::new(None);
+static SPECULATION_DONE: Condvar = Condvar::new();
+// longest wait for a running speculation before transcribing again
+const SPECULATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+// utterances longer than this are transcribed in segments cut in their
+// quietest stretches: a single whisper call on a long one times out on slow
+// hardware
+const LONG_UTTERANCE_SECS: usize = 30;
+// bounds of a segment, whisper works on 30 s windows
+const MIN_SEGMENT_SECS: usize = 10;
+const MAX_SEGMENT_SECS: usize = 25;
+// a segment ends in the middle of the quietest stretch this long
+const PAUSE_WINDOW_MS: usize = 200;
+
 // API
 // ------------------------------------------------------------------
 
+/// An utterance and what was said in it.
+pub struct Transcript {
+  pub utt: AudioChunk,
+  pub text: String,
+  /// Mean probability of the transcribed tokens (0 to 1), None without whisper.
+  pub confidence: Option<f32>,
+  // when the transcription ran, reported in the trace of the turn
+  started: SystemTime,
+  finished: SystemTime,
+}
+
+impl Transcript {
+  /// How long the transcription took.
+  pub fn duration(&self) -> std::time::Duration {
+    self
+      .finished
+      .duration_since(self.started)
+      .unwrap_or_default()
+  }
+
+  /// Records the transcription as the stt stage of the current turn.
+  pub fn trace(&self) {
+    crate::traces::record(
+      "stt",
+      self.started,
+      self.finished,
+      &[("stt.chars", self.text.chars().count().into())],
+    );
+  }
+}
+
+/// Transcribes the utterances in the order they were said, off the
+/// conversation thread: it keeps handling commands and interrupts while
+/// whisper runs. Blocks.
+pub fn transcribe_thread(
+  model_path: String,
+  rx_utt: Receiver<AudioChunk>,
+  tx_text: Sender<Transcript>,
+) {
+  init_whisper_context(&model_path);
+  // stands in for the model while idle in low power mode and in fast mode
+  let tiny_path = crate::config::resolved_whisper_model_path("");
+  let mut tiny: Option<Arc<SttContext>> = None;
+  // why the tiny model didn't load, it isn't tried again
+  let mut tiny_error: Option<String> = None;
+  // once fast mode was used both models stay loaded, to switch at once
+  let mut keep_tiny = false;
+  for utt in rx_utt {
+    let gate = crate::speaker::gate(&utt);
+    if gate == crate::speaker::Gate::Drop {
+      crate::ignored::record(crate::ignored::Reason::OtherSpeaker);
+      crate::state::advance_turn(&[TurnState::Transcribing], TurnState::Idle);
+      continue;
+    }
+    keep_tiny |= crate::fast::is_active();
+    let ctx = if (crate::power::is_idle() || crate::fast::is_active()) && model_path != tiny_path {
+      if tiny.is_none() && tiny_error.is_none() {
+        match new_whisper_context(&tiny_path) {
+          Ok(ctx) => tiny = Some(Arc::new(ctx)),
+          Err(e) => {
+            crate::log::log("warning", &format!("Tiny whisper model not loaded: {}", e));
+            tiny_error = Some(e.to_string());
+          }
+        }
+      }
+      match (&tiny, &tiny_error) {
+        (Some(tiny), _) => tiny.clone(),
+        // fast mode can't do without it: turned off, the utterance is said again
+        (None, Some(e)) if crate::fast::is_active() => {
+          crate::fast::set_active(false);
+          crate::log::error_panel(
+            &format!(
+              "Fast mode turned off, the tiny whisper model didn't load: {}",
+              e
+            ),
+            Some("Download it with: vtmate assets pull whisper-tiny"),
+          );
+          crate::state::advance_turn(&[TurnState::Transcribing], TurnState::Idle);
+          continue;
+        }
+        // idle in low power mode, the model of the agent is loaded back
+        _ => init_whisper_context(&model_path),
+      }
+    } else {
+      if !keep_tiny {
+        tiny = None;
+      }
+      init_whisper_context(&model_path)
+    };
+    crate::power::touch();
+    crate::earcons::play(crate::earcons::Earcon::Accepted);
+    let started = SystemTime::now();
+    // transcribed ahead on a probable end of speech, unless more was said
+    let (text, confidence) = match take_speculation(&utt) {
+      Some(scored) => scored,
+      None => {
+        let language = GLOBAL_STATE
+          .get()
+          .map(|s| {
+            s.language
+              .lock()
+              .unwrap_or_else(PoisonError::into_inner)
+              .clone()
+          })
+          .unwrap_or_default();
+        let mono = crate::audio::convert_to_mono(&utt);
+        // a long one shows its text as its segments are transcribed
+        let partial = |text: &str| crate::log::partial_transcript(text);
+        let transcribe =
+          |part: &[f32]| whisper_transcribe_scored(&ctx, part, utt.sample_rate, &language);
+        match transcribe_segments(&mono, utt.sample_rate, transcribe, partial) {
+          Ok(scored) => scored,
+          Err(e) => {
+            crate::error::report(&e);
+            crate::state::advance_turn(&[TurnState::Transcribing], TurnState::Idle);
+            continue;
+          }
+        }
+      }
+    };
+    let text = match gate {
+      crate::speaker::Gate::Tag => format!("{} {}", crate::speaker::OTHER_SPEAKER_TAG, text),
+      _ => text,
+    };
+    let transcript = Transcript {
+      utt,
+      text,
+      confidence,
+      started,
+      finished: SystemTime::now(),
+    };
+    if tx_text.send(transcript).is_err() {
+      break;
+    }
+  }
+}
+
+/// Initialise the Whisper context once, performing a warm‑up.
+#[cfg(feature = "whisper")]
+pub fn init_whisper_context(model_path: &str) -> Arc<SttContext> {
+  WHISPER_MODEL.set(model_path.to_string()).ok();
+  WHISPER_CTX
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .get_or_insert_with(|| {
+      let ctx = WhisperContext::new_with_params(model_path, Default::default())
+        .expect("Failed to create WhisperContext");
+      // Perform warm‑up to load the model into memory
+      whisper_warmup(model_path).expect("Whisper warm‑up failed");
+      Arc::new(ctx)
+    })
+    .clone()
+}
+
+#[cfg(not(feature = "whisper"))]
+pub fn init_whisper_context(model_path: &str) -> Arc<SttContext> {
+  WHISPER_MODEL.set(model_path.to_string()).ok();
+  WHISPER_CTX
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .get_or_insert_with(|| Arc::new(SttContext))
+    .clone()
+}
+
+/// Unloads the shared context (low power mode), unless its model is the
+/// tiny one that stands in for it.
+pub fn suspend() {
+  let tiny_path = crate::config::resolved_whisper_model_path("");
+  if WHISPER_MODEL.get().is_some_and(|model| *model != tiny_path) {
+    WHISPER_CTX
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .take();
+  }
+}
+
+/// Loads back in the background what `suspend` unloaded.
+pub fn resume() {
+  let Some(model) = WHISPER_MODEL.get() else {
+    return;
+  };
+  if WHISPER_CTX
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .is_none()
+  {
+    std::thread::spawn(move || init_whisper_context(model));
+  }
+}
+
+/// A Whisper context of its own, outside the shared one (to compare models).
+#[cfg(feature = "whisper")]
+pub fn new_whisper_context(model_path: &str) -> crate::error::Result<SttContext> {
+  if !std::path::Path::new(model_path).is_file() {
+    return Err(SttError::ModelMissing(model_path.to_string()).into());
+  }
+  Ok(WhisperContext::new_with_params(model_path, Default::default()).map_err(whisper_error)?)
+}
+
+#[cfg(not(feature = "whisper"))]
+pub fn new_whisper_context(_model_path: &str) -> crate::error::Result<SttContext> {
+  Ok(SttContext)
+}
+
 /// Warm‑up helper for Whisper
 /// Call this once at startup to load the model and perform a no‑op
 /// inference to cache the model into memory.
-pub fn whisper_warmup(
-  whisper_model_path: &str,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+#[cfg(feature = "whisper")]
+pub fn whisper_warmup(whisper_model_path: &str) -> crate::error::Result<()> {
   if !std::path::Path::new(whisper_model_path).is_file() {
-    return Err(format!("Whisper model not found: {}", whisper_model_path).into());
+    return Err(SttError::ModelMissing(whisper_model_path.to_string()).into());
   }
-  let ctx = WhisperContext::new_with_params(whisper_model_path, Default::default())?;
+  let ctx = WhisperContext::new_with_params(whisper_model_path, Default::default())
+    .map_err(whisper_error)?;
   let mut state = ctx.create_state().expect("failed to create state");
   let warmup = vec![0.0f32; 16000]; // 1.0s @ 16kHz
-  state.full(
-    FullParams::new(SamplingStrategy::Greedy { best_of: 1 }),
-    &warmup,
-  )?;
+  state
+    .full(
+      FullParams::new(SamplingStrategy::Greedy { best_of: 1 }),
+      &warmup,
+    )
+    .map_err(whisper_error)?;
   Ok(())
 }
 
 pub fn whisper_transcribe_with_ctx(
-  ctx: &WhisperContext,
+  ctx: &SttContext,
   pcm_mono_f32: &[f32],
   sample_rate: u32,
   language: &str,
-) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+) -> crate::error::Result<String> {
+  whisper_transcribe_scored(ctx, pcm_mono_f32, sample_rate, language).map(|(text, _)| text)
+}
+
+/// Like `whisper_transcribe_with_ctx`, along with the mean probability of
+/// the transcribed tokens (None when nothing was transcribed).
+#[cfg(feature = "whisper")]
+pub fn whisper_transcribe_scored(
+  ctx: &SttContext,
+  pcm_mono_f32: &[f32],
+  sample_rate: u32,
+  language: &str,
+) -> crate::error::Result<(String, Option<f32>)> {
   // Ensure bounded samples (optional if already normalized)
   let mono: Vec<f32> = pcm_mono_f32.iter().map(|s| s.clamp(-1.0, 1.0)).collect();
 
@@ -45,10 +299,10 @@ pub fn whisper_transcribe_with_ctx(
 
   // Guard against too-short audio
   if mono_16k.len() < 1920 {
-    return Ok(String::new());
+    return Ok((String::new(), None));
   }
 
-  let mut state = ctx.create_state()?;
+  let mut state = ctx.create_state().map_err(whisper_error)?;
 
   let mut params = FullParams::new(SamplingStrategy::BeamSearch {
     beam_size: 5,
@@ -63,20 +317,187 @@ pub fn whisper_transcribe_with_ctx(
 
   state
     .full(params, &mono_16k)
-    .map_err(|e| format!("Inference failed: {:?}", e))?;
+    .map_err(|e| SttError::Whisper(format!("Inference failed: {:?}", e)))?;
 
   let mut result = String::new();
+  let (mut prob_sum, mut tokens) = (0.0f32, 0usize);
   let seg_count = state.full_n_segments();
   for i in 0..seg_count {
     let seg = state
       .get_segment(i)
-      .ok_or_else(|| format!("Segment {} out of range", i))?;
+      .ok_or_else(|| SttError::Whisper(format!("Segment {} out of range", i)))?;
     let seg_text = seg
       .to_str_lossy()
-      .map_err(|e| format!("Failed to get segment text: {:?}", e))?;
+      .map_err(|e| SttError::Whisper(format!("Failed to get segment text: {:?}", e)))?;
     result.push_str(&seg_text);
     result.push(' ');
+    for t in 0..seg.n_tokens() {
+      if let Some(token) = seg.get_token(t) {
+        prob_sum += token.token_probability();
+        tokens += 1;
+      }
+    }
+  }
+
+  let confidence = (tokens > 0).then(|| prob_sum / tokens as f32);
+  Ok((result.trim_end().to_string(), confidence))
+}
+
+// Without whisper nothing is transcribed, utterances are dropped as silence
+#[cfg(not(feature = "whisper"))]
+pub fn whisper_transcribe_scored(
+  _ctx: &SttContext,
+  _pcm_mono_f32: &[f32],
+  _sample_rate: u32,
+  _language: &str,
+) -> crate::error::Result<(String, Option<f32>)> {
+  Ok((String::new(), None))
+}
+
+/// Where a long utterance (mono samples at `sample_rate`) is cut to be
+/// transcribed in segments: in the middle of its quietest PAUSE_WINDOW_MS
+/// (by energy, not a pause whisper found), every MAX_SEGMENT_SECS at most.
+/// The whole of it when it isn't long.
+pub fn segments(mono: &[f32], sample_rate: u32) -> Vec<std::ops::Range<usize>> {
+  let rate = sample_rate.max(1) as usize;
+  let long = mono.len() > LONG_UTTERANCE_SECS * rate;
+  let window = (rate * PAUSE_WINDOW_MS / 1000).max(1);
+  let energy = |at: usize| mono[at..at + window].iter().map(|s| s * s).sum::<f32>();
+  let mut segments = Vec::new();
+  let mut start = 0;
+  while long && mono.len() - start > MAX_SEGMENT_SECS * rate {
+    let end = start + MAX_SEGMENT_SECS * rate;
+    let cut = (start + MIN_SEGMENT_SECS * rate..end - window)
+      .step_by((window / 2).max(1))
+      .min_by(|a, b| energy(*a).total_cmp(&energy(*b)))
+      .map_or(end, |at| at + window / 2);
+    segments.push(start..cut);
+    start = cut;
+  }
+  segments.push(start..mono.len());
+  segments
+}
+
+/// Transcribes `mono` with `transcribe` a segment at a time (see
+/// `segments`), passing the text of each one of a long utterance to
+/// `partial` as it's done. The texts are joined, the confidence is the mean
+/// of the segments'.
+pub fn transcribe_segments(
+  mono: &[f32],
+  sample_rate: u32,
+  mut transcribe: impl FnMut(&[f32]) -> crate::error::Result<(String, Option<f32>)>,
+  mut partial: impl FnMut(&str),
+) -> crate::error::Result<(String, Option<f32>)> {
+  let segments = segments(mono, sample_rate);
+  if segments.len() == 1 {
+    return transcribe(mono);
+  }
+  crate::log::log(
+    "info",
+    &format!(
+      "Long utterance ({}s), transcribed in {} segments",
+      mono.len() / sample_rate.max(1) as usize,
+      segments.len()
+    ),
+  );
+  let mut text = String::new();
+  let mut confidences = Vec::new();
+  for range in segments {
+    let (part, confidence) = transcribe(&mono[range])?;
+    confidences.extend(confidence);
+    if !part.is_empty() {
+      if !text.is_empty() {
+        text.push(' ');
+      }
+      text.push_str(&part);
+      partial(&part);
+    }
   }
+  let confidence =
+    (!confidences.is_empty()).then(|| confidences.iter().sum::<f32>() / confidences.len() as f32);
+  Ok((text, confidence))
+}
+
+/// Transcribes the utterance so far, before its end is confirmed, with the
+/// shared context. Blocks; the transcript is picked up by
+/// `take_speculation` if the utterance ends with nothing louder than
+/// `vad_thresh` after it. Not in fast mode, the tiny model is quick enough.
+pub fn speculate(utt: AudioChunk, vad_thresh: f32, language: &str) {
+  if crate::fast::is_active() {
+    return;
+  }
+  let Some(ctx) = WHISPER_CTX
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone()
+  else {
+    return;
+  };
+  let audio = utt.data.clone();
+  *SPECULATION.lock().unwrap_or_else(PoisonError::into_inner) = Some(Speculation {
+    audio: audio.clone(),
+    vad_thresh,
+    text: None,
+    done: false,
+  });
+  let mono = crate::audio::convert_to_mono(&utt);
+  let transcribe = |part: &[f32]| whisper_transcribe_scored(&ctx, part, utt.sample_rate, language);
+  let text = transcribe_segments(&mono, utt.sample_rate, transcribe, |_| {}).ok();
+  let mut spec = SPECULATION.lock().unwrap_or_else(PoisonError::into_inner);
+  // replaced meanwhile by a newer one
+  if let Some(spec) = spec.as_mut().filter(|s| Arc::ptr_eq(&s.audio, &audio)) {
+    spec.text = text;
+    spec.done = true;
+  }
+  SPECULATION_DONE.notify_all();
+}
+
+/// The speculative transcript of `utt`, when it was started on this same
+/// utterance and nothing was said after (waits for it if still running).
+/// None means `utt` has to be transcribed.
+pub fn take_speculation(utt: &AudioChunk) -> Option<(String, Option<f32>)> {
+  let mut spec = SPECULATION.lock().unwrap_or_else(PoisonError::into_inner);
+  loop {
+    let s = spec.as_ref()?;
+    if !s.matches(utt) {
+      return None;
+    }
+    if s.done {
+      return spec.take().and_then(|s| s.text);
+    }
+    let (guard, wait) = SPECULATION_DONE
+      .wait_timeout(spec, SPECULATION_TIMEOUT)
+      .unwrap_or_else(PoisonError::into_inner);
+    if wait.timed_out() {
+      return None;
+    }
+    spec = guard;
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+struct Speculation {
+  // the utterance audio when it started
+  audio: Arc<[f32]>,
+  vad_thresh: f32,
+  // the transcript and its confidence
+  text: Option<(String, Option<f32>)>,
+  done: bool,
+}
+
+impl Speculation {
+  // `utt` begins with the speculated audio and is silent after it
+  fn matches(&self, utt: &AudioChunk) -> bool {
+    let n = self.audio.len();
+    utt.data.len() >= n
+      && utt.data[..n] == self.audio[..]
+      && utt.data[n..].iter().all(|s| s.abs() < self.vad_thresh)
+  }
+}
 
-  Ok(result.trim_end().to_string())
+#[cfg(feature = "whisper")]
+fn whisper_error(e: whisper_rs::WhisperError) -> SttError {
+  SttError::Whisper(e.to_string())
 }
@@ -0,0 +1,209 @@
+// ------------------------------------------------------------------
+//  Voice styles (--voice-styles)
+// ------------------------------------------------------------------
+//
+// With --voice-styles the llm is asked to mark how a sentence is to be said
+// with a tag in front of it: "[whisper] It's a secret." The tag is never
+// shown nor spoken, it changes the speed and the loudness of the voice from
+// there to the end of the phrase (or the next tag). kokoro and supersonic2
+// honor both, opentts neither: its replies only lose the tags.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// tag, speed and gain relative to the voice
+const STYLES: [(&str, f32, f32); 8] = [
+  ("whisper", 0.9, 0.5),
+  ("soft", 0.95, 0.75),
+  ("calm", 0.9, 0.9),
+  ("sad", 0.85, 0.8),
+  ("serious", 0.95, 1.0),
+  ("happy", 1.08, 1.1),
+  ("excited", 1.15, 1.2),
+  ("normal", 1.0, 1.0),
+];
+// the longest tag, "[excited]"
+const MAX_TAG_LEN: usize = 9;
+const INSTRUCTION: &str = "You may start a sentence with one of these tags to set how it is said \
+aloud: [whisper], [soft], [calm], [sad], [serious], [happy], [excited], [normal]. Use them \
+sparingly, only where the tone really changes, and never explain them.";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// API
+// ------------------------------------------------------------------
+
+/// How a run of a phrase is said, relative to the voice.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+  pub speed: f32,
+  pub gain: f32,
+}
+
+impl Default for Style {
+  fn default() -> Self {
+    Self {
+      speed: 1.0,
+      gain: 1.0,
+    }
+  }
+}
+
+pub fn set_enabled(enabled: bool) {
+  ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+  ENABLED.load(Ordering::Relaxed)
+}
+
+/// `prompt` with the tags explained, when the styles are on.
+pub fn system_prompt(prompt: &str) -> String {
+  if is_enabled() {
+    format!("{}\n\n{}", prompt, INSTRUCTION)
+  } else {
+    prompt.to_string()
+  }
+}
+
+/// The runs of `phrase` to speak one after the other, without their tags,
+/// with their style. The phrase as a single run when it has no tag.
+pub fn runs(phrase: &str) -> Vec<(String, Style)> {
+  if !is_enabled() || find_tag(phrase).is_none() {
+    return vec![(phrase.to_string(), Style::default())];
+  }
+  let mut runs: Vec<(String, Style)> = Vec::new();
+  let mut style = Style::default();
+  let mut rest = phrase;
+  while let Some((at, len, next)) = find_tag(rest) {
+    push_run(&mut runs, &rest[..at], style);
+    style = next;
+    rest = &rest[at + len..];
+  }
+  push_run(&mut runs, rest, style);
+  runs
+}
+
+/// `text` without its tags.
+pub fn strip(text: &str) -> String {
+  if !is_enabled() {
+    return text.to_string();
+  }
+  let mut result = String::new();
+  let mut rest = text;
+  while let Some((at, len, _)) = find_tag(rest) {
+    result.push_str(&rest[..at]);
+    rest = &rest[at + len..];
+    // the space after the tag goes with it
+    rest = rest.strip_prefix(' ').unwrap_or(rest);
+  }
+  result.push_str(rest);
+  result
+}
+
+/// `crate::util::strip_special_chars` keeping the tags, which would be read
+/// out as words without their brackets.
+pub fn strip_special_chars(phrase: &str) -> String {
+  if !is_enabled() {
+    return crate::util::strip_special_chars(phrase);
+  }
+  let mut result = String::new();
+  let mut rest = phrase;
+  while let Some((at, len, _)) = find_tag(rest) {
+    result.push_str(&crate::util::strip_special_chars(&rest[..at]));
+    result.push_str(&rest[at..at + len]);
+    rest = &rest[at + len..];
+  }
+  result.push_str(&crate::util::strip_special_chars(rest));
+  result
+}
+
+/// Removes the tags from the reply as it streams: a piece ending in what may
+/// be the start of a tag is held until the next one tells.
+#[derive(Default)]
+pub struct Stripper {
+  held: String,
+}
+
+impl Stripper {
+  /// The text of `piece` (and of the ones held) to show now.
+  pub fn push(&mut self, piece: &str) -> String {
+    if !is_enabled() {
+      return piece.to_string();
+    }
+    self.held.push_str(piece);
+    let mut shown = String::new();
+    loop {
+      let Some(open) = self.held.find('[') else {
+        shown.push_str(&self.held);
+        self.held.clear();
+        return shown;
+      };
+      shown.push_str(&self.held[..open]);
+      self.held.drain(..open);
+      match self.held.find(']') {
+        Some(close) => {
+          let tag = self.held[..=close].to_string();
+          self.held.drain(..=close);
+          if style_of(&tag).is_some() {
+            // the space after the tag goes with it
+            if self.held.starts_with(' ') {
+              self.held.remove(0);
+            }
+          } else {
+            shown.push_str(&tag);
+          }
+        }
+        // may still become a tag
+        None if self.held.len() < MAX_TAG_LEN => return shown,
+        None => {
+          shown.push('[');
+          self.held.remove(0);
+        }
+      }
+    }
+  }
+
+  /// What is still held, at the end of the reply.
+  pub fn flush(&mut self) -> String {
+    std::mem::take(&mut self.held)
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// the first tag of `text`: where it starts, its length and its style
+fn find_tag(text: &str) -> Option<(usize, usize, Style)> {
+  let mut from = 0;
+  while let Some(open) = text[from..].find('[').map(|i| from + i) {
+    let close = text[open..].find(']').map(|i| open + i)?;
+    if let Some(style) = style_of(&text[open..=close]) {
+      return Some((open, close + 1 - open, style));
+    }
+    from = open + 1;
+  }
+  None
+}
+
+// "[whisper]" -> its style
+fn style_of(tag: &str) -> Option<Style> {
+  let name = tag
+    .strip_prefix('[')?
+    .strip_suffix(']')?
+    .trim()
+    .to_lowercase();
+  STYLES
+    .iter()
+    .find(|(tag, _, _)| *tag == name)
+    .map(|(_, speed, gain)| Style {
+      speed: *speed,
+      gain: *gain,
+    })
+}
+
+fn push_run(runs: &mut Vec<(String, Style)>, text: &str, style: Style) {
+  let text = text.trim();
+  if !text.is_empty() {
+    runs.push((text.to_string(), style));
+  }
+}
@@ -0,0 +1,77 @@
+// ------------------------------------------------------------------
+//  Supervisor (worker threads restarted after a panic)
+// ------------------------------------------------------------------
+
+use crate::engine::BoxError;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+// a thread crashing more often than this is given up on
+const MAX_CRASHES: usize = 5;
+const CRASH_WINDOW: Duration = Duration::from_secs(60);
+
+// set once a thread was given up on
+static GAVE_UP: AtomicBool = AtomicBool::new(false);
+
+// API
+// ------------------------------------------------------------------
+
+/// Runs `body` on a thread named `name` and runs it again whenever it
+/// panics, so one bad phrase or chunk doesn't end a long session. `body`
+/// takes fresh clones of the channel endpoints it needs on every run. A
+/// panic while holding a lock poisons it: the crate's locks are taken with
+/// `unwrap_or_else(PoisonError::into_inner)`, not `unwrap`, so the restarted
+/// body doesn't panic again on them. The thread ends with the first result
+/// `body` returns.
+pub fn spawn<F>(name: &'static str, mut body: F) -> JoinHandle<Result<(), BoxError>>
+where
+  F: FnMut() -> Result<(), BoxError> + Send + 'static,
+{
+  thread::Builder::new()
+    .name(name.to_string())
+    .spawn(move || {
+      let mut crashes: VecDeque<Instant> = VecDeque::new();
+      loop {
+        let payload = match panic::catch_unwind(AssertUnwindSafe(&mut body)) {
+          Ok(result) => return result,
+          Err(payload) => payload,
+        };
+        let reason = payload
+          .downcast_ref::<&str>()
+          .map(|s| s.to_string())
+          .or_else(|| payload.downcast_ref::<String>().cloned())
+          .unwrap_or_else(|| "unknown panic".to_string());
+        crashes.push_back(Instant::now());
+        crate::telemetry::error(&format!("{}_thread_crash", name));
+        while crashes
+          .front()
+          .is_some_and(|at| at.elapsed() > CRASH_WINDOW)
+        {
+          crashes.pop_front();
+        }
+        if crashes.len() > MAX_CRASHES {
+          GAVE_UP.store(true, Ordering::Relaxed);
+          crate::log::log(
+            "error",
+            &format!("The {} thread keeps crashing ({}), giving up", name, reason),
+          );
+          return Err(format!("{} thread crashed: {}", name, reason).into());
+        }
+        crate::log::log(
+          "error",
+          &format!("The {} thread crashed ({}), restarting it", name, reason),
+        );
+        thread::sleep(RESTART_DELAY);
+      }
+    })
+    .expect("failed to spawn a worker thread")
+}
+
+/// False once a supervised thread crashed too often and was given up on.
+pub fn healthy() -> bool {
+  !GAVE_UP.load(Ordering::Relaxed)
+}
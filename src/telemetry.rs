@@ -0,0 +1,228 @@
+// ------------------------------------------------------------------
+//  Usage telemetry (--telemetry)
+// ------------------------------------------------------------------
+//
+// Off unless asked for. Counts which features are used and which kinds of
+// errors happen, by fixed names only: never what is said, the agents, the
+// models or a path. The counts stay in ~/.vtmate/telemetry.json, nothing is
+// sent anywhere: `vtmate telemetry export` prints them to share with the
+// maintainers (e.g. attached to an issue), `vtmate telemetry clear` removes
+// them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// the counts are written at most this often while running, and on exit
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static USAGE: Mutex<Option<Usage>> = Mutex::new(None);
+static LAST_SAVE: Mutex<Option<Instant>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+/// Starts counting (with --telemetry), on top of the counts kept so far.
+pub fn init(args: &crate::config::Args) {
+  if !args.telemetry {
+    return;
+  }
+  let mut usage = load().unwrap_or_default();
+  usage.sessions += 1;
+  usage.version = env!("CARGO_PKG_VERSION").to_string();
+  usage.os = std::env::consts::OS.to_string();
+  for name in features_of(args) {
+    *usage.features.entry(name.to_string()).or_default() += 1;
+  }
+  *USAGE.lock().unwrap_or_else(PoisonError::into_inner) = Some(usage);
+  ENABLED.store(true, Ordering::Relaxed);
+  crate::util::on_shutdown(save);
+  if let Some(path) = usage_path() {
+    crate::log::log(
+      "info",
+      &format!(
+        "Telemetry on: usage counts are kept in {}, nothing is sent",
+        path.display()
+      ),
+    );
+  }
+}
+
+pub fn is_enabled() -> bool {
+  ENABLED.load(Ordering::Relaxed)
+}
+
+/// Counts a use of the feature `name` (a fixed name, never user text).
+pub fn feature(name: &str) {
+  count(|usage| &mut usage.features, name);
+}
+
+/// Counts an error of the class `class` (a fixed name, never the message).
+pub fn error(class: &str) {
+  count(|usage| &mut usage.errors, class);
+}
+
+/// `vtmate telemetry <action>`, returns the exit code.
+pub fn run_telemetry_command(action: &crate::config::TelemetryAction) -> i32 {
+  let Some(path) = usage_path() else {
+    eprintln!("❌ Unable to determine home directory");
+    return 1;
+  };
+  match action {
+    crate::config::TelemetryAction::Export { output } => {
+      let Some(usage) = load() else {
+        eprintln!("❌ Nothing recorded yet, run vtmate with --telemetry first");
+        return 1;
+      };
+      let json = serde_json::to_string_pretty(&usage).unwrap_or_default();
+      let written = match output {
+        Some(file) => std::fs::write(file, json + "\n"),
+        None => {
+          println!("{}", json);
+          Ok(())
+        }
+      };
+      match written {
+        Ok(()) => 0,
+        Err(e) => {
+          eprintln!("❌ Failed to write the telemetry: {}", e);
+          1
+        }
+      }
+    }
+    crate::config::TelemetryAction::Clear => match std::fs::remove_file(&path) {
+      Ok(()) => {
+        println!("Removed {}", path.display());
+        0
+      }
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+      Err(e) => {
+        eprintln!("❌ Failed to remove {}: {}", path.display(), e);
+        1
+      }
+    },
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+#[derive(Serialize, Deserialize)]
+struct Usage {
+  // first day counted
+  since: String,
+  version: String,
+  os: String,
+  sessions: u64,
+  features: BTreeMap<String, u64>,
+  errors: BTreeMap<String, u64>,
+}
+
+impl Default for Usage {
+  fn default() -> Self {
+    Self {
+      since: chrono::Local::now().format("%Y-%m-%d").to_string(),
+      version: String::new(),
+      os: String::new(),
+      sessions: 0,
+      features: BTreeMap::new(),
+      errors: BTreeMap::new(),
+    }
+  }
+}
+
+fn count(field: impl FnOnce(&mut Usage) -> &mut BTreeMap<String, u64>, name: &str) {
+  if !is_enabled() {
+    return;
+  }
+  if let Some(usage) = USAGE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_mut()
+  {
+    *field(usage).entry(name.to_string()).or_default() += 1;
+  }
+  let due = LAST_SAVE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .is_none_or(|at| at.elapsed() >= SAVE_INTERVAL);
+  if due {
+    save();
+  }
+}
+
+fn save() {
+  let Some(path) = usage_path() else {
+    return;
+  };
+  let json = match USAGE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .as_ref()
+  {
+    Some(usage) => serde_json::to_string_pretty(usage).unwrap_or_default(),
+    None => return,
+  };
+  *LAST_SAVE.lock().unwrap_or_else(PoisonError::into_inner) = Some(Instant::now());
+  if let Some(dir) = path.parent() {
+    let _ = std::fs::create_dir_all(dir);
+  }
+  if let Err(e) = std::fs::write(&path, json) {
+    crate::log::log("warning", &format!("Failed to save the telemetry: {}", e));
+  }
+}
+
+fn load() -> Option<Usage> {
+  let text = std::fs::read_to_string(usage_path()?).ok()?;
+  serde_json::from_str(&text).ok()
+}
+
+fn usage_path() -> Option<PathBuf> {
+  crate::util::get_user_home_path().map(|h| h.join(".vtmate").join("telemetry.json"))
+}
+
+// the options of the session, by name
+fn features_of(args: &crate::config::Args) -> Vec<&'static str> {
+  [
+    ("save", args.save),
+    ("punctuate", args.punctuate),
+    ("debate", args.debate.is_some()),
+    ("quiet", args.quiet),
+    ("read_file", args.read_file.is_some()),
+    ("no_tts", args.no_tts),
+    ("captions", args.captions.is_some()),
+    ("mirror", args.mirror.is_some()),
+    ("notify", args.notify),
+    ("media_keys", args.media_keys),
+    ("duck", args.duck.is_some()),
+    ("earcons", args.earcons),
+    ("follow_up_window", args.follow_up_window.is_some()),
+    ("status_led", args.status_led.is_some()),
+    ("status_display", args.status_display.is_some()),
+    ("low_power", args.low_power),
+    ("dictate", args.dictate),
+    ("meeting", args.meeting),
+    ("quiet_hours", args.quiet_hours.is_some()),
+    ("speaker_verify", args.speaker_verify.is_some()),
+    ("redact", args.redact || !args.redact_pattern.is_empty()),
+    ("encrypt", args.encrypt),
+    ("otlp", args.otlp_endpoint.is_some()),
+    ("serve", args.serve.is_some()),
+    ("wyoming", args.wyoming.is_some()),
+    ("stdio_protocol", args.stdio_protocol),
+    ("daemon", args.daemon),
+    ("headless", args.headless),
+    ("service", args.service),
+    ("bridge", !args.bridge.is_empty()),
+    ("secondary_output", args.secondary_output.is_some()),
+  ]
+  .into_iter()
+  .filter(|(_, used)| *used)
+  .map(|(name, _)| name)
+  .collect()
+}
@@ -0,0 +1,289 @@
+// ------------------------------------------------------------------
+//  Testing (the testing feature)
+// ------------------------------------------------------------------
+//
+// Stand-ins for the microphone, the speakers and the backends, so the voice
+// loop runs in tests without devices, models or an llm server, and gives the
+// same result on every run: scripted transcripts and replies, a tts that
+// sends silence as long as the text, and generated input audio to go
+// through the voice detection (see crate::record::Segmenter).
+
+use crate::audio::AudioChunk;
+use crate::cancel::CancellationToken;
+use crate::config::AgentSettings;
+use crate::conversation::ChatMessage;
+use crate::engine::{LanguageModel, SpeechToText, TextToSpeech};
+use crate::error::TtsError;
+use crate::tts::SpeakOutcome;
+use crossbeam_channel::{Receiver, Sender};
+use std::collections::VecDeque;
+use std::sync::PoisonError;
+use std::sync::{Arc, Mutex};
+
+pub const SAMPLE_RATE: u32 = 16000;
+// level of the generated speech, well over the voice threshold of the agents
+const SPEECH_LEVEL: f32 = 0.5;
+const SPEECH_HZ: f32 = 220.0;
+// audio a MockTts sends per character
+const MS_PER_CHAR: u32 = 10;
+
+// API
+// ------------------------------------------------------------------
+
+/// Agent settings for tests: no model is loaded, nothing is downloaded.
+pub fn agent_settings() -> AgentSettings {
+  AgentSettings {
+    name: "test".to_string(),
+    language: "en".to_string(),
+    tts: "mock".to_string(),
+    voice: "mock".to_string(),
+    provider: "mock".to_string(),
+    baseurl: String::new(),
+    model: "mock".to_string(),
+    system_prompt: "You are a test.".to_string(),
+    ptt: false,
+    whisper_model_path: String::new(),
+    sound_threshold_peak: 0.1,
+    end_silence_ms: 1000,
+    voice_speed: 1.0,
+    shell_commands: String::new(),
+    barge_in_ack: String::new(),
+    wake_phrases: String::new(),
+    stop_phrases: String::new(),
+    follow_up_questions: false,
+  }
+}
+
+/// Input audio, one mono sample per frame at SAMPLE_RATE.
+#[derive(Default)]
+pub struct Signal {
+  samples: Vec<f32>,
+}
+
+impl Signal {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// A tone loud enough to be taken for voice, for `ms`.
+  pub fn speech(self, ms: u32) -> Self {
+    self.tone(ms, SPEECH_LEVEL)
+  }
+
+  /// A tone peaking at `level`, for `ms`: quieter speech.
+  pub fn tone(mut self, ms: u32, level: f32) -> Self {
+    let start = self.samples.len();
+    self.samples.extend((0..frames(ms)).map(|i| {
+      let t = (start + i) as f32 / SAMPLE_RATE as f32;
+      level * (2.0 * std::f32::consts::PI * SPEECH_HZ * t).sin().signum()
+    }));
+    self
+  }
+
+  pub fn silence(mut self, ms: u32) -> Self {
+    self.samples.extend(std::iter::repeat_n(0.0, frames(ms)));
+    self
+  }
+
+  pub fn samples(&self) -> &[f32] {
+    &self.samples
+  }
+
+  /// As an utterance, for `Engine::listen`.
+  pub fn chunk(&self) -> AudioChunk {
+    AudioChunk {
+      data: self.samples.as_slice().into(),
+      channels: 1,
+      sample_rate: SAMPLE_RATE,
+    }
+  }
+}
+
+/// Where the tts audio goes instead of the speakers.
+pub struct AudioSink {
+  tx: Sender<AudioChunk>,
+  rx: Receiver<AudioChunk>,
+}
+
+impl AudioSink {
+  pub fn new() -> Self {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    Self { tx, rx }
+  }
+
+  pub fn sender(&self) -> Sender<AudioChunk> {
+    self.tx.clone()
+  }
+
+  /// The chunks received since the last call.
+  pub fn drain(&self) -> Vec<AudioChunk> {
+    self.rx.try_iter().collect()
+  }
+}
+
+impl Default for AudioSink {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Milliseconds of audio in `chunks`.
+pub fn duration_ms(chunks: &[AudioChunk]) -> u64 {
+  chunks
+    .iter()
+    .map(|c| {
+      let frames = c.data.len() as u64 / c.channels.max(1) as u64;
+      frames * 1000 / c.sample_rate.max(1) as u64
+    })
+    .sum()
+}
+
+/// Transcribes every utterance as the next of the scripted transcripts,
+/// nothing once they are used up.
+pub struct MockStt {
+  transcripts: Mutex<VecDeque<String>>,
+}
+
+impl MockStt {
+  pub fn new<S: Into<String>>(transcripts: impl IntoIterator<Item = S>) -> Self {
+    Self {
+      transcripts: Mutex::new(transcripts.into_iter().map(Into::into).collect()),
+    }
+  }
+}
+
+impl SpeechToText for MockStt {
+  fn transcribe(&self, _utterance: &AudioChunk, _language: &str) -> crate::error::Result<String> {
+    Ok(
+      self
+        .transcripts
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .pop_front()
+        .unwrap_or_default(),
+    )
+  }
+}
+
+/// Streams the next of the scripted replies, one token per piece, and keeps
+/// the messages it was sent (see `requests`).
+pub struct MockLlm {
+  replies: Mutex<VecDeque<Vec<String>>>,
+  requests: Arc<Mutex<Vec<Vec<ChatMessage>>>>,
+  // the token before which the turn is interrupted, as the user talking over
+  interrupt_at: Option<usize>,
+}
+
+impl MockLlm {
+  /// Replies given as their tokens, e.g. `[&["Hello", " there."][..]]`.
+  pub fn new(replies: &[&[&str]]) -> Self {
+    Self {
+      replies: Mutex::new(
+        replies
+          .iter()
+          .map(|r| r.iter().map(|t| t.to_string()).collect())
+          .collect(),
+      ),
+      requests: Arc::new(Mutex::new(Vec::new())),
+      interrupt_at: None,
+    }
+  }
+
+  /// Interrupts every turn before its `token`-th token (from 0).
+  pub fn interrupt_at(mut self, token: usize) -> Self {
+    self.interrupt_at = Some(token);
+    self
+  }
+
+  /// The messages of every request so far, shared with the engine.
+  pub fn requests(&self) -> Arc<Mutex<Vec<Vec<ChatMessage>>>> {
+    self.requests.clone()
+  }
+}
+
+impl LanguageModel for MockLlm {
+  fn stream_reply(
+    &self,
+    messages: &[ChatMessage],
+    cancel: &CancellationToken,
+    on_piece: &mut dyn FnMut(&str),
+  ) -> crate::error::Result<()> {
+    self
+      .requests
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .push(messages.to_vec());
+    let tokens = self
+      .replies
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .pop_front()
+      .unwrap_or_default();
+    for (i, token) in tokens.iter().enumerate() {
+      if self.interrupt_at == Some(i) {
+        cancel.cancel();
+      }
+      if cancel.is_cancelled() {
+        break;
+      }
+      on_piece(token);
+    }
+    Ok(())
+  }
+}
+
+/// Sends silence as long as the text (MS_PER_CHAR per character) for every
+/// phrase, and keeps the phrases it was given (see `spoken`).
+pub struct MockTts {
+  spoken: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockTts {
+  pub fn new() -> Self {
+    Self {
+      spoken: Arc::new(Mutex::new(Vec::new())),
+    }
+  }
+
+  /// The phrases synthesized so far, in order, shared with the engine.
+  pub fn spoken(&self) -> Arc<Mutex<Vec<String>>> {
+    self.spoken.clone()
+  }
+}
+
+impl Default for MockTts {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl TextToSpeech for MockTts {
+  fn speak(
+    &self,
+    text: &str,
+    _language: &str,
+    _voice: &str,
+    tx: Sender<AudioChunk>,
+    cancel: &CancellationToken,
+  ) -> crate::error::Result<SpeakOutcome> {
+    if cancel.is_cancelled() {
+      return Ok(SpeakOutcome::Interrupted);
+    }
+    self
+      .spoken
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner)
+      .push(text.to_string());
+    let ms = text.chars().count() as u32 * MS_PER_CHAR;
+    tx.send(Signal::new().silence(ms).chunk())
+      .map_err(|e| TtsError::Synthesis(e.to_string()))?;
+    Ok(SpeakOutcome::Completed)
+  }
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+fn frames(ms: u32) -> usize {
+  (SAMPLE_RATE as u64 * ms as u64 / 1000) as usize
+}
@@ -0,0 +1,329 @@
+// ------------------------------------------------------------------
+//  Traces (--otlp-endpoint)
+// ------------------------------------------------------------------
+//
+// Every turn is a trace: a "turn" span with the stt, llm, tts and playback
+// spans of that turn below it. Turns are posted as OTLP/HTTP JSON once their
+// playback is over, to any OpenTelemetry collector (Jaeger, Grafana Tempo).
+
+use crate::state::GLOBAL_STATE;
+use crossbeam_channel::Sender;
+use serde_json::{Value, json};
+use std::sync::PoisonError;
+use std::sync::{Mutex, OnceLock, atomic::Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// a finished turn is exported after its playback stayed idle this long
+const IDLE_GRACE: Duration = Duration::from_secs(1);
+const PLAYBACK_POLL_INTERVAL: Duration = Duration::from_millis(20);
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// set once export is running, every call is a no-op before that
+static EXPORT_TX: OnceLock<Sender<Vec<Value>>> = OnceLock::new();
+static TURN: Mutex<Option<Turn>> = Mutex::new(None);
+
+// API
+// ------------------------------------------------------------------
+
+/// A stage of the current turn being timed, recorded by `end` (or when
+/// dropped).
+pub struct Span {
+  name: &'static str,
+  start: u64,
+  // trace of the turn the span started in, None when not traced
+  trace_id: Option<String>,
+}
+
+impl Span {
+  /// Records the span, ending now, with some attributes.
+  pub fn end(mut self, attributes: &[(&str, Value)]) {
+    self.record(attributes);
+  }
+
+  fn record(&mut self, attributes: &[(&str, Value)]) {
+    let Some(trace_id) = self.trace_id.take() else {
+      return;
+    };
+    let mut turn = TURN.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(turn) = turn.as_mut().filter(|t| t.trace_id == trace_id) {
+      turn.open_spans = turn.open_spans.saturating_sub(1);
+      turn.push_span(self.name, self.start, now_nanos(), attributes);
+    }
+  }
+}
+
+impl Drop for Span {
+  fn drop(&mut self) {
+    self.record(&[]);
+  }
+}
+
+/// Posts the turn traces to the OTLP/HTTP collector at `endpoint`
+/// (e.g. http://localhost:4318). Blocks.
+pub fn trace_thread(endpoint: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  let url = traces_url(endpoint);
+  let (tx, rx) = crossbeam_channel::unbounded::<Vec<Value>>();
+  EXPORT_TX
+    .set(tx)
+    .map_err(|_| "trace export is already running")?;
+  std::thread::spawn(watch_playback);
+  crate::log::log("info", &format!("Exporting turn traces to {}", url));
+
+  let client = reqwest::blocking::Client::builder()
+    .timeout(EXPORT_TIMEOUT)
+    .build()?;
+  for spans in rx {
+    let body = json!({
+      "resourceSpans": [{
+        "resource": {
+          "attributes": attributes(&[
+            ("service.name", "vtmate".into()),
+            ("service.version", env!("CARGO_PKG_VERSION").into()),
+          ]),
+        },
+        "scopeSpans": [{ "scope": { "name": "vtmate" }, "spans": spans }],
+      }],
+    });
+    if let Err(e) = client
+      .post(&url)
+      .json(&body)
+      .send()
+      .and_then(|r| r.error_for_status())
+    {
+      crate::log::log("warning", &format!("Trace export failed: {}", e));
+    }
+  }
+  Ok(())
+}
+
+/// Starts the trace of a new turn, the previous one is exported as it is.
+pub fn begin_turn() {
+  if EXPORT_TX.get().is_none() {
+    return;
+  }
+  let agent = GLOBAL_STATE
+    .get()
+    .map(|s| {
+      s.agent_name
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+    })
+    .unwrap_or_default();
+  let mut turn = TURN.lock().unwrap_or_else(PoisonError::into_inner);
+  if let Some(previous) = turn.take() {
+    previous.export();
+  }
+  *turn = Some(Turn::new(agent));
+}
+
+/// Starts timing a stage of the current turn.
+pub fn start(name: &'static str) -> Span {
+  let mut turn = TURN.lock().unwrap_or_else(PoisonError::into_inner);
+  let trace_id = turn.as_mut().map(|t| {
+    t.open_spans += 1;
+    t.trace_id.clone()
+  });
+  Span {
+    name,
+    start: now_nanos(),
+    trace_id,
+  }
+}
+
+/// Records a stage of the current turn that was timed elsewhere, possibly
+/// starting before the turn did (a transcription).
+pub fn record(
+  name: &'static str,
+  start: SystemTime,
+  end: SystemTime,
+  attributes: &[(&str, Value)],
+) {
+  if let Some(turn) = TURN.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+    let (start, end) = (unix_nanos(start), unix_nanos(end));
+    turn.start = turn.start.min(start);
+    turn.push_span(name, start, end, attributes);
+  }
+}
+
+/// Marks the first token of the reply of the current turn.
+pub fn first_token() {
+  if let Some(turn) = TURN.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+    turn.first_token.get_or_insert_with(now_nanos);
+  }
+}
+
+/// The reply of the current turn is complete: the turn is exported once
+/// what is left of it has been spoken.
+pub fn end_turn() {
+  if let Some(turn) = TURN.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+    turn.replied = true;
+  }
+}
+
+/// W3C trace context of the current turn, sent along the llm requests so a
+/// traced llm server joins the same trace.
+pub fn traceparent() -> Option<String> {
+  let turn = TURN.lock().unwrap_or_else(PoisonError::into_inner);
+  turn
+    .as_ref()
+    .map(|t| format!("00-{}-{}-01", t.trace_id, t.span_id))
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+struct Turn {
+  trace_id: String,
+  span_id: String,
+  agent: String,
+  start: u64,
+  // end of the latest span
+  last_end: u64,
+  first_token: Option<u64>,
+  spans: Vec<Value>,
+  // spans started and not recorded yet (a phrase being synthesized)
+  open_spans: usize,
+  replied: bool,
+}
+
+impl Turn {
+  fn new(agent: String) -> Self {
+    let start = now_nanos();
+    Self {
+      trace_id: uuid::Uuid::new_v4().simple().to_string(),
+      span_id: new_span_id(),
+      agent,
+      start,
+      last_end: start,
+      first_token: None,
+      spans: Vec::new(),
+      open_spans: 0,
+      replied: false,
+    }
+  }
+
+  fn push_span(&mut self, name: &str, start: u64, end: u64, attrs: &[(&str, Value)]) {
+    self.last_end = self.last_end.max(end);
+    self.spans.push(json!({
+      "traceId": self.trace_id,
+      "spanId": new_span_id(),
+      "parentSpanId": self.span_id,
+      "name": name,
+      "kind": 1,
+      "startTimeUnixNano": start.to_string(),
+      "endTimeUnixNano": end.to_string(),
+      "attributes": attributes(attrs),
+    }));
+  }
+
+  // Sends the turn span and its stages to the export thread.
+  fn export(mut self) {
+    let mut attrs = vec![("vtmate.agent", Value::from(self.agent.clone()))];
+    let mut events = Vec::new();
+    if let Some(at) = self.first_token {
+      attrs.push((
+        "vtmate.first_token_ms",
+        ((at.saturating_sub(self.start)) / 1_000_000).into(),
+      ));
+      events.push(json!({ "name": "first token", "timeUnixNano": at.to_string() }));
+    }
+    self.spans.push(json!({
+      "traceId": self.trace_id,
+      "spanId": self.span_id,
+      "name": "turn",
+      "kind": 1,
+      "startTimeUnixNano": self.start.to_string(),
+      "endTimeUnixNano": self.last_end.to_string(),
+      "attributes": attributes(&attrs),
+      "events": events,
+    }));
+    if let Some(tx) = EXPORT_TX.get() {
+      let _ = tx.send(self.spans);
+    }
+  }
+}
+
+// Records playback as spans of the current turn, and exports the turn once
+// it's replied and its audio is over.
+fn watch_playback() {
+  let mut playing_since: Option<u64> = None;
+  let mut idle_since = Instant::now();
+  loop {
+    std::thread::sleep(PLAYBACK_POLL_INTERVAL);
+    let Some(state) = GLOBAL_STATE.get() else {
+      continue;
+    };
+    let playing = state.playback.playback_active.load(Ordering::Relaxed);
+    let mut turn = TURN.lock().unwrap_or_else(PoisonError::into_inner);
+    match playing_since {
+      None if playing => playing_since = Some(now_nanos()),
+      Some(start) if !playing => {
+        playing_since = None;
+        idle_since = Instant::now();
+        if let Some(turn) = turn.as_mut() {
+          turn.push_span("playback", start, now_nanos(), &[]);
+        }
+      }
+      _ => {}
+    }
+    let finished = turn
+      .as_ref()
+      .is_some_and(|t| t.replied && t.open_spans == 0);
+    if finished
+      && !playing
+      && idle_since.elapsed() >= IDLE_GRACE
+      && let Some(turn) = turn.take()
+    {
+      turn.export();
+    }
+  }
+}
+
+// OTLP key / value attributes
+fn attributes(attrs: &[(&str, Value)]) -> Vec<Value> {
+  attrs
+    .iter()
+    .map(|(key, value)| {
+      let value = match value {
+        Value::Bool(b) => json!({ "boolValue": b }),
+        Value::Number(n) if n.is_f64() => json!({ "doubleValue": n }),
+        // 64 bit integers are strings in OTLP JSON
+        Value::Number(n) => json!({ "intValue": n.to_string() }),
+        Value::String(s) => json!({ "stringValue": s }),
+        other => json!({ "stringValue": other.to_string() }),
+      };
+      json!({ "key": key, "value": value })
+    })
+    .collect()
+}
+
+// "localhost:4318" -> "http://localhost:4318/v1/traces"
+fn traces_url(endpoint: &str) -> String {
+  let endpoint = endpoint.trim_end_matches('/');
+  let endpoint = if endpoint.contains("://") {
+    endpoint.to_string()
+  } else {
+    format!("http://{}", endpoint)
+  };
+  if endpoint.ends_with("/v1/traces") {
+    endpoint
+  } else {
+    format!("{}/v1/traces", endpoint)
+  }
+}
+
+fn new_span_id() -> String {
+  uuid::Uuid::new_v4().simple().to_string()[..16].to_string()
+}
+
+fn now_nanos() -> u64 {
+  unix_nanos(SystemTime::now())
+}
+
+fn unix_nanos(time: SystemTime) -> u64 {
+  time
+    .duration_since(UNIX_EPOCH)
+    .map(|d| d.as_nanos() as u64)
+    .unwrap_or(0)
+}
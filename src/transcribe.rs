@@ -0,0 +1,45 @@
+// ------------------------------------------------------------------
+//  Transcribe (one-shot speech to text)
+// ------------------------------------------------------------------
+
+use crate::config::Args;
+use crate::engine::{SpeechToText, WhisperStt};
+
+// API
+// ------------------------------------------------------------------
+
+/// Transcribes the WAV `file` (stdin for "-") with the agent's whisper model
+/// and language, printing the transcript or writing it to `output`. Returns
+/// the exit code.
+pub fn run_transcribe_command(file: &str, output: Option<&str>, args: &Args) -> i32 {
+  let result = crate::config::load_agent(args).and_then(|agent| {
+    if !cfg!(feature = "whisper") {
+      return Err("this build has no whisper support".to_string());
+    }
+    crate::assets::ensure_agent_assets(std::slice::from_ref(&agent), false)?;
+    #[cfg(feature = "whisper")]
+    whisper_rs::install_logging_hooks();
+    let audio = crate::audio::read_wav(file)?;
+    let stt = WhisperStt {
+      model_path: crate::config::resolved_whisper_model_path(&agent.whisper_model_path),
+    };
+    let text = stt
+      .transcribe(&audio, &agent.language)
+      .map_err(|e| format!("Transcription failed: {}", e))?;
+    match output {
+      Some(path) => crate::audio::write_txt(std::path::Path::new(path), &format!("{}\n", text))
+        .map_err(|e| format!("Failed to write {}: {}", path, e)),
+      None => {
+        println!("{}", text);
+        Ok(())
+      }
+    }
+  });
+  match result {
+    Ok(()) => 0,
+    Err(e) => {
+      eprintln!("❌ {}", e);
+      1
+    }
+  }
+}
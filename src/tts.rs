@@ -2,18 +2,34 @@
 //  TTS - Text to Speech
 // ------------------------------------------------------------------
 
+use crate::cancel::CancellationToken;
 use crate::state::GLOBAL_STATE;
-use crate::tts::kokoro_tts::KOKORO_VOICES_PER_LANGUAGE;
+use crate::tts::voices::{
+  DEFAULT_OPENTTS_VOICES_PER_LANGUAGE, KOKORO_VOICES_PER_LANGUAGE, SUPERSONIC2_LANGS,
+  SUPERSONIC2_VOICE_STYLES,
+};
 use crossbeam_channel::{Receiver, Sender};
+#[cfg(feature = "kokoro")]
 use kokoro_micro::TtsEngine;
+#[cfg(feature = "supersonic2")]
 extern crate supersonic2_tts as supersonic2_tts_crate;
+#[cfg(feature = "supersonic2")]
 use supersonic2_tts_crate::TtsEngine as SupersonicTtsEngine;
+#[cfg(feature = "kokoro")]
 pub mod kokoro_tts;
+#[cfg(feature = "opentts")]
 pub mod opentts_tts;
+#[cfg(feature = "supersonic2")]
 pub mod supersonic2_tts;
+pub mod voices;
 
+#[cfg(any(feature = "kokoro", feature = "supersonic2"))]
+use std::sync::Arc;
+use std::sync::Mutex;
+#[cfg(feature = "supersonic2")]
 use std::sync::OnceLock;
-use std::sync::{Arc, Mutex, atomic::AtomicU64};
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 // API
 // ------------------------------------------------------------------
@@ -21,6 +37,7 @@ use std::sync::{Arc, Mutex, atomic::AtomicU64};
 // TUNABLES
 // ------------------------------------------------------------------
 
+#[cfg(feature = "opentts")]
 pub const CHUNK_FRAMES: usize = 1024; // Frames per chunk (per-channel interleaved)
 pub const QUEUE_CAP_FRAMES: usize = 48_000 * 15; // Playback queue capacity in frames at output SR; 15 seconds worth (scaled by channels)
 
@@ -34,96 +51,318 @@ pub enum SpeakOutcome {
   Interrupted,
 }
 
-static KOKORO_ENGINE: OnceLock<Arc<Mutex<TtsEngine>>> = OnceLock::new();
+// None until first used, and while suspended in low power mode
+#[cfg(feature = "kokoro")]
+static KOKORO_ENGINE: Mutex<Option<Arc<Mutex<TtsEngine>>>> = Mutex::new(None);
+#[cfg(feature = "supersonic2")]
 static SUPSONIC_ENGINE: OnceLock<Arc<Mutex<SupersonicTtsEngine>>> = OnceLock::new();
 
-// Supported languages for Supersonic2 TTS
-static SUPSONIC_LANGS: &[&str] = &["en", "es", "fr", "ko", "pt"];
+// the backend and voice switched to during the session (t key, `vtmate ctl
+// tts`), over those of the agent until another agent or persona is chosen
+static SWITCHED: Mutex<Option<(String, String)>> = Mutex::new(None);
 
+// text only answers (--no-tts, m key)
+static MUTED: AtomicBool = AtomicBool::new(false);
+// speed of the run being synthesized relative to the voice speed (f32 bits),
+// see crate::pace
+static PACE: AtomicU32 = AtomicU32::new(0x3f80_0000);
+// loudness of the run being synthesized (f32 bits), see crate::styles
+static GAIN: AtomicU32 = AtomicU32::new(0x3f80_0000);
+// a phrase taken off the queue and not yet fully synthesized
+static SYNTHESIZING: AtomicBool = AtomicBool::new(false);
+
+pub fn set_muted(muted: bool) {
+  MUTED.store(muted, Ordering::Relaxed);
+}
+
+pub fn is_muted() -> bool {
+  MUTED.load(Ordering::Relaxed)
+}
+
+/// Switches between spoken and text only answers, returns whether they are
+/// text only now.
+pub fn toggle_muted() -> bool {
+  !MUTED.fetch_xor(true, Ordering::Relaxed)
+}
+
+/// Whether a phrase is being synthesized right now (the ones still queued
+/// are not counted).
+pub fn is_synthesizing() -> bool {
+  SYNTHESIZING.load(Ordering::Relaxed)
+}
+
+/// The speed the backends synthesize at: the voice speed, slowed down for
+/// numbers and identifiers (see crate::pace).
+pub fn speed() -> f32 {
+  crate::state::get_speed() * f32::from_bits(PACE.load(Ordering::Relaxed))
+}
+
+fn set_pace(pace: f32) {
+  PACE.store(pace.to_bits(), Ordering::Relaxed);
+}
+
+/// The loudness of the run being synthesized relative to the backend's own,
+/// for the backends able to change it (see crate::styles).
+pub fn gain() -> f32 {
+  f32::from_bits(GAIN.load(Ordering::Relaxed))
+}
+
+fn set_gain(gain: f32) {
+  GAIN.store(gain.to_bits(), Ordering::Relaxed);
+}
+
+/// Unloads the kokoro model to free its memory (low power mode), until
+/// `resume_engines` or the next phrase.
+pub fn suspend_engines() {
+  #[cfg(feature = "kokoro")]
+  kokoro_tts::suspend_engine();
+}
+
+/// Loads back in the background what `suspend_engines` unloaded.
+pub fn resume_engines() {
+  #[cfg(feature = "kokoro")]
+  kokoro_tts::resume_engine();
+}
+
+/// The backends compiled into this build.
+pub fn backends() -> Vec<&'static str> {
+  let mut backends = Vec::new();
+  if cfg!(feature = "kokoro") {
+    backends.push("kokoro");
+  }
+  if cfg!(feature = "supersonic2") {
+    backends.push("supersonic2");
+  }
+  if cfg!(feature = "opentts") {
+    backends.push("opentts");
+  }
+  backends
+}
+
+/// Speaks with `backend` from the next phrase on (the one after the current
+/// backend having a voice for the language when None), keeping the voice
+/// when the backend has it. Its engine is loaded with the first phrase.
+/// Returns the backend and the voice.
+pub fn switch_backend(backend: Option<&str>) -> Result<(String, String), String> {
+  let state = GLOBAL_STATE.get().ok_or("not started")?;
+  let current = state
+    .tts
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let language = state
+    .language
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let backends = backends();
+  let backend = match backend {
+    Some(backend) if backends.contains(&backend) => backend.to_string(),
+    Some(backend) => {
+      return Err(format!(
+        "tts '{}' is not in this build (available: {})",
+        backend,
+        backends.join(", ")
+      ));
+    }
+    None => {
+      let at = backends.iter().position(|b| *b == current).unwrap_or(0);
+      backends
+        .iter()
+        .cycle()
+        .skip(at + 1)
+        .take(backends.len())
+        .find(|b| !get_voices_for(b, &language).is_empty())
+        .ok_or_else(|| format!("No tts has a voice for '{}'", language))?
+        .to_string()
+    }
+  };
+  let voices = get_voices_for(&backend, &language);
+  let voice = state
+    .voice
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let voice = match voices.first() {
+    _ if voices.contains(&voice.as_str()) => voice,
+    Some(first) => first.to_string(),
+    None => return Err(format!("{} has no voice for '{}'", backend, language)),
+  };
+  // the models the agent would need with this backend
+  let name = state
+    .agent_name
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let mut agent = state
+    .agents
+    .iter()
+    .find(|a| a.name == name)
+    .cloned()
+    .ok_or("no current agent")?;
+  agent.tts = backend.clone();
+  agent.language = language;
+  if !crate::assets::is_ready(&agent) {
+    return Err(format!(
+      "The {} models are not downloaded, get them with: vtmate assets pull {}",
+      backend, backend
+    ));
+  }
+  *state.tts.lock().unwrap_or_else(PoisonError::into_inner) = backend.clone();
+  *state.voice.lock().unwrap_or_else(PoisonError::into_inner) = voice.clone();
+  *SWITCHED.lock().unwrap_or_else(PoisonError::into_inner) = Some((backend.clone(), voice.clone()));
+  crate::telemetry::feature("tts_switch");
+  crate::log::log("info", &format!("TTS switched to {} ({})", backend, voice));
+  Ok((backend, voice))
+}
+
+/// `agent` with the backend and voice switched to, if any.
+pub fn apply_backend(mut agent: crate::config::AgentSettings) -> crate::config::AgentSettings {
+  if let Some((backend, voice)) = SWITCHED
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone()
+  {
+    agent.tts = backend;
+    agent.voice = voice;
+  }
+  agent
+}
+
+/// Goes back to the backend of the agent, as when another agent is chosen.
+pub fn clear_backend() {
+  *SWITCHED.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+#[allow(unused_variables)] // some arguments are only used by optional backends
 pub fn speak(
   text: &str,
   tts: &str,
   opentts_base_url: &str,
   language: &str,
   voice: &str,
+  speed: f32,
   out_sample_rate: u32, // MUST match CPAL playback SR
   tx: Sender<crate::audio::AudioChunk>,
-  interrupt_counter: Arc<AtomicU64>,
-  expected_interrupt: u64,
-) -> Result<SpeakOutcome, Box<dyn std::error::Error + Send + Sync>> {
-  let outcome = if tts == "opentts" {
-    opentts_tts::speak_via_opentts(
+  cancel: &CancellationToken,
+) -> crate::error::Result<SpeakOutcome> {
+  match tts {
+    #[cfg(feature = "opentts")]
+    "opentts" => opentts_tts::speak_via_opentts(
       text,
       opentts_base_url,
       language,
       voice,
       out_sample_rate,
       tx,
-      interrupt_counter,
-      expected_interrupt,
-    )
-  } else if tts == "supersonic2" {
-    let speed = crate::state::get_speed();
-    let gain = 1.0;
-    supersonic2_tts::speak_via_supersonic2(
-      text,
-      voice,
-      speed,
-      gain,
-      language,
-      tx,
-      interrupt_counter,
-      expected_interrupt,
-    )
-  } else {
-    let lang = if language == "zh" { "cmn" } else { language };
-    kokoro_tts::speak_via_kokoro(text, lang, voice, tx, interrupt_counter, expected_interrupt)
-  }?;
-  Ok(outcome)
+      cancel,
+    ),
+    #[cfg(feature = "supersonic2")]
+    "supersonic2" => {
+      let gain = 1.0;
+      supersonic2_tts::speak_via_supersonic2(text, voice, speed, gain, language, tx, cancel)
+    }
+    #[cfg(feature = "kokoro")]
+    "kokoro" => {
+      let lang = if language == "zh" { "cmn" } else { language };
+      kokoro_tts::speak_via_kokoro(text, lang, voice, speed, tx, cancel)
+    }
+    // backend left out of this build: the reply is shown as text only
+    _ => {
+      static WARNED: std::sync::Once = std::sync::Once::new();
+      WARNED.call_once(|| {
+        crate::log::log(
+          "warning",
+          &format!(
+            "tts '{}' is not compiled into this build, replies are text only",
+            tts
+          ),
+        );
+      });
+      Ok(SpeakOutcome::Completed)
+    }
+  }
 }
 
 // tts_thread - dedicated thread for speaking phrases
 pub fn tts_thread(
   out_sample_rate: u32,
   tx_play: Sender<crate::audio::AudioChunk>,
-  interrupt_counter: Arc<AtomicU64>,
-  rx_tts: Receiver<(String, u64, String)>,
+  rx_tts: Receiver<(String, CancellationToken, String)>,
   stop_play_tx: Sender<()>,
   tx_tts_done: Sender<()>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  crate::captions::watch_voice(&tx_play);
+  // after a crash: the phrase that was being synthesized is lost
+  SYNTHESIZING.store(false, Ordering::Relaxed);
   loop {
     crate::log::log("info", "🔄 TTS thread waiting for next phrase...");
     // Wait for either a new phrase or a stop signal
     crossbeam_channel::select! {
       recv(rx_tts) -> msg => {
-        let (phrase, expected_interrupt, voice) = match msg {
+        let (phrase, cancel, voice) = match msg {
           Ok(v) => v,
           Err(_) => break,
         };
+        SYNTHESIZING.store(true, Ordering::Relaxed);
+        // text only: the phrase is already on screen
+        if is_muted() {
+          SYNTHESIZING.store(false, Ordering::Relaxed);
+          let _ = tx_tts_done.try_send(());
+          continue;
+        }
         let state = GLOBAL_STATE.get().expect("AppState not initialized");
-        // crate::log::log("info", &format!("TTS received phrase (len={}), expected_interrupt={}", phrase.len(), expected_interrupt));
 
-        let tts_val = state.tts.lock().unwrap().clone();
-        let language = state.language.lock().unwrap().clone();
+        let tts_val = state.tts.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        let language = state.language.lock().unwrap_or_else(PoisonError::into_inner).clone();
 
         // Use OPENTTS_BASE_URL_DEFAULT when TTS is set to opentts
         let opentts_url = if tts_val == "opentts" {
           crate::config::OPENTTS_BASE_URL_DEFAULT.to_string()
         } else {
-          state.baseurl.lock().unwrap().clone()
+          state.baseurl.lock().unwrap_or_else(PoisonError::into_inner).clone()
         };
 
-        let outcome = crate::tts::speak(
-          &phrase,
-          &tts_val,
-          &opentts_url,
-          &language,
-          &voice,
-          out_sample_rate,
-          tx_play.clone(),
-          interrupt_counter.clone(),
-          expected_interrupt,
-        );
+        crate::events::emit(crate::engine::TurnEvent::Phrase(phrase.clone()));
+        let tts_span = crate::traces::start("tts");
+        let synthesized_from = crate::captions::synthesized_position();
+        crate::clips::phrase_started(&cancel);
+        let mut outcome = Ok(SpeakOutcome::Completed);
+        'runs: for (styled, style) in crate::styles::runs(&phrase) {
+          set_gain(style.gain);
+          for (text, pace) in crate::pace::runs(&styled) {
+            set_pace(pace * style.speed);
+            outcome = speak(
+              &text,
+              &tts_val,
+              &opentts_url,
+              &language,
+              &voice,
+              speed(),
+              out_sample_rate,
+              tx_play.clone(),
+              &cancel,
+            );
+            if !matches!(outcome, Ok(SpeakOutcome::Completed)) {
+              break 'runs;
+            }
+          }
+        }
+        set_pace(1.0);
+        set_gain(1.0);
+        SYNTHESIZING.store(false, Ordering::Relaxed);
+        tts_span.end(&[
+          ("tts.backend", tts_val.into()),
+          ("tts.voice", voice.into()),
+          ("tts.chars", phrase.chars().count().into()),
+        ]);
+        if outcome.is_ok() {
+          crate::captions::phrase(&phrase, synthesized_from, crate::captions::synthesized_position());
+          crate::clips::phrase_spoken(&phrase);
+        }
+        if matches!(outcome, Ok(crate::tts::SpeakOutcome::Completed)) {
+          let _ = tx_play.send(crate::playback::phrase_end());
+        }
 
         match outcome {
           Ok(o) => {
@@ -142,11 +381,11 @@ pub fn tts_thread(
             }
             let _ = tx_tts_done.try_send(());
           }
-          Err(_e) => {
-            crate::log::log("error", &format!("TTS error. Can't play audio speech. Make sure OpenTTS is running: docker run --rm -p 5500:5500 synesthesiam/opentts:all"));
-            // Signal completion before breaking
+          Err(e) => {
+            crate::error::report(&e);
+            // Signal completion, the next phrase gets another try
             let _ = tx_tts_done.try_send(());
-            break;
+            continue;
           }
         }
       }
@@ -162,12 +401,12 @@ pub fn get_all_available_languages() -> Vec<&'static str> {
     .map(|(lang, _)| *lang)
     .collect();
   langs.extend(
-    crate::tts::opentts_tts::DEFAULT_OPENTTS_VOICES_PER_LANGUAGE
+    DEFAULT_OPENTTS_VOICES_PER_LANGUAGE
       .iter()
       .map(|(lang, _)| *lang),
   );
   // Include supersonic2 supported languages
-  langs.extend(SUPSONIC_LANGS.iter().copied());
+  langs.extend(SUPERSONIC2_LANGS.iter().copied());
   langs.sort();
   langs.dedup();
   langs
@@ -184,7 +423,7 @@ pub fn get_voices_for(tts: &str, language: &str) -> Vec<&'static str> {
       Vec::new()
     }
     "opentts" => {
-      for (lang, voice) in crate::tts::opentts_tts::DEFAULT_OPENTTS_VOICES_PER_LANGUAGE.iter() {
+      for (lang, voice) in DEFAULT_OPENTTS_VOICES_PER_LANGUAGE.iter() {
         if *lang == language {
           return vec![*voice];
         }
@@ -193,9 +432,8 @@ pub fn get_voices_for(tts: &str, language: &str) -> Vec<&'static str> {
     }
     "supersonic2" => {
       // Supersonic2 voices are supported only for specific languages
-      let supersonic_voices = crate::tts::supersonic2_tts::SUPERSONIC2_VOICE_STYLES;
-      if SUPSONIC_LANGS.contains(&language) {
-        supersonic_voices.to_vec()
+      if SUPERSONIC2_LANGS.contains(&language) {
+        SUPERSONIC2_VOICE_STYLES.to_vec()
       } else {
         Vec::new()
       }
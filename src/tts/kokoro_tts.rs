@@ -4,11 +4,13 @@
 
 use super::{KOKORO_ENGINE, SpeakOutcome};
 use crate::audio::AudioChunk;
+use crate::cancel::CancellationToken;
+use crate::error::TtsError;
 use crossbeam_channel::Sender;
 use kokoro_micro::TtsEngine;
 use std::sync::{
-  Arc, Mutex,
-  atomic::{AtomicBool, AtomicU64, Ordering},
+  Arc, Mutex, PoisonError,
+  atomic::{AtomicBool, Ordering},
 };
 use std::thread;
 use std::time::Duration;
@@ -20,48 +22,81 @@ pub struct StreamingTts {
   pub is_speaking: Arc<AtomicBool>,
   pub interrupt_flag: Arc<AtomicBool>,
   voice: String,
+  speed: f32,
   gain: f32,
 }
 
+/// Sets the intra-op threads (0 keeps the onnx runtime default) and the
+/// execution provider ("cpu", "coreml", "cuda") of the onnx sessions. Must
+/// run before the engine starts.
+pub fn configure_onnx(threads: usize, execution_provider: &str) {
+  let mut env = ort::init();
+  if threads > 0 {
+    match ort::environment::GlobalThreadPoolOptions::default().with_intra_threads(threads) {
+      Ok(pool) => env = env.with_global_thread_pool(pool),
+      Err(e) => crate::log::log("warning", &format!("Kokoro threads not set: {}", e)),
+    }
+  }
+  env = match execution_provider {
+    "coreml" => env.with_execution_providers([ort::ep::CoreML::default().build()]),
+    "cuda" => env.with_execution_providers([ort::ep::CUDA::default().build()]),
+    _ => env,
+  };
+  env.commit();
+}
+
 // Engine initialization
-pub fn start_kokoro_engine() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-  let rt = tokio::runtime::Builder::new_current_thread()
-    .enable_all()
-    .build()?;
-  let engine = rt.block_on(TtsEngine::new())?;
-  KOKORO_ENGINE.set(Arc::new(Mutex::new(engine))).ok();
+pub fn start_kokoro_engine() -> crate::error::Result<()> {
+  shared_engine().map_err(TtsError::Synthesis)?;
   Ok(())
 }
 
+/// Drops the engine, the phrases being spoken keep it until they end.
+pub fn suspend_engine() {
+  if KOKORO_ENGINE
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .take()
+    .is_some()
+  {
+    SUSPENDED.store(true, Ordering::Relaxed);
+  }
+}
+
+/// Starts the engine again in the background, when suspended.
+pub fn resume_engine() {
+  if SUSPENDED.swap(false, Ordering::Relaxed) {
+    thread::spawn(|| {
+      if let Err(e) = shared_engine() {
+        crate::log::log("warning", &format!("Kokoro not loaded back: {}", e));
+      }
+    });
+  }
+}
+
 // Speak via Kokoro
 pub fn speak_via_kokoro(
   text: &str,
   language: &str,
   voice: &str,
+  speed: f32,
   tx: Sender<crate::audio::AudioChunk>,
-  interrupt_counter: Arc<AtomicU64>,
-  expected_interrupt: u64,
-) -> Result<SpeakOutcome, Box<dyn std::error::Error + Send + Sync>> {
-  let engine = KOKORO_ENGINE.get_or_init(|| {
-    let rt = tokio::runtime::Builder::new_current_thread()
-      .enable_all()
-      .build()
-      .unwrap();
-    let e = rt.block_on(TtsEngine::new()).unwrap();
-    Arc::new(Mutex::new(e))
-  });
+  cancel: &CancellationToken,
+) -> crate::error::Result<SpeakOutcome> {
+  let engine = shared_engine().map_err(TtsError::Synthesis)?;
 
-  let mut streaming = StreamingTts::new(engine.clone());
+  let mut streaming = StreamingTts::new(engine);
   streaming.set_voice(voice);
+  streaming.set_speed(speed);
 
-  // interrupt monitoring
+  // interrupt monitoring, until the phrase is synthesized
   let interrupt_flag = streaming.interrupt_flag.clone();
-  let int_counter = interrupt_counter.clone();
-  let expected = expected_interrupt;
+  let synthesis = cancel.child();
+  let monitored = synthesis.clone();
 
   thread::spawn(move || {
     loop {
-      if int_counter.load(Ordering::SeqCst) != expected {
+      if monitored.is_cancelled() {
         interrupt_flag.store(true, Ordering::Relaxed);
         break;
       }
@@ -70,10 +105,8 @@ pub fn speak_via_kokoro(
   });
 
   // Start synthesis - the monitoring thread will handle interruptions during synthesis
-  let rt = tokio::runtime::Builder::new_current_thread()
-    .enable_all()
-    .build()?;
-  let res = rt.block_on(streaming.speak_stream(text, tx.clone(), language));
+  let res = crate::util::runtime().block_on(streaming.speak_stream(text, tx.clone(), language));
+  synthesis.cancel();
 
   match res {
     Ok(_) => Ok(SpeakOutcome::Completed),
@@ -81,143 +114,32 @@ pub fn speak_via_kokoro(
   }
 }
 
-pub const KOKORO_VOICES_PER_LANGUAGE: &[(&str, &[&str])] = &[
-  // English language
-  // ----------------------------------------
-  (
-    "en",
-    &[
-      // American english - female
-      "af_alloy",
-      "af_aoede",
-      "af_bella",
-      "af_heart",
-      "af_jessica",
-      "af_kore",
-      "af_nicole",
-      "af_nova",
-      "af_river",
-      "af_sarah",
-      "af_sky",
-      // American english - male
-      "am_adam",
-      "am_echo",
-      "am_eric",
-      "am_fenrir",
-      "am_liam",
-      "am_michael",
-      "am_onyx",
-      "am_puck",
-      "am_santa",
-      // British english - female
-      "bf_alice",
-      "bf_emma",
-      "bf_isabella",
-      "bf_lily",
-      // British english - male
-      "bm_daniel",
-      "bm_fable",
-      "bm_george",
-      "bm_lewis",
-    ],
-  ),
-  // Spanish language
-  // ----------------------------------------
-  (
-    "es",
-    &[
-      // Spanish - female
-      "ef_dora", // Spanish - male
-      "em_alex", "em_santa",
-    ],
-  ),
-  // Mandarin chinese language
-  // ----------------------------------------
-  (
-    "zh",
-    &[
-      // Mandarin chinese - female
-      "zf_xiaobei",
-      "zf_xiaoni",
-      "zf_xiaoxiao",
-      "zf_xiaoyi",
-      // Mandarin chinese - male
-      "zm_yunjian",
-      "zm_yunxi",
-      "zm_yunxia",
-      "zm_yunyang",
-    ],
-  ),
-  // Japanese language
-  // ----------------------------------------
-  (
-    "ja",
-    &[
-      // Japanese - female
-      "jf_alpha",
-      "jf_gongitsune",
-      "jf_nezumi",
-      "jf_tebukuro",
-      // Japanese - male
-      "jm_kumo",
-    ],
-  ),
-  // Portuguese / Brazil language
-  // ----------------------------------------
-  (
-    "pt",
-    &[
-      // Portuguese - female
-      "pf_dora", // Portuguese - male
-      "pm_alex", "pm_santa",
-    ],
-  ),
-  // Italian language
-  // ----------------------------------------
-  (
-    "it",
-    &[
-      // Italian - female
-      "if_sara",
-      // Italian - male
-      "im_nicola",
-    ],
-  ),
-  // Hindi language
-  // ----------------------------------------
-  (
-    "hi",
-    &[
-      // Hindi - female
-      "hf_alpha", "hf_beta", // Hindi - male
-      "hm_omega", "hm_psi",
-    ],
-  ),
-  // French language
-  // ----------------------------------------
-  (
-    "fr",
-    &[
-      // French - female
-      "ff_siwis",
-    ],
-  ),
-];
-
-pub const _DEFAULT_KOKORO_VOICES_PER_LANGUAGE: &[(&str, &str)] = &[
-  ("en", "bf_emma"),
-  ("es", "em_santa"),
-  ("zh", "zf_xiaoni"),
-  ("ja", "jm_kumo"),
-  ("pt", "pf_dora"),
-  ("it", "if_sara"),
-  ("hi", "hf_alpha"),
-  ("fr", "ff_siwis"),
-];
-
 // PRIVATE
 // ------------------------------------------------------------------
 
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+// The running engine, started first if needed.
+fn shared_engine() -> Result<Arc<Mutex<TtsEngine>>, String> {
+  let mut engine = KOKORO_ENGINE.lock().unwrap_or_else(PoisonError::into_inner);
+  if let Some(engine) = engine.as_ref() {
+    return Ok(engine.clone());
+  }
+  let started = Arc::new(Mutex::new(crate::util::runtime().block_on(new_engine())?));
+  *engine = Some(started.clone());
+  Ok(started)
+}
+
+// kokoro_micro defaults to ~/.cache/k, load the model from the data dir instead
+async fn new_engine() -> Result<TtsEngine, String> {
+  let dir = std::env::var_os("KOKORO_TTS_DATA_DIRECTORY")
+    .map(std::path::PathBuf::from)
+    .ok_or_else(|| "KOKORO_TTS_DATA_DIRECTORY is not set".to_string())?;
+  let model = dir.join("0.onnx");
+  let voices = dir.join("0.bin");
+  TtsEngine::with_paths(&model.to_string_lossy(), &voices.to_string_lossy()).await
+}
+
 // smaller chunks reduce long synth stalls -> fewer underruns/glitches.
 // (Words are variable length; 10–15 is a safer range for real-time streaming.)
 const MAX_CHUNK_SIZE: usize = 10;
@@ -229,6 +151,7 @@ impl StreamingTts {
       is_speaking: Arc::new(AtomicBool::new(false)),
       interrupt_flag: Arc::new(AtomicBool::new(false)),
       voice: "".to_string(),
+      speed: 1.0,
       gain: 1.5,
     }
   }
@@ -237,6 +160,10 @@ impl StreamingTts {
     self.voice = voice.to_string();
   }
 
+  pub fn set_speed(&mut self, speed: f32) {
+    self.speed = speed;
+  }
+
   fn split_into_chunks(text: &str) -> Vec<String> {
     let mut chunks = Vec::new();
     let mut current = String::new();
@@ -272,7 +199,8 @@ impl StreamingTts {
     let chunks = Self::split_into_chunks(text);
     let engine = self.engine.clone();
     let voice = self.voice.clone();
-    let gain = self.gain;
+    let speed = self.speed;
+    let gain = self.gain * crate::tts::gain();
     let interrupt_flag_main = self.interrupt_flag.clone();
     let interrupt_flag_thread = interrupt_flag_main.clone();
 
@@ -283,13 +211,9 @@ impl StreamingTts {
           break;
         }
         if let Ok(mut e) = engine.lock() {
-          if let Ok(mut samples) = e.synthesize_with_options(
-            &chunk,
-            Some(&voice),
-            crate::state::get_speed(),
-            gain,
-            Some(&language),
-          ) {
+          if let Ok(mut samples) =
+            e.synthesize_with_options(&chunk, Some(&voice), speed, gain, Some(&language))
+          {
             // sanitize output samples (prevents nasty noise if NaN/Inf/out-of-range)
             for s in &mut samples {
               if !s.is_finite() {
@@ -299,7 +223,7 @@ impl StreamingTts {
               }
             }
             let audio = AudioChunk {
-              data: samples,
+              data: samples.into(),
               channels: 1,
               sample_rate: 24000,
             };
@@ -307,6 +231,7 @@ impl StreamingTts {
             if interrupt_flag_thread.load(Ordering::Relaxed) {
               break;
             }
+            crate::captions::synthesized(&tx, &audio);
             if tx.send(audio).is_err() {
               break;
             }
@@ -5,13 +5,13 @@
 use crossbeam_channel::Sender;
 use reqwest;
 use std::io::{BufReader, Read};
-use std::sync::{
-  Arc,
-  atomic::{AtomicU64, Ordering},
-};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 use urlencoding;
 
 use crate::audio::{AudioChunk, resample_to};
+use crate::cancel::CancellationToken;
+use crate::error::TtsError;
 use crate::log::log;
 
 // API
@@ -24,10 +24,8 @@ pub fn speak_via_opentts(
   voice: &str,
   out_sample_rate: u32,
   tx: Sender<AudioChunk>,
-  interrupt_counter: Arc<AtomicU64>,
-
-  expected_interrupt: u64,
-) -> Result<crate::tts::SpeakOutcome, Box<dyn std::error::Error + Send + Sync>> {
+  cancel: &CancellationToken,
+) -> crate::error::Result<crate::tts::SpeakOutcome> {
   if text.is_empty() {
     return Ok(crate::tts::SpeakOutcome::Completed);
   }
@@ -41,281 +39,273 @@ pub fn speak_via_opentts(
     urlencoding::encode(text),
   );
 
-  stream_wav16le_over_http(
-    &url,
-    tx,
-    out_sample_rate,
-    interrupt_counter,
-    expected_interrupt,
-  )
+  stream_wav_over_http(&url, tx, out_sample_rate, cancel).map_err(|e| match e {
+    // the phrase url is long, the server is what matters
+    TtsError::OpenTtsUnreachable { reason, .. } => TtsError::OpenTtsUnreachable {
+      url: opentts_base_url.to_string(),
+      reason,
+    }
+    .into(),
+    e => e.into(),
+  })
 }
 
-pub const DEFAULT_OPENTTS_VOICES_PER_LANGUAGE: &[(&str, &str)] = &[
-  ("ar", "festival:ara_norm_ziad_hts"),
-  ("bn", "flite:cmu_indic_ben_rm"),
-  ("ca", "festival:upc_ca_ona_hts"),
-  ("cs", "festival:czech_machac"),
-  ("de", "glow-speak:de_thorsten"),
-  ("el", "glow-speak:el_rapunzelina"),
-  ("en", "larynx:cmu_fem-glow_tts"),
-  ("es", "larynx:karen_savage-glow_tts"),
-  ("fi", "glow-speak:fi_harri_tapani_ylilammi"),
-  ("fr", "larynx:gilles_le_blanc-glow_tts"),
-  ("gu", "flite:cmu_indic_guj_ad"),
-  ("hi", "flite:cmu_indic_hin_ab"),
-  ("hu", "glow-speak:hu_diana_majlinger"),
-  ("it", "larynx:riccardo_fasol-glow_tts"),
-  ("ja", "coqui-tts:ja_kokoro"),
-  ("kn", "flite:cmu_indic_kan_plv"),
-  ("ko", "glow-speak:ko_kss"),
-  ("mr", "flite:cmu_indic_mar_aup"),
-  ("nl", "glow-speak:nl_rdh"),
-  ("pa", "flite:cmu_indic_pan_amp"),
-  ("ru", "glow-speak:ru_nikolaev"),
-  ("sv", "glow-speak:sv_talesyntese"),
-  ("sw", "glow-speak:sw_biblia_takatifu"),
-  ("ta", "flite:cmu_indic_tam_sdr"),
-  ("te", "marytts:cmu-nk-hsmm"),
-  ("tr", "marytts:dfki-ot-hsmm"),
-  ("zh", "coqui-tts:zh_baker"),
-];
-
 // PRIVATE
 // ------------------------------------------------------------------
 
-fn read_exact_in_chunks<R: Read>(
-  reader: &mut R,
-  total: usize,
-) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
-  let mut remaining = total;
-  let mut buf = vec![0u8; 8192];
-  let mut out = Vec::with_capacity(total);
-  while remaining > 0 {
-    let to_read = std::cmp::min(remaining, buf.len());
-    let n = reader.read(&mut buf[..to_read])?;
-    if n == 0 {
-      return Err("Unexpected EOF while reading wav data".into());
-    }
-    out.extend_from_slice(&buf[..n]);
-    remaining -= n;
-  }
-  Ok(out)
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+// data size written by servers streaming a WAV of unknown length
+const WAV_SIZE_UNKNOWN: u32 = 0xFFFF_FFFF;
+// to give up on a server that isn't there, the synthesis itself has no limit
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug)]
+enum SampleFormat {
+  Pcm16,
+  Pcm24,
+  Float32,
 }
 
-fn stream_wav16le_over_http(
-  url: &str,
-  tx: Sender<AudioChunk>,
-  target_sr: u32,
-
-  interrupt_counter: Arc<AtomicU64>,
-  expected_interrupt: u64,
-) -> Result<crate::tts::SpeakOutcome, Box<dyn std::error::Error + Send + Sync>> {
-  let resp = reqwest::blocking::get(url)?;
+impl SampleFormat {
+  fn bytes(self) -> usize {
+    match self {
+      SampleFormat::Pcm16 => 2,
+      SampleFormat::Pcm24 => 3,
+      SampleFormat::Float32 => 4,
+    }
+  }
 
-  if !resp.status().is_success() {
-    return Err(format!("HTTP {} from {}", resp.status(), url).into());
+  fn decode(self, bytes: &[u8], out: &mut Vec<f32>) {
+    match self {
+      SampleFormat::Pcm16 => out.extend(
+        bytes
+          .chunks_exact(2)
+          .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0),
+      ),
+      // sign extended by shifting the 3 bytes into the top of an i32
+      SampleFormat::Pcm24 => out.extend(
+        bytes
+          .chunks_exact(3)
+          .map(|b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8_388_608.0),
+      ),
+      SampleFormat::Float32 => out.extend(
+        bytes
+          .chunks_exact(4)
+          .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]).clamp(-1.0, 1.0)),
+      ),
+    }
   }
+}
 
-  let mut reader = BufReader::new(resp);
+struct WavFormat {
+  sample_format: SampleFormat,
+  channels: u16,
+  sample_rate: u32,
+  // None when the server streams the data without knowing its size
+  data_len: Option<usize>,
+}
 
-  // RIFF header
+// Reads the RIFF header and the chunks up to the start of the samples.
+fn read_wav_header<R: Read>(reader: &mut R) -> Result<WavFormat, TtsError> {
   let mut riff = [0u8; 12];
-  reader.read_exact(&mut riff)?;
+  reader.read_exact(&mut riff).map_err(bad_wav)?;
   if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
-    return Err("not a RIFF/WAVE file".into());
+    return Err(bad_wav("not a RIFF/WAVE file"));
   }
 
-  let mut channels: u16 = 0;
-  let mut sample_rate: u32 = 0;
-  let data_len_opt: Option<u32>;
-
-  // Parse chunks until fmt + data
+  let mut format: Option<(SampleFormat, u16, u32)> = None;
   loop {
-    if interrupt_counter.load(Ordering::SeqCst) != expected_interrupt {
-      return Ok(crate::tts::SpeakOutcome::Interrupted);
-    }
-
     let mut hdr = [0u8; 8];
-    reader.read_exact(&mut hdr)?;
+    reader.read_exact(&mut hdr).map_err(bad_wav)?;
     let id = &hdr[0..4];
-    let size = u32::from_le_bytes(hdr[4..8].try_into().unwrap());
+    let size = u32::from_le_bytes([hdr[4], hdr[5], hdr[6], hdr[7]]);
+
+    if id == b"data" {
+      let (sample_format, channels, sample_rate) =
+        format.ok_or_else(|| bad_wav("data chunk before fmt chunk"))?;
+      return Ok(WavFormat {
+        sample_format,
+        channels,
+        sample_rate,
+        data_len: (size != 0 && size != WAV_SIZE_UNKNOWN).then_some(size as usize),
+      });
+    }
 
+    // chunks are padded to an even size
+    let mut body = vec![0u8; size as usize + (size as usize % 2)];
+    reader.read_exact(&mut body).map_err(bad_wav)?;
     if id == b"fmt " {
-      let mut fmt = vec![0u8; size as usize];
-      reader.read_exact(&mut fmt)?;
-      if fmt.len() < 16 {
-        return Err("fmt chunk too small".into());
-      }
+      format = Some(parse_fmt(&body[..size as usize])?);
+    }
+  }
+}
 
-      let audio_format = u16::from_le_bytes([fmt[0], fmt[1]]);
-      channels = u16::from_le_bytes([fmt[2], fmt[3]]);
-      sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
-      let bits_per_sample = u16::from_le_bytes([fmt[14], fmt[15]]);
+fn parse_fmt(fmt: &[u8]) -> Result<(SampleFormat, u16, u32), TtsError> {
+  if fmt.len() < 16 {
+    return Err(bad_wav("fmt chunk too small"));
+  }
+  let mut audio_format = u16::from_le_bytes([fmt[0], fmt[1]]);
+  let channels = u16::from_le_bytes([fmt[2], fmt[3]]);
+  let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+  let bits_per_sample = u16::from_le_bytes([fmt[14], fmt[15]]);
+  // the actual format is the first 2 bytes of the sub format guid
+  if audio_format == WAVE_FORMAT_EXTENSIBLE && fmt.len() >= 26 {
+    audio_format = u16::from_le_bytes([fmt[24], fmt[25]]);
+  }
+  if channels == 0 || sample_rate == 0 {
+    return Err(bad_wav("missing WAV fmt info"));
+  }
 
-      if audio_format != 1 {
-        return Err(format!("unsupported WAV format {}, need PCM (1)", audio_format).into());
-      }
-      if bits_per_sample != 16 {
-        return Err(format!("unsupported bits_per_sample {}, need 16", bits_per_sample).into());
-      }
-    } else if id == b"data" {
-      data_len_opt = Some(size);
-      break;
-    } else {
-      let mut skip = vec![0u8; size as usize];
-      reader.read_exact(&mut skip)?;
+  let sample_format = match (audio_format, bits_per_sample) {
+    (WAVE_FORMAT_PCM, 16) => SampleFormat::Pcm16,
+    (WAVE_FORMAT_PCM, 24) => SampleFormat::Pcm24,
+    (WAVE_FORMAT_IEEE_FLOAT, 32) => SampleFormat::Float32,
+    _ => {
+      return Err(bad_wav(format!(
+        "unsupported WAV format {} with {} bits per sample, need 16/24 bit PCM or 32 bit float",
+        audio_format, bits_per_sample
+      )));
     }
+  };
+  Ok((sample_format, channels, sample_rate))
+}
 
-    if size % 2 == 1 {
-      let mut pad = [0u8; 1];
-      reader.read_exact(&mut pad)?;
+// Reads what has arrived, at least a byte unless the stream has ended.
+fn read_some<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+  loop {
+    match reader.read(buf) {
+      Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+      result => return result,
     }
   }
+}
 
-  let data_len = data_len_opt.ok_or("missing data chunk")?;
-  if channels == 0 || sample_rate == 0 {
-    return Err("missing WAV fmt info".into());
+// One client for every phrase, so the connection to the server is kept
+// alive. No overall timeout: a long phrase streams for as long as the server
+// takes to synthesize it.
+fn client() -> &'static reqwest::blocking::Client {
+  static CLIENT: OnceLock<reqwest::blocking::Client> = OnceLock::new();
+  CLIENT.get_or_init(|| {
+    reqwest::blocking::Client::builder()
+      .connect_timeout(CONNECT_TIMEOUT)
+      .timeout(None)
+      .build()
+      .expect("failed to build the OpenTTS http client")
+  })
+}
+
+// Decodes the WAV as it downloads (chunked or not), sending every whole
+// frame as soon as it is in so playback starts with the first bytes of
+// audio, not after the whole response or even a full chunk of it.
+fn stream_wav_over_http(
+  url: &str,
+  tx: Sender<AudioChunk>,
+  target_sr: u32,
+  cancel: &CancellationToken,
+) -> Result<crate::tts::SpeakOutcome, TtsError> {
+  let started = Instant::now();
+  let resp = client()
+    .get(url)
+    .header(reqwest::header::ACCEPT, "audio/wav")
+    .send()
+    .map_err(|e| TtsError::OpenTtsUnreachable {
+      url: url.to_string(),
+      reason: e.to_string(),
+    })?;
+
+  if !resp.status().is_success() {
+    return Err(TtsError::Synthesis(format!(
+      "HTTP {} from OpenTTS",
+      resp.status()
+    )));
   }
+
+  let mut reader = BufReader::new(resp);
+  let wav = read_wav_header(&mut reader)?;
+  let channels = wav.channels;
   log(
     "info",
     &format!(
-      "OpenTTS WAV: PCM16LE, {} ch @ {} Hz, data {} bytes (target {} Hz)",
-      channels, sample_rate, data_len, target_sr
+      "OpenTTS WAV: {:?}, {} ch @ {} Hz, data {} (target {} Hz)",
+      wav.sample_format,
+      channels,
+      wav.sample_rate,
+      wav
+        .data_len
+        .map(|len| format!("{} bytes", len))
+        .unwrap_or_else(|| "streamed".to_string()),
+      target_sr
     ),
   );
 
-  // IMPORTANT: Don't `read_exact(data_len)` in one shot.
-  let samples_per_chunk = crate::tts::CHUNK_FRAMES * channels as usize;
-
-  if sample_rate == target_sr {
-    let mut remaining = data_len as usize;
-    let mut pending: Vec<f32> = Vec::with_capacity(samples_per_chunk * 2);
-    let mut buf = vec![0u8; 8192];
-
-    while remaining > 0 {
-      if interrupt_counter.load(Ordering::SeqCst) != expected_interrupt {
-        return Ok(crate::tts::SpeakOutcome::Interrupted);
-      }
-
-      let want = remaining.min(buf.len());
-      let mut read_bytes = 0usize;
-      while read_bytes < want {
-        let n = reader.read(&mut buf[read_bytes..want])?;
-        if n == 0 {
-          break;
-        }
-        read_bytes += n;
-      }
-      if read_bytes < want {
-        return Err(
-          format!(
-            "failed to fill whole buffer: expected {} bytes, got {}",
-            want, read_bytes
-          )
-          .into(),
-        );
-      }
-      remaining -= want;
-
-      // Read all PCM data first
-      let pcm = match read_exact_in_chunks(&mut reader, remaining) {
-        Ok(v) => v,
-        Err(e) => return Err(e),
-      };
-      // After reading the rest, no bytes left
-      remaining = 0;
+  let frame_bytes = wav.sample_format.bytes() * channels as usize;
+  let mut buf = vec![0u8; crate::tts::CHUNK_FRAMES * frame_bytes];
+  // bytes at the start of buf not sent yet: less than a frame
+  let mut filled = 0;
+  let mut remaining = wav.data_len;
+  let mut first_audio = true;
+  let mut decoded: Vec<f32> = Vec::with_capacity(crate::tts::CHUNK_FRAMES * channels as usize);
 
-      // Decode PCM16LE -> f32
-      let mut decoded: Vec<f32> = Vec::with_capacity(pcm.len() / 2);
-      for i in (0..pcm.len()).step_by(2) {
-        let s = i16::from_le_bytes([pcm[i], pcm[i + 1]]);
-        decoded.push(s as f32 / 32768.0);
-      }
-      // Resample once
-      let resampled = resample_to(&decoded, channels, sample_rate, target_sr);
-      // Normalize to avoid volume drift
-      let max_val = resampled.iter().map(|v| v.abs()).fold(0.0, f32::max);
-      let factor = if max_val > 1.0 { 1.0 / max_val } else { 1.0 };
-      let resampled: Vec<f32> = resampled.into_iter().map(|v| v * factor).collect();
-      let mut offset = 0usize;
-      while offset < resampled.len() {
-        if interrupt_counter.load(Ordering::SeqCst) != expected_interrupt {
-          return Ok(crate::tts::SpeakOutcome::Interrupted);
-        }
-        let end = (offset + samples_per_chunk).min(resampled.len());
-        let mut data = resampled[offset..end].to_vec();
-        let aligned = data.len() - (data.len() % channels as usize);
-        if aligned == 0 {
-          break;
-        }
-        data.truncate(aligned);
-        tx.send(AudioChunk {
-          data,
-          channels,
-          sample_rate: target_sr,
-        })?;
-        offset = end;
-      }
+  loop {
+    if cancel.is_cancelled() {
+      return Ok(crate::tts::SpeakOutcome::Interrupted);
     }
 
-    let aligned = pending.len() - (pending.len() % channels as usize);
-    pending.truncate(aligned);
-    if !pending.is_empty() {
-      tx.send(AudioChunk {
-        data: pending,
+    // the data chunk may be followed by other chunks, don't read into them
+    let room = buf.len() - filled;
+    let want = remaining.map_or(room, |r| r.min(room));
+    if want == 0 {
+      break;
+    }
+    let n = read_some(&mut reader, &mut buf[filled..filled + want]).map_err(bad_wav)?;
+    if let Some(r) = remaining.as_mut() {
+      *r -= n;
+    }
+    filled += n;
+    let whole = filled - (filled % frame_bytes);
+    if whole > 0 {
+      decoded.clear();
+      wav.sample_format.decode(&buf[..whole], &mut decoded);
+      let data = resample_to(&decoded, channels, wav.sample_rate, target_sr);
+      let chunk = AudioChunk {
+        data: data.into(),
         channels,
         sample_rate: target_sr,
-      })?;
-    }
-  } else {
-    let mut pcm = vec![0u8; data_len as usize];
-    let mut read_bytes = 0usize;
-    while read_bytes < pcm.len() {
-      let n = reader.read(&mut pcm[read_bytes..])?;
-      if n == 0 {
-        break;
+      };
+      if first_audio {
+        first_audio = false;
+        log(
+          "debug",
+          &format!(
+            "OpenTTS first audio after {} ms",
+            started.elapsed().as_millis()
+          ),
+        );
       }
-      read_bytes += n;
+      crate::captions::synthesized(&tx, &chunk);
+      tx.send(chunk)
+        .map_err(|e| TtsError::Synthesis(e.to_string()))?;
+      buf.copy_within(whole..filled, 0);
+      filled -= whole;
     }
-    if read_bytes < pcm.len() {
-      return Err(
-        format!(
-          "failed to read PCM data: expected {} bytes, got {}",
-          pcm.len(),
-          read_bytes
-        )
-        .into(),
-      );
-    }
-
-    let mut decoded: Vec<f32> = Vec::with_capacity(pcm.len() / 2);
-    for i in (0..pcm.len()).step_by(2) {
-      let s = i16::from_le_bytes([pcm[i], pcm[i + 1]]);
-      decoded.push(s as f32 / 32768.0);
+    if n == 0 {
+      // a partial frame can only be the truncated end of the stream
+      if let Some(short) = remaining.filter(|r| *r > 0) {
+        log(
+          "warning",
+          &format!("OpenTTS WAV ended {} bytes short of its data size", short),
+        );
+      }
+      break;
     }
-    let mut resampled = resample_to(&decoded, channels, sample_rate, target_sr);
-    // normalize to fixed peak level
-    let max_val = resampled.iter().map(|v| v.abs()).fold(0.0, f32::max);
-    let target_peak = 0.95_f32;
-    let factor = if max_val > 0.0 {
-      target_peak / max_val
-    } else {
-      1.0
-    };
-    resampled = resampled.into_iter().map(|v| v * factor).collect();
-    // send entire resampled audio as one chunk
-    let aligned_len = resampled.len() - (resampled.len() % channels as usize);
-    let data = if aligned_len > 0 {
-      resampled[..aligned_len].to_vec()
-    } else {
-      Vec::new()
-    };
-    tx.send(AudioChunk {
-      data,
-      channels,
-      sample_rate: target_sr,
-    })?;
   }
 
   Ok(crate::tts::SpeakOutcome::Completed)
 }
+
+// the audio from the server can't be read
+fn bad_wav(e: impl ToString) -> TtsError {
+  TtsError::Synthesis(format!("bad WAV from OpenTTS: {}", e.to_string()))
+}
@@ -3,15 +3,16 @@
 // ------------------------------------------------------------------
 
 use crate::audio::AudioChunk;
+use crate::cancel::CancellationToken;
+use crate::error::TtsError;
 
 use crossbeam_channel::Sender;
 use std::sync::{
   Arc, Mutex,
-  atomic::{AtomicBool, AtomicU64, Ordering},
+  atomic::{AtomicBool, Ordering},
 };
 use std::thread;
 use std::time::Duration;
-use tokio::runtime::Runtime;
 extern crate supersonic2_tts as supersonic2_tts_crate;
 use super::{SUPSONIC_ENGINE, SpeakOutcome};
 use supersonic2_tts_crate::TtsEngine;
@@ -19,9 +20,6 @@ use supersonic2_tts_crate::TtsEngine;
 // API
 // ------------------------------------------------------------------
 
-pub const SUPERSONIC2_VOICE_STYLES: [&str; 10] =
-  ["M1", "M2", "M3", "M4", "M5", "F1", "F2", "F3", "F4", "F5"];
-
 pub struct StreamingTts {
   engine: Arc<Mutex<TtsEngine>>,
   pub is_speaking: Arc<AtomicBool>,
@@ -31,15 +29,11 @@ pub struct StreamingTts {
 }
 
 // Engine initialization
-pub fn start_supersonic_engine() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-  let rt = tokio::runtime::Builder::new_current_thread()
-    .enable_all()
-    .build()?;
-
-  let home = crate::util::get_user_home_path().expect("Could not determine home directory");
-  let onnx = home.join(".vtmate/tts/supersonic2-model/onnx");
-  let base = home.join(".vtmate/tts/supersonic2-model");
-  let engine = rt.block_on(TtsEngine::new(onnx, base, false))?;
+pub fn start_supersonic_engine() -> crate::error::Result<()> {
+  let (onnx, base) = model_dirs();
+  let engine = crate::util::runtime()
+    .block_on(TtsEngine::new(onnx, base, false))
+    .map_err(|e| TtsError::Synthesis(e.to_string()))?;
 
   SUPSONIC_ENGINE.set(Arc::new(Mutex::new(engine))).ok();
   Ok(())
@@ -53,20 +47,16 @@ pub fn speak_via_supersonic2(
   _gain: f32,
   language: &str,
   tx: Sender<crate::audio::AudioChunk>,
-  interrupt_counter: Arc<AtomicU64>,
-  expected_interrupt: u64,
-) -> Result<SpeakOutcome, Box<dyn std::error::Error + Send + Sync>> {
+  cancel: &CancellationToken,
+) -> crate::error::Result<SpeakOutcome> {
   if text.is_empty() {
     return Ok(SpeakOutcome::Completed);
   }
-  let rt = tokio::runtime::Builder::new_current_thread()
-    .enable_all()
-    .build()?;
   let engine = SUPSONIC_ENGINE.get_or_init(|| {
-    let home = crate::util::get_user_home_path().expect("Could not determine home directory");
-    let onnx = home.join(".vtmate/tts/supersonic2-model/onnx");
-    let base = home.join(".vtmate/tts/supersonic2-model");
-    let e = rt.block_on(TtsEngine::new(onnx, base, false)).unwrap();
+    let (onnx, base) = model_dirs();
+    let e = crate::util::runtime()
+      .block_on(TtsEngine::new(onnx, base, false))
+      .unwrap();
     Arc::new(Mutex::new(e))
   });
 
@@ -75,14 +65,14 @@ pub fn speak_via_supersonic2(
   let mut streaming = StreamingTts::new(engine.clone());
   streaming.set_voice(voice);
 
-  // interrupt monitoring
+  // interrupt monitoring, until the phrase is synthesized
   let interrupt_flag = streaming.interrupt_flag.clone();
-  let int_counter = interrupt_counter.clone();
-  let expected = expected_interrupt;
+  let synthesis = cancel.child();
+  let monitored = synthesis.clone();
 
   thread::spawn(move || {
     loop {
-      if int_counter.load(Ordering::SeqCst) != expected {
+      if monitored.is_cancelled() {
         interrupt_flag.store(true, Ordering::Relaxed);
         break;
       }
@@ -90,10 +80,9 @@ pub fn speak_via_supersonic2(
     }
   });
 
-  let rt2 = tokio::runtime::Builder::new_current_thread()
-    .enable_all()
-    .build()?;
-  let res = rt2.block_on(streaming.speak_stream(text, tx.clone(), language, speed));
+  let res =
+    crate::util::runtime().block_on(streaming.speak_stream(text, tx.clone(), language, speed));
+  synthesis.cancel();
 
   match res {
     Ok(_) => Ok(SpeakOutcome::Completed),
@@ -104,6 +93,14 @@ pub fn speak_via_supersonic2(
 // PRIVATE
 // ------------------------------------------------------------------
 
+// (onnx dir, model dir) as set up by assets::ensure_supersonic2_assets
+fn model_dirs() -> (std::path::PathBuf, std::path::PathBuf) {
+  let base = std::env::var_os("SUPERSONIC2_DATA_DIRECTORY")
+    .map(std::path::PathBuf::from)
+    .expect("SUPERSONIC2_DATA_DIRECTORY is not set");
+  (base.join("onnx"), base)
+}
+
 // smaller chunks reduce long synth stalls -> fewer underruns/glitches.
 // (Words are variable length; 10–15 is a safer range for real-time streaming.)
 const MAX_CHUNK_SIZE: usize = 15;
@@ -159,17 +156,13 @@ impl StreamingTts {
     let chunks = Self::split_into_chunks(text);
     let engine = self.engine.clone();
     let voice = self.voice.clone();
-    let gain = self.gain;
+    let gain = self.gain * crate::tts::gain();
     let interrupt_flag_main = self.interrupt_flag.clone();
     let interrupt_flag_thread = interrupt_flag_main.clone();
 
     let language = language.to_string();
     let handle = thread::spawn(move || {
-      // Create a single runtime for the thread
-      let rt = match Runtime::new() {
-        Ok(r) => r,
-        Err(_) => return,
-      };
+      let rt = crate::util::runtime();
       for chunk in chunks {
         if interrupt_flag_thread.load(Ordering::Relaxed) {
           break;
@@ -193,13 +186,14 @@ impl StreamingTts {
                 }
               }
               let audio = AudioChunk {
-                data: samples,
+                data: samples.into(),
                 channels: 1,
                 sample_rate: 48000,
               };
               if interrupt_flag_thread.load(Ordering::Relaxed) {
                 break;
               }
+              crate::captions::synthesized(&tx, &audio);
               if tx.send(audio).is_err() {
                 break;
               }
@@ -0,0 +1,179 @@
+// ------------------------------------------------------------------
+//  TTS voices
+// ------------------------------------------------------------------
+
+// Voice tables are kept apart from the engines so settings validate the
+// same way whichever backends this binary was built with.
+
+// API
+// ------------------------------------------------------------------
+
+pub const KOKORO_VOICES_PER_LANGUAGE: &[(&str, &[&str])] = &[
+  // English language
+  // ----------------------------------------
+  (
+    "en",
+    &[
+      // American english - female
+      "af_alloy",
+      "af_aoede",
+      "af_bella",
+      "af_heart",
+      "af_jessica",
+      "af_kore",
+      "af_nicole",
+      "af_nova",
+      "af_river",
+      "af_sarah",
+      "af_sky",
+      // American english - male
+      "am_adam",
+      "am_echo",
+      "am_eric",
+      "am_fenrir",
+      "am_liam",
+      "am_michael",
+      "am_onyx",
+      "am_puck",
+      "am_santa",
+      // British english - female
+      "bf_alice",
+      "bf_emma",
+      "bf_isabella",
+      "bf_lily",
+      // British english - male
+      "bm_daniel",
+      "bm_fable",
+      "bm_george",
+      "bm_lewis",
+    ],
+  ),
+  // Spanish language
+  // ----------------------------------------
+  (
+    "es",
+    &[
+      // Spanish - female
+      "ef_dora", // Spanish - male
+      "em_alex", "em_santa",
+    ],
+  ),
+  // Mandarin chinese language
+  // ----------------------------------------
+  (
+    "zh",
+    &[
+      // Mandarin chinese - female
+      "zf_xiaobei",
+      "zf_xiaoni",
+      "zf_xiaoxiao",
+      "zf_xiaoyi",
+      // Mandarin chinese - male
+      "zm_yunjian",
+      "zm_yunxi",
+      "zm_yunxia",
+      "zm_yunyang",
+    ],
+  ),
+  // Japanese language
+  // ----------------------------------------
+  (
+    "ja",
+    &[
+      // Japanese - female
+      "jf_alpha",
+      "jf_gongitsune",
+      "jf_nezumi",
+      "jf_tebukuro",
+      // Japanese - male
+      "jm_kumo",
+    ],
+  ),
+  // Portuguese / Brazil language
+  // ----------------------------------------
+  (
+    "pt",
+    &[
+      // Portuguese - female
+      "pf_dora", // Portuguese - male
+      "pm_alex", "pm_santa",
+    ],
+  ),
+  // Italian language
+  // ----------------------------------------
+  (
+    "it",
+    &[
+      // Italian - female
+      "if_sara",
+      // Italian - male
+      "im_nicola",
+    ],
+  ),
+  // Hindi language
+  // ----------------------------------------
+  (
+    "hi",
+    &[
+      // Hindi - female
+      "hf_alpha", "hf_beta", // Hindi - male
+      "hm_omega", "hm_psi",
+    ],
+  ),
+  // French language
+  // ----------------------------------------
+  (
+    "fr",
+    &[
+      // French - female
+      "ff_siwis",
+    ],
+  ),
+];
+
+pub const _DEFAULT_KOKORO_VOICES_PER_LANGUAGE: &[(&str, &str)] = &[
+  ("en", "bf_emma"),
+  ("es", "em_santa"),
+  ("zh", "zf_xiaoni"),
+  ("ja", "jm_kumo"),
+  ("pt", "pf_dora"),
+  ("it", "if_sara"),
+  ("hi", "hf_alpha"),
+  ("fr", "ff_siwis"),
+];
+
+pub const DEFAULT_OPENTTS_VOICES_PER_LANGUAGE: &[(&str, &str)] = &[
+  ("ar", "festival:ara_norm_ziad_hts"),
+  ("bn", "flite:cmu_indic_ben_rm"),
+  ("ca", "festival:upc_ca_ona_hts"),
+  ("cs", "festival:czech_machac"),
+  ("de", "glow-speak:de_thorsten"),
+  ("el", "glow-speak:el_rapunzelina"),
+  ("en", "larynx:cmu_fem-glow_tts"),
+  ("es", "larynx:karen_savage-glow_tts"),
+  ("fi", "glow-speak:fi_harri_tapani_ylilammi"),
+  ("fr", "larynx:gilles_le_blanc-glow_tts"),
+  ("gu", "flite:cmu_indic_guj_ad"),
+  ("hi", "flite:cmu_indic_hin_ab"),
+  ("hu", "glow-speak:hu_diana_majlinger"),
+  ("it", "larynx:riccardo_fasol-glow_tts"),
+  ("ja", "coqui-tts:ja_kokoro"),
+  ("kn", "flite:cmu_indic_kan_plv"),
+  ("ko", "glow-speak:ko_kss"),
+  ("mr", "flite:cmu_indic_mar_aup"),
+  ("nl", "glow-speak:nl_rdh"),
+  ("pa", "flite:cmu_indic_pan_amp"),
+  ("ru", "glow-speak:ru_nikolaev"),
+  ("sv", "glow-speak:sv_talesyntese"),
+  ("sw", "glow-speak:sw_biblia_takatifu"),
+  ("ta", "flite:cmu_indic_tam_sdr"),
+  ("te", "marytts:cmu-nk-hsmm"),
+  ("tr", "marytts:dfki-ot-hsmm"),
+  ("zh", "coqui-tts:zh_baker"),
+];
+
+pub const SUPERSONIC2_VOICE_STYLES: [&str; 10] =
+  ["M1", "M2", "M3", "M4", "M5", "F1", "F2", "F3", "F4", "F5"];
+
+// Supported languages for Supersonic2 TTS
+pub const SUPERSONIC2_LANGS: &[&str] = &["en", "es", "fr", "ko", "pt"];
@@ -2,7 +2,7 @@
 //  UI
 // ------------------------------------------------------------------
 
-use crate::state::{GLOBAL_STATE, get_speed};
+use crate::state::{GLOBAL_STATE, TurnState, get_speed};
 use crate::util::get_flag;
 use crossbeam_channel::Receiver;
 use crossterm::{
@@ -12,8 +12,9 @@ use crossterm::{
   terminal::{self, Clear, ClearType, ScrollUp},
 };
 use std::io::{self, Write};
+use std::sync::PoisonError;
 use std::sync::{
-  Arc, Mutex,
+  Arc, Mutex, OnceLock,
   atomic::{AtomicBool, Ordering},
 };
 use std::thread;
@@ -24,9 +25,79 @@ use std::time::Duration;
 
 pub static STOP_STREAM: AtomicBool = AtomicBool::new(false);
 
-// ANSI labels
-pub const USER_LABEL: &str = "\x1b[47;30mUSER:\x1b[0m";
-pub const ASSIST_LABEL: &str = "\x1b[48;5;22;37mASSISTANT:\x1b[0m";
+// labels of the turns, set from the flags at startup
+static LABELS: OnceLock<Labels> = OnceLock::new();
+
+/// How the turns are labelled (--user-label, --assistant-label, their
+/// colors and --timestamps).
+#[derive(Clone, Debug)]
+pub struct Labels {
+  pub user: String,
+  pub assistant: String,
+  // SGR parameters of the labels, as made by label_color
+  pub user_color: String,
+  pub assistant_color: String,
+  // the time of the turn before its label
+  pub timestamps: bool,
+}
+
+impl Default for Labels {
+  fn default() -> Self {
+    Self {
+      user: "USER".to_string(),
+      assistant: "ASSISTANT".to_string(),
+      user_color: "47;30".to_string(),
+      assistant_color: "48;5;22;37".to_string(),
+      timestamps: false,
+    }
+  }
+}
+
+/// Sets the labels for the session, before anything is shown.
+pub fn init_labels(labels: Labels) {
+  let _ = LABELS.set(labels);
+}
+
+/// The labels of the session, the defaults until init_labels.
+pub fn labels() -> &'static Labels {
+  LABELS.get_or_init(Labels::default)
+}
+
+/// The colored label of a user turn.
+pub fn user_label() -> String {
+  let labels = labels();
+  paint(&labels.user, &labels.user_color, labels.timestamps)
+}
+
+/// The colored label of a reply.
+pub fn assist_label() -> String {
+  agent_label(&labels().assistant)
+}
+
+/// The colored label of a reply of the agent `name` (debates, attach).
+pub fn agent_label(name: &str) -> String {
+  let labels = labels();
+  paint(name, &labels.assistant_color, labels.timestamps)
+}
+
+/// The SGR parameters of a `BG[/FG]` label color: color names or 0-255
+/// terminal colors, as in `white/black` or `22/white`.
+pub fn label_color(spec: &str) -> Result<String, String> {
+  let (bg, fg) = match spec.split_once('/') {
+    Some((bg, fg)) => (bg, Some(fg)),
+    None => (spec, None),
+  };
+  let mut params = vec![color_param(bg, 40, 48)?];
+  if let Some(fg) = fg {
+    params.push(color_param(fg, 30, 38)?);
+  }
+  params.retain(|p| !p.is_empty());
+  if params.is_empty() {
+    // "none": no color, still reset after
+    params.push("0".to_string());
+  }
+  Ok(params.join(";"))
+}
 
 pub fn get_banner() -> &'static str {
   r#"
@@ -36,6 +107,8 @@ pub fn get_banner() -> &'static str {
 }
 
 const CHAR_DELAY_MS: u64 = 4;
+// length of the progress bar of the reply in the status bar
+const PROGRESS_LEN: usize = 10;
 
 pub fn spawn_ui_thread(
   ui_state: crate::state::UiState,
@@ -181,15 +254,23 @@ pub fn spawn_ui_thread(
             out.flush().unwrap();
 
             // Re-send history lines
-            for msg in conversation_history.lock().unwrap().iter() {
-              let role_label = if msg.role == "assistant" {
-                "\x1b[48;5;22;37mASSISTANT:\x1b[0m"
+            let hist = conversation_history
+              .lock()
+              .unwrap_or_else(PoisonError::into_inner);
+            for (i, msg) in hist.iter().enumerate() {
+              // the time of past turns isn't kept, they're shown without it
+              let labels = labels();
+              let mut role_label = if msg.role == "assistant" {
+                paint(&labels.assistant, &labels.assistant_color, false)
               } else {
-                "\x1b[47;30mUSER:\x1b[0m"
+                paint(&labels.user, &labels.user_color, false)
               };
+              if hist.is_bookmarked(i) {
+                role_label.push_str(" 🔖");
+              }
               handle_line_message(
                 &mut out,
-                role_label,
+                &role_label,
                 &mut buffer,
                 &mut ui_state,
                 &spinner,
@@ -239,7 +320,37 @@ pub fn spawn_ui_thread(
       } else {
         skip_next_bottom_bar = false;
       }
-      thread::sleep(Duration::from_millis(10));
+      thread::sleep(crate::power::poll_interval(Duration::from_millis(10)));
+    }
+  })
+}
+
+/// Stands in for the ui without a terminal (--headless, --daemon): every turn
+/// is written to stderr as plain lines, the ui messages are dropped.
+pub fn spawn_plain_thread(rx_ui: Receiver<String>) -> thread::JoinHandle<()> {
+  use crate::engine::TurnEvent;
+
+  let events = crate::events::subscribe();
+  thread::spawn(move || for _ in rx_ui {});
+  thread::spawn(move || {
+    for event in events {
+      match event {
+        TurnEvent::Transcript(text) => eprintln!("{}: {}", labels().user, one_line(&text)),
+        TurnEvent::Done(reply) => {
+          let agent = GLOBAL_STATE
+            .get()
+            .map(|s| {
+              s.agent_name
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+            })
+            .unwrap_or_else(|| labels().assistant.clone());
+          eprintln!("{}: {}", agent, one_line(&reply));
+        }
+        TurnEvent::Interrupted => eprintln!("(interrupted)"),
+        TurnEvent::Token(_) | TurnEvent::Phrase(_) => {}
+      }
     }
   })
 }
@@ -247,6 +358,43 @@ pub fn spawn_ui_thread(
 // PRIVATE
 // ------------------------------------------------------------------
 
+// "[14:05] USER:" with the label in its colors
+fn paint(text: &str, color: &str, timestamp: bool) -> String {
+  let time = if timestamp {
+    format!("\x1b[90m[{}]\x1b[0m ", chrono::Local::now().format("%H:%M"))
+  } else {
+    String::new()
+  };
+  format!("{}\x1b[{}m{}:\x1b[0m", time, color, text)
+}
+
+// one color of a label: `base` + 0-7 for the names, `extended`;5;N for 0-255
+fn color_param(color: &str, base: u8, extended: u8) -> Result<String, String> {
+  const NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+  ];
+  let color = color.trim().to_lowercase();
+  if let Some(i) = NAMES.iter().position(|n| *n == color) {
+    return Ok((base + i as u8).to_string());
+  }
+  match color.as_str() {
+    "grey" | "gray" => Ok(format!("{};5;244", extended)),
+    "none" | "" => Ok(String::new()),
+    _ => color
+      .parse::<u8>()
+      .map(|n| format!("{};5;{}", extended, n))
+      .map_err(|_| format!("unknown color '{}': a color name or 0-255", color)),
+  }
+}
+
+// a turn on a single log line
+fn one_line(text: &str) -> String {
+  crate::redact::redact(text)
+    .split_whitespace()
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
 // computes viewport for scroll
 fn viewport(buffer_len: usize, term_height: u16) -> (usize, usize) {
   let visible = term_height.saturating_sub(1) as usize;
@@ -437,31 +585,42 @@ fn render_bottom_bar<W: Write>(
     return String::new();
   }
   let state = GLOBAL_STATE.get().expect("AppState not initialized");
-  let agent_name = state.agent_name.lock().unwrap().clone();
-  let speak = ui_state.agent_speaking.load(Ordering::Relaxed);
-
-  let think = ui_state.thinking.load(Ordering::Relaxed);
-  let play = ui_state.playing.load(Ordering::Relaxed);
-  let recording_paused = state.recording_paused.load(Ordering::Relaxed);
-
-  let status = if recording_paused {
+  let agent_name = state
+    .agent_name
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .clone();
+  let turn = state.turn.get();
+  let dnd = crate::dnd::is_active();
+  let recording_paused = state.recording_paused.load(Ordering::Relaxed) || dnd;
+
+  let status = if dnd {
+    "🌙".to_string()
+  } else if recording_paused {
     "⏸️".to_string()
-  } else if play {
-    format!("🔊 ")
-  } else if speak {
-    format!("🎤 ")
-  } else if think {
-    format!("🤔 {}", spinner[ui_state.spinner_index % spinner.len()])
   } else {
-    format!("🎤 ")
+    match turn {
+      TurnState::Speaking => "🔊 ".to_string(),
+      TurnState::Generating => {
+        format!("🤔 {}", spinner[ui_state.spinner_index % spinner.len()])
+      }
+      _ => "🎤 ".to_string(),
+    }
   };
 
-  let speed_str = format!("[{:.1}x]", get_speed());
+  let speed_str = if crate::tts::is_muted() {
+    "[text]".to_string()
+  } else {
+    format!("[{:.1}x]", get_speed())
+  };
 
   // Check if debate mode is enabled
   let debate_enabled = state.debate_enabled.load(Ordering::Relaxed);
   let mode = if debate_enabled {
-    let debate_agents = state.debate_agents.lock().unwrap();
+    let debate_agents = state
+      .debate_agents
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner);
     if debate_agents.len() >= 2 {
       let agent1_name = debate_agents[0].name.chars().take(8).collect::<String>();
       let agent2_name = debate_agents[1].name.chars().take(8).collect::<String>();
@@ -472,14 +631,24 @@ fn render_bottom_bar<W: Write>(
     } else {
       format!("\x1b[44m\x1b[37m CONVERSATION \x1b[0m")
     }
+  } else if crate::rapid::is_active() {
+    "\x1b[105m\x1b[30m RAPID \x1b[0m".to_string()
   } else {
     format!("\x1b[44m\x1b[37m CONVERSATION \x1b[0m")
   };
 
-  let recording_paused_str = if recording_paused {
-    "\x1b[43m\x1b[30m  paused  \x1b[0m"
+  let recording_paused_str = if dnd {
+    "\x1b[45m\x1b[30m   dnd    \x1b[0m".to_string()
+  } else if recording_paused {
+    "\x1b[43m\x1b[30m  paused  \x1b[0m".to_string()
+  } else if let Some(left) = crate::followup::remaining() {
+    // your turn, for a while
+    format!(
+      "\x1b[102m\x1b[30m listening {:>2}s \x1b[0m",
+      left.as_secs_f32().ceil() as u64
+    )
   } else {
-    "\x1b[42m\x1b[30m listening \x1b[0m"
+    "\x1b[42m\x1b[30m listening \x1b[0m".to_string()
   };
 
   let internal_status = format!(
@@ -489,7 +658,7 @@ fn render_bottom_bar<W: Write>(
     } else {
       "\x1b[97m█\x1b[0m"
     },
-    if speak {
+    if turn == TurnState::Listening {
       "\x1b[97m█\x1b[0m"
     } else {
       "\x1b[90m█\x1b[0m"
@@ -512,13 +681,78 @@ fn render_bottom_bar<W: Write>(
     "\x1b[42m\x1b[30m LIVE \x1b[0m"
   };
 
-  let lang_guard = state.language.lock().unwrap();
+  let lang_guard = state
+    .language
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
   let flag = get_flag(&lang_guard);
-  let agent_display = format!("{} {}", flag, agent_name);
+  let agent_name = match crate::personas::active() {
+    Some(persona) => format!("{} as {}", agent_name, persona),
+    None => agent_name,
+  };
+  let agent_display = match crate::contexts::label() {
+    Some(context) => format!("{} {} \x1b[90m[{}]\x1b[0m", flag, agent_name, context),
+    None => format!("{} {}", flag, agent_name),
+  };
+  // the input is clipped (too much --mic-gain), or the gain when set
+  let mic = if crate::gain::is_clipping() {
+    "\x1b[41m\x1b[37m CLIP \x1b[0m ".to_string()
+  } else if crate::gain::get() != 1.0 {
+    format!("\x1b[90mmic {:.1}x\x1b[0m ", crate::gain::get())
+  } else {
+    String::new()
+  };
+  // an utterance was just ignored and why, then how many were
+  let ignored = match crate::ignored::recent() {
+    Some(reason) => format!("\x1b[43m\x1b[30m ignored: {} \x1b[0m ", reason.label()),
+    None => match crate::ignored::count() {
+      0 => String::new(),
+      n => format!("\x1b[90m{} ignored\x1b[0m ", n),
+    },
+  };
+  // the latency of the last turn against the one aimed for (--latency-target-ms)
+  let latency = match (crate::latency::target_ms(), crate::latency::reached_ms()) {
+    (Some(target), Some(reached)) => format!(
+      "{}{}ms\x1b[0m ",
+      if reached <= target {
+        "\x1b[32m"
+      } else {
+        "\x1b[31m"
+      },
+      reached
+    ),
+    (Some(target), None) => format!("\x1b[90m<{}ms\x1b[0m ", target),
+    _ => String::new(),
+  };
+  // how far the voice of the reply got and how long it still speaks for
+  let progress = match crate::playback::progress() {
+    Some(progress) if turn == TurnState::Speaking => {
+      let done = (progress.fraction() * PROGRESS_LEN as f32).round() as usize;
+      format!(
+        "\x1b[36m{}\x1b[90m{} {:.0}s left\x1b[0m ",
+        "━".repeat(done),
+        "━".repeat(PROGRESS_LEN.saturating_sub(done)),
+        progress.remaining_secs.ceil()
+      )
+    }
+    _ => String::new(),
+  };
+  // usage counts are being kept (--telemetry)
+  let telemetry = if crate::telemetry::is_enabled() {
+    "\x1b[90mtelemetry\x1b[0m "
+  } else {
+    ""
+  };
   let combined_status = if debate_enabled {
-    format!("{} {} {} ", mode, ptt, internal_status)
+    format!(
+      "{} {} {} {}{}{}{}{}",
+      mode, ptt, internal_status, progress, ignored, mic, latency, telemetry
+    )
   } else {
-    format!("{} {} {} {} ", mode, ptt, agent_display, internal_status)
+    format!(
+      "{} {} {} {} {}{}{}{}{}",
+      mode, ptt, agent_display, internal_status, progress, ignored, mic, latency, telemetry
+    )
   };
 
   let cols = crossterm::terminal::size().unwrap_or((80, 24)).0 as usize;
@@ -533,14 +767,14 @@ fn render_bottom_bar<W: Write>(
   );
 
   let max_bar_len = if available > 40 { 40 } else { available };
-  let peak_val = *ui_state.peak.lock().unwrap();
+  let peak_val = *ui_state.peak.lock().unwrap_or_else(PoisonError::into_inner);
   let mut bar_len = ((peak_val * (max_bar_len as f32)).round() as usize).min(max_bar_len);
   if recording_paused {
     bar_len = 0;
   }
   let bar_color = if recording_paused {
     "\x1b[37m"
-  } else if speak {
+  } else if turn == TurnState::Listening {
     "\x1b[31m"
   } else {
     "\x1b[37m"
@@ -617,9 +851,18 @@ fn redraw_buffer<W: Write>(out: &mut W, buffer: &[String]) {
 fn render_debate_modal<W: Write>(out: &mut W, buffer: &[String]) {
   let state = GLOBAL_STATE.get().expect("AppState not initialized");
   let agents = state.agents.as_ref();
-  let agent1_idx = *state.debate_modal_selected_agent1.lock().unwrap();
-  let agent2_idx = *state.debate_modal_selected_agent2.lock().unwrap();
-  let focus = *state.debate_modal_focus.lock().unwrap();
+  let agent1_idx = *state
+    .debate_modal_selected_agent1
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
+  let agent2_idx = *state
+    .debate_modal_selected_agent2
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
+  let focus = *state
+    .debate_modal_focus
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner);
 
   let (cols, rows) = terminal::size().unwrap_or((80, 24));
 
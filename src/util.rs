@@ -3,12 +3,13 @@
 // ------------------------------------------------------------------
 
 use crossterm::cursor::Show;
+use crossterm::event::DisableFocusChange;
 use crossterm::{
   cursor::MoveTo,
   execute,
   terminal::{Clear, ClearType},
 };
-use directories::UserDirs;
+use directories::{BaseDirs, UserDirs};
 use encoding_rs::*;
 use std::cell::Cell;
 use std::fs;
@@ -16,14 +17,24 @@ use std::io::IsTerminal;
 use std::io::{self, Read, Write};
 use std::path::PathBuf;
 use std::process;
-use std::sync::OnceLock;
-use std::sync::atomic::AtomicU64;
+use std::sync::PoisonError;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
 use std::thread;
 use std::time::{Duration, Instant};
 
 /// Global timestamp of last speech end (in ms since program start).
 pub static SPEECH_END_AT: AtomicU64 = AtomicU64::new(0);
 
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+
+// run by `shutdown` before exiting (save the session)
+static SHUTDOWN_HOOKS: Mutex<Vec<Box<dyn FnOnce() + Send>>> = Mutex::new(Vec::new());
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+// longest wait for the reply and playback in progress to stop
+const SHUTDOWN_GRACE: Duration = Duration::from_millis(500);
+
 thread_local! {
   static IN_CODE_BLOCK: Cell<bool> = Cell::new(false);
 }
@@ -103,6 +114,19 @@ pub fn env_u64(name: &str, default: u64) -> u64 {
     .unwrap_or(default)
 }
 
+/// The async runtime shared by the llm requests and the tts engines, built on
+/// first use. Call `block_on` from plain threads only, never from a task.
+pub fn runtime() -> &'static tokio::runtime::Runtime {
+  RUNTIME.get_or_init(|| {
+    tokio::runtime::Builder::new_multi_thread()
+      .worker_threads(2)
+      .thread_name("vtmate-async")
+      .enable_all()
+      .build()
+      .expect("failed to build the async runtime")
+  })
+}
+
 pub fn get_flag(lang: &str) -> &str {
   match lang {
     "en" => "🇬🇧",
@@ -140,17 +164,56 @@ pub fn get_flag(lang: &str) -> &str {
 pub fn terminal_supported() -> bool {
   let is_tty = std::io::stdout().is_terminal();
   let term = std::env::var("TERM").unwrap_or_default();
-  is_tty && term != "dumb"
+  is_tty && term != "dumb" && ansi_enabled()
 }
 
 /// Returns the current user's home directory.
-/// Works on Unix (~, $HOME) and Windows.
+/// Works on Unix (~, $HOME) and Windows (%USERPROFILE%).
 pub fn get_user_home_path() -> Option<PathBuf> {
-  if let Ok(h) = std::env::var("HOME") {
-    Some(PathBuf::from(h))
-  } else {
-    UserDirs::new().map(|u| u.home_dir().to_path_buf())
+  ["HOME", "USERPROFILE"]
+    .into_iter()
+    .filter_map(std::env::var_os)
+    .find(|h| !h.is_empty())
+    .map(PathBuf::from)
+    .or_else(|| UserDirs::new().map(|u| u.home_dir().to_path_buf()))
+}
+
+/// Sets the data root (models, espeak data) given by --data-dir / VTMATE_DATA_DIR.
+pub fn set_data_dir(dir: Option<&str>) {
+  let dir = match dir {
+    Some(d) if !d.is_empty() => d,
+    _ => return,
+  };
+  let mut path = PathBuf::from(dir);
+  if path.starts_with("~")
+    && let Some(home) = get_user_home_path()
+  {
+    path = home.join(path.strip_prefix("~").unwrap_or(&path));
+  }
+  let _ = DATA_DIR.set(path);
+}
+
+// The Windows 10+ console only understands the ANSI colors and cursor moves
+// of the UI once virtual terminal processing is turned on, which this does.
+#[cfg(windows)]
+fn ansi_enabled() -> bool {
+  crossterm::ansi_support::supports_ansi()
+}
+
+#[cfg(not(windows))]
+fn ansi_enabled() -> bool {
+  true
+}
+
+/// Returns the data root: the configured one, or $XDG_DATA_HOME/vtmate
+/// (the platform data dir on macOS and Windows).
+pub fn get_data_dir() -> Option<PathBuf> {
+  if let Some(dir) = DATA_DIR.get() {
+    return Some(dir.clone());
   }
+  BaseDirs::new()
+    .map(|b| b.data_dir().join("vtmate"))
+    .or_else(|| get_user_home_path().map(|h| h.join(".vtmate")))
 }
 
 /// Strip special characters from text for TTS
@@ -189,37 +252,83 @@ pub fn strip_special_chars(s: &str) -> String {
   result
 }
 
-pub fn _strip_ansi(s: &str) -> String {
-  let mut result = String::new();
-  let mut in_escape = false;
-  for c in s.chars() {
-    if in_escape {
-      if c == 'm' {
-        in_escape = false;
-      }
+/// The text without its terminal escapes (colors, labels), as the llm and
+/// the saved conversations should see it.
+pub fn strip_ansi(s: &str) -> String {
+  let mut result = String::with_capacity(s.len());
+  let mut chars = s.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c != '\x1b' {
+      result.push(c);
       continue;
     }
-    if c == '\x1b' {
-      in_escape = true;
-      continue;
+    // ESC [ params final: the final byte is in @..~ (m for the colors)
+    if chars.next_if_eq(&'[').is_some() {
+      for c in chars.by_ref() {
+        if ('@'..='~').contains(&c) {
+          break;
+        }
+      }
+    } else {
+      chars.next();
     }
-    result.push(c);
   }
   result
 }
 
+/// Registers something to do before exiting through `shutdown`.
+pub fn on_shutdown(hook: impl FnOnce() + Send + 'static) {
+  SHUTDOWN_HOOKS
+    .lock()
+    .unwrap_or_else(PoisonError::into_inner)
+    .push(Box::new(hook));
+}
+
+/// Exits cleanly (Ctrl+C, SIGTERM, the terminal closed): stops the reply and
+/// playback in progress, runs the shutdown hooks and restores the terminal.
+pub fn shutdown(code: i32) -> ! {
+  if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+    // already shutting down (a second Ctrl+C): leave right away
+    terminate(code);
+  }
+  if let Some(state) = crate::state::GLOBAL_STATE.get() {
+    state.interrupter.interrupt();
+    state
+      .playback
+      .playback_active
+      .store(false, Ordering::Relaxed);
+    let deadline = Instant::now() + SHUTDOWN_GRACE;
+    while state.turn.get() == crate::state::TurnState::Generating && Instant::now() < deadline {
+      thread::sleep(Duration::from_millis(20));
+    }
+  }
+  let hooks = std::mem::take(
+    &mut *SHUTDOWN_HOOKS
+      .lock()
+      .unwrap_or_else(PoisonError::into_inner),
+  );
+  for hook in hooks {
+    hook();
+  }
+  terminate(code);
+}
+
 pub fn terminate(code: i32) -> ! {
-   // Disable raw mode if enabled, to restore terminal state
-   let _ = crossterm::terminal::disable_raw_mode();
-  // show cursor and clear bottom line before exiting
+  // Disable raw mode if enabled, to restore terminal state
+  let _ = crossterm::terminal::disable_raw_mode();
+  // show cursor and clear bottom line before exiting (not into a log file)
   let mut stdout = std::io::stdout();
-  let (_cols, rows) = crossterm::terminal::size().unwrap_or((80, 24));
-  let _ = execute!(
-    stdout,
-    MoveTo(0, rows.saturating_sub(1)),
-    Clear(ClearType::CurrentLine),
-    Show
-  );
+  if stdout.is_terminal() {
+    let (_cols, rows) = crossterm::terminal::size().unwrap_or((80, 24));
+    let _ = execute!(
+      stdout,
+      MoveTo(0, rows.saturating_sub(1)),
+      Clear(ClearType::CurrentLine),
+      Show,
+      // stop the focus reports of --notify, the shell would print them
+      DisableFocusChange
+    );
+  }
   stdout.flush().ok();
   thread::sleep(Duration::from_millis(100));
   process::exit(code);
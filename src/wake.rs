@@ -0,0 +1,124 @@
+// ------------------------------------------------------------------
+//  Wake and stop phrases (wake_phrases, stop_phrases)
+// ------------------------------------------------------------------
+//
+// An agent with wake phrases ignores what is said until one of them starts
+// an utterance ("computer, what time is it?"). From then on it listens
+// without them (hot listening) until a stop phrase is said. Each agent of
+// the settings has its own, so everyone in a household can call theirs.
+
+use crate::config::AgentSettings;
+use crossbeam_channel::Sender;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const STOP_PHRASE_DEFAULT: &str = "that's all";
+
+static HOT: AtomicBool = AtomicBool::new(false);
+
+// API
+// ------------------------------------------------------------------
+
+/// What is left to answer of `text` with the wake phrases of `agent`: the
+/// text itself when hot listening (or without wake phrases), the request
+/// after a wake phrase, None when there is nothing to answer.
+pub fn filter(text: &str, agent: &AgentSettings, tx_ui: &Sender<String>) -> Option<String> {
+  let wake_phrases = phrases(&agent.wake_phrases);
+  if wake_phrases.is_empty() {
+    return Some(text.to_string());
+  }
+  let words: Vec<&str> = text.split_whitespace().collect();
+  let normalized: Vec<String> = words.iter().map(|w| normalize(w)).collect();
+  let normalized: Vec<&str> = normalized
+    .iter()
+    .map(String::as_str)
+    .filter(|w| !w.is_empty())
+    .collect();
+
+  let stop_phrases = match phrases(&agent.stop_phrases) {
+    stop_phrases if stop_phrases.is_empty() => phrases(STOP_PHRASE_DEFAULT),
+    stop_phrases => stop_phrases,
+  };
+  if stop_phrases.iter().any(|p| *p == normalized) {
+    crate::rapid::set_active(false);
+    if HOT.swap(false, Ordering::Relaxed) {
+      crate::log::log("info", "Stop phrase: waiting for a wake phrase");
+      let _ = tx_ui.send(format!(
+        "line|\n\x1b[35m💤 Waiting for \"{}\"\x1b[0m\n",
+        agent.wake_phrases.split(';').next().unwrap_or("").trim()
+      ));
+    }
+    return None;
+  }
+
+  let wake = wake_phrases
+    .iter()
+    .find(|p| normalized.len() >= p.len() && normalized[..p.len()] == p[..]);
+  let Some(wake) = wake else {
+    // the answer to a question of the agent (follow_up_questions), or rapid mode
+    if HOT.load(Ordering::Relaxed)
+      || crate::followup::remaining().is_some()
+      || crate::rapid::is_active()
+    {
+      return Some(text.to_string());
+    }
+    crate::log::log("debug", &format!("No wake phrase, ignored: '{}'", text));
+    return None;
+  };
+  if !HOT.swap(true, Ordering::Relaxed) {
+    crate::log::log("info", "Wake phrase: hot listening");
+    let _ = tx_ui.send(format!(
+      "line|\n\x1b[35m👂 Listening until \"{}\"\x1b[0m\n",
+      agent
+        .stop_phrases
+        .split(';')
+        .map(str::trim)
+        .find(|p| !p.is_empty())
+        .unwrap_or(STOP_PHRASE_DEFAULT)
+    ));
+  }
+  // the words of the wake phrase, and what was between them (punctuation)
+  let mut matched = 0;
+  let rest: Vec<&str> = words
+    .into_iter()
+    .skip_while(|w| {
+      let skip = matched < wake.len();
+      if skip && !normalize(w).is_empty() {
+        matched += 1;
+      }
+      skip
+    })
+    .collect();
+  let rest = rest.join(" ");
+  let rest = rest.trim_start_matches(|c: char| c.is_ascii_punctuation() || c.is_whitespace());
+  if rest.is_empty() {
+    crate::earcons::play(crate::earcons::Earcon::Listening);
+    return None;
+  }
+  Some(rest.to_string())
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// The normalized words of every phrase of `setting` (';' separated).
+fn phrases(setting: &str) -> Vec<Vec<String>> {
+  setting
+    .split(';')
+    .map(|phrase| {
+      phrase
+        .split_whitespace()
+        .map(normalize)
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+    })
+    .filter(|words| !words.is_empty())
+    .collect()
+}
+
+fn normalize(word: &str) -> String {
+  word
+    .chars()
+    .filter(|c| c.is_alphanumeric())
+    .collect::<String>()
+    .to_lowercase()
+}
@@ -0,0 +1,423 @@
+// ------------------------------------------------------------------
+//  Wyoming protocol server (Home Assistant voice pipeline)
+// ------------------------------------------------------------------
+
+use crate::audio::AudioChunk;
+use crate::cancel::CancellationToken;
+use crate::config::AgentSettings;
+use crate::conversation::ChatMessage;
+use crate::engine::{BuiltinTts, LanguageModel, ServerLlm, SpeechToText, TextToSpeech, WhisperStt};
+use serde_json::{Map, Value, json};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+// sample rate asked to the tts backends that can resample (opentts)
+const TTS_SAMPLE_RATE: u32 = 22050;
+// samples per audio-chunk event sent back
+const CHUNK_SAMPLES: usize = 2048;
+// largest header line, extra data and payload accepted from a peer, the
+// lengths come from the peer and are checked before reading
+const MAX_HEADER_BYTES: u64 = 64 * 1024;
+const MAX_DATA_BYTES: u64 = 1024 * 1024;
+const MAX_PAYLOAD_BYTES: u64 = 4 * 1024 * 1024;
+// audio formats taken from a peer (i16 samples only)
+const MIN_RATE: u64 = 8000;
+const MAX_RATE: u64 = 48000;
+const MAX_CHANNELS: u64 = 2;
+// longest utterance taken from a peer, the audio over it is dropped
+pub const MAX_UTTERANCE_SECS: usize = 120;
+
+// API
+// ------------------------------------------------------------------
+
+/// Serves the agent as a Wyoming speech to text (whisper), text to speech and
+/// intent handling (llm) provider. Blocks, one thread per connection.
+pub fn wyoming_server(
+  addr: &str,
+  agent: &AgentSettings,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+  let listener = TcpListener::bind(addr)?;
+  for stream in listener.incoming() {
+    let Ok(stream) = stream else { continue };
+    let agent = agent.clone();
+    thread::spawn(move || {
+      if let Err(e) = handle_connection(stream, &agent) {
+        crate::log::log("debug", &format!("Wyoming connection error: {}", e));
+      }
+    });
+  }
+  Ok(())
+}
+
+/// An event of the protocol, also the framing of the intercom.
+pub struct Event {
+  pub kind: String,
+  pub data: Map<String, Value>,
+  pub payload: Vec<u8>,
+}
+
+/// Reads one event: a JSON header line, then `data_length` bytes of extra
+/// data (merged into `data`) and `payload_length` bytes of payload. None at
+/// the end of the stream, InvalidData when a part is over its size limit.
+pub fn read_event(reader: &mut impl BufRead) -> std::io::Result<Option<Event>> {
+  let mut line = String::new();
+  if reader
+    .by_ref()
+    .take(MAX_HEADER_BYTES)
+    .read_line(&mut line)?
+    == 0
+  {
+    return Ok(None);
+  }
+  if !line.ends_with('\n') && line.len() as u64 == MAX_HEADER_BYTES {
+    return Err(too_large("header", MAX_HEADER_BYTES));
+  }
+  let header: Value = serde_json::from_str(line.trim())
+    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+  let kind = header
+    .get("type")
+    .and_then(|t| t.as_str())
+    .unwrap_or("")
+    .to_string();
+  let mut data = header
+    .get("data")
+    .and_then(|d| d.as_object())
+    .cloned()
+    .unwrap_or_default();
+  let data_length = header
+    .get("data_length")
+    .and_then(|v| v.as_u64())
+    .unwrap_or(0);
+  if data_length > MAX_DATA_BYTES {
+    return Err(too_large("data", MAX_DATA_BYTES));
+  }
+  if data_length > 0 {
+    let buf = read_bytes(reader, data_length)?;
+    if let Ok(Value::Object(extra)) = serde_json::from_slice::<Value>(&buf) {
+      data.extend(extra);
+    }
+  }
+  let payload_length = header
+    .get("payload_length")
+    .and_then(|v| v.as_u64())
+    .unwrap_or(0);
+  if payload_length > MAX_PAYLOAD_BYTES {
+    return Err(too_large("payload", MAX_PAYLOAD_BYTES));
+  }
+  let payload = read_bytes(reader, payload_length)?;
+  Ok(Some(Event {
+    kind,
+    data,
+    payload,
+  }))
+}
+
+/// Writes one event, its payload if any after the header line.
+pub fn write_event(
+  writer: &mut impl Write,
+  kind: &str,
+  data: Value,
+  payload: &[u8],
+) -> std::io::Result<()> {
+  let mut header = json!({ "type": kind, "data": data });
+  if !payload.is_empty() {
+    header["payload_length"] = json!(payload.len());
+  }
+  writer.write_all(header.to_string().as_bytes())?;
+  writer.write_all(b"\n")?;
+  writer.write_all(payload)?;
+  writer.flush()
+}
+
+/// The audio format (rate, width, channels) of an `audio-start` event,
+/// InvalidData when it isn't one taken from a peer.
+pub fn audio_format(event: &Event) -> std::io::Result<(u32, u16, u16)> {
+  let field = |key: &str, default: u64| {
+    event
+      .data
+      .get(key)
+      .and_then(|v| v.as_u64())
+      .unwrap_or(default)
+  };
+  let (rate, width, channels) = (
+    field("rate", 16000),
+    field("width", 2),
+    field("channels", 1),
+  );
+  if !(MIN_RATE..=MAX_RATE).contains(&rate) || width != 2 || !(1..=MAX_CHANNELS).contains(&channels)
+  {
+    return Err(std::io::Error::new(
+      std::io::ErrorKind::InvalidData,
+      format!(
+        "unsupported audio: {} Hz, {} bytes per sample, {} channels",
+        rate, width, channels
+      ),
+    ));
+  }
+  Ok((rate as u32, width as u16, channels as u16))
+}
+
+/// Bytes of MAX_UTTERANCE_SECS of audio in `format` (see `audio_format`).
+pub fn max_utterance_bytes((rate, width, channels): (u32, u16, u16)) -> usize {
+  MAX_UTTERANCE_SECS * rate as usize * width as usize * channels as usize
+}
+
+// PRIVATE
+// ------------------------------------------------------------------
+
+// exactly `len` bytes, allocated as they arrive
+fn read_bytes(reader: &mut impl Read, len: u64) -> std::io::Result<Vec<u8>> {
+  let mut buf = Vec::new();
+  reader.take(len).read_to_end(&mut buf)?;
+  if (buf.len() as u64) < len {
+    return Err(std::io::ErrorKind::UnexpectedEof.into());
+  }
+  Ok(buf)
+}
+
+fn too_large(part: &str, limit: u64) -> std::io::Error {
+  std::io::Error::new(
+    std::io::ErrorKind::InvalidData,
+    format!("event {} over {} bytes", part, limit),
+  )
+}
+
+// audio being received for a transcription
+struct Recording {
+  language: String,
+  // (rate, width, channels), checked by audio_format
+  format: (u32, u16, u16),
+  pcm: Vec<u8>,
+}
+
+fn handle_connection(stream: TcpStream, agent: &AgentSettings) -> std::io::Result<()> {
+  let mut writer = stream.try_clone()?;
+  let mut reader = BufReader::new(stream);
+  let stt = WhisperStt {
+    model_path: crate::config::resolved_whisper_model_path(&agent.whisper_model_path),
+  };
+  let mut recording: Option<Recording> = None;
+  let mut language = agent.language.clone();
+
+  while let Some(event) = read_event(&mut reader)? {
+    match event.kind.as_str() {
+      "describe" => write_event(&mut writer, "info", info(agent), &[])?,
+      "ping" => write_event(&mut writer, "pong", json!({}), &[])?,
+      "transcribe" => {
+        if let Some(lang) = event.data.get("language").and_then(|l| l.as_str()) {
+          language = lang.to_string();
+        }
+      }
+      // a format or a length it can't take ends the connection, after saying why
+      "audio-start" => {
+        let format = audio_format(&event).inspect_err(|e| {
+          let _ = write_event(&mut writer, "error", json!({ "text": e.to_string() }), &[]);
+        })?;
+        recording = Some(Recording {
+          language: language.clone(),
+          format,
+          pcm: Vec::new(),
+        });
+      }
+      "audio-chunk" => {
+        if let Some(rec) = recording.as_mut() {
+          if rec.pcm.len() + event.payload.len() > max_utterance_bytes(rec.format) {
+            let text = format!("audio over {} s", MAX_UTTERANCE_SECS);
+            write_event(&mut writer, "error", json!({ "text": text }), &[])?;
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, text));
+          }
+          rec.pcm.extend_from_slice(&event.payload);
+        }
+      }
+      "audio-stop" => {
+        if let Some(rec) = recording.take() {
+          let text = transcribe(&stt, &rec).unwrap_or_else(|e| {
+            crate::log::log("error", &format!("Wyoming transcription failed: {}", e));
+            String::new()
+          });
+          write_event(&mut writer, "transcript", json!({ "text": text }), &[])?;
+        }
+      }
+      "synthesize" => {
+        let text = event
+          .data
+          .get("text")
+          .and_then(|t| t.as_str())
+          .unwrap_or("");
+        let voice = event
+          .data
+          .get("voice")
+          .and_then(|v| v.get("name"))
+          .and_then(|n| n.as_str())
+          .filter(|n| crate::tts::get_voices_for(&agent.tts, &agent.language).contains(n))
+          .unwrap_or(&agent.voice);
+        synthesize(&mut writer, agent, text, voice)?;
+      }
+      // intent handling: the agent answers the transcript
+      "transcript" => {
+        let text = event
+          .data
+          .get("text")
+          .and_then(|t| t.as_str())
+          .unwrap_or("");
+        let reply = ask(agent, text).unwrap_or_else(|e| {
+          crate::log::log("error", &format!("Wyoming llm request failed: {}", e));
+          String::new()
+        });
+        write_event(&mut writer, "handled", json!({ "text": reply }), &[])?;
+      }
+      _ => {}
+    }
+  }
+  Ok(())
+}
+
+fn transcribe(
+  stt: &WhisperStt,
+  rec: &Recording,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+  let (sample_rate, _, channels) = rec.format;
+  let data: Arc<[f32]> = rec
+    .pcm
+    .chunks_exact(2)
+    .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / i16::MAX as f32)
+    .collect();
+  let utterance = AudioChunk {
+    data,
+    channels,
+    sample_rate,
+  };
+  Ok(
+    stt
+      .transcribe(&utterance, &rec.language)?
+      .trim()
+      .to_string(),
+  )
+}
+
+fn synthesize(
+  writer: &mut impl Write,
+  agent: &AgentSettings,
+  text: &str,
+  voice: &str,
+) -> std::io::Result<()> {
+  let tts = BuiltinTts {
+    tts: agent.tts.clone(),
+    out_sample_rate: TTS_SAMPLE_RATE,
+    speed: agent.voice_speed,
+  };
+  let (tx, rx) = crossbeam_channel::unbounded::<AudioChunk>();
+  if let Err(e) = tts.speak(
+    &crate::util::strip_special_chars(text),
+    &agent.language,
+    voice,
+    tx,
+    &CancellationToken::new(),
+  ) {
+    crate::log::log("error", &format!("Wyoming synthesis failed: {}", e));
+  }
+  let chunks: Vec<AudioChunk> = rx.try_iter().collect();
+  let (rate, channels) = chunks
+    .first()
+    .map(|c| (c.sample_rate, c.channels))
+    .unwrap_or((TTS_SAMPLE_RATE, 1));
+  let format = json!({ "rate": rate, "width": 2, "channels": channels });
+  write_event(writer, "audio-start", format.clone(), &[])?;
+  let samples: Vec<i16> = chunks
+    .iter()
+    .flat_map(|c| crate::audio::f32_to_i16(&c.data))
+    .collect();
+  for part in samples.chunks(CHUNK_SAMPLES * channels as usize) {
+    let bytes: Vec<u8> = part.iter().flat_map(|s| s.to_le_bytes()).collect();
+    write_event(writer, "audio-chunk", format.clone(), &bytes)?;
+  }
+  write_event(writer, "audio-stop", json!({}), &[])
+}
+
+fn ask(
+  agent: &AgentSettings,
+  text: &str,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+  let llm = ServerLlm {
+    provider: agent.provider.clone(),
+    baseurl: agent.baseurl.clone(),
+    model: agent.model.clone(),
+  };
+  let messages = [
+    ChatMessage {
+      role: "system".to_string(),
+      content: agent.system_prompt.replace("\\n", "\n"),
+      agent_name: None,
+      meta: None,
+    },
+    ChatMessage {
+      role: "user".to_string(),
+      content: text.to_string(),
+      agent_name: None,
+      meta: None,
+    },
+  ];
+  let mut reply = String::new();
+  llm.stream_reply(&messages, &CancellationToken::new(), &mut |piece| {
+    reply.push_str(piece)
+  })?;
+  Ok(reply.trim().to_string())
+}
+
+fn info(agent: &AgentSettings) -> Value {
+  let version = env!("CARGO_PKG_VERSION");
+  let attribution = json!({ "name": "vtmate", "url": "https://github.com/DavidValin/vtmate" });
+  let languages = [agent.language.clone()];
+  let voices: Vec<Value> = crate::tts::get_voices_for(&agent.tts, &agent.language)
+    .iter()
+    .map(|v| {
+      json!({
+        "name": v,
+        "description": format!("{} {}", agent.tts, v),
+        "attribution": attribution,
+        "installed": true,
+        "languages": languages,
+        "version": version,
+      })
+    })
+    .collect();
+  let model = std::path::Path::new(&agent.whisper_model_path)
+    .file_name()
+    .map(|n| n.to_string_lossy().to_string())
+    .unwrap_or_default();
+  json!({
+    "asr": [{
+      "name": "vtmate-whisper",
+      "description": "whisper speech to text",
+      "attribution": attribution,
+      "installed": true,
+      "version": version,
+      "models": [{
+        "name": model,
+        "description": model,
+        "attribution": attribution,
+        "installed": true,
+        "languages": crate::tts::get_all_available_languages(),
+        "version": version,
+      }],
+    }],
+    "tts": [{
+      "name": format!("vtmate-{}", agent.tts),
+      "description": format!("{} text to speech", agent.tts),
+      "attribution": attribution,
+      "installed": true,
+      "version": version,
+      "voices": voices,
+    }],
+    "handle": [{
+      "name": format!("vtmate-{}", agent.name),
+      "description": format!("{} ({})", agent.name, agent.model),
+      "attribution": attribution,
+      "installed": true,
+      "version": version,
+      "languages": languages,
+    }],
+  })
+}
@@ -1,3 +1,4 @@
+use clap::Parser;
 use std::env::temp_dir;
 use std::fs::File;
 use std::io::Write;
@@ -23,16 +24,26 @@ mod util {
   pub fn get_user_home_path() -> Option<PathBuf> {
     Some(PathBuf::from("/tmp"))
   }
+  pub fn get_data_dir() -> Option<PathBuf> {
+    Some(PathBuf::from("/tmp/vtmate"))
+  }
+  pub fn terminate(code: i32) -> ! {
+    std::process::exit(code)
+  }
 }
 
 mod log {
   pub fn log(_level: &str, _msg: &str) {}
 }
 
+mod ui {
+  pub use vtmate::ui::label_color;
+}
+
 #[path = "../src/config.rs"]
 mod config;
 
-use config::{AgentSettings, Args, load_settings};
+use config::{Args, load_personas, load_settings, load_snippets};
 
 #[test]
 fn test_load_settings_with_double_quotes() {
@@ -61,6 +72,10 @@ end_silence_ms = "2000"
 ptt = "false"
 whisper_model_path = "~/.whisper-models/ggml-tiny.bin"
 voice_speed = 5.0
+shell_commands = "disk space: df -h; uptime"
+barge_in_ack = "okay"
+wake_phrases = "computer; hey mate"
+stop_phrases = "that's all"
 "#;
 
   let mut file = File::create(&path).expect("Failed to create temp config file");
@@ -76,11 +91,86 @@ voice_speed = 5.0
     verbose: false,
     agent: Some("main agent".to_string()),
     list_voices: false,
+    mic_test: false,
     ptt: Some(true),
     debate: None,
     read_file: None,
     quiet: false,
     save: false,
+    punctuate: false,
+    data_dir: None,
+    asset_mirror: vec![],
+    third_party_mirrors: false,
+    offline: false,
+    auto_download: false,
+    audio_buffer_frames: 1024,
+    secondary_output: None,
+    input_channel: None,
+    no_tts: false,
+    no_auto_pace: false,
+    voice_styles: false,
+    double_check_numbers: None,
+    max_spoken_seconds: None,
+    tts_threads: 0,
+    tts_ep: "cpu".to_string(),
+    utterance_queue: 4,
+    playback_queue: 2,
+    interrupt_granularity: "instant".to_string(),
+    barge_in_interrupts: false,
+    history_turns: 200,
+    history_tokens: 32000,
+    captions: None,
+    mirror: None,
+    notify: false,
+    media_keys: false,
+    duck: None,
+    mic_gain: 1.0,
+    earcons: false,
+    user_label: "USER".to_string(),
+    assistant_label: "ASSISTANT".to_string(),
+    user_color: "47;30".to_string(),
+    assistant_color: "48;5;22;37".to_string(),
+    timestamps: false,
+    follow_up_window: None,
+    status_led: None,
+    status_display: None,
+    low_power: false,
+    low_power_idle: 120,
+    rapid_silence: 700,
+    rapid_idle: 120,
+    fast_stt: false,
+    latency_target_ms: None,
+    dictate: false,
+    dictate_output: None,
+    dictate_type: false,
+    meeting: false,
+    meeting_summary: 10,
+    quiet_hours: None,
+    speaker_verify: None,
+    speaker_threshold: None,
+    redact: false,
+    redact_pattern: vec![],
+    encrypt: false,
+    encrypt_key_file: None,
+    telemetry: false,
+    otlp_endpoint: None,
+    serve: None,
+    serve_origin: Vec::new(),
+    wyoming: None,
+    intercom: None,
+    satellite: None,
+    intercom_secret: None,
+    control_socket: None,
+    stdio_protocol: false,
+    daemon: false,
+    headless: false,
+    service: false,
+    bridge: vec![],
+    bridge_user: vec![],
+    context: vec![],
+    bridge_voice: false,
+    plugins_dir: None,
+    command: None,
   };
 
   let agents = load_settings(&path, &args).expect("Failed to load settings");
@@ -102,6 +192,10 @@ voice_speed = 5.0
   assert_eq!(agent.end_silence_ms, 2000);
   assert_eq!(agent.voice_speed, 5.0);
   assert_eq!(agent.whisper_model_path, "~/.whisper-models/ggml-tiny.bin");
+  assert_eq!(agent.shell_commands, "disk space: df -h; uptime");
+  assert_eq!(agent.barge_in_ack, "okay");
+  assert_eq!(agent.wake_phrases, "computer; hey mate");
+  assert_eq!(agent.stop_phrases, "that's all");
 }
 
 #[test]
@@ -146,11 +240,86 @@ voice_speed = 5.0
     verbose: false,
     agent: Some("Test Agent".to_string()),
     list_voices: false,
+    mic_test: false,
     ptt: None,
     debate: None,
     read_file: None,
     quiet: false,
     save: false,
+    punctuate: false,
+    data_dir: None,
+    asset_mirror: vec![],
+    third_party_mirrors: false,
+    offline: false,
+    auto_download: false,
+    audio_buffer_frames: 1024,
+    secondary_output: None,
+    input_channel: None,
+    no_tts: false,
+    no_auto_pace: false,
+    voice_styles: false,
+    double_check_numbers: None,
+    max_spoken_seconds: None,
+    tts_threads: 0,
+    tts_ep: "cpu".to_string(),
+    utterance_queue: 4,
+    playback_queue: 2,
+    interrupt_granularity: "instant".to_string(),
+    barge_in_interrupts: false,
+    history_turns: 200,
+    history_tokens: 32000,
+    captions: None,
+    mirror: None,
+    notify: false,
+    media_keys: false,
+    duck: None,
+    mic_gain: 1.0,
+    earcons: false,
+    user_label: "USER".to_string(),
+    assistant_label: "ASSISTANT".to_string(),
+    user_color: "47;30".to_string(),
+    assistant_color: "48;5;22;37".to_string(),
+    timestamps: false,
+    follow_up_window: None,
+    status_led: None,
+    status_display: None,
+    low_power: false,
+    low_power_idle: 120,
+    rapid_silence: 700,
+    rapid_idle: 120,
+    fast_stt: false,
+    latency_target_ms: None,
+    dictate: false,
+    dictate_output: None,
+    dictate_type: false,
+    meeting: false,
+    meeting_summary: 10,
+    quiet_hours: None,
+    speaker_verify: None,
+    speaker_threshold: None,
+    redact: false,
+    redact_pattern: vec![],
+    encrypt: false,
+    encrypt_key_file: None,
+    telemetry: false,
+    otlp_endpoint: None,
+    serve: None,
+    serve_origin: Vec::new(),
+    wyoming: None,
+    intercom: None,
+    satellite: None,
+    intercom_secret: None,
+    control_socket: None,
+    stdio_protocol: false,
+    daemon: false,
+    headless: false,
+    service: false,
+    bridge: vec![],
+    bridge_user: vec![],
+    context: vec![],
+    bridge_voice: false,
+    plugins_dir: None,
+    command: None,
   };
 
   let agents = load_settings(&path, &args).expect("Failed to load settings");
@@ -172,4 +341,192 @@ voice_speed = 5.0
   assert_eq!(agent.end_silence_ms, 2000);
   assert_eq!(agent.voice_speed, 5.0);
   assert_eq!(agent.whisper_model_path, "~/.whisper-models/ggml-tiny.bin");
+  assert_eq!(agent.shell_commands, "");
+  assert_eq!(agent.barge_in_ack, "");
+  assert_eq!(agent.wake_phrases, "");
+  assert_eq!(agent.stop_phrases, "");
+}
+
+#[test]
+fn test_load_personas_apart_from_the_agents() {
+  let mut path = temp_dir();
+  path.push(format!(
+    "ai_mate_test_config_{}.ini",
+    SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .unwrap()
+      .as_nanos()
+  ));
+
+  let contents = r#"
+[agent]
+name = main agent
+language = en
+tts = kokoro
+voice = bf_alice
+provider = ollama
+baseurl = http://127.0.0.1:11434
+model = llama3.2:3b
+system_prompt = You are a helpful assistant.
+sound_threshold_peak = 0.1
+end_silence_ms = 2000
+ptt = true
+whisper_model_path = ~/.whisper-models/ggml-tiny.bin
+voice_speed = 1.0
+
+[persona]
+name = chef
+system_prompt = "You are a french chef."
+language = en
+tts = kokoro
+voice = bf_alice
+voice_speed = 1.2
+"#;
+
+  let mut file = File::create(&path).expect("Failed to create temp config file");
+  file
+    .write_all(contents.as_bytes())
+    .expect("Failed to write to temp config file");
+
+  let personas = load_personas(&path).expect("Failed to load personas");
+  assert_eq!(personas.len(), 1);
+  let persona = &personas[0];
+  assert_eq!(persona.name, "chef");
+  assert_eq!(persona.system_prompt, "You are a french chef.");
+  assert_eq!(persona.voice, "bf_alice");
+  assert_eq!(persona.voice_speed, 1.2);
+
+  // the agent keeps its own prompt and voice, the persona only goes over it
+  let agents =
+    load_settings(&path, &Args::parse_from(["vtmate"])).expect("Failed to load settings");
+  assert_eq!(agents.len(), 1);
+  assert_eq!(agents[0].system_prompt, "You are a helpful assistant.");
+  let as_chef = persona.over(&agents[0]);
+  assert_eq!(as_chef.system_prompt, "You are a french chef.");
+  assert_eq!(as_chef.model, "llama3.2:3b");
+}
+
+#[test]
+fn test_load_snippets() {
+  let mut path = temp_dir();
+  path.push(format!(
+    "ai_mate_test_config_{}.ini",
+    SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .unwrap()
+      .as_nanos()
+  ));
+
+  let contents = r#"
+[agent]
+name = main agent
+language = en
+tts = kokoro
+voice = bf_alice
+provider = ollama
+baseurl = http://127.0.0.1:11434
+model = llama3.2:3b
+system_prompt = You are a helpful assistant.
+sound_threshold_peak = 0.1
+end_silence_ms = 2000
+ptt = true
+whisper_model_path = ~/.whisper-models/ggml-tiny.bin
+voice_speed = 1.0
+
+[snippet]
+trigger = "run my standup prompt; standup"
+prompt = "Summarize my day as three bullet lists."
+"#;
+
+  let mut file = File::create(&path).expect("Failed to create temp config file");
+  file
+    .write_all(contents.as_bytes())
+    .expect("Failed to write to temp config file");
+
+  let snippets = load_snippets(&path).expect("Failed to load snippets");
+  assert_eq!(snippets.len(), 1);
+  assert_eq!(snippets[0].trigger, "run my standup prompt; standup");
+  assert_eq!(
+    snippets[0].prompt,
+    "Summarize my day as three bullet lists."
+  );
+}
+
+#[test]
+fn test_latency_target_trades_quality_for_speed() {
+  let mut path = temp_dir();
+  path.push(format!(
+    "ai_mate_test_config_{}.ini",
+    SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .unwrap()
+      .as_nanos()
+  ));
+
+  let contents = r#"
+[agent]
+name = main agent
+language = en
+tts = kokoro
+voice = bf_alice
+provider = ollama
+baseurl = http://127.0.0.1:11434
+model = llama3.2:3b
+system_prompt = You are a helpful assistant.
+sound_threshold_peak = 0.1
+end_silence_ms = 2000
+ptt = false
+whisper_model_path = ggml-small.bin
+voice_speed = 1.0
+"#;
+
+  let mut file = File::create(&path).expect("Failed to create temp config file");
+  file
+    .write_all(contents.as_bytes())
+    .expect("Failed to write to temp config file");
+
+  let args = Args::parse_from(["vtmate", "--latency-target-ms", "1500"]);
+  let agents = load_settings(&path, &args).expect("Failed to load settings");
+  assert_eq!(agents[0].end_silence_ms, 600);
+  assert_eq!(agents[0].whisper_model_path, "ggml-tiny.bin");
+
+  // a target loose enough keeps the settings
+  let args = Args::parse_from(["vtmate", "--latency-target-ms", "6000"]);
+  let agents = load_settings(&path, &args).expect("Failed to load settings");
+  assert_eq!(agents[0].end_silence_ms, 2000);
+  assert_eq!(agents[0].whisper_model_path, "ggml-small.bin");
+}
+
+#[test]
+fn test_labels_are_configurable_and_kept_out_of_the_prompts() {
+  let args = Args::parse_from([
+    "vtmate",
+    "--user-label",
+    "ME",
+    "--user-color",
+    "blue/white",
+    "--assistant-color",
+    "130",
+  ]);
+  assert_eq!(args.user_label, "ME");
+  assert_eq!(args.assistant_label, "ASSISTANT");
+  assert_eq!(args.user_color, "44;37");
+  assert_eq!(args.assistant_color, "48;5;130");
+  assert!(Args::try_parse_from(["vtmate", "--user-color", "mauve"]).is_err());
+
+  let mut history = vtmate::history::History::new(10, 10_000);
+  history.push(vtmate::conversation::ChatMessage {
+    role: "assistant".to_string(),
+    content: "\x1b[48;5;130mASSISTANT:\x1b[0m Hi \x1b[1mthere\x1b[0m".to_string(),
+    agent_name: None,
+    meta: None,
+  });
+  assert_eq!(history.last().unwrap().content, "ASSISTANT: Hi there");
+}
+
+#[test]
+fn test_bridge_needs_the_users_it_answers() {
+  assert!(Args::try_parse_from(["vtmate", "--bridge", "telegram:42"]).is_err());
+  let args = Args::parse_from(["vtmate", "--bridge", "telegram:42", "--bridge-user", "7,8"]);
+  assert_eq!(args.bridge_user, ["7", "8"]);
 }
@@ -0,0 +1,925 @@
+use std::sync::atomic::Ordering;
+use vtmate::cancel::Interrupter;
+use vtmate::record::Segmenter;
+use vtmate::testing::{AudioSink, MockLlm, MockStt, MockTts, SAMPLE_RATE, Signal, agent_settings};
+use vtmate::{Engine, TurnEvent};
+
+// the voice threshold and end silence of agent_settings()
+const VAD_THRESH: f32 = 0.1;
+const END_SILENCE_MS: u64 = 1000;
+
+fn engine(stt: MockStt, llm: MockLlm, tts: MockTts) -> Engine {
+  Engine::with_backends(
+    agent_settings(),
+    Box::new(stt),
+    Box::new(llm),
+    Box::new(tts),
+  )
+}
+
+fn durations_ms(utterances: &[vtmate::audio::AudioChunk]) -> Vec<u64> {
+  utterances
+    .iter()
+    .map(|u| vtmate::testing::duration_ms(std::slice::from_ref(u)))
+    .collect()
+}
+
+// --- Phrase sequencing -----------------------------------------
+
+#[test]
+fn test_reply_is_spoken_phrase_by_phrase_in_order() {
+  let llm = MockLlm::new(&[&["Hello", " there.", " The answer", " is", " 42.", " Bye"]]);
+  let tts = MockTts::new();
+  let spoken = tts.spoken();
+  let mut engine = engine(MockStt::new(Vec::<String>::new()), llm, tts);
+  let sink = AudioSink::new();
+
+  let mut events = Vec::new();
+  let reply = engine
+    .ask("question", sink.sender(), |e| events.push(e))
+    .unwrap();
+
+  assert_eq!(reply, "Hello there. The answer is 42. Bye");
+  let phrases: Vec<&str> = events
+    .iter()
+    .filter_map(|e| match e {
+      TurnEvent::Phrase(p) => Some(p.as_str()),
+      _ => None,
+    })
+    .collect();
+  assert_eq!(phrases, ["Hello there.", "The answer is 42.", "Bye"]);
+  assert_eq!(
+    *spoken.lock().unwrap(),
+    ["Hello there", "The answer is 42", "Bye"]
+  );
+  // 10ms of audio per character spoken
+  assert_eq!(vtmate::testing::duration_ms(&sink.drain()), 300);
+  assert_eq!(events.last(), Some(&TurnEvent::Done(reply)));
+}
+
+#[test]
+fn test_phrase_is_announced_after_its_tokens() {
+  let llm = MockLlm::new(&[&["One", " two."]]);
+  let mut engine = engine(MockStt::new(Vec::<String>::new()), llm, MockTts::new());
+
+  let mut events = Vec::new();
+  engine
+    .ask("count", AudioSink::new().sender(), |e| events.push(e))
+    .unwrap();
+
+  assert_eq!(
+    events,
+    [
+      TurnEvent::Token("One".to_string()),
+      TurnEvent::Token(" two.".to_string()),
+      TurnEvent::Phrase("One two.".to_string()),
+      TurnEvent::Done("One two.".to_string()),
+    ]
+  );
+}
+
+#[test]
+fn test_critical_numbers_are_said_again() {
+  use vtmate::numbers::{Mode, check_phrase};
+
+  let reply = "Your 2 tickets cost $1,250.50 and the flight leaves on March 5, 2024.";
+  assert_eq!(
+    check_phrase(reply, Mode::Repeat).as_deref(),
+    Some("To be sure, 1,250.50 dollars and March 5, 2024")
+  );
+  assert_eq!(
+    check_phrase("It's 45% off.", Mode::Spell).as_deref(),
+    Some("To be sure, 4, 5 percent")
+  );
+  assert_eq!(check_phrase("Take 2 of them.", Mode::Repeat), None);
+}
+
+#[test]
+fn test_long_replies_stop_to_ask_to_go_on() {
+  use vtmate::continuation::{Answer, Budget, answer, init};
+
+  init(Some(5));
+  let phrases = [
+    "This first phrase takes about four seconds to say out loud.",
+    "The second one goes over the limit of five seconds.",
+    "The third waits.",
+    "So does the fourth.",
+  ];
+  let mut budget = Budget::new();
+  let spoken: Vec<bool> = phrases.iter().map(|p| budget.admit(p)).collect();
+  assert_eq!(spoken, [true, true, false, false]);
+  assert_eq!(budget.pause(), Some("Shall I go on?"));
+
+  assert_eq!(answer("What about tomorrow?"), Answer::Other);
+  assert_eq!(answer("Go on"), Answer::Other, "the reply was dropped");
+
+  let mut budget = Budget::new();
+  phrases.iter().for_each(|p| {
+    budget.admit(p);
+  });
+  budget.pause();
+  assert_eq!(
+    answer("Yes, please go on!"),
+    Answer::Go(vec![phrases[2].to_string(), phrases[3].to_string()])
+  );
+  init(None);
+}
+
+#[test]
+fn test_style_tags_are_spoken_and_not_shown() {
+  use vtmate::styles::{Stripper, runs, set_enabled, strip};
+  set_enabled(true);
+
+  let phrase = "Sure. [whisper] It's a secret. [unknown] stays";
+  let runs: Vec<(String, f32)> = runs(phrase)
+    .into_iter()
+    .map(|(text, style)| (text, style.gain))
+    .collect();
+  assert_eq!(
+    runs,
+    [
+      ("Sure.".to_string(), 1.0),
+      ("It's a secret. [unknown] stays".to_string(), 0.5)
+    ]
+  );
+  assert_eq!(strip(phrase), "Sure. It's a secret. [unknown] stays");
+
+  // a tag split between the streamed pieces
+  let mut stripper = Stripper::default();
+  let shown: String = ["Well [exc", "ited] wow", " [1] done", " [a"]
+    .iter()
+    .map(|piece| stripper.push(piece))
+    .collect();
+  assert_eq!(shown + &stripper.flush(), "Well wow [1] done [a");
+  set_enabled(false);
+}
+
+// --- Interruption ----------------------------------------------
+
+#[test]
+fn test_interruption_stops_the_reply() {
+  let llm = MockLlm::new(&[&["One", " two.", " Three", " four."]]).interrupt_at(2);
+  let tts = MockTts::new();
+  let spoken = tts.spoken();
+  let mut engine = engine(MockStt::new(Vec::<String>::new()), llm, tts);
+
+  let mut events = Vec::new();
+  let reply = engine
+    .ask("count", AudioSink::new().sender(), |e| events.push(e))
+    .unwrap();
+
+  assert_eq!(reply, "One two.");
+  assert_eq!(*spoken.lock().unwrap(), ["One two"]);
+  assert_eq!(events.last(), Some(&TurnEvent::Interrupted));
+  assert!(!events.iter().any(|e| matches!(e, TurnEvent::Done(_))));
+  // what was said before the interruption stays in the conversation
+  assert_eq!(engine.history().len(), 2);
+  assert_eq!(engine.history()[1].content, "One two.");
+}
+
+#[test]
+fn test_turn_after_an_interruption_completes() {
+  let llm = MockLlm::new(&[&["Stop", " here.", " Never"], &["Next", " one."]]).interrupt_at(2);
+  let mut engine = engine(MockStt::new(Vec::<String>::new()), llm, MockTts::new());
+  let sink = AudioSink::new();
+
+  engine.ask("first", sink.sender(), |_| {}).unwrap();
+  let mut events = Vec::new();
+  let reply = engine
+    .ask("second", sink.sender(), |e| events.push(e))
+    .unwrap();
+
+  assert_eq!(reply, "Next one.");
+  assert_eq!(events.last(), Some(&TurnEvent::Done(reply)));
+}
+
+#[test]
+fn test_interruption_reaches_the_turns_started_before_it_only() {
+  let interrupter = Interrupter::new();
+  let before = interrupter.token().child();
+  let cancelled_alone = interrupter.token().child();
+  cancelled_alone.cancel();
+  assert!(!before.is_cancelled());
+
+  interrupter.interrupt();
+  let after = interrupter.token().child();
+
+  assert!(before.is_cancelled());
+  assert!(!after.is_cancelled());
+  assert_eq!(interrupter.count(), 1);
+}
+
+#[test]
+fn test_commands_over_the_reply_are_told_apart_from_questions() {
+  use vtmate::interjection::{Control, Interjection, classify};
+
+  assert_eq!(
+    classify("Louder, please."),
+    Interjection::Command(Control::Louder)
+  );
+  assert_eq!(
+    classify("slow down"),
+    Interjection::Command(Control::Slower)
+  );
+  assert_eq!(classify("OK"), Interjection::Command(Control::Continue));
+  assert_eq!(classify("Stop!"), Interjection::Stop);
+  assert_eq!(
+    classify("stop, what about tomorrow?"),
+    Interjection::Question
+  );
+}
+
+// --- Transcription and history ---------------------------------
+
+#[test]
+fn test_listen_transcribes_then_answers() {
+  let llm = MockLlm::new(&[&["Noon."]]);
+  let requests = llm.requests();
+  let mut engine = engine(MockStt::new(["what time is it"]), llm, MockTts::new());
+  let utterance = Signal::new().speech(500).chunk();
+
+  let mut events = Vec::new();
+  let reply = engine
+    .listen(&utterance, AudioSink::new().sender(), |e| events.push(e))
+    .unwrap();
+
+  assert_eq!(reply, "Noon.");
+  assert_eq!(
+    events.first(),
+    Some(&TurnEvent::Transcript("what time is it".to_string()))
+  );
+  let requests = requests.lock().unwrap();
+  let messages = &requests[0];
+  assert_eq!(messages[0].role, "system");
+  assert_eq!(messages.last().unwrap().content, "what time is it");
+}
+
+#[test]
+fn test_nothing_said_is_not_answered() {
+  let llm = MockLlm::new(&[&["Unused."]]);
+  let requests = llm.requests();
+  let mut engine = engine(MockStt::new([""]), llm, MockTts::new());
+  let utterance = Signal::new().silence(500).chunk();
+
+  let mut events = Vec::new();
+  let reply = engine
+    .listen(&utterance, AudioSink::new().sender(), |e| events.push(e))
+    .unwrap();
+
+  assert_eq!(reply, "");
+  assert!(events.is_empty());
+  assert!(requests.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_history_is_sent_with_the_next_request() {
+  let llm = MockLlm::new(&[&["Paris."], &["About 2 million."]]);
+  let requests = llm.requests();
+  let mut engine = engine(MockStt::new(Vec::<String>::new()), llm, MockTts::new());
+  let sink = AudioSink::new();
+
+  engine
+    .ask("capital of France?", sink.sender(), |_| {})
+    .unwrap();
+  engine.ask("population?", sink.sender(), |_| {}).unwrap();
+
+  let requests = requests.lock().unwrap();
+  let roles: Vec<&str> = requests[1].iter().map(|m| m.role.as_str()).collect();
+  assert_eq!(roles, ["system", "user", "assistant", "user"]);
+  assert_eq!(requests[1][2].content, "Paris.");
+}
+
+#[test]
+fn test_bookmarks_follow_the_turns_they_mark() {
+  use vtmate::conversation::ChatMessage;
+  use vtmate::history::History;
+
+  let message = |role: &str, content: &str| ChatMessage {
+    role: role.to_string(),
+    content: content.to_string(),
+    agent_name: None,
+    meta: None,
+  };
+  let mut history = History::new(2, 10_000);
+  assert_eq!(history.toggle_bookmark(), None);
+  history.push(message("user", "capital of France?"));
+  history.push(message("assistant", "Paris."));
+  assert_eq!(history.toggle_bookmark(), Some(true));
+  history.push(message("user", "population?"));
+  history.push(message("assistant", "About 2 million."));
+  assert_eq!(history.toggle_bookmark(), Some(true));
+  assert_eq!(history.toggle_bookmark(), Some(false));
+  history.push(message("user", "and Rome?"));
+
+  // the first turn is dropped with its bookmark, the position stays right
+  assert_eq!(history.dropped(), 2);
+  assert!(history.bookmarked().is_empty());
+  history.push(message("assistant", "Rome is the capital of Italy."));
+  history.toggle_bookmark();
+  assert!(history.is_bookmarked(3));
+  let bookmarked: Vec<&str> = history
+    .bookmarked()
+    .iter()
+    .map(|m| m.content.as_str())
+    .collect();
+  assert_eq!(bookmarked, ["Rome is the capital of Italy."]);
+
+  // undone with its turn
+  history.pop();
+  assert!(history.bookmarked().is_empty());
+}
+
+#[test]
+fn test_dropped_turns_stay_in_the_transcript() {
+  use vtmate::conversation::ChatMessage;
+  use vtmate::history::History;
+
+  let message = |role: &str, content: &str| ChatMessage {
+    role: role.to_string(),
+    content: content.to_string(),
+    agent_name: None,
+    meta: None,
+  };
+  let mut history = History::new(1, 10_000);
+  history.push(message("user", "capital of France?"));
+  history.push(message("assistant", "Paris."));
+  history.toggle_bookmark();
+  history.push(message("user", "population?"));
+  history.push(message("assistant", "About 2 million."));
+
+  // only the last turn is sent, the saved conversation has both
+  let sent: Vec<&str> = history.iter().map(|m| m.content.as_str()).collect();
+  assert_eq!(sent, ["population?", "About 2 million."]);
+  let transcript: Vec<(&str, bool)> = history
+    .transcript()
+    .map(|(m, bookmarked)| (m.content.as_str(), bookmarked))
+    .collect();
+  assert_eq!(
+    transcript,
+    [
+      ("capital of France?", false),
+      ("Paris.", true),
+      ("population?", false),
+      ("About 2 million.", false),
+    ]
+  );
+}
+
+// --- Voice detection -------------------------------------------
+
+#[test]
+fn test_utterances_end_after_the_end_silence() {
+  let mut segmenter = Segmenter::new(SAMPLE_RATE, VAD_THRESH, END_SILENCE_MS);
+  let input = Signal::new()
+    .speech(500)
+    .silence(1200)
+    .speech(700)
+    .silence(1200);
+
+  let utterances = segmenter.feed(input.samples());
+
+  // the speech and a little of the silence that ended it
+  assert_eq!(durations_ms(&utterances), [750, 950]);
+}
+
+#[test]
+fn test_pause_shorter_than_the_end_silence_keeps_the_utterance() {
+  let mut segmenter = Segmenter::new(SAMPLE_RATE, VAD_THRESH, END_SILENCE_MS);
+
+  let first = segmenter.feed(Signal::new().speech(500).silence(500).speech(300).samples());
+  let second = segmenter.feed(Signal::new().silence(1000).samples());
+
+  assert!(first.is_empty());
+  assert_eq!(durations_ms(&second), [1550]);
+}
+
+#[test]
+fn test_quieter_speech_under_the_threshold_keeps_the_utterance() {
+  let mut segmenter = Segmenter::new(SAMPLE_RATE, VAD_THRESH, END_SILENCE_MS);
+  // under the threshold, over the level the voice gate closes at
+  let quieter = VAD_THRESH * 0.7;
+  let input = Signal::new()
+    .speech(500)
+    .tone(1200, quieter)
+    .speech(300)
+    .silence(1200);
+
+  let utterances = segmenter.feed(input.samples());
+
+  assert_eq!(durations_ms(&utterances), [2250]);
+}
+
+#[test]
+fn test_quieter_sound_alone_is_no_utterance() {
+  let mut segmenter = Segmenter::new(SAMPLE_RATE, VAD_THRESH, END_SILENCE_MS);
+
+  assert!(
+    segmenter
+      .feed(
+        Signal::new()
+          .tone(2000, VAD_THRESH * 0.7)
+          .silence(1200)
+          .samples()
+      )
+      .is_empty()
+  );
+}
+
+#[test]
+fn test_silence_alone_is_no_utterance() {
+  let mut segmenter = Segmenter::new(SAMPLE_RATE, VAD_THRESH, END_SILENCE_MS);
+
+  assert!(
+    segmenter
+      .feed(Signal::new().silence(3000).samples())
+      .is_empty()
+  );
+}
+
+#[test]
+fn test_speech_over_the_playback_interrupts_it_once() {
+  let mut segmenter = Segmenter::new(SAMPLE_RATE, VAD_THRESH, END_SILENCE_MS);
+  let playback_active = segmenter.playback_active();
+  let interrupts = segmenter.interrupter();
+  playback_active.store(true, Ordering::Relaxed);
+
+  segmenter.feed(Signal::new().speech(100).samples());
+  assert_eq!(interrupts.count(), 0);
+  segmenter.feed(Signal::new().speech(300).samples());
+  assert_eq!(interrupts.count(), 1);
+  assert!(!playback_active.load(Ordering::Relaxed));
+
+  // the same utterance going on doesn't interrupt the next reply
+  playback_active.store(true, Ordering::Relaxed);
+  segmenter.feed(Signal::new().speech(500).samples());
+  assert_eq!(interrupts.count(), 1);
+}
+
+#[test]
+fn test_short_noises_over_the_playback_dont_interrupt_it() {
+  let mut segmenter = Segmenter::new(SAMPLE_RATE, VAD_THRESH, END_SILENCE_MS);
+  let playback_active = segmenter.playback_active();
+  let interrupts = segmenter.interrupter();
+  playback_active.store(true, Ordering::Relaxed);
+
+  segmenter.feed(
+    Signal::new()
+      .speech(100)
+      .silence(200)
+      .speech(100)
+      .silence(200)
+      .samples(),
+  );
+
+  assert_eq!(interrupts.count(), 0);
+  assert!(playback_active.load(Ordering::Relaxed));
+}
+
+#[test]
+fn test_long_utterances_are_cut_in_their_quietest_stretch() {
+  let input = Signal::new()
+    .speech(12000)
+    .silence(500)
+    .speech(12000)
+    .silence(300)
+    .speech(12000);
+  let ms = |samples: usize| samples as u64 * 1000 / SAMPLE_RATE as u64;
+
+  let segments = vtmate::stt::segments(input.samples(), SAMPLE_RATE);
+
+  assert_eq!(segments.len(), 2);
+  assert!((12000..=12500).contains(&ms(segments[0].end)));
+  assert_eq!(segments[1].start, segments[0].end);
+  assert_eq!(segments[1].end, input.samples().len());
+  // a short one is transcribed whole
+  let short = Signal::new().speech(20000);
+  assert_eq!(vtmate::stt::segments(short.samples(), SAMPLE_RATE).len(), 1);
+}
+
+#[test]
+fn test_long_utterance_transcript_joins_its_segments() {
+  let input = Signal::new()
+    .speech(12000)
+    .silence(500)
+    .speech(12000)
+    .silence(500)
+    .speech(12000)
+    .silence(500)
+    .speech(12000);
+  let mut texts = vec![
+    ("hello there", Some(0.9)),
+    ("", None),
+    ("how are you", Some(0.5)),
+  ]
+  .into_iter();
+  let mut calls = 0;
+  let mut partials = Vec::new();
+
+  let (text, confidence) = vtmate::stt::transcribe_segments(
+    input.samples(),
+    SAMPLE_RATE,
+    |_| {
+      calls += 1;
+      let (text, confidence) = texts.next().unwrap_or(("how are you", Some(0.5)));
+      Ok((text.to_string(), confidence))
+    },
+    |part| partials.push(part.to_string()),
+  )
+  .unwrap();
+
+  // a segment with nothing said adds neither text nor confidence
+  assert_eq!(calls, 3);
+  assert_eq!(text, "hello there how are you");
+  assert!((confidence.unwrap() - 0.7).abs() < 1e-6);
+  assert_eq!(partials, ["hello there", "how are you"]);
+}
+
+// --- Snippets ------------------------------------------------
+
+#[test]
+fn test_snippet_trigger_expands_into_its_prompt() {
+  vtmate::snippets::init(vec![vtmate::config::Snippet {
+    trigger: "run my standup prompt; standup".to_string(),
+    prompt: "List what I did yesterday.\\nThen what I plan today.".to_string(),
+  }]);
+
+  assert_eq!(
+    vtmate::snippets::expand("Run my standup prompt.").as_deref(),
+    Some("List what I did yesterday.\nThen what I plan today.")
+  );
+  // what is said after the trigger goes with the prompt
+  assert_eq!(
+    vtmate::snippets::expand("Standup, mention the release").as_deref(),
+    Some("List what I did yesterday.\nThen what I plan today.\n\nmention the release")
+  );
+  assert_eq!(vtmate::snippets::expand("standups are boring"), None);
+}
+
+// --- Microphone test -----------------------------------------
+
+#[test]
+fn test_mic_test_recommends_a_threshold_between_the_room_and_the_voice() {
+  let room = Signal::new().tone(2000, 0.01);
+  let voice = Signal::new().silence(1000).speech(3000);
+
+  let threshold =
+    vtmate::mictest::recommend_threshold(room.samples(), voice.samples(), SAMPLE_RATE).unwrap();
+
+  // over the noise with room for the voice gate to close, under the voice
+  assert!((0.025..=0.25).contains(&threshold), "{}", threshold);
+}
+
+#[test]
+fn test_mic_test_tells_when_the_voice_is_lost_in_the_noise() {
+  let room = Signal::new().tone(2000, 0.3);
+  let voice = Signal::new().speech(3000);
+
+  let error = vtmate::mictest::recommend_threshold(room.samples(), voice.samples(), SAMPLE_RATE)
+    .expect_err("the voice is hardly louder than the room");
+  assert!(error.contains("hardly louder"));
+}
+
+// --- Errors ----------------------------------------------------
+
+#[test]
+fn test_unreachable_llm_is_reported_with_what_to_do() {
+  let cancel = vtmate::cancel::CancellationToken::new();
+  // nothing listens on the discard port
+  let result = vtmate::util::runtime().block_on(vtmate::llm::llama_server_stream_response_into(
+    &Vec::new(),
+    "http://127.0.0.1:9",
+    "llama3.2",
+    "ollama",
+    &cancel,
+    &mut |_| {},
+  ));
+
+  let error = result.expect_err("nothing answers");
+  assert_eq!(error.stage(), "llm");
+  assert!(matches!(
+    error,
+    vtmate::error::Error::Llm(vtmate::error::LlmError::Unreachable { .. })
+  ));
+  assert!(error.hint().unwrap().contains("ollama serve"));
+}
+
+#[test]
+fn test_oversized_wyoming_events_are_refused_before_reading_them() {
+  use std::io::{Cursor, ErrorKind};
+
+  let mut header =
+    Cursor::new(b"{\"type\":\"audio-chunk\",\"payload_length\":1000000000}\n".to_vec());
+  let error = vtmate::wyoming::read_event(&mut header)
+    .err()
+    .expect("payload is too large");
+  assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+  let mut truncated = Cursor::new(b"{\"type\":\"audio-chunk\",\"payload_length\":4}\nab".to_vec());
+  let error = vtmate::wyoming::read_event(&mut truncated)
+    .err()
+    .expect("payload is cut");
+  assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+
+  let mut event = Cursor::new(b"{\"type\":\"audio-chunk\",\"payload_length\":2}\nab".to_vec());
+  let event = vtmate::wyoming::read_event(&mut event).unwrap().unwrap();
+  assert_eq!(
+    (event.kind.as_str(), event.payload.as_slice()),
+    ("audio-chunk", &b"ab"[..])
+  );
+}
+
+#[test]
+fn test_wyoming_audio_formats_out_of_bounds_are_refused() {
+  use std::io::{Cursor, ErrorKind};
+
+  let format = |data: &str| {
+    let line = format!("{{\"type\":\"audio-start\",\"data\":{}}}\n", data);
+    let event = vtmate::wyoming::read_event(&mut Cursor::new(line.into_bytes()))
+      .unwrap()
+      .unwrap();
+    vtmate::wyoming::audio_format(&event)
+  };
+
+  assert_eq!(format("{}").unwrap(), (16000, 2, 1));
+  assert_eq!(
+    format("{\"rate\":48000,\"width\":2,\"channels\":2}").unwrap(),
+    (48000, 2, 2)
+  );
+  for data in [
+    "{\"rate\":0}",
+    "{\"rate\":4294983296}",
+    "{\"width\":4}",
+    "{\"channels\":0}",
+    "{\"channels\":65537}",
+  ] {
+    assert_eq!(
+      format(data).unwrap_err().kind(),
+      ErrorKind::InvalidData,
+      "{}",
+      data
+    );
+  }
+  // two minutes of 16 kHz mono i16
+  assert_eq!(
+    vtmate::wyoming::max_utterance_bytes((16000, 2, 1)),
+    120 * 16000 * 2
+  );
+}
+
+#[test]
+fn test_thread_restarted_after_a_panic_holding_a_lock_keeps_working() {
+  use std::sync::{Arc, Mutex, PoisonError};
+
+  let shared = Arc::new(Mutex::new(Vec::new()));
+  let runs = Arc::new(Mutex::new(0));
+  let handle = vtmate::supervisor::spawn("test", {
+    let shared = shared.clone();
+    move || {
+      let mut run = runs.lock().unwrap_or_else(PoisonError::into_inner);
+      *run += 1;
+      let mut values = shared.lock().unwrap_or_else(PoisonError::into_inner);
+      if *run == 1 {
+        panic!("bad chunk");
+      }
+      values.push(*run);
+      Ok(())
+    }
+  });
+
+  assert!(handle.join().unwrap().is_ok());
+  assert!(shared.is_poisoned());
+  assert_eq!(*shared.lock().unwrap_or_else(PoisonError::into_inner), [2]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_control_socket_does_not_replace_a_file() {
+  let path = std::env::temp_dir().join(format!("vtmate_test_socket_{}", std::process::id()));
+  std::fs::write(&path, "notes").unwrap();
+  let (tx_cmd, _rx_cmd) = crossbeam_channel::unbounded();
+  let (stop_play_tx, _stop_play_rx) = crossbeam_channel::unbounded();
+
+  let error = vtmate::control::control_thread(path.clone(), tx_cmd, stop_play_tx)
+    .expect_err("a file is in the way");
+  assert!(error.to_string().contains("not a socket"));
+  assert_eq!(std::fs::read_to_string(&path).unwrap(), "notes");
+  let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_websocket_accept_key_of_rfc_6455() {
+  assert_eq!(
+    vtmate::server::websocket_accept("dGhlIHNhbXBsZSBub25jZQ=="),
+    "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+  );
+}
+
+// a control server (--serve) on a free port of localhost, the address
+fn control_server(origins: &[&str]) -> String {
+  let addr = std::net::TcpListener::bind("127.0.0.1:0")
+    .unwrap()
+    .local_addr()
+    .unwrap()
+    .to_string();
+  let (tx_cmd, _rx_cmd) = crossbeam_channel::unbounded();
+  let origins = origins.iter().map(|o| o.to_string()).collect();
+  let server_addr = addr.clone();
+  std::thread::spawn(move || {
+    let _keep = _rx_cmd;
+    vtmate::server::server_thread(server_addr, origins, tx_cmd)
+  });
+  for _ in 0..100 {
+    if std::net::TcpStream::connect(&addr).is_ok() {
+      break;
+    }
+    std::thread::sleep(std::time::Duration::from_millis(10));
+  }
+  addr
+}
+
+// sends a raw request, returns the connection and the head of the response
+fn http_request(addr: &str, request: &str) -> (std::net::TcpStream, String) {
+  use std::io::{Read, Write};
+
+  let mut stream = std::net::TcpStream::connect(addr).unwrap();
+  stream
+    .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+    .unwrap();
+  stream.write_all(request.as_bytes()).unwrap();
+  let mut head = Vec::new();
+  let mut byte = [0u8; 1];
+  while !head.ends_with(b"\r\n\r\n") && matches!(stream.read(&mut byte), Ok(1)) {
+    head.push(byte[0]);
+  }
+  (stream, String::from_utf8(head).unwrap())
+}
+
+#[test]
+fn test_event_stream_refuses_foreign_origins_and_answers_a_close() {
+  use std::io::{Read, Write};
+
+  let addr = control_server(&["https://dash.example.com"]);
+  let upgrade = |origin: &str| {
+    format!(
+      "GET /events HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nOrigin: {}\r\n\r\n",
+      addr, origin
+    )
+  };
+
+  let (_, head) = http_request(&addr, &upgrade("https://evil.example.com"));
+  assert!(head.starts_with("HTTP/1.1 403"), "{}", head);
+  let (_, head) = http_request(&addr, &upgrade("http://localhost.evil.example.com"));
+  assert!(head.starts_with("HTTP/1.1 403"), "{}", head);
+  let (_, head) = http_request(&addr, &upgrade("https://dash.example.com"));
+  assert!(head.starts_with("HTTP/1.1 101"), "{}", head);
+
+  let (mut ws, head) = http_request(&addr, &upgrade("http://localhost:3000"));
+  assert!(head.starts_with("HTTP/1.1 101"), "{}", head);
+  // masked client frames: a ping, then a close with status 1000
+  let mask = [1u8, 2, 3, 4];
+  let masked = |opcode: u8, payload: &[u8]| {
+    let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+  };
+  ws.write_all(&masked(0x9, b"hi")).unwrap();
+  let mut pong = [0u8; 4];
+  ws.read_exact(&mut pong).unwrap();
+  assert_eq!(pong, [0x8A, 2, b'h', b'i']);
+  ws.write_all(&masked(0x8, &1000u16.to_be_bytes())).unwrap();
+  let mut close = Vec::new();
+  ws.read_to_end(&mut close).unwrap();
+  assert_eq!(close, [0x88, 2, 0x03, 0xE8]);
+}
+
+#[test]
+fn test_control_server_refuses_requests_a_web_page_could_forge() {
+  let addr = control_server(&[]);
+  let port = addr.rsplit(':').next().unwrap();
+  let ask = |host: &str, content_type: &str| {
+    let body = r#"{"text":"run backup"}"#;
+    format!(
+      "POST /ask HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+      host,
+      content_type,
+      body.len(),
+      body
+    )
+  };
+  let status = |request: &str| {
+    let (_, head) = http_request(&addr, request);
+    head.split_whitespace().nth(1).unwrap_or("").to_string()
+  };
+
+  // a page can post text/plain and forms without a preflight
+  assert_eq!(status(&ask(&addr, "text/plain")), "415");
+  assert_eq!(
+    status(&ask(&addr, "application/x-www-form-urlencoded")),
+    "415"
+  );
+  // a domain pointed at 127.0.0.1 (DNS rebinding), or another port
+  assert_eq!(
+    status(&ask(
+      &format!("evil.example.com:{}", port),
+      "application/json"
+    )),
+    "403"
+  );
+  assert_eq!(status(&ask("localhost:1", "application/json")), "403");
+  // the head is read within limits
+  let long_header = format!(
+    "GET /status HTTP/1.1\r\nX-Filler: {}\r\n\r\n",
+    "a".repeat(10_000)
+  );
+  assert_eq!(status(&long_header), "400");
+  let many_headers = format!(
+    "GET /status HTTP/1.1\r\n{}\r\n",
+    "X-Filler: a\r\n".repeat(100)
+  );
+  assert_eq!(status(&many_headers), "400");
+}
+
+// --- Assets ----------------------------------------------------
+
+#[test]
+fn test_voices_pack_is_pruned_to_the_languages_in_use() {
+  use std::io::{Read, Write};
+  use zip::write::SimpleFileOptions;
+
+  let pack = std::env::temp_dir().join(format!("vtmate_test_voices_{}.bin", std::process::id()));
+  let mut writer = zip::ZipWriter::new(std::fs::File::create(&pack).unwrap());
+  for voice in ["af_heart", "ef_dora", "jf_alpha"] {
+    writer
+      .start_file(format!("{}.npy", voice), SimpleFileOptions::default())
+      .unwrap();
+    writer.write_all(voice.as_bytes()).unwrap();
+  }
+  writer.finish().unwrap();
+
+  let kept =
+    vtmate::assets::prune_voices_pack(&pack, &["en".to_string(), "es".to_string()]).unwrap();
+
+  assert_eq!(kept, 2);
+  let mut archive = zip::ZipArchive::new(std::fs::File::open(&pack).unwrap()).unwrap();
+  let mut voices = Vec::new();
+  for i in 0..archive.len() {
+    let mut entry = archive.by_index(i).unwrap();
+    let mut content = String::new();
+    entry.read_to_string(&mut content).unwrap();
+    voices.push((entry.name().to_string(), content));
+  }
+  assert_eq!(
+    voices,
+    [
+      ("af_heart.npy".to_string(), "af_heart".to_string()),
+      ("ef_dora.npy".to_string(), "ef_dora".to_string()),
+    ]
+  );
+  let _ = std::fs::remove_file(&pack);
+}
+
+// --- Redaction -------------------------------------------------
+
+#[test]
+fn test_redaction_masks_phones_but_not_dates_years_or_amounts() {
+  use vtmate::redact::{init, redact};
+
+  init(true, &[]).unwrap();
+  let masked = |text: &str| redact(text).into_owned();
+
+  assert_eq!(masked("call me at +34 612 345 678"), "call me at [phone]");
+  assert_eq!(
+    masked("the office is (030) 1234 5678"),
+    "the office is [phone]"
+  );
+  assert_eq!(masked("or 555-123-4567 after six"), "or [phone] after six");
+  assert_eq!(masked("write to ana@example.com"), "write to [email]");
+
+  for kept in [
+    "it was signed on 2024-05-17",
+    "due 17.05.2024 at noon",
+    "he led it from 1999-2001",
+    "it costs 1.250.000 euros",
+  ] {
+    assert_eq!(masked(kept), kept);
+  }
+}
+
+// --- Shell commands --------------------------------------------
+
+#[test]
+fn test_typed_text_cancels_a_shell_command_waiting_for_its_yes() {
+  use vtmate::shell::{Intercept, cancel_pending, intercept};
+
+  let mut agent = agent_settings();
+  agent.shell_commands = "greet: echo hi".to_string();
+
+  assert!(matches!(
+    intercept("Run greet.", &agent),
+    Some(Intercept::Say(_))
+  ));
+  // what the conversation does with an /ask before the answer
+  cancel_pending();
+  assert!(intercept("yes", &agent).is_none());
+}